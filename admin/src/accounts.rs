@@ -0,0 +1,54 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! A module for reading and printing exported user accounts.
+
+use mdchat_common::account::AccountRecord;
+
+use serde_json::json;
+use serde_json::Value;
+
+use std::fs;
+
+/// Version of the format of exported user accounts. It is increased whenever the
+/// format changes incompatibly.
+const FORMAT_VERSION: u64 = 1;
+
+/// Prints given accounts to `stdout` as a single JSON document, which can be
+/// imported again using [`read`].
+pub fn print(accounts: &[AccountRecord]) {
+    let document = json!({
+        "version": FORMAT_VERSION,
+        "accounts": accounts,
+    });
+    println!("{}", serde_json::to_string_pretty(&document).unwrap());
+}
+
+/// Reads accounts from a JSON document in given file which was printed by
+/// [`print`].
+pub fn read(path: &str) -> Result<Vec<AccountRecord>, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|err| format!("Could not read `{}`: {}", path, err))?;
+    let mut document: Value = serde_json::from_str(&content)
+        .map_err(|err| format!("`{}` is not a valid JSON document: {}", path, err))?;
+    match document.get("version").and_then(Value::as_u64) {
+        Option::Some(FORMAT_VERSION) => {},
+        Option::Some(version) => return Result::Err(format!("Unsupported version {} of `{}`", version, path)),
+        Option::None => return Result::Err(format!("`{}` does not contain a version", path)),
+    }
+    serde_json::from_value(document["accounts"].take())
+        .map_err(|err| format!("`{}` does not contain valid accounts: {}", path, err))
+}
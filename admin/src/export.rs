@@ -0,0 +1,72 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module for printing exported message history.
+
+use mdchat_common::message::HistoryEntry;
+
+use serde_json::json;
+
+use std::str::FromStr;
+
+/// Format of exported message history.
+#[derive(Clone, Copy, Debug)]
+pub enum Format {
+    /// JSON Lines, that is one JSON object per message.
+    Jsonl,
+    /// Comma-separated values with a header.
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "jsonl" => Result::Ok(Self::Jsonl),
+            "csv" => Result::Ok(Self::Csv),
+            other => Result::Err(format!("`{}` is an unknown export format", other)),
+        }
+    }
+}
+
+/// Prints given history entries to `stdout` in given format.
+pub fn print(entries: &[HistoryEntry], format: Format) {
+    match format {
+        Format::Jsonl => entries.iter().for_each(|entry| println!("{}", json!({
+            "id": entry.id(),
+            "sender": entry.message().sender(),
+            "date_time": entry.message().date_time().to_rfc3339(),
+            "text": entry.message().text(),
+        }))),
+        Format::Csv => {
+            println!("id,sender,date_time,text");
+            entries.iter().for_each(|entry| println!("{},{},{},{}",
+                entry.id(),
+                csv_field(entry.message().sender()),
+                entry.message().date_time().to_rfc3339(),
+                csv_field(entry.message().text())
+            ));
+        }
+    }
+}
+
+/// Quotes given string as a CSV field.
+#[doc(hidden)]
+fn csv_field(string: &str) -> String {
+    format!("\"{}\"", string.replace('"', "\"\""))
+}
@@ -0,0 +1,275 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+mod accounts;
+mod export;
+mod util;
+
+use chrono::DateTime;
+use chrono::Utc;
+
+use crate::util::recv_command;
+use crate::util::send_command;
+
+use mdchat_common::command::c2s;
+use mdchat_common::command::s2c;
+use mdchat_common::hello;
+use mdchat_common::hello::ClientVersion;
+use mdchat_common::log::LogLevel;
+use mdchat_common::login::LoginRequest;
+
+use mdswp::MdswpStream;
+
+use std::env;
+use std::io::stdin;
+use std::io::stdout;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::process::exit;
+
+/// Prints given message to `stderr` and ends the program with exit code 1.
+macro_rules! fatal {
+    ($str:literal $(, $arg:expr)*) => {{
+        eprintln!($str $(, $arg)*);
+        exit(1)
+    }}
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 4 {
+        fatal!("Usage: {} <server-socket-address> <nickname> <command> [arguments...]", args[0]);
+    }
+    // Parse the socket address:
+    let socket: SocketAddr = match args[1].parse() {
+        Result::Ok(socket) => socket,
+        Result::Err(err) => fatal!("Invalid socket address `{}`: {}", args[1], err),
+    };
+    // Parse the command before connecting, so a typo does not require login:
+    let request = match parse_command(&args[3], &args[4..]) {
+        Result::Ok(request) => request,
+        Result::Err(err) => fatal!("{}", err),
+    };
+    // Ask for password:
+    print!("Password: ");
+    stdout().flush().unwrap();
+    let mut password = String::new();
+    stdin().read_line(&mut password).unwrap();
+    // Connect and log in:
+    let mut conn = match MdswpStream::connect(socket) {
+        Result::Ok(stream) => stream,
+        Result::Err(err) => fatal!("Could not connect: {}", err),
+    };
+    let client_version = ClientVersion::new(env!("CARGO_PKG_NAME").to_string(), env!("CARGO_PKG_VERSION").to_string());
+    let challenge = hello::new_challenge();
+    send_or_exit(&mut conn, c2s::Command::Hello { client_version, challenge: challenge.clone(), suppress_echo: false, locale: hello::locale_from_env(), encodings: Vec::new(), compressions: Vec::new() });
+    // Verify the identity of the server before sending the password:
+    match recv_command(&mut conn) {
+        Result::Ok(s2c::Command::Hello { identity, .. }) if !identity.verify(&challenge) =>
+            fatal!("Server failed to prove its identity"),
+        Result::Ok(s2c::Command::Hello { identity, .. }) => match env::var("MDCHAT_SERVER_FINGERPRINT") {
+            Result::Ok(expected) if expected != identity.fingerprint() =>
+                fatal!("Server identity has changed! Expected fingerprint {}, received {}", expected, identity.fingerprint()),
+            Result::Ok(_) => {},
+            Result::Err(_) => eprintln!("Server fingerprint is {}", identity.fingerprint()),
+        },
+        Result::Ok(s2c::Command::Error(description)) => fatal!("Could not connect: {}", description),
+        Result::Ok(other) => fatal!("Unexpected response from server: {:?}", other),
+        Result::Err(err) => fatal!("Cannot communicate with server: {}", err),
+    }
+    let login_request = LoginRequest::login(args[2].clone(), password.trim().to_string());
+    send_or_exit(&mut conn, c2s::Command::Login(login_request));
+    match recv_command(&mut conn) {
+        Result::Ok(s2c::Command::LoginSuccess) => {},
+        Result::Ok(s2c::Command::Error(description)) => fatal!("Could not log in: {}", description),
+        Result::Ok(other) => fatal!("Unexpected response from server: {:?}", other),
+        Result::Err(err) => fatal!("Cannot communicate with server: {}", err),
+    }
+    // Execute the command and print everything server responds with. Commands
+    // with a single response end the loop, `tail` and `mod-events` run until
+    // terminated:
+    send_or_exit(&mut conn, request.command);
+    loop {
+        match recv_command(&mut conn) {
+            Result::Ok(s2c::Command::Ping) => send_or_exit(&mut conn, c2s::Command::Pong),
+            Result::Ok(s2c::Command::LogRecord(record)) => println!("{}", record),
+            Result::Ok(s2c::Command::ModEvent(event)) => println!("{}", event),
+            Result::Ok(s2c::Command::ConnectionList(connections)) => {
+                connections.iter().for_each(|connection| println!("{}", connection));
+                return
+            },
+            Result::Ok(s2c::Command::FilterStats(stats)) => {
+                stats.iter().for_each(|hits| println!("{}", hits));
+                return
+            },
+            Result::Ok(s2c::Command::FilterDecision(decision)) => {
+                println!("{}", decision);
+                return
+            },
+            Result::Ok(s2c::Command::FilterRuleAdded { rule, persisted: true }) => {
+                println!("Added and saved rule `{}`", rule);
+                return
+            },
+            Result::Ok(s2c::Command::FilterRuleAdded { rule, persisted: false }) => {
+                println!("Added rule `{}` until the server restarts", rule);
+                return
+            },
+            Result::Ok(s2c::Command::LockdownState(true)) => {
+                println!("Lockdown is on");
+                return
+            },
+            Result::Ok(s2c::Command::LockdownState(false)) => {
+                println!("Lockdown is off");
+                return
+            },
+            Result::Ok(s2c::Command::HistoryExport(entries)) => {
+                export::print(&entries, request.export_format.unwrap());
+                return
+            },
+            Result::Ok(s2c::Command::UserExport(accounts)) => {
+                accounts::print(&accounts);
+                return
+            },
+            Result::Ok(s2c::Command::UserImportResult { imported, skipped }) => {
+                println!("Imported {} accounts", imported.len());
+                skipped.iter().for_each(|(nickname, reason)| println!("Skipped `{}`: {}", nickname, reason));
+                return
+            },
+            Result::Ok(s2c::Command::Warning(description)) => fatal!("WARNING: {}", description),
+            Result::Ok(s2c::Command::Error(description)) => fatal!("FATAL: {}", description),
+            Result::Ok(_) => {},
+            Result::Err(err) => fatal!("Cannot communicate with server: {}", err),
+        }
+    }
+}
+
+/// An administrative command given as program arguments.
+struct Request {
+    /// Command to be sent to the server
+    command: c2s::Command,
+    /// Format of the exported message history, if `export` command is used
+    export_format: Option<export::Format>,
+}
+
+impl From<c2s::Command> for Request {
+    fn from(command: c2s::Command) -> Self {
+        Self { command, export_format: Option::None }
+    }
+}
+
+/// Converts the administrative command given as program arguments into
+/// a [`Request`].
+fn parse_command(name: &str, args: &[String]) -> Result<Request, String> {
+    match name {
+        "add-rule" => parse_add_rule(args),
+        "connections" => Result::Ok(c2s::Command::ListConnections.into()),
+        "explain" => parse_explain(args),
+        "export" => parse_export(args),
+        "filter-stats" => Result::Ok(c2s::Command::GetFilterStats.into()),
+        "lockdown" => match args.first().map(String::as_str) {
+            Option::Some("on") => Result::Ok(c2s::Command::SetLockdown(true).into()),
+            Option::Some("off") => Result::Ok(c2s::Command::SetLockdown(false).into()),
+            _ => Result::Err("Usage: lockdown <on|off>".to_string()),
+        },
+        "mod-events" => Result::Ok(c2s::Command::SubscribeModEvents(true).into()),
+        "tail" => parse_log_level(args.first().map(String::as_str).unwrap_or("info"))
+            .map(|level| c2s::Command::Tail(Option::Some(level)).into()),
+        "users" => parse_users(args),
+        other => Result::Err(format!("`{}` is an unknown command", other)),
+    }
+}
+
+#[doc(hidden)]
+fn parse_add_rule(args: &[String]) -> Result<Request, String> {
+    const USAGE: &str = "Usage: add-rule [--persist] <ip|message|nickname> <rule>";
+    let (persist, args) = match args.first().map(String::as_str) {
+        Option::Some("--persist") => (true, &args[1..]),
+        _ => (false, args),
+    };
+    let filter = args.first().ok_or(USAGE.to_string())?.parse()?;
+    match &args[1..] {
+        [] => Result::Err(USAGE.to_string()),
+        rule => Result::Ok(c2s::Command::AddFilterRule { filter, rule: rule.join(" "), persist }.into()),
+    }
+}
+
+#[doc(hidden)]
+fn parse_explain(args: &[String]) -> Result<Request, String> {
+    const USAGE: &str = "Usage: explain <ip|message|nickname> <input>";
+    let filter = args.first().ok_or(USAGE.to_string())?.parse()?;
+    match &args[1..] {
+        [] => Result::Err(USAGE.to_string()),
+        input => Result::Ok(c2s::Command::Explain { filter, input: input.join(" ") }.into()),
+    }
+}
+
+#[doc(hidden)]
+fn parse_export(args: &[String]) -> Result<Request, String> {
+    let format = args.first()
+        .ok_or("Usage: export <jsonl|csv> [from] [to]".to_string())?
+        .parse()?;
+    let from = parse_date_time(args.get(1))?;
+    let to = parse_date_time(args.get(2))?;
+    Result::Ok(Request {
+        command: c2s::Command::ExportHistory { from, to },
+        export_format: Option::Some(format),
+    })
+}
+
+#[doc(hidden)]
+fn parse_users(args: &[String]) -> Result<Request, String> {
+    const USAGE: &str = "Usage: users export | users import [--overwrite] <file>";
+    match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["export"] => Result::Ok(c2s::Command::ExportUsers.into()),
+        ["import", path] => accounts::read(path)
+            .map(|accounts| c2s::Command::ImportUsers { accounts, overwrite: false }.into()),
+        ["import", "--overwrite", path] => accounts::read(path)
+            .map(|accounts| c2s::Command::ImportUsers { accounts, overwrite: true }.into()),
+        _ => Result::Err(USAGE.to_string()),
+    }
+}
+
+/// Parses an optional RFC 3339 date and time. Missing value or `-` means that
+/// the time range is unbounded.
+#[doc(hidden)]
+fn parse_date_time(arg: Option<&String>) -> Result<Option<DateTime<Utc>>, String> {
+    match arg.map(String::as_str) {
+        Option::None | Option::Some("-") => Result::Ok(Option::None),
+        Option::Some(string) => DateTime::parse_from_rfc3339(string)
+            .map(|date_time| Option::Some(date_time.with_timezone(&Utc)))
+            .map_err(|err| format!("`{}` is an invalid date and time: {}", string, err)),
+    }
+}
+
+#[doc(hidden)]
+fn parse_log_level(level: &str) -> Result<LogLevel, String> {
+    match level {
+        "debug" => Result::Ok(LogLevel::Debug),
+        "info" => Result::Ok(LogLevel::Info),
+        "warning" => Result::Ok(LogLevel::Warning),
+        "error" => Result::Ok(LogLevel::Error),
+        "fatal" => Result::Ok(LogLevel::Fatal),
+        other => Result::Err(format!("`{}` is an invalid log level", other)),
+    }
+}
+
+#[doc(hidden)]
+fn send_or_exit(conn: &mut MdswpStream, command: c2s::Command) {
+    if let Result::Err(err) = send_command(conn, command) {
+        fatal!("Cannot communicate with server: {}", err);
+    }
+}
@@ -0,0 +1,72 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use mdchat_common::command::c2s;
+use mdchat_common::command::s2c;
+use mdchat_common::envelope;
+use mdchat_common::Error;
+
+use mdswp::MdswpStream;
+
+use std::io::Read;
+use std::io::Write;
+
+/// Encrypts and sends a [`c2s::Command`] using given [`MdswpStream`].
+pub fn send_command(conn: &mut MdswpStream, command: c2s::Command) -> Result<(), Error> {
+    // Convert to JSON and encrypt:
+    let json = envelope::encode(&command)?;
+    let bytes = json.into_bytes();
+    let encrypted = encrypt(bytes);
+    // Check length:
+    let len = encrypted.len();
+    if len > u32::MAX as usize {
+        return Result::Err(Error::Framing("Maximum size of a single command exceeded".to_string()))
+    }
+    // Send command:
+    let len = len as u32;
+    conn.write_all(&len.to_be_bytes())?;
+    conn.write_all(&encrypted)?;
+    conn.flush()?;
+    Result::Ok(())
+}
+
+/// Method for receiving single command from the server.
+pub fn recv_command(conn: &mut MdswpStream) -> Result<s2c::Command, Error> {
+    // Load length
+    let mut len = [0; 4];
+    conn.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len) as usize;
+    // Load encrypted content:
+    let mut buf = vec![0; len];
+    conn.read_exact(&mut buf)?;
+    // Decrypt and decode:
+    let decrypted = decrypt(buf);
+    let string = String::from_utf8(decrypted).map_err(Error::serialization)?;
+    let command = envelope::decode(&string)?;
+    // Return Ok if successful:
+    Result::Ok(command)
+}
+
+#[doc(hidden)]
+fn encrypt(data: Vec<u8>) -> Vec<u8> {
+    data
+}
+
+#[doc(hidden)]
+fn decrypt(data: Vec<u8>) -> Vec<u8> {
+    data
+}
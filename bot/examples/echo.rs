@@ -0,0 +1,46 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! A bot repeating text given to its `!echo` command and answering `!ping`.
+//! `!help` is answered by the library. Run it using:
+//!
+//! ```sh
+//! cargo run --example echo -- 127.0.0.1:4000 echobot password
+//! ```
+
+use mdchat_bot::Bot;
+
+use std::env;
+use std::process::exit;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 4 {
+        println!("Usage: {} <address> <nickname> <password>", args[0]);
+        exit(1);
+    }
+    let result = Bot::new(&args[1], &args[2], &args[3])
+        .command("echo", "repeats given text", |context, args| match args.is_empty() {
+            true => context.reply("Usage: !echo <text>"),
+            false => context.reply(args),
+        })
+        .command("ping", "checks whether the bot is running", |context, _| context.reply("pong"))
+        .run();
+    if let Result::Err(err) = result {
+        println!("{}", err);
+        exit(1);
+    }
+}
@@ -0,0 +1,129 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! A module for data given to handlers of received messages.
+
+use crate::outbox::Outbox;
+
+use mdchat_common::command::c2s;
+use mdchat_common::message::DirectMessage;
+use mdchat_common::message::Message;
+use mdchat_common::message::MessageKind;
+
+/// A message received by the bot.
+#[derive(Clone, Debug)]
+pub enum Incoming {
+    /// A message sent into the chat or into a channel.
+    Message(Message),
+    /// A direct message sent to the bot.
+    Direct(DirectMessage),
+}
+
+impl Incoming {
+    /// Returns the nickname of the user who sent the message.
+    pub fn sender(&self) -> &str {
+        match self {
+            Self::Message(message) => message.sender(),
+            Self::Direct(message) => message.sender(),
+        }
+    }
+
+    /// Returns the text of the message.
+    pub fn text(&self) -> &str {
+        match self {
+            Self::Message(message) => message.text(),
+            Self::Direct(message) => message.text(),
+        }
+    }
+
+    /// Returns the channel the message was sent into, or [`Option::None`] if it
+    /// was sent into the main chat or it is a direct message.
+    pub fn channel(&self) -> Option<&str> {
+        match self {
+            Self::Message(message) => message.channel().map(String::as_str),
+            Self::Direct(_) => Option::None,
+        }
+    }
+}
+
+/// Data given to a handler of a received message. Replies and other commands
+/// are queued and sent after the handler returns, as fast as the rate limit of
+/// the bot allows.
+pub struct Context<'a> {
+    nickname: &'a str,
+    incoming: &'a Incoming,
+    outbox: &'a mut Outbox,
+    stop: &'a mut bool,
+}
+
+impl<'a> Context<'a> {
+    pub(crate) fn new(nickname: &'a str, incoming: &'a Incoming, outbox: &'a mut Outbox, stop: &'a mut bool) -> Self {
+        Self { nickname, incoming, outbox, stop }
+    }
+
+    /// Returns the nickname the bot is logged in with.
+    pub fn nickname(&self) -> &'a str {
+        self.nickname
+    }
+
+    /// Returns the received message.
+    pub fn incoming(&self) -> &'a Incoming {
+        self.incoming
+    }
+
+    /// Replies to the received message. A reply to a direct message is sent as
+    /// a direct message to its sender, a reply to any other message is sent into
+    /// the same channel or the main chat.
+    pub fn reply(&mut self, text: &str) {
+        match self.incoming {
+            Incoming::Message(message) => self.send(message.channel().cloned(), text),
+            Incoming::Direct(message) => self.send_direct(message.sender(), text),
+        }
+    }
+
+    /// Sends a message into given channel, or into the main chat if
+    /// [`Option::None`] is given.
+    pub fn send(&mut self, channel: Option<String>, text: &str) {
+        self.outbox.push(c2s::Command::SendMessage {
+            channel,
+            kind: MessageKind::User,
+            text: text.to_string(),
+            ttl: Option::None,
+            client_tag: Option::None,
+        });
+    }
+
+    /// Sends a direct message to given user.
+    pub fn send_direct(&mut self, recipient: &str, text: &str) {
+        self.outbox.push(c2s::Command::SendDirectMessage {
+            recipient: recipient.to_string(),
+            text: text.to_string(),
+        });
+    }
+
+    /// Sends any command, for example a moderation command. Commands which send
+    /// messages are subject to the rate limit of the bot.
+    pub fn command(&mut self, command: c2s::Command) {
+        self.outbox.push(command);
+    }
+
+    /// Stops the bot after queued commands are sent, so [`Bot::run`] returns.
+    ///
+    /// [`Bot::run`]: crate::Bot::run
+    pub fn quit(&mut self) {
+        *self.stop = true;
+    }
+}
@@ -0,0 +1,381 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! A library for writing bots for MDChat on top of the client library. A bot
+//! logs into a server, calls registered handlers for commands such as `!echo`
+//! found in received messages and reconnects when the connection is lost.
+//! Replies are sent at most as fast as the [`RateLimit`] of the bot allows and
+//! received messages are acknowledged, so they are not handled twice.
+//!
+//! ```no_run
+//! use mdchat_bot::Bot;
+//!
+//! Bot::new("127.0.0.1:4000", "echobot", "password")
+//!     .command("echo", "repeats given text", |context, args| context.reply(args))
+//!     .run()
+//!     .unwrap();
+//! ```
+//!
+//! The bot answers `!help` with the list of registered commands, unless a `help`
+//! command is registered or no command is registered at all.
+
+pub mod context;
+mod outbox;
+
+pub use crate::context::Context;
+pub use crate::context::Incoming;
+pub use crate::outbox::RateLimit;
+
+use crate::outbox::Outbox;
+
+use chrono::Utc;
+
+use mdchat_client::recv_command;
+use mdchat_client::send_command;
+
+use mdchat_common::command::c2s;
+use mdchat_common::command::s2c;
+use mdchat_common::hello;
+use mdchat_common::hello::ClientVersion;
+use mdchat_common::login::LoginRequest;
+use mdchat_common::Error;
+
+use mdswp::MdswpStream;
+
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Delay before the first attempt to reconnect. Each failed attempt doubles the
+/// delay up to [`MAX_RECONNECT_DELAY`].
+const MIN_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Maximum delay between attempts to reconnect.
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+/// How often commands queued by other threads are checked, see
+/// [`Bot::command_sender`].
+const SENDER_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A handler of a command, which is given the arguments of the command.
+type CommandHandler = Box<dyn FnMut(&mut Context, &str)>;
+
+/// A handler of received messages, which are not commands.
+type MessageHandler = Box<dyn FnMut(&mut Context)>;
+
+/// A bot, see [crate documentation](crate).
+pub struct Bot {
+    address: String,
+    nickname: String,
+    password: String,
+    expected_fingerprint: Option<String>,
+    prefix: String,
+    rate_limit: RateLimit,
+    commands: BTreeMap<String, (String, CommandHandler)>,
+    on_message: Option<MessageHandler>,
+    command_sender: Sender<c2s::Command>,
+    queued_commands: Receiver<c2s::Command>,
+}
+
+/// Reason why a connection to the server has ended.
+#[doc(hidden)]
+enum SessionEnd {
+    /// A handler has stopped the bot.
+    Stopped,
+    /// The connection has been lost. `logged_in` tells whether the bot managed
+    /// to log in before, so reconnecting does not need to be delayed.
+    Lost { reason: String, logged_in: bool },
+}
+
+impl Bot {
+    /// Creates a new [`Bot`] with commands prefixed by `!` and with the default
+    /// [`RateLimit`].
+    ///
+    /// # Parameters
+    ///
+    ///  -  `address`: socket address of the server, for example `127.0.0.1:4000`
+    ///  -  `nickname`: nickname of the account of the bot
+    ///  -  `password`: password of the account of the bot
+    pub fn new(address: &str, nickname: &str, password: &str) -> Self {
+        let (command_sender, queued_commands) = mpsc::channel();
+        Self {
+            address: address.to_string(),
+            nickname: nickname.to_string(),
+            password: password.to_string(),
+            expected_fingerprint: Option::None,
+            prefix: "!".to_string(),
+            rate_limit: RateLimit::default(),
+            commands: BTreeMap::new(),
+            on_message: Option::None,
+            command_sender,
+            queued_commands,
+        }
+    }
+
+    /// Sets the prefix of commands.
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// Makes the bot refuse to log in unless the identity key of the server has
+    /// given fingerprint. By default, any identity is accepted.
+    pub fn with_fingerprint(mut self, fingerprint: &str) -> Self {
+        self.expected_fingerprint = Option::Some(fingerprint.to_lowercase());
+        self
+    }
+
+    /// Sets how fast the bot sends messages. It should not be faster than rate
+    /// limits of the server, otherwise some messages are rejected.
+    pub fn with_rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = rate_limit;
+        self
+    }
+
+    /// Registers a handler of given command. The handler is called when
+    /// a received message starts with the prefix followed by the name of the
+    /// command and is given the rest of the message as arguments.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `name`: name of the command without the prefix
+    ///  -  `description`: description of the command shown by `help`
+    ///  -  `handler`: the handler
+    pub fn command<F>(mut self, name: &str, description: &str, handler: F) -> Self
+    where
+        F: FnMut(&mut Context, &str) + 'static
+    {
+        self.commands.insert(name.to_string(), (description.to_string(), Box::new(handler)));
+        self
+    }
+
+    /// Registers a handler of received messages, which are not commands, for
+    /// example for moderation bots.
+    pub fn on_message<F>(mut self, handler: F) -> Self
+    where
+        F: FnMut(&mut Context) + 'static
+    {
+        self.on_message = Option::Some(Box::new(handler));
+        self
+    }
+
+    /// Returns a sender, through which other threads can queue commands to be
+    /// sent by the bot, for example messages relayed from another chat. Commands
+    /// are sent while the bot is connected, messages are subject to the rate
+    /// limit of the bot.
+    pub fn command_sender(&self) -> Sender<c2s::Command> {
+        self.command_sender.clone()
+    }
+
+    /// Connects to the server and handles received messages. When the connection
+    /// is lost, the bot connects again after a delay.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] when a handler stops the bot using [`Context::quit`]
+    ///  -  [`Result::Err`] with the description of the error, if the server
+    ///     rejected logging in, its identity could not be verified or it sent
+    ///     a command the bot does not understand
+    pub fn run(mut self) -> Result<(), String> {
+        let mut outbox = Outbox::new(self.rate_limit);
+        let mut delay = MIN_RECONNECT_DELAY;
+        loop {
+            match self.session(&mut outbox)? {
+                SessionEnd::Stopped => return Result::Ok(()),
+                SessionEnd::Lost { reason, logged_in } => {
+                    if logged_in {
+                        delay = MIN_RECONNECT_DELAY;
+                    }
+                    eprintln!("Connection to {} lost: {}, reconnecting in {} seconds", self.address, reason,
+                        delay.as_secs());
+                    thread::sleep(delay);
+                    delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+                }
+            }
+        }
+    }
+
+    /// Connects to the server, verifies its identity and handles received
+    /// commands until the connection ends.
+    #[doc(hidden)]
+    fn session(&mut self, outbox: &mut Outbox) -> Result<SessionEnd, String> {
+        let lost = |reason: String| Result::Ok(SessionEnd::Lost { reason, logged_in: false });
+        let mut stream = match MdswpStream::connect(self.address.as_str()) {
+            Result::Ok(stream) => stream,
+            Result::Err(err) => return lost(err.to_string()),
+        };
+        let client_version = ClientVersion::new(env!("CARGO_PKG_NAME").to_string(), env!("CARGO_PKG_VERSION").to_string());
+        let challenge = hello::new_challenge();
+        // Messages of the bot itself are ignored, so they do not need to be
+        // received:
+        let hello = c2s::Command::Hello { client_version, challenge: challenge.clone(), suppress_echo: true, locale: Option::None, encodings: Vec::new(), compressions: Vec::new() };
+        if let Result::Err(err) = send_command(&mut stream, hello) {
+            return lost(err.to_string())
+        }
+        match recv_command(&mut stream) {
+            Result::Ok(s2c::Command::Hello { identity, .. }) if !identity.verify(&challenge) =>
+                return Result::Err("Server failed to prove its identity".to_string()),
+            Result::Ok(s2c::Command::Hello { identity, .. }) => match &self.expected_fingerprint {
+                Option::Some(expected) if *expected != identity.fingerprint() => return Result::Err(format!(
+                    "Server identity does not match! Expected fingerprint {}, received {}", expected, identity.fingerprint())),
+                _ => {}
+            },
+            // The server may be refusing connections only for a while:
+            Result::Ok(s2c::Command::Error(description)) => return lost(description),
+            Result::Ok(_) => return Result::Err("Server did not prove its identity".to_string()),
+            Result::Err(err) => return lost(err.to_string()),
+        }
+        let login_request = LoginRequest::login(self.nickname.clone(), self.password.clone());
+        if let Result::Err(err) = send_command(&mut stream, c2s::Command::Login(login_request)) {
+            return lost(err.to_string())
+        }
+        let events = match spawn_receiver(&stream) {
+            Result::Ok(events) => events,
+            Result::Err(err) => return lost(err.to_string()),
+        };
+        outbox.reconnected();
+        let result = self.serve(&mut stream, &events, outbox);
+        // Make the receiving thread stop:
+        let _ = stream.finish_write();
+        let _ = stream.reset();
+        result
+    }
+
+    /// Handles received commands and sends queued commands until the connection
+    /// ends.
+    #[doc(hidden)]
+    fn serve(&mut self, stream: &mut MdswpStream, events: &Receiver<Result<s2c::Command, Error>>, outbox: &mut Outbox)
+        -> Result<SessionEnd, String>
+    {
+        let mut logged_in = false;
+        let mut last_msg_id = 0;
+        let mut stop = false;
+        let mut wait = Option::None;
+        loop {
+            let wait_for = wait.map_or(SENDER_POLL_INTERVAL, |wait: Duration| wait.min(SENDER_POLL_INTERVAL));
+            let received = events.recv_timeout(wait_for);
+            match received {
+                Result::Ok(Result::Ok(s2c::Command::LoginSuccess)) => logged_in = true,
+                // Pings are answered at once, not after queued messages:
+                Result::Ok(Result::Ok(s2c::Command::Ping)) => if let Result::Err(err) = send_command(stream, c2s::Command::Pong) {
+                    return Result::Ok(SessionEnd::Lost { reason: err.to_string(), logged_in })
+                },
+                // Errors received before logging in, such as a wrong password, do
+                // not go away by reconnecting:
+                Result::Ok(Result::Ok(s2c::Command::Error(description))) if !logged_in =>
+                    return Result::Err(description),
+                Result::Ok(Result::Ok(s2c::Command::Error(reason))) =>
+                    return Result::Ok(SessionEnd::Lost { reason, logged_in }),
+                // Messages are received in the order of their IDs, so a message
+                // with an older ID has been already handled:
+                Result::Ok(Result::Ok(s2c::Command::MessageRecv { id, .. })) if id <= last_msg_id => {},
+                Result::Ok(Result::Ok(s2c::Command::MessageRecv { id, message, .. })) => {
+                    last_msg_id = id;
+                    self.handle(Incoming::Message(message), outbox, &mut stop);
+                    outbox.push(c2s::Command::AckUpTo(id));
+                },
+                Result::Ok(Result::Ok(s2c::Command::DirectMessageRecv(message))) =>
+                    self.handle(Incoming::Direct(message), outbox, &mut stop),
+                Result::Ok(Result::Ok(s2c::Command::QuotaExceeded { resets_at, .. })) => {
+                    let resets_in = resets_at.map(|resets_at| (resets_at - Utc::now()).to_std().unwrap_or_default());
+                    outbox.pause(resets_in.map(|resets_in| Instant::now() + resets_in));
+                },
+                Result::Ok(Result::Ok(_)) => {},
+                // A server which sends commands the bot does not understand is
+                // not going to send different ones after reconnecting:
+                Result::Ok(Result::Err(err)) if err.is_fatal() => return Result::Err(err.to_string()),
+                Result::Ok(Result::Err(err)) => return Result::Ok(SessionEnd::Lost { reason: err.to_string(), logged_in }),
+                Result::Err(RecvTimeoutError::Timeout) => {},
+                Result::Err(RecvTimeoutError::Disconnected) =>
+                    return Result::Ok(SessionEnd::Lost { reason: "connection closed".to_string(), logged_in }),
+            }
+            self.queued_commands.try_iter().for_each(|command| outbox.push(command));
+            wait = match outbox.flush(stream) {
+                Result::Ok(wait) => wait,
+                Result::Err(err) => return Result::Ok(SessionEnd::Lost { reason: err.to_string(), logged_in }),
+            };
+            // Queued commands are sent before stopping, unless messages cannot
+            // be sent until reconnecting:
+            if stop && (outbox.is_empty() || wait.is_none()) {
+                return Result::Ok(SessionEnd::Stopped)
+            }
+        }
+    }
+
+    /// Calls the handler of the command contained in a received message, or the
+    /// handler of other messages.
+    #[doc(hidden)]
+    fn handle(&mut self, incoming: Incoming, outbox: &mut Outbox, stop: &mut bool) {
+        // Messages sent by the bot from other connections are received too:
+        if incoming.sender() == self.nickname {
+            return
+        }
+        let mut context = Context::new(&self.nickname, &incoming, outbox, stop);
+        if let Option::Some(command) = incoming.text().trim().strip_prefix(self.prefix.as_str()) {
+            let (name, args) = match command.split_once(char::is_whitespace) {
+                Option::Some((name, args)) => (name, args.trim()),
+                Option::None => (command, ""),
+            };
+            if let Option::Some((_, handler)) = self.commands.get_mut(name) {
+                handler(&mut context, args);
+                return
+            }
+            if name == "help" && !self.commands.is_empty() {
+                context.reply(&self.help());
+                return
+            }
+        }
+        if let Option::Some(handler) = &mut self.on_message {
+            handler(&mut context);
+        }
+    }
+
+    /// Returns the reply to the `help` command, which lists registered commands.
+    #[doc(hidden)]
+    fn help(&self) -> String {
+        let mut commands: Vec<String> = self.commands.iter()
+            .map(|(name, (description, _))| format!("{}{}: {}", self.prefix, name, description))
+            .collect();
+        if !self.commands.contains_key("help") {
+            commands.push(format!("{}help: lists available commands", self.prefix));
+        }
+        format!("Available commands: {}", commands.join(", "))
+    }
+}
+
+/// Starts a thread receiving commands from given stream. The channel is closed
+/// after an error is received.
+#[doc(hidden)]
+fn spawn_receiver(stream: &MdswpStream) -> io::Result<Receiver<Result<s2c::Command, Error>>> {
+    let mut reader = stream.try_clone()?;
+    let (sender, events) = mpsc::channel();
+    thread::Builder::new()
+        .name("mdchat bot receiver".to_string())
+        .spawn(move || loop {
+            let command = recv_command(&mut reader);
+            let failed = command.is_err();
+            if sender.send(command).is_err() || failed {
+                break;
+            }
+        })?;
+    Result::Ok(events)
+}
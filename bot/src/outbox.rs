@@ -0,0 +1,172 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Queue of commands waiting to be sent. Messages are sent at most as fast as
+//! the rate limit of the bot allows, so the bot is not rejected by rate limits
+//! of the server.
+
+use mdchat_client::send_command;
+
+use mdchat_common::command::c2s;
+use mdchat_common::Error;
+
+use mdswp::MdswpStream;
+
+use std::collections::VecDeque;
+use std::num::NonZeroU32;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Rate limit of sent messages: a bucket holding at most `burst` tokens, which
+/// is refilled by `count` tokens per `period`. Each message takes a token.
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimit {
+    count: NonZeroU32,
+    period: Duration,
+    burst: NonZeroU32,
+}
+
+impl RateLimit {
+    /// Creates a new [`RateLimit`].
+    ///
+    /// # Parameters
+    ///
+    ///  -  `count`: number of messages which can be sent per `period`
+    ///  -  `period`: the period
+    ///  -  `burst`: number of messages which can be sent at once
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] with the rate limit
+    ///  -  [`Result::Err`] if the period is zero
+    pub fn new(count: NonZeroU32, period: Duration, burst: NonZeroU32) -> Result<Self, String> {
+        if period.is_zero() {
+            return Result::Err("Period of a rate limit must not be zero".to_string())
+        }
+        Result::Ok(Self { count, period, burst })
+    }
+
+    /// Returns the time needed to refill a single token.
+    #[doc(hidden)]
+    fn token_period(&self) -> Duration {
+        self.period / self.count.get()
+    }
+}
+
+/// One message per second with bursts of three messages by default.
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            count: NonZeroU32::new(1).unwrap(),
+            period: Duration::from_secs(1),
+            burst: NonZeroU32::new(3).unwrap(),
+        }
+    }
+}
+
+/// Commands waiting to be sent, see [module documentation](self).
+pub(crate) struct Outbox {
+    rate_limit: RateLimit,
+    queue: VecDeque<c2s::Command>,
+    tokens: f64,
+    refilled_at: Instant,
+    /// Messages are not sent until this time, because a message quota has been
+    /// used up. [`Option::None`] inside means until the bot connects again.
+    paused_until: Option<Option<Instant>>,
+}
+
+impl Outbox {
+    pub(crate) fn new(rate_limit: RateLimit) -> Self {
+        Self {
+            rate_limit,
+            queue: VecDeque::new(),
+            tokens: rate_limit.burst.get() as f64,
+            refilled_at: Instant::now(),
+            paused_until: Option::None,
+        }
+    }
+
+    /// Adds a command to the end of the queue.
+    pub(crate) fn push(&mut self, command: c2s::Command) {
+        self.queue.push_back(command);
+    }
+
+    /// Returns whether there is no command waiting to be sent.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Stops sending messages until given time, or until the bot connects again
+    /// if [`Option::None`] is given.
+    pub(crate) fn pause(&mut self, until: Option<Instant>) {
+        self.paused_until = Option::Some(until);
+    }
+
+    /// Starts sending messages again, if they were paused until the bot connects
+    /// again.
+    pub(crate) fn reconnected(&mut self) {
+        if self.paused_until == Option::Some(Option::None) {
+            self.paused_until = Option::None;
+        }
+    }
+
+    /// Sends queued commands in their order until a message has to wait for the
+    /// rate limit or the queue is empty.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] with time to wait before calling this function again,
+    ///     or [`Option::None`] if the queue is empty or paused indefinitely
+    ///  -  [`Result::Err`] if sending failed; the command which could not be
+    ///     sent stays in the queue
+    pub(crate) fn flush(&mut self, stream: &mut MdswpStream) -> Result<Option<Duration>, Error> {
+        self.refill();
+        while let Option::Some(command) = self.queue.front() {
+            if is_message(command) {
+                match self.paused_until {
+                    Option::Some(Option::Some(until)) if until > Instant::now() =>
+                        return Result::Ok(Option::Some(until - Instant::now())),
+                    Option::Some(Option::Some(_)) => self.paused_until = Option::None,
+                    Option::Some(Option::None) => return Result::Ok(Option::None),
+                    Option::None => {}
+                }
+                if self.tokens < 1.0 {
+                    let missing = self.rate_limit.token_period().mul_f64(1.0 - self.tokens);
+                    return Result::Ok(Option::Some(missing))
+                }
+                self.tokens -= 1.0;
+            }
+            send_command(stream, command.clone())?;
+            self.queue.pop_front();
+        }
+        Result::Ok(Option::None)
+    }
+
+    #[doc(hidden)]
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled_at);
+        let added = elapsed.as_secs_f64() / self.rate_limit.token_period().as_secs_f64();
+        self.tokens = (self.tokens + added).min(self.rate_limit.burst.get() as f64);
+        self.refilled_at = now;
+    }
+}
+
+/// Returns whether given command sends a message and is therefore limited.
+#[doc(hidden)]
+fn is_message(command: &c2s::Command) -> bool {
+    matches!(command, c2s::Command::SendMessage { .. } | c2s::Command::SendDirectMessage { .. })
+}
@@ -0,0 +1,201 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Configuration of the bridge, which is read from a file given on the command
+//! line. Each line of the file contains an option followed by its value, lines
+//! starting with a hash (`#`) are comments.
+
+use std::env;
+use std::fs;
+use std::net::SocketAddr;
+use std::str::FromStr;
+
+/// Environment variable, which can contain the password instead of the
+/// configuration file.
+const ENV_PASSWORD: &str = "MDCHAT_BRIDGE_PASSWORD";
+
+/// Kinds of webhooks messages can be forwarded to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WebhookKind {
+    /// A Discord webhook.
+    Discord,
+    /// A Slack incoming webhook.
+    Slack,
+}
+
+impl FromStr for WebhookKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "discord" => Result::Ok(Self::Discord),
+            "slack" => Result::Ok(Self::Slack),
+            other => Result::Err(format!("`{}` is not a valid kind of webhook, `discord` or `slack` was expected", other)),
+        }
+    }
+}
+
+/// Configuration of the bridge.
+#[derive(Clone, Debug, Default)]
+pub struct BridgeConfig {
+    server: Option<String>,
+    nickname: Option<String>,
+    password: Option<String>,
+    fingerprint: Option<String>,
+    webhooks: Vec<(WebhookKind, String)>,
+    forward: Vec<Option<String>>,
+    relay_listen: Option<SocketAddr>,
+    relay_token: Option<String>,
+    relay_channel: Option<String>,
+}
+
+impl BridgeConfig {
+    /// Returns the socket address of the MDChat server.
+    pub fn server(&self) -> &str {
+        self.server.as_deref().unwrap_or_default()
+    }
+
+    /// Returns the nickname the bridge logs in with.
+    pub fn nickname(&self) -> &str {
+        self.nickname.as_deref().unwrap_or_default()
+    }
+
+    /// Returns the password the bridge logs in with.
+    pub fn password(&self) -> &str {
+        self.password.as_deref().unwrap_or_default()
+    }
+
+    /// Returns the fingerprint the identity key of the server must have, if
+    /// given.
+    pub fn fingerprint(&self) -> Option<&str> {
+        self.fingerprint.as_deref()
+    }
+
+    /// Returns webhooks messages are forwarded to.
+    pub fn webhooks(&self) -> &[(WebhookKind, String)] {
+        &self.webhooks
+    }
+
+    /// Returns whether messages sent into given channel, or into the main chat
+    /// if [`Option::None`] is given, are forwarded. By default, only messages
+    /// sent into the main chat are forwarded.
+    pub fn is_forwarded(&self, channel: Option<&String>) -> bool {
+        match self.forward.is_empty() {
+            true => channel.is_none(),
+            false => self.forward.iter().any(|forwarded| forwarded.as_ref() == channel),
+        }
+    }
+
+    /// Returns the socket address replies from other chats are accepted at, or
+    /// [`Option::None`] if replies are not relayed.
+    pub fn relay_listen(&self) -> Option<SocketAddr> {
+        self.relay_listen
+    }
+
+    /// Returns the token other chats must send together with replies.
+    pub fn relay_token(&self) -> &str {
+        self.relay_token.as_deref().unwrap_or_default()
+    }
+
+    /// Returns the channel relayed replies are sent into, or [`Option::None`]
+    /// for the main chat.
+    pub fn relay_channel(&self) -> Option<&String> {
+        self.relay_channel.as_ref()
+    }
+
+    /// Processes a single line of the configuration file.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if the line is valid
+    ///  -  [`Result::Err`] with the description of the error otherwise
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Result::Ok(())
+        }
+        let (option, value) = line.split_once(char::is_whitespace)
+            .map(|(option, value)| (option, value.trim()))
+            .ok_or(format!("A value was expected after `{}`", line))?;
+        match option {
+            "server" => self.server = Option::Some(value.to_string()),
+            "nickname" => self.nickname = Option::Some(value.to_string()),
+            "password" => self.password = Option::Some(value.to_string()),
+            "fingerprint" => self.fingerprint = Option::Some(value.to_lowercase()),
+            "webhook" => {
+                let (kind, url) = value.split_once(char::is_whitespace)
+                    .ok_or(format!("A URL was expected after `webhook {}`", value))?;
+                let url = url.trim();
+                if !url.starts_with("https://") && !url.starts_with("http://") {
+                    return Result::Err(format!("`{}` is not an HTTP URL", url))
+                }
+                self.webhooks.push((kind.parse()?, url.to_string()));
+            },
+            "forward" => self.forward.push(parse_channel(value)),
+            "relay-listen" => self.relay_listen = Option::Some(value.parse()
+                .map_err(|err| format!("`{}` is not a valid socket address: {}", value, err))?),
+            "relay-token" => self.relay_token = Option::Some(value.to_string()),
+            "relay-channel" => self.relay_channel = parse_channel(value),
+            other => return Result::Err(format!("Unknown option `{}`", other)),
+        }
+        Result::Ok(())
+    }
+
+    /// Checks that all required options are given.
+    #[doc(hidden)]
+    fn check(&self) -> Result<(), String> {
+        let missing = [
+            ("server", self.server.is_none()),
+            ("nickname", self.nickname.is_none()),
+            ("password", self.password.is_none()),
+            ("webhook", self.webhooks.is_empty()),
+            ("relay-token", self.relay_listen.is_some() && self.relay_token.is_none()),
+        ];
+        match missing.iter().find(|(_, missing)| *missing) {
+            Option::Some((option, _)) => Result::Err(format!("Option `{}` is required", option)),
+            Option::None => Result::Ok(()),
+        }
+    }
+}
+
+/// Reads the configuration file at given path. The password may be given by
+/// `MDCHAT_BRIDGE_PASSWORD` environment variable instead.
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] with the configuration
+///  -  [`Result::Err`] with the description of the first error
+pub fn load(path: &str) -> Result<BridgeConfig, String> {
+    let source = fs::read_to_string(path).map_err(|err| format!("Cannot read {}: {}", path, err))?;
+    let mut config = BridgeConfig::default();
+    for (line_num, line) in source.lines().enumerate() {
+        config.process_line(line).map_err(|err| format!("{}:{}: {}", path, line_num + 1, err))?;
+    }
+    if let Result::Ok(password) = env::var(ENV_PASSWORD) {
+        config.password = Option::Some(password);
+    }
+    config.check()?;
+    Result::Ok(config)
+}
+
+/// Parses a channel name, where `main` means the main chat.
+#[doc(hidden)]
+fn parse_channel(value: &str) -> Option<String> {
+    match value {
+        "main" => Option::None,
+        channel => Option::Some(channel.strip_prefix('#').unwrap_or(channel).to_string()),
+    }
+}
@@ -0,0 +1,213 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! A module implementing the small subset of HTTP/1.1 needed by the relay
+//! endpoint: a single request per connection, with the body given by
+//! `Content-Length` header. Requests are read within [`REQUEST_TIMEOUT`] and the
+//! number of connections handled at once is limited by [`ConnectionLimit`], so
+//! slow or numerous clients cannot exhaust the bridge.
+
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Write;
+use std::net::TcpStream;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Maximum time of reading a whole request, including its body, and of writing
+/// the response.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Maximum number of connections of one HTTP endpoint handled at once.
+pub const MAX_CONNECTIONS: usize = 64;
+
+/// Maximum length of the request line and of each header.
+const MAX_LINE_LENGTH: usize = 8 * 1024;
+
+/// Maximum length of the request line and all headers together.
+const MAX_HEAD_LENGTH: usize = 16 * 1024;
+
+/// Maximum number of headers of a request.
+const MAX_HEADERS: usize = 64;
+
+/// Limits the number of connections of an endpoint handled at once.
+pub struct ConnectionLimit {
+    active: Arc<AtomicUsize>,
+    max: usize,
+}
+
+impl ConnectionLimit {
+    /// Creates a new [`ConnectionLimit`] allowing at most `max` connections to be
+    /// handled at once, with no connection being handled.
+    pub fn new(max: usize) -> Self {
+        Self { active: Arc::new(AtomicUsize::new(0)), max }
+    }
+
+    /// Returns a [`ConnectionPermit`] allowing to handle a connection, or
+    /// [`Option::None`] if the maximum number of connections is being handled.
+    pub fn acquire(&self) -> Option<ConnectionPermit> {
+        self.active.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |active| match active < self.max {
+            true => Option::Some(active + 1),
+            false => Option::None,
+        }).ok()?;
+        Option::Some(ConnectionPermit { active: self.active.clone() })
+    }
+}
+
+/// Allows to handle a connection until it is dropped, see
+/// [`ConnectionLimit::acquire`].
+pub struct ConnectionPermit {
+    active: Arc<AtomicUsize>,
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        self.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Reads a request from a connection until its deadline. Unlike a read timeout of
+/// the connection, which limits each read, the deadline limits reading of the
+/// whole request, so a client cannot keep the connection open by sending the
+/// request byte by byte.
+pub struct RequestReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for RequestReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Result::Err(io::ErrorKind::TimedOut.into())
+        }
+        self.stream.set_read_timeout(Option::Some(remaining))?;
+        let mut stream = self.stream;
+        stream.read(buf)
+    }
+}
+
+/// Returns a reader of a request from given connection, which must be read within
+/// [`REQUEST_TIMEOUT`]. The response must be written within the same time.
+pub fn reader(stream: &TcpStream) -> BufReader<RequestReader<'_>> {
+    let _ = stream.set_write_timeout(Option::Some(REQUEST_TIMEOUT));
+    BufReader::new(RequestReader { stream, deadline: Instant::now() + REQUEST_TIMEOUT })
+}
+
+/// Represents an HTTP response with an error status.
+pub struct HttpError {
+    pub status: u16,
+    pub reason: &'static str,
+}
+
+impl HttpError {
+    /// Creates a new [`HttpError`] instance.
+    pub fn new(status: u16, reason: &'static str) -> Self {
+        Self { status, reason }
+    }
+}
+
+/// The request line and headers of a request.
+pub struct RequestHead {
+    pub method: String,
+    pub path: String,
+    headers: Vec<(String, String)>,
+}
+
+impl RequestHead {
+    /// Returns the value of given header, if present. `name` must be lowercase.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Reads the request line and headers of a request.
+pub fn read_head(reader: &mut BufReader<RequestReader>) -> Result<RequestHead, HttpError> {
+    let mut head_length = 0;
+    let request_line = read_line(reader, &mut head_length)?;
+    let mut request_line = request_line.split(' ');
+    let (method, path) = match (request_line.next(), request_line.next()) {
+        (Option::Some(method), Option::Some(path)) => (method.to_string(), path.to_string()),
+        _ => return Result::Err(HttpError::new(400, "Bad Request")),
+    };
+    let mut headers = Vec::new();
+    loop {
+        let line = read_line(reader, &mut head_length)?;
+        if line.is_empty() {
+            break;
+        }
+        if headers.len() == MAX_HEADERS {
+            return Result::Err(HttpError::new(431, "Request Header Fields Too Large"));
+        }
+        let (name, value) = line.split_once(':').ok_or(HttpError::new(400, "Bad Request"))?;
+        headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+    }
+    Result::Ok(RequestHead { method, path, headers })
+}
+
+/// Reads the body of a request whose head has been read. Bodies larger than
+/// `max_size` bytes are rejected.
+pub fn read_body(reader: &mut BufReader<RequestReader>, head: &RequestHead, max_size: usize) -> Result<Vec<u8>, HttpError> {
+    let content_length = head.header("content-length")
+        .ok_or(HttpError::new(411, "Length Required"))?
+        .parse::<usize>()
+        .map_err(|_| HttpError::new(400, "Bad Request"))?;
+    if content_length > max_size {
+        return Result::Err(HttpError::new(413, "Payload Too Large"));
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).map_err(|_| HttpError::new(400, "Bad Request"))?;
+    Result::Ok(body)
+}
+
+/// Writes a response and closes the connection.
+///
+/// # Parameters
+///
+///  -  `stream`: the connection to write to
+///  -  `status`: status code and reason phrase of the response
+///  -  `content_type`: media type of the body
+///  -  `body`: body of the response, may be empty
+pub fn respond(mut stream: &TcpStream, status: (u16, &str), content_type: &str, body: &[u8]) {
+    let _ = write!(stream, "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status.0, status.1, content_type, body.len());
+    let _ = stream.write_all(body);
+    let _ = stream.flush();
+}
+
+/// Reads a single line of the request without the line ending and adds its length
+/// to `head_length`. Lines longer than [`MAX_LINE_LENGTH`] and lines exceeding
+/// [`MAX_HEAD_LENGTH`] of the whole head are rejected.
+#[doc(hidden)]
+fn read_line(reader: &mut BufReader<RequestReader>, head_length: &mut usize) -> Result<String, HttpError> {
+    let mut line = String::new();
+    let max_length = MAX_LINE_LENGTH.min(MAX_HEAD_LENGTH - *head_length);
+    let result = reader.by_ref().take(max_length as u64).read_line(&mut line);
+    *head_length += line.len();
+    match result {
+        Result::Ok(_) if line.ends_with('\n') => Result::Ok(line.trim_end_matches(['\r', '\n']).to_string()),
+        Result::Ok(_) => Result::Err(HttpError::new(431, "Request Header Fields Too Large")),
+        Result::Err(err) if err.kind() == io::ErrorKind::InvalidData => Result::Err(HttpError::new(400, "Bad Request")),
+        Result::Err(_) => Result::Err(HttpError::new(408, "Request Timeout")),
+    }
+}
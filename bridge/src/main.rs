@@ -0,0 +1,85 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! A bridge, which logs into an MDChat server as a bot and forwards messages
+//! from selected channels to Discord and Slack webhooks. Optionally, it accepts
+//! replies from the other chats over HTTP and sends them into MDChat.
+
+mod config;
+mod http;
+mod relay;
+mod webhook;
+
+use crate::relay::Relay;
+
+use mdchat_bot::Bot;
+use mdchat_bot::Incoming;
+
+use mdchat_common::message::MessageKind;
+
+use std::env;
+use std::net::TcpListener;
+use std::process::exit;
+use std::thread;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 2 {
+        println!("Usage: {} <config-file>", args[0]);
+        exit(1);
+    }
+    let config = match config::load(&args[1]) {
+        Result::Ok(config) => config,
+        Result::Err(err) => {
+            println!("{}", err);
+            exit(1);
+        }
+    };
+    let webhooks = webhook::spawn(config.webhooks().to_vec());
+    let mut bot = Bot::new(config.server(), config.nickname(), config.password());
+    if let Option::Some(fingerprint) = config.fingerprint() {
+        bot = bot.with_fingerprint(fingerprint);
+    }
+    // Start accepting replies from other chats:
+    if let Option::Some(relay_listen) = config.relay_listen() {
+        let listener = match TcpListener::bind(relay_listen) {
+            Result::Ok(listener) => listener,
+            Result::Err(err) => {
+                println!("Could not bind to {}: {}", relay_listen, err);
+                exit(1);
+            }
+        };
+        let relay = Relay {
+            token: config.relay_token().to_string(),
+            channel: config.relay_channel().cloned(),
+            commands: bot.command_sender(),
+        };
+        thread::spawn(move || relay::listen(listener, relay));
+    }
+    // Forward messages written by users, notices of the server and messages of
+    // integrations are not forwarded:
+    let bot = bot.on_message(move |context| match context.incoming() {
+        Incoming::Message(message) if config.is_forwarded(message.channel())
+            && matches!(message.kind(), MessageKind::User | MessageKind::Action | MessageKind::Announcement) => {
+            let _ = webhooks.send(message.clone());
+        },
+        _ => {}
+    });
+    if let Result::Err(err) = bot.run() {
+        println!("{}", err);
+        exit(1);
+    }
+}
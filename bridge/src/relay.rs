@@ -0,0 +1,184 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! HTTP endpoint, through which other chats relay replies into MDChat. It
+//! accepts either a JSON object with `user` and `text` fields authenticated by
+//! `Authorization: Bearer <token>` header, or a form sent by a Slack outgoing
+//! webhook, which contains the token in its `token` field.
+
+use crate::http;
+use crate::http::ConnectionLimit;
+use crate::http::HttpError;
+
+use mdchat_common::command::c2s;
+use mdchat_common::message::MessageKind;
+
+use serde_json::Value;
+
+use subtle::ConstantTimeEq;
+
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread;
+
+/// Path of the endpoint.
+const PATH: &str = "/relay";
+
+/// Maximum size of a request body.
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+/// Where and how relayed replies are sent.
+pub struct Relay {
+    /// Token other chats must send.
+    pub token: String,
+    /// Channel replies are sent into, or [`Option::None`] for the main chat.
+    pub channel: Option<String>,
+    /// Sender of commands sent by the bot.
+    pub commands: Sender<c2s::Command>,
+}
+
+/// Accepts connections to the relay endpoint. This is a blocking function to be
+/// run in a separate thread.
+pub fn listen(listener: TcpListener, relay: Relay) {
+    let relay = Arc::new(relay);
+    let limit = ConnectionLimit::new(http::MAX_CONNECTIONS);
+    for stream in listener.incoming() {
+        match stream {
+            Result::Ok(stream) => match limit.acquire() {
+                Option::Some(permit) => {
+                    let relay = relay.clone();
+                    let _ = thread::Builder::new()
+                        .name("relay request".to_string())
+                        .spawn(move || {
+                            handle_conn(stream, &relay);
+                            drop(permit);
+                        });
+                },
+                Option::None => eprintln!("Too many connections to the relay endpoint, closing a new one"),
+            },
+            Result::Err(err) => eprintln!("A chat could not connect to the relay endpoint: {}", err),
+        }
+    }
+}
+
+#[doc(hidden)]
+fn handle_conn(stream: TcpStream, relay: &Relay) {
+    let status = match handle_request(&stream, relay) {
+        Result::Ok(()) => (200, "OK"),
+        Result::Err(err) => {
+            if let Result::Ok(peer_addr) = stream.peer_addr() {
+                eprintln!("Rejected relay request from {}: {} {}", peer_addr, err.status, err.reason);
+            }
+            (err.status, err.reason)
+        },
+    };
+    http::respond(&stream, status, "text/plain", &[]);
+}
+
+/// Reads a single request, checks the token and queues the reply. The token of a
+/// JSON request is checked before its body is read.
+#[doc(hidden)]
+fn handle_request(stream: &TcpStream, relay: &Relay) -> Result<(), HttpError> {
+    let mut reader = http::reader(stream);
+    let head = http::read_head(&mut reader)?;
+    match (head.method.as_str(), head.path.as_str()) {
+        ("POST", PATH) => {},
+        (_, PATH) => return Result::Err(HttpError::new(405, "Method Not Allowed")),
+        _ => return Result::Err(HttpError::new(404, "Not Found")),
+    }
+    let (user, text) = match head.header("content-type") {
+        Option::Some(content_type) if content_type.starts_with("application/x-www-form-urlencoded") => {
+            let body = http::read_body(&mut reader, &head, MAX_BODY_SIZE)?;
+            let (token, user, text) = parse_form(&body)?;
+            check_token(&token, relay)?;
+            (user, text)
+        },
+        _ => {
+            let token = head.header("authorization")
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .ok_or(HttpError::new(401, "Unauthorized"))?;
+            check_token(token.trim(), relay)?;
+            parse_json(&http::read_body(&mut reader, &head, MAX_BODY_SIZE)?)?
+        },
+    };
+    if user.is_empty() || text.trim().is_empty() {
+        return Result::Err(HttpError::new(422, "Unprocessable Entity"));
+    }
+    let command = c2s::Command::SendMessage {
+        channel: relay.channel.clone(),
+        kind: MessageKind::User,
+        text: format!("<{}> {}", user, text.trim()),
+        ttl: Option::None,
+        client_tag: Option::None,
+    };
+    relay.commands.send(command).map_err(|_| HttpError::new(503, "Service Unavailable"))
+}
+
+/// Returns an error unless `token` is the token of the relay.
+#[doc(hidden)]
+fn check_token(token: &str, relay: &Relay) -> Result<(), HttpError> {
+    match bool::from(token.as_bytes().ct_eq(relay.token.as_bytes())) {
+        true => Result::Ok(()),
+        false => Result::Err(HttpError::new(401, "Unauthorized")),
+    }
+}
+
+/// Returns the user and the text of a JSON request.
+#[doc(hidden)]
+fn parse_json(body: &[u8]) -> Result<(String, String), HttpError> {
+    let payload: Value = serde_json::from_slice(body).map_err(|_| HttpError::new(400, "Bad Request"))?;
+    let field = |name| payload.get(name)
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or(HttpError::new(422, "Unprocessable Entity"));
+    Result::Ok((field("user")?, field("text")?))
+}
+
+/// Returns the token, the user and the text of a form sent by a Slack outgoing
+/// webhook.
+#[doc(hidden)]
+fn parse_form(body: &[u8]) -> Result<(String, String, String), HttpError> {
+    let body = std::str::from_utf8(body).map_err(|_| HttpError::new(400, "Bad Request"))?;
+    let fields: Vec<(&str, &str)> = body.split('&')
+        .filter_map(|field| field.split_once('='))
+        .collect();
+    let field = |name| fields.iter()
+        .find(|(field, _)| *field == name)
+        .and_then(|(_, value)| percent_decode(value))
+        .ok_or(HttpError::new(422, "Unprocessable Entity"));
+    Result::Ok((field("token")?, field("user_name")?, field("text")?))
+}
+
+/// Decodes a value of a URL-encoded form. Returns [`Option::None`] if the value
+/// is not valid.
+#[doc(hidden)]
+fn percent_decode(value: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut iter = value.bytes();
+    while let Option::Some(byte) = iter.next() {
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = [iter.next()?, iter.next()?];
+                bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+            },
+            byte => bytes.push(byte),
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
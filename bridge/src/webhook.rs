@@ -0,0 +1,135 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Forwarding of messages to Discord and Slack webhooks. Messages are posted by
+//! a separate thread, so a slow webhook does not delay the bot.
+
+use crate::config::WebhookKind;
+
+use mdchat_common::markup;
+use mdchat_common::message::Message;
+use mdchat_common::message::MessageKind;
+
+use serde_json::json;
+use serde_json::Value;
+
+use std::sync::mpsc;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+/// Maximum length of a Discord message in characters.
+const DISCORD_MAX_LENGTH: usize = 2000;
+
+/// Maximum time of posting a single message.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How many times a message is posted again when a webhook asks the bridge to
+/// slow down.
+const MAX_RETRIES: u32 = 3;
+
+/// Starts a thread posting messages sent through the returned sender to given
+/// webhooks. Failures are reported and the message is skipped.
+pub fn spawn(webhooks: Vec<(WebhookKind, String)>) -> Sender<Message> {
+    let (sender, messages) = mpsc::channel::<Message>();
+    let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+    thread::spawn(move || for message in messages {
+        for (kind, url) in &webhooks {
+            if let Result::Err(err) = post(&agent, url, &payload(*kind, &message)) {
+                eprintln!("Cannot forward a message to {}: {}", redact(url), err);
+            }
+        }
+    });
+    sender
+}
+
+/// Returns the payload of a webhook request forwarding given message.
+#[doc(hidden)]
+fn payload(kind: WebhookKind, message: &Message) -> Value {
+    let place = match message.channel() {
+        Option::Some(channel) => format!("[#{}] ", channel),
+        Option::None => String::new(),
+    };
+    match kind {
+        // Discord shows the nickname as the author of the message and understands
+        // the markup of MDChat. Mentions are not parsed, so users of the other
+        // chat cannot ping everyone:
+        WebhookKind::Discord => {
+            let content = match message.kind() {
+                MessageKind::Action => format!("{}_{} {}_", place, message.sender(), message.text()),
+                _ => format!("{}{}", place, message.text()),
+            };
+            json!({
+                "username": message.sender(),
+                "content": truncate(&content, DISCORD_MAX_LENGTH),
+                "allowed_mentions": { "parse": [] },
+            })
+        },
+        // Slack uses a different markup, so the text is sent without it:
+        WebhookKind::Slack => {
+            let plain = markup::to_plain(message.text());
+            let text = match message.kind() {
+                MessageKind::Action => format!("{}_*{}* {}_", place, message.sender(), plain),
+                _ => format!("{}*{}*: {}", place, message.sender(), plain),
+            };
+            json!({ "text": text })
+        },
+    }
+}
+
+/// Posts given payload. When the webhook responds with `429 Too Many Requests`,
+/// the payload is posted again after the time given by `Retry-After` header.
+#[doc(hidden)]
+fn post(agent: &ureq::Agent, url: &str, payload: &Value) -> Result<(), String> {
+    let mut retries = 0;
+    loop {
+        let result = agent.post(url)
+            .set("Content-Type", "application/json")
+            .send_string(&payload.to_string());
+        match result {
+            Result::Ok(_) => return Result::Ok(()),
+            Result::Err(ureq::Error::Status(429, response)) if retries < MAX_RETRIES => {
+                let retry_after = response.header("Retry-After")
+                    .and_then(|retry_after| retry_after.parse::<f64>().ok())
+                    .unwrap_or(1.0);
+                thread::sleep(Duration::from_secs_f64(retry_after.clamp(0.0, 60.0)));
+                retries += 1;
+            },
+            Result::Err(ureq::Error::Status(status, _)) => return Result::Err(format!("status {}", status)),
+            Result::Err(err) => return Result::Err(err.kind().to_string()),
+        }
+    }
+}
+
+/// Shortens given text to at most `max_length` characters.
+#[doc(hidden)]
+fn truncate(text: &str, max_length: usize) -> String {
+    match text.chars().count() > max_length {
+        true => text.chars().take(max_length - 1).chain(Option::Some('…')).collect(),
+        false => text.to_string(),
+    }
+}
+
+/// Removes the path from a webhook URL, since it contains the secret of the
+/// webhook.
+#[doc(hidden)]
+fn redact(url: &str) -> &str {
+    let after_scheme = url.find("://").map_or(0, |index| index + 3);
+    match url[after_scheme..].find('/') {
+        Option::Some(index) => &url[..after_scheme + index],
+        Option::None => url,
+    }
+}
@@ -0,0 +1,92 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+/*
+ * A minimal C ABI of the MDChat client library. Received commands are returned
+ * as JSON strings in the same form as they are sent by the server.
+ */
+
+#ifndef MDCHAT_H
+#define MDCHAT_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* A connection to a server. */
+typedef struct MdchatClient MdchatClient;
+
+/*
+ * Connects to a server at given socket address, for example "127.0.0.1:4000",
+ * and verifies that the server owns the identity key it presents. Returns NULL
+ * on failure.
+ */
+MdchatClient *mdchat_connect(const char *address);
+
+/*
+ * Returns the fingerprint of the identity key of the server. The string is
+ * owned by the client and must not be freed.
+ */
+const char *mdchat_fingerprint(const MdchatClient *client);
+
+/*
+ * Logs in, or registers a new user if is_registering is non-zero. Returns 0 if
+ * the request was sent, -1 otherwise. The result is received as an event.
+ */
+int mdchat_login(MdchatClient *client, const char *nickname, const char *password, int is_registering);
+
+/*
+ * Logs in opening a read-only session, which receives messages, but cannot send
+ * them. Returns 0 if the request was sent, -1 otherwise. The result is received
+ * as an event.
+ */
+int mdchat_login_read_only(MdchatClient *client, const char *nickname, const char *password);
+
+/*
+ * Sends a message into given channel, or into the main chat if channel is NULL.
+ * Returns 0 if the message was sent, -1 otherwise.
+ */
+int mdchat_send(MdchatClient *client, const char *channel, const char *text);
+
+/*
+ * Acknowledges that all messages up to the one with given ID have been
+ * received. Front-ends should call it with the id of each received MessageRecv
+ * event, otherwise the message is sent again the next time the user logs in.
+ * Returns 0 if the acknowledgment was sent, -1 otherwise.
+ */
+int mdchat_ack(MdchatClient *client, uint64_t message_id);
+
+/*
+ * Waits at most timeout_ms milliseconds for a command received from the server
+ * and returns it as a JSON string, which must be freed by mdchat_free_string.
+ * Returns NULL if nothing was received in time or the connection is closed.
+ */
+char *mdchat_poll_event(MdchatClient *client, uint32_t timeout_ms);
+
+/* Frees a string returned by mdchat_poll_event. */
+void mdchat_free_string(char *string);
+
+/* Disconnects from the server and frees the client. */
+void mdchat_free(MdchatClient *client);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* MDCHAT_H */
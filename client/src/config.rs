@@ -0,0 +1,395 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Configuration of the client, which is read from `~/.mdchat/client.conf`, or
+//! from `~/.mdchat/profiles/<name>.conf` if a profile is selected. Each line of
+//! the file contains an option followed by its value, lines starting with a hash
+//! (`#`) are comments. If the file does not exist, default values are used.
+
+use once_cell::sync::Lazy;
+use once_cell::sync::OnceCell;
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+static CONFIG: Lazy<ClientConfig> = Lazy::new(load);
+static PROFILE: OnceCell<String> = OnceCell::new();
+
+/// How times of messages are shown.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeFormat {
+    /// Full date and time as in RFC 2822.
+    Full,
+    /// Only time, for example `21:30`.
+    Hours24,
+    /// Only time, for example `9:30 PM`.
+    Hours12,
+}
+
+impl FromStr for TimeFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "full" => Result::Ok(Self::Full),
+            "24h" => Result::Ok(Self::Hours24),
+            "12h" => Result::Ok(Self::Hours12),
+            other => Result::Err(format!("`{}` is not a valid time format, `full`, `24h` or `12h` was expected", other)),
+        }
+    }
+}
+
+/// Kinds of received messages, which the user can be alerted about by the
+/// terminal bell or by a desktop notification.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AlertEvent {
+    /// A message mentioning the user.
+    Mention,
+    /// A direct message.
+    DirectMessage,
+    /// An announcement.
+    Announcement,
+    /// Any message.
+    Message,
+}
+
+impl FromStr for AlertEvent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mentions" => Result::Ok(Self::Mention),
+            "dms" => Result::Ok(Self::DirectMessage),
+            "announcements" => Result::Ok(Self::Announcement),
+            "messages" => Result::Ok(Self::Message),
+            other => Result::Err(format!("`{}` is not a valid event, `mentions`, `dms`, `announcements` or `messages` was expected", other)),
+        }
+    }
+}
+
+/// Events, on which commands given by the user are run, see
+/// [`ClientConfig::hooks`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HookEvent {
+    /// A message or a direct message has been received.
+    Message,
+    /// A message mentioning the user has been received.
+    Mention,
+    /// The client has connected to the server and verified its identity.
+    Connect,
+    /// The client has been disconnected from the server.
+    Disconnect,
+}
+
+impl FromStr for HookEvent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "message" => Result::Ok(Self::Message),
+            "mention" => Result::Ok(Self::Mention),
+            "connect" => Result::Ok(Self::Connect),
+            "disconnect" => Result::Ok(Self::Disconnect),
+            other => Result::Err(format!("`{}` is not a valid event, `message`, `mention`, `connect` or `disconnect` was expected", other)),
+        }
+    }
+}
+
+impl fmt::Display for HookEvent {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        fmtr.write_str(match self {
+            Self::Message => "message",
+            Self::Mention => "mention",
+            Self::Connect => "connect",
+            Self::Disconnect => "disconnect",
+        })
+    }
+}
+
+/// Local actions, which can be bound to commands typed by the user, see
+/// [`ClientConfig::binding`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    /// Searches the local transcript.
+    Search,
+    /// Jumps to the next match of the latest search.
+    NextMatch,
+    /// Jumps to the previous match of the latest search.
+    PreviousMatch,
+    /// Shows only messages from given sender, or from everyone.
+    Filter,
+    /// Shows how many messages hidden by the filter were received.
+    Unread,
+    /// Mutes or unmutes alerts.
+    Mute,
+    /// Disconnects from the server and quits the client.
+    Quit,
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "search" => Result::Ok(Self::Search),
+            "next" => Result::Ok(Self::NextMatch),
+            "prev" => Result::Ok(Self::PreviousMatch),
+            "filter" => Result::Ok(Self::Filter),
+            "unread" => Result::Ok(Self::Unread),
+            "mute" => Result::Ok(Self::Mute),
+            "quit" => Result::Ok(Self::Quit),
+            other => Result::Err(format!("`{}` is not a valid action, `search`, `next`, `prev`, `filter`, `unread`, `mute` or `quit` was expected", other)),
+        }
+    }
+}
+
+/// Configuration of the client.
+#[derive(Clone, Debug)]
+pub struct ClientConfig {
+    time_format: TimeFormat,
+    day_change: bool,
+    read_receipts: bool,
+    nickname_width: Option<usize>,
+    max_width: Option<usize>,
+    bell: HashSet<AlertEvent>,
+    notify: HashSet<AlertEvent>,
+    hooks: HashMap<HookEvent, Vec<String>>,
+    bindings: HashMap<String, Action>,
+}
+
+impl ClientConfig {
+    /// Creates a new [`ClientConfig`] with default values. Using this
+    /// constructor is same as using [`Default`]'s implementation.
+    pub fn new() -> Self {
+        Self {
+            time_format: TimeFormat::Full,
+            day_change: false,
+            read_receipts: false,
+            nickname_width: Option::None,
+            max_width: Option::None,
+            bell: HashSet::from([AlertEvent::Mention]),
+            notify: HashSet::new(),
+            hooks: HashMap::new(),
+            bindings: HashMap::from([
+                ("/search".to_string(), Action::Search),
+                ("/next".to_string(), Action::NextMatch),
+                ("/prev".to_string(), Action::PreviousMatch),
+                ("/filter".to_string(), Action::Filter),
+                ("/unread".to_string(), Action::Unread),
+                ("/mute".to_string(), Action::Mute),
+                ("/quit".to_string(), Action::Quit),
+            ]),
+        }
+    }
+
+    /// Returns how times of messages are shown.
+    pub fn time_format(&self) -> TimeFormat {
+        self.time_format
+    }
+
+    /// Returns whether a line with the date is printed before the first message
+    /// of each day.
+    pub fn day_change(&self) -> bool {
+        self.day_change
+    }
+
+    /// Returns whether it is printed when other users read messages of the user.
+    /// Read receipts are not printed by default.
+    pub fn read_receipts(&self) -> bool {
+        self.read_receipts
+    }
+
+    /// Returns the width nicknames are padded to, so they are aligned in
+    /// a column. [`Option::None`] means that nicknames are not aligned.
+    pub fn nickname_width(&self) -> Option<usize> {
+        self.nickname_width
+    }
+
+    /// Returns the maximum width of a printed message in columns. Longer messages
+    /// are wrapped between words. [`Option::None`] means that messages are not
+    /// wrapped by the client.
+    pub fn max_width(&self) -> Option<usize> {
+        self.max_width
+    }
+
+    /// Returns whether the terminal bell rings when given event happens. By
+    /// default, it rings only when the user is mentioned.
+    pub fn bell(&self, event: AlertEvent) -> bool {
+        self.bell.contains(&event)
+    }
+
+    /// Returns whether a desktop notification is shown when given event happens.
+    /// By default, no notifications are shown.
+    pub fn notify(&self, event: AlertEvent) -> bool {
+        self.notify.contains(&event)
+    }
+
+    /// Returns shell commands, which are run when given event happens. Data of
+    /// the event are passed to the commands as JSON on their standard input.
+    pub fn hooks(&self, event: HookEvent) -> &[String] {
+        self.hooks.get(&event).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Returns the action bound to given command, which is the first word of
+    /// a typed line. By default, each action is bound to its name preceded by
+    /// a slash, for example `/search`.
+    pub fn binding(&self, command: &str) -> Option<Action> {
+        self.bindings.get(command).copied()
+    }
+
+    /// Processes a single line of the configuration file.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if the line is valid
+    ///  -  [`Result::Err`] with the description of the error otherwise
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Result::Ok(())
+        }
+        let (option, value) = line.split_once(char::is_whitespace)
+            .map(|(option, value)| (option, value.trim()))
+            .ok_or(format!("A value was expected after `{}`", line))?;
+        match option {
+            "time-format" => self.time_format = value.parse()?,
+            "day-change" => self.day_change = parse_switch(option, value)?,
+            "read-receipts" => self.read_receipts = parse_switch(option, value)?,
+            "align-nicknames" => self.nickname_width = parse_width(option, value)?,
+            "max-width" => self.max_width = parse_width(option, value)?,
+            "bell" => parse_alert(&mut self.bell, option, value)?,
+            "notify" => parse_alert(&mut self.notify, option, value)?,
+            "hook" => {
+                let (event, command) = value.split_once(char::is_whitespace)
+                    .ok_or(format!("A command was expected after `hook {}`", value))?;
+                self.hooks.entry(event.parse()?).or_default().push(command.trim().to_string());
+            },
+            "bind" => {
+                let (command, action) = value.split_once(char::is_whitespace)
+                    .ok_or(format!("An action or `off` was expected after `bind {}`", value))?;
+                match action.trim() {
+                    "off" => self.bindings.remove(command),
+                    action => self.bindings.insert(command.to_string(), action.parse()?),
+                };
+            },
+            other => return Result::Err(format!("Unknown option `{}`", other)),
+        }
+        Result::Ok(())
+    }
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the configuration of the client. The configuration file is read when
+/// this function is called for the first time.
+pub fn get() -> &'static ClientConfig {
+    &CONFIG
+}
+
+/// Selects a profile, so the configuration is read from
+/// `~/.mdchat/profiles/<name>.conf`. Must be called before [`get`] is called
+/// for the first time.
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] if the profile has been selected
+///  -  [`Result::Err`] with the description of the error if the name is not
+///     valid or a profile has been already selected
+pub fn set_profile(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Result::Err(format!("`{}` is not a valid profile name", name))
+    }
+    PROFILE.set(name.to_string()).map_err(|_| "A profile has been already selected".to_string())
+}
+
+#[doc(hidden)]
+fn parse_switch(option: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "on" => Result::Ok(true),
+        "off" => Result::Ok(false),
+        _ => Result::Err(format!("`on` or `off` was expected after `{}`", option)),
+    }
+}
+
+#[doc(hidden)]
+fn parse_alert(events: &mut HashSet<AlertEvent>, option: &str, value: &str) -> Result<(), String> {
+    let (name, switch) = value.split_once(char::is_whitespace)
+        .ok_or(format!("`on` or `off` was expected after `{} {}`", option, value))?;
+    let event = name.parse()?;
+    match parse_switch(&format!("{} {}", option, name), switch.trim())? {
+        true => events.insert(event),
+        false => events.remove(&event),
+    };
+    Result::Ok(())
+}
+
+#[doc(hidden)]
+fn parse_width(option: &str, value: &str) -> Result<Option<usize>, String> {
+    match value {
+        "off" => Result::Ok(Option::None),
+        value => value.parse().ok()
+            .filter(|width| *width > 0)
+            .map(Option::Some)
+            .ok_or(format!("A positive number of columns or `off` was expected after `{}`", option)),
+    }
+}
+
+/// Returns the path to the configuration file, or [`Option::None`] if the home
+/// directory is not known.
+#[doc(hidden)]
+fn path() -> Option<PathBuf> {
+    let dir = PathBuf::from(env::var_os("HOME")?).join(".mdchat");
+    Option::Some(match PROFILE.get() {
+        Option::Some(profile) => dir.join("profiles").join(format!("{}.conf", profile)),
+        Option::None => dir.join("client.conf"),
+    })
+}
+
+/// Reads the configuration file. Invalid lines are reported and ignored, so
+/// a mistake in the file does not prevent the user from chatting.
+#[doc(hidden)]
+fn load() -> ClientConfig {
+    let mut config = ClientConfig::new();
+    let path = match path() {
+        Option::Some(path) => path,
+        Option::None => return config,
+    };
+    let source = match fs::read_to_string(&path) {
+        Result::Ok(source) => source,
+        // A missing file of a selected profile is probably a typo:
+        Result::Err(err) if err.kind() == io::ErrorKind::NotFound && PROFILE.get().is_none() => return config,
+        Result::Err(err) => {
+            println!("WARNING: Cannot read {}: {}", path.display(), err);
+            return config
+        }
+    };
+    for (line_num, line) in source.lines().enumerate() {
+        if let Result::Err(err) = config.process_line(line) {
+            println!("WARNING: {}:{}: {}", path.display(), line_num + 1, err);
+        }
+    }
+    config
+}
@@ -0,0 +1,106 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Logging of every sent and received frame, which helps with diagnosing
+//! problems with interoperability of clients and servers. Each sent command is
+//! logged before encryption and as the sent frame, each received frame is logged
+//! as received and after decryption together with time spent decoding it.
+
+use chrono::SecondsFormat;
+use chrono::Utc;
+
+use mdchat_common::codec;
+
+use std::fmt;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::Instant;
+
+static FRAME_LOG: Mutex<Option<Box<dyn Write + Send>>> = Mutex::new(Option::None);
+
+/// Starts logging frames into given writer, or stops logging them if
+/// [`Option::None`] is given.
+pub fn set_frame_log(log: Option<Box<dyn Write + Send>>) {
+    *FRAME_LOG.lock().unwrap() = log;
+}
+
+/// Logs a command before it is encrypted.
+pub(crate) fn sending(plain: &[u8]) {
+    if is_enabled() {
+        write(format_args!("SEND plain {} bytes: {}", plain.len(), codec::to_text(plain)));
+    }
+}
+
+/// Logs a frame which is being sent.
+pub(crate) fn sending_frame(frame: &[u8]) {
+    write(format_args!("SEND frame {} bytes: {}", frame.len(), hex(frame)));
+}
+
+/// Logs a received frame.
+///
+/// # Return value
+///
+/// Time when decoding the frame started, if frames are logged.
+pub(crate) fn received_frame(frame: &[u8]) -> Option<Instant> {
+    if !is_enabled() {
+        return Option::None
+    }
+    write(format_args!("RECV frame {} bytes: {}", frame.len(), hex(frame)));
+    Option::Some(Instant::now())
+}
+
+/// Logs a decrypted command.
+///
+/// # Parameters
+///
+///  -  `plain`: the decrypted command
+///  -  `started`: value returned by [`received_frame`]
+///  -  `error`: error of decoding the command, if any
+pub(crate) fn received<E: fmt::Display>(plain: &[u8], started: Option<Instant>, error: Option<E>) {
+    let micros = match started {
+        Option::Some(started) => started.elapsed().as_micros(),
+        Option::None => return,
+    };
+    match error {
+        Option::Some(error) => write(format_args!("RECV plain {} bytes, not decoded after {} us ({}): {}",
+            plain.len(), micros, error, codec::to_text(plain))),
+        Option::None => write(format_args!("RECV plain {} bytes, decoded in {} us: {}",
+            plain.len(), micros, codec::to_text(plain))),
+    }
+}
+
+#[doc(hidden)]
+fn is_enabled() -> bool {
+    FRAME_LOG.lock().unwrap().is_some()
+}
+
+#[doc(hidden)]
+fn write(entry: fmt::Arguments) {
+    let mut log = FRAME_LOG.lock().unwrap();
+    if let Option::Some(writer) = log.as_mut() {
+        let now = Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true);
+        // Stop logging if the log cannot be written, instead of failing to
+        // communicate:
+        if writeln!(writer, "{} {}", now, entry).and_then(|()| writer.flush()).is_err() {
+            *log = Option::None;
+        }
+    }
+}
+
+#[doc(hidden)]
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,309 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! A module exposing a minimal C ABI, so front-ends written in other languages
+//! can connect to a server without reimplementing the protocol. The ABI is
+//! declared in `include/mdchat.h`.
+//!
+//! Received commands are returned by [`mdchat_poll_event`] as JSON strings in the
+//! same form as they are sent by the server. Strings returned by the library must
+//! be freed using [`mdchat_free_string`] and clients must be freed using
+//! [`mdchat_free`].
+
+use crate::recv_command;
+use crate::send_command;
+
+use mdchat_common::command::c2s;
+use mdchat_common::command::s2c;
+use mdchat_common::hello;
+use mdchat_common::hello::ClientVersion;
+use mdchat_common::login::LoginRequest;
+use mdchat_common::message::MessageKind;
+use mdchat_common::Error;
+
+use mdswp::MdswpStream;
+
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::os::raw::c_int;
+use std::ptr;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+/// A connection to a server used through the C ABI.
+pub struct MdchatClient {
+    stream: MdswpStream,
+    fingerprint: CString,
+    events: Receiver<s2c::Command>,
+    send_lock: Arc<Mutex<()>>,
+}
+
+/// Connects to a server at given socket address, for example `127.0.0.1:4000`,
+/// and verifies that the server owns the identity key it presents. Front-ends
+/// should compare the [fingerprint](mdchat_fingerprint) of the key with the one
+/// seen on previous connections.
+///
+/// Returns `NULL` if the address is invalid, the connection failed or the server
+/// failed to prove its identity.
+///
+/// # Safety
+///
+/// `address` must be a valid pointer to a null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn mdchat_connect(address: *const c_char) -> *mut MdchatClient {
+    let address = match to_string(address) {
+        Option::Some(address) => address,
+        Option::None => return ptr::null_mut(),
+    };
+    match connect(&address) {
+        Option::Some(client) => Box::into_raw(Box::new(client)),
+        Option::None => ptr::null_mut(),
+    }
+}
+
+/// Returns the fingerprint of the identity key of the server. The string is owned
+/// by the client and must not be freed.
+///
+/// # Safety
+///
+/// `client` must be a valid pointer returned by [`mdchat_connect`].
+#[no_mangle]
+pub unsafe extern "C" fn mdchat_fingerprint(client: *const MdchatClient) -> *const c_char {
+    match client.as_ref() {
+        Option::Some(client) => client.fingerprint.as_ptr(),
+        Option::None => ptr::null(),
+    }
+}
+
+/// Logs in, or registers a new user if `is_registering` is non-zero. The result
+/// is received as an event, see [`mdchat_poll_event`].
+///
+/// Returns 0 if the request was sent, -1 otherwise.
+///
+/// # Safety
+///
+/// `client` must be a valid pointer returned by [`mdchat_connect`], `nickname`
+/// and `password` must be valid pointers to null-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn mdchat_login(client: *mut MdchatClient, nickname: *const c_char,
+                                      password: *const c_char, is_registering: c_int) -> c_int {
+    match (client.as_mut(), to_string(nickname), to_string(password)) {
+        (Option::Some(client), Option::Some(nickname), Option::Some(password)) => {
+            let login_request = LoginRequest::new(is_registering != 0, nickname, password);
+            to_status(send_command(&mut client.stream, c2s::Command::Login(login_request)).is_ok())
+        },
+        _ => -1,
+    }
+}
+
+/// Logs in opening a read-only session, which receives messages, but cannot
+/// send them. The result is received as an event, see [`mdchat_poll_event`].
+///
+/// Returns 0 if the request was sent, -1 otherwise.
+///
+/// # Safety
+///
+/// `client` must be a valid pointer returned by [`mdchat_connect`], `nickname`
+/// and `password` must be valid pointers to null-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn mdchat_login_read_only(client: *mut MdchatClient, nickname: *const c_char,
+                                                password: *const c_char) -> c_int {
+    match (client.as_mut(), to_string(nickname), to_string(password)) {
+        (Option::Some(client), Option::Some(nickname), Option::Some(password)) => {
+            let login_request = LoginRequest::login(nickname, password).with_read_only(true);
+            to_status(send_command(&mut client.stream, c2s::Command::Login(login_request)).is_ok())
+        },
+        _ => -1,
+    }
+}
+
+/// Sends a message into given channel, or into the main chat if `channel` is
+/// `NULL`.
+///
+/// Returns 0 if the message was sent, -1 otherwise. Whether the server accepted
+/// the message is received as an event.
+///
+/// # Safety
+///
+/// `client` must be a valid pointer returned by [`mdchat_connect`], `text` must
+/// be a valid pointer to a null-terminated string and `channel` must be either
+/// `NULL` or a valid pointer to a null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn mdchat_send(client: *mut MdchatClient, channel: *const c_char, text: *const c_char) -> c_int {
+    let channel = match channel.is_null() {
+        true => Option::None,
+        false => match to_string(channel) {
+            Option::Some(channel) => Option::Some(channel),
+            Option::None => return -1,
+        },
+    };
+    match (client.as_mut(), to_string(text)) {
+        (Option::Some(client), Option::Some(text)) => {
+            let command = c2s::Command::SendMessage { channel, kind: MessageKind::User, text, ttl: Option::None, client_tag: Option::None };
+            to_status(send_locked(&mut client.stream, &client.send_lock, command).is_ok())
+        },
+        _ => -1,
+    }
+}
+
+/// Acknowledges that all messages up to the one with given ID have been received.
+/// Front-ends should call this function with the `id` of each received
+/// `MessageRecv` event after they have processed it, otherwise the message is
+/// sent again the next time the user logs in.
+///
+/// Returns 0 if the acknowledgment was sent, -1 otherwise.
+///
+/// # Safety
+///
+/// `client` must be a valid pointer returned by [`mdchat_connect`].
+#[no_mangle]
+pub unsafe extern "C" fn mdchat_ack(client: *mut MdchatClient, message_id: u64) -> c_int {
+    match client.as_mut() {
+        Option::Some(client) => to_status(send_locked(&mut client.stream, &client.send_lock, c2s::Command::AckUpTo(message_id)).is_ok()),
+        Option::None => -1,
+    }
+}
+
+/// Waits at most `timeout_ms` milliseconds for a command received from the
+/// server and returns it as a JSON string, which must be freed using
+/// [`mdchat_free_string`]. When the connection fails, an `Error` command is
+/// returned.
+///
+/// Returns `NULL` if nothing was received in time or the connection is closed.
+///
+/// # Safety
+///
+/// `client` must be a valid pointer returned by [`mdchat_connect`].
+#[no_mangle]
+pub unsafe extern "C" fn mdchat_poll_event(client: *mut MdchatClient, timeout_ms: u32) -> *mut c_char {
+    let client = match client.as_mut() {
+        Option::Some(client) => client,
+        Option::None => return ptr::null_mut(),
+    };
+    match client.events.recv_timeout(Duration::from_millis(timeout_ms as u64)) {
+        Result::Ok(command) => CString::new(serde_json::to_string(&command).unwrap())
+            .map(CString::into_raw)
+            .unwrap_or(ptr::null_mut()),
+        Result::Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Frees a string returned by [`mdchat_poll_event`].
+///
+/// # Safety
+///
+/// `string` must be either `NULL` or a pointer returned by [`mdchat_poll_event`]
+/// which has not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn mdchat_free_string(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+/// Disconnects from the server and frees the client.
+///
+/// # Safety
+///
+/// `client` must be either `NULL` or a pointer returned by [`mdchat_connect`]
+/// which has not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn mdchat_free(client: *mut MdchatClient) {
+    if !client.is_null() {
+        let mut client = Box::from_raw(client);
+        let _send_lock = client.send_lock.lock().unwrap();
+        let _ = client.stream.finish_write();
+        let _ = client.stream.reset();
+    }
+}
+
+/// Connects to given address, verifies the identity of the server and starts
+/// a thread receiving commands.
+#[doc(hidden)]
+fn connect(address: &str) -> Option<MdchatClient> {
+    let mut stream = MdswpStream::connect(address).ok()?;
+    let client_version = ClientVersion::new(env!("CARGO_PKG_NAME").to_string(), env!("CARGO_PKG_VERSION").to_string());
+    let challenge = hello::new_challenge();
+    send_command(&mut stream, c2s::Command::Hello { client_version, challenge: challenge.clone(), suppress_echo: false, locale: hello::locale_from_env(), encodings: Vec::new(), compressions: Vec::new() }).ok()?;
+    let fingerprint = match recv_command(&mut stream).ok()? {
+        s2c::Command::Hello { identity, .. } if identity.verify(&challenge) => identity.fingerprint(),
+        _ => return Option::None,
+    };
+    // Receive commands in a separate thread, so polling can time out. Messages
+    // are received in the order of their IDs, so a message with an older ID has
+    // been already received and is not reported again:
+    let mut reader = stream.try_clone().ok()?;
+    let send_lock = Arc::new(Mutex::new(()));
+    let reader_send_lock = Arc::clone(&send_lock);
+    let (sender, events) = mpsc::channel();
+    thread::Builder::new()
+        .name("mdchat receiver".to_string())
+        .spawn(move || {
+            let mut last_msg_id = 0;
+            loop {
+                let command = recv_command(&mut reader);
+                let failed = command.is_err();
+                let command = command.unwrap_or_else(|err| s2c::Command::Error(err.to_string()));
+                match command {
+                    // Pings are answered here, so front-ends need not handle them:
+                    s2c::Command::Ping => {
+                        let _ = send_locked(&mut reader, &reader_send_lock, c2s::Command::Pong);
+                        continue
+                    },
+                    s2c::Command::MessageRecv { id, .. } if id <= last_msg_id => continue,
+                    s2c::Command::MessageRecv { id, .. } => last_msg_id = id,
+                    _ => {},
+                }
+                if sender.send(command).is_err() || failed {
+                    break;
+                }
+            }
+        })
+        .ok()?;
+    Option::Some(MdchatClient { stream, fingerprint: CString::new(fingerprint).ok()?, events, send_lock })
+}
+
+/// Sends given command while holding given lock, since commands are sent both by
+/// the front-end and by the thread receiving commands, each using its own clone
+/// of the connection, and frames written at once would interleave.
+#[doc(hidden)]
+fn send_locked(stream: &mut MdswpStream, send_lock: &Mutex<()>, command: c2s::Command) -> Result<(), Error> {
+    let _send_lock = send_lock.lock().unwrap();
+    send_command(stream, command)
+}
+
+/// Copies a null-terminated UTF-8 string. Returns [`Option::None`] if the pointer
+/// is `NULL` or the string is not valid UTF-8.
+#[doc(hidden)]
+unsafe fn to_string(string: *const c_char) -> Option<String> {
+    match string.is_null() {
+        true => Option::None,
+        false => CStr::from_ptr(string).to_str().ok().map(str::to_string),
+    }
+}
+
+#[doc(hidden)]
+fn to_status(success: bool) -> c_int {
+    match success {
+        true => 0,
+        false => -1,
+    }
+}
@@ -0,0 +1,83 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Detection of messages missed between sessions. The ID of the latest message
+//! received from each server is saved into `~/.mdchat/last_ids` for each
+//! nickname. If the first message received after logging in again has a greater
+//! ID than the next one, the client asks the server for the messages between.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+/// Returns the ID of the latest message received from given server by given
+/// user in a previous session, or [`Option::None`] if no message has been
+/// received yet.
+pub fn last_id(server: &SocketAddr, nickname: &str) -> Option<u64> {
+    __read().into_iter()
+        .find(|(s, n, _)| *s == server.to_string() && n == nickname)
+        .map(|(_, _, id)| id)
+}
+
+/// Saves the ID of the latest message received from given server by given user.
+/// Failures are ignored, since the worst consequence is that a gap is not
+/// detected next time.
+pub fn save(server: &SocketAddr, nickname: &str, id: u64) {
+    let server = server.to_string();
+    let mut entries = __read();
+    match entries.iter_mut().find(|(s, n, _)| *s == server && n == nickname) {
+        Option::Some(entry) => entry.2 = id,
+        Option::None => entries.push((server, nickname.to_string(), id)),
+    }
+    let _ = __write(&entries);
+}
+
+/// Returns the path to the file with IDs of latest messages, or
+/// [`Option::None`] if the home directory is not known.
+#[doc(hidden)]
+fn path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".mdchat").join("last_ids"))
+}
+
+/// Reads the saved IDs. Each line of the file contains the server address, the
+/// nickname and the ID separated by spaces. Invalid lines are skipped.
+#[doc(hidden)]
+fn __read() -> Vec<(String, String, u64)> {
+    let content = path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .unwrap_or_default();
+    content.lines()
+        .filter_map(|line| {
+            let (server, rest) = line.split_once(' ')?;
+            let (nickname, id) = rest.rsplit_once(' ')?;
+            Option::Some((server.to_string(), nickname.to_string(), id.parse().ok()?))
+        })
+        .collect()
+}
+
+#[doc(hidden)]
+fn __write(entries: &[(String, String, u64)]) -> io::Result<()> {
+    let path = path().ok_or_else(|| io::Error::other("HOME is not set"))?;
+    if let Option::Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let content: String = entries.iter()
+        .map(|(server, nickname, id)| format!("{} {} {}\n", server, nickname, id))
+        .collect();
+    fs::write(path, content)
+}
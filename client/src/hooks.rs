@@ -0,0 +1,97 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Running commands given by the user on events, so the user can add custom
+//! notifications or automation without modifying the client. Each command is run
+//! by the shell and data of the event are passed to it as a JSON object on its
+//! standard input. The object always contains `event` field with the name of the
+//! event.
+
+use crate::config;
+use crate::config::HookEvent;
+use crate::transcript::Entry;
+
+use serde_json::json;
+use serde_json::Value;
+
+use std::io;
+use std::io::Write;
+use std::process::Command;
+use std::process::Stdio;
+use std::thread;
+
+/// Runs hooks for a received message. Hooks for [`HookEvent::Mention`] are run
+/// in addition to hooks for [`HookEvent::Message`] if the message mentions the
+/// user.
+pub fn message(entry: &Entry, mention: bool) {
+    let (direct, message) = match entry {
+        Entry::Message(message) => (false, json!(message)),
+        Entry::Direct(message) => (true, json!(message)),
+    };
+    run(HookEvent::Message, json!({ "direct": direct, "message": message }));
+    if mention {
+        run(HookEvent::Mention, json!({ "direct": direct, "message": message }));
+    }
+}
+
+/// Runs hooks for given event. Data of the event are extended with `event`
+/// field. Commands run in the background, their output is discarded.
+pub fn run(event: HookEvent, mut data: Value) {
+    let hooks = config::get().hooks(event);
+    if hooks.is_empty() {
+        return
+    }
+    data["event"] = json!(event.to_string());
+    let data = data.to_string();
+    for hook in hooks {
+        let hook = hook.clone();
+        let data = data.clone();
+        thread::spawn(move || {
+            if let Result::Err(err) = __run(&hook, &data) {
+                println!("WARNING: Hook `{}` failed: {}", hook, err);
+            }
+        });
+    }
+}
+
+#[doc(hidden)]
+fn __run(hook: &str, data: &str) -> io::Result<()> {
+    let mut command = match cfg!(windows) {
+        true => {
+            let mut command = Command::new("cmd");
+            command.arg("/C").arg(hook);
+            command
+        },
+        false => {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(hook);
+            command
+        },
+    };
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // The hook may not read its input, so failing to write it is not an error:
+    if let Option::Some(mut stdin) = child.stdin.take() {
+        let _ = writeln!(stdin, "{}", data);
+    }
+    match child.wait()? {
+        status if status.success() => Result::Ok(()),
+        status => Result::Err(io::Error::other(format!("the command has {}", status))),
+    }
+}
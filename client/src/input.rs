@@ -0,0 +1,348 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::config;
+use crate::config::Action;
+
+use chrono::Utc;
+
+use mdchat_common::channel::ChannelMode;
+use mdchat_common::command::c2s;
+use mdchat_common::message::COLOR_COUNT;
+use mdchat_common::message::MessageKind;
+use mdchat_common::profile::Profile;
+
+/// Maximum number of messages requested by `/find` and `/find-regex` commands.
+const SEARCH_LIMIT: u32 = 50;
+
+/// A line typed by the user, converted by [`parse`].
+#[derive(Clone, Debug)]
+pub enum Input {
+    /// A command, which is sent to the server.
+    Send(c2s::Command),
+    /// `/search <text>`: searches the local transcript.
+    Search(String),
+    /// `/next`: jumps to the next match of the latest search.
+    NextMatch,
+    /// `/prev`: jumps to the previous match of the latest search.
+    PreviousMatch,
+    /// `/filter [nickname]`: shows only messages from given sender, or from
+    /// everyone if no sender is given.
+    Filter(Option<String>),
+    /// `/log <on|off>`: turns logging of received messages on or off.
+    Log(bool),
+    /// `/unread`: shows how many messages hidden by the filter were received.
+    Unread,
+    /// `/older`: loads messages older than the oldest message received in this
+    /// session.
+    Older,
+    /// `/delete [id]`: deletes the message with given ID, or the newest message
+    /// sent by the user and received in this session if no ID is given.
+    Delete(Option<u64>),
+    /// `/react [id] <emoji>`: reacts to the message with given ID, or to the
+    /// newest message received in this session if no ID is given.
+    React(Option<u64>, String),
+    /// `/resend`: sends lines, which could not be sent in previous sessions.
+    Resend,
+    /// `/discard`: forgets lines, which could not be sent in previous sessions.
+    Discard,
+    /// `/mute`: mutes or unmutes the terminal bell and desktop notifications.
+    Mute,
+    /// `/quit`: disconnects from the server and quits the client.
+    Quit,
+}
+
+/// Converts a line typed by the user into an [`Input`]. Lines starting with
+/// a slash (`/`) are commands, lines starting with a hash (`#`) followed by
+/// a channel name are messages sent into given channel, any other line is
+/// a message.
+///
+/// Local commands `/search`, `/next`, `/prev`, `/filter`, `/unread`, `/mute`
+/// and `/quit` can be bound to other commands in the configuration of the
+/// client, see [`config::ClientConfig::binding`].
+///
+/// # Supported commands
+///
+///  -  `/search <text>`: searches the local transcript for messages containing
+///     given text
+///  -  `/next`, `/prev`: jumps to the next or the previous match of the latest
+///     search
+///  -  `/filter [nickname]`: shows only messages from given user, or from
+///     everyone if no nickname is given
+///  -  `/log <on|off>`: starts or stops appending received messages into the
+///     log file
+///  -  `/unread`: shows how many messages hidden by `/filter` were received
+///  -  `/older`: loads a page of older messages from the message history
+///  -  `/delete [id]`: deletes given message sent by the user, or their newest
+///     message received in this session
+///  -  `/react [id] <emoji>`: reacts to given message, or to the newest message
+///     received in this session, or removes the reaction
+///  -  `/resend`, `/discard`: sends or forgets messages, which could not be
+///     sent in previous sessions, because the connection was lost
+///  -  `/mute`: mutes the terminal bell and desktop notifications, or unmutes
+///     them if they are muted
+///  -  `/quit`: disconnects from the server and quits the client
+///  -  `/find <text>`: searches the message history for messages containing
+///     given text
+///  -  `/find-regex <regex>`: searches the message history for messages
+///     matching given regular expression
+///  -  `/expire <seconds> <text>`: sends a message which expires after given
+///     number of seconds
+///  -  `/msg <nickname> <text>`: sends a direct message to given user
+///  -  `/me <text>`: describes what the user is doing
+///  -  `/announce <text>`: sends an announcement
+///  -  `/block <nickname>`: stops receiving messages from given user
+///  -  `/unblock <nickname>`: starts receiving messages from given user again
+///  -  `/profile <nickname>`: shows the profile of given user
+///  -  `/set-name [name]`, `/set-bio [bio]`, `/set-status [status]`: changes
+///     a field of the user's profile, or removes it if no value is given
+///  -  `/set-color <color>`: changes the color of the user's nickname
+///  -  `/users`: lists all registered users
+///  -  `/whois <nickname>`: shows when given user was last seen
+///  -  `/availability <available|away|dnd>`: changes whether the user wants
+///     to be disturbed
+///  -  `/time`: shows the time of the server and how much the clock of the
+///     client differs from it
+///  -  `/hide-last-seen <on|off>`: hides or shows the time the user was last
+///     seen to other users
+///  -  `/mod-events <on|off>`: starts or stops receiving events relevant for
+///     moderation
+///  -  `/review`: lists messages waiting for review by a moderator
+///  -  `/approve <id>`, `/reject <id>`: approves or rejects a message waiting
+///     for review
+///  -  `/approve-user <nickname>`: approves an account registered during a raid
+///  -  `/channels`: lists all channels
+///  -  `/topic <channel> [topic]`: changes the topic of a channel, or removes
+///     it if no topic is given
+///  -  `/invite <channel> <nickname>`: invites given user into a channel
+///  -  `/join <channel>`: joins a public channel
+///  -  `/leave <channel>`: leaves a channel
+///  -  `/create-channel <name> [mode]`: creates a new channel, which is public
+///     unless a mode is given
+///  -  `/delete-channel <name>`: deletes a channel owned by the user
+///  -  `/channel-mod <channel> <nickname>`, `/channel-unmod <channel>
+///     <nickname>`: appoints or dismisses a moderator of a channel owned by
+///     the user
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] with the converted line
+///  -  [`Result::Err`] with a description of the error, if the line is not
+///     a valid command
+pub fn parse(line: String) -> Result<Input, String> {
+    let (command, arg) = match line.split_once(' ') {
+        Option::Some((command, arg)) => (command, arg.trim()),
+        Option::None => (line.as_str(), ""),
+    };
+    match config::get().binding(command) {
+        Option::Some(Action::Search) if arg.is_empty() => return Result::Err(format!("Usage: {} <text>", command)),
+        Option::Some(Action::Search) => return Result::Ok(Input::Search(arg.to_string())),
+        Option::Some(Action::NextMatch) => return Result::Ok(Input::NextMatch),
+        Option::Some(Action::PreviousMatch) => return Result::Ok(Input::PreviousMatch),
+        Option::Some(Action::Filter) => return Result::Ok(Input::Filter(Option::Some(arg.to_string()).filter(|arg| !arg.is_empty()))),
+        Option::Some(Action::Unread) => return Result::Ok(Input::Unread),
+        Option::Some(Action::Mute) => return Result::Ok(Input::Mute),
+        Option::Some(Action::Quit) => return Result::Ok(Input::Quit),
+        Option::None => {}
+    }
+    match command {
+        "/older" => Result::Ok(Input::Older),
+        "/delete" if arg.is_empty() => Result::Ok(Input::Delete(Option::None)),
+        "/delete" => match arg.parse() {
+            Result::Ok(id) => Result::Ok(Input::Delete(Option::Some(id))),
+            Result::Err(_) => Result::Err("Usage: /delete [id]".to_string()),
+        },
+        "/react" => match arg.split_once(' ') {
+            Option::Some((id, emoji)) => match id.parse() {
+                Result::Ok(id) => Result::Ok(Input::React(Option::Some(id), emoji.trim().to_string())),
+                Result::Err(_) => Result::Err("Usage: /react [id] <emoji>".to_string()),
+            },
+            Option::None if !arg.is_empty() => Result::Ok(Input::React(Option::None, arg.to_string())),
+            Option::None => Result::Err("Usage: /react [id] <emoji>".to_string()),
+        },
+        "/resend" => Result::Ok(Input::Resend),
+        "/discard" => Result::Ok(Input::Discard),
+        "/log" => match arg {
+            "on" => Result::Ok(Input::Log(true)),
+            "off" => Result::Ok(Input::Log(false)),
+            _ => Result::Err("Usage: /log <on|off>".to_string()),
+        },
+        _ => parse_command(line).map(Input::Send),
+    }
+}
+
+/// Converts a line, which is not a local command, into a [`c2s::Command`].
+#[doc(hidden)]
+fn parse_command(line: String) -> Result<c2s::Command, String> {
+    if let Option::Some(line) = line.strip_prefix('#') {
+        return match line.split_once(' ') {
+            Option::Some((channel, text)) if !channel.is_empty() => Result::Ok(c2s::Command::SendMessage {
+                channel: Option::Some(channel.to_string()),
+                kind: MessageKind::User,
+                text: text.trim().to_string(),
+                ttl: Option::None,
+                client_tag: Option::None,
+            }),
+            _ => Result::Err("Usage: #<channel> <text>".to_string()),
+        }
+    }
+    if !line.starts_with('/') {
+        return Result::Ok(c2s::Command::SendMessage { channel: Option::None, kind: MessageKind::User, text: line, ttl: Option::None, client_tag: Option::None })
+    }
+    let (command, arg) = match line.split_once(' ') {
+        Option::Some((command, arg)) => (command, arg.trim()),
+        Option::None => (line.as_str(), ""),
+    };
+    match command {
+        "/find" | "/find-regex" if arg.is_empty() =>
+            Result::Err(format!("Usage: {} <query>", command)),
+        "/find" => Result::Ok(search(arg, false)),
+        "/find-regex" => Result::Ok(search(arg, true)),
+        "/expire" => {
+            let usage = "Usage: /expire <seconds> <text>".to_string();
+            let (ttl, text) = arg.split_once(' ').ok_or(usage.clone())?;
+            let ttl = ttl.parse().map_err(|_| usage)?;
+            Result::Ok(c2s::Command::SendMessage {
+                channel: Option::None,
+                kind: MessageKind::User,
+                text: text.trim().to_string(),
+                ttl: Option::Some(ttl),
+                client_tag: Option::None,
+            })
+        },
+        "/msg" => match arg.split_once(' ') {
+            Option::Some((recipient, text)) => Result::Ok(c2s::Command::SendDirectMessage {
+                recipient: recipient.to_string(),
+                text: text.trim().to_string(),
+            }),
+            Option::None => Result::Err("Usage: /msg <nickname> <text>".to_string()),
+        },
+        "/me" | "/announce" if arg.is_empty() => Result::Err(format!("Usage: {} <text>", command)),
+        "/me" => Result::Ok(c2s::Command::SendMessage {
+            channel: Option::None,
+            kind: MessageKind::Action,
+            text: arg.to_string(),
+            ttl: Option::None,
+            client_tag: Option::None,
+        }),
+        "/announce" => Result::Ok(c2s::Command::SendMessage {
+            channel: Option::None,
+            kind: MessageKind::Announcement,
+            text: arg.to_string(),
+            ttl: Option::None,
+            client_tag: Option::None,
+        }),
+        "/block" | "/unblock" if arg.is_empty() =>
+            Result::Err(format!("Usage: {} <nickname>", command)),
+        "/block" => Result::Ok(c2s::Command::Block(arg.to_string())),
+        "/unblock" => Result::Ok(c2s::Command::Unblock(arg.to_string())),
+        "/profile" if arg.is_empty() => Result::Err("Usage: /profile <nickname>".to_string()),
+        "/profile" => Result::Ok(c2s::Command::GetProfile(arg.to_string())),
+        "/set-name" => Result::Ok(update_profile(Option::Some(arg), Option::None, Option::None, Option::None)),
+        "/set-bio" => Result::Ok(update_profile(Option::None, Option::Some(arg), Option::None, Option::None)),
+        "/set-status" => Result::Ok(update_profile(Option::None, Option::None, Option::Some(arg), Option::None)),
+        "/set-color" => match arg.parse() {
+            Result::Ok(color) if color < COLOR_COUNT =>
+                Result::Ok(update_profile(Option::None, Option::None, Option::None, Option::Some(color))),
+            _ => Result::Err(format!("Usage: /set-color <0-{}>", COLOR_COUNT - 1)),
+        },
+        "/verify" if arg.is_empty() => Result::Err("Usage: /verify <code>".to_string()),
+        "/verify" => Result::Ok(c2s::Command::VerifyCode(arg.to_string())),
+        "/users" => Result::Ok(c2s::Command::ListUsers),
+        "/whois" if arg.is_empty() => Result::Err("Usage: /whois <nickname>".to_string()),
+        "/whois" => Result::Ok(c2s::Command::WhoIs(arg.to_string())),
+        "/availability" => arg.parse()
+            .map(c2s::Command::SetAvailability)
+            .map_err(|_| "Usage: /availability <available|away|dnd>".to_string()),
+        "/time" => Result::Ok(c2s::Command::TimeSync(Utc::now())),
+        "/hide-last-seen" => match arg {
+            "on" => Result::Ok(c2s::Command::HideLastSeen(true)),
+            "off" => Result::Ok(c2s::Command::HideLastSeen(false)),
+            _ => Result::Err("Usage: /hide-last-seen <on|off>".to_string()),
+        },
+        "/mod-events" => match arg {
+            "on" => Result::Ok(c2s::Command::SubscribeModEvents(true)),
+            "off" => Result::Ok(c2s::Command::SubscribeModEvents(false)),
+            _ => Result::Err("Usage: /mod-events <on|off>".to_string()),
+        },
+        "/review" => Result::Ok(c2s::Command::ListReviewQueue),
+        "/approve" | "/reject" => match arg.parse() {
+            Result::Ok(id) => Result::Ok(c2s::Command::ReviewMessage { id, approve: command == "/approve" }),
+            Result::Err(_) => Result::Err(format!("Usage: {} <id>", command)),
+        },
+        "/approve-user" if arg.is_empty() => Result::Err("Usage: /approve-user <nickname>".to_string()),
+        "/approve-user" => Result::Ok(c2s::Command::ApproveUser(arg.to_string())),
+        "/channels" => Result::Ok(c2s::Command::ListChannels),
+        "/topic" if arg.is_empty() => Result::Err("Usage: /topic <channel> [topic]".to_string()),
+        "/topic" => {
+            let (channel, topic) = arg.split_once(' ').unwrap_or((arg, ""));
+            Result::Ok(c2s::Command::SetTopic {
+                channel: channel.to_string(),
+                topic: topic.trim().to_string(),
+            })
+        },
+        "/invite" => match arg.split_once(' ') {
+            Option::Some((channel, nickname)) => Result::Ok(c2s::Command::Invite {
+                channel: channel.to_string(),
+                nickname: nickname.trim().to_string(),
+            }),
+            Option::None => Result::Err("Usage: /invite <channel> <nickname>".to_string()),
+        },
+        "/join" if arg.is_empty() => Result::Err("Usage: /join <channel>".to_string()),
+        "/join" => Result::Ok(c2s::Command::JoinChannel(arg.to_string())),
+        "/leave" if arg.is_empty() => Result::Err("Usage: /leave <channel>".to_string()),
+        "/leave" => Result::Ok(c2s::Command::LeaveChannel(arg.to_string())),
+        "/create-channel" => {
+            let usage = "Usage: /create-channel <name> [public|invite-only|moderated]".to_string();
+            let (name, mode) = arg.split_once(' ').unwrap_or((arg, ""));
+            let mode = match mode.trim() {
+                "" => ChannelMode::Public,
+                mode => mode.parse().map_err(|_| usage.clone())?,
+            };
+            match name {
+                "" => Result::Err(usage),
+                name => Result::Ok(c2s::Command::CreateChannel { name: name.to_string(), mode }),
+            }
+        },
+        "/delete-channel" if arg.is_empty() => Result::Err("Usage: /delete-channel <name>".to_string()),
+        "/delete-channel" => Result::Ok(c2s::Command::DeleteChannel(arg.to_string())),
+        "/channel-mod" | "/channel-unmod" => match arg.split_once(' ') {
+            Option::Some((channel, nickname)) => Result::Ok(c2s::Command::SetChannelModerator {
+                channel: channel.to_string(),
+                nickname: nickname.trim().to_string(),
+                moderator: command == "/channel-mod",
+            }),
+            Option::None => Result::Err(format!("Usage: {} <channel> <nickname>", command)),
+        },
+        other => Result::Err(format!("Unknown command `{}`", other)),
+    }
+}
+
+#[doc(hidden)]
+fn search(query: &str, regex: bool) -> c2s::Command {
+    c2s::Command::Search { query: query.to_string(), regex, limit: SEARCH_LIMIT }
+}
+
+#[doc(hidden)]
+fn update_profile(display_name: Option<&str>, bio: Option<&str>, status: Option<&str>, color: Option<u8>) -> c2s::Command {
+    c2s::Command::UpdateProfile(Profile::new(
+        display_name.map(str::to_string),
+        bio.map(str::to_string),
+        status.map(str::to_string),
+        color,
+    ))
+}
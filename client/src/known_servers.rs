@@ -0,0 +1,90 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Remembering identities of servers the client has connected to. When
+//! connecting to a server for the first time, the fingerprint of its identity key
+//! is saved and on subsequent connections it must stay the same. This detects a
+//! server impersonating another one, but not a man in the middle relaying the
+//! connection to the real server, see
+//! [`ServerIdentity`](mdchat_common::hello::ServerIdentity).
+
+use std::env;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+/// Checks the fingerprint of the identity key of the server at given socket
+/// address against the fingerprint saved on previous connection. If the client
+/// has never connected to the server, the fingerprint is saved.
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] if the server can be trusted
+///  -  [`Result::Err`] with the description of the problem if the fingerprint
+///     has changed or the known servers file cannot be used
+pub fn verify(socket_addr: &SocketAddr, fingerprint: &str) -> Result<(), String> {
+    let path = path()
+        .ok_or("Cannot verify the identity of the server: HOME is not set".to_string())?;
+    let known_servers = match fs::read_to_string(&path) {
+        Result::Ok(known_servers) => known_servers,
+        Result::Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Result::Err(err) => return Result::Err(format!("Cannot read {}: {}", path.display(), err)),
+    };
+    let address = socket_addr.to_string();
+    let known = known_servers.lines()
+        .filter_map(|line| line.split_once(' '))
+        .find(|(known_address, _)| *known_address == address)
+        .map(|(_, known_fingerprint)| known_fingerprint.trim());
+    match known {
+        Option::Some(known) if known == fingerprint => Result::Ok(()),
+        Option::Some(known) => Result::Err(format!("\n\
+            @@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@\n\
+            @    WARNING: SERVER IDENTITY HAS CHANGED!                @\n\
+            @@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@\n\
+            Someone may be impersonating the server {} to steal your password!\n\
+            Expected fingerprint: {}\n\
+            Received fingerprint: {}\n\
+            If the server administrator has changed the identity key, remove the line\n\
+            for {} from {} and connect again.",
+            address, known, fingerprint, address, path.display())),
+        Option::None => {
+            save(&path, &address, fingerprint)
+                .map_err(|err| format!("Cannot write {}: {}", path.display(), err))?;
+            println!("Connecting to {} for the first time, its fingerprint is {}", address, fingerprint);
+            Result::Ok(())
+        }
+    }
+}
+
+/// Returns the path to the file with fingerprints of known servers, or
+/// [`Option::None`] if the home directory is not known.
+#[doc(hidden)]
+fn path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".mdchat").join("known_servers"))
+}
+
+#[doc(hidden)]
+fn save(path: &PathBuf, address: &str, fingerprint: &str) -> io::Result<()> {
+    if let Option::Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{} {}", address, fingerprint)
+}
@@ -0,0 +1,92 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! A library for communicating with an MDChat server. It implements encoding of
+//! commands and their framing on top of any [`Transport`], so front-ends do not
+//! need to reimplement the protocol. On `wasm32` targets, the library contains
+//! [`WebSocketTransport`] for browser front-ends connecting through the WebSocket
+//! gateway of the server.
+//!
+//! On other targets, the library also exposes a minimal C ABI, see [`ffi`], and
+//! can log every sent and received frame, see [`debug`].
+//!
+//! [`Transport`]: crate::transport::Transport
+//! [`WebSocketTransport`]: crate::transport::WebSocketTransport
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod debug;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod ffi;
+pub mod transport;
+
+use crate::transport::Transport;
+
+use mdchat_common::codec;
+use mdchat_common::codec::Encoding;
+use mdchat_common::command::c2s;
+use mdchat_common::compression;
+use mdchat_common::compression::Compression;
+use mdchat_common::command::s2c;
+use mdchat_common::Error;
+
+/// Encrypts and sends a [`c2s::Command`] using given [`Transport`], encoded as
+/// JSON.
+pub fn send_command<T: Transport>(transport: &mut T, command: c2s::Command) -> Result<(), Error> {
+    send_encoded(transport, command, Encoding::Json, Compression::None)
+}
+
+/// Encrypts and sends a [`c2s::Command`] using given [`Transport`], encoded in
+/// given encoding and compressed using given compression. Only JSON without
+/// compression can be used before the server reports the encoding and the
+/// compression it has chosen, see [`codec`] and [`compression`].
+pub fn send_encoded<T: Transport>(transport: &mut T, command: c2s::Command, encoding: Encoding,
+                                  compression: Compression) -> Result<(), Error> {
+    // Encode, compress and encrypt:
+    let bytes = encoding.encode(&command)?;
+    #[cfg(not(target_arch = "wasm32"))]
+    debug::sending(&bytes);
+    let encrypted = encrypt(compression.compress(bytes));
+    // Send command:
+    #[cfg(not(target_arch = "wasm32"))]
+    debug::sending_frame(&encrypted);
+    transport.send_frame(&encrypted)
+}
+
+/// Receives a single [`s2c::Command`] using given [`Transport`].
+pub fn recv_command<T: Transport>(transport: &mut T) -> Result<s2c::Command, Error> {
+    // Load encrypted content:
+    let buf = transport.recv_frame()?;
+    #[cfg(not(target_arch = "wasm32"))]
+    let started = debug::received_frame(&buf);
+    // Decrypt, decompress and decode in any encoding:
+    let decrypted = compression::decompress(decrypt(buf))?;
+    let command = codec::decode(&decrypted);
+    #[cfg(not(target_arch = "wasm32"))]
+    debug::received(&decrypted, started, command.as_ref().err());
+    let command = command?;
+    // Return Ok if successful:
+    Result::Ok(command)
+}
+
+#[doc(hidden)]
+fn encrypt(data: Vec<u8>) -> Vec<u8> {
+    data
+}
+
+#[doc(hidden)]
+fn decrypt(data: Vec<u8>) -> Vec<u8> {
+    data
+}
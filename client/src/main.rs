@@ -18,9 +18,23 @@
 #[macro_use]
 mod util;
 
-use crate::util::{error, io_error, is_err, recv_command};
-use crate::util::send_command;
+mod config;
+mod gaps;
+mod hooks;
+mod input;
+mod known_servers;
+mod notify;
+mod render;
+mod transcript;
+mod unsent;
 
+use crate::config::HookEvent;
+use crate::input::Input;
+use crate::transcript::Entry;
+use crate::util::{error, io_error, is_err, send, set_format};
+
+use std::env;
+use std::fs::OpenOptions;
 use std::io::BufReader;
 use std::io::BufRead;
 use std::io::stdin;
@@ -29,19 +43,50 @@ use std::io::Write;
 use std::net::SocketAddr;
 use std::process::exit;
 use std::sync::RwLock;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
 use std::thread;
 
+use chrono::Local;
+use chrono::Utc;
+
+use mdchat_client::recv_command;
+use mdchat_client::send_command;
+
+use mdchat_common::codec;
 use mdchat_common::command::{c2s, s2c};
+use mdchat_common::compression;
+use mdchat_common::hello;
+use mdchat_common::hello::ClientVersion;
 use mdchat_common::login::LoginRequest;
+use mdchat_common::message::Message;
 
 use mdswp::MdswpStream;
 
 use once_cell::sync::Lazy;
 
+use serde_json::json;
+
 static mut STDIN_READER: Lazy<BufReader<Stdin>> = Lazy::new(|| BufReader::new(stdin()));
 static IS_ERR: Lazy<RwLock<bool>> = Lazy::new(|| RwLock::new(false));
+/// ID of the oldest message received in this session, or 0 if no message has
+/// been received yet. Older messages are fetched using `/older`.
+static OLDEST_MSG_ID: AtomicU64 = AtomicU64::new(0);
+/// ID of the newest message received in this session, or 0 if no message has
+/// been received yet. The message is reacted to by `/react` without an ID.
+static NEWEST_MSG_ID: AtomicU64 = AtomicU64::new(0);
+/// ID of the newest message sent by the user and received in this session, or
+/// 0 if there is no such message. The message is deleted by `/delete`.
+static OWN_MSG_ID: AtomicU64 = AtomicU64::new(0);
+
+/// How many older messages are requested by `/older`. The server may send fewer.
+const OLDER_PAGE_SIZE: u32 = 20;
 
 fn main() {
+    let args = parse_args();
+    // Read the configuration now, so mistakes in it are reported before
+    // connecting:
+    config::get();
     // IP address and port:
     let mut ip_addr = Option::None;
     let mut port = Option::None;
@@ -65,13 +110,18 @@ fn main() {
     // Ask for username and password:
     let nickname = input!("Nickname: ");
     let password = input!("Password: ");
-    // Ask for login or register
+    // Ask for login, register or read-only login
     let mut is_registering = Option::None;
+    let mut is_read_only = false;
     while matches!(is_registering, Option::None) {
-        let lor = input!("Login or register? (login is default) [L/R] ");
+        let lor = input!("Login, register or read-only login? (login is default) [L/R/O] ");
         match lor.trim() {
             "L" | "l" | "" => is_registering = Option::Some(false),
             "R" | "r" => is_registering = Option::Some(true),
+            "O" | "o" => {
+                is_registering = Option::Some(false);
+                is_read_only = true;
+            },
             _other => {}
         }
     }
@@ -79,6 +129,11 @@ fn main() {
     let ip_addr = ip_addr.unwrap();
     let port = port.unwrap();
     let is_registering = is_registering.unwrap();
+    // Server may require an e-mail address for registration:
+    let email = match is_registering {
+        true => Option::Some(input!("E-mail address (optional): ")).filter(|email| !email.is_empty()),
+        false => Option::None,
+    };
     let socket = SocketAddr::new(ip_addr, port);
     // Connect to server
     let mut conn = match MdswpStream::connect(socket) {
@@ -92,30 +147,199 @@ fn main() {
             exit(1);
         }
     };
+    // Report the client version and verify the identity of the server before
+    // sending the password:
+    let client_version = ClientVersion::new(env!("CARGO_PKG_NAME").to_string(), env!("CARGO_PKG_VERSION").to_string());
+    let challenge = hello::new_challenge();
+    if let Result::Err(err) = send_command(&mut conn, c2s::Command::Hello {
+        client_version,
+        challenge: challenge.clone(),
+        suppress_echo: false,
+        locale: hello::locale_from_env(),
+        encodings: codec::SUPPORTED.to_vec(),
+        compressions: compression::SUPPORTED.to_vec(),
+    }) {
+        io_error(&mut conn, err)
+    }
+    match recv_command(&mut conn) {
+        Result::Ok(s2c::Command::Hello { identity, .. }) if !identity.verify(&challenge) =>
+            error(&mut conn, "Server failed to prove its identity".to_string()),
+        Result::Ok(s2c::Command::Hello { identity, info }) => {
+            let fingerprint = identity.fingerprint();
+            // A fingerprint given on the command line replaces the known servers
+            // file:
+            let verified = match &args.expected_fingerprint {
+                Option::Some(expected) if *expected != fingerprint => Result::Err(format!(
+                    "Server identity does not match! Expected fingerprint {}, received {}", expected, fingerprint)),
+                Option::Some(_) => Result::Ok(()),
+                Option::None => known_servers::verify(&socket, &fingerprint),
+            };
+            match verified {
+                Result::Ok(()) => {
+                    set_format(info.encoding(), info.compression());
+                    println!("{}", render::server_info(&info, &fingerprint));
+                    hooks::run(HookEvent::Connect, json!({
+                        "server": socket.to_string(),
+                        "fingerprint": fingerprint,
+                    }));
+                },
+                Result::Err(err) => error(&mut conn, err),
+            }
+        },
+        Result::Ok(s2c::Command::Error(description)) => error(&mut conn, description),
+        Result::Ok(_) => error(&mut conn, "Server did not prove its identity".to_string()),
+        Result::Err(err) => io_error(&mut conn, err),
+    }
+    if is_err() {
+        input!("");
+        exit(1);
+    }
     // Receiver thread
     let conn_clone = conn.try_clone().unwrap();
-    thread::spawn(|| listen_for_incoming(conn_clone));
+    let listener_nickname = nickname.clone();
+    thread::spawn(move || listen_for_incoming(conn_clone, socket, listener_nickname));
     // Login command
-    let login_request = LoginRequest::new(is_registering, nickname, password);
+    let login_request = LoginRequest::new(is_registering, nickname, password)
+        .with_email(email)
+        .with_read_only(is_read_only);
     let login_command = c2s::Command::Login(login_request);
     // Send login command
-    match send_command(&mut conn, login_command) {
+    match send(&mut conn, login_command) {
         Result::Ok(()) => {}
         Result::Err(err) => io_error(&mut conn, err)
     }
 
     loop {
         let message = input!("");
-        if is_err() { return }
-        let command = c2s::Command::SendMessage(message);
-        let send_result =  util::send_command(&mut conn, command);
+        if is_err() {
+            // The connection has been lost while the user was typing:
+            if matches!(input::parse(message.clone()), Result::Ok(Input::Send(_))) {
+                unsent::save(&[&message]);
+            }
+            return
+        }
+        let command = match input::parse(message.clone()) {
+            Result::Ok(Input::Send(command)) => command,
+            Result::Ok(Input::Search(query)) => { transcript::search(&query); continue }
+            Result::Ok(Input::NextMatch) => { transcript::next_match(); continue }
+            Result::Ok(Input::PreviousMatch) => { transcript::previous_match(); continue }
+            Result::Ok(Input::Filter(sender)) => { transcript::set_filter(sender); continue }
+            Result::Ok(Input::Unread) => { transcript::print_unread(); continue }
+            Result::Ok(Input::Older) => c2s::Command::FetchHistory {
+                before_id: Option::Some(OLDEST_MSG_ID.load(Ordering::Relaxed)).filter(|&id| id != 0),
+                limit: OLDER_PAGE_SIZE,
+            },
+            Result::Ok(Input::Delete(Option::Some(id))) => c2s::Command::DeleteMessage(id),
+            Result::Ok(Input::Delete(Option::None)) => match OWN_MSG_ID.swap(0, Ordering::Relaxed) {
+                0 => { println!("None of your messages has been received in this session"); continue }
+                id => c2s::Command::DeleteMessage(id),
+            },
+            Result::Ok(Input::React(Option::Some(msg_id), emoji)) => c2s::Command::React { msg_id, emoji },
+            Result::Ok(Input::React(Option::None, emoji)) => match NEWEST_MSG_ID.load(Ordering::Relaxed) {
+                0 => { println!("No message has been received in this session"); continue }
+                msg_id => c2s::Command::React { msg_id, emoji },
+            },
+            Result::Ok(Input::Resend) => { resend(&mut conn); continue }
+            Result::Ok(Input::Discard) => { unsent::clear(); continue }
+            Result::Ok(Input::Quit) => return,
+            Result::Ok(Input::Mute) => {
+                match notify::toggle_mute() {
+                    true => println!("Alerts are muted, type /mute again to unmute them"),
+                    false => println!("Alerts are not muted anymore"),
+                }
+                continue
+            },
+            Result::Ok(Input::Log(on)) => {
+                if let Result::Err(err) = transcript::set_logging(on) {
+                    println!("{}", err);
+                }
+                continue
+            },
+            Result::Err(err) => { println!("{}", err); continue }
+        };
+        let send_result =  send(&mut conn, command);
         if let Result::Err(err) = send_result {
+            unsent::save(&[&message]);
             util::io_error(&mut conn, err);
         }
     }
 }
 
-fn listen_for_incoming(mut conn: MdswpStream) {
+/// Sends lines, which could not be sent in previous sessions. If sending fails
+/// again, the lines which were not sent are saved again.
+fn resend(conn: &mut MdswpStream) {
+    let unsent = unsent::load();
+    unsent::clear();
+    for (index, line) in unsent.iter().enumerate() {
+        let command = match input::parse(line.clone()) {
+            Result::Ok(Input::Send(command)) => command,
+            _ => continue,
+        };
+        if let Result::Err(err) = send(conn, command) {
+            unsent::save(&unsent[index..]);
+            io_error(conn, err);
+            return
+        }
+    }
+    println!("{} message(s) have been sent", unsent.len());
+}
+
+/// Command-line arguments of the client.
+struct Args {
+    /// Fingerprint the identity of the server must have, given by
+    /// `--expect-fingerprint <fingerprint>`.
+    expected_fingerprint: Option<String>,
+}
+
+/// Processes command-line arguments:
+///
+///  -  `--debug-frames <file>` appends every sent and received frame into given
+///     file
+///  -  `--expect-fingerprint <fingerprint>` refuses to log in unless the identity
+///     of the server has given fingerprint
+///  -  `--profile <name>` reads the configuration from
+///     `~/.mdchat/profiles/<name>.conf`
+fn parse_args() -> Args {
+    let args: Vec<String> = env::args().collect();
+    let mut result = Args { expected_fingerprint: Option::None };
+    let mut rest = &args[1..];
+    while !rest.is_empty() {
+        match rest {
+            [flag, path, ..] if flag == "--debug-frames" => {
+                match OpenOptions::new().create(true).append(true).open(path) {
+                    Result::Ok(file) => mdchat_client::debug::set_frame_log(Option::Some(Box::new(file))),
+                    Result::Err(err) => {
+                        println!("Cannot open {}: {}", path, err);
+                        exit(1);
+                    }
+                }
+            },
+            [flag, fingerprint, ..] if flag == "--expect-fingerprint" =>
+                result.expected_fingerprint = Option::Some(fingerprint.to_lowercase()),
+            [flag, profile, ..] if flag == "--profile" => {
+                if let Result::Err(err) = config::set_profile(profile) {
+                    println!("{}", err);
+                    exit(1);
+                }
+            },
+            _ => {
+                println!("Usage: {} [--debug-frames <file>] [--expect-fingerprint <fingerprint>] [--profile <name>]", args[0]);
+                exit(1);
+            }
+        }
+        rest = &rest[2..];
+    }
+    result
+}
+
+fn listen_for_incoming(mut conn: MdswpStream, server: SocketAddr, nickname: String) {
+    let mut last_msg_id = 0;
+    // ID of the latest message received in the previous session, until the
+    // first message of this session is received:
+    let mut previous_msg_id = gaps::last_id(&server, &nickname);
+    // Messages received while missed messages are being fetched, so they are
+    // shown after them:
+    let mut held_back: Option<Vec<(u64, Message, bool)>> = Option::None;
     while !is_err() {
         let command = recv_command(&mut conn);
         let command = match command {
@@ -123,10 +347,156 @@ fn listen_for_incoming(mut conn: MdswpStream) {
             Result::Err(err) => { io_error(&mut conn, err); return; }
         };
         match command {
-            s2c::Command::LoginSuccess => println!("Login successful! Now type your messages."),
-            s2c::Command::MessageRecv(message) => println!("{}", message),
+            s2c::Command::LoginSuccess => {
+                println!("Login successful! Now type your messages.");
+                unsent::announce();
+            },
+            s2c::Command::VerificationRequired => println!("A verification code has been sent to your e-mail address. Type /verify <code> to activate your account."),
+            // Messages are received in the order of their IDs, so a message with an
+            // older ID has been already received:
+            s2c::Command::MessageRecv { id, .. } if id <= last_msg_id => {},
+            s2c::Command::MessageRecv { id, message, mention } => {
+                last_msg_id = id;
+                // A smaller ID means the message history of the server has been
+                // reset, so there is nothing to fetch:
+                if let Option::Some(previous_msg_id) = previous_msg_id.take().filter(|&previous| id > previous + 1) {
+                    let fetch = c2s::Command::FetchRange { after_id: previous_msg_id, before_id: id };
+                    if let Result::Err(err) = send(&mut conn, fetch) {
+                        io_error(&mut conn, err);
+                        return;
+                    }
+                    held_back = Option::Some(Vec::new());
+                }
+                match &mut held_back {
+                    Option::Some(held_back) => held_back.push((id, message, mention)),
+                    Option::None => show_message(&mut conn, &server, &nickname, id, message, mention),
+                }
+            },
+            // The message is received afterwards, unless the echo is suppressed,
+            // so /delete works even then:
+            s2c::Command::MessageAccepted { id, .. } => {
+                OWN_MSG_ID.fetch_max(id, Ordering::Relaxed);
+            },
+            s2c::Command::HistoryRange { entries, complete, .. } => {
+                if !entries.is_empty() {
+                    transcript::mark(&format!("{} message(s) missed since the last session", entries.len()));
+                }
+                for entry in entries {
+                    received_msg_id(entry.id());
+                    transcript::show(Entry::Message(entry.message().clone()), false);
+                }
+                if !complete {
+                    transcript::mark("Some messages could not be recovered");
+                }
+                for (id, message, mention) in held_back.take().unwrap_or_default() {
+                    show_message(&mut conn, &server, &nickname, id, message, mention);
+                }
+            },
+            s2c::Command::HistoryChunk { entries, more, .. } => {
+                println!("{} older message(s):", entries.len());
+                for entry in entries {
+                    received_msg_id(entry.id());
+                    println!("  {}", render::message(entry.message()));
+                }
+                if !more {
+                    println!("There are no older messages.");
+                }
+            },
+            s2c::Command::DirectMessageRecv(message) => {
+                let entry = Entry::Direct(message);
+                hooks::message(&entry, false);
+                transcript::show(entry, false);
+            },
+            s2c::Command::QuotaExceeded { kind, limit, resets_at } => match resets_at {
+                Option::Some(resets_at) => println!("WARNING: You have used up your {} quota of {} messages, it resets at {}",
+                    kind, limit, resets_at.with_timezone(&Local).to_rfc2822()),
+                Option::None => println!("WARNING: You have used up your {} quota of {} messages, reconnect to reset it",
+                    kind, limit),
+            },
+            s2c::Command::MessageDeleted(id) => println!("Message {} has been deleted", id),
+            s2c::Command::ReactionUpdate { msg_id, reactions } if reactions.is_empty() =>
+                println!("Reactions to message {} have been removed", msg_id),
+            s2c::Command::ReactionUpdate { msg_id, reactions } => println!("Reactions to message {}: {}", msg_id,
+                reactions.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")),
             s2c::Command::Warning(description) => println!("WARNING: {}", description),
-            s2c::Command::Error(description) => error(&mut conn, description)
+            s2c::Command::Error(description) => error(&mut conn, description),
+            s2c::Command::Unsupported(name) => println!("WARNING: Server does not support command `{}`", name),
+            s2c::Command::ChannelInfo(info) => println!("Channel {}", info),
+            s2c::Command::ChannelDeleted(name) => println!("Channel {} has been deleted", name),
+            s2c::Command::ProfileInfo { nickname, profile } => println!("Profile of {}: {}", nickname, profile),
+            s2c::Command::UserInfo(info) => println!("User {}", info),
+            s2c::Command::MessageRead { reader, .. } if config::get().read_receipts() =>
+                println!("{}{} has read your messages{}", render::DIM, reader, render::RESET),
+            s2c::Command::MessageRead { .. } => {}
+            // Users joining and leaving are announced by notices of the server:
+            s2c::Command::UserJoined(_) | s2c::Command::UserLeft(_) => {}
+            s2c::Command::Welcome(messages) => messages.iter()
+                .for_each(|text| println!("{}", render::welcome(text))),
+            s2c::Command::UserList(users) => {
+                println!("{} registered user(s):", users.len());
+                users.iter().for_each(|user| println!("  {}", user));
+            },
+            s2c::Command::ModEvent(event) => println!("MODERATION: {}", event),
+            s2c::Command::ReviewQueue(messages) => {
+                println!("{} message(s) waiting for review:", messages.len());
+                messages.iter().for_each(|message| println!("  {}", message));
+            },
+            s2c::Command::SearchResult(messages) => {
+                println!("Found {} message(s):", messages.len());
+                messages.iter().for_each(|message| println!("  {}", render::message(message)));
+            },
+            s2c::Command::TimeSync(time_sync) => println!("{}", render::time_sync(&time_sync, Utc::now())),
+            s2c::Command::Ping => if let Result::Err(err) = send(&mut conn, c2s::Command::Pong) {
+                io_error(&mut conn, err);
+                return;
+            },
+            // The identity of the server has been already verified:
+            s2c::Command::Hello { .. } => {}
+            // Commands added in newer versions of the protocol:
+            s2c::Command::Unknown(_) => {}
+            // Responses to administrative commands, which this client never sends:
+            s2c::Command::LogRecord(_)
+            | s2c::Command::ConnectionList(_)
+            | s2c::Command::FilterStats(_)
+            | s2c::Command::FilterDecision(_)
+            | s2c::Command::FilterRuleAdded { .. }
+            | s2c::Command::LockdownState(_)
+            | s2c::Command::HistoryExport(_)
+            | s2c::Command::UserExport(_)
+            | s2c::Command::UserImportResult { .. } => {}
+        }
+    }
+}
+
+/// Shows a message received from the server, tells the server it does not need
+/// to be sent again and remembers its ID for detecting missed messages in the
+/// next session, see [`gaps`]. Unless messages are filtered, the message and all
+/// older ones are marked as read.
+fn show_message(conn: &mut MdswpStream, server: &SocketAddr, nickname: &str, id: u64, message: Message, mention: bool) {
+    received_msg_id(id);
+    NEWEST_MSG_ID.fetch_max(id, Ordering::Relaxed);
+    if message.sender() == nickname && !message.is_from_server() {
+        OWN_MSG_ID.fetch_max(id, Ordering::Relaxed);
+    }
+    let entry = Entry::Message(message);
+    hooks::message(&entry, mention);
+    transcript::show(entry, mention);
+    if let Result::Err(err) = send(conn, c2s::Command::AckUpTo(id)) {
+        io_error(conn, err);
+        return;
+    }
+    if !transcript::is_filtered() {
+        if let Result::Err(err) = send(conn, c2s::Command::MarkRead(id)) {
+            io_error(conn, err);
+            return;
         }
     }
-}
\ No newline at end of file
+    gaps::save(server, nickname, id);
+}
+
+/// Remembers given message ID as the oldest one received in this session, if
+/// it is older than all previously received ones, see [`OLDEST_MSG_ID`].
+fn received_msg_id(id: u64) {
+    let _ = OLDEST_MSG_ID.fetch_update(Ordering::Relaxed, Ordering::Relaxed,
+        |oldest| (oldest == 0 || id < oldest).then_some(id));
+}
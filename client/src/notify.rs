@@ -0,0 +1,118 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Alerting the user about received messages by the terminal bell and by
+//! desktop notifications, as set by [`bell`] and [`notify`] options of the
+//! configuration. Alerts can be muted for the rest of the session.
+//!
+//! [`bell`]: crate::config::ClientConfig::bell
+//! [`notify`]: crate::config::ClientConfig::notify
+
+use crate::config;
+use crate::config::AlertEvent;
+use crate::transcript::Entry;
+
+use mdchat_common::message::MessageKind;
+
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::thread;
+
+/// Whether the user has muted alerts using `/mute`.
+static MUTED: AtomicBool = AtomicBool::new(false);
+
+/// Whether showing a desktop notification has failed, so the failure is
+/// reported only once.
+static NOTIFY_FAILED: AtomicBool = AtomicBool::new(false);
+
+/// Mutes alerts, or unmutes them if they are already muted.
+///
+/// # Return value
+///
+/// Whether alerts are muted now.
+pub fn toggle_mute() -> bool {
+    !MUTED.fetch_xor(true, Ordering::SeqCst)
+}
+
+/// Shows a desktop notification about a received message if the user wants to
+/// be notified about it.
+///
+/// # Parameters
+///
+///  -  `entry`: the received message
+///  -  `mention`: whether the message mentions the user
+///
+/// # Return value
+///
+/// Whether the terminal bell should ring.
+pub fn alert(entry: &Entry, mention: bool) -> bool {
+    if MUTED.load(Ordering::SeqCst) {
+        return false
+    }
+    let config = config::get();
+    let events = events(entry, mention);
+    if events.iter().any(|event| config.notify(*event)) {
+        __notify(&format!("{} ({})", entry.sender(), entry.place()), entry.text());
+    }
+    events.iter().any(|event| config.bell(*event))
+}
+
+/// Returns all events a received message belongs to.
+#[doc(hidden)]
+fn events(entry: &Entry, mention: bool) -> Vec<AlertEvent> {
+    let mut events = vec![AlertEvent::Message];
+    if mention {
+        events.push(AlertEvent::Mention);
+    }
+    match entry {
+        Entry::Direct(_) => events.push(AlertEvent::DirectMessage),
+        Entry::Message(message) if message.kind() == MessageKind::Announcement =>
+            events.push(AlertEvent::Announcement),
+        Entry::Message(_) => {}
+    }
+    events
+}
+
+/// Shows a desktop notification using `notify-send`, or `osascript` on macOS.
+#[doc(hidden)]
+fn __notify(title: &str, body: &str) {
+    let mut command = match cfg!(target_os = "macos") {
+        true => {
+            let mut command = Command::new("osascript");
+            command.arg("-e").arg(format!("display notification {:?} with title {:?}", body, title));
+            command
+        },
+        false => {
+            let mut command = Command::new("notify-send");
+            command.arg("--").arg(title).arg(body);
+            command
+        },
+    };
+    let result = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    match result {
+        // Wait for the command in another thread, so it does not stay a zombie:
+        Result::Ok(mut child) => { thread::spawn(move || child.wait()); },
+        Result::Err(err) => if !NOTIFY_FAILED.swap(true, Ordering::SeqCst) {
+            println!("WARNING: Cannot show a desktop notification: {}", err);
+        },
+    }
+}
@@ -0,0 +1,365 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::config;
+use crate::config::TimeFormat;
+
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Local;
+use chrono::NaiveDate;
+use chrono::Utc;
+
+use mdchat_common::hello::ServerInfo;
+use mdchat_common::markup;
+use mdchat_common::markup::Span;
+use mdchat_common::message::COLOR_COUNT;
+use mdchat_common::message::DirectMessage;
+use mdchat_common::message::LinkPreview;
+use mdchat_common::message::Message;
+use mdchat_common::message::MessageKind;
+use mdchat_common::message::SERVER_PREFIX;
+use mdchat_common::time::TimeSync;
+
+/// ANSI escape sequences used for sender colors. There must be exactly
+/// [`COLOR_COUNT`] of them.
+const PALETTE: [&str; COLOR_COUNT as usize] = [
+    "\x1b[31m", "\x1b[32m", "\x1b[33m", "\x1b[34m", "\x1b[35m", "\x1b[36m",
+    "\x1b[91m", "\x1b[92m", "\x1b[93m", "\x1b[94m", "\x1b[95m", "\x1b[96m",
+];
+
+/// ANSI escape sequence resetting the text color.
+pub const RESET: &str = "\x1b[0m";
+
+/// Indentation of continuation lines of a wrapped message, if the prefix of the
+/// message is too wide to indent them under the text.
+const WRAP_INDENT: usize = 4;
+
+/// Difference between the clock of the client and the clock of the server, which
+/// is worth warning the user about.
+const MAX_CLOCK_OFFSET_SECS: i64 = 5;
+
+/// ANSI escape sequence for dim text, used for system messages and labels of bot
+/// messages.
+pub const DIM: &str = "\x1b[2m";
+
+/// ANSI escape sequence for bold text, used for announcements and bold parts of
+/// messages.
+const BOLD: &str = "\x1b[1m";
+
+/// ANSI escape sequence for italics in messages.
+const ITALIC: &str = "\x1b[3m";
+
+/// ANSI escape sequence for code in messages, which is printed on a gray
+/// background.
+const CODE: &str = "\x1b[100m";
+
+/// ANSI escape sequence for underlined text, used for texts of links.
+const UNDERLINE: &str = "\x1b[4m";
+
+/// ANSI escape sequences turning reverse video on and off, used for highlighting
+/// matches of a search. Turning it off does not reset other attributes, so
+/// a match can be highlighted inside dimmed or colored text.
+const REVERSE: &str = "\x1b[7m";
+const REVERSE_OFF: &str = "\x1b[27m";
+
+/// Formats a [`Message`] for printing into the terminal. Nickname of the sender
+/// is printed in the color assigned by the server, preceded by the channel the
+/// message was sent into, if any. Each [`MessageKind`] is formatted differently.
+pub fn message(message: &Message) -> String {
+    __message(message, Option::None)
+}
+
+/// Same as [`message`], but occurrences of given query in the text of the message
+/// are highlighted, see [`highlight`].
+pub fn highlighted_message(message: &Message, query: &str) -> String {
+    __message(message, Option::Some(query))
+}
+
+#[doc(hidden)]
+fn __message(message: &Message, query: Option<&str>) -> String {
+    let color = PALETTE[message.color() as usize % PALETTE.len()];
+    let channel = message.channel().map(|channel| format!("#{} ", channel)).unwrap_or_default();
+    let date_time = time(message.date_time());
+    let sender = nickname(message.sender());
+    // The text is formatted separately, so it can be wrapped. The style of the
+    // text is restored after each formatted part of it:
+    let (prefix, style, suffix) = match message.kind() {
+        MessageKind::User => (format!("{}{}{}{} @ {}: ",
+            channel, color, sender, RESET, date_time), "", ""),
+        MessageKind::System if message.is_from_server() => (format!("{}{}{} {} -- {} ",
+            channel, DIM, SERVER_PREFIX, date_time, sender), DIM, RESET),
+        MessageKind::System => (format!("{}{}{} -- {} ",
+            channel, DIM, date_time, sender), DIM, RESET),
+        MessageKind::Action => (format!("{}{} * {}{}{} ",
+            channel, date_time, color, sender, RESET), "", ""),
+        MessageKind::Announcement => (format!("{}{}ANNOUNCEMENT from {}{}{}{} @ {}: ",
+            channel, BOLD, color, sender, RESET, BOLD, date_time), BOLD, RESET),
+        MessageKind::Bot => (format!("{}{}[bot]{} {}{}{} @ {}: ",
+            channel, DIM, RESET, color, sender, RESET, date_time), "", ""),
+    };
+    let mut result = layout(&prefix, &rich_text(message.text(), style, query), suffix);
+    result.push_str(&link_previews(message.previews()));
+    result
+}
+
+/// Formats previews of links, which the server has fetched titles of, as dim
+/// lines following the message. Other links are visible in the text already.
+#[doc(hidden)]
+fn link_previews(previews: &[LinkPreview]) -> String {
+    previews.iter()
+        .filter_map(|preview| preview.title().map(|title| match preview.description() {
+            Option::Some(description) => format!("{} -- {}", title, description),
+            Option::None => title.clone(),
+        }))
+        .map(|text| format!("\n{}", layout(&format!("{}{}", DIM, " ".repeat(WRAP_INDENT)), &text, RESET)))
+        .collect()
+}
+
+/// Formats a message of the welcome sequence sent by the server after the first
+/// login, which is printed like a system message of the server.
+pub fn welcome(text: &str) -> String {
+    layout(&format!("{}{} ", DIM, SERVER_PREFIX), &rich_text(text, DIM, Option::None), RESET)
+}
+
+/// Formats a [`DirectMessage`] for printing into the terminal. Nickname of the
+/// sender is printed in the color assigned by the server.
+pub fn direct_message(message: &DirectMessage) -> String {
+    __direct_message(message, Option::None)
+}
+
+/// Same as [`direct_message`], but occurrences of given query in the text of the
+/// message are highlighted, see [`highlight`].
+pub fn highlighted_direct_message(message: &DirectMessage, query: &str) -> String {
+    __direct_message(message, Option::Some(query))
+}
+
+#[doc(hidden)]
+fn __direct_message(message: &DirectMessage, query: Option<&str>) -> String {
+    let color = PALETTE[message.color() as usize % PALETTE.len()];
+    let prefix = format!("{}[direct]{} {}{}{} @ {}: ",
+            BOLD,
+            RESET,
+            color,
+            nickname(message.sender()),
+            RESET,
+            time(message.date_time())
+    );
+    layout(&prefix, &rich_text(message.text(), "", query), "")
+}
+
+/// Formats a text of a message with markup, see [`mdchat_common::markup`].
+/// Links are followed by their URL in angle brackets, since not all terminals
+/// support links.
+///
+/// # Parameters
+///
+///  -  `text`: the text with markup
+///  -  `style`: ANSI escape sequences of the style of the whole text, which is
+///     restored after each formatted part
+///  -  `query`: query which is highlighted in the text, see [`highlight`], if
+///     any
+#[doc(hidden)]
+fn rich_text(text: &str, style: &str, query: Option<&str>) -> String {
+    let highlighted = |text: &str| match query {
+        Option::Some(query) => highlight(text, query),
+        Option::None => text.to_string(),
+    };
+    let mut result = String::with_capacity(text.len());
+    for span in markup::parse(text) {
+        let (format, text) = match &span {
+            Span::Text(text) => {
+                result.push_str(&highlighted(text));
+                continue
+            },
+            Span::Bold(text) => (BOLD, highlighted(text)),
+            Span::Italic(text) => (ITALIC, highlighted(text)),
+            Span::Code(text) => (CODE, highlighted(text)),
+            Span::Link { text, url } => (UNDERLINE, format!("{}{}{} <{}>", highlighted(text), RESET, style, url)),
+        };
+        result.push_str(format);
+        result.push_str(&text);
+        result.push_str(RESET);
+        result.push_str(style);
+    }
+    result
+}
+
+/// Formats the line printed before the first message of a day, if
+/// [`config::ClientConfig::day_change`] is turned on.
+pub fn day_change(date: NaiveDate) -> String {
+    format!("{}---------- {} ----------{}", DIM, date.format("%A, %-d %B %Y"), RESET)
+}
+
+/// Formats the time of a message according to [`config::ClientConfig::time_format`].
+#[doc(hidden)]
+fn time(date_time: &DateTime<Utc>) -> String {
+    let date_time = date_time.with_timezone(&Local);
+    match config::get().time_format() {
+        TimeFormat::Full => date_time.to_rfc2822(),
+        TimeFormat::Hours24 => date_time.format("%H:%M").to_string(),
+        TimeFormat::Hours12 => date_time.format("%-I:%M %p").to_string(),
+    }
+}
+
+/// Pads given nickname to [`config::ClientConfig::nickname_width`], so nicknames are
+/// aligned in a column.
+#[doc(hidden)]
+fn nickname(nickname: &str) -> String {
+    match config::get().nickname_width() {
+        Option::Some(width) => format!("{:>width$}", nickname, width = width),
+        Option::None => nickname.to_string(),
+    }
+}
+
+/// Joins the prefix of a message, such as the nickname of its sender, with its
+/// text. If [`config::ClientConfig::max_width`] is set, the text is wrapped between words
+/// and continuation lines are indented to start under the text, unless the
+/// prefix is too wide.
+#[doc(hidden)]
+fn layout(prefix: &str, text: &str, suffix: &str) -> String {
+    let max_width = match config::get().max_width() {
+        Option::Some(max_width) => max_width,
+        Option::None => return format!("{}{}{}", prefix, text, suffix),
+    };
+    let indent = match visible_width(prefix) {
+        indent if indent <= max_width / 2 => indent,
+        _ => WRAP_INDENT.min(max_width / 2),
+    };
+    let mut result = prefix.to_string();
+    let mut column = visible_width(prefix);
+    for (index, line) in text.split('\n').enumerate() {
+        if index > 0 {
+            result.push('\n');
+            result.push_str(&" ".repeat(indent));
+            column = indent;
+        }
+        for (index, word) in line.split(' ').enumerate() {
+            let width = visible_width(word);
+            // Words longer than the whole line are not split:
+            if index > 0 && column + 1 + width > max_width {
+                result.push('\n');
+                result.push_str(&" ".repeat(indent));
+                column = indent;
+            } else if index > 0 {
+                result.push(' ');
+                column += 1;
+            }
+            result.push_str(word);
+            column += width;
+        }
+    }
+    result.push_str(suffix);
+    result
+}
+
+/// Returns the number of characters of given text without ANSI escape sequences.
+#[doc(hidden)]
+fn visible_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut in_escape = false;
+    for c in text.chars() {
+        match (in_escape, c) {
+            (false, '\x1b') => in_escape = true,
+            (false, _) => width += 1,
+            (true, c) if c.is_ascii_alphabetic() => in_escape = false,
+            (true, _) => {}
+        }
+    }
+    width
+}
+
+/// Formats the description of a server shown after connecting to it, including
+/// the fingerprint of its identity and its message of the day.
+pub fn server_info(info: &ServerInfo, fingerprint: &str) -> String {
+    let mut result = format!("Server: {}\nProtocol version: {}\nFingerprint: {}\nLimits: {}",
+            info.name().map(String::as_str).unwrap_or("(no name)"),
+            info.protocol_version(),
+            fingerprint,
+            info.limits()
+    );
+    if let Option::Some(motd) = info.motd() {
+        result.push('\n');
+        result.push_str(&format!("{}{}{}", BOLD, motd, RESET));
+    }
+    result
+}
+
+/// Formats the result of a clock synchronization for printing into the terminal.
+/// If the clock of the client differs too much from the clock of the server,
+/// a warning is added, since times of messages may be confusing.
+pub fn time_sync(time_sync: &TimeSync, received_at: DateTime<Utc>) -> String {
+    let offset = time_sync.clock_offset(received_at);
+    let mut result = format!("Server time: {}, round trip: {} ms, clock offset: {} ms",
+            time_sync.server_sent_at().with_timezone(&Local).to_rfc2822(),
+            time_sync.round_trip(received_at).num_milliseconds(),
+            offset.num_milliseconds()
+    );
+    if offset.abs() > Duration::seconds(MAX_CLOCK_OFFSET_SECS) {
+        result.push_str("\nWARNING: The clock of your computer differs from the clock of the server, times of messages may be confusing");
+    }
+    result
+}
+
+/// Highlights all occurrences of given query in given text using reverse video.
+/// Letters are compared case-insensitively.
+pub fn highlight(text: &str, query: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Option::Some((start, end)) = find(rest, query) {
+        result.push_str(&rest[..start]);
+        result.push_str(REVERSE);
+        result.push_str(&rest[start..end]);
+        result.push_str(REVERSE_OFF);
+        rest = &rest[end..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Returns whether given text contains given query. Letters are compared
+/// case-insensitively.
+pub fn contains(text: &str, query: &str) -> bool {
+    find(text, query).is_some()
+}
+
+/// Finds the first occurrence of given query in given text, comparing letters
+/// case-insensitively.
+///
+/// # Return value
+///
+///  -  [`Option::Some`] with byte offsets of the start and the end of the
+///     occurrence
+///  -  [`Option::None`] if the text does not contain the query or the query is
+///     empty
+#[doc(hidden)]
+fn find(text: &str, query: &str) -> Option<(usize, usize)> {
+    if query.is_empty() {
+        return Option::None
+    }
+    text.char_indices().find_map(|(start, _)| {
+        let mut chars = text[start..].char_indices();
+        for expected in query.chars() {
+            match chars.next() {
+                Option::Some((_, c)) if c.to_lowercase().eq(expected.to_lowercase()) => {}
+                _ => return Option::None,
+            }
+        }
+        let end = chars.next().map(|(offset, _)| start + offset).unwrap_or(text.len());
+        Option::Some((start, end))
+    })
+}
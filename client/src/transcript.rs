@@ -0,0 +1,383 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Local transcript of received messages. The client keeps the latest messages
+//! in memory, so they can be searched or filtered by sender without asking the
+//! server, and optionally appends them to a log file in the home directory.
+
+use crate::config;
+use crate::notify;
+use crate::render;
+
+use chrono::DateTime;
+use chrono::Local;
+use chrono::NaiveDate;
+use chrono::Utc;
+
+use mdchat_common::message::DirectMessage;
+use mdchat_common::message::Message;
+
+use once_cell::sync::Lazy;
+
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Maximum number of messages kept in memory.
+const SCROLLBACK_SIZE: usize = 1000;
+
+static STATE: Lazy<Mutex<State>> = Lazy::new(|| Mutex::new(State::new()));
+
+/// A received message kept in the transcript.
+#[derive(Clone, Debug)]
+pub enum Entry {
+    /// A message sent into the chat or into a channel.
+    Message(Message),
+    /// A direct message.
+    Direct(DirectMessage),
+}
+
+impl Entry {
+    /// Returns the nickname of the user who sent the message.
+    pub fn sender(&self) -> &str {
+        match self {
+            Self::Message(message) => message.sender(),
+            Self::Direct(message) => message.sender(),
+        }
+    }
+
+    /// Returns date and time when the message was sent.
+    pub fn date_time(&self) -> &DateTime<Utc> {
+        match self {
+            Self::Message(message) => message.date_time(),
+            Self::Direct(message) => message.date_time(),
+        }
+    }
+
+    /// Returns the content of the message.
+    pub fn text(&self) -> &str {
+        match self {
+            Self::Message(message) => message.text(),
+            Self::Direct(message) => message.text(),
+        }
+    }
+
+    /// Formats the message for printing into the terminal, see [`render`].
+    /// Occurrences of given query are highlighted.
+    pub fn render(&self, query: Option<&str>) -> String {
+        match (self, query) {
+            (Self::Message(message), Option::None) => render::message(message),
+            (Self::Message(message), Option::Some(query)) => render::highlighted_message(message, query),
+            (Self::Direct(message), Option::None) => render::direct_message(message),
+            (Self::Direct(message), Option::Some(query)) => render::highlighted_direct_message(message, query),
+        }
+    }
+
+    /// Returns where the message was sent: `#channel` for messages sent into
+    /// a channel, `main chat` or `direct messages`.
+    pub fn place(&self) -> String {
+        match self {
+            Self::Message(message) => message.channel()
+                .map(|channel| format!("#{}", channel))
+                .unwrap_or("main chat".to_string()),
+            Self::Direct(_) => "direct messages".to_string(),
+        }
+    }
+
+    /// Formats the message as a single line of the log file.
+    #[doc(hidden)]
+    fn log_line(&self) -> String {
+        let line = match self {
+            Self::Message(message) => message.to_string(),
+            Self::Direct(message) => format!("[direct] {}", message),
+        };
+        line.replace('\n', " ")
+    }
+}
+
+#[doc(hidden)]
+struct State {
+    scrollback: VecDeque<Entry>,
+    /// Number of messages ever added into the scrollback.
+    received: u64,
+    /// Log file, if the user has turned logging on.
+    log: Option<File>,
+    /// Length of the log file when the client was started. Only this part is
+    /// searched, since newer lines are in the scrollback.
+    log_start: u64,
+    /// Nickname of the only sender whose messages are printed.
+    filter: Option<String>,
+    /// Value of `received` when the filter was set. Messages received since
+    /// then, which were not printed, are unread.
+    filtered_since: u64,
+    /// Local date of the latest printed message.
+    last_day: Option<NaiveDate>,
+    /// Query of the latest search, its matches and index of the match the user
+    /// is looking at.
+    search: Option<(String, Vec<String>, usize)>,
+}
+
+impl State {
+    fn new() -> Self {
+        let log_start = log_path()
+            .and_then(|path| fs::metadata(path).ok())
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+        Self {
+            scrollback: VecDeque::new(),
+            received: 0,
+            log: Option::None,
+            log_start,
+            filter: Option::None,
+            filtered_since: 0,
+            last_day: Option::None,
+            search: Option::None,
+        }
+    }
+}
+
+/// Adds a received message into the transcript and prints it, unless messages
+/// are filtered and the message is from another sender.
+///
+/// # Parameters
+///
+///  -  `entry`: the received message
+///  -  `mention`: whether the message mentions the user
+pub fn show(entry: Entry, mention: bool) {
+    let bell = notify::alert(&entry, mention);
+    let mut state = STATE.lock().unwrap();
+    if state.filter.as_deref().is_none_or(|sender| sender == entry.sender()) {
+        let day = entry.date_time().with_timezone(&Local).date_naive();
+        if config::get().day_change() && state.last_day.is_none_or(|last_day| last_day != day) {
+            println!("{}", render::day_change(day));
+        }
+        state.last_day = Option::Some(day);
+        match bell {
+            true => println!("\x07{}", entry.render(Option::None)),
+            false => println!("{}", entry.render(Option::None)),
+        }
+    } else if mention {
+        // Mentions are not missed even if the message is hidden:
+        println!("{}You have been mentioned by {} in {}, type /filter to show all messages",
+            if bell { "\x07" } else { "" }, entry.sender(), entry.place());
+    }
+    if let Option::Some(log) = &mut state.log {
+        if let Result::Err(err) = writeln!(log, "{}", entry.log_line()) {
+            println!("WARNING: Cannot write to the log file, logging is turned off: {}", err);
+            state.log = Option::None;
+        }
+    }
+    if state.scrollback.len() == SCROLLBACK_SIZE {
+        state.scrollback.pop_front();
+    }
+    state.scrollback.push_back(entry);
+    state.received += 1;
+}
+
+/// Returns whether messages are filtered, so received messages from other senders
+/// are not printed, see [`set_filter`].
+pub fn is_filtered() -> bool {
+    STATE.lock().unwrap().filter.is_some()
+}
+
+/// Prints a divider line with given text and appends it into the log file, so
+/// it is marked where messages are missing or where older messages follow.
+pub fn mark(text: &str) {
+    let mut state = STATE.lock().unwrap();
+    println!("{}---------- {} ----------{}", render::DIM, text, render::RESET);
+    if let Option::Some(log) = &mut state.log {
+        if let Result::Err(err) = writeln!(log, "---------- {} ----------", text) {
+            println!("WARNING: Cannot write to the log file, logging is turned off: {}", err);
+            state.log = Option::None;
+        }
+    }
+}
+
+/// Turns appending received messages into the log file on or off.
+pub fn set_logging(on: bool) -> Result<(), String> {
+    let mut state = STATE.lock().unwrap();
+    if !on {
+        state.log = Option::None;
+        return Result::Ok(())
+    }
+    let path = log_path().ok_or("Cannot open the log file: HOME is not set".to_string())?;
+    let log = open_log(&path).map_err(|err| format!("Cannot open {}: {}", path.display(), err))?;
+    println!("Received messages are logged into {}", path.display());
+    state.log = Option::Some(log);
+    Result::Ok(())
+}
+
+/// Searches the log file and the messages in memory for given text, prints how
+/// many messages contain it and jumps to the newest of them. Letters are compared
+/// case-insensitively.
+pub fn search(query: &str) {
+    let mut state = STATE.lock().unwrap();
+    let mut matches = match read_log(state.log_start) {
+        Result::Ok(log) => log.lines()
+            .filter(|line| render::contains(line, query))
+            .map(|line| render::highlight(line, query))
+            .collect(),
+        Result::Err(err) => {
+            println!("WARNING: Cannot search the log file: {}", err);
+            Vec::new()
+        }
+    };
+    matches.extend(state.scrollback.iter()
+        .filter(|entry| render::contains(entry.text(), query))
+        .map(|entry| entry.render(Option::Some(query))));
+    println!("Found {} message(s) containing `{}`, use /prev and /next to jump between them", matches.len(), query);
+    if !matches.is_empty() {
+        let current = matches.len() - 1;
+        state.search = Option::Some((query.to_string(), matches, current));
+        __print_match(&state);
+    } else {
+        state.search = Option::None;
+    }
+}
+
+/// Jumps to the next, that is newer, match of the latest search.
+pub fn next_match() {
+    __jump(|current, len| (current + 1 < len).then(|| current + 1));
+}
+
+/// Jumps to the previous, that is older, match of the latest search.
+pub fn previous_match() {
+    __jump(|current, _| current.checked_sub(1));
+}
+
+/// Starts printing only messages from given sender, or all messages if
+/// [`Option::None`] is given. When a sender is given, messages from them which
+/// are in memory are printed again. When the filter is removed, messages hidden
+/// by it are printed after a divider line.
+pub fn set_filter(sender: Option<String>) {
+    let mut state = STATE.lock().unwrap();
+    match &sender {
+        Option::Some(sender) => {
+            println!("Showing only messages from {}, type /filter without a nickname to show all messages", sender);
+            state.scrollback.iter()
+                .filter(|entry| entry.sender() == sender)
+                .for_each(|entry| println!("{}", entry.render(Option::None)));
+            // Messages hidden by the previous filter stay unread:
+            if state.filter.is_none() {
+                state.filtered_since = state.received;
+            }
+        },
+        Option::None => {
+            println!("Showing messages from everyone");
+            let unread: Vec<_> = __unread(&state).collect();
+            if !unread.is_empty() {
+                println!("{}---------- {} new message(s) ----------{}", render::DIM, unread.len(), render::RESET);
+                unread.iter().for_each(|entry| println!("{}", entry.render(Option::None)));
+            }
+        },
+    }
+    state.filter = sender;
+}
+
+/// Prints how many messages hidden by the filter were received, for each place
+/// they were sent to, see [`Entry::place`].
+pub fn print_unread() {
+    let state = STATE.lock().unwrap();
+    let mut unread = BTreeMap::new();
+    __unread(&state).for_each(|entry| *unread.entry(entry.place()).or_insert(0) += 1);
+    match unread.is_empty() {
+        true => println!("There are no unread messages"),
+        false => println!("Unread messages: {}", unread.iter()
+            .map(|(place, count)| format!("{} {}", place, count))
+            .collect::<Vec<_>>()
+            .join(", ")),
+    }
+}
+
+/// Returns messages in memory, which were hidden by the current filter.
+#[doc(hidden)]
+fn __unread(state: &State) -> impl Iterator<Item = &Entry> {
+    let filter = state.filter.as_deref();
+    // Skip messages received before the filter was set:
+    let first = state.received - state.scrollback.len() as u64;
+    let skip = state.filtered_since.saturating_sub(first) as usize;
+    state.scrollback.iter()
+        .skip(skip)
+        .filter(move |entry| filter.is_some_and(|sender| sender != entry.sender()))
+}
+
+#[doc(hidden)]
+fn __jump<F>(next: F) where F: FnOnce(usize, usize) -> Option<usize> {
+    let mut state = STATE.lock().unwrap();
+    let (_, matches, current) = match &mut state.search {
+        Option::Some(search) => search,
+        Option::None => {
+            println!("There is nothing to jump to, use /search <text> first");
+            return
+        }
+    };
+    match next(*current, matches.len()) {
+        Option::Some(next) => *current = next,
+        Option::None => {
+            println!("There are no more matches");
+            return
+        }
+    }
+    __print_match(&state);
+}
+
+#[doc(hidden)]
+fn __print_match(state: &State) {
+    if let Option::Some((query, matches, current)) = &state.search {
+        println!("[{}/{} `{}`] {}", current + 1, matches.len(), query, matches[*current]);
+    }
+}
+
+/// Returns the path to the log file, or [`Option::None`] if the home directory is
+/// not known.
+#[doc(hidden)]
+fn log_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".mdchat").join("transcript.log"))
+}
+
+#[doc(hidden)]
+fn open_log(path: &PathBuf) -> io::Result<File> {
+    if let Option::Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Reads the first `len` bytes of the log file. A missing log file is same as an
+/// empty one.
+#[doc(hidden)]
+fn read_log(len: u64) -> io::Result<String> {
+    let path = match log_path() {
+        Option::Some(path) => path,
+        Option::None => return Result::Ok(String::new()),
+    };
+    let file = match File::open(path) {
+        Result::Ok(file) => file,
+        Result::Err(err) if err.kind() == io::ErrorKind::NotFound => return Result::Ok(String::new()),
+        Result::Err(err) => return Result::Err(err),
+    };
+    let mut log = String::new();
+    file.take(len).read_to_string(&mut log)?;
+    Result::Ok(log)
+}
@@ -0,0 +1,67 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! A module for transports carrying frames between a client and a server. Each
+//! frame contains a single encoded command.
+
+#[cfg(target_arch = "wasm32")]
+mod websocket;
+
+#[cfg(target_arch = "wasm32")]
+pub use self::websocket::WebSocketTransport;
+
+use mdchat_common::Error;
+
+use std::convert::TryFrom;
+use std::io::Read;
+use std::io::Write;
+
+/// A connection to a server able to send and receive whole frames.
+pub trait Transport {
+    /// Sends a single frame.
+    fn send_frame(&mut self, frame: &[u8]) -> Result<(), Error>;
+
+    /// Receives a single frame. Transports which cannot block, such as the
+    /// WebSocket transport on `wasm32` targets, return an [`Error::Transport`]
+    /// of [`std::io::ErrorKind::WouldBlock`] kind if no frame has been received
+    /// yet.
+    fn recv_frame(&mut self) -> Result<Vec<u8>, Error>;
+}
+
+/// Byte streams, such as `MdswpStream`, carry frames prefixed by their length as
+/// a big-endian 32-bit number.
+impl<S: Read + Write> Transport for S {
+    fn send_frame(&mut self, frame: &[u8]) -> Result<(), Error> {
+        // Check length:
+        let len = u32::try_from(frame.len())
+            .map_err(|_| Error::Framing("Maximum size of a single command exceeded".to_string()))?;
+        // Send frame:
+        self.write_all(&len.to_be_bytes())?;
+        self.write_all(frame)?;
+        Result::Ok(self.flush()?)
+    }
+
+    fn recv_frame(&mut self) -> Result<Vec<u8>, Error> {
+        // Load length:
+        let mut len = [0; 4];
+        self.read_exact(&mut len)?;
+        let len = u32::from_be_bytes(len) as usize;
+        // Load content:
+        let mut buf = vec![0; len];
+        self.read_exact(&mut buf)?;
+        Result::Ok(buf)
+    }
+}
@@ -0,0 +1,126 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::transport::Transport;
+
+use mdchat_common::Error;
+
+use js_sys::ArrayBuffer;
+use js_sys::Uint8Array;
+
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use wasm_bindgen::JsValue;
+
+use web_sys::BinaryType;
+use web_sys::CloseEvent;
+use web_sys::MessageEvent;
+use web_sys::WebSocket;
+
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io;
+use std::rc::Rc;
+
+/// A [`Transport`] using a browser WebSocket, for connecting to the WebSocket
+/// gateway of a server. Each binary WebSocket message carries a single frame.
+///
+/// Browsers deliver received messages asynchronously, so received frames are
+/// queued and [`Transport::recv_frame`] never blocks. Front-ends should call it
+/// from the callback given to [`WebSocketTransport::connect`].
+pub struct WebSocketTransport {
+    socket: WebSocket,
+    received: Rc<RefCell<VecDeque<Vec<u8>>>>,
+    closed: Rc<Cell<bool>>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_close: Closure<dyn FnMut(CloseEvent)>,
+}
+
+impl WebSocketTransport {
+    /// Opens a WebSocket connection to the gateway at given URL. Frames can be
+    /// sent after the connection is [open](Self::is_open).
+    ///
+    /// # Parameters
+    ///
+    ///  -  `url`: URL of the gateway, for example `wss://chat.example.com/`
+    ///  -  `on_frame`: callback called whenever a frame is received or the
+    ///     connection is closed
+    pub fn connect<F>(url: &str, on_frame: F) -> io::Result<Self> where F: FnMut() + 'static {
+        let socket = WebSocket::new(url).map_err(js_error)?;
+        socket.set_binary_type(BinaryType::Arraybuffer);
+        let on_frame = Rc::new(RefCell::new(on_frame));
+        // Queue received frames:
+        let received = Rc::new(RefCell::new(VecDeque::new()));
+        let on_message = {
+            let received = received.clone();
+            let on_frame = on_frame.clone();
+            Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+                if let Result::Ok(buffer) = event.data().dyn_into::<ArrayBuffer>() {
+                    received.borrow_mut().push_back(Uint8Array::new(&buffer).to_vec());
+                    (on_frame.borrow_mut())();
+                }
+            })
+        };
+        socket.set_onmessage(Option::Some(on_message.as_ref().unchecked_ref()));
+        // Remember that the connection is closed:
+        let closed = Rc::new(Cell::new(false));
+        let on_close = {
+            let closed = closed.clone();
+            Closure::<dyn FnMut(CloseEvent)>::new(move |_: CloseEvent| {
+                closed.set(true);
+                (on_frame.borrow_mut())();
+            })
+        };
+        socket.set_onclose(Option::Some(on_close.as_ref().unchecked_ref()));
+        Result::Ok(Self { socket, received, closed, _on_message: on_message, _on_close: on_close })
+    }
+
+    /// Returns whether the connection is open, so frames can be sent.
+    pub fn is_open(&self) -> bool {
+        self.socket.ready_state() == WebSocket::OPEN
+    }
+}
+
+impl Transport for WebSocketTransport {
+    fn send_frame(&mut self, frame: &[u8]) -> Result<(), Error> {
+        Result::Ok(self.socket.send_with_u8_array(frame).map_err(js_error)?)
+    }
+
+    fn recv_frame(&mut self) -> Result<Vec<u8>, Error> {
+        match self.received.borrow_mut().pop_front() {
+            Option::Some(frame) => Result::Ok(frame),
+            Option::None if self.closed.get() => Result::Err(Error::Transport(io::Error::new(
+                io::ErrorKind::UnexpectedEof, "The connection has been closed"))),
+            Option::None => Result::Err(Error::Transport(io::Error::new(
+                io::ErrorKind::WouldBlock, "No frame has been received yet"))),
+        }
+    }
+}
+
+impl Drop for WebSocketTransport {
+    fn drop(&mut self) {
+        self.socket.set_onmessage(Option::None);
+        self.socket.set_onclose(Option::None);
+        let _ = self.socket.close();
+    }
+}
+
+/// Converts an exception thrown by the browser into an [`io::Error`].
+#[doc(hidden)]
+fn js_error(err: JsValue) -> io::Error {
+    io::Error::other(format!("WebSocket error: {:?}", err))
+}
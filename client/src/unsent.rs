@@ -0,0 +1,82 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Lines typed by the user, which could not be sent to the server, because the
+//! connection was lost. They are saved into `~/.mdchat/unsent`, so they are not
+//! lost and the user can send them after connecting again.
+
+use std::env;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Saves lines, which could not be sent. A failure is reported to the user,
+/// since there is nothing else to do with the lines.
+pub fn save<S: AsRef<str>>(lines: &[S]) {
+    let result = match path() {
+        Option::Some(path) => append(&path, lines)
+            .map_err(|err| format!("Cannot write {}: {}", path.display(), err)),
+        Option::None => Result::Err("HOME is not set".to_string()),
+    };
+    match result {
+        Result::Ok(()) => println!("{} unsent message(s) have been saved, type /resend after connecting again to send them",
+            lines.len()),
+        Result::Err(err) => println!("WARNING: Unsent messages could not be saved: {}", err),
+    }
+}
+
+/// Returns lines saved in previous sessions.
+pub fn load() -> Vec<String> {
+    path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .map(|unsent| unsent.lines().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// Removes all saved lines.
+pub fn clear() {
+    if let Option::Some(path) = path() {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// Tells the user about lines saved in previous sessions, if there are any.
+pub fn announce() {
+    let unsent = load();
+    if !unsent.is_empty() {
+        println!("{} message(s) could not be sent last time:", unsent.len());
+        unsent.iter().for_each(|line| println!("  {}", line));
+        println!("Type /resend to send them or /discard to forget them.");
+    }
+}
+
+/// Returns the path to the file with unsent lines, or [`Option::None`] if the
+/// home directory is not known.
+#[doc(hidden)]
+fn path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".mdchat").join("unsent"))
+}
+
+#[doc(hidden)]
+fn append<S: AsRef<str>>(path: &PathBuf, lines: &[S]) -> io::Result<()> {
+    if let Option::Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    lines.iter().try_for_each(|line| writeln!(file, "{}", line.as_ref()))
+}
@@ -15,17 +15,31 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+use crate::config::HookEvent;
+use crate::hooks;
 use crate::IS_ERR;
 
+use mdchat_client::send_encoded;
+
+use mdchat_common::codec::Encoding;
+use mdchat_common::compression::Compression;
 use mdchat_common::command::c2s;
-use mdchat_common::command::s2c;
+use mdchat_common::Error;
 
 use mdswp::MdswpStream;
 
-use std::error::Error;
-use std::io;
-use std::io::Read;
-use std::io::Write;
+use serde_json::json;
+
+use std::sync::Mutex;
+use std::sync::RwLock;
+
+/// Lock held while a command is sent, since commands are sent both by the main
+/// thread and by the thread receiving commands.
+static SEND_LOCK: Mutex<()> = Mutex::new(());
+
+/// Encoding and compression of sent commands the server has chosen during the
+/// handshake.
+static FORMAT: RwLock<(Encoding, Compression)> = RwLock::new((Encoding::Json, Compression::None));
 
 /// Flushes `stdout`.
 macro_rules! flush {
@@ -61,65 +75,26 @@ macro_rules! input {
     } }
 }
 
-/// Encrypts and sends a [`c2s::Command`] using given [`MdswpStream`].
-pub fn send_command(conn: &mut MdswpStream, command: c2s::Command) -> io::Result<()> {
-    // Convert to JSON and encrypt:
-    let json = serde_json::to_string(&command).unwrap();
-    let bytes = json.into_bytes();
-    let encrypted = encrypt(bytes);
-    // Check length:
-    let len = encrypted.len();
-    if len > u32::MAX as usize {
-        return Result::Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            "Maximum size of a single command exceeded."
-        ))
-    }
-    // Send command:
-    let len = len as u32;
-    conn.write_all(&len.to_be_bytes())?;
-    conn.write_all(&encrypted)?;
-    conn.flush()?;
-    Result::Ok(())
-}
-
-/// Method for receiving single command from the server.
-pub fn recv_command(conn: &mut MdswpStream) -> io::Result<s2c::Command> {
-    // Functions used in closures:
-    fn cannot_decode<E>(err: E) -> io::Error where E: Error {
-        io::Error::new(io::ErrorKind::BrokenPipe,
-            format!("Could not decode received command: {}", err))
-    }
-    // Load length
-    let mut len = [0; 4];
-    conn.read_exact(&mut len)?;
-    let len = u32::from_be_bytes(len) as usize;
-    // Load encrypted content:
-    let mut buf = vec![0; len];
-    conn.read_exact(&mut buf)?;
-    // Decrypt and decode:
-    let decrypted = decrypt(buf);
-    let string = String::from_utf8(decrypted).map_err(cannot_decode)?;
-    let command = serde_json::from_str(&string).map_err(cannot_decode)?;
-    // Return Ok if successful:
-    Result::Ok(command)
-}
-
 /// Returns if an error occurred.
 pub fn is_err() -> bool { *IS_ERR.read().unwrap() }
 
-#[doc(hidden)]
-fn encrypt(data: Vec<u8>) -> Vec<u8> {
-    data
+/// Sends given command to the server in the encoding and compression chosen by
+/// the server, see [`set_format`]. Unlike [`send_encoded`], this function can be
+/// called from multiple threads, each with its own clone of the connection.
+pub fn send(conn: &mut MdswpStream, command: c2s::Command) -> Result<(), Error> {
+    let _send_lock = SEND_LOCK.lock().unwrap();
+    let (encoding, compression) = *FORMAT.read().unwrap();
+    send_encoded(conn, command, encoding, compression)
 }
 
-#[doc(hidden)]
-fn decrypt(data: Vec<u8>) -> Vec<u8> {
-    data
+/// Sets the encoding and compression of commands sent by [`send`], which the
+/// server has chosen during the handshake.
+pub fn set_format(encoding: Encoding, compression: Compression) {
+    *FORMAT.write().unwrap() = (encoding, compression);
 }
 
 /// To inform user about I/O error during communicating with the server.
-pub fn io_error(conn: &mut MdswpStream, err: io::Error) {
+pub fn io_error(conn: &mut MdswpStream, err: Error) {
     error(conn, format!("Cannot commuticate with server: {}", err));
 }
 
@@ -127,5 +102,6 @@ pub fn io_error(conn: &mut MdswpStream, err: io::Error) {
 pub fn error(conn: &mut MdswpStream, description: String) {
     let _ = conn.reset();
     println!("FATAL: {}\nFATAL: Press Enter to quit", description);
+    hooks::run(HookEvent::Disconnect, json!({ "reason": description }));
     *IS_ERR.write().unwrap() = true;
 }
\ No newline at end of file
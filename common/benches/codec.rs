@@ -0,0 +1,125 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Benchmarks of encoding and decoding frames in all encodings, see
+//! [`mdchat_common::codec`], and of broadcasting a message, which the server
+//! encodes once for each recipient, since recipients are notified about mentions
+//! differently.
+
+use chrono::Utc;
+
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::BenchmarkId;
+use criterion::Criterion;
+use criterion::Throughput;
+
+use mdchat_common::codec;
+use mdchat_common::codec::Encoding;
+use mdchat_common::command::s2c;
+use mdchat_common::message::HistoryEntry;
+use mdchat_common::message::Message;
+use mdchat_common::message::MessageKind;
+
+use std::hint::black_box;
+
+/// Numbers of messages in a replayed range of the message history.
+const HISTORY_LENGTHS: [usize; 3] = [1, 100, 1000];
+
+/// Numbers of recipients of a broadcast message.
+const RECIPIENT_COUNTS: [usize; 3] = [10, 100, 1000];
+
+/// Returns a typical message with given ID.
+fn message(id: u64) -> Message {
+    let text = "Hello @everybody, the build is green again. See you at the meeting tomorrow!".to_string();
+    Message::new("alice".to_string(), Option::None, MessageKind::User, Utc::now(), text, Option::None, 3)
+        .with_id(id)
+}
+
+/// Returns a command delivering a single message.
+fn message_recv(mention: bool) -> s2c::Command {
+    s2c::Command::MessageRecv { id: 1, message: message(1), mention }
+}
+
+/// Returns a command replaying given number of messages from the message
+/// history, such as after login.
+fn history_range(length: usize) -> s2c::Command {
+    let entries = (1..=length as u64).map(|id| HistoryEntry::new(id, message(id))).collect();
+    s2c::Command::HistoryRange { after_id: 0, before_id: length as u64 + 1, entries, complete: true }
+}
+
+fn encode(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("encode");
+    for encoding in codec::SUPPORTED {
+        let command = message_recv(false);
+        group.bench_function(BenchmarkId::new(format!("{:?}", encoding), "MessageRecv"), |bencher| {
+            bencher.iter(|| encoding.encode(black_box(&command)).unwrap())
+        });
+        for length in HISTORY_LENGTHS {
+            let command = history_range(length);
+            group.throughput(Throughput::Elements(length as u64));
+            group.bench_with_input(BenchmarkId::new(format!("{:?} HistoryRange", encoding), length), &command, |bencher, command| {
+                bencher.iter(|| encoding.encode(black_box(command)).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+fn decode(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("decode");
+    for encoding in codec::SUPPORTED {
+        let frame = encoding.encode(&message_recv(false)).unwrap();
+        group.bench_function(BenchmarkId::new(format!("{:?}", encoding), "MessageRecv"), |bencher| {
+            bencher.iter(|| codec::decode::<s2c::Command>(black_box(&frame)).unwrap())
+        });
+        for length in HISTORY_LENGTHS {
+            let frame = encoding.encode(&history_range(length)).unwrap();
+            group.throughput(Throughput::Elements(length as u64));
+            group.bench_with_input(BenchmarkId::new(format!("{:?} HistoryRange", encoding), length), &frame, |bencher, frame| {
+                bencher.iter(|| codec::decode::<s2c::Command>(black_box(frame)).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+fn fan_out(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("fan_out");
+    let message = message(1);
+    // Like the server does, the message is cloned into a command for each
+    // recipient and every tenth recipient is mentioned:
+    let command = |mention| s2c::Command::MessageRecv { id: 1, message: message.clone(), mention };
+    for recipient_count in RECIPIENT_COUNTS {
+        group.throughput(Throughput::Elements(recipient_count as u64));
+        group.bench_with_input(BenchmarkId::new("per recipient", recipient_count), &recipient_count, |bencher, &count| {
+            bencher.iter(|| (0..count)
+                .map(|recipient| Encoding::MessagePack.encode(&command(recipient % 10 == 0)).unwrap())
+                .collect::<Vec<_>>())
+        });
+        // Baseline for serialization shared by all recipients:
+        group.bench_with_input(BenchmarkId::new("shared", recipient_count), &recipient_count, |bencher, &count| {
+            bencher.iter(|| {
+                let frames = [false, true].map(|mention| Encoding::MessagePack.encode(&command(mention)).unwrap());
+                (0..count).map(|recipient| frames[(recipient % 10 == 0) as usize].clone()).collect::<Vec<_>>()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, encode, decode, fan_out);
+criterion_main!(benches);
@@ -0,0 +1,173 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! A module for exporting and importing user accounts, for example when migrating
+//! accounts to another server or backing them up.
+
+use crate::profile::Profile;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use std::fmt::Write;
+
+/// Name of the algorithm the server uses to hash passwords. The hash is SHA-512 of
+/// the concatenation of the secret pepper of the server, the salt and the
+/// password.
+pub const PASSWORD_ALGORITHM: &str = "sha512-pepper-salt-password";
+
+/// A structure containing a hashed password together with the parameters needed
+/// to verify it. The salt and the hash are encoded as lowercase hexadecimal
+/// strings. The pepper is not included, so hashes can be verified only by servers
+/// using the same pepper.
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct PasswordHash {
+    algorithm: String,
+    salt: String,
+    hash: String,
+}
+
+impl PasswordHash {
+    /// Creates a new [`PasswordHash`] instance.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `algorithm`: name of the algorithm used to compute the hash
+    ///  -  `salt`: salt of the password
+    ///  -  `hash`: hash of the password
+    pub fn new(algorithm: String, salt: &[u8], hash: &[u8]) -> Self {
+        Self { algorithm, salt: encode_hex(salt), hash: encode_hex(hash) }
+    }
+
+    /// Returns name of the algorithm used to compute the hash.
+    pub fn algorithm(&self) -> &String {
+        &self.algorithm
+    }
+
+    /// Returns the decoded salt of the password, or [`Result::Err`] if the salt
+    /// is not a valid hexadecimal string.
+    pub fn salt(&self) -> Result<Vec<u8>, String> {
+        decode_hex(&self.salt)
+    }
+
+    /// Returns the decoded hash of the password, or [`Result::Err`] if the hash
+    /// is not a valid hexadecimal string.
+    pub fn hash(&self) -> Result<Vec<u8>, String> {
+        decode_hex(&self.hash)
+    }
+}
+
+/// A structure containing flags of a user account.
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Serialize, Deserialize)]
+pub struct AccountFlags {
+    /// Whether the user has been approved and is allowed to send messages.
+    pub approved: bool,
+    /// Whether the e-mail address of the user has been verified.
+    pub email_verified: bool,
+    /// Whether the user hides when they were last seen.
+    pub last_seen_hidden: bool,
+}
+
+/// A structure describing a single exported user account. Runtime state, like
+/// the availability of the user or pending verification codes, is not exported.
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct AccountRecord {
+    nickname: String,
+    role: String,
+    password: Option<PasswordHash>,
+    email: Option<String>,
+    flags: AccountFlags,
+    blocked: Vec<String>,
+    profile: Profile,
+}
+
+impl AccountRecord {
+    /// Creates a new [`AccountRecord`] instance.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `nickname`: nickname of the user
+    ///  -  `role`: role of the user on the exporting server, which is only
+    ///     informational, because roles are assigned in the configuration of the
+    ///     server
+    ///  -  `password`: hashed password of the user, or [`Option::None`] if the
+    ///     user has no password set
+    ///  -  `email`: e-mail address of the user, if any
+    ///  -  `flags`: flags of the account
+    ///  -  `blocked`: nicknames of users blocked by the user
+    ///  -  `profile`: profile of the user
+    pub fn new(nickname: String, role: String, password: Option<PasswordHash>, email: Option<String>,
+               flags: AccountFlags, blocked: Vec<String>, profile: Profile) -> Self {
+        Self { nickname, role, password, email, flags, blocked, profile }
+    }
+
+    /// Returns nickname of the user.
+    pub fn nickname(&self) -> &String {
+        &self.nickname
+    }
+
+    /// Returns role of the user on the exporting server.
+    pub fn role(&self) -> &String {
+        &self.role
+    }
+
+    /// Returns hashed password of the user, if the user has a password set.
+    pub fn password(&self) -> Option<&PasswordHash> {
+        self.password.as_ref()
+    }
+
+    /// Returns e-mail address of the user, if any.
+    pub fn email(&self) -> Option<&String> {
+        self.email.as_ref()
+    }
+
+    /// Returns flags of the account.
+    pub fn flags(&self) -> AccountFlags {
+        self.flags
+    }
+
+    /// Returns nicknames of users blocked by the user.
+    pub fn blocked(&self) -> &Vec<String> {
+        &self.blocked
+    }
+
+    /// Returns profile of the user.
+    pub fn profile(&self) -> &Profile {
+        &self.profile
+    }
+}
+
+#[doc(hidden)]
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut string, byte| {
+        write!(string, "{:02x}", byte).unwrap();
+        string
+    })
+}
+
+#[doc(hidden)]
+fn decode_hex(string: &str) -> Result<Vec<u8>, String> {
+    if !string.len().is_multiple_of(2) || !string.is_ascii() {
+        return Result::Err(format!("`{}` is not a valid hexadecimal string", string))
+    }
+    (0..string.len()).step_by(2)
+        .map(|index| u8::from_str_radix(&string[index..index + 2], 16)
+            .map_err(|_| format!("`{}` is not a valid hexadecimal string", string)))
+        .collect()
+}
@@ -0,0 +1,112 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module for information about chat channels.
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use std::fmt;
+use std::str::FromStr;
+
+/// An enumeration of modes of a channel, which determine who can read and send
+/// messages in the channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub enum ChannelMode {
+    /// Everybody can read and send messages.
+    Public,
+    /// Only members of the channel can read and send messages. Users become
+    /// members when they are invited.
+    InviteOnly,
+    /// Everybody can read messages, but only members of the channel and
+    /// moderators can send them.
+    Moderated,
+}
+
+impl FromStr for ChannelMode {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "public" => Result::Ok(Self::Public),
+            "invite-only" => Result::Ok(Self::InviteOnly),
+            "moderated" => Result::Ok(Self::Moderated),
+            other => Result::Err(format!("`{}` is not a valid channel mode", other)),
+        }
+    }
+}
+
+impl fmt::Display for ChannelMode {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        fmtr.write_str(match self {
+            Self::Public => "public",
+            Self::InviteOnly => "invite-only",
+            Self::Moderated => "moderated",
+        })
+    }
+}
+
+/// A structure describing a channel of the chat.
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct ChannelInfo {
+    name: String,
+    topic: Option<String>,
+    mode: ChannelMode,
+}
+
+impl ChannelInfo {
+    /// Creates a new [`ChannelInfo`] instance.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `name`: name of the channel
+    ///  -  `topic`: topic of the channel, if any is set
+    ///  -  `mode`: mode of the channel
+    pub fn new(name: String, topic: Option<String>, mode: ChannelMode) -> Self {
+        Self { name, topic, mode }
+    }
+
+    /// Returns the name of the channel.
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    /// Returns the topic of the channel, if any is set.
+    pub fn topic(&self) -> Option<&String> {
+        self.topic.as_ref()
+    }
+
+    /// Returns the mode of the channel.
+    pub fn mode(&self) -> ChannelMode {
+        self.mode
+    }
+}
+
+impl fmt::Display for ChannelInfo {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "#{}", self.name)?;
+        if self.mode != ChannelMode::Public {
+            write!(fmtr, " ({})", self.mode)?;
+        }
+        match &self.topic {
+            Option::Some(topic) => write!(fmtr, ": {}", topic),
+            Option::None => Result::Ok(()),
+        }
+    }
+}
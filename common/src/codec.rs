@@ -0,0 +1,249 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! A module for encodings of commands on the wire. Commands are encoded as JSON
+//! by default. A client can offer binary encodings, which are more compact and
+//! faster to decode, in [`c2s::Command::Hello`]. The server chooses one of them,
+//! see [`negotiate`], and reports it in [`ServerInfo::encoding`]. Commands sent
+//! after the handshake are encoded in the chosen encoding, while the hello
+//! commands themselves are always encoded as JSON.
+//!
+//! In every encoding, commands are wrapped in an envelope containing the type of
+//! the command, the version of its schema and its contents, see [`envelope`].
+//! Receivers recognize the encoding of each frame, so [`decode`] does not need
+//! to know which encoding has been negotiated.
+//!
+//! [`c2s::Command::Hello`]: crate::command::c2s::Command::Hello
+//! [`ServerInfo::encoding`]: crate::hello::ServerInfo::encoding
+//! [`envelope`]: crate::envelope
+
+use crate::envelope;
+use crate::envelope::Versioned;
+use crate::Error;
+
+use serde::de::value::StrDeserializer;
+use serde::de::DeserializeOwned;
+use serde::de::IntoDeserializer;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+
+use serde_json::Map;
+use serde_json::Value;
+
+/// Encodings implemented by this crate in the order of preference.
+pub const SUPPORTED: [Encoding; 2] = [Encoding::MessagePack, Encoding::Json];
+
+/// Represents an encoding of commands on the wire.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub enum Encoding {
+    /// Text encoding readable by humans, which all clients and servers
+    /// understand.
+    #[default]
+    Json,
+    /// Binary encoding described at <https://msgpack.org/>.
+    MessagePack,
+}
+
+impl Encoding {
+    /// Serializes given command into a frame of this encoding, wrapped in an
+    /// envelope.
+    pub fn encode<T: Serialize + Versioned>(self, command: &T) -> Result<Vec<u8>, Error> {
+        match self {
+            Self::Json => Result::Ok(envelope::encode(command)?.into_bytes()),
+            Self::MessagePack => {
+                let (kind, payload) = envelope::split(command)?;
+                let envelope = BinaryEnvelope { kind, version: command.version(), payload };
+                Result::Ok(rmp_serde::to_vec_named(&envelope)?)
+            },
+        }
+    }
+
+    /// Returns the encoding given frame is encoded in. JSON frames start with
+    /// an object or with a string, while frames of binary encodings never start
+    /// with these characters.
+    pub fn detect(frame: &[u8]) -> Self {
+        match frame.iter().find(|byte| !byte.is_ascii_whitespace()) {
+            Option::Some(b'{') | Option::Some(b'"') => Self::Json,
+            _ => Self::MessagePack,
+        }
+    }
+}
+
+/// An envelope wrapping a single command in a binary encoding. Unlike
+/// [`Envelope`], its payload is kept decoded, since binary encodings cannot be
+/// embedded into each other without decoding them.
+///
+/// [`Envelope`]: crate::envelope::Envelope
+#[doc(hidden)]
+#[derive(Serialize, Deserialize)]
+struct BinaryEnvelope {
+    #[serde(rename = "type")]
+    kind: String,
+    version: u32,
+    payload: Value,
+}
+
+/// Decodes a command from given frame of any encoding, see [`Encoding::detect`].
+/// Commands of types unknown to the receiver are decoded as [`UnknownCommand`]
+/// and envelopes of versions not supported by the receiver are rejected, see
+/// [`Versioned::supports_version`].
+///
+/// [`UnknownCommand`]: crate::command::UnknownCommand
+pub fn decode<T: DeserializeOwned + Versioned>(frame: &[u8]) -> Result<T, Error> {
+    match Encoding::detect(frame) {
+        Encoding::Json => envelope::decode(std::str::from_utf8(frame).map_err(Error::serialization)?),
+        Encoding::MessagePack => {
+            let envelope: BinaryEnvelope = rmp_serde::from_slice(frame)?;
+            // The payload is put back into the form the command is serialized
+            // into:
+            let mut command = Map::new();
+            command.insert(envelope.kind.clone(), envelope.payload);
+            envelope::check_version(serde_json::from_value(Value::Object(command))?, &envelope.kind, envelope.version)
+        },
+    }
+}
+
+/// Returns given frame of any encoding as text suitable for logging, that is
+/// converted into JSON if it is in a binary encoding.
+pub fn to_text(frame: &[u8]) -> String {
+    match Encoding::detect(frame) {
+        Encoding::Json => String::from_utf8_lossy(frame).to_string(),
+        Encoding::MessagePack => match rmp_serde::from_slice::<Value>(frame) {
+            Result::Ok(value) => value.to_string(),
+            Result::Err(err) => format!("<invalid MessagePack: {}>", err),
+        },
+    }
+}
+
+/// Chooses the encoding of a connection from encodings offered by the client,
+/// that is the first offered encoding, or JSON if the client has offered none.
+pub fn negotiate(offered: &[Encoding]) -> Encoding {
+    offered.first().copied().unwrap_or_default()
+}
+
+/// Deserializes encodings or compressions offered by a client, skipping those
+/// unknown to this crate, so clients can offer encodings and compressions added
+/// by newer versions of the protocol.
+pub(crate) fn deserialize_offered<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    let names = Vec::<String>::deserialize(deserializer)?;
+    Result::Ok(names.iter()
+        .filter_map(|name| {
+            let name: StrDeserializer<serde::de::value::Error> = name.as_str().into_deserializer();
+            T::deserialize(name).ok()
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode;
+    use super::deserialize_offered;
+    use super::negotiate;
+    use super::to_text;
+    use super::Encoding;
+    use super::SUPPORTED;
+
+    use crate::command::c2s;
+    use crate::command::s2c;
+    use crate::message::MessageKind;
+
+    use serde::Deserialize;
+
+    #[test]
+    fn commands_survive_round_trip() {
+        for encoding in SUPPORTED {
+            let command = c2s::Command::SendMessage {
+                channel: Option::Some("lobby".to_string()),
+                kind: MessageKind::Action,
+                text: "waves at \"everybody\"".to_string(),
+                ttl: Option::Some(60),
+                client_tag: Option::None,
+            };
+            let frame = encoding.encode(&command).unwrap();
+            assert_eq!(Encoding::detect(&frame), encoding);
+            match decode(&frame).unwrap() {
+                c2s::Command::SendMessage { channel, kind, text, ttl, client_tag } => {
+                    assert_eq!(channel.as_deref(), Option::Some("lobby"));
+                    assert_eq!(kind, MessageKind::Action);
+                    assert_eq!(text, "waves at \"everybody\"");
+                    assert_eq!(ttl, Option::Some(60));
+                    assert_eq!(client_tag, Option::None);
+                },
+                command => panic!("Decoded `{}` in {:?}", command.name(), encoding),
+            }
+        }
+    }
+
+    #[test]
+    fn commands_without_contents_survive_round_trip() {
+        for encoding in SUPPORTED {
+            let frame = encoding.encode(&c2s::Command::ListChannels).unwrap();
+            assert!(matches!(decode(&frame).unwrap(), c2s::Command::ListChannels));
+        }
+    }
+
+    #[test]
+    fn unknown_commands_are_decoded_by_name() {
+        for encoding in SUPPORTED {
+            let frame = encoding.encode(&s2c::Command::MessageDeleted(42)).unwrap();
+            match decode(&frame).unwrap() {
+                c2s::Command::Unknown(unknown) => assert_eq!(unknown.name(), "MessageDeleted"),
+                command => panic!("Decoded `{}` in {:?}", command.name(), encoding),
+            }
+        }
+    }
+
+    #[test]
+    fn detect_recognizes_json_after_whitespace() {
+        assert_eq!(Encoding::detect(b"{}"), Encoding::Json);
+        assert_eq!(Encoding::detect(b" \r\n\t{}"), Encoding::Json);
+        assert_eq!(Encoding::detect(b"\"Ping\""), Encoding::Json);
+        assert_eq!(Encoding::detect(&[0x83]), Encoding::MessagePack);
+        assert_eq!(Encoding::detect(b""), Encoding::MessagePack);
+    }
+
+    #[test]
+    fn binary_frames_are_logged_as_json() {
+        let frame = Encoding::MessagePack.encode(&c2s::Command::MarkRead(7)).unwrap();
+        let text = to_text(&frame);
+        assert!(text.contains("\"MarkRead\""), "{}", text);
+        assert!(to_text(&[0xc1]).starts_with("<invalid MessagePack"));
+    }
+
+    #[test]
+    fn negotiate_chooses_first_offered_encoding() {
+        assert_eq!(negotiate(&[]), Encoding::Json);
+        assert_eq!(negotiate(&[Encoding::MessagePack, Encoding::Json]), Encoding::MessagePack);
+        assert_eq!(negotiate(&[Encoding::Json, Encoding::MessagePack]), Encoding::Json);
+    }
+
+    #[test]
+    fn unknown_offered_encodings_are_skipped() {
+        #[derive(Deserialize)]
+        struct Offer {
+            #[serde(deserialize_with = "deserialize_offered")]
+            encodings: Vec<Encoding>,
+        }
+        let offer: Offer = serde_json::from_str(r#"{"encodings": ["Cbor", "MessagePack", "Json"]}"#).unwrap();
+        assert_eq!(offer.encodings, vec![Encoding::MessagePack, Encoding::Json]);
+    }
+}
@@ -15,5 +15,81 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+//! Commands exchanged between clients and servers. Commands sent by clients are
+//! in the [`c2s`] module, commands sent by servers are in the [`s2c`] module.
+
 pub mod c2s;
-pub mod s2c;
\ No newline at end of file
+pub mod s2c;
+
+use serde::de;
+use serde::de::IgnoredAny;
+use serde::de::MapAccess;
+use serde::de::Visitor;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+
+use std::fmt;
+
+/// A command which the receiving side does not know, for example because the
+/// other side uses a newer version of the protocol. Only the name of the command
+/// is kept, its contents are ignored.
+///
+/// Both [`c2s::Command`] and [`s2c::Command`] fall back to this when a command
+/// cannot be deserialized, so receiving such a command is not an error. Note
+/// that a known command with malformed contents falls back to this too. Since
+/// the contents are not known, the command is serialized as its name only.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownCommand {
+    name: String,
+}
+
+impl UnknownCommand {
+    /// Returns the name of the command, that is the name of the enum variant it
+    /// would be deserialized into if it was known.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Serialize for UnknownCommand {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.name)
+    }
+}
+
+impl<'de> Deserialize<'de> for UnknownCommand {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(UnknownCommandVisitor)
+    }
+}
+
+/// Visitor of commands which are either a name only (for commands without
+/// contents) or a map with the name as the only key.
+#[doc(hidden)]
+struct UnknownCommandVisitor;
+
+impl<'de> Visitor<'de> for UnknownCommandVisitor {
+    type Value = UnknownCommand;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a command")
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<Self::Value, E> {
+        Result::Ok(UnknownCommand { name: name.to_string() })
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let name: String = match map.next_key()? {
+            Option::Some(name) => name,
+            Option::None => return Result::Err(de::Error::invalid_length(0, &self)),
+        };
+        map.next_value::<IgnoredAny>()?;
+        if map.next_key::<IgnoredAny>()?.is_some() {
+            return Result::Err(de::Error::invalid_length(2, &self))
+        }
+        Result::Ok(UnknownCommand { name })
+    }
+}
\ No newline at end of file
@@ -17,32 +17,631 @@
 
 //! A module for commands that can be sent to server by client.
 
+use chrono::DateTime;
+use chrono::Utc;
+
+use crate::account::AccountRecord;
+use crate::channel::ChannelMode;
+use crate::codec;
+use crate::codec::Encoding;
+use crate::compression::Compression;
+use crate::command::UnknownCommand;
+use crate::envelope::Versioned;
+use crate::hello::ClientVersion;
+use crate::log::LogLevel;
 use crate::login::LoginRequest;
+use crate::message::MessageKind;
+use crate::moderation::FilterKind;
+use crate::profile::Profile;
+use crate::user::Availability;
 
 use serde::Deserialize;
 use serde::Serialize;
 
+use std::net::SocketAddr;
+
 /// An enumeration of possible commands that a client can send to a server.
+///
+/// A connection starts with [`Hello`], followed by [`Login`]. Until the user
+/// logs in, only these commands, [`VerifyCode`] and [`TimeSync`] can be sent;
+/// [`Hello`] and [`Login`] cannot be sent again afterwards. The server responds
+/// to a command sent at a wrong moment with [`Error`] and closes the connection.
+///
+/// [`Error`]: crate::command::s2c::Command::Error
+/// [`Hello`]: Command::Hello
+/// [`Login`]: Command::Login
+/// [`TimeSync`]: Command::TimeSync
+/// [`VerifyCode`]: Command::VerifyCode
 #[derive(Clone, Debug)]
 #[derive(Serialize, Deserialize)]
 pub enum Command {
 
+    /// Command informing the server about the client application. It should be
+    /// sent before [`Login`], since the server may reject clients which are too
+    /// old or which do not report their version at all.
+    ///
+    /// `challenge` should be generated using [`new_challenge`] and is signed by
+    /// the server to prove its identity.
+    ///
+    /// If `suppress_echo` is `true`, messages sent by the client are not sent
+    /// back to it as [`MessageRecv`], which is useful for clients showing sent
+    /// messages immediately. Other clients of the same user still receive them.
+    ///
+    /// `locale` is the preferred language of the user, such as `cs-CZ`, see
+    /// [`locale_from_env`]. The server sends errors, warnings and other messages
+    /// in that language, if it can, and reports the chosen locale in [`Hello`].
+    ///
+    /// `encodings` are encodings of commands the client understands in the order
+    /// of its preference, see [`codec`]. The server reports the chosen encoding in
+    /// [`Hello`]. Clients which offer no encoding use JSON.
+    ///
+    /// `compressions` are compressions of frames the client understands in the
+    /// order of its preference, see [`compression`]. The server reports the
+    /// chosen compression in [`Hello`]. Frames of clients which offer no
+    /// compression are not compressed.
+    ///
+    /// Server should respond with [`Hello`], or with [`Error`] if the client is
+    /// not allowed to connect.
+    ///
+    /// [`Error`]: crate::command::s2c::Command::Error
+    /// [`Hello`]: crate::command::s2c::Command::Hello
+    /// [`Login`]: Command::Login
+    /// [`MessageRecv`]: crate::command::s2c::Command::MessageRecv
+    /// [`codec`]: crate::codec
+    /// [`compression`]: crate::compression
+    /// [`locale_from_env`]: crate::hello::locale_from_env
+    /// [`new_challenge`]: crate::hello::new_challenge
+    Hello {
+        client_version: ClientVersion,
+        challenge: Vec<u8>,
+        #[serde(default)] suppress_echo: bool,
+        #[serde(default)] locale: Option<String>,
+        #[serde(default, deserialize_with = "codec::deserialize_offered")] encodings: Vec<Encoding>,
+        #[serde(default, deserialize_with = "codec::deserialize_offered")] compressions: Vec<Compression>,
+    },
+
+    /// Command sent by the WebSocket gateway of the server before anything else,
+    /// telling the server the socket address of the browser the connection is
+    /// bridged for. `token` is a secret of the server process the gateway runs in,
+    /// so the server trusts only its own gateway. Clients should not send it.
+    ///
+    /// Server does not respond to this command.
+    Forwarded { peer_addr: SocketAddr, token: String },
+
     /// Command for logging in or registering.
     ///
-    /// Server should respond with [`LoginOk`] or [`Error`] message.
+    /// Server should respond with [`LoginOk`] or [`Error`] message. If the
+    /// e-mail address of the user must be verified first, server responds with
+    /// [`VerificationRequired`] instead of [`LoginOk`].
     ///
     /// [`Error`]: crate::command::s2c::Command::Error
     /// [`LoginOk`]: crate::command::s2c::Command::LoginOk
+    /// [`VerificationRequired`]: crate::command::s2c::Command::VerificationRequired
     Login (LoginRequest),
 
+    /// Command for verifying the e-mail address of the user using the code sent
+    /// by the server. Should be sent after the server responds with
+    /// [`VerificationRequired`].
+    ///
+    /// Server should respond with [`LoginSuccess`] if the code is valid, or with
+    /// [`Warning`] otherwise.
+    ///
+    /// [`LoginSuccess`]: crate::command::s2c::Command::LoginSuccess
+    /// [`VerificationRequired`]: crate::command::s2c::Command::VerificationRequired
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    VerifyCode (String),
+
     /// Command for sending a message.
     ///
+    /// If `channel` is given, the message is sent to the channel, otherwise it
+    /// is sent to the main chat. `kind` must be either [`MessageKind::User`],
+    /// [`MessageKind::Action`] or [`MessageKind::Announcement`], which only
+    /// users allowed by the server can send. If `ttl` is given, the message
+    /// expires after given number of seconds. Server can adjust the number of
+    /// seconds to fit its limits.
+    ///
+    /// `client_tag` is an arbitrary text of at most [`MAX_CLIENT_TAG_LENGTH`]
+    /// characters chosen by the client, which the server sends back in
+    /// [`MessageAccepted`], so the client can tell which of its messages has been
+    /// accepted.
+    ///
     /// Server should respond with:
     ///
     ///  -  [`Warning`] if given message is not allowed due to regulation rules
-    ///  -  [`RecvMessage`] with the same message text if given message is accepted
+    ///     or the user is not allowed to send messages of given kind to given
+    ///     channel
+    ///  -  [`MessageAccepted`] with the ID the message has been assigned, if the
+    ///     message is accepted and the client uses version 2 of the protocol or
+    ///     newer, followed by [`MessageRecv`] with the same message text, unless
+    ///     the client has suppressed the echo of its messages
+    ///
+    /// [`MAX_CLIENT_TAG_LENGTH`]: crate::message::MAX_CLIENT_TAG_LENGTH
+    /// [`MessageAccepted`]: crate::command::s2c::Command::MessageAccepted
+    /// [`MessageRecv`]: crate::command::s2c::Command::MessageRecv
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    SendMessage {
+        channel: Option<String>,
+        kind: MessageKind,
+        text: String,
+        ttl: Option<u64>,
+        #[serde(default)] client_tag: Option<String>,
+    },
+
+    /// Command for sending a direct message to a user. If the recipient is not
+    /// online, the server stores the message and delivers it when the recipient
+    /// logs in next time.
+    ///
+    /// Server should respond with [`Warning`] if given message is not allowed
+    /// due to regulation rules, the recipient does not exist or cannot store
+    /// any more messages.
+    ///
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    SendDirectMessage { recipient: String, text: String },
+
+    /// Command for deleting a message with given ID from the message history.
+    /// Users can delete only messages they have sent themselves. The server
+    /// replaces the message with a tombstone, so it is never delivered again,
+    /// and informs all clients using [`MessageDeleted`].
+    ///
+    /// Server should respond with [`Warning`] if there is no such message or
+    /// the user is not allowed to delete it.
+    ///
+    /// [`MessageDeleted`]: crate::command::s2c::Command::MessageDeleted
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    DeleteMessage (u64),
+
+    /// Command for reacting to a message with given ID using an emoji, see
+    /// [`is_valid_emoji`]. If the user has already reacted to the message using
+    /// the same emoji, the reaction is removed instead. The server informs all
+    /// clients which have received the message using [`ReactionUpdate`].
+    ///
+    /// Server should respond with [`Warning`] if there is no such message, the
+    /// emoji is not valid or the message has too many different reactions.
+    ///
+    /// [`is_valid_emoji`]: crate::message::is_valid_emoji
+    /// [`ReactionUpdate`]: crate::command::s2c::Command::ReactionUpdate
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    React { msg_id: u64, emoji: String },
+
+    /// Command for subscribing to the server log. This command is allowed only
+    /// for administrators.
+    ///
+    /// If [`Option::Some`] is given, server starts sending [`LogRecord`] commands
+    /// for each record with at least given severity. If [`Option::None`] is
+    /// given, server stops sending the records. If client is not allowed to use
+    /// this command, server responds with [`Warning`].
+    ///
+    /// [`LogRecord`]: crate::command::s2c::Command::LogRecord
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    Tail (Option<LogLevel>),
+
+    /// Command for subscribing to (`true`) or unsubscribing from (`false`) the
+    /// stream of events relevant for moderation. This command is allowed only
+    /// for moderators.
+    ///
+    /// While subscribed, client receives [`ModEvent`] for each such event. Server
+    /// should respond with [`Warning`] if the client is not allowed to subscribe.
+    ///
+    /// [`ModEvent`]: crate::command::s2c::Command::ModEvent
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    SubscribeModEvents (bool),
+
+    /// Command for listing messages held in the review queue. This command is
+    /// allowed only for moderators.
+    ///
+    /// Server should respond with [`ReviewQueue`], or with [`Warning`] if the
+    /// client is not allowed to use this command.
+    ///
+    /// [`ReviewQueue`]: crate::command::s2c::Command::ReviewQueue
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    ListReviewQueue,
+
+    /// Command for approving (`approve` is `true`) or rejecting (`approve` is
+    /// `false`) a message held in the review queue. Approved messages are
+    /// delivered as if they were just sent. This command is allowed only for
+    /// moderators.
+    ///
+    /// Server should respond with [`Warning`] if the client is not allowed to
+    /// use this command or there is no message with given ID in the queue.
     ///
-    /// [`RecvMessage`]: crate::command::s2c::Command::RecvMessage
     /// [`Warning`]: crate::command::s2c::Command::Warning
-    SendMessage (String),
-}
\ No newline at end of file
+    ReviewMessage { id: u64, approve: bool },
+
+    /// Command for approving an account registered during a raid, so the user
+    /// can send messages. This command is allowed only for moderators.
+    ///
+    /// Server should respond with [`Warning`] if the client is not allowed to
+    /// use this command or the user does not exist.
+    ///
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    ApproveUser (String),
+
+    /// Command for getting how many messages each banned pattern of the message
+    /// filter has matched. This command is allowed only for administrators.
+    ///
+    /// Server should respond with [`FilterStats`], or with [`Warning`] if the
+    /// client is not allowed to use this command.
+    ///
+    /// [`FilterStats`]: crate::command::s2c::Command::FilterStats
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    GetFilterStats,
+
+    /// Command for explaining which rule of given filter allows or rejects given
+    /// input, such as an IP address, a message text or a nickname. This command
+    /// is allowed only for administrators.
+    ///
+    /// Server should respond with [`FilterDecision`], or with [`Warning`] if the
+    /// client is not allowed to use this command or the input is not valid.
+    ///
+    /// [`FilterDecision`]: crate::command::s2c::Command::FilterDecision
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    Explain { filter: FilterKind, input: String },
+
+    /// Command for adding a rule to given filter at runtime, such as a banned
+    /// pattern or a banned IP address. The rule is written the same way as in the
+    /// configuration file without the filter name, for example `ban (?i)spam` for
+    /// the message filter. If `persist` is `true`, the rule is also appended to the
+    /// rules file of the server, so it survives restarts. This command is allowed
+    /// only for moderators.
+    ///
+    /// Server should respond with [`FilterRuleAdded`], or with [`Warning`] if the
+    /// client is not allowed to use this command or the rule is not valid or
+    /// could not be saved.
+    ///
+    /// [`FilterRuleAdded`]: crate::command::s2c::Command::FilterRuleAdded
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    AddFilterRule { filter: FilterKind, rule: String, persist: bool },
+
+    /// Command for turning the emergency lockdown on (`true`) or off (`false`).
+    /// During the lockdown, nobody can register, new connections are rejected
+    /// and users can send messages only rarely. This command is allowed only for
+    /// administrators.
+    ///
+    /// Server should respond with [`LockdownState`], or with [`Warning`] if the
+    /// client is not allowed to use this command or the state of the lockdown
+    /// could not be saved.
+    ///
+    /// [`LockdownState`]: crate::command::s2c::Command::LockdownState
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    SetLockdown (bool),
+
+    /// Command for listing all connections to the server. This command is
+    /// allowed only for administrators.
+    ///
+    /// Server should respond with [`ConnectionList`], or with [`Warning`] if the
+    /// client is not allowed to use this command.
+    ///
+    /// [`ConnectionList`]: crate::command::s2c::Command::ConnectionList
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    ListConnections,
+
+    /// Command for searching in the message history.
+    ///
+    /// If `regex` is `true`, `query` is a regular expression, otherwise it is
+    /// a substring the message text must contain. At most `limit` newest
+    /// matching messages are returned, although server can lower the limit.
+    ///
+    /// Server should respond with [`SearchResult`], or with [`Warning`] if the
+    /// client is not allowed to search or searches too often.
+    ///
+    /// [`SearchResult`]: crate::command::s2c::Command::SearchResult
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    Search { query: String, regex: bool, limit: u32 },
+
+    /// Command for exporting the message history. This command is allowed only
+    /// for administrators.
+    ///
+    /// Only messages sent in given time range are exported. If a bound is
+    /// [`Option::None`], the range is unbounded from that side.
+    ///
+    /// Server should respond with [`HistoryExport`], or with [`Warning`] if the
+    /// client is not allowed to use this command.
+    ///
+    /// [`HistoryExport`]: crate::command::s2c::Command::HistoryExport
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    ExportHistory { from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>> },
+
+    /// Command for exporting all user accounts. This command is allowed only for
+    /// administrators.
+    ///
+    /// Server should respond with [`UserExport`], or with [`Warning`] if the
+    /// client is not allowed to use this command.
+    ///
+    /// [`UserExport`]: crate::command::s2c::Command::UserExport
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    ExportUsers,
+
+    /// Command for importing user accounts, for example exported from another
+    /// server using [`ExportUsers`]. This command is allowed only for
+    /// administrators. Existing accounts are replaced only if `overwrite` is
+    /// `true`, otherwise they are skipped. Roles are not imported.
+    ///
+    /// Server should respond with [`UserImportResult`], or with [`Warning`] if the
+    /// client is not allowed to use this command.
+    ///
+    /// [`ExportUsers`]: Command::ExportUsers
+    /// [`UserImportResult`]: crate::command::s2c::Command::UserImportResult
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    ImportUsers { accounts: Vec<AccountRecord>, overwrite: bool },
+
+    /// Command for changing the topic of a channel. This command is allowed only
+    /// for moderators. Empty topic removes the topic of the channel.
+    ///
+    /// Server should respond with [`ChannelInfo`] sent to all logged in clients,
+    /// or with [`Warning`] if the client is not allowed to change the topic or
+    /// the topic is not valid.
+    ///
+    /// [`ChannelInfo`]: crate::command::s2c::Command::ChannelInfo
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    SetTopic { channel: String, topic: String },
+
+    /// Command for inviting a user into a channel. The user becomes a member of
+    /// the channel, so the user can read and send messages in an invite-only
+    /// channel, or send messages in a moderated channel. Members of a channel
+    /// can invite other users into it, other users need to be allowed to invite
+    /// by the server.
+    ///
+    /// Server should respond with [`Warning`] if the client is not allowed to
+    /// invite into the channel or the channel or the user does not exist.
+    ///
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    Invite { channel: String, nickname: String },
+
+    /// Command for joining a public channel. The client becomes a member of the
+    /// channel, so other members are told about it joining and leaving the chat
+    /// if the server sends such notices into channels. Invite-only and moderated
+    /// channels can be joined only by an invitation, see [`Invite`].
+    ///
+    /// Server should respond with [`ChannelInfo`] and a notice sent into the
+    /// channel, or with [`Warning`] if the channel is not public, the client is
+    /// already its member or the channel does not exist.
+    ///
+    /// [`Invite`]: Command::Invite
+    /// [`ChannelInfo`]: crate::command::s2c::Command::ChannelInfo
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    JoinChannel (String),
+
+    /// Command for leaving a channel. The client stops being a member of the
+    /// channel, so it can no longer read an invite-only channel or send messages
+    /// into a moderated channel. The owner of a channel cannot leave it.
+    ///
+    /// Server should respond with a notice sent into the channel, or with
+    /// [`Warning`] if the client is not a member of the channel or owns it.
+    ///
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    LeaveChannel (String),
+
+    /// Command for creating a new channel. The client becomes the owner of the
+    /// channel. Name of the channel must be allowed by the server.
+    ///
+    /// Server should respond with [`ChannelInfo`] sent to all logged in clients
+    /// which can read the channel, or with [`Warning`] if the client is not
+    /// allowed to create channels, the name is not valid or the channel already
+    /// exists.
+    ///
+    /// [`ChannelInfo`]: crate::command::s2c::Command::ChannelInfo
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    CreateChannel { name: String, mode: ChannelMode },
+
+    /// Command for deleting a channel created by a user. Only the owner of the
+    /// channel and users allowed by the server can delete it. Message history of
+    /// the channel is archived by the server.
+    ///
+    /// Server should respond with [`ChannelDeleted`] sent to all logged in
+    /// clients which could read the channel, or with [`Warning`] if the client
+    /// is not allowed to delete the channel.
+    ///
+    /// [`ChannelDeleted`]: crate::command::s2c::Command::ChannelDeleted
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    DeleteChannel (String),
+
+    /// Command for appointing or dismissing a moderator of a channel created by
+    /// a user. Only the owner of the channel and users allowed by the server can
+    /// do that. Moderators of a channel can always send messages into it, invite
+    /// users into it and change its topic.
+    ///
+    /// Server should respond with [`Warning`] if the client is not allowed to do
+    /// that or the channel or the user does not exist.
+    ///
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    SetChannelModerator { channel: String, nickname: String, moderator: bool },
+
+    /// Command for blocking a user. Messages sent by blocked users are never
+    /// delivered to the client.
+    ///
+    /// Server should respond with [`Warning`] if given user does not exist.
+    ///
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    Block (String),
+
+    /// Command for unblocking a previously blocked user.
+    Unblock (String),
+
+    /// Command for updating the profile of the user the client is logged in as.
+    ///
+    /// Fields of given [`Profile`] which are [`Option::None`] are left
+    /// unchanged, fields containing an empty string are removed from the
+    /// profile. If a field is not allowed due to regulation rules or the color
+    /// is not lower than [`COLOR_COUNT`], no field is changed and server
+    /// responds with [`Warning`].
+    ///
+    /// [`COLOR_COUNT`]: crate::message::COLOR_COUNT
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    UpdateProfile (Profile),
+
+    /// Command for getting a profile of the user with given nickname.
+    ///
+    /// Server should respond with [`ProfileInfo`], or with [`Warning`] if the
+    /// user does not exist.
+    ///
+    /// [`ProfileInfo`]: crate::command::s2c::Command::ProfileInfo
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    GetProfile (String),
+
+    /// Command for listing all channels.
+    ///
+    /// Server should respond with [`ChannelInfo`] for each channel.
+    ///
+    /// [`ChannelInfo`]: crate::command::s2c::Command::ChannelInfo
+    ListChannels,
+
+    /// Command for listing all registered users.
+    ///
+    /// Server should respond with [`UserList`].
+    ///
+    /// [`UserList`]: crate::command::s2c::Command::UserList
+    ListUsers,
+
+    /// Command for getting information about the user with given nickname,
+    /// including the time the user was last seen.
+    ///
+    /// Server should respond with [`UserInfo`], or with [`Warning`] if the user
+    /// does not exist.
+    ///
+    /// [`UserInfo`]: crate::command::s2c::Command::UserInfo
+    /// [`Warning`]: crate::command::s2c::Command::Warning
+    WhoIs (String),
+
+    /// Command for hiding (`true`) or showing (`false`) the time the user the
+    /// client is logged in as was last seen to other users.
+    HideLastSeen (bool),
+
+    /// Command for changing whether the user the client is logged in as wants to
+    /// be disturbed. When the user changes the availability from
+    /// [`Availability::DoNotDisturb`], the server delivers direct messages it
+    /// has deferred.
+    SetAvailability (Availability),
+
+    /// Command for synchronizing clocks. Contains the time the command was sent,
+    /// measured by the clock of the client.
+    ///
+    /// Server should respond with [`TimeSync`], which contains the time the
+    /// command was received and the current time of the server.
+    ///
+    /// [`TimeSync`]: crate::command::s2c::Command::TimeSync
+    TimeSync (DateTime<Utc>),
+
+    /// Answers [`Ping`], telling the server the client is still connected.
+    ///
+    /// [`Ping`]: crate::command::s2c::Command::Ping
+    Pong,
+
+    /// Acknowledges that the client has received all messages up to the one with
+    /// given ID, see [`MessageRecv`]. Messages which have not been acknowledged
+    /// are sent again the next time the user logs in, so client should send this
+    /// command after it has processed received messages. Once the user has
+    /// marked a message as read, unread messages are sent again instead, see
+    /// [`MarkRead`].
+    ///
+    /// [`MessageRecv`]: crate::command::s2c::Command::MessageRecv
+    /// [`MarkRead`]: Command::MarkRead
+    AckUpTo (u64),
+
+    /// Marks all messages up to the one with given ID as read by the user, e. g.
+    /// because they have been displayed to the user. Messages which have not been
+    /// read are sent again the next time the user logs in. Senders of the read
+    /// messages are informed using [`MessageRead`], unless the user has hidden
+    /// the time they were last seen, see [`HideLastSeen`].
+    ///
+    /// [`MessageRead`]: crate::command::s2c::Command::MessageRead
+    /// [`HideLastSeen`]: Command::HideLastSeen
+    MarkRead (u64),
+
+    /// Requests messages with ID greater than `after_id` and less than
+    /// `before_id`, which the user is allowed to read. Clients use this command
+    /// to fill a gap in their view of the chat, e. g. after a reconnect.
+    ///
+    /// Server responds with [`HistoryRange`].
+    ///
+    /// [`HistoryRange`]: crate::command::s2c::Command::HistoryRange
+    FetchRange { after_id: u64, before_id: u64 },
+
+    /// Requests at most `limit` newest messages with ID less than `before_id`,
+    /// which the user is allowed to read. If `before_id` is [`Option::None`],
+    /// the newest messages are requested. Clients use this command to load older
+    /// messages on demand, passing the ID of the oldest message they have as
+    /// `before_id`. The server may send fewer messages than requested.
+    ///
+    /// Server responds with [`HistoryChunk`].
+    ///
+    /// [`HistoryChunk`]: crate::command::s2c::Command::HistoryChunk
+    FetchHistory { before_id: Option<u64>, limit: u32 },
+
+    /// A command which is not known to the server, see [`UnknownCommand`]. It is
+    /// only received from clients using a newer version of the protocol, clients
+    /// should not send it.
+    ///
+    /// Server responds with [`Unsupported`] and keeps the connection open.
+    ///
+    /// [`Unsupported`]: crate::command::s2c::Command::Unsupported
+    #[serde(untagged)]
+    Unknown (UnknownCommand),
+}
+
+impl Command {
+    /// Returns the name of the command, which is same as its name in the
+    /// serialized form. It is useful for logging.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Hello { .. } => "Hello",
+            Self::Forwarded { .. } => "Forwarded",
+            Self::Login(..) => "Login",
+            Self::VerifyCode(..) => "VerifyCode",
+            Self::SendMessage { .. } => "SendMessage",
+            Self::SendDirectMessage { .. } => "SendDirectMessage",
+            Self::DeleteMessage(..) => "DeleteMessage",
+            Self::React { .. } => "React",
+            Self::Tail(..) => "Tail",
+            Self::SubscribeModEvents(..) => "SubscribeModEvents",
+            Self::ListReviewQueue => "ListReviewQueue",
+            Self::ReviewMessage { .. } => "ReviewMessage",
+            Self::ApproveUser(..) => "ApproveUser",
+            Self::GetFilterStats => "GetFilterStats",
+            Self::Explain { .. } => "Explain",
+            Self::AddFilterRule { .. } => "AddFilterRule",
+            Self::SetLockdown(..) => "SetLockdown",
+            Self::ListConnections => "ListConnections",
+            Self::Search { .. } => "Search",
+            Self::ExportHistory { .. } => "ExportHistory",
+            Self::ExportUsers => "ExportUsers",
+            Self::ImportUsers { .. } => "ImportUsers",
+            Self::SetTopic { .. } => "SetTopic",
+            Self::Invite { .. } => "Invite",
+            Self::JoinChannel(..) => "JoinChannel",
+            Self::LeaveChannel(..) => "LeaveChannel",
+            Self::CreateChannel { .. } => "CreateChannel",
+            Self::DeleteChannel(..) => "DeleteChannel",
+            Self::SetChannelModerator { .. } => "SetChannelModerator",
+            Self::Block(..) => "Block",
+            Self::Unblock(..) => "Unblock",
+            Self::UpdateProfile(..) => "UpdateProfile",
+            Self::GetProfile(..) => "GetProfile",
+            Self::ListChannels => "ListChannels",
+            Self::ListUsers => "ListUsers",
+            Self::WhoIs(..) => "WhoIs",
+            Self::HideLastSeen(..) => "HideLastSeen",
+            Self::SetAvailability(..) => "SetAvailability",
+            Self::TimeSync(..) => "TimeSync",
+            Self::Pong => "Pong",
+            Self::AckUpTo(..) => "AckUpTo",
+            Self::MarkRead(..) => "MarkRead",
+            Self::FetchRange { .. } => "FetchRange",
+            Self::FetchHistory { .. } => "FetchHistory",
+            Self::Unknown(unknown) => unknown.name(),
+        }
+    }
+}
+
+impl Versioned for Command {
+    fn version(&self) -> u32 {
+        // No command has changed since envelopes have been introduced:
+        1
+    }
+
+    fn supports_version(&self, version: u32) -> bool {
+        match self {
+            Self::Unknown(..) => true,
+            _ => version == self.version(),
+        }
+    }
+}
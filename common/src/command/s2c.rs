@@ -17,7 +17,29 @@
 
 //! A module for commands that can be sent by server to a client.
 
+use chrono::DateTime;
+use chrono::Utc;
+
+use crate::account::AccountRecord;
+use crate::channel::ChannelInfo;
+use crate::command::UnknownCommand;
+use crate::envelope::Versioned;
+use crate::connection::ConnectionStats;
+use crate::hello::ServerIdentity;
+use crate::hello::ServerInfo;
+use crate::log::LogRecord;
+use crate::message::DirectMessage;
+use crate::message::HistoryEntry;
 use crate::message::Message;
+use crate::message::Reaction;
+use crate::moderation::FilterDecision;
+use crate::moderation::FilterHits;
+use crate::moderation::ModEvent;
+use crate::moderation::QuarantinedMessage;
+use crate::profile::Profile;
+use crate::quota::QuotaKind;
+use crate::time::TimeSync;
+use crate::user::UserInfo;
 
 use serde::Deserialize;
 use serde::Serialize;
@@ -35,9 +57,268 @@ pub enum Command {
     /// re-established.
     Warning(String),
 
+    /// Informs client that the server does not support a command it has sent,
+    /// for example because the client uses a newer version of the protocol than
+    /// the server. Contains the name of the command. The command is ignored, but
+    /// the connection does not have to be re-established.
+    Unsupported(String),
+
+    /// Proves the identity of the server and describes it. Sent as a response to
+    /// [`c2s::Command::Hello`].
+    ///
+    /// [`c2s::Command::Hello`]: crate::command::c2s::Command::Hello
+    Hello { identity: ServerIdentity, info: ServerInfo },
+
     /// Informs about successful login attempt.
     LoginSuccess,
 
-    /// Informs client about a new message.
-    MessageRecv(Message)
-}
\ No newline at end of file
+    /// Informs client that the e-mail address of the user must be verified
+    /// before the user is logged in. A verification code has been sent to the
+    /// e-mail address and client should send it back using
+    /// [`c2s::Command::VerifyCode`].
+    ///
+    /// [`c2s::Command::VerifyCode`]: crate::command::c2s::Command::VerifyCode
+    VerificationRequired,
+
+    /// Informs client about a new message with given ID. The message carries the
+    /// same ID, see [`Message::id`], `id` is kept for clients which do not know
+    /// it. `mention` is `true` if the message mentions the user and the client
+    /// should notify the user about it. Users who do not want to be disturbed are
+    /// never notified. Client should acknowledge received messages using
+    /// [`c2s::Command::AckUpTo`].
+    ///
+    /// Messages are sent in the order of their IDs and a message is never sent
+    /// twice over the same connection, but clients should still ignore messages
+    /// whose ID is not greater than the ID of the previous one.
+    ///
+    /// [`c2s::Command::AckUpTo`]: crate::command::c2s::Command::AckUpTo
+    MessageRecv { id: u64, message: Message, mention: bool },
+
+    /// Informs client that a message it has sent using
+    /// [`c2s::Command::SendMessage`] has been accepted and assigned given ID and
+    /// timestamp. Contains the tag the client has attached to the message, if
+    /// any. Sent before the message is broadcast, so before the client receives
+    /// the message as [`MessageRecv`].
+    ///
+    /// [`c2s::Command::SendMessage`]: crate::command::c2s::Command::SendMessage
+    /// [`MessageRecv`]: Command::MessageRecv
+    MessageAccepted { client_tag: Option<String>, id: u64, timestamp: DateTime<Utc> },
+
+    /// Informs client about a direct message addressed to its user. Direct
+    /// messages sent while the user was offline are sent after successful login,
+    /// after messages from the message history.
+    DirectMessageRecv(DirectMessage),
+
+    /// Informs client that its message has been rejected, because the user has
+    /// used up a message quota. `limit` is the number of messages allowed by
+    /// the quota, `resets_at` is the date and time when the quota is reset, or
+    /// [`Option::None`] if it is reset only by connecting again.
+    QuotaExceeded { kind: QuotaKind, limit: u32, resets_at: Option<DateTime<Utc>> },
+
+    /// Informs client that a message with given ID has been deleted, for example
+    /// because the message has expired or its sender has deleted it, see
+    /// [`DeleteMessage`]. Message IDs are assigned by the server.
+    ///
+    /// [`DeleteMessage`]: crate::command::c2s::Command::DeleteMessage
+    MessageDeleted(u64),
+
+    /// Informs client about all reactions to the message with ID `msg_id`, after
+    /// they have been changed using [`c2s::Command::React`] or when the message
+    /// is delivered again, for example after a reconnect. Each reaction
+    /// aggregates all users who have used the same emoji. The list is empty if
+    /// all reactions have been removed.
+    ///
+    /// [`c2s::Command::React`]: crate::command::c2s::Command::React
+    ReactionUpdate { msg_id: u64, reactions: Vec<Reaction> },
+
+    /// Informs an administrator about a new record in the server log. Sent only
+    /// to clients subscribed using [`c2s::Command::Tail`].
+    ///
+    /// [`c2s::Command::Tail`]: crate::command::c2s::Command::Tail
+    LogRecord(LogRecord),
+
+    /// Informs a moderator about an event relevant for moderation. Sent only
+    /// to clients subscribed using [`c2s::Command::SubscribeModEvents`].
+    ///
+    /// [`c2s::Command::SubscribeModEvents`]: crate::command::c2s::Command::SubscribeModEvents
+    ModEvent(ModEvent),
+
+    /// Informs a moderator about messages held in the review queue. Sent as
+    /// a response to [`c2s::Command::ListReviewQueue`]. Messages are ordered
+    /// from the oldest to the newest one.
+    ///
+    /// [`c2s::Command::ListReviewQueue`]: crate::command::c2s::Command::ListReviewQueue
+    ReviewQueue(Vec<QuarantinedMessage>),
+
+    /// Informs an administrator how many messages each banned pattern of the
+    /// message filter has matched. Sent as a response to
+    /// [`c2s::Command::GetFilterStats`]. Patterns which have not matched any
+    /// message are included too.
+    ///
+    /// [`c2s::Command::GetFilterStats`]: crate::command::c2s::Command::GetFilterStats
+    FilterStats(Vec<FilterHits>),
+
+    /// Informs an administrator which rule of a filter allows or rejects an
+    /// input. Sent as a response to [`c2s::Command::Explain`].
+    ///
+    /// [`c2s::Command::Explain`]: crate::command::c2s::Command::Explain
+    FilterDecision(FilterDecision),
+
+    /// Informs a moderator that a filter rule has been added. `rule` is the rule
+    /// as it is written in the configuration file and `persisted` tells whether it
+    /// has been saved into the rules file. Sent as a response to
+    /// [`c2s::Command::AddFilterRule`].
+    ///
+    /// [`c2s::Command::AddFilterRule`]: crate::command::c2s::Command::AddFilterRule
+    FilterRuleAdded { rule: String, persisted: bool },
+
+    /// Informs an administrator whether the emergency lockdown is active. Sent as
+    /// a response to [`c2s::Command::SetLockdown`].
+    ///
+    /// [`c2s::Command::SetLockdown`]: crate::command::c2s::Command::SetLockdown
+    LockdownState(bool),
+
+    /// Informs an administrator about all connections to the server. Sent as
+    /// a response to [`c2s::Command::ListConnections`].
+    ///
+    /// [`c2s::Command::ListConnections`]: crate::command::c2s::Command::ListConnections
+    ConnectionList(Vec<ConnectionStats>),
+
+    /// Informs client about messages matching its search. Sent as a response to
+    /// [`c2s::Command::Search`]. Messages are ordered from the oldest to the
+    /// newest one.
+    ///
+    /// [`c2s::Command::Search`]: crate::command::c2s::Command::Search
+    SearchResult(Vec<Message>),
+
+    /// Informs an administrator about exported messages. Sent as a response to
+    /// [`c2s::Command::ExportHistory`]. Messages are ordered from the oldest to
+    /// the newest one.
+    ///
+    /// [`c2s::Command::ExportHistory`]: crate::command::c2s::Command::ExportHistory
+    HistoryExport(Vec<HistoryEntry>),
+
+    /// Sent as a response to [`c2s::Command::FetchRange`]. Contains messages
+    /// from the requested range, which the user is allowed to read, ordered
+    /// from the oldest to the newest one. `complete` is `false` if some messages
+    /// of the range are not available anymore, e. g. because they have been
+    /// removed from the message history.
+    ///
+    /// [`c2s::Command::FetchRange`]: crate::command::c2s::Command::FetchRange
+    HistoryRange { after_id: u64, before_id: u64, entries: Vec<HistoryEntry>, complete: bool },
+
+    /// Sent as a response to [`c2s::Command::FetchHistory`]. Contains a page of
+    /// messages older than `before_id`, which the user is allowed to read,
+    /// ordered from the oldest to the newest one. `more` is `true` if there are
+    /// even older messages, which can be fetched using the ID of the first
+    /// message of the page.
+    ///
+    /// [`c2s::Command::FetchHistory`]: crate::command::c2s::Command::FetchHistory
+    HistoryChunk { before_id: Option<u64>, entries: Vec<HistoryEntry>, more: bool },
+
+    /// Informs an administrator about exported user accounts. Sent as a response
+    /// to [`c2s::Command::ExportUsers`]. Accounts are ordered by nicknames.
+    ///
+    /// [`c2s::Command::ExportUsers`]: crate::command::c2s::Command::ExportUsers
+    UserExport(Vec<AccountRecord>),
+
+    /// Informs an administrator about the result of an import of user accounts.
+    /// Sent as a response to [`c2s::Command::ImportUsers`]. `imported` contains
+    /// nicknames of imported accounts, `skipped` contains nicknames of accounts
+    /// which were not imported together with the reason.
+    ///
+    /// [`c2s::Command::ImportUsers`]: crate::command::c2s::Command::ImportUsers
+    UserImportResult { imported: Vec<String>, skipped: Vec<(String, String)> },
+
+    /// Informs client about a channel. Sent for each channel after successful
+    /// login, as a response to [`c2s::Command::ListChannels`] and whenever the
+    /// topic of the channel changes.
+    ///
+    /// [`c2s::Command::ListChannels`]: crate::command::c2s::Command::ListChannels
+    ChannelInfo(ChannelInfo),
+
+    /// Informs client that a channel has been deleted. Contains the name of the
+    /// deleted channel.
+    ChannelDeleted(String),
+
+    /// Informs client about a profile of a user. Sent as a response to
+    /// [`c2s::Command::GetProfile`].
+    ///
+    /// [`c2s::Command::GetProfile`]: crate::command::c2s::Command::GetProfile
+    ProfileInfo { nickname: String, profile: Profile },
+
+    /// Informs client about all registered users. Sent as a response to
+    /// [`c2s::Command::ListUsers`].
+    ///
+    /// [`c2s::Command::ListUsers`]: crate::command::c2s::Command::ListUsers
+    UserList(Vec<UserInfo>),
+
+    /// Informs client about a user. Sent as a response to
+    /// [`c2s::Command::WhoIs`].
+    ///
+    /// [`c2s::Command::WhoIs`]: crate::command::c2s::Command::WhoIs
+    UserInfo(UserInfo),
+
+    /// Informs client that a user has read all messages up to the one with ID
+    /// `up_to`, which is the newest read message sent by the user the client is
+    /// logged in as. Sent to all clients of the sender when the reader sends
+    /// [`c2s::Command::MarkRead`].
+    ///
+    /// [`c2s::Command::MarkRead`]: crate::command::c2s::Command::MarkRead
+    MessageRead { reader: String, up_to: u64 },
+
+    /// Informs client that a user has logged in and is not logged in from any
+    /// other connection. Sent to all logged in clients, so they can keep a list
+    /// of online users up to date without sending [`c2s::Command::ListUsers`]
+    /// repeatedly.
+    ///
+    /// [`c2s::Command::ListUsers`]: crate::command::c2s::Command::ListUsers
+    UserJoined(UserInfo),
+
+    /// Informs client that a user has closed their last connection. Contains
+    /// the nickname of the user. Sent to all logged in clients, see
+    /// [`UserJoined`].
+    ///
+    /// [`UserJoined`]: Command::UserJoined
+    UserLeft(String),
+
+    /// Contains the welcome sequence, that is texts of system messages, such as
+    /// rules of the server, which are sent to a user only on their first login
+    /// after registration. Sent right after the user has logged in. The messages
+    /// are not a part of the message history and may contain markup.
+    Welcome(Vec<String>),
+
+    /// Informs client about the time of the server. Sent as a response to
+    /// [`c2s::Command::TimeSync`].
+    ///
+    /// [`c2s::Command::TimeSync`]: crate::command::c2s::Command::TimeSync
+    TimeSync(TimeSync),
+
+    /// Asks client whether it is still connected. Sent to clients which have not
+    /// sent anything for a while, if they use version 2 of the protocol or newer.
+    /// Client should answer with [`c2s::Command::Pong`] soon, otherwise it is
+    /// disconnected.
+    ///
+    /// [`c2s::Command::Pong`]: crate::command::c2s::Command::Pong
+    Ping,
+
+    /// A command which is not known to the client, see [`UnknownCommand`]. It is
+    /// only received from servers using a newer version of the protocol, servers
+    /// do not send it. Clients should ignore it.
+    #[serde(untagged)]
+    Unknown(UnknownCommand),
+}
+
+impl Versioned for Command {
+    fn version(&self) -> u32 {
+        // No command has changed since envelopes have been introduced:
+        1
+    }
+
+    fn supports_version(&self, version: u32) -> bool {
+        match self {
+            Self::Unknown(..) => true,
+            _ => version == self.version(),
+        }
+    }
+}
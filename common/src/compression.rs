@@ -0,0 +1,200 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! A module for compression of frames. A client can offer compressions in
+//! [`c2s::Command::Hello`]. The server chooses one of them, see [`negotiate`],
+//! and reports it in [`ServerInfo::compression`]. Frames sent after the
+//! handshake are compressed after commands are encoded, see [`codec`], and before
+//! they are encrypted and prefixed by their length.
+//!
+//! Compressing small frames does not pay off, so only frames of at least
+//! [`MIN_COMPRESSED_LENGTH`] bytes are compressed. Receivers recognize compressed
+//! frames by their header, so [`decompress`] does not need to know which
+//! compression has been negotiated.
+//!
+//! [`c2s::Command::Hello`]: crate::command::c2s::Command::Hello
+//! [`ServerInfo::compression`]: crate::hello::ServerInfo::compression
+//! [`codec`]: crate::codec
+
+use crate::Error;
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use std::io::Read;
+use std::io::Write;
+
+/// Compressions implemented by this crate in the order of preference.
+pub const SUPPORTED: [Compression; 1] = [Compression::Deflate];
+
+/// Minimum length of a frame in bytes which is compressed.
+pub const MIN_COMPRESSED_LENGTH: usize = 256;
+
+/// Maximum length of a decompressed frame in bytes, so a small compressed frame
+/// cannot exhaust memory of the receiver.
+pub const MAX_DECOMPRESSED_LENGTH: usize = 16 * 1024 * 1024;
+
+/// Represents a compression of frames.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub enum Compression {
+    /// Frames are not compressed.
+    #[default]
+    None,
+    /// Frames are compressed using the DEFLATE algorithm in the zlib format,
+    /// see RFC 1950.
+    Deflate,
+}
+
+impl Compression {
+    /// Compresses given encoded frame, unless it is shorter than
+    /// [`MIN_COMPRESSED_LENGTH`].
+    pub fn compress(self, frame: Vec<u8>) -> Vec<u8> {
+        match self {
+            Self::Deflate if frame.len() >= MIN_COMPRESSED_LENGTH => {
+                // Frames are compressed quickly, since the server compresses each
+                // frame for each client separately:
+                let mut encoder = ZlibEncoder::new(Vec::with_capacity(frame.len() / 2), flate2::Compression::fast());
+                // Writing into a vector cannot fail:
+                encoder.write_all(&frame).unwrap();
+                encoder.finish().unwrap()
+            },
+            _ => frame,
+        }
+    }
+}
+
+/// Returns whether given frame is compressed. A compressed frame starts with the
+/// zlib header, which cannot start a frame of any encoding.
+pub fn is_compressed(frame: &[u8]) -> bool {
+    match frame {
+        // The compression method is DEFLATE and the first two bytes are a
+        // multiple of 31:
+        [cmf, flg, ..] => cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0,
+        _ => false,
+    }
+}
+
+/// Decompresses given frame if it is compressed, see [`is_compressed`]. Frames
+/// which are not compressed are returned as they are.
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] with the decompressed frame
+///  -  [`Result::Err`] if the compressed frame is corrupted or if it is longer
+///     than [`MAX_DECOMPRESSED_LENGTH`] after decompression
+pub fn decompress(frame: Vec<u8>) -> Result<Vec<u8>, Error> {
+    if !is_compressed(&frame) {
+        return Result::Ok(frame)
+    }
+    let mut decompressed = Vec::with_capacity(frame.len() * 4);
+    ZlibDecoder::new(frame.as_slice())
+        .take(MAX_DECOMPRESSED_LENGTH as u64 + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(|err| Error::Framing(format!("Could not decompress frame: {}", err)))?;
+    if decompressed.len() > MAX_DECOMPRESSED_LENGTH {
+        return Result::Err(Error::Framing("Decompressed frame is too large".to_string()))
+    }
+    Result::Ok(decompressed)
+}
+
+/// Chooses the compression of a connection from compressions offered by the
+/// client, that is the first offered compression, or no compression if the
+/// client has offered none.
+pub fn negotiate(offered: &[Compression]) -> Compression {
+    offered.first().copied().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decompress;
+    use super::is_compressed;
+    use super::negotiate;
+    use super::Compression;
+    use super::MAX_DECOMPRESSED_LENGTH;
+    use super::MIN_COMPRESSED_LENGTH;
+
+    use crate::codec::Encoding;
+    use crate::command::c2s;
+    use crate::message::MessageKind;
+
+    use flate2::write::ZlibEncoder;
+
+    use std::io::Write;
+
+    #[test]
+    fn frames_survive_round_trip() {
+        let frame = "Hello, world! ".repeat(100).into_bytes();
+        let compressed = Compression::Deflate.compress(frame.clone());
+        assert!(is_compressed(&compressed));
+        assert!(compressed.len() < frame.len());
+        assert_eq!(decompress(compressed).unwrap(), frame);
+    }
+
+    #[test]
+    fn small_frames_are_not_compressed() {
+        let frame = vec![b'a'; MIN_COMPRESSED_LENGTH - 1];
+        assert_eq!(Compression::Deflate.compress(frame.clone()), frame);
+        let frame = vec![b'a'; MIN_COMPRESSED_LENGTH];
+        assert_eq!(Compression::None.compress(frame.clone()), frame);
+    }
+
+    #[test]
+    fn encoded_frames_are_not_recognized_as_compressed() {
+        let command = c2s::Command::SendMessage {
+            channel: Option::None,
+            kind: MessageKind::User,
+            text: "x".repeat(MIN_COMPRESSED_LENGTH),
+            ttl: Option::None,
+            client_tag: Option::None,
+        };
+        for encoding in [Encoding::Json, Encoding::MessagePack] {
+            let frame = encoding.encode(&command).unwrap();
+            assert!(!is_compressed(&frame), "{:?} frame is recognized as compressed", encoding);
+            assert_eq!(decompress(frame.clone()).unwrap(), frame);
+        }
+        assert!(!is_compressed(b" {}"));
+        assert!(!is_compressed(b"\"Ping\""));
+        assert!(!is_compressed(&[0x78]));
+        assert!(!is_compressed(&[]));
+    }
+
+    #[test]
+    fn corrupted_frames_are_rejected() {
+        let mut frame = Compression::Deflate.compress(vec![b'a'; 4096]);
+        frame.truncate(frame.len() / 2);
+        assert!(decompress(frame).is_err());
+    }
+
+    #[test]
+    fn oversized_frames_are_rejected() {
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(&vec![0; MAX_DECOMPRESSED_LENGTH + 1]).unwrap();
+        assert!(decompress(encoder.finish().unwrap()).is_err());
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(&vec![0; MAX_DECOMPRESSED_LENGTH]).unwrap();
+        assert_eq!(decompress(encoder.finish().unwrap()).unwrap().len(), MAX_DECOMPRESSED_LENGTH);
+    }
+
+    #[test]
+    fn negotiate_chooses_first_offered_compression() {
+        assert_eq!(negotiate(&[]), Compression::None);
+        assert_eq!(negotiate(&[Compression::Deflate]), Compression::Deflate);
+    }
+}
@@ -0,0 +1,143 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module for information about connections to the server.
+
+use chrono::DateTime;
+use chrono::Local;
+use chrono::Utc;
+
+use crate::hello::ClientVersion;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use std::fmt;
+use std::net::SocketAddr;
+
+/// A structure representing statistics of a single connection to the server.
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct ConnectionStats {
+    socket_addr: SocketAddr,
+    nickname: Option<String>,
+    connected_at: DateTime<Utc>,
+    last_activity: DateTime<Utc>,
+    messages_sent: u64,
+    bytes_in: u64,
+    bytes_out: u64,
+    client_version: Option<ClientVersion>,
+}
+
+impl ConnectionStats {
+    /// Creates a new [`ConnectionStats`] instance.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `socket_addr`: socket address of the client
+    ///  -  `nickname`: nickname of the user, if the client is logged in
+    ///  -  `connected_at`: date and time, when the client connected
+    ///  -  `last_activity`: date and time, when the client sent the last command
+    ///  -  `messages_sent`: number of messages sent by the client
+    ///  -  `bytes_in`: number of bytes received from the client
+    ///  -  `bytes_out`: number of bytes sent to the client
+    pub fn new(
+        socket_addr: SocketAddr,
+        nickname: Option<String>,
+        connected_at: DateTime<Utc>,
+        last_activity: DateTime<Utc>,
+        messages_sent: u64,
+        bytes_in: u64,
+        bytes_out: u64,
+    ) -> Self {
+        Self {
+            socket_addr,
+            nickname,
+            connected_at,
+            last_activity,
+            messages_sent,
+            bytes_in,
+            bytes_out,
+            client_version: Option::None,
+        }
+    }
+
+    /// Returns `self` with the client application set.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `client_version`: client application reported by the client, if the
+    ///     client has reported it
+    pub fn with_client_version(self, client_version: Option<ClientVersion>) -> Self {
+        Self { client_version, ..self }
+    }
+
+    /// Returns the socket address of the client.
+    pub fn socket_addr(&self) -> &SocketAddr {
+        &self.socket_addr
+    }
+
+    /// Returns the nickname of the user if the client is logged in.
+    pub fn nickname(&self) -> Option<&String> {
+        self.nickname.as_ref()
+    }
+
+    /// Returns date and time when the client connected.
+    pub fn connected_at(&self) -> &DateTime<Utc> {
+        &self.connected_at
+    }
+
+    /// Returns date and time when the client sent the last command.
+    pub fn last_activity(&self) -> &DateTime<Utc> {
+        &self.last_activity
+    }
+
+    /// Returns number of messages sent by the client.
+    pub fn messages_sent(&self) -> u64 {
+        self.messages_sent
+    }
+
+    /// Returns number of bytes received from the client.
+    pub fn bytes_in(&self) -> u64 {
+        self.bytes_in
+    }
+
+    /// Returns number of bytes sent to the client.
+    pub fn bytes_out(&self) -> u64 {
+        self.bytes_out
+    }
+
+    /// Returns the client application, if the client has reported it.
+    pub fn client_version(&self) -> Option<&ClientVersion> {
+        self.client_version.as_ref()
+    }
+}
+
+impl fmt::Display for ConnectionStats {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "{} ({}, {}): connected {}, last active {}, {} messages, {} B in, {} B out",
+               self.socket_addr,
+               self.nickname.as_deref().unwrap_or("not logged in"),
+               self.client_version.as_ref().map(ClientVersion::to_string).as_deref().unwrap_or("unknown client"),
+               self.connected_at.with_timezone(&Local).to_rfc2822(),
+               self.last_activity.with_timezone(&Local).to_rfc2822(),
+               self.messages_sent,
+               self.bytes_in,
+               self.bytes_out
+        )
+    }
+}
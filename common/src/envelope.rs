@@ -0,0 +1,216 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module for envelopes, which wrap commands sent over the wire since version
+//! 2 of the protocol, see [`PROTOCOL_VERSION`].
+//!
+//! An envelope contains the type of the command, that is its name, the version
+//! of the schema of the command and the contents of the command as a payload:
+//!
+//! ```text
+//! {"type":"SendMessage","version":1,"payload":{"channel":null,...}}
+//! ```
+//!
+//! Each command has its own schema version, so commands can evolve
+//! independently, see [`Versioned`]. Bridges and gateways can route commands by
+//! their type without decoding their payloads, see [`Envelope::parse`].
+//!
+//! [`PROTOCOL_VERSION`]: crate::hello::PROTOCOL_VERSION
+
+use crate::Error;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde::Serialize;
+
+use serde_json::value::RawValue;
+use serde_json::Value;
+
+/// Commands which have a version of their schema. The version of a command
+/// should be increased whenever its contents change in a way receivers using
+/// the previous version cannot decode.
+pub trait Versioned {
+    /// Returns the version of the schema of the command.
+    fn version(&self) -> u32;
+
+    /// Returns whether this command, decoded from an envelope of given version,
+    /// is understood by the receiver. By default, only the version returned by
+    /// [`Versioned::version`] is supported. Commands of types unknown to the
+    /// receiver should support any version, so they can be ignored.
+    fn supports_version(&self, version: u32) -> bool {
+        version == self.version()
+    }
+}
+
+/// An envelope wrapping a single command.
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct Envelope {
+    #[serde(rename = "type")]
+    kind: String,
+    version: u32,
+    payload: Box<RawValue>,
+}
+
+impl Envelope {
+    /// Wraps given command into a new [`Envelope`].
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] with the envelope
+    ///  -  [`Result::Err`] if the command cannot be serialized or if it is not
+    ///     an enum variant, such as [`c2s::Command`] or [`s2c::Command`]
+    ///
+    /// [`c2s::Command`]: crate::command::c2s::Command
+    /// [`s2c::Command`]: crate::command::s2c::Command
+    pub fn wrap<T: Serialize + Versioned>(command: &T) -> Result<Self, Error> {
+        let (kind, payload) = split(command)?;
+        Result::Ok(Self {
+            kind,
+            version: command.version(),
+            payload: serde_json::value::to_raw_value(&payload)?,
+        })
+    }
+
+    /// Parses an envelope from given JSON without decoding its payload.
+    pub fn parse(json: &str) -> Result<Self, Error> {
+        Result::Ok(serde_json::from_str(json)?)
+    }
+
+    /// Returns the type of the wrapped command, that is its name.
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    /// Returns the version of the schema of the wrapped command.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Returns the payload, that is the contents of the wrapped command, as JSON.
+    pub fn payload(&self) -> &str {
+        self.payload.get()
+    }
+
+    /// Decodes the wrapped command. Commands of types unknown to the receiver
+    /// are decoded as [`UnknownCommand`].
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] with the command
+    ///  -  [`Result::Err`] if the payload cannot be decoded or if the version of
+    ///     the envelope is not supported, see [`Versioned::supports_version`]
+    ///
+    /// [`UnknownCommand`]: crate::command::UnknownCommand
+    pub fn open<T: DeserializeOwned + Versioned>(&self) -> Result<T, Error> {
+        // The payload is put back into the form the command is serialized into:
+        let json = format!("{{{}:{}}}", serde_json::to_string(&self.kind)?, self.payload.get());
+        check_version(serde_json::from_str(&json)?, &self.kind, self.version)
+    }
+
+    /// Serializes the envelope into JSON.
+    pub fn to_json(&self) -> Result<String, Error> {
+        Result::Ok(serde_json::to_string(self)?)
+    }
+}
+
+/// Splits given command into its type and its payload, see [`Envelope`].
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] with the type and the payload
+///  -  [`Result::Err`] if the command cannot be serialized or if it is not an
+///     enum variant
+pub(crate) fn split<T: Serialize>(command: &T) -> Result<(String, Value), Error> {
+    // Enum variants are serialized as their name if they have no contents, or as
+    // a map with the name as the only key otherwise:
+    match serde_json::to_value(command)? {
+        Value::String(kind) => Result::Ok((kind, Value::Null)),
+        Value::Object(map) if map.len() == 1 => Result::Ok(map.into_iter().next().unwrap()),
+        _ => Result::Err(Error::Protocol("Only commands can be wrapped in an envelope".to_string())),
+    }
+}
+
+/// Returns given command decoded from an envelope of given type and version, or
+/// [`Error::Protocol`] if the version is not supported by the receiver, see
+/// [`Versioned::supports_version`].
+pub(crate) fn check_version<T: Versioned>(command: T, kind: &str, version: u32) -> Result<T, Error> {
+    match command.supports_version(version) {
+        true => Result::Ok(command),
+        false => Result::Err(Error::Protocol(format!("Unsupported version {} of `{}` command", version, kind))),
+    }
+}
+
+/// Serializes given command into JSON wrapped in an [`Envelope`].
+pub fn encode<T: Serialize + Versioned>(command: &T) -> Result<String, Error> {
+    Envelope::wrap(command)?.to_json()
+}
+
+/// Decodes a command from given JSON. Commands of peers using version 1 of the
+/// protocol are not wrapped in an [`Envelope`], so both forms are accepted.
+/// Envelopes of versions not supported by the receiver are rejected, see
+/// [`Envelope::open`].
+pub fn decode<T: DeserializeOwned + Versioned>(json: &str) -> Result<T, Error> {
+    match Envelope::parse(json) {
+        Result::Ok(envelope) => envelope.open(),
+        Result::Err(_) => Result::Ok(serde_json::from_str(json)?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode;
+    use crate::codec;
+    use crate::command::c2s;
+    use crate::Error;
+
+    use serde_json::json;
+
+    #[test]
+    fn supported_version_is_decoded() {
+        let json = r#"{"type":"MarkRead","version":1,"payload":7}"#;
+        assert!(matches!(decode(json).unwrap(), c2s::Command::MarkRead(7)));
+    }
+
+    #[test]
+    fn unsupported_version_is_rejected() {
+        for version in [0, 2] {
+            let json = format!(r#"{{"type":"MarkRead","version":{},"payload":7}}"#, version);
+            match decode::<c2s::Command>(&json) {
+                Result::Err(Error::Protocol(message)) => assert!(message.contains("MarkRead"), "{}", message),
+                _ => panic!("version {} has been accepted", version),
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_command_is_decoded_in_any_version() {
+        let json = r#"{"type":"Teleport","version":5,"payload":{"to":"moon"}}"#;
+        match decode(json).unwrap() {
+            c2s::Command::Unknown(unknown) => assert_eq!(unknown.name(), "Teleport"),
+            _ => panic!("unknown command has been decoded as a known one"),
+        }
+    }
+
+    #[test]
+    fn unsupported_version_of_binary_frame_is_rejected() {
+        let frame = rmp_serde::to_vec_named(&json!({"type": "MarkRead", "version": 2, "payload": 7})).unwrap();
+        assert!(matches!(codec::decode::<c2s::Command>(&frame), Result::Err(Error::Protocol(_))));
+        let frame = rmp_serde::to_vec_named(&json!({"type": "MarkRead", "version": 1, "payload": 7})).unwrap();
+        assert!(matches!(codec::decode(&frame).unwrap(), c2s::Command::MarkRead(7)));
+    }
+}
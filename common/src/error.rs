@@ -0,0 +1,118 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! A module for errors which can occur when a client and a server communicate.
+
+use std::io;
+
+use thiserror::Error;
+
+/// Boxed error of a library used to encode or decode commands.
+type BoxedError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Represents an error which occurred while sending or receiving a command. Each
+/// variant tells which layer of the protocol has failed, so callers can decide
+/// whether the operation is worth retrying, see [`Error::is_retryable`].
+#[derive(Error, Debug)]
+pub enum Error {
+    /// A frame could not be sent or received, because it is larger than the
+    /// protocol allows or it is incomplete.
+    #[error("Invalid frame: {0}")]
+    Framing(String),
+
+    /// A command could not be encoded or a received frame could not be decoded
+    /// into a command.
+    #[error("Could not decode received command: {0}")]
+    Serialization(#[source] BoxedError),
+
+    /// The underlying connection has failed or has been closed.
+    #[error("{0}")]
+    Transport(#[from] io::Error),
+
+    /// The other side has sent a command which is not valid at the moment, for
+    /// example a command of a client which has not said hello yet.
+    #[error("Protocol violation: {0}")]
+    Protocol(String),
+}
+
+impl Error {
+    /// Creates a new [`Error::Serialization`] instance from an error of the
+    /// library used to encode or decode commands.
+    pub fn serialization<E>(err: E) -> Self
+    where
+        E: Into<BoxedError>
+    {
+        Self::Serialization(err.into())
+    }
+
+    /// Returns whether the operation may succeed if it is retried, possibly over
+    /// a new connection. Only failures of the underlying connection are worth
+    /// retrying; invalid frames, commands which cannot be decoded and protocol
+    /// violations mean that the other side does not understand the protocol, so
+    /// they are fatal.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Transport(err) => !matches!(err.kind(),
+                io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData | io::ErrorKind::Unsupported),
+            Self::Framing(_) | Self::Serialization(_) | Self::Protocol(_) => false,
+        }
+    }
+
+    /// Returns whether the error is fatal, that is it is not worth retrying the
+    /// operation, see [`Error::is_retryable`].
+    pub fn is_fatal(&self) -> bool {
+        !self.is_retryable()
+    }
+
+    /// Returns the kind of the underlying I/O error, if the error is an
+    /// [`Error::Transport`].
+    pub fn io_kind(&self) -> Option<io::ErrorKind> {
+        match self {
+            Self::Transport(err) => Option::Some(err.kind()),
+            _ => Option::None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Self::serialization(err)
+    }
+}
+
+impl From<rmp_serde::encode::Error> for Error {
+    fn from(err: rmp_serde::encode::Error) -> Self {
+        Self::serialization(err)
+    }
+}
+
+impl From<rmp_serde::decode::Error> for Error {
+    fn from(err: rmp_serde::decode::Error) -> Self {
+        Self::serialization(err)
+    }
+}
+
+/// Errors are converted into [`io::Error`] for functions which report other I/O
+/// errors, too. Errors other than [`Error::Transport`] are of
+/// [`io::ErrorKind::InvalidData`] kind.
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Transport(err) => err,
+            err => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
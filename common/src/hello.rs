@@ -0,0 +1,378 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module for information exchanged when a client connects to the server.
+
+use crate::codec::Encoding;
+use crate::compression::Compression;
+
+use ed25519_dalek::Signature;
+use ed25519_dalek::Signer;
+use ed25519_dalek::SigningKey;
+use ed25519_dalek::Verifier;
+use ed25519_dalek::VerifyingKey;
+
+use rand::RngCore;
+use rand::rngs::OsRng;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use sha2::Digest;
+use sha2::Sha256;
+
+use std::convert::TryFrom;
+use std::env;
+use std::fmt;
+use std::fmt::Write;
+
+/// Version of the protocol implemented by this crate. It is increased whenever
+/// commands change in a way older clients or servers cannot understand.
+///
+/// Since version 2, commands are wrapped in envelopes, see [`envelope`].
+///
+/// [`envelope`]: crate::envelope
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Length of a secret identity key of a server in bytes.
+pub const IDENTITY_KEY_LENGTH: usize = 32;
+
+/// Length of a challenge sent by client in bytes.
+pub const CHALLENGE_LENGTH: usize = 32;
+
+/// Returns the locale of the user given by `LC_ALL`, `LC_MESSAGES` or `LANG`
+/// environment variables, in this order, in the form a client sends to the
+/// server in [`c2s::Command::Hello`], such as `cs-CZ`. Returns [`Option::None`]
+/// if no locale is set, or if it is the `C` or `POSIX` locale.
+///
+/// [`c2s::Command::Hello`]: crate::command::c2s::Command::Hello
+pub fn locale_from_env() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"].iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+        // Encoding and modifier, such as `.UTF-8` or `@euro`, are not a part of
+        // the locale:
+        .map(|value| value.split(['.', '@']).next().unwrap().replace('_', "-"))
+        .filter(|locale| !locale.is_empty() && locale != "C" && locale != "POSIX")
+}
+
+/// Prefix of the signed data, so the identity key of the server cannot be used
+/// to sign anything else than a challenge of a client.
+const SIGNATURE_CONTEXT: &[u8] = b"mdchat server identity\0";
+
+/// A structure describing the client application used to connect to the server.
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct ClientVersion {
+    name: String,
+    version: String,
+    #[serde(default)]
+    protocol_version: u32,
+}
+
+impl ClientVersion {
+    /// Creates a new [`ClientVersion`] instance.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `name`: name of the client application
+    ///  -  `version`: version of the client application, for example `0.2.0`
+    ///
+    /// The client is considered to implement [`PROTOCOL_VERSION`].
+    pub fn new(name: String, version: String) -> Self {
+        Self { name, version, protocol_version: PROTOCOL_VERSION }
+    }
+
+    /// Returns the name of the client application.
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    /// Returns the version of the client application.
+    pub fn version(&self) -> &String {
+        &self.version
+    }
+
+    /// Returns the version of the protocol implemented by the client. Clients
+    /// which do not report it return 0.
+    pub fn protocol_version(&self) -> u32 {
+        self.protocol_version
+    }
+}
+
+impl fmt::Display for ClientVersion {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "{} {}", self.name, self.version)
+    }
+}
+
+/// Generates a new random challenge which client sends to server to be signed by
+/// the identity key of the server.
+pub fn new_challenge() -> Vec<u8> {
+    let mut challenge = vec![0; CHALLENGE_LENGTH];
+    OsRng.fill_bytes(&mut challenge);
+    challenge
+}
+
+/// Generates a new random secret identity key of a server.
+pub fn new_identity_key() -> [u8; IDENTITY_KEY_LENGTH] {
+    SigningKey::generate(&mut OsRng).to_bytes()
+}
+
+/// Returns the fingerprint of the public part of given secret identity key of
+/// a server. See [`ServerIdentity::fingerprint`] for more information.
+pub fn identity_fingerprint(identity_key: &[u8; IDENTITY_KEY_LENGTH]) -> String {
+    fingerprint(SigningKey::from_bytes(identity_key).verifying_key().as_bytes())
+}
+
+#[doc(hidden)]
+fn fingerprint(public_key: &[u8]) -> String {
+    Sha256::digest(public_key).iter().fold(String::new(), |mut fingerprint, byte| {
+        write!(fingerprint, "{:02x}", byte).unwrap();
+        fingerprint
+    })
+}
+
+/// A structure by which a server proves its identity. It contains the public part
+/// of the long-term identity key of the server and the signature of a challenge
+/// sent by client.
+///
+/// Client should remember the [fingerprint] of the server when connecting for
+/// the first time and check it on subsequent connections. If the fingerprint
+/// changes, someone may be impersonating the server.
+///
+/// The signature covers only the challenge, it is not bound to the connection it
+/// is sent over. It detects a server which does not own the identity key, but
+/// not a man in the middle who relays the challenge to the real server and its
+/// signature back to the client.
+///
+/// [fingerprint]: ServerIdentity::fingerprint
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct ServerIdentity {
+    public_key: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+impl ServerIdentity {
+    /// Creates a new [`ServerIdentity`] instance by signing given challenge.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `identity_key`: secret identity key of the server, see
+    ///     [`new_identity_key`]
+    ///  -  `challenge`: challenge sent by client
+    pub fn sign(identity_key: &[u8; IDENTITY_KEY_LENGTH], challenge: &[u8]) -> Self {
+        let signing_key = SigningKey::from_bytes(identity_key);
+        let signature = signing_key.sign(&[SIGNATURE_CONTEXT, challenge].concat());
+        Self {
+            public_key: signing_key.verifying_key().to_bytes().to_vec(),
+            signature: signature.to_bytes().to_vec(),
+        }
+    }
+
+    /// Returns the public identity key of the server.
+    pub fn public_key(&self) -> &Vec<u8> {
+        &self.public_key
+    }
+
+    /// Returns whether the signature of given challenge is valid, that is whether
+    /// the server owns the secret part of the identity key.
+    pub fn verify(&self, challenge: &[u8]) -> bool {
+        let verifying_key = match VerifyingKey::try_from(self.public_key.as_slice()) {
+            Result::Ok(verifying_key) => verifying_key,
+            Result::Err(_) => return false,
+        };
+        let signature = match Signature::from_slice(&self.signature) {
+            Result::Ok(signature) => signature,
+            Result::Err(_) => return false,
+        };
+        verifying_key.verify(&[SIGNATURE_CONTEXT, challenge].concat(), &signature).is_ok()
+    }
+
+    /// Returns the fingerprint of the public identity key of the server, that is
+    /// the SHA-256 hash of the key written in hexadecimal.
+    pub fn fingerprint(&self) -> String {
+        fingerprint(&self.public_key)
+    }
+}
+
+/// A structure describing the server, which is sent to a client together with
+/// [`ServerIdentity`], so the client can show it to the user before logging in.
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct ServerInfo {
+    protocol_version: u32,
+    name: Option<String>,
+    motd: Option<String>,
+    limits: ServerLimits,
+    #[serde(default)]
+    locale: Option<String>,
+    #[serde(default)]
+    encoding: Encoding,
+    #[serde(default)]
+    compression: Compression,
+}
+
+impl ServerInfo {
+    /// Creates a new [`ServerInfo`] instance.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `protocol_version`: version of the protocol used for the connection,
+    ///     that is the lower of versions implemented by the client and the
+    ///     server
+    ///  -  `name`: name of the server, if any
+    ///  -  `motd`: message of the day, if any
+    ///  -  `limits`: limits the client should respect
+    pub fn new(protocol_version: u32, name: Option<String>, motd: Option<String>, limits: ServerLimits) -> Self {
+        Self { protocol_version, name, motd, limits, locale: Option::None, encoding: Encoding::Json,
+               compression: Compression::None }
+    }
+
+    /// Returns `self` with given encoding, see [`ServerInfo::encoding`].
+    ///
+    /// # Parameters
+    ///
+    ///  -  `encoding`: the encoding of commands sent after the handshake
+    pub fn with_encoding(self, encoding: Encoding) -> Self {
+        Self { encoding, ..self }
+    }
+
+    /// Returns `self` with given compression, see [`ServerInfo::compression`].
+    ///
+    /// # Parameters
+    ///
+    ///  -  `compression`: the compression of frames sent after the handshake
+    pub fn with_compression(self, compression: Compression) -> Self {
+        Self { compression, ..self }
+    }
+
+    /// Returns `self` with given locale, see [`ServerInfo::locale`].
+    ///
+    /// # Parameters
+    ///
+    ///  -  `locale`: the locale of messages sent by the server
+    pub fn with_locale(self, locale: String) -> Self {
+        Self { locale: Option::Some(locale), ..self }
+    }
+
+    /// Returns the version of the protocol used for the connection.
+    pub fn protocol_version(&self) -> u32 {
+        self.protocol_version
+    }
+
+    /// Returns the name of the server, if any.
+    pub fn name(&self) -> Option<&String> {
+        self.name.as_ref()
+    }
+
+    /// Returns the message of the day, if any. It may contain multiple lines.
+    pub fn motd(&self) -> Option<&String> {
+        self.motd.as_ref()
+    }
+
+    /// Returns limits the client should respect.
+    pub fn limits(&self) -> &ServerLimits {
+        &self.limits
+    }
+
+    /// Returns the locale of errors, warnings and other messages the server
+    /// sends to the client, which the server has chosen based on the locale
+    /// the client asked for. Older servers do not report it.
+    pub fn locale(&self) -> Option<&String> {
+        self.locale.as_ref()
+    }
+
+    /// Returns the encoding the server has chosen from encodings offered by the
+    /// client, see [`codec`](crate::codec). Both sides encode commands sent after
+    /// the handshake in this encoding. Older servers do not report it, so they
+    /// use JSON.
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Returns the compression the server has chosen from compressions offered
+    /// by the client, see [`compression`](crate::compression). Both sides
+    /// compress frames sent after the handshake this way. Older servers do not
+    /// report it, so they do not compress frames.
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+}
+
+/// Limits advertised by the server. Messages violating them are rejected, so
+/// a client may check them before sending a message.
+#[derive(Clone, Copy, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct ServerLimits {
+    max_message_length: u16,
+    max_nickname_length: u8,
+    messages_per_day: Option<u32>,
+    messages_per_session: Option<u32>,
+}
+
+impl ServerLimits {
+    /// Creates a new [`ServerLimits`] instance.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `max_message_length`: maximum length of a message in characters
+    ///  -  `max_nickname_length`: maximum length of a nickname in characters
+    ///  -  `messages_per_day`: how many messages a user can send per day, if
+    ///     limited
+    ///  -  `messages_per_session`: how many messages a user can send per
+    ///     session, if limited
+    pub fn new(max_message_length: u16, max_nickname_length: u8, messages_per_day: Option<u32>,
+               messages_per_session: Option<u32>) -> Self {
+        Self { max_message_length, max_nickname_length, messages_per_day, messages_per_session }
+    }
+
+    /// Returns the maximum length of a message in characters.
+    pub fn max_message_length(&self) -> u16 {
+        self.max_message_length
+    }
+
+    /// Returns the maximum length of a nickname in characters.
+    pub fn max_nickname_length(&self) -> u8 {
+        self.max_nickname_length
+    }
+
+    /// Returns how many messages a user can send per day, if limited.
+    pub fn messages_per_day(&self) -> Option<u32> {
+        self.messages_per_day
+    }
+
+    /// Returns how many messages a user can send per session, if limited.
+    pub fn messages_per_session(&self) -> Option<u32> {
+        self.messages_per_session
+    }
+}
+
+impl fmt::Display for ServerLimits {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "messages up to {} characters, nicknames up to {} characters",
+            self.max_message_length, self.max_nickname_length)?;
+        if let Option::Some(limit) = self.messages_per_day {
+            write!(fmtr, ", {} messages per day", limit)?;
+        }
+        if let Option::Some(limit) = self.messages_per_session {
+            write!(fmtr, ", {} messages per session", limit)?;
+        }
+        Result::Ok(())
+    }
+}
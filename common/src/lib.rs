@@ -15,6 +15,24 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+pub mod account;
+pub mod channel;
+pub mod codec;
 pub mod command;
+pub mod compression;
+pub mod connection;
+pub mod envelope;
+pub mod error;
+pub mod hello;
+pub mod log;
 pub mod login;
-pub mod message;
\ No newline at end of file
+pub mod markup;
+pub mod message;
+pub mod moderation;
+pub mod profile;
+pub mod quota;
+pub mod sanitize;
+pub mod time;
+pub mod user;
+
+pub use crate::error::Error;
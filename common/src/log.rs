@@ -0,0 +1,104 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module for log records which server can send to its administrators.
+
+use chrono::DateTime;
+use chrono::Local;
+use chrono::Utc;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use std::fmt;
+
+/// Represents severity of a log record. Levels are ordered by their severity, so
+/// [`LogLevel::Debug`] is the lowest and [`LogLevel::Fatal`] is the highest one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Serialize, Deserialize)]
+pub enum LogLevel {
+    /// Information useful only for debugging.
+    Debug,
+    /// Information about normal operation of the server.
+    Info,
+    /// Something unusual, which does not affect the server as a whole.
+    Warning,
+    /// An error which the server can recover from.
+    Error,
+    /// An error which the server cannot recover from.
+    Fatal,
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Debug => "DEBUG",
+            Self::Info => "INFO",
+            Self::Warning => "WARNING",
+            Self::Error => "ERROR",
+            Self::Fatal => "FATAL",
+        };
+        fmtr.write_str(name)
+    }
+}
+
+/// A structure representing a single record of the server log.
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct LogRecord {
+    level: LogLevel,
+    date_time: DateTime<Utc>,
+    text: String,
+}
+
+impl LogRecord {
+    /// Creates a new [`LogRecord`] instance.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `level`: severity of the record
+    ///  -  `date_time`: date and time, when the record was logged
+    ///  -  `text`: content of the record
+    pub fn new(level: LogLevel, date_time: DateTime<Utc>, text: String) -> Self {
+        Self { level, date_time, text }
+    }
+
+    /// Returns the severity of the record.
+    pub fn level(&self) -> LogLevel {
+        self.level
+    }
+
+    /// Returns date and time when the record was logged.
+    pub fn date_time(&self) -> &DateTime<Utc> {
+        &self.date_time
+    }
+
+    /// Returns the content of the record.
+    pub fn text(&self) -> &String {
+        &self.text
+    }
+}
+
+impl fmt::Display for LogRecord {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "[{}] {}: {}",
+               self.date_time.with_timezone(&Local).to_rfc2822(),
+               self.level,
+               self.text
+        )
+    }
+}
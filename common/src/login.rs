@@ -25,7 +25,10 @@ use serde::Serialize;
 pub struct LoginRequest {
     is_registering: bool,
     nickname: String,
-    password: String
+    password: String,
+    email: Option<String>,
+    #[serde(default)]
+    read_only: bool,
 }
 
 impl LoginRequest {
@@ -39,7 +42,7 @@ impl LoginRequest {
     ///  -  `nickname`: nickname of the user to log into/register
     ///  -  `password`: password of the user to log into/register
     pub fn new(is_registering: bool, nickname: String, password: String) -> Self {
-        Self { is_registering, nickname, password }
+        Self { is_registering, nickname, password, email: Option::None, read_only: false }
     }
 
     /// Creates a new [`LoginRequest`] instance for a client which *does not* want
@@ -53,7 +56,9 @@ impl LoginRequest {
         Self {
             is_registering: false,
             nickname,
-            password
+            password,
+            email: Option::None,
+            read_only: false,
         }
     }
 
@@ -68,10 +73,35 @@ impl LoginRequest {
         Self {
             is_registering: true,
             nickname,
-            password
+            password,
+            email: Option::None,
+            read_only: false,
         }
     }
 
+    /// Returns `self` with the e-mail address of the user set. The e-mail address
+    /// is used only when registering and only if the server verifies e-mail
+    /// addresses of new users.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `email`: e-mail address of the user, if any
+    pub fn with_email(self, email: Option<String>) -> Self {
+        Self { email, ..self }
+    }
+
+    /// Returns `self` with a read-only session requested. A read-only session
+    /// receives messages and the message history, but cannot send messages, which
+    /// is useful for dashboards or logging bots. Servers may not allow read-only
+    /// sessions.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `read_only`: whether the session should be read-only
+    pub fn with_read_only(self, read_only: bool) -> Self {
+        Self { read_only, ..self }
+    }
+
     /// Returns whether the client wants to create a new user.
     pub fn is_registering(&self) -> bool {
         self.is_registering
@@ -88,4 +118,14 @@ impl LoginRequest {
     pub fn password(&self) -> &String {
         &self.password
     }
+
+    /// Returns the e-mail address of the user to register, if given.
+    pub fn email(&self) -> Option<&String> {
+        self.email.as_ref()
+    }
+
+    /// Returns whether the client wants a read-only session.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
 }
\ No newline at end of file
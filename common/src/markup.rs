@@ -0,0 +1,322 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module for the small subset of Markdown, which can be used to format texts
+//! of messages:
+//!
+//!  -  `**bold**`
+//!  -  `*italics*`
+//!  -  `` `code` ``
+//!  -  `[text](https://example.com)`, where the URL must start with `http://`,
+//!     `https://` or `mailto:`
+//!
+//! Formatting cannot be nested. A backslash makes the following `*`, `` ` ``,
+//! `[`, `]` or `\` an ordinary character. Markup which cannot be parsed, such as
+//! an unclosed `**` or a link with an invalid URL, is an ordinary text.
+//!
+//! The server normalizes texts of messages, see [`normalize`], so all clients
+//! using [`parse`] see the same formatting.
+
+use std::mem;
+
+/// A part of a text with the same formatting.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Span {
+    /// An ordinary text.
+    Text(String),
+    /// A bold text.
+    Bold(String),
+    /// A text in italics.
+    Italic(String),
+    /// A code, which is usually displayed using a monospace font.
+    Code(String),
+    /// A link with a text, which is displayed instead of the URL.
+    Link { text: String, url: String },
+}
+
+/// Characters, which are escaped by a backslash in an ordinary text.
+const SPECIAL: [char; 3] = ['*', '`', '['];
+
+/// Schemes of URLs, which links can point to.
+const URL_SCHEMES: [&str; 3] = ["http://", "https://", "mailto:"];
+
+/// Splits given text into [`Span`]s. Adjacent ordinary texts are merged into
+/// a single [`Span::Text`] and no [`Span`] is empty. This function never fails,
+/// markup which cannot be parsed is an ordinary text.
+pub fn parse(text: &str) -> Vec<Span> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut spans = Vec::new();
+    let mut plain = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let parsed = match chars[i] {
+            '\\' if i + 1 < chars.len() && __is_escapable(chars[i + 1]) => {
+                plain.push(chars[i + 1]);
+                i += 2;
+                continue
+            },
+            '*' if chars.get(i + 1) == Option::Some(&'*') => __parse_delimited(&chars, i + 2, "**")
+                .map(|(content, end)| (Span::Bold(content), end)),
+            '*' => __parse_delimited(&chars, i + 1, "*")
+                .map(|(content, end)| (Span::Italic(content), end)),
+            '`' => __parse_code(&chars, i + 1),
+            '[' => __parse_link(&chars, i + 1),
+            _ => Option::None,
+        };
+        match parsed {
+            Option::Some((span, end)) => {
+                if !plain.is_empty() {
+                    spans.push(Span::Text(mem::take(&mut plain)));
+                }
+                spans.push(span);
+                i = end;
+            },
+            // An unclosed `**` is ordinary text as a whole, so it does not start
+            // italics:
+            Option::None if chars[i] == '*' && chars.get(i + 1) == Option::Some(&'*') => {
+                plain.push_str("**");
+                i += 2;
+            },
+            Option::None => {
+                plain.push(chars[i]);
+                i += 1;
+            },
+        }
+    }
+    if !plain.is_empty() {
+        spans.push(Span::Text(plain));
+    }
+    spans
+}
+
+/// Returns given text in the normal form, in which the server stores and
+/// broadcasts it. [`parse`] returns the same [`Span`]s for both texts, but the
+/// normal form uses only one way of writing each [`Span`] and escapes special
+/// characters of ordinary text, so clients with a less careful parser cannot be
+/// confused.
+pub fn normalize(text: &str) -> String {
+    to_markup(&parse(text))
+}
+
+/// Converts given [`Span`]s back into a text with markup. The result is in the
+/// normal form, see [`normalize`].
+pub fn to_markup(spans: &[Span]) -> String {
+    let mut result = String::new();
+    for (index, span) in spans.iter().enumerate() {
+        let followed = index + 1 < spans.len();
+        match span {
+            Span::Text(text) => __escape(text, &SPECIAL, followed, &mut result),
+            Span::Bold(text) => {
+                result.push_str("**");
+                __escape(text, &['*'], true, &mut result);
+                result.push_str("**");
+            },
+            Span::Italic(text) => {
+                result.push('*');
+                __escape(text, &['*'], true, &mut result);
+                result.push('*');
+            },
+            Span::Code(text) => {
+                result.push('`');
+                result.push_str(text);
+                result.push('`');
+            },
+            Span::Link { text, url } => {
+                result.push('[');
+                __escape(text, &[']'], true, &mut result);
+                result.push_str("](");
+                result.push_str(url);
+                result.push(')');
+            },
+        }
+    }
+    result
+}
+
+/// Returns given text without markup, e. g. for displays which cannot format
+/// texts. Links are replaced by their text followed by the URL in angle
+/// brackets.
+pub fn to_plain(text: &str) -> String {
+    parse(text).into_iter()
+        .map(|span| match span {
+            Span::Text(text) | Span::Bold(text) | Span::Italic(text) | Span::Code(text) => text,
+            Span::Link { text, url } => format!("{} <{}>", text, url),
+        })
+        .collect()
+}
+
+/// Returns whether given URL can be a target of a link: it must start with one
+/// of allowed schemes, must not be empty otherwise and must not contain
+/// whitespace, control characters or parentheses.
+pub fn is_valid_url(url: &str) -> bool {
+    URL_SCHEMES.iter().any(|scheme| url.len() > scheme.len()
+            && url.get(..scheme.len()).is_some_and(|prefix| prefix.eq_ignore_ascii_case(scheme)))
+        && !url.chars().any(|c| c.is_whitespace() || c.is_control() || c == '(' || c == ')')
+}
+
+#[doc(hidden)]
+fn __is_escapable(c: char) -> bool {
+    matches!(c, '*' | '`' | '[' | ']' | '\\')
+}
+
+/// Parses the content of a bold text or italics starting at `start` up to the
+/// closing `delimiter`, resolving escapes.
+///
+/// # Return value
+///
+/// The content and the index after the closing delimiter, or [`Option::None`]
+/// if the delimiter is not closed or the content is empty.
+#[doc(hidden)]
+fn __parse_delimited(chars: &[char], start: usize, delimiter: &str) -> Option<(String, usize)> {
+    let delimiter: Vec<char> = delimiter.chars().collect();
+    let mut content = String::new();
+    let mut i = start;
+    while i < chars.len() {
+        if chars[i] == '\\' && i + 1 < chars.len() && __is_escapable(chars[i + 1]) {
+            content.push(chars[i + 1]);
+            i += 2;
+        } else if chars[i..].starts_with(&delimiter) {
+            return match content.is_empty() {
+                true => Option::None,
+                false => Option::Some((content, i + delimiter.len())),
+            }
+        } else {
+            content.push(chars[i]);
+            i += 1;
+        }
+    }
+    Option::None
+}
+
+#[doc(hidden)]
+fn __parse_code(chars: &[char], start: usize) -> Option<(Span, usize)> {
+    let length = chars[start..].iter().position(|&c| c == '`')?;
+    match length {
+        0 => Option::None,
+        _ => Option::Some((Span::Code(chars[start..start + length].iter().collect()), start + length + 1)),
+    }
+}
+
+#[doc(hidden)]
+fn __parse_link(chars: &[char], start: usize) -> Option<(Span, usize)> {
+    let (text, after_text) = __parse_delimited(chars, start, "](")?;
+    let length = chars[after_text..].iter().position(|&c| c == ')')?;
+    let url: String = chars[after_text..after_text + length].iter().collect();
+    match is_valid_url(&url) {
+        true => Option::Some((Span::Link { text, url }, after_text + length + 1)),
+        false => Option::None,
+    }
+}
+
+/// Appends given text to `result`, escaping given special characters and
+/// backslashes, which would escape the following character otherwise.
+///
+/// # Parameters
+///
+///  -  `text`: the text to escape
+///  -  `special`: characters, which must be escaped
+///  -  `followed`: whether the text is followed by a markup character, so
+///     a trailing backslash must be escaped too
+///  -  `result`: where the escaped text is appended
+#[doc(hidden)]
+fn __escape(text: &str, special: &[char], followed: bool, result: &mut String) {
+    let mut chars = text.chars().peekable();
+    while let Option::Some(c) = chars.next() {
+        let escape = match c {
+            '\\' => chars.peek().map_or(followed, |&next| __is_escapable(next)),
+            c => special.contains(&c),
+        };
+        if escape {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_valid_url;
+    use super::normalize;
+    use super::parse;
+    use super::to_plain;
+    use super::Span;
+
+    fn text(text: &str) -> Span {
+        Span::Text(text.to_string())
+    }
+
+    #[test]
+    fn formatting_is_parsed() {
+        assert_eq!(parse("a **b** *c* `d` [e](https://f.example)"), vec![
+            text("a "),
+            Span::Bold("b".to_string()),
+            text(" "),
+            Span::Italic("c".to_string()),
+            text(" "),
+            Span::Code("d".to_string()),
+            text(" "),
+            Span::Link { text: "e".to_string(), url: "https://f.example".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn unparsable_markup_is_ordinary_text() {
+        assert_eq!(parse("**unclosed *x*"), vec![text("**unclosed "), Span::Italic("x".to_string())]);
+        assert_eq!(parse("a ** b"), vec![text("a ** b")]);
+        assert_eq!(parse("``"), vec![text("``")]);
+        assert_eq!(parse("[x](javascript:alert(1))"), vec![text("[x](javascript:alert(1))")]);
+    }
+
+    #[test]
+    fn escapes_make_ordinary_characters() {
+        assert_eq!(parse(r"\*not italics\*"), vec![text("*not italics*")]);
+        assert_eq!(parse(r"a\\b \n"), vec![text(r"a\b \n")]);
+        assert_eq!(parse(r"*a\*b*"), vec![Span::Italic("a*b".to_string())]);
+    }
+
+    #[test]
+    fn code_is_not_formatted() {
+        assert_eq!(parse("`**x**`"), vec![Span::Code("**x**".to_string())]);
+    }
+
+    #[test]
+    fn normal_form_parses_the_same() {
+        for input in ["a *b* c", r"\[x] **y\*z**", "**unclosed", r"trailing \", "[a\\]b](http://x.example) `c`", "a ** b"] {
+            let normal = normalize(input);
+            assert_eq!(parse(&normal), parse(input), "{:?} -> {:?}", input, normal);
+            assert_eq!(normalize(&normal), normal);
+        }
+        assert_eq!(normalize("a [b] *c*"), r"a \[b] *c*");
+        assert_eq!(normalize("**unclosed"), r"\*\*unclosed");
+    }
+
+    #[test]
+    fn plain_text_contains_link_urls() {
+        assert_eq!(to_plain("**hi** [docs](https://docs.example)"), "hi docs <https://docs.example>");
+    }
+
+    #[test]
+    fn only_allowed_urls_are_valid() {
+        assert!(is_valid_url("https://example.com/a?b=c"));
+        assert!(is_valid_url("HTTP://example.com"));
+        assert!(is_valid_url("mailto:alice@example.com"));
+        assert!(!is_valid_url("https://"));
+        assert!(!is_valid_url("javascript:alert(1)"));
+        assert!(!is_valid_url("https://example.com/a b"));
+        assert!(!is_valid_url("https://example.com/(x)"));
+    }
+}
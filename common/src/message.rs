@@ -24,13 +24,87 @@ use serde::Serialize;
 
 use std::fmt;
 
+/// Number of colors which can be assigned to message senders. See
+/// [`Message::color`] for more information.
+pub const COLOR_COUNT: u8 = 12;
+
+/// Prefix with which clients mark messages sent by the server itself, see
+/// [`Message::is_from_server`]. No nickname can start with it, see
+/// [`is_reserved_name`], so users cannot impersonate the server.
+pub const SERVER_PREFIX: &str = "[server]";
+
+/// Maximum number of characters of an emoji used as a reaction, see
+/// [`is_valid_emoji`]. Some emoji consist of several characters joined
+/// together, such as flags or people with a skin tone.
+pub const MAX_EMOJI_LENGTH: usize = 8;
+
+/// Maximum number of characters of a tag a client attaches to a message it sends,
+/// see [`c2s::Command::SendMessage`].
+///
+/// [`c2s::Command::SendMessage`]: crate::command::c2s::Command::SendMessage
+pub const MAX_CLIENT_TAG_LENGTH: usize = 64;
+
+/// Returns whether given text can be used as a reaction to a message, that is
+/// whether it is not empty, has at most [`MAX_EMOJI_LENGTH`] characters and
+/// contains neither whitespace, nor control characters, nor letters or digits
+/// of the ASCII. The server does not check whether the text is really an emoji.
+pub fn is_valid_emoji(emoji: &str) -> bool {
+    !emoji.is_empty()
+        && emoji.chars().count() <= MAX_EMOJI_LENGTH
+        && !emoji.chars().any(|c| c.is_whitespace() || c.is_control() || c.is_ascii_alphanumeric())
+}
+
+/// Returns whether given name starts with [`SERVER_PREFIX`], ignoring the case
+/// and leading whitespace. Such names are reserved for the server and cannot be
+/// used as nicknames.
+pub fn is_reserved_name(name: &str) -> bool {
+    name.trim_start()
+        .get(..SERVER_PREFIX.len())
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case(SERVER_PREFIX))
+}
+
+/// Represents what kind of a message a [`Message`] is, so clients can render
+/// different kinds of messages differently.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub enum MessageKind {
+    /// An ordinary message written by the sender.
+    User,
+    /// A notice generated by the server about the sender, for example that the
+    /// sender has joined or left the chat, or has changed the topic of a channel.
+    /// Clients cannot send these messages.
+    System,
+    /// A description of what the sender is doing, usually written in the third
+    /// person, such as "waves at everybody".
+    Action,
+    /// An announcement sent by a user allowed to send announcements by the
+    /// server.
+    Announcement,
+    /// A message posted by an integration, such as a CI system or monitoring,
+    /// using a webhook. The sender is the name of the integration. Clients cannot
+    /// send these messages.
+    Bot,
+}
+
 /// A structure representing a message in a chat.
 #[derive(Clone, Debug)]
 #[derive(Serialize, Deserialize)]
 pub struct Message {
+    #[serde(default)]
+    id: u64,
     sender: String,
+    channel: Option<String>,
+    kind: MessageKind,
     date_time: DateTime<Utc>,
     text: String,
+    expires_at: Option<DateTime<Utc>>,
+    color: u8,
+    #[serde(default)]
+    from_server: bool,
+    #[serde(default)]
+    previews: Vec<LinkPreview>,
+    #[serde(default)]
+    deleted: bool,
 }
 
 impl Message {
@@ -39,10 +113,69 @@ impl Message {
     /// # Parameters
     ///
     ///  -  `sender`: nickname of user, which has sent the message
+    ///  -  `channel`: name of the channel the message was sent to, or
+    ///     [`Option::None`] if it was sent to the main chat
+    ///  -  `kind`: what kind of a message it is
     ///  -  `date_time`: date and time, when the message was sent
     ///  -  `text`: content of the message
-    pub fn new(sender: String, date_time: DateTime<Utc>, text: String) -> Self {
-        Self { sender, date_time, text }
+    ///  -  `expires_at`: date and time, when the message expires, or
+    ///     [`Option::None`] if the message never expires
+    ///  -  `color`: color of the sender, see [`Message::color`]
+    pub fn new(
+        sender: String,
+        channel: Option<String>,
+        kind: MessageKind,
+        date_time: DateTime<Utc>,
+        text: String,
+        expires_at: Option<DateTime<Utc>>,
+        color: u8,
+    ) -> Self {
+        Self { id: 0, sender, channel, kind, date_time, text, expires_at, color, from_server: false, previews: Vec::new(), deleted: false }
+    }
+
+    /// Returns `self` with given ID assigned by the server, see [`Message::id`].
+    ///
+    /// # Parameters
+    ///
+    ///  -  `id`: ID of the message in the message history
+    pub fn with_id(self, id: u64) -> Self {
+        Self { id, ..self }
+    }
+
+    /// Returns `self` marked as generated by the server, see
+    /// [`Message::is_from_server`].
+    ///
+    /// # Parameters
+    ///
+    ///  -  `from_server`: whether the message was generated by the server
+    pub fn with_from_server(self, from_server: bool) -> Self {
+        Self { from_server, ..self }
+    }
+
+    /// Returns `self` with given link previews attached, see
+    /// [`Message::previews`].
+    ///
+    /// # Parameters
+    ///
+    ///  -  `previews`: previews of links found in the text of the message
+    pub fn with_previews(self, previews: Vec<LinkPreview>) -> Self {
+        Self { previews, ..self }
+    }
+
+    /// Returns a tombstone of `self`, which keeps the sender, the channel and
+    /// the date and time of the message, but not its content, see
+    /// [`Message::is_deleted`].
+    pub fn into_tombstone(self) -> Self {
+        Self { text: String::new(), previews: Vec::new(), deleted: true, ..self }
+    }
+
+    /// Returns the ID the server has assigned to the message when writing it into
+    /// the message history, which clients use to reference the message, for
+    /// example when deleting it or reacting to it. The ID is 0 if it has not been
+    /// assigned yet or if the message comes from a server which does not embed
+    /// IDs into messages.
+    pub fn id(&self) -> u64 {
+        self.id
     }
 
     /// Returns the nickname of the user who sent the message.
@@ -50,6 +183,18 @@ impl Message {
         &self.sender
     }
 
+    /// Returns the name of the channel the message was sent to, or
+    /// [`Option::None`] if it was sent to the main chat, which all users can
+    /// read.
+    pub fn channel(&self) -> Option<&String> {
+        self.channel.as_ref()
+    }
+
+    /// Returns what kind of a message it is.
+    pub fn kind(&self) -> MessageKind {
+        self.kind
+    }
+
     /// Returns date and time when the message was sent.
     pub fn date_time(&self) -> &DateTime<Utc> {
         &self.date_time
@@ -59,14 +204,245 @@ impl Message {
     pub fn text(&self) -> &String {
         &self.text
     }
+
+    /// Returns date and time when the message expires, or [`Option::None`] if
+    /// the message never expires.
+    pub fn expires_at(&self) -> Option<&DateTime<Utc>> {
+        self.expires_at.as_ref()
+    }
+
+    /// Returns the color of the sender. The color is an index lower than
+    /// [`COLOR_COUNT`] assigned by the server, so all clients can display the
+    /// same sender in the same color. It is up to the client which palette
+    /// is used.
+    pub fn color(&self) -> u8 {
+        self.color
+    }
+
+    /// Returns whether the message was generated by the server, such as
+    /// a [`MessageKind::System`] notice. Only the server sets this flag, so
+    /// clients should mark these messages with [`SERVER_PREFIX`] and never mark
+    /// other messages this way.
+    pub fn is_from_server(&self) -> bool {
+        self.from_server
+    }
+
+    /// Returns previews of links found in the text of the message by the server,
+    /// which clients can render as link cards. The slice is empty if the server
+    /// does not detect links.
+    pub fn previews(&self) -> &[LinkPreview] {
+        &self.previews
+    }
+
+    /// Returns whether the message has been deleted and only its tombstone has
+    /// been kept, see [`Message::into_tombstone`].
+    pub fn is_deleted(&self) -> bool {
+        self.deleted
+    }
+
+    /// Returns whether the text of the message mentions given user, that is
+    /// whether it contains `@` followed by the nickname, which is not followed
+    /// by another alphanumeric character.
+    pub fn mentions(&self, nickname: &str) -> bool {
+        let mention = format!("@{}", nickname);
+        self.text.match_indices(&mention).any(|(i, _)| {
+            !self.text[i + mention.len()..].starts_with(|c: char| c.is_alphanumeric() || c == '_' || c == '-')
+        })
+    }
+
+    /// Returns whether the message has already expired at given date and time.
+    pub fn is_expired_at(&self, date_time: &DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= *date_time)
+    }
 }
 
 impl fmt::Display for Message {
     fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
-        write!(fmtr, "{} @ {}: {}",
+        if let Option::Some(channel) = &self.channel {
+            write!(fmtr, "#{} ", channel)?;
+        }
+        let date_time = self.date_time.with_timezone(&Local).to_rfc2822();
+        match self.kind {
+            MessageKind::User => write!(fmtr, "{} @ {}: {}", self.sender, date_time, self.text),
+            MessageKind::System if self.from_server => write!(fmtr, "{} {} -- {} {}", SERVER_PREFIX, date_time, self.sender, self.text),
+            MessageKind::System => write!(fmtr, "{} -- {} {}", date_time, self.sender, self.text),
+            MessageKind::Action => write!(fmtr, "{} * {} {}", date_time, self.sender, self.text),
+            MessageKind::Announcement => write!(fmtr, "Announcement from {} @ {}: {}", self.sender, date_time, self.text),
+            MessageKind::Bot => write!(fmtr, "[bot] {} @ {}: {}", self.sender, date_time, self.text),
+        }
+    }
+}
+
+/// A structure representing a link found in the text of a message, optionally
+/// together with the title and the description of the linked page, if the
+/// server has fetched them.
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct LinkPreview {
+    url: String,
+    title: Option<String>,
+    description: Option<String>,
+}
+
+impl LinkPreview {
+    /// Creates a new [`LinkPreview`] instance
+    ///
+    /// # Parameters
+    ///
+    ///  -  `url`: the link found in the text of the message
+    ///  -  `title`: title of the linked page, or [`Option::None`] if unknown
+    ///  -  `description`: description of the linked page, or [`Option::None`]
+    ///     if unknown
+    pub fn new(url: String, title: Option<String>, description: Option<String>) -> Self {
+        Self { url, title, description }
+    }
+
+    /// Returns the link found in the text of the message.
+    pub fn url(&self) -> &String {
+        &self.url
+    }
+
+    /// Returns the title of the linked page, or [`Option::None`] if it is not
+    /// known.
+    pub fn title(&self) -> Option<&String> {
+        self.title.as_ref()
+    }
+
+    /// Returns the description of the linked page, or [`Option::None`] if it is
+    /// not known.
+    pub fn description(&self) -> Option<&String> {
+        self.description.as_ref()
+    }
+}
+
+/// A structure representing all reactions to a message using the same emoji,
+/// see [`is_valid_emoji`].
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct Reaction {
+    emoji: String,
+    users: Vec<String>,
+}
+
+impl Reaction {
+    /// Creates a new [`Reaction`] instance
+    ///
+    /// # Parameters
+    ///
+    ///  -  `emoji`: the emoji used to react
+    ///  -  `users`: nicknames of users who have reacted using the emoji
+    pub fn new(emoji: String, users: Vec<String>) -> Self {
+        Self { emoji, users }
+    }
+
+    /// Returns the emoji used to react.
+    pub fn emoji(&self) -> &String {
+        &self.emoji
+    }
+
+    /// Returns nicknames of users who have reacted using the emoji, ordered
+    /// alphabetically.
+    pub fn users(&self) -> &[String] {
+        &self.users
+    }
+}
+
+impl fmt::Display for Reaction {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "{} {}", self.emoji, self.users.len())
+    }
+}
+
+/// A structure representing a direct message, which only its sender and its
+/// recipient can read. Direct messages are not a part of the message history.
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct DirectMessage {
+    sender: String,
+    recipient: String,
+    date_time: DateTime<Utc>,
+    text: String,
+    color: u8,
+}
+
+impl DirectMessage {
+    /// Creates a new [`DirectMessage`] instance
+    ///
+    /// # Parameters
+    ///
+    ///  -  `sender`: nickname of user, which has sent the message
+    ///  -  `recipient`: nickname of user, to which the message is addressed
+    ///  -  `date_time`: date and time, when the message was sent
+    ///  -  `text`: content of the message
+    ///  -  `color`: color of the sender, see [`Message::color`]
+    pub fn new(sender: String, recipient: String, date_time: DateTime<Utc>, text: String, color: u8) -> Self {
+        Self { sender, recipient, date_time, text, color }
+    }
+
+    /// Returns the nickname of the user who sent the message.
+    pub fn sender(&self) -> &String {
+        &self.sender
+    }
+
+    /// Returns the nickname of the user the message is addressed to.
+    pub fn recipient(&self) -> &String {
+        &self.recipient
+    }
+
+    /// Returns date and time when the message was sent.
+    pub fn date_time(&self) -> &DateTime<Utc> {
+        &self.date_time
+    }
+
+    /// Returns the content of the message.
+    pub fn text(&self) -> &String {
+        &self.text
+    }
+
+    /// Returns the color of the sender, see [`Message::color`].
+    pub fn color(&self) -> u8 {
+        self.color
+    }
+}
+
+impl fmt::Display for DirectMessage {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "{} -> {} @ {}: {}",
                self.sender,
+               self.recipient,
                self.date_time.with_timezone(&Local).to_rfc2822(),
                self.text
         )
     }
-}
\ No newline at end of file
+}
+
+/// A structure representing a message in a chat together with the ID server has
+/// assigned to it.
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct HistoryEntry {
+    id: u64,
+    message: Message,
+}
+
+impl HistoryEntry {
+    /// Creates a new [`HistoryEntry`] instance
+    ///
+    /// # Parameters
+    ///
+    ///  -  `id`: ID of the message assigned by the server
+    ///  -  `message`: the message itself
+    pub fn new(id: u64, message: Message) -> Self {
+        Self { id, message }
+    }
+
+    /// Returns the ID of the message assigned by the server.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns the message itself.
+    pub fn message(&self) -> &Message {
+        &self.message
+    }
+}
@@ -0,0 +1,336 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module for events which are relevant for moderators.
+
+use chrono::DateTime;
+use chrono::Local;
+use chrono::Utc;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::SocketAddr;
+use std::str::FromStr;
+
+/// A structure representing an event relevant for moderation, such as a rejected
+/// message or a failed login.
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct ModEvent {
+    date_time: DateTime<Utc>,
+    kind: ModEventKind,
+}
+
+impl ModEvent {
+    /// Creates a new [`ModEvent`] instance.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `date_time`: date and time, when the event happened
+    ///  -  `kind`: what happened
+    pub fn new(date_time: DateTime<Utc>, kind: ModEventKind) -> Self {
+        Self { date_time, kind }
+    }
+
+    /// Returns date and time when the event happened.
+    pub fn date_time(&self) -> &DateTime<Utc> {
+        &self.date_time
+    }
+
+    /// Returns what happened.
+    pub fn kind(&self) -> &ModEventKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for ModEvent {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "{} {}", self.date_time.with_timezone(&Local).to_rfc2822(), self.kind)
+    }
+}
+
+/// An enumeration of events relevant for moderation.
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub enum ModEventKind {
+
+    /// A message was not accepted by the server, because it is not allowed by
+    /// the regulation rules.
+    MessageRejected { sender: String, text: String, reason: String },
+
+    /// A client failed to log in or register.
+    LoginFailed { socket_addr: SocketAddr, nickname: String, reason: String },
+
+    /// A message was held in the review queue, because it matches a quarantine
+    /// pattern.
+    MessageQuarantined { id: u64, sender: String, text: String, pattern: String },
+
+    /// A user tried to do something more often than allowed.
+    RateLimited { nickname: String, action: String },
+
+    /// Too many clients connected or registered from different IP addresses
+    /// within a short time. The numbers describe the time window, in which the
+    /// raid was detected.
+    RaidDetected { connections: u32, registrations: u32, distinct_ips: u32 },
+
+    /// A user registered during a raid and cannot send messages until
+    /// a moderator approves the account.
+    ApprovalRequired { nickname: String, socket_addr: SocketAddr },
+
+    /// An administrator turned the emergency lockdown on (`active` is `true`) or
+    /// off (`active` is `false`).
+    LockdownChanged { nickname: String, active: bool },
+
+    /// A moderator added a filter rule at runtime. The rule is written as in the
+    /// configuration file.
+    FilterRuleAdded { nickname: String, rule: String },
+}
+
+impl fmt::Display for ModEventKind {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::MessageRejected { sender, text, reason } =>
+                write!(fmtr, "rejected message from `{}` ({}): {}", sender, reason, text),
+            Self::LoginFailed { socket_addr, nickname, reason } =>
+                write!(fmtr, "failed login as `{}` from {}: {}", nickname, socket_addr, reason),
+            Self::MessageQuarantined { id, sender, text, pattern } =>
+                write!(fmtr, "message {} from `{}` waits for review (matches `{}`): {}", id, sender, pattern, text),
+            Self::RateLimited { nickname, action } =>
+                write!(fmtr, "`{}` is trying to {} too often", nickname, action),
+            Self::RaidDetected { connections, registrations, distinct_ips } =>
+                write!(fmtr, "raid detected: {} connections and {} registrations from {} IP addresses",
+                       connections, registrations, distinct_ips),
+            Self::ApprovalRequired { nickname, socket_addr } =>
+                write!(fmtr, "account `{}` registered from {} waits for approval", nickname, socket_addr),
+            Self::LockdownChanged { nickname, active: true } =>
+                write!(fmtr, "`{}` turned the lockdown on", nickname),
+            Self::LockdownChanged { nickname, active: false } =>
+                write!(fmtr, "`{}` turned the lockdown off", nickname),
+            Self::FilterRuleAdded { nickname, rule } =>
+                write!(fmtr, "`{}` added filter rule `{}`", nickname, rule),
+        }
+    }
+}
+
+/// A structure representing how many messages a single banned pattern of the
+/// message filter has matched.
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct FilterHits {
+    pattern: String,
+    trial: bool,
+    per_user: BTreeMap<String, u64>,
+}
+
+impl FilterHits {
+    /// Creates a new [`FilterHits`] instance.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `pattern`: the banned pattern
+    ///  -  `trial`: whether the pattern is only on trial, that is messages
+    ///     matching it are not rejected
+    ///  -  `per_user`: number of matched messages for each user who sent any
+    pub fn new(pattern: String, trial: bool, per_user: BTreeMap<String, u64>) -> Self {
+        Self { pattern, trial, per_user }
+    }
+
+    /// Returns the banned pattern.
+    pub fn pattern(&self) -> &String {
+        &self.pattern
+    }
+
+    /// Returns whether the pattern is only on trial, that is messages matching
+    /// it are not rejected.
+    pub fn is_trial(&self) -> bool {
+        self.trial
+    }
+
+    /// Returns number of matched messages for each user who sent any.
+    pub fn per_user(&self) -> &BTreeMap<String, u64> {
+        &self.per_user
+    }
+
+    /// Returns total number of matched messages.
+    pub fn total(&self) -> u64 {
+        self.per_user.values().sum()
+    }
+}
+
+impl fmt::Display for FilterHits {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "`{}`{}: {} hit(s)", self.pattern, if self.trial { " (trial)" } else { "" }, self.total())?;
+        for (nickname, count) in &self.per_user {
+            write!(fmtr, "\n  {}: {}", nickname, count)?;
+        }
+        Result::Ok(())
+    }
+}
+
+/// An enumeration of filters of the server, whose decisions can be explained
+/// using [`c2s::Command::Explain`].
+///
+/// [`c2s::Command::Explain`]: crate::command::c2s::Command::Explain
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub enum FilterKind {
+    /// Filter of IP addresses of connecting clients
+    Ip,
+    /// Filter of message texts
+    Message,
+    /// Filter of nicknames of registering users
+    Nickname,
+}
+
+impl FromStr for FilterKind {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "ip" => Result::Ok(Self::Ip),
+            "message" => Result::Ok(Self::Message),
+            "nickname" => Result::Ok(Self::Nickname),
+            other => Result::Err(format!("`{}` is not a valid filter", other)),
+        }
+    }
+}
+
+impl fmt::Display for FilterKind {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        fmtr.write_str(match self {
+            Self::Ip => "ip",
+            Self::Message => "message",
+            Self::Nickname => "nickname",
+        })
+    }
+}
+
+/// A structure representing whether a filter allows an input and which rule
+/// decided it.
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct FilterDecision {
+    allowed: bool,
+    rule: String,
+}
+
+impl FilterDecision {
+    /// Creates a new [`FilterDecision`] instance allowing the input.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `rule`: description of the rule which allowed the input
+    pub fn allowed(rule: String) -> Self {
+        Self { allowed: true, rule }
+    }
+
+    /// Creates a new [`FilterDecision`] instance rejecting the input.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `rule`: description of the rule which rejected the input
+    pub fn rejected(rule: String) -> Self {
+        Self { allowed: false, rule }
+    }
+
+    /// Returns whether the input is allowed.
+    pub fn is_allowed(&self) -> bool {
+        self.allowed
+    }
+
+    /// Returns description of the rule which decided whether the input is
+    /// allowed.
+    pub fn rule(&self) -> &String {
+        &self.rule
+    }
+}
+
+impl fmt::Display for FilterDecision {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        match self.allowed {
+            true => write!(fmtr, "allowed: {}", self.rule),
+            false => write!(fmtr, "rejected: {}", self.rule),
+        }
+    }
+}
+
+/// A structure representing a message held in the review queue, because it
+/// matches a quarantine pattern of the message filter.
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct QuarantinedMessage {
+    id: u64,
+    sender: String,
+    date_time: DateTime<Utc>,
+    text: String,
+    pattern: String,
+}
+
+impl QuarantinedMessage {
+    /// Creates a new [`QuarantinedMessage`] instance.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `id`: ID of the message in the review queue
+    ///  -  `sender`: nickname of the user who sent the message
+    ///  -  `date_time`: date and time, when the message was sent
+    ///  -  `text`: content of the message
+    ///  -  `pattern`: the quarantine pattern the message matches
+    pub fn new(id: u64, sender: String, date_time: DateTime<Utc>, text: String, pattern: String) -> Self {
+        Self { id, sender, date_time, text, pattern }
+    }
+
+    /// Returns the ID of the message in the review queue.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns the nickname of the user who sent the message.
+    pub fn sender(&self) -> &String {
+        &self.sender
+    }
+
+    /// Returns date and time when the message was sent.
+    pub fn date_time(&self) -> &DateTime<Utc> {
+        &self.date_time
+    }
+
+    /// Returns the content of the message.
+    pub fn text(&self) -> &String {
+        &self.text
+    }
+
+    /// Returns the quarantine pattern the message matches.
+    pub fn pattern(&self) -> &String {
+        &self.pattern
+    }
+}
+
+impl fmt::Display for QuarantinedMessage {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "[{}] {} @ {} (matches `{}`): {}",
+               self.id,
+               self.sender,
+               self.date_time.with_timezone(&Local).to_rfc2822(),
+               self.pattern,
+               self.text
+        )
+    }
+}
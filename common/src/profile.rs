@@ -0,0 +1,84 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module for user profiles.
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use std::fmt;
+
+/// A structure representing a profile of a user. All fields of the profile are
+/// optional.
+#[derive(Clone, Debug, Default)]
+#[derive(Serialize, Deserialize)]
+pub struct Profile {
+    display_name: Option<String>,
+    bio: Option<String>,
+    status: Option<String>,
+    color: Option<u8>,
+}
+
+impl Profile {
+    /// Creates a new [`Profile`] instance.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `display_name`: name of the user which should be displayed instead of
+    ///     the nickname
+    ///  -  `bio`: a short text about the user
+    ///  -  `status`: text describing what the user is doing right now
+    ///  -  `color`: preferred color of the user's nickname, see
+    ///     [`Message::color`] for more information
+    ///
+    /// [`Message::color`]: crate::message::Message::color
+    pub fn new(display_name: Option<String>, bio: Option<String>, status: Option<String>, color: Option<u8>) -> Self {
+        Self { display_name, bio, status, color }
+    }
+
+    /// Returns the name of the user which should be displayed instead of the
+    /// nickname.
+    pub fn display_name(&self) -> Option<&String> {
+        self.display_name.as_ref()
+    }
+
+    /// Returns a short text about the user.
+    pub fn bio(&self) -> Option<&String> {
+        self.bio.as_ref()
+    }
+
+    /// Returns text describing what the user is doing right now.
+    pub fn status(&self) -> Option<&String> {
+        self.status.as_ref()
+    }
+
+    /// Returns preferred color of the user's nickname.
+    pub fn color(&self) -> Option<u8> {
+        self.color
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "display name: {}, bio: {}, status: {}, color: {}",
+               self.display_name.as_deref().unwrap_or("-"),
+               self.bio.as_deref().unwrap_or("-"),
+               self.status.as_deref().unwrap_or("-"),
+               self.color.map(|color| color.to_string()).as_deref().unwrap_or("-")
+        )
+    }
+}
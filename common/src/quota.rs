@@ -0,0 +1,44 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module for limits of how many messages users can send.
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use std::fmt;
+
+/// An enumeration of message quotas a user can exceed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub enum QuotaKind {
+
+    /// Quota of messages a user can send per day.
+    Daily,
+
+    /// Quota of messages which can be sent using a single connection.
+    Session,
+}
+
+impl fmt::Display for QuotaKind {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Daily => write!(fmtr, "daily"),
+            Self::Session => write!(fmtr, "session"),
+        }
+    }
+}
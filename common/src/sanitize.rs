@@ -0,0 +1,151 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module for removing characters which could mislead users or break
+//! terminals from texts sent by users. Clients print received texts as they are,
+//! so the server sanitizes them before they are stored and broadcast.
+
+use std::iter::Peekable;
+
+/// Returns whether given character is an invisible character changing the
+/// direction of the text, such as the right-to-left override. These characters
+/// can make a text look different from what it is.
+pub fn is_bidi_control(c: char) -> bool {
+    matches!(c, '\u{061C}' | '\u{200E}' | '\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}')
+}
+
+/// Returns whether given character is a zero-width character, which cannot be
+/// seen, but makes two otherwise equal texts different.
+pub fn is_zero_width(c: char) -> bool {
+    matches!(c, '\u{180E}' | '\u{200B}'..='\u{200D}' | '\u{2060}' | '\u{FEFF}')
+}
+
+/// Returns whether given name, such as a nickname, contains no control
+/// characters, zero-width characters or characters changing the direction of
+/// the text. Names are not sanitized, but rejected, since a sanitized name could
+/// be equal to a name of another user.
+pub fn is_clean_name(name: &str) -> bool {
+    !name.chars().any(|c| c.is_control() || is_zero_width(c) || is_bidi_control(c))
+}
+
+/// Sanitizes a text sent by a user, such as a message or a topic of a channel.
+/// Terminal escape sequences are removed as a whole, other control characters
+/// except line feeds and tabs are removed, as well as characters changing the
+/// direction of the text. Zero-width characters are removed too, except
+/// joiners and non-joiners, which are needed for emoji sequences and some
+/// scripts.
+pub fn text(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Option::Some(c) = chars.next() {
+        match c {
+            '\n' | '\t' | '\u{200C}' | '\u{200D}' => result.push(c),
+            // Control sequence introducer, either as an escape sequence or as
+            // a single character, followed by parameters and a final byte:
+            '\x1b' if chars.peek() == Option::Some(&'[') => {
+                chars.next();
+                __skip_control_sequence(&mut chars);
+            },
+            '\u{9b}' => __skip_control_sequence(&mut chars),
+            // Operating system commands and other strings terminated by a bell
+            // or a string terminator:
+            '\x1b' if matches!(chars.peek(), Option::Some(']' | 'P' | 'X' | '^' | '_')) => {
+                chars.next();
+                __skip_string(&mut chars);
+            },
+            '\u{90}' | '\u{98}' | '\u{9d}' | '\u{9e}' | '\u{9f}' => __skip_string(&mut chars),
+            // Other escape sequences consist of a single character:
+            '\x1b' => {
+                chars.next();
+            },
+            c if c.is_control() || is_zero_width(c) || is_bidi_control(c) => {},
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+#[doc(hidden)]
+fn __skip_control_sequence<I: Iterator<Item = char>>(chars: &mut I) {
+    for c in chars {
+        if ('\x40'..='\x7e').contains(&c) {
+            break
+        }
+    }
+}
+
+#[doc(hidden)]
+fn __skip_string<I: Iterator<Item = char>>(chars: &mut Peekable<I>) {
+    while let Option::Some(c) = chars.next() {
+        match c {
+            '\x07' | '\u{9c}' => break,
+            '\x1b' if chars.peek() == Option::Some(&'\\') => {
+                chars.next();
+                break
+            },
+            _ => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_clean_name;
+    use super::text;
+
+    #[test]
+    fn plain_text_is_kept() {
+        assert_eq!(text("Hello, world!\n\tBye 👋"), "Hello, world!\n\tBye 👋");
+    }
+
+    #[test]
+    fn escape_sequences_are_removed_as_whole() {
+        assert_eq!(text("\x1b[31mred\x1b[0m"), "red");
+        assert_eq!(text("\u{9b}2Jclear"), "clear");
+        assert_eq!(text("\x1b]0;title\x07after"), "after");
+        assert_eq!(text("\x1b]8;;http://evil\x1b\\link\x1b]8;;\x1b\\"), "link");
+        assert_eq!(text("\x1bcreset"), "reset");
+    }
+
+    #[test]
+    fn control_characters_are_removed() {
+        assert_eq!(text("a\rb\x08c\x00d"), "abcd");
+    }
+
+    #[test]
+    fn bidi_and_zero_width_characters_are_removed() {
+        assert_eq!(text("user\u{202E}gpj.exe"), "usergpj.exe");
+        assert_eq!(text("ad\u{200B}min\u{FEFF}"), "admin");
+    }
+
+    #[test]
+    fn joiners_are_kept() {
+        let family = "👨\u{200D}👩\u{200D}👧";
+        assert_eq!(text(family), family);
+        assert_eq!(text("a\u{200C}b"), "a\u{200C}b");
+    }
+
+    #[test]
+    fn unclean_names_are_detected() {
+        assert!(is_clean_name("alice"));
+        assert!(is_clean_name("Zoë"));
+        assert!(!is_clean_name("ad\u{200B}min"));
+        assert!(!is_clean_name("user\u{202E}"));
+        assert!(!is_clean_name("two\nlines"));
+        assert!(!is_clean_name("\x1b[31mred"));
+    }
+}
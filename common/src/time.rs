@@ -0,0 +1,102 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module for synchronization of clocks between clients and the server.
+
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Utc;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+/// A structure containing timestamps of a single clock synchronization. Client
+/// sends the time the request was sent, server adds the time the request was
+/// received and the time the response was sent. Using the time the response was
+/// received, client can estimate the delays and the difference between its clock
+/// and the clock of the server the same way as NTP does.
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct TimeSync {
+    client_sent_at: DateTime<Utc>,
+    server_received_at: DateTime<Utc>,
+    server_sent_at: DateTime<Utc>,
+}
+
+impl TimeSync {
+    /// Creates a new [`TimeSync`] instance.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `client_sent_at`: time the client sent the request, measured by the
+    ///     clock of the client
+    ///  -  `server_received_at`: time the server received the request, measured
+    ///     by the clock of the server
+    ///  -  `server_sent_at`: time the server sent the response, measured by the
+    ///     clock of the server
+    pub fn new(client_sent_at: DateTime<Utc>, server_received_at: DateTime<Utc>, server_sent_at: DateTime<Utc>) -> Self {
+        Self { client_sent_at, server_received_at, server_sent_at }
+    }
+
+    /// Returns the time the client sent the request, measured by the clock of
+    /// the client.
+    pub fn client_sent_at(&self) -> &DateTime<Utc> {
+        &self.client_sent_at
+    }
+
+    /// Returns the time the server received the request, measured by the clock
+    /// of the server.
+    pub fn server_received_at(&self) -> &DateTime<Utc> {
+        &self.server_received_at
+    }
+
+    /// Returns the time the server sent the response, that is the current time
+    /// of the server when the response was sent.
+    pub fn server_sent_at(&self) -> &DateTime<Utc> {
+        &self.server_sent_at
+    }
+
+    /// Returns the measured delay between sending the request and receiving it
+    /// by the server. Since it is measured using two different clocks, it
+    /// includes the difference between them.
+    pub fn upstream_delay(&self) -> Duration {
+        self.server_received_at - self.client_sent_at
+    }
+
+    /// Returns the measured delay between sending the response and receiving it
+    /// by the client at given time. Since it is measured using two different
+    /// clocks, it includes the difference between them.
+    pub fn downstream_delay(&self, client_received_at: DateTime<Utc>) -> Duration {
+        client_received_at - self.server_sent_at
+    }
+
+    /// Returns the time the request and the response spent on the network, that
+    /// is the round trip time without the time the server spent processing the
+    /// request. It does not depend on the difference between the clocks.
+    pub fn round_trip(&self, client_received_at: DateTime<Utc>) -> Duration {
+        self.upstream_delay() + self.downstream_delay(client_received_at)
+    }
+
+    /// Returns the estimated difference between the clock of the server and the
+    /// clock of the client, assuming that the delays in both directions are the
+    /// same. Positive value means the clock of the server is ahead. Adding it to
+    /// times measured by the client gives times comparable to times measured by
+    /// the server.
+    pub fn clock_offset(&self, client_received_at: DateTime<Utc>) -> Duration {
+        (self.upstream_delay() - self.downstream_delay(client_received_at)) / 2
+    }
+}
@@ -0,0 +1,123 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module for public information about users.
+
+use chrono::DateTime;
+use chrono::Local;
+use chrono::Utc;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Represents whether a user wants to be disturbed. Users who do not want to be
+/// disturbed are not notified about mentions and, if the server is configured to
+/// do so, get direct messages only after they change their availability.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub enum Availability {
+    /// The user can be disturbed.
+    #[default]
+    Available,
+    /// The user is away, but can be disturbed.
+    Away,
+    /// The user does not want to be disturbed.
+    DoNotDisturb,
+}
+
+impl FromStr for Availability {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "available" => Result::Ok(Self::Available),
+            "away" => Result::Ok(Self::Away),
+            "dnd" => Result::Ok(Self::DoNotDisturb),
+            other => Result::Err(format!("`{}` is not a valid availability", other)),
+        }
+    }
+}
+
+impl fmt::Display for Availability {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        fmtr.write_str(match self {
+            Self::Available => "available",
+            Self::Away => "away",
+            Self::DoNotDisturb => "dnd",
+        })
+    }
+}
+
+/// A structure describing a registered user of the chat.
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct UserInfo {
+    nickname: String,
+    online: bool,
+    last_seen: Option<DateTime<Utc>>,
+    availability: Availability,
+}
+
+impl UserInfo {
+    /// Creates a new [`UserInfo`] instance.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `nickname`: nickname of the user
+    ///  -  `online`: whether the user is currently logged in
+    ///  -  `last_seen`: date and time of the last activity of the user, or
+    ///     [`Option::None`] if it is unknown or the user has hidden it
+    ///  -  `availability`: whether the user wants to be disturbed
+    pub fn new(nickname: String, online: bool, last_seen: Option<DateTime<Utc>>, availability: Availability) -> Self {
+        Self { nickname, online, last_seen, availability }
+    }
+
+    /// Returns the nickname of the user.
+    pub fn nickname(&self) -> &String {
+        &self.nickname
+    }
+
+    /// Returns whether the user is currently logged in.
+    pub fn is_online(&self) -> bool {
+        self.online
+    }
+
+    /// Returns date and time of the last activity of the user, if it is known
+    /// and the user has not hidden it.
+    pub fn last_seen(&self) -> Option<&DateTime<Utc>> {
+        self.last_seen.as_ref()
+    }
+
+    /// Returns whether the user wants to be disturbed.
+    pub fn availability(&self) -> Availability {
+        self.availability
+    }
+}
+
+impl fmt::Display for UserInfo {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        match (self.online, &self.last_seen) {
+            (true, _) => write!(fmtr, "{} (online, {})", self.nickname, self.availability),
+            (false, Option::Some(last_seen)) => write!(fmtr, "{} (last seen {})",
+                self.nickname, last_seen.with_timezone(&Local).to_rfc2822()),
+            (false, Option::None) => write!(fmtr, "{}", self.nickname),
+        }
+    }
+}
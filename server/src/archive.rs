@@ -0,0 +1,47 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use chrono::Utc;
+
+use crate::global_config;
+use crate::message_list;
+
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::path::PathBuf;
+
+/// Archives the message history of given channel. Messages sent into the channel
+/// are saved as JSON into a new file in the archive directory given by global
+/// configuration and removed from the message history.
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] with the path to the archive and the number of archived
+///     messages
+///  -  [`Result::Err`] if the archive could not be written; no messages are
+///     removed in that case
+pub fn archive_channel(channel: &str) -> io::Result<(PathBuf, usize)> {
+    let archive_dir = global_config().channels().read().unwrap().get_archive_dir().clone();
+    fs::create_dir_all(&archive_dir)?;
+    let path = archive_dir.join(format!("{}-{}.json", channel, Utc::now().format("%Y%m%dT%H%M%SZ")));
+    let entries = message_list::entries_of_channel(channel);
+    let file = File::options().write(true).create_new(true).open(&path)?;
+    serde_json::to_writer_pretty(file, &entries)?;
+    message_list::remove_channel(channel);
+    Result::Ok((path, entries.len()))
+}
@@ -0,0 +1,91 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Authentication of users. Users are authenticated by an [`AuthProvider`]
+//! selected by global configuration, so the server can use an existing identity
+//! system instead of its own user database.
+
+mod builtin;
+mod command;
+mod ldap;
+mod oauth;
+
+use crate::global_config;
+
+use mdchat_serverconf::AuthProviderKind;
+
+use once_cell::sync::OnceCell;
+
+use std::io;
+
+/// The authentication provider selected by global configuration.
+static PROVIDER: OnceCell<Box<dyn AuthProvider>> = OnceCell::new();
+
+/// A source of identities users are authenticated against.
+pub trait AuthProvider: Send + Sync {
+    /// Returns the name of the provider used in the server log.
+    fn name(&self) -> &'static str;
+
+    /// Returns whether new users can register. If they cannot, accounts are
+    /// managed by the identity system the provider uses.
+    fn can_register(&self) -> bool {
+        false
+    }
+
+    /// Stores credentials of a newly registered user. Called only if
+    /// [`AuthProvider::can_register`] returns `true`, after the user has been
+    /// added into the user list.
+    fn register(&self, _nickname: &str, _password: &str) -> io::Result<()> {
+        Result::Err(io::Error::new(io::ErrorKind::Unsupported, "Registration is not supported"))
+    }
+
+    /// Checks credentials of a user.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] with whether the user exists and the password is valid
+    ///  -  [`Result::Err`] if the identity system could not be asked or it has
+    ///     not answered in time, see
+    ///     [`AuthConfig::get_timeout`](mdchat_serverconf::AuthConfig::get_timeout)
+    fn authenticate(&self, nickname: &str, password: &str) -> io::Result<bool>;
+}
+
+/// Creates the authentication provider selected by global configuration.
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] with the name of the provider
+///  -  [`Result::Err`] if the configuration of the provider is incomplete
+pub fn load() -> Result<&'static str, String> {
+    let global_config = global_config();
+    let auth_config = global_config.auth().read().unwrap();
+    let timeout = auth_config.get_timeout();
+    let provider: Box<dyn AuthProvider> = match auth_config.get_provider() {
+        AuthProviderKind::Builtin => Box::new(builtin::BuiltinProvider),
+        AuthProviderKind::Command => Box::new(command::CommandProvider::new(auth_config.get_command(), timeout)?),
+        AuthProviderKind::Ldap => Box::new(ldap::LdapProvider::new(auth_config.get_ldap(), timeout)?),
+        AuthProviderKind::OAuth => Box::new(oauth::OAuthProvider::new(auth_config.get_oauth(), timeout)?),
+    };
+    let name = provider.name();
+    PROVIDER.set(provider).map_err(|_| panic!("Authentication provider already loaded")).unwrap();
+    Result::Ok(name)
+}
+
+/// Returns the authentication provider selected by global configuration.
+pub fn provider() -> &'static dyn AuthProvider {
+    PROVIDER.get().unwrap().as_ref()
+}
@@ -0,0 +1,43 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::auth::AuthProvider;
+use crate::user_list;
+
+use std::io;
+
+/// An [`AuthProvider`] which stores passwords in the user list of the server.
+pub struct BuiltinProvider;
+
+impl AuthProvider for BuiltinProvider {
+    fn name(&self) -> &'static str {
+        "builtin"
+    }
+
+    fn can_register(&self) -> bool {
+        true
+    }
+
+    fn register(&self, nickname: &str, password: &str) -> io::Result<()> {
+        user_list::set_password(nickname, password);
+        Result::Ok(())
+    }
+
+    fn authenticate(&self, nickname: &str, password: &str) -> io::Result<bool> {
+        Result::Ok(user_list::verify_password(nickname, password.to_string()))
+    }
+}
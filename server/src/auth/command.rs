@@ -0,0 +1,67 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::auth::AuthProvider;
+use crate::process;
+
+use std::io;
+use std::io::Write;
+use std::process::Command;
+use std::process::Stdio;
+use std::time::Duration;
+
+/// An [`AuthProvider`] which runs an external command. The nickname is passed as
+/// the last argument after `--`, so it is not taken for an option, and the
+/// password is written to the standard input, so it is not visible in the list
+/// of processes. The user is authenticated if the command exits successfully.
+pub struct CommandProvider {
+    program: String,
+    args: Vec<String>,
+    timeout: Duration,
+}
+
+impl CommandProvider {
+    /// Creates a new [`CommandProvider`] running given command: the program
+    /// followed by its arguments. The command is killed if it does not exit
+    /// within `timeout`.
+    pub fn new(command: &[String], timeout: Duration) -> Result<Self, String> {
+        match command.split_first() {
+            Option::Some((program, args)) =>
+                Result::Ok(Self { program: program.clone(), args: args.to_vec(), timeout }),
+            Option::None => Result::Err("`auth command` must be set to use the `command` provider".to_string()),
+        }
+    }
+}
+
+impl AuthProvider for CommandProvider {
+    fn name(&self) -> &'static str {
+        "command"
+    }
+
+    fn authenticate(&self, nickname: &str, password: &str) -> io::Result<bool> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .arg("--")
+            .arg(nickname)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()?;
+        // The command may exit without reading the password:
+        let _ = writeln!(child.stdin.take().unwrap(), "{}", password);
+        process::wait_with_timeout(&mut child, self.timeout).map(|status| status.success())
+    }
+}
@@ -0,0 +1,66 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::auth::AuthProvider;
+
+use ldap3::LdapConn;
+use ldap3::LdapConnSettings;
+use ldap3::dn_escape;
+
+use mdchat_serverconf::LdapAuthConfig;
+
+use std::io;
+use std::time::Duration;
+
+/// An [`AuthProvider`] which binds to an LDAP directory as the user. The user is
+/// authenticated if the bind succeeds.
+pub struct LdapProvider {
+    url: String,
+    user_dn: String,
+    timeout: Duration,
+}
+
+impl LdapProvider {
+    /// Creates a new [`LdapProvider`] as given by configuration. Connecting to the
+    /// directory and binding to it must each finish within `timeout`.
+    pub fn new(config: &LdapAuthConfig, timeout: Duration) -> Result<Self, String> {
+        match (config.get_url(), config.get_user_dn()) {
+            (Option::Some(url), Option::Some(user_dn)) =>
+                Result::Ok(Self { url: url.clone(), user_dn: user_dn.clone(), timeout }),
+            _ => Result::Err("`auth ldap url` and `auth ldap user-dn` must be set to use the `ldap` provider".to_string()),
+        }
+    }
+}
+
+impl AuthProvider for LdapProvider {
+    fn name(&self) -> &'static str {
+        "ldap"
+    }
+
+    fn authenticate(&self, nickname: &str, password: &str) -> io::Result<bool> {
+        // Bind with an empty password is an anonymous bind, which would succeed:
+        if password.is_empty() {
+            return Result::Ok(false)
+        }
+        let user_dn = self.user_dn.replace("{nickname}", &dn_escape(nickname));
+        let settings = LdapConnSettings::new().set_conn_timeout(self.timeout);
+        let mut ldap = LdapConn::with_settings(settings, &self.url).map_err(io::Error::other)?;
+        let result = ldap.with_timeout(self.timeout).simple_bind(&user_dn, password).map_err(io::Error::other)?;
+        let _ = ldap.unbind();
+        Result::Ok(result.success().is_ok())
+    }
+}
@@ -0,0 +1,81 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::auth::AuthProvider;
+
+use mdchat_serverconf::OAuthConfig;
+
+use serde_json::Value;
+
+use ureq::Agent;
+use ureq::AgentBuilder;
+
+use std::io;
+use std::time::Duration;
+
+/// An [`AuthProvider`] which treats the password as an OAuth access token and
+/// checks it using the token introspection endpoint (RFC 7662). The user is
+/// authenticated if the token is active and issued to the user.
+pub struct OAuthProvider {
+    agent: Agent,
+    introspection_url: String,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    nickname_claim: String,
+}
+
+impl OAuthProvider {
+    /// Creates a new [`OAuthProvider`] as given by configuration. Each request to
+    /// the introspection endpoint must finish within `timeout`.
+    pub fn new(config: &OAuthConfig, timeout: Duration) -> Result<Self, String> {
+        let introspection_url = config.get_introspection_url()
+            .ok_or("`auth oauth introspection-url` must be set to use the `oauth` provider".to_string())?;
+        Result::Ok(Self {
+            agent: AgentBuilder::new().timeout(timeout).build(),
+            introspection_url: introspection_url.clone(),
+            client_id: config.get_client_id().cloned(),
+            client_secret: config.get_client_secret().cloned(),
+            nickname_claim: config.get_nickname_claim().clone(),
+        })
+    }
+}
+
+impl AuthProvider for OAuthProvider {
+    fn name(&self) -> &'static str {
+        "oauth"
+    }
+
+    fn authenticate(&self, nickname: &str, password: &str) -> io::Result<bool> {
+        // Client credentials are sent in the request body:
+        let mut form = vec![("token", password), ("token_type_hint", "access_token")];
+        if let Option::Some(client_id) = &self.client_id {
+            form.push(("client_id", client_id));
+        }
+        if let Option::Some(client_secret) = &self.client_secret {
+            form.push(("client_secret", client_secret));
+        }
+        let response = self.agent.post(&self.introspection_url)
+            .send_form(&form)
+            .map_err(io::Error::other)?
+            .into_string()?;
+        let response: Value = serde_json::from_str(&response)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let active = response["active"].as_bool().unwrap_or(false);
+        let issued_to_user = response[&self.nickname_claim].as_str() == Option::Some(nickname);
+        Result::Ok(active && issued_to_user)
+    }
+}
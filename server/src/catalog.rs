@@ -0,0 +1,142 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module for the message catalogs, which contain texts of errors, warnings and
+//! notices the server sends to clients in different languages. Each client is
+//! sent messages in the locale negotiated when it connects, see [`negotiate`].
+//! Messages are usually formatted using [`tr!`](crate::tr) macro.
+
+use crate::global_config;
+
+use mdchat_serverconf::LocaleConfig;
+use mdchat_serverconf::locale::BUILTIN_LOCALE;
+
+use once_cell::sync::Lazy;
+use once_cell::sync::OnceCell;
+
+use tracing::warn;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+
+/// A catalog mapping keys of messages to their texts.
+type Catalog = HashMap<String, String>;
+
+/// The catalog of messages built into the server.
+static BUILTIN: Lazy<Catalog> = Lazy::new(|| parse(include_str!("../locale/en.catalog"))
+    .expect("Built-in catalog is not valid"));
+
+/// Catalogs given by global configuration, mapped by their normalized locale tags.
+static CATALOGS: OnceCell<HashMap<String, Catalog>> = OnceCell::new();
+
+/// Loads catalogs given by global configuration. Keys which are not in the
+/// built-in catalog are reported as warnings, since they are probably misspelled
+/// or left from an older version of the server.
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] with the number of loaded catalogs
+///  -  [`Result::Err`] if a catalog file could not be read or is not valid
+pub fn load() -> io::Result<usize> {
+    let catalog_files = global_config().locale().read().unwrap().get_catalogs().clone();
+    let mut catalogs = HashMap::new();
+    for (tag, path) in catalog_files {
+        let contents = fs::read_to_string(&path)?;
+        let catalog = parse(&contents)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), err)))?;
+        for key in catalog.keys().filter(|key| !BUILTIN.contains_key(*key)) {
+            warn!("Catalog {} contains unknown message `{}`", path.display(), key);
+        }
+        catalogs.insert(tag, catalog);
+    }
+    let count = catalogs.len();
+    CATALOGS.set(catalogs).map_err(|_| panic!("Catalogs already loaded")).unwrap();
+    Result::Ok(count)
+}
+
+/// Parses a catalog file. Each line contains a key, `=` and the text of
+/// a message; empty lines and lines starting with `#` are ignored.
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] with the parsed catalog
+///  -  [`Result::Err`] describing the first invalid line
+fn parse(contents: &str) -> Result<Catalog, String> {
+    let mut catalog = HashMap::new();
+    for (line_num, line) in (1u32..).zip(contents.lines()) {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue
+        }
+        let (key, text) = line.split_once('=')
+            .ok_or(format!("line {}: `=` was expected after the key", line_num))?;
+        catalog.insert(key.trim().to_string(), text.trim().to_string());
+    }
+    Result::Ok(catalog)
+}
+
+/// Returns the locale used for clients which have not asked for any locale, and
+/// for notices, as given by global configuration.
+pub fn default_locale() -> String {
+    global_config().locale().read().unwrap().get_default().clone()
+}
+
+/// Chooses the locale of messages for a client, which has asked for given
+/// locale. The locale is used if there is a catalog for it, otherwise
+/// a catalog of the same language is used, such as `pt` for `pt-BR`. If there
+/// is no such catalog, the default locale is used.
+///
+/// # Parameters
+///
+///  -  `requested`: the locale the client has asked for, if any
+pub fn negotiate(requested: Option<&str>) -> String {
+    let requested = match requested.filter(|requested| LocaleConfig::is_valid_tag(requested)) {
+        Option::Some(requested) => LocaleConfig::normalize_tag(requested),
+        Option::None => return default_locale(),
+    };
+    let is_available = |tag: &str| tag == BUILTIN_LOCALE || CATALOGS.get().is_some_and(|catalogs| catalogs.contains_key(tag));
+    let language = requested.split('-').next().unwrap();
+    match (is_available(&requested), is_available(language)) {
+        (true, _) => requested,
+        (false, true) => language.to_string(),
+        (false, false) => default_locale(),
+    }
+}
+
+/// Returns the text of the message with given key in given locale, with
+/// placeholders replaced by given values. Messages missing in the catalog of the
+/// locale are taken from the catalog of the default locale, and then from the
+/// built-in catalog. If the key is unknown, the key itself is returned.
+///
+/// # Parameters
+///
+///  -  `locale`: normalized tag of the locale, see [`negotiate`]
+///  -  `key`: key of the message
+///  -  `args`: names of placeholders and their values
+pub fn text(locale: &str, key: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+    let default_locale = default_locale();
+    let catalogs = CATALOGS.get();
+    let template = [locale, default_locale.as_str()].iter()
+        .filter_map(|locale| catalogs.and_then(|catalogs| catalogs.get(*locale)))
+        .chain([&*BUILTIN])
+        .find_map(|catalog| catalog.get(key))
+        .map(String::as_str)
+        .unwrap_or(key);
+    args.iter().fold(template.to_string(), |text, (name, value)| text.replace(&format!("{{{}}}", name), &value.to_string()))
+}
@@ -0,0 +1,48 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use mdchat_common::channel::ChannelInfo;
+use mdchat_common::channel::ChannelMode;
+
+use std::collections::BTreeSet;
+
+#[derive(Clone)]
+pub struct Channel {
+    pub name: String,
+    pub topic: Option<String>,
+    pub mode: ChannelMode,
+    pub members: BTreeSet<String>,
+    pub owner: Option<String>,
+    pub moderators: BTreeSet<String>,
+}
+
+impl Channel {
+    /// Returns information about the channel which can be sent to clients.
+    pub fn info(&self) -> ChannelInfo {
+        ChannelInfo::new(self.name.clone(), self.topic.clone(), self.mode)
+    }
+
+    /// Returns whether given user can read messages sent to the channel.
+    pub fn can_read(&self, nickname: &str) -> bool {
+        self.mode != ChannelMode::InviteOnly || self.members.contains(nickname)
+    }
+
+    /// Returns whether given user is the owner or a moderator of the channel.
+    pub fn is_moderator(&self, nickname: &str) -> bool {
+        self.owner.as_deref() == Option::Some(nickname) || self.moderators.contains(nickname)
+    }
+}
@@ -0,0 +1,309 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::sync::RwLock;
+use once_cell::sync::Lazy;
+
+use mdchat_common::channel::ChannelInfo;
+use mdchat_common::channel::ChannelMode;
+
+use crate::channel::Channel;
+use crate::global_config;
+use crate::state;
+
+static CHANNEL_LIST: Lazy<RwLock<BTreeMap<String, Channel>>> = Lazy::new(|| RwLock::new(BTreeMap::new()));
+
+/// Adds a new channel without a topic and members into the list of channels. If
+/// the channel already exists, nothing happens.
+pub fn add_channel(name: String, mode: ChannelMode) {
+    CHANNEL_LIST.write().unwrap()
+        .entry(name.clone())
+        .or_insert(Channel {
+            name,
+            topic: Option::None,
+            mode,
+            members: BTreeSet::new(),
+            owner: Option::None,
+            moderators: BTreeSet::new(),
+        });
+}
+
+/// Creates a new channel owned by given user, who becomes its member, and saves
+/// channels created by users into the state file given by global configuration.
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] with information about the created channel, or with
+///     [`Option::None`] if the channel already exists
+///  -  [`Result::Err`] if the state file or the membership file could not be
+///     written; the channel exists anyway
+pub fn create_channel(name: String, mode: ChannelMode, owner: String) -> io::Result<Option<ChannelInfo>> {
+    let mut channel_list = CHANNEL_LIST.write().unwrap();
+    if channel_list.contains_key(&name) {
+        return Result::Ok(Option::None)
+    }
+    let channel = Channel {
+        name: name.clone(),
+        topic: Option::None,
+        mode,
+        members: BTreeSet::from([owner.clone()]),
+        owner: Option::Some(owner),
+        moderators: BTreeSet::new(),
+    };
+    let info = channel.info();
+    channel_list.insert(name, channel);
+    drop(channel_list);
+    save_channels()?;
+    save_members()?;
+    Result::Ok(Option::Some(info))
+}
+
+/// Removes given channel from the list of channels and saves channels created by
+/// users and members of channels.
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] with the removed channel, or with [`Option::None`] if the
+///     channel does not exist
+///  -  [`Result::Err`] if the state file or the membership file could not be
+///     written; the channel is removed anyway
+pub fn remove_channel(name: &str) -> io::Result<Option<Channel>> {
+    let removed = CHANNEL_LIST.write().unwrap().remove(name);
+    if let Option::Some(channel) = &removed {
+        save_channels()?;
+        save_members()?;
+        if channel.topic.is_some() {
+            state::save()?;
+        }
+    }
+    Result::Ok(removed)
+}
+
+/// Returns the owner of given channel, or [`Option::None`] if the channel does
+/// not exist or is given by configuration.
+pub fn get_owner(name: &str) -> Option<String> {
+    CHANNEL_LIST.read().unwrap().get(name).and_then(|channel| channel.owner.clone())
+}
+
+/// Returns whether given user is the owner or a moderator of given channel.
+pub fn is_moderator(name: &str, nickname: &str) -> bool {
+    CHANNEL_LIST.read().unwrap()
+        .get(name)
+        .is_some_and(|channel| channel.is_moderator(nickname))
+}
+
+/// Appoints or dismisses a moderator of given channel and saves channels created
+/// by users.
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] with whether anything has changed
+///  -  [`Result::Err`] if the state file could not be written; the change is
+///     made anyway
+pub fn set_moderator(name: &str, nickname: String, moderator: bool) -> io::Result<bool> {
+    let changed = CHANNEL_LIST.write().unwrap()
+        .get_mut(name)
+        .is_some_and(|channel| match moderator {
+            true => channel.moderators.insert(nickname),
+            false => channel.moderators.remove(&nickname),
+        });
+    if changed {
+        save_channels()?;
+    }
+    Result::Ok(changed)
+}
+
+/// Sets the topic of given channel and returns updated information about the
+/// channel, or [`Option::None`] if the channel does not exist. Topics should be
+/// saved into the state file afterwards.
+pub fn set_topic(name: &str, topic: Option<String>) -> Option<ChannelInfo> {
+    let mut channel_list = CHANNEL_LIST.write().unwrap();
+    let channel = channel_list.get_mut(name)?;
+    channel.topic = topic;
+    Option::Some(channel.info())
+}
+
+/// Sets the topic of given channel loaded from the state file. If the channel
+/// does not exist, nothing happens.
+pub fn restore_topic(name: &str, topic: String) {
+    if let Option::Some(channel) = CHANNEL_LIST.write().unwrap().get_mut(name) {
+        channel.topic = Option::Some(topic);
+    }
+}
+
+/// Returns topics of all channels which have one, by names of the channels.
+pub fn topics() -> BTreeMap<String, String> {
+    CHANNEL_LIST.read().unwrap()
+        .values()
+        .filter_map(|channel| channel.topic.clone().map(|topic| (channel.name.clone(), topic)))
+        .collect()
+}
+
+/// Returns the mode of given channel, or [`Option::None`] if the channel does
+/// not exist.
+pub fn get_mode(name: &str) -> Option<ChannelMode> {
+    CHANNEL_LIST.read().unwrap().get(name).map(|channel| channel.mode)
+}
+
+/// Returns whether given user is a member of given channel.
+pub fn is_member(name: &str, nickname: &str) -> bool {
+    CHANNEL_LIST.read().unwrap()
+        .get(name)
+        .is_some_and(|channel| channel.members.contains(nickname))
+}
+
+/// Returns whether given user can read messages sent to given channel.
+/// [`Option::None`] stands for the main chat, which everybody can read.
+pub fn can_read(name: Option<&String>, nickname: &str) -> bool {
+    match name {
+        Option::Some(name) => CHANNEL_LIST.read().unwrap()
+            .get(name)
+            .is_some_and(|channel| channel.can_read(nickname)),
+        Option::None => true,
+    }
+}
+
+/// Returns names of all channels given user is a member of, ordered by their
+/// names.
+pub fn channels_of(nickname: &str) -> Vec<String> {
+    CHANNEL_LIST.read().unwrap()
+        .values()
+        .filter(|channel| channel.members.contains(nickname))
+        .map(|channel| channel.name.clone())
+        .collect()
+}
+
+/// Makes given user a member of given channel and saves members of all channels
+/// into the membership file given by global configuration.
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] with whether the user has not been a member yet
+///  -  [`Result::Err`] if the membership file could not be written; the user
+///     is a member anyway
+pub fn add_member(name: &str, nickname: String) -> io::Result<bool> {
+    let added = CHANNEL_LIST.write().unwrap()
+        .get_mut(name)
+        .is_some_and(|channel| channel.members.insert(nickname));
+    if added {
+        save_members()?;
+    }
+    Result::Ok(added)
+}
+
+/// Removes given user from members of given channel and saves members of all
+/// channels into the membership file given by global configuration.
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] with whether the user has been a member
+///  -  [`Result::Err`] if the membership file could not be written; the user
+///     is not a member anyway
+pub fn remove_member(name: &str, nickname: &str) -> io::Result<bool> {
+    let removed = CHANNEL_LIST.write().unwrap()
+        .get_mut(name)
+        .is_some_and(|channel| channel.members.remove(nickname));
+    if removed {
+        save_members()?;
+    }
+    Result::Ok(removed)
+}
+
+/// Loads channels created by users from the state file given by global
+/// configuration. Each line of the file contains the name, the mode and the
+/// owner of a channel followed by its moderators.
+pub fn load_channels() -> io::Result<()> {
+    let state_file = global_config().channels().read().unwrap().get_state_file().clone();
+    let content = match fs::read_to_string(&state_file) {
+        Result::Ok(content) => content,
+        Result::Err(err) if err.kind() == io::ErrorKind::NotFound => return Result::Ok(()),
+        Result::Err(err) => return Result::Err(err),
+    };
+    let mut channel_list = CHANNEL_LIST.write().unwrap();
+    for line in content.lines() {
+        let mut split = line.split(' ');
+        let (name, mode, owner) = match (split.next(), split.next(), split.next()) {
+            (Option::Some(name), Option::Some(mode), Option::Some(owner)) => (name, mode, owner),
+            _ => return Result::Err(io::Error::new(io::ErrorKind::InvalidData, format!("Invalid line `{}`", line))),
+        };
+        let mode = mode.parse().map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        channel_list.entry(name.to_string()).or_insert(Channel {
+            name: name.to_string(),
+            topic: Option::None,
+            mode,
+            members: BTreeSet::new(),
+            owner: Option::Some(owner.to_string()),
+            moderators: split.map(str::to_string).collect(),
+        });
+    }
+    Result::Ok(())
+}
+
+/// Loads members of channels from the membership file given by global
+/// configuration. Members of channels which do not exist are ignored.
+pub fn load_members() -> io::Result<()> {
+    let membership_file = global_config().channels().read().unwrap().get_membership_file().clone();
+    let content = match fs::read_to_string(&membership_file) {
+        Result::Ok(content) => content,
+        Result::Err(err) if err.kind() == io::ErrorKind::NotFound => return Result::Ok(()),
+        Result::Err(err) => return Result::Err(err),
+    };
+    let mut channel_list = CHANNEL_LIST.write().unwrap();
+    for (name, nickname) in content.lines().filter_map(|line| line.split_once(' ')) {
+        if let Option::Some(channel) = channel_list.get_mut(name) {
+            channel.members.insert(nickname.to_string());
+        }
+    }
+    Result::Ok(())
+}
+
+#[doc(hidden)]
+fn save_members() -> io::Result<()> {
+    let membership_file = global_config().channels().read().unwrap().get_membership_file().clone();
+    let content: String = CHANNEL_LIST.read().unwrap()
+        .values()
+        .flat_map(|channel| channel.members.iter().map(move |nickname| format!("{} {}\n", channel.name, nickname)))
+        .collect();
+    fs::write(membership_file, content)
+}
+
+#[doc(hidden)]
+fn save_channels() -> io::Result<()> {
+    let state_file = global_config().channels().read().unwrap().get_state_file().clone();
+    let content: String = CHANNEL_LIST.read().unwrap()
+        .values()
+        .filter_map(|channel| channel.owner.as_ref().map(|owner| {
+            let moderators: String = channel.moderators.iter().map(|moderator| format!(" {}", moderator)).collect();
+            format!("{} {} {}{}\n", channel.name, channel.mode, owner, moderators)
+        }))
+        .collect();
+    fs::write(state_file, content)
+}
+
+/// Returns information about all channels given user can read, ordered by their
+/// names. Invite-only channels are listed only to their members.
+pub fn infos_for(nickname: &str) -> Vec<ChannelInfo> {
+    CHANNEL_LIST.read().unwrap()
+        .values()
+        .filter(|channel| channel.can_read(nickname))
+        .map(Channel::info)
+        .collect()
+}
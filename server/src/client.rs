@@ -15,51 +15,192 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+mod handler;
+
+use chrono::DateTime;
+use chrono::Utc;
+
+use crate::archive;
+use crate::catalog;
+use crate::channel_list;
 use crate::client_list;
+use crate::connection_state::ConnectionState;
+use crate::auth;
 use crate::decrypt;
+use crate::email;
 use crate::encrypt;
+use crate::filter_stats;
+use crate::gateway;
 use crate::global_config;
-use crate::log;
+use crate::identity;
+use crate::listener;
+use crate::lockdown;
+use crate::mailbox;
 use crate::message_queue;
 use crate::message_list;
+use crate::moderation;
+use crate::outbound::OutboundQueue;
+use crate::permission::PermissionChecker;
+use crate::presence;
+use crate::quota;
+use crate::raid;
+use crate::ratelimit;
+use crate::review_queue;
+use crate::review_queue::Held;
+use crate::rules;
+use crate::state;
+use crate::supervisor;
 use crate::user_list;
+use crate::welcome;
 
+use mdchat_common::account::AccountRecord;
+use mdchat_common::channel::ChannelMode;
+use mdchat_common::codec;
+use mdchat_common::codec::Encoding;
+use mdchat_common::compression;
+use mdchat_common::compression::Compression;
 use mdchat_common::command::c2s;
 use mdchat_common::command::s2c;
+use mdchat_common::connection::ConnectionStats;
+use mdchat_common::hello::ClientVersion;
+use mdchat_common::hello::PROTOCOL_VERSION;
+use mdchat_common::hello::ServerInfo;
+use mdchat_common::hello::ServerLimits;
+use mdchat_common::log::LogLevel;
+use mdchat_common::log::LogRecord;
 use mdchat_common::login::LoginRequest;
+use mdchat_common::message;
+use mdchat_common::message::DirectMessage;
+use mdchat_common::message::Message;
+use mdchat_common::message::MessageKind;
+use mdchat_common::message::COLOR_COUNT;
+use mdchat_common::moderation::FilterKind;
+use mdchat_common::moderation::ModEvent;
+use mdchat_common::moderation::ModEventKind;
+use mdchat_common::profile::Profile;
+use mdchat_common::quota::QuotaKind;
+use mdchat_common::sanitize;
+use mdchat_common::time::TimeSync;
+use mdchat_common::user::Availability;
+use mdchat_common::Error;
 
-use mdlog::LogLevel;
+use mdchat_serverconf::Capability;
+use mdchat_serverconf::RateLimitBucket;
+use mdchat_serverconf::Role;
 
 use mdswp::MdswpStream;
 
+use regex::RegexBuilder;
+
+use once_cell::sync::OnceCell;
+
+use tracing::debug;
+use tracing::error;
+use tracing::field;
+use tracing::info;
+use tracing::info_span;
+use tracing::warn;
+
+use std::collections::BTreeMap;
 use std::io;
 use std::io::Read;
 use std::io::Write;
 use std::mem::size_of;
 use std::net::SocketAddr;
+use std::num::NonZeroU32;
 use std::sync::Arc;
 use std::sync::RwLock;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU32;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::time::Instant;
 
 pub struct Client {
     socket_addr: SocketAddr,
+    forwarded_for: OnceCell<SocketAddr>,
+    admission_pending: AtomicBool,
     stream: RwLock<MdswpStream>,
+    state: RwLock<ConnectionState>,
     nickname: RwLock<Option<String>>,
+    tail_level: RwLock<Option<LogLevel>>,
+    mod_events: AtomicBool,
+    connected_at: DateTime<Utc>,
+    last_activity: RwLock<DateTime<Utc>>,
+    messages_sent: AtomicU64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    last_message: RwLock<Option<Instant>>,
+    client_version: RwLock<Option<ClientVersion>>,
+    protocol_version: AtomicU32,
+    encoding: RwLock<Encoding>,
+    compression: RwLock<Compression>,
+    pending_verification: RwLock<Option<String>>,
+    last_delivered_msg_id: AtomicU64,
+    outbound: OutboundQueue,
+    listener: SocketAddr,
+    admin_only: bool,
+    read_only: AtomicBool,
+    torn_down: AtomicBool,
+    suppress_echo: AtomicBool,
+    locale: RwLock<String>,
 }
 
 impl Client {
-    /// Creates a new [`Client`] instance from given [`MdswpStream`].
+    /// Creates a new [`Client`] instance from given [`MdswpStream`] accepted by
+    /// the listener bound to `listener` socket address. If `admin_only` is
+    /// `true`, the connection has been accepted on an admin-only listener, so
+    /// only administrators and moderators can log in. If
+    /// `from_gateway` is `true`, the connection may have been bridged by the
+    /// WebSocket gateway, so it is admitted only after its first command, see
+    /// [`listener::admit`].
     ///
     /// > **Note!**
     /// >
     /// > There should be no [`MdswpStream`] socket clones. If reading or writing to
     /// > the [`MdswpStream`] socket is done outside this struct, it leads to
     /// > unpredictable behavior.
-    pub fn new(stream: MdswpStream) -> Arc<Client> {
-        Arc::new(Self {
+    pub fn new(stream: MdswpStream, listener: SocketAddr, admin_only: bool, from_gateway: bool) -> Arc<Client> {
+        let client = Arc::new(Self {
             socket_addr: stream.peer_addr().unwrap(),
+            forwarded_for: OnceCell::new(),
+            admission_pending: AtomicBool::new(from_gateway),
             stream: RwLock::new(stream),
-            nickname: RwLock::new(Option::None)
-        })
+            state: RwLock::new(ConnectionState::Connected),
+            nickname: RwLock::new(Option::None),
+            tail_level: RwLock::new(Option::None),
+            mod_events: AtomicBool::new(false),
+            connected_at: Utc::now(),
+            last_activity: RwLock::new(Utc::now()),
+            messages_sent: AtomicU64::new(0),
+            bytes_in: AtomicU64::new(0),
+            bytes_out: AtomicU64::new(0),
+            last_message: RwLock::new(Option::None),
+            client_version: RwLock::new(Option::None),
+            // Clients which do not say hello are expected to use the first
+            // version of the protocol:
+            protocol_version: AtomicU32::new(1),
+            encoding: RwLock::new(Encoding::Json),
+            compression: RwLock::new(Compression::None),
+            pending_verification: RwLock::new(Option::None),
+            last_delivered_msg_id: AtomicU64::new(0),
+            outbound: OutboundQueue::new(),
+            listener,
+            admin_only,
+            read_only: AtomicBool::new(false),
+            torn_down: AtomicBool::new(false),
+            suppress_echo: AtomicBool::new(false),
+            locale: RwLock::new(catalog::default_locale()),
+        });
+        // Commands are written by a separate thread, so slow clients do not block
+        // the threads sending commands to them:
+        supervisor::spawn_once(
+            format!("client {} writer", client.socket_addr),
+            cls_clone!(client -> move || client.writer_thread()),
+            cls_clone!(client -> move || client.teardown()),
+        ).unwrap();
+        client
     }
 
     /// Returns client's socket address
@@ -67,6 +208,26 @@ impl Client {
         &self.socket_addr
     }
 
+    /// Returns the socket address of the user: the address of the browser if the
+    /// connection has been bridged by the WebSocket gateway, or
+    /// [`Client::socket_addr`] otherwise. Bans, rate limits and raid detection
+    /// apply to this address.
+    pub fn remote_addr(&self) -> &SocketAddr {
+        self.forwarded_for.get().unwrap_or(&self.socket_addr)
+    }
+
+    /// Returns the socket address of the listener which has accepted the
+    /// connection, as given by `listen` option.
+    pub fn listener(&self) -> &SocketAddr {
+        &self.listener
+    }
+
+    /// Returns whether the connection has been accepted on an admin-only
+    /// listener, so only administrators and moderators can log in.
+    pub fn is_admin_only(&self) -> bool {
+        self.admin_only
+    }
+
     /// Returns what nickname is client logged into.
     ///
     /// # Return value
@@ -77,68 +238,304 @@ impl Client {
         self.nickname.write().unwrap().clone()
     }
 
+    /// Sets the nickname of the user the client is logged in as. It should be
+    /// called only by [`client_list::log_in`], so the number of connections of
+    /// the user is counted correctly.
+    pub fn set_nickname(&self, nickname: String) {
+        *self.nickname.write().unwrap() = Option::Some(nickname);
+    }
+
+    /// Returns the version of the protocol negotiated with the client, see
+    /// [`PROTOCOL_VERSION`]. Clients which have not said hello use version 1.
+    pub fn protocol_version(&self) -> u32 {
+        self.protocol_version.load(Ordering::Relaxed)
+    }
+
+    /// Returns the time the last command has been received from the client, or
+    /// the time it has connected if it has not sent any command yet.
+    pub fn last_activity(&self) -> DateTime<Utc> {
+        *self.last_activity.read().unwrap()
+    }
+
+    /// Returns the state of the connection, which determines which commands the
+    /// client can send.
+    pub fn state(&self) -> ConnectionState {
+        *self.state.read().unwrap()
+    }
+
+    /// Returns the locale of messages sent to the client, which has been
+    /// negotiated when the client connected, see [`catalog::negotiate`]. Before
+    /// that, the default locale is used.
+    pub fn locale(&self) -> String {
+        self.locale.read().unwrap().clone()
+    }
+
+    /// Returns statistics of the client's connection.
+    pub fn stats(&self) -> ConnectionStats {
+        ConnectionStats::new(
+            *self.remote_addr(),
+            self.nickname(),
+            self.connected_at,
+            *self.last_activity.read().unwrap(),
+            self.messages_sent.load(Ordering::Relaxed),
+            self.bytes_in.load(Ordering::Relaxed),
+            self.bytes_out.load(Ordering::Relaxed),
+        ).with_client_version(self.client_version.read().unwrap().clone())
+    }
+
     /// This is a method that should be run is a seperate thread each time after
     /// a new [`Client`] instance is constructed.
     pub fn client_thread(&self) {
-        while !self.is_err() {
+        let connection_span = info_span!("connection", peer = %self.socket_addr, forwarded_for = field::Empty,
+                                         nickname = field::Empty);
+        let _connection_span = connection_span.enter();
+        while self.state().is_open() && !self.is_err() {
+            // Stop reading commands while the client is receiving commands slower
+            // than they are sent to it:
+            if !self.outbound.wait_until_resumed() {
+                warn!("Disconnected a client which has been too slow to receive commands");
+                self.disconnect_slow();
+                break
+            }
             // Next command:
             let command = match self.recv_command() {
                 Result::Ok(Option::Some(command)) => command,
-                Result::Ok(Option::None) => {
-                    let _ = self.stream.write().unwrap().finish_write();
-                    return
-                }
-                Result::Err(err) => {
+                Result::Ok(Option::None) => break,
+                // The client is told why it is being disconnected, unless the
+                // connection itself has failed:
+                Result::Err(err) if err.is_fatal() => {
                     self.error(err.to_string());
-                    return
+                    break
+                },
+                Result::Err(err) => {
+                    debug!("Connection failed: {}", err);
+                    self.outbound.close(true);
+                    break
                 },
             };
+            // Connections from the gateway are admitted once it has told the address
+            // of the browser:
+            if self.admission_pending.swap(false, Ordering::AcqRel) {
+                let forwarded = self.on_forwarded(&command);
+                if !listener::admit(*self.remote_addr()) {
+                    self.outbound.close(true);
+                    break
+                }
+                if forwarded {
+                    connection_span.record("forwarded_for", field::display(self.remote_addr()));
+                    continue
+                }
+            }
+            // Commands unknown to the server are ignored, so newer clients can talk
+            // to older servers:
+            if let c2s::Command::Unknown(unknown) = &command {
+                self.on_unknown_command(unknown.name());
+                continue
+            }
+            // Commands which cannot be sent in the current state of the connection
+            // are rejected the same way regardless of the command:
+            if !self.state().allows(&command) {
+                self.reject_command(&command);
+                break
+            }
             // Process command:
-            match command {
-                c2s::Command::Login(request) => self.on_login(request),
-                c2s::Command::SendMessage(text) => self.on_message(text),
-            };
+            let received_at = Utc::now();
+            let _command_span = info_span!("command", name = command.name()).entered();
+            match handler::get(command.name()) {
+                Option::Some(handler) => handler.handle(self, command, received_at),
+                Option::None => error!("No handler of `{}` is registered", command.name()),
+            }
+            // Record the activity of the user:
+            if let Option::Some(nickname) = self.nickname() {
+                connection_span.record("nickname", nickname.as_str());
+                user_list::set_last_seen(&nickname, Utc::now());
+            }
+        }
+        // Remove connection when error occurred or the client disconnected:
+        self.teardown();
+    }
+
+    /// Tears the connection down: commands already queued for the client are
+    /// still written before the connection is finished, the connection is removed
+    /// from the client list and other users are told the user has left, if it was
+    /// the last connection of the user.
+    ///
+    /// The connection can fail in the thread of the client, in its writer thread
+    /// or while other threads send commands to it, and the failure can be noticed
+    /// by the keepalive reaper, too. Therefore this method can be called any number
+    /// of times from any thread, but the connection is torn down only once.
+    pub fn teardown(&self) {
+        if self.torn_down.swap(true, Ordering::AcqRel) {
+            return
         }
-        // Remove connection when error occurred:
-        client_list::remove_connection(&self.socket_addr);
+        self.outbound.close(false);
+        self.set_state(ConnectionState::Closed);
+        let last_connection = client_list::remove_connection(self).unwrap_or(false);
+        // Tell other users the user has left:
+        if let Option::Some(nickname) = self.nickname() {
+            presence::left(nickname, last_connection);
+        }
+        debug!("Connection of {} has been torn down", self.socket_addr);
     }
 
     /// Returns if the underlying [`MdswpStream`] has errored. See
     /// [`MdswpStream::is_err`] for more information.
     pub fn is_err(&self) -> bool {
-        self.stream.read().unwrap().is_err()
+        self.outbound.is_closed() || self.stream.read().unwrap().is_err()
     }
 
     /// Sends given [`s2c::Command`] to the client. Since [`ClientInfo`] uses
     /// synchronization internally, this method can be called concurrently in
-    /// different threads.
-    pub fn send_command(&self, command: s2c::Command) -> io::Result<()> {
-        let json = serde_json::to_string(&command).unwrap();
-        let encrypted = encrypt(&json.into_bytes());
+    /// different threads. The command is only queued, it is written to the
+    /// connection by the writer thread of the client.
+    pub fn send_command(&self, command: s2c::Command) -> Result<(), Error> {
+        // Commands are wrapped in envelopes only for clients which understand
+        // them:
+        let encoded = match self.protocol_version.load(Ordering::Relaxed) {
+            1 => serde_json::to_vec(&command)?,
+            _ => self.encoding.read().unwrap().encode(&command)?,
+        };
+        let compressed = self.compression.read().unwrap().compress(encoded);
+        let encrypted = encrypt(&compressed);
         if encrypted.len() > u32::MAX as usize {
-            return Result::Err(io::Error::new(io::ErrorKind::InvalidInput, "Data too large"));
+            return Result::Err(Error::Framing("Data too large".to_string()));
+        }
+        let mut frame = Vec::with_capacity(size_of::<u32>() + encrypted.len());
+        frame.extend_from_slice(&(encrypted.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&encrypted);
+        let result = self.outbound.push(frame).map_err(Error::from);
+        // The queue is discarded if the client is too slow:
+        if matches!(&result, Result::Err(err) if err.io_kind() == Option::Some(io::ErrorKind::TimedOut)) {
+            self.disconnect_slow();
+        }
+        result
+    }
+
+    /// Returns the ID of the newest message sent to the client, or 0 if no message
+    /// has been sent yet.
+    pub fn last_delivered_msg_id(&self) -> u64 {
+        self.last_delivered_msg_id.load(Ordering::SeqCst)
+    }
+
+    /// Records that the message with given ID has been sent to the client.
+    pub fn set_last_delivered_msg_id(&self, message_id: u64) {
+        self.last_delivered_msg_id.fetch_max(message_id, Ordering::SeqCst);
+    }
+
+    /// Returns whether the client does not want to receive messages it has sent
+    /// itself.
+    pub fn is_echo_suppressed(&self) -> bool {
+        self.suppress_echo.load(Ordering::Relaxed)
+    }
+
+    /// Sends given [`LogRecord`] to the client, if the client is subscribed to
+    /// the server log and the record is severe enough. If the record cannot be
+    /// sent, the connection is reset. This method is called while logging, so
+    /// it avoids logging itself; records logged meanwhile anyway, for example
+    /// when the client is too slow, are not forwarded to administrators.
+    pub fn send_log_record(&self, record: &LogRecord) {
+        let tail_level = *self.tail_level.read().unwrap();
+        if tail_level.is_none_or(|level| record.level() < level) {
+            return
+        }
+        if self.send_command(s2c::Command::LogRecord(record.clone())).is_err() {
+            self.outbound.close(true);
+        }
+    }
+
+    /// Sends given [`ModEvent`] to the client, if the client is subscribed to
+    /// moderation events. If the event cannot be sent, the connection is reset.
+    pub fn send_mod_event(&self, event: &ModEvent) {
+        if !self.mod_events.load(Ordering::Relaxed) {
+            return
+        }
+        if self.send_command(s2c::Command::ModEvent(event.clone())).is_err() {
+            self.outbound.close(true);
         }
-        let mut stream = self.stream.write().unwrap();
-        stream.write_all(&(encrypted.len() as u32).to_be_bytes())?;
-        stream.write_all(&encrypted)?;
-        stream.flush()?;
-        Result::Ok(())
     }
 
     /// This method should be used to signal an error. This method will
     /// automatically inform client about error that happened and will close the
     /// connection.
     pub fn error(&self, err: String) {
-        // Send response back to client:
+        // Send response back to client, the connection is closed after the
+        // response is written:
         let command = s2c::Command::Error(err.to_string());
         let _ = self.send_command(command);
-        let mut stream = self.stream.write().unwrap();
+        self.outbound.close(true);
+        self.set_state(ConnectionState::Draining);
+    }
+
+    /// Changes the state of the connection, if the connection can change from
+    /// its current state into given one, see [`ConnectionState::can_become`].
+    #[doc(hidden)]
+    fn set_state(&self, next: ConnectionState) {
+        let mut state = self.state.write().unwrap();
+        match state.can_become(next) {
+            true => *state = next,
+            false => debug!("Connection of {} cannot change from {} state to {} state", self.socket_addr, *state, next),
+        }
+    }
+
+    /// Disconnects the client, because it has sent given command, which the
+    /// current state of the connection does not allow.
+    #[doc(hidden)]
+    fn reject_command(&self, command: &c2s::Command) {
+        let state = self.state();
+        if !state.is_open() {
+            return
+        }
+        warn!("Rejected command {} sent in {} state", command.name(), state);
+        match ConnectionState::Authenticated.allows(command) && !state.is_authenticated() {
+            true => self.error(tr!(self.locale(), "state.not-logged-in", command = command.name())),
+            false => self.error(tr!(self.locale(), "state.unexpected-command", command = command.name())),
+        }
+    }
+
+    /// Writes queued commands to the connection until the queue is closed. Commands
+    /// queued at the same time are written at once. This method is run in a
+    /// separate thread for each client.
+    #[doc(hidden)]
+    fn writer_thread(&self) {
+        let mut stream = match self.stream.read().unwrap().try_clone() {
+            Result::Ok(stream) => stream,
+            Result::Err(_) => {
+                self.disconnect_slow();
+                return
+            },
+        };
+        while let Option::Some(batch) = self.outbound.pop_batch() {
+            if let Result::Err(err) = stream.write_all(&batch).and_then(|()| stream.flush()) {
+                debug!("Could not write to {}: {}", self.socket_addr, err);
+                self.outbound.abort();
+                break
+            }
+            self.bytes_out.fetch_add(batch.len() as u64, Ordering::Relaxed);
+        }
         let _ = stream.finish_write();
-        let _ = stream.reset();
+        if self.outbound.is_reset() {
+            let _ = stream.reset();
+        }
+    }
+
+    /// Disconnects the client, because it has not sent anything for longer than
+    /// given timeout, not even an answer to a keepalive ping.
+    pub fn disconnect_idle(&self, idle_timeout: Duration) {
+        info!("Disconnected {}, which has been silent for more than {} seconds", self.socket_addr, idle_timeout.as_secs());
+        self.disconnect_slow();
+    }
+
+    /// Discards commands waiting to be written and resets the connection
+    /// immediately, since the client is not able to receive them.
+    #[doc(hidden)]
+    fn disconnect_slow(&self) {
+        self.outbound.abort();
+        self.set_state(ConnectionState::Draining);
+        let _ = self.stream.write().unwrap().reset();
     }
 
     #[doc(hidden)]
-    fn recv_command(&self) -> io::Result<Option<c2s::Command>> {
+    fn recv_command(&self) -> Result<Option<c2s::Command>, Error> {
         // Lock stream
         let mut stream = self.stream.read().unwrap().try_clone()?;
         // Read exactly four bytes which will denote next message length:
@@ -148,10 +545,7 @@ impl Client {
         if read_bytes == 0 { return Result::Ok(Option::None) }
         // If we have not read all four bytes, it is an error:
         if read_bytes < size_of::<u32>() {
-            return Result::Err(io::Error::new(
-                io::ErrorKind::UnexpectedEof,
-                "End of stream was not expected"
-            ))
+            return Result::Err(Error::Framing("End of stream was not expected".to_string()))
         }
         // The data length:
         let data_len = u32::from_be_bytes(buffer) as usize;
@@ -159,16 +553,97 @@ impl Client {
         // `data_len` bytes:
         let mut buffer = vec![0; data_len];
         stream.read_exact(&mut buffer[0..data_len])?;
+        // Update statistics:
+        self.bytes_in.fetch_add((size_of::<u32>() + data_len) as u64, Ordering::Relaxed);
+        *self.last_activity.write().unwrap() = Utc::now();
         // Decrypt
         let decrypted = decrypt(&buffer[0..data_len]);
-        // Convert to `String`:
-        let string = String::from_utf8(decrypted)
-            .map_err(|err| format!("Received invalid data: {}", err))
-            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
-        // Deserialize:
-        serde_json::from_str(&string)
-            .map_err(|err| format!("Received invalid data: {}", err))
-            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+        // Decompress if compressed and deserialize in any encoding:
+        let decompressed = compression::decompress(decrypted)?;
+        codec::decode(&decompressed).map(Option::Some)
+    }
+
+    #[doc(hidden)]
+    fn on_unknown_command(&self, name: &str) {
+        // A command the server has a handler of could not be deserialized only
+        // because its contents are malformed:
+        if handler::get(name).is_some() {
+            self.error(tr!(self.locale(), "command.malformed", command = name));
+            warn!("Received malformed `{}` command", name);
+            return
+        }
+        debug!("Received unsupported `{}` command", name);
+        if let Result::Err(err) = self.send_command(s2c::Command::Unsupported(name.to_string())) {
+            self.error(err.to_string());
+        }
+    }
+
+    /// Handles the first command of a connection which may have been bridged by
+    /// the WebSocket gateway. Returns whether the command is
+    /// [`c2s::Command::Forwarded`] sent by the gateway of this process, in which
+    /// case the address of the browser is recorded.
+    #[doc(hidden)]
+    fn on_forwarded(&self, command: &c2s::Command) -> bool {
+        match command {
+            c2s::Command::Forwarded { peer_addr, token } if gateway::is_own_token(token) => {
+                let _ = self.forwarded_for.set(*peer_addr);
+                debug!("Connection is bridged by the gateway for {}", peer_addr);
+                true
+            },
+            c2s::Command::Forwarded { .. } => {
+                warn!("Received `Forwarded` command with an invalid token");
+                false
+            },
+            _ => false,
+        }
+    }
+
+    #[doc(hidden)]
+    fn on_hello(&self, client_version: ClientVersion, challenge: Vec<u8>, suppress_echo: bool, locale: Option<String>,
+                encodings: Vec<Encoding>, compressions: Vec<Compression>) {
+        info!("Client {} uses {}", self.remote_addr(), client_version);
+        // Even the rejection of an outdated client is sent in its language:
+        let locale = catalog::negotiate(locale.as_deref());
+        *self.locale.write().unwrap() = locale.clone();
+        let global_config = global_config();
+        let client_config = global_config.client();
+        if !client_config.read().unwrap().is_allowed_version(client_version.version()) {
+            let min_version = client_config.read().unwrap().get_min_version().unwrap_or_default();
+            self.error(tr!(locale, "client.outdated", version = client_version.version(), min_version = min_version));
+            info!("Rejected outdated client {}", client_version);
+            return
+        }
+        let protocol_version = client_version.protocol_version().min(PROTOCOL_VERSION);
+        self.protocol_version.store(protocol_version.max(1), Ordering::Relaxed);
+        *self.client_version.write().unwrap() = Option::Some(client_version);
+        self.suppress_echo.store(suppress_echo, Ordering::Relaxed);
+        // Prove the identity of the server and describe it:
+        let limits = global_config.limits().read().unwrap();
+        let server_limits = ServerLimits::new(
+            global_config.message_filtering().read().unwrap().get_max_len().get(),
+            global_config.nickname_filtering().read().unwrap().get_max_len().get(),
+            limits.get_messages_per_day().map(NonZeroU32::get),
+            limits.get_messages_per_session().map(NonZeroU32::get),
+        );
+        drop(limits);
+        let server_config = global_config.server().read().unwrap();
+        let encoding = codec::negotiate(&encodings);
+        let compression = compression::negotiate(&compressions);
+        let info = ServerInfo::new(protocol_version, server_config.get_name().cloned(), server_config.get_motd(), server_limits)
+            .with_locale(locale)
+            .with_encoding(encoding)
+            .with_compression(compression);
+        drop(server_config);
+        // The hello itself is encoded as JSON and not compressed, since the client
+        // learns the chosen encoding and compression from it:
+        match self.send_command(s2c::Command::Hello { identity: identity::sign(&challenge), info }) {
+            Result::Ok(()) => {
+                *self.encoding.write().unwrap() = encoding;
+                *self.compression.write().unwrap() = compression;
+                self.set_state(ConnectionState::HandshakeDone);
+            },
+            Result::Err(err) => self.error(err.to_string()),
+        }
     }
 
     #[doc(hidden)]
@@ -176,12 +651,68 @@ impl Client {
         let is_registering = request.is_registering();
         let nickname = request.nickname();
         let password = request.password();
+        let email = request.email();
+        // Check client version, if there is a minimum:
+        let min_version = global_config().client().read().unwrap().get_min_version();
+        if let (Option::Some(min_version), Option::None) = (min_version, &*self.client_version.read().unwrap()) {
+            self.error(tr!(self.locale(), "client.unreported-version", min_version = min_version));
+            info!("Rejected client which did not report its version");
+            return
+        }
         // Check nickname policy:
         if !global_config().is_allowed_nickname(nickname) {
-            let log_message = format!("Tried to used banned nickname `{}`", nickname);
-            let client_message = format!("`{}` is not an allowed nickname due to regulations.", nickname);
-            self.error(client_message);
-            log(LogLevel::Warning, &log_message);
+            self.error(tr!(self.locale(), "login.nickname-not-allowed", nickname = nickname));
+            warn!("Tried to used banned nickname `{}`", nickname);
+            self.publish_failed_login(nickname, "banned nickname");
+            return
+        }
+        // Read-only sessions can be disabled by global configuration:
+        if request.is_read_only() && !global_config().client().read().unwrap().get_read_only() {
+            self.error(tr!(self.locale(), "login.read-only-not-allowed"));
+            info!("Tried to open a read-only session as `{}`, but they are not allowed", nickname);
+            return
+        }
+        self.read_only.store(request.is_read_only(), Ordering::Relaxed);
+        // New accounts are regular users, so they cannot be registered on
+        // admin-only listeners:
+        if is_registering && self.admin_only {
+            self.error(tr!(self.locale(), "register.admin-only"));
+            info!("Tried to register `{}` on an admin-only listener", nickname);
+            self.publish_failed_login(nickname, "registration on admin-only listener");
+            return
+        }
+        // Nobody can register during the emergency lockdown:
+        if is_registering && lockdown::is_active() {
+            self.error(tr!(self.locale(), "register.lockdown"));
+            info!("Tried to register `{}` during lockdown", nickname);
+            self.publish_failed_login(nickname, "registration during lockdown");
+            return
+        }
+        // Accounts of external identity systems cannot be created here:
+        if is_registering && !auth::provider().can_register() {
+            self.error(tr!(self.locale(), "register.unavailable"));
+            info!("Tried to register `{}`, but the authentication provider does not allow it", nickname);
+            self.publish_failed_login(nickname, "registration not available");
+            return
+        }
+        // New users must give a valid e-mail address if it is verified:
+        let email_verification = global_config().auth().read().unwrap().get_email().is_verification();
+        if is_registering && email_verification && !email.is_some_and(|email| email::is_valid_address(email)) {
+            self.error(tr!(self.locale(), "register.email-required"));
+            info!("Tried to register `{}` without a valid e-mail address", nickname);
+            self.publish_failed_login(nickname, "invalid e-mail address");
+            return
+        }
+        // Registrations are limited by the IP address, logins by the account and
+        // the IP address, so passwords cannot be guessed quickly:
+        let rate_limit = match is_registering {
+            true => ratelimit::take_ip(RateLimitBucket::Registrations, self.remote_addr().ip()),
+            false => ratelimit::take_login(self.remote_addr().ip(), nickname),
+        };
+        if let Result::Err(retry_after) = rate_limit {
+            self.error(tr!(self.locale(), "login.too-many-attempts", seconds = retry_after.as_secs() + 1));
+            info!("Tried to log in or register as `{}` too often", nickname);
+            self.publish_failed_login(nickname, "too many attempts");
             return
         }
         // Get if nickname is already registered:
@@ -190,75 +721,1369 @@ impl Client {
         // already exists
         match (is_registering, is_present) {
             (true, true) => self.register_error_already_exists(&nickname),
-            (true, false) => self.register(nickname.clone(), password.clone()),
-            (false, true) => self.login(nickname.clone(), password.clone()),
-            (false, false) => self.login_error_not_existing(&nickname)
+            (true, false) if email_verification =>
+                self.register(nickname.clone(), password.clone(), email.cloned()),
+            (true, false) => self.register(nickname.clone(), password.clone(), Option::None),
+            // Non-existing accounts are handled the same way as wrong passwords,
+            // so it cannot be found out which accounts exist:
+            (false, _) => self.login(nickname.clone(), password.clone()),
         }
     }
 
     #[doc(hidden)]
     fn register_error_already_exists(&self, nickname: &str) {
-        let log_message = format!("Tried to register already existing nickname: `{}`", nickname);
-        let client_message = format!("`{}` is already existing user account", nickname);
-        self.error(client_message);
-        log(LogLevel::Info, &log_message);
+        self.error(tr!(self.locale(), "register.already-exists", nickname = nickname));
+        info!("Tried to register already existing nickname: `{}`", nickname);
+        self.publish_failed_login(nickname, "account already exists");
     }
 
     #[doc(hidden)]
-    fn register(&self, nickname: String, password: String) {
-        user_list::add_user(nickname.clone(), password.clone());
-        *self.nickname.write().unwrap() = Option::Some(nickname.clone());
-        let log_message = format!("Successfully registered and logged in as `{}`", nickname);
-        log(LogLevel::Info, &log_message);
+    fn register(&self, nickname: String, password: String, email: Option<String>) {
+        raid::record_registration(self.remote_addr().ip());
+        // Accounts registered during a raid must be approved by a moderator:
+        let approved = !raid::is_active();
+        user_list::add_user(nickname.clone(), approved);
+        if let Result::Err(err) = auth::provider().register(&nickname, &password) {
+            user_list::remove_user(&nickname);
+            self.error(tr!(self.locale(), "register.failed"));
+            error!("Could not register `{}`: {}", nickname, err);
+            return
+        }
+        if !approved {
+            let client_message = tr!(self.locale(), "register.approval-required");
+            let _ = self.send_command(s2c::Command::Warning(client_message));
+            moderation::publish(ModEventKind::ApprovalRequired { nickname: nickname.clone(), socket_addr: *self.remote_addr() });
+        }
+        // The account stays inactive until the e-mail address is verified:
+        if let Option::Some(email) = email {
+            user_list::set_unverified_email(&nickname, email);
+            info!("Registered `{}`, waiting for e-mail verification", nickname);
+            self.require_verification(nickname);
+            return
+        }
+        let first_connection = client_list::log_in(self, nickname.clone());
+        self.set_state(ConnectionState::Authenticated);
+        info!("Successfully registered and logged in as `{}`", nickname);
+        self.send_channel_infos();
+        self.send_welcome(&nickname);
+        presence::joined(nickname, &self.socket_addr, first_connection);
     }
 
     #[doc(hidden)]
     fn login(&self, nickname: String, password: String) {
         // If client tried to log in with wrong password, kick it:
-        if !user_list::verify_password(&nickname, password.clone()) {
-            self.error("Invalid password".to_string());
-            log(LogLevel::Warning, "Tried to log in as {} with invalid password")
+        let authenticated = match auth::provider().authenticate(&nickname, &password) {
+            Result::Ok(authenticated) => authenticated,
+            Result::Err(err) => {
+                self.error(tr!(self.locale(), "login.auth-unavailable"));
+                error!("Could not authenticate `{}`: {}", nickname, err);
+                return
+            }
+        };
+        if !authenticated {
+            self.error(tr!(self.locale(), "login.invalid-credentials"));
+            match user_list::exists(&nickname) {
+                true => {
+                    warn!("Tried to log in as `{}` with invalid password", nickname);
+                    self.publish_failed_login(&nickname, "invalid password");
+                },
+                false => {
+                    warn!("Tried to log into a non-existing account: `{}`", nickname);
+                    self.publish_failed_login(&nickname, "account does not exist");
+                },
+            }
+            return
+        }
+        // Users of external identity systems have no record when logging in for
+        // the first time:
+        if !user_list::exists(&nickname) {
+            user_list::add_user(nickname.clone(), true);
+            info!("Created a record of `{}` authenticated by {} provider", nickname, auth::provider().name());
+        }
+        // Accounts with unverified e-mail address cannot be used:
+        if user_list::get_unverified_email(&nickname).is_some() {
+            self.require_verification(nickname);
+            return
+        }
+        self.finish_login(nickname);
+    }
+
+    /// Makes the user verify their e-mail address. A new verification code is
+    /// sent, unless the user already has one which has not expired yet.
+    #[doc(hidden)]
+    fn require_verification(&self, nickname: String) {
+        let now = Utc::now();
+        if !user_list::has_valid_verification_code(&nickname, now) {
+            let email = user_list::get_unverified_email(&nickname).unwrap();
+            let code = email::generate_code();
+            if let Result::Err(err) = email::send_verification_code(&email, &code) {
+                self.error(tr!(self.locale(), "verify.code-not-sent"));
+                error!("Could not send verification code to `{}`: {}", nickname, err);
+                return
+            }
+            let code_ttl = global_config().auth().read().unwrap().get_email().get_code_ttl();
+            let expires_at = chrono::Duration::from_std(code_ttl).ok()
+                .and_then(|code_ttl| now.checked_add_signed(code_ttl))
+                .unwrap_or(DateTime::<Utc>::MAX_UTC);
+            user_list::set_verification_code(&nickname, Option::Some((code, expires_at)));
+            info!("Sent verification code to `{}`", nickname);
+        }
+        *self.pending_verification.write().unwrap() = Option::Some(nickname);
+        if let Result::Err(err) = self.send_command(s2c::Command::VerificationRequired) {
+            self.error(err.to_string());
+        }
+    }
+
+    #[doc(hidden)]
+    fn on_verify_code(&self, code: String) {
+        let nickname = match self.pending_verification.read().unwrap().clone() {
+            Option::Some(nickname) => nickname,
+            Option::None => {
+                let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "verify.nothing")));
+                return
+            }
+        };
+        let now = Utc::now();
+        if !user_list::verify_email(&nickname, code.trim(), now) {
+            info!("Invalid verification code for `{}`", nickname);
+            // After too many invalid codes, the user must log in again to get a
+            // new code:
+            if !user_list::has_valid_verification_code(&nickname, now) {
+                *self.pending_verification.write().unwrap() = Option::None;
+                let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "verify.code-removed")));
+                return
+            }
+            let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "verify.invalid-code")));
+            return
+        }
+        *self.pending_verification.write().unwrap() = Option::None;
+        info!("Verified e-mail address of `{}`", nickname);
+        self.finish_login(nickname);
+    }
+
+    #[doc(hidden)]
+    fn finish_login(&self, nickname: String) {
+        // Only administrators and moderators can log in on admin-only listeners:
+        if self.admin_only && global_config().role_of(&nickname) == Role::User {
+            self.error(tr!(self.locale(), "login.admin-only"));
+            warn!("Tried to log in as `{}` on an admin-only listener", nickname);
+            self.publish_failed_login(&nickname, "not allowed on admin-only listener");
+            return
         }
         // Send LoginSuccess
         if let Result::Err(err) = self.send_command(s2c::Command::LoginSuccess) {
             self.error(err.to_string());
             return
         }
-        // Update nickname
-        *self.nickname.write().unwrap() = Option::Some(nickname.clone());
+        // Update nickname:
+        let first_connection = client_list::log_in(self, nickname.clone());
+        self.set_state(ConnectionState::Authenticated);
         // Log successful login
-        let message = format!("Logged in as `{}`", nickname);
-        log(LogLevel::Info, &message);
+        info!("Logged in as `{}`", nickname);
+        // Describe available channels:
+        self.send_channel_infos();
         // Send messages that were sent when the user was not connected,
         // only if last send message ID is present:
-        let last_msg_id = user_list::get_last_sent_msg_id(&nickname);
-        if let Option::Some(last_msg_id) = last_msg_id {
-            message_list::for_messages_newer_than(last_msg_id, |_, message| {
-                match self.send_command(s2c::Command::MessageRecv(message.clone())) {
-                    Result::Ok(()) => {},
-                    Result::Err(err) => self.error(err.to_string())
+        message_queue::send_missed(self, &nickname);
+        // Send direct messages sent when the user was not connected, unless they
+        // are deferred until the user wants to be disturbed:
+        if !is_deferring_direct_messages(&nickname) {
+            for message in mailbox::take(&nickname) {
+                if let Result::Err(err) = self.send_command(s2c::Command::DirectMessageRecv(message)) {
+                    self.error(err.to_string());
+                    return
                 }
+            }
+        }
+        // Greet users logging in for the first time:
+        self.send_welcome(&nickname);
+        // Tell other users the user has joined:
+        presence::joined(nickname, &self.socket_addr, first_connection);
+    }
+
+    /// Sends the welcome sequence to the user, if the user has not been sent it
+    /// yet. If the template file cannot be read, the user is not sent it at all,
+    /// so the welcome sequence is never sent to users who have been using the
+    /// server for a while already.
+    #[doc(hidden)]
+    fn send_welcome(&self, nickname: &str) {
+        if !user_list::take_welcome_pending(nickname) {
+            return
+        }
+        let messages = match welcome::messages(nickname) {
+            Result::Ok(messages) if messages.is_empty() => return,
+            Result::Ok(messages) => messages,
+            Result::Err(err) => {
+                error!("Could not read the welcome sequence for `{}`: {}", nickname, err);
+                return
+            }
+        };
+        if let Result::Err(err) = self.send_command(s2c::Command::Welcome(messages)) {
+            self.error(err.to_string());
+        }
+    }
+
+    #[doc(hidden)]
+    fn publish_failed_login(&self, nickname: &str, reason: &str) {
+        moderation::publish(ModEventKind::LoginFailed {
+            socket_addr: *self.remote_addr(),
+            nickname: nickname.to_string(),
+            reason: reason.to_string(),
+        });
+    }
+
+    #[doc(hidden)]
+    fn on_message(&self, channel: Option<String>, kind: MessageKind, text: String, ttl: Option<u64>, client_tag: Option<String>) {
+        match self.nickname() {
+            Option::Some(nickname) => {
+                if !self.check_client_tag(client_tag.as_ref())
+                    || !self.check_message_kind(kind)
+                    || !self.check_channel(&nickname, channel.as_ref())
+                    || !self.check_approved(&nickname)
+                    || !self.check_message_interval(&nickname)
+                    || !self.check_message_text(&nickname, &text)
+                    || !self.use_quotas(&nickname) {
+                    return
+                }
+                self.messages_sent.fetch_add(1, Ordering::Relaxed);
+                // Hold the message for review if it matches a quarantine pattern:
+                let quarantine_pattern = global_config().message_filtering().read().unwrap()
+                    .find_quarantine_pattern(&text)
+                    .map(|pattern| pattern.to_string());
+                match quarantine_pattern {
+                    Option::Some(pattern) => self.quarantine(nickname, channel, kind, text, ttl.map(Duration::from_secs), pattern),
+                    Option::None => if !message_queue::push(Option::Some(self.socket_addr), client_tag, nickname, channel, kind, text, ttl.map(Duration::from_secs)) {
+                        let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "message.server-busy")));
+                    },
+                }
+            },
+            Option::None => {
+                self.error(tr!(self.locale(), "not-logged-in.send"));
+                warn!("Tried to send a message while not logged in");
+            },
+        }
+    }
+
+    #[doc(hidden)]
+    fn on_direct_message(&self, recipient: String, text: String) {
+        let nickname = match self.nickname() {
+            Option::Some(nickname) => nickname,
+            Option::None => {
+                self.error(tr!(self.locale(), "not-logged-in.send-direct"));
+                return
+            }
+        };
+        if !self.check_approved(&nickname)
+            || !self.check_message_interval(&nickname)
+            || !self.check_message_text(&nickname, &text)
+            || !self.use_quotas(&nickname) {
+            return
+        }
+        if !user_list::exists(&recipient) {
+            let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "user.not-found", nickname = recipient)));
+            return
+        }
+        self.messages_sent.fetch_add(1, Ordering::Relaxed);
+        // Messages from blocked users are discarded without telling the sender:
+        if user_list::has_blocked(&recipient, &nickname) {
+            return
+        }
+        let color = user_list::color_of(&nickname);
+        let message = DirectMessage::new(nickname, recipient.clone(), Utc::now(), text, color);
+        // Deliver the message to all clients of the recipient, or store it until
+        // the recipient logs in or wants to be disturbed:
+        let mut delivered = false;
+        if is_deferring_direct_messages(&recipient) {
+            if !mailbox::store(message) {
+                let client_message = tr!(self.locale(), "direct.rate-limited", nickname = recipient);
+                let _ = self.send_command(s2c::Command::Warning(client_message));
+            }
+            return
+        }
+        let command = s2c::Command::DirectMessageRecv(message.clone());
+        client_list::for_each(|_, client| if client.nickname().as_ref() == Option::Some(&recipient) {
+            match client.send_command(command.clone()) {
+                Result::Ok(()) => delivered = true,
+                Result::Err(err) => client.error(err.to_string()),
+            }
+        });
+        if !delivered && !mailbox::store(message) {
+            let client_message = tr!(self.locale(), "direct.mailbox-full", nickname = recipient);
+            let _ = self.send_command(s2c::Command::Warning(client_message));
+        }
+    }
+
+    /// Returns whether the client can send messages of given kind. System
+    /// messages are generated only by the server and announcements require
+    /// a permission. If not, client gets a [`s2c::Command::Warning`].
+    #[doc(hidden)]
+    fn check_message_kind(&self, kind: MessageKind) -> bool {
+        match kind {
+            MessageKind::User | MessageKind::Action => true,
+            MessageKind::Announcement => self.require(Capability::Announce),
+            MessageKind::System => {
+                let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "message.system-not-allowed")));
+                false
+            },
+            MessageKind::Bot => {
+                let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "message.bot-not-allowed")));
+                false
+            },
+        }
+    }
+
+    /// Returns whether given user can send messages into given channel. If not,
+    /// client gets a [`s2c::Command::Warning`]. [`Option::None`] stands for the
+    /// main chat, where everybody can send messages.
+    #[doc(hidden)]
+    fn check_channel(&self, nickname: &str, channel: Option<&String>) -> bool {
+        let channel = match channel {
+            Option::Some(channel) => channel,
+            Option::None => return true,
+        };
+        let allowed = match channel_list::get_mode(channel) {
+            Option::None => false,
+            Option::Some(ChannelMode::Public) => true,
+            Option::Some(ChannelMode::InviteOnly) => channel_list::is_member(channel, nickname),
+            Option::Some(ChannelMode::Moderated) => channel_list::is_member(channel, nickname)
+                || channel_list::is_moderator(channel, nickname)
+                || PermissionChecker::new().is_allowed(nickname, Capability::SendModerated),
+        };
+        if !allowed {
+            // Do not reveal whether an invite-only channel exists:
+            let client_message = match channel_list::can_read(Option::Some(channel), nickname) {
+                true => tr!(self.locale(), "channel.send-not-allowed", channel = channel),
+                false => tr!(self.locale(), "channel.not-found", channel = channel),
+            };
+            let _ = self.send_command(s2c::Command::Warning(client_message));
+        }
+        allowed
+    }
+
+    /// Returns whether given message text is allowed by the message filter.
+    /// Matches of banned and trial patterns are counted. If the text is not
+    /// allowed, client gets a [`s2c::Command::Warning`], the rejection is logged
+    /// and moderators are informed.
+    #[doc(hidden)]
+    fn check_message_text(&self, nickname: &str, text: &str) -> bool {
+        let global_config = global_config();
+        let message_filtering = global_config.message_filtering().read().unwrap();
+        let trial_patterns: Vec<String> = message_filtering.find_trial_patterns(text)
+            .map(|pattern| pattern.to_string())
+            .collect();
+        let banned_pattern = message_filtering.find_banned_pattern(text).map(|pattern| pattern.to_string());
+        let result = message_filtering.check(text);
+        drop(message_filtering);
+        // Count the matches:
+        for pattern in trial_patterns {
+            filter_stats::record(&pattern, nickname);
+            info!("Message from `{}` would be rejected by trial pattern `{}`", nickname, pattern);
+        }
+        if let Option::Some(pattern) = banned_pattern {
+            filter_stats::record(&pattern, nickname);
+        }
+        // Reject the message if it is not allowed:
+        match result {
+            Result::Ok(()) => true,
+            Result::Err(reason) => {
+                let client_message = tr!(self.locale(), "message.not-allowed");
+                let _ = self.send_command(s2c::Command::Warning(client_message));
+                info!("Message from `{}` rejected: {}", nickname, reason);
+                moderation::publish(ModEventKind::MessageRejected {
+                    sender: nickname.to_string(),
+                    text: text.to_string(),
+                    reason,
+                });
+                false
+            }
+        }
+    }
+
+    /// Returns whether given user has been approved by a moderator. If not, client
+    /// gets a [`s2c::Command::Warning`].
+    #[doc(hidden)]
+    fn check_approved(&self, nickname: &str) -> bool {
+        let approved = user_list::is_approved(nickname);
+        if !approved {
+            let client_message = tr!(self.locale(), "message.not-approved");
+            let _ = self.send_command(s2c::Command::Warning(client_message));
+        }
+        approved
+    }
+
+    /// Returns whether the tag the client has attached to its message is not
+    /// longer than [`message::MAX_CLIENT_TAG_LENGTH`]. If it is, the client is
+    /// warned.
+    #[doc(hidden)]
+    fn check_client_tag(&self, client_tag: Option<&String>) -> bool {
+        let too_long = client_tag.is_some_and(|client_tag| client_tag.chars().count() > message::MAX_CLIENT_TAG_LENGTH);
+        if too_long {
+            let client_message = tr!(self.locale(), "message.tag-too-long", max_length = message::MAX_CLIENT_TAG_LENGTH);
+            let _ = self.send_command(s2c::Command::Warning(client_message));
+        }
+        !too_long
+    }
+
+    /// Returns whether the client can change anything, that is the session is not
+    /// read-only. If it is read-only, the client is warned.
+    #[doc(hidden)]
+    fn check_writable(&self) -> bool {
+        let read_only = self.read_only.load(Ordering::Relaxed);
+        if read_only {
+            let client_message = tr!(self.locale(), "message.read-only");
+            let _ = self.send_command(s2c::Command::Warning(client_message));
+        }
+        !read_only
+    }
+
+    /// Returns whether the client can send a message with respect to the
+    /// emergency lockdown and raid countermeasures. During them, users who are
+    /// not moderators can send messages only once per interval given by global
+    /// configuration.
+    #[doc(hidden)]
+    fn check_message_interval(&self, nickname: &str) -> bool {
+        let restricted = lockdown::is_active() || raid::is_active();
+        if !restricted || PermissionChecker::new().is_allowed(nickname, Capability::BypassMessageInterval) {
+            return true
+        }
+        let interval = global_config().lockdown().read().unwrap().get_message_interval();
+        let now = Instant::now();
+        let mut last_message = self.last_message.write().unwrap();
+        if last_message.is_some_and(|last_message| now.duration_since(last_message) < interval) {
+            drop(last_message);
+            let client_message = tr!(self.locale(), "message.too-often");
+            let _ = self.send_command(s2c::Command::Warning(client_message));
+            moderation::publish(ModEventKind::RateLimited {
+                nickname: nickname.to_string(),
+                action: "send messages during lockdown or raid".to_string(),
             });
+            return false
+        }
+        *last_message = Option::Some(now);
+        true
+    }
+
+    /// Returns whether the user can do an action with respect to rate limits given
+    /// by global configuration. If not, client gets a [`s2c::Command::Warning`]
+    /// and moderators are informed.
+    #[doc(hidden)]
+    fn check_rate_limit(&self, nickname: &str, bucket: RateLimitBucket) -> bool {
+        let retry_after = match ratelimit::take(bucket, nickname) {
+            Result::Ok(()) => return true,
+            Result::Err(retry_after) => retry_after,
+        };
+        let (action, key) = match bucket {
+            RateLimitBucket::DirectMessages => ("send direct messages", "rate-limit.direct-messages"),
+            RateLimitBucket::Searches => ("search the message history", "rate-limit.searches"),
+            _ => ("send messages", "rate-limit.messages"),
+        };
+        let client_message = tr!(self.locale(), key, seconds = retry_after.as_secs() + 1);
+        let _ = self.send_command(s2c::Command::Warning(client_message));
+        moderation::publish(ModEventKind::RateLimited {
+            nickname: nickname.to_string(),
+            action: action.to_string(),
+        });
+        false
+    }
+
+    /// Counts a message into message quotas of the user, if the quotas are not
+    /// used up yet. Client gets a [`s2c::Command::Warning`] when a quota is
+    /// nearly used up, or a [`s2c::Command::QuotaExceeded`] when the message
+    /// does not fit into a quota.
+    ///
+    /// # Return value
+    ///
+    /// Whether the message can be accepted.
+    #[doc(hidden)]
+    fn use_quotas(&self, nickname: &str) -> bool {
+        let (messages_per_day, messages_per_session) = {
+            let global_config = global_config();
+            let limits_config = global_config.limits().read().unwrap();
+            (limits_config.get_messages_per_day(), limits_config.get_messages_per_session())
+        };
+        let (period_start, period_end) = quota::daily_period(Utc::now());
+        let sent_today = user_list::get_daily_message_count(nickname, period_start);
+        let sent_in_session = self.messages_sent.load(Ordering::Relaxed);
+        // Reject the message if a quota is used up:
+        let exceeded = match (messages_per_day, messages_per_session) {
+            (Option::Some(limit), _) if sent_today >= limit.get() =>
+                Option::Some((QuotaKind::Daily, limit, Option::Some(period_end))),
+            (_, Option::Some(limit)) if sent_in_session >= limit.get() as u64 =>
+                Option::Some((QuotaKind::Session, limit, Option::None)),
+            _ => Option::None,
+        };
+        if let Option::Some((kind, limit, resets_at)) = exceeded {
+            let command = s2c::Command::QuotaExceeded { kind, limit: limit.get(), resets_at };
+            if let Result::Err(err) = self.send_command(command) {
+                self.error(err.to_string());
+            }
+            info!("`{}` has used up the {} message quota", nickname, kind);
+            return false
+        }
+        // Count the message and warn the user if a quota is nearly used up:
+        let sent_today = user_list::count_daily_message(nickname, period_start);
+        let warning = match (messages_per_day, messages_per_session) {
+            (Option::Some(limit), _) if sent_today == quota::warning_threshold(limit) =>
+                Option::Some((sent_today, limit, "quota.nearly-used-daily")),
+            (_, Option::Some(limit)) if sent_in_session + 1 == quota::warning_threshold(limit) as u64 =>
+                Option::Some((sent_in_session as u32 + 1, limit, "quota.nearly-used-session")),
+            _ => Option::None,
+        };
+        if let Option::Some((sent, limit, key)) = warning {
+            let client_message = tr!(self.locale(), key, sent = sent, limit = limit);
+            let _ = self.send_command(s2c::Command::Warning(client_message));
+        }
+        true
+    }
+
+    #[doc(hidden)]
+    fn quarantine(&self, nickname: String, channel: Option<String>, kind: MessageKind, text: String, ttl: Option<Duration>, pattern: String) {
+        let held = review_queue::hold(nickname, channel, kind, text, ttl, pattern);
+        let client_message = tr!(self.locale(), "message.held-for-review");
+        let _ = self.send_command(s2c::Command::Warning(client_message));
+        info!("Message {} from `{}` held for review", held.id(), held.sender());
+        moderation::publish(ModEventKind::MessageQuarantined {
+            id: held.id(),
+            sender: held.sender().clone(),
+            text: held.text().clone(),
+            pattern: held.pattern().clone(),
+        });
+    }
+
+    /// Returns whether the client is logged in as a user allowed to use given
+    /// capability. If not, client gets a [`s2c::Command::Warning`] and the
+    /// attempt is logged.
+    #[doc(hidden)]
+    fn require(&self, capability: Capability) -> bool {
+        let allowed = self.nickname()
+            .map(|nickname| PermissionChecker::new().is_allowed(&nickname, capability))
+            .unwrap_or(false);
+        if !allowed {
+            let action = tr!(self.locale(), &format!("capability.{}", capability.name()));
+            let client_message = tr!(self.locale(), "permission.denied", action = action);
+            let _ = self.send_command(s2c::Command::Warning(client_message));
+            warn!("Tried to {} without being allowed to", capability);
+        }
+        allowed
+    }
+
+    #[doc(hidden)]
+    fn on_tail(&self, level: Option<LogLevel>) {
+        *self.tail_level.write().unwrap() = level;
+        match level {
+            Option::Some(level) => info!("Subscribed to the server log with level {}", level),
+            Option::None => info!("Unsubscribed from the server log"),
+        }
+    }
+
+    #[doc(hidden)]
+    fn on_subscribe_mod_events(&self, subscribe: bool) {
+        self.mod_events.store(subscribe, Ordering::Relaxed);
+        match subscribe {
+            true => info!("Subscribed to moderation events"),
+            false => info!("Unsubscribed from moderation events"),
+        }
+    }
+
+    #[doc(hidden)]
+    fn on_list_review_queue(&self) {
+        if let Result::Err(err) = self.send_command(s2c::Command::ReviewQueue(review_queue::list())) {
+            self.error(err.to_string());
+        }
+    }
+
+    #[doc(hidden)]
+    fn on_review_message(&self, id: u64, approve: bool) {
+        let Held { message, channel, kind, ttl } = match review_queue::take(id) {
+            Option::Some(held) => held,
+            Option::None => {
+                let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "review.not-found", id = id)));
+                return
+            }
+        };
+        if approve {
+            info!("Approved message {} from `{}`", id, message.sender());
+            if !message_queue::push(Option::None, Option::None, message.sender().clone(), channel, kind, message.text().clone(), ttl) {
+                let client_message = tr!(self.locale(), "review.server-busy", id = id);
+                let _ = self.send_command(s2c::Command::Warning(client_message));
+            }
+            return
         }
+        info!("Rejected message {} from `{}`", id, message.sender());
+        // Tell the sender, if logged in:
+        client_list::for_each(|_, client| if client.nickname().as_ref() == Option::Some(message.sender()) {
+            let client_message = tr!(client.locale(), "review.rejected", text = message.text());
+            if let Result::Err(err) = client.send_command(s2c::Command::Warning(client_message)) {
+                client.error(err.to_string());
+            }
+        });
     }
 
     #[doc(hidden)]
-    fn login_error_not_existing(&self, nickname: &str) {
-        let log_message = format!("Tried to log into a non-existing account: `{}`", nickname);
-        let client_message = format!("User with nickname `{}` does not exist", nickname);
-        self.error(client_message);
-        log(LogLevel::Warning, &log_message);
+    fn on_approve_user(&self, nickname: String) {
+        match user_list::approve(&nickname) {
+            true => info!("Approved user `{}`", nickname),
+            false => {
+                let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "user.not-found", nickname = nickname)));
+            },
+        }
+    }
+
+    #[doc(hidden)]
+    fn on_get_filter_stats(&self) {
+        if let Result::Err(err) = self.send_command(s2c::Command::FilterStats(filter_stats::snapshot())) {
+            self.error(err.to_string());
+        }
+    }
+
+    #[doc(hidden)]
+    fn on_explain(&self, filter: FilterKind, input: String) {
+        let global_config = global_config();
+        let decision = match filter {
+            FilterKind::Ip => match input.parse() {
+                Result::Ok(ip_addr) => global_config.ip_filtering().read().unwrap().explain(&ip_addr),
+                Result::Err(err) => {
+                    let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "ban.invalid-ip", input = input, error = err)));
+                    return
+                },
+            },
+            FilterKind::Message => global_config.message_filtering().read().unwrap().explain(&input),
+            FilterKind::Nickname => global_config.nickname_filtering().read().unwrap().explain(&input),
+        };
+        if let Result::Err(err) = self.send_command(s2c::Command::FilterDecision(decision)) {
+            self.error(err.to_string());
+        }
+    }
+
+    #[doc(hidden)]
+    fn on_add_filter_rule(&self, filter: FilterKind, rule: String, persist: bool) {
+        let line = match rules::add(filter, &rule, persist) {
+            Result::Ok(line) => line,
+            Result::Err(err) => {
+                let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "filter.rule-not-added", error = err)));
+                warn!("Could not add filter rule `{} {}`: {}", filter, rule, err);
+                return
+            },
+        };
+        info!("Added filter rule `{}`", line);
+        moderation::publish(ModEventKind::FilterRuleAdded { nickname: self.nickname().unwrap_or_default(), rule: line.clone() });
+        if let Result::Err(err) = self.send_command(s2c::Command::FilterRuleAdded { rule: line, persisted: persist }) {
+            self.error(err.to_string());
+        }
+    }
+
+    #[doc(hidden)]
+    fn on_set_lockdown(&self, active: bool) {
+        if let Result::Err(err) = lockdown::set_active(active) {
+            let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "lockdown.not-saved", error = err)));
+            error!("Could not save the lockdown state: {}", err);
+            return
+        }
+        match active {
+            true => warn!("Emergency lockdown turned on"),
+            false => warn!("Emergency lockdown turned off"),
+        }
+        moderation::publish(ModEventKind::LockdownChanged { nickname: self.nickname().unwrap_or_default(), active });
+        if let Result::Err(err) = self.send_command(s2c::Command::LockdownState(active)) {
+            self.error(err.to_string());
+        }
+    }
+
+    #[doc(hidden)]
+    fn on_list_connections(&self) {
+        let mut connections = Vec::new();
+        client_list::for_each(|_, client| connections.push(client.stats()));
+        if let Result::Err(err) = self.send_command(s2c::Command::ConnectionList(connections)) {
+            self.error(err.to_string());
+        }
+    }
+
+    #[doc(hidden)]
+    fn on_search(&self, query: String, regex: bool, limit: u32) {
+        // Search the message history:
+        let max_results = global_config().history().read().unwrap().get_search_max_results();
+        let limit = limit.min(max_results.get()) as usize;
+        let found = if regex {
+            match RegexBuilder::new(&query).size_limit(1 << 20).build() {
+                Result::Ok(regex) => message_list::find_newest(limit, |message|
+                    regex.is_match(message.text()) && self.can_see(message)),
+                Result::Err(err) => {
+                    let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "search.invalid-regex", error = err)));
+                    return
+                }
+            }
+        } else {
+            message_list::find_newest(limit, |message|
+                message.text().contains(&query) && self.can_see(message))
+        };
+        if let Result::Err(err) = self.send_command(s2c::Command::SearchResult(found)) {
+            self.error(err.to_string());
+        }
     }
 
     #[doc(hidden)]
-    fn on_message(&self, text: String) {
+    fn on_export_history(&self, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) {
+        let entries = message_list::entries_between(from, to);
+        info!("Exporting {} messages from the message history", entries.len());
+        if let Result::Err(err) = self.send_command(s2c::Command::HistoryExport(entries)) {
+            self.error(err.to_string());
+        }
+    }
+
+    #[doc(hidden)]
+    fn on_export_users(&self) {
+        let accounts = user_list::export_accounts();
+        info!("Exporting {} user accounts", accounts.len());
+        if let Result::Err(err) = self.send_command(s2c::Command::UserExport(accounts)) {
+            self.error(err.to_string());
+        }
+    }
+
+    #[doc(hidden)]
+    fn on_import_users(&self, accounts: Vec<AccountRecord>, overwrite: bool) {
+        let global_config = global_config();
+        let mut imported = Vec::new();
+        let mut skipped = Vec::new();
+        for account in &accounts {
+            let result = match global_config.is_allowed_nickname(account.nickname()) {
+                true => user_list::import_account(account, overwrite),
+                false => Result::Err("nickname is not allowed".to_string()),
+            };
+            match result {
+                Result::Ok(()) => imported.push(account.nickname().clone()),
+                Result::Err(reason) => skipped.push((account.nickname().clone(), reason)),
+            }
+        }
+        info!("Imported {} user accounts, skipped {}", imported.len(), skipped.len());
+        if let Result::Err(err) = self.send_command(s2c::Command::UserImportResult { imported, skipped }) {
+            self.error(err.to_string());
+        }
+    }
+
+    #[doc(hidden)]
+    fn on_set_topic(&self, channel: String, topic: String) {
+        // Moderators of a channel can change its topic:
+        let is_moderator = self.nickname().is_some_and(|nickname| channel_list::is_moderator(&channel, &nickname));
+        if !is_moderator && !self.require(Capability::SetTopic) {
+            return
+        }
+        // Empty topic removes the topic, other topics must be allowed messages:
+        let topic = match topic.trim() {
+            "" => Option::None,
+            topic if global_config().is_allowed_message_text(topic) => Option::Some(topic.to_string()),
+            _ => {
+                let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "topic.not-allowed")));
+                return
+            }
+        };
+        let notice = match &topic {
+            Option::Some(topic) => tr!(catalog::default_locale(), "notice.topic-changed", topic = topic),
+            Option::None => tr!(catalog::default_locale(), "notice.topic-removed"),
+        };
+        let info = match channel_list::set_topic(&channel, topic) {
+            Option::Some(info) => info,
+            Option::None => {
+                let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "channel.not-found", channel = channel)));
+                return
+            }
+        };
+        // The topic is changed even if it cannot be saved, it is lost on restart:
+        if let Result::Err(err) = state::save() {
+            let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "topic.not-saved", error = err)));
+            error!("Could not save the topic of channel `{}`: {}", channel, err);
+        }
+        info!("Changed topic of channel `{}`", channel);
+        if let Option::Some(nickname) = self.nickname() {
+            message_queue::push_notice(nickname, Option::Some(channel.clone()), notice);
+        }
+        // Inform all clients which are logged in and can read the channel:
+        let command = s2c::Command::ChannelInfo(info);
+        client_list::for_each(|_, client| match client.nickname() {
+            Option::Some(nickname) if channel_list::can_read(Option::Some(&channel), &nickname) => {
+                if let Result::Err(err) = client.send_command(command.clone()) {
+                    client.error(err.to_string());
+                }
+            },
+            _ => {},
+        });
+    }
+
+    #[doc(hidden)]
+    fn on_delete_message(&self, id: u64) {
+        let nickname = match self.nickname() {
+            Option::Some(nickname) => nickname,
+            Option::None => {
+                self.error(tr!(self.locale(), "not-logged-in.delete"));
+                return
+            }
+        };
+        // Users can delete only their own messages, not the notices generated
+        // by the server about them:
+        let mut channel = Option::None;
+        let deleted = message_list::delete(id, |message| {
+            channel = message.channel().cloned();
+            message.sender() == &nickname && !message.is_from_server()
+        });
+        match deleted {
+            Option::Some(true) => {
+                info!("User `{}` deleted message {}", nickname, id);
+                message_queue::broadcast_deleted(id, channel.as_ref());
+            },
+            Option::Some(false) => {
+                let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "message.delete-not-allowed", id = id)));
+            },
+            Option::None => {
+                let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "message.delete-not-found", id = id)));
+            },
+        }
+    }
+
+    #[doc(hidden)]
+    fn on_react(&self, msg_id: u64, emoji: String) {
+        let nickname = match self.nickname() {
+            Option::Some(nickname) => nickname,
+            Option::None => {
+                self.error(tr!(self.locale(), "not-logged-in.react"));
+                return
+            }
+        };
+        if !message::is_valid_emoji(&emoji) {
+            let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "reaction.invalid-emoji")));
+            return
+        }
+        // Users cannot react to messages they cannot read:
+        let message = match message_list::get(msg_id).filter(|message| self.can_see(message)) {
+            Option::Some(message) => message,
+            Option::None => {
+                let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "reaction.not-found", id = msg_id)));
+                return
+            }
+        };
+        match message_list::toggle_reaction(msg_id, &nickname, &emoji) {
+            Option::Some(true) => message_queue::broadcast_reactions(msg_id, &message),
+            Option::Some(false) => {
+                let limit = message_list::MAX_REACTION_KINDS;
+                let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "reaction.too-many", limit = limit)));
+            },
+            Option::None => {
+                let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "reaction.not-found", id = msg_id)));
+            },
+        }
+    }
+
+    #[doc(hidden)]
+    fn on_invite(&self, channel: String, invited: String) {
+        let nickname = match self.nickname() {
+            Option::Some(nickname) => nickname,
+            Option::None => {
+                self.error(tr!(self.locale(), "not-logged-in.invite"));
+                return
+            }
+        };
+        // Do not reveal whether an invite-only channel exists:
+        if !channel_list::can_read(Option::Some(&channel), &nickname) {
+            let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "channel.not-found", channel = channel)));
+            return
+        }
+        if !channel_list::is_member(&channel, &nickname)
+            && !channel_list::is_moderator(&channel, &nickname)
+            && !self.require(Capability::Invite) {
+            return
+        }
+        if !user_list::exists(&invited) {
+            let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "user.not-found", nickname = invited)));
+            return
+        }
+        match channel_list::add_member(&channel, invited.clone()) {
+            Result::Ok(true) => info!("Invited `{}` into channel `{}`", invited, channel),
+            Result::Ok(false) => {
+                let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "channel.already-member-other", nickname = invited, channel = channel)));
+                return
+            },
+            Result::Err(err) => error!("Could not save members of channels: {}", err),
+        }
+        // Tell the invited user, if logged in:
+        let info = channel_list::infos_for(&invited).into_iter().find(|info| info.name() == &channel);
+        client_list::for_each(|_, client| if client.nickname().as_ref() == Option::Some(&invited) {
+            let client_message = tr!(client.locale(), "channel.invited", channel = channel, nickname = nickname);
+            let result = client.send_command(s2c::Command::Warning(client_message))
+                .and_then(|()| match &info {
+                    Option::Some(info) => client.send_command(s2c::Command::ChannelInfo(info.clone())),
+                    Option::None => Result::Ok(()),
+                });
+            if let Result::Err(err) = result {
+                client.error(err.to_string());
+            }
+        });
+    }
+
+    #[doc(hidden)]
+    fn on_join_channel(&self, channel: String) {
+        let nickname = match self.nickname() {
+            Option::Some(nickname) => nickname,
+            Option::None => {
+                self.error(tr!(self.locale(), "not-logged-in.join"));
+                return
+            }
+        };
+        // Do not reveal whether an invite-only channel exists:
+        match channel_list::get_mode(&channel) {
+            Option::Some(ChannelMode::Public) => {},
+            Option::Some(ChannelMode::Moderated) => {
+                let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "channel.moderated", channel = channel)));
+                return
+            },
+            Option::Some(ChannelMode::InviteOnly) | Option::None => {
+                let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "channel.not-found", channel = channel)));
+                return
+            },
+        }
+        match channel_list::add_member(&channel, nickname.clone()) {
+            Result::Ok(true) => info!("Joined channel `{}`", channel),
+            Result::Ok(false) => {
+                let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "channel.already-member", channel = channel)));
+                return
+            },
+            Result::Err(err) => error!("Could not save members of channels: {}", err),
+        }
+        if let Option::Some(info) = channel_list::infos_for(&nickname).into_iter().find(|info| info.name() == &channel) {
+            if let Result::Err(err) = self.send_command(s2c::Command::ChannelInfo(info)) {
+                self.error(err.to_string());
+                return
+            }
+        }
+        message_queue::push_notice(nickname, Option::Some(channel), tr!(catalog::default_locale(), "notice.joined-channel"));
+    }
+
+    #[doc(hidden)]
+    fn on_leave_channel(&self, channel: String) {
+        let nickname = match self.nickname() {
+            Option::Some(nickname) => nickname,
+            Option::None => {
+                self.error(tr!(self.locale(), "not-logged-in.leave"));
+                return
+            }
+        };
+        if channel_list::get_owner(&channel).as_ref() == Option::Some(&nickname) {
+            let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "channel.owner-cannot-leave", channel = channel)));
+            return
+        }
+        match channel_list::remove_member(&channel, &nickname) {
+            Result::Ok(true) => info!("Left channel `{}`", channel),
+            Result::Ok(false) => {
+                let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "channel.not-member", channel = channel)));
+                return
+            },
+            Result::Err(err) => error!("Could not save members of channels: {}", err),
+        }
+        message_queue::push_notice(nickname, Option::Some(channel), tr!(catalog::default_locale(), "notice.left-channel"));
+    }
+
+    #[doc(hidden)]
+    fn on_create_channel(&self, name: String, mode: ChannelMode) {
+        let nickname = self.nickname().unwrap();
+        if !global_config().channels().read().unwrap().is_valid_name(&name) {
+            let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "channel.invalid-name", channel = name)));
+            return
+        }
+        let info = match channel_list::create_channel(name.clone(), mode, nickname.clone()) {
+            Result::Ok(Option::Some(info)) => info,
+            Result::Ok(Option::None) => {
+                let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "channel.already-exists", channel = name)));
+                return
+            },
+            Result::Err(err) => {
+                error!("Could not save channels: {}", err);
+                match channel_list::infos_for(&nickname).into_iter().find(|info| info.name() == &name) {
+                    Option::Some(info) => info,
+                    Option::None => return,
+                }
+            },
+        };
+        info!("Created {} channel `{}`", mode, name);
+        // Inform all clients which are logged in and can read the channel:
+        let command = s2c::Command::ChannelInfo(info);
+        client_list::for_each(|_, client| match client.nickname() {
+            Option::Some(nickname) if channel_list::can_read(Option::Some(&name), &nickname) => {
+                if let Result::Err(err) = client.send_command(command.clone()) {
+                    client.error(err.to_string());
+                }
+            },
+            _ => {},
+        });
+    }
+
+    #[doc(hidden)]
+    fn on_delete_channel(&self, name: String) {
+        let nickname = match self.nickname() {
+            Option::Some(nickname) => nickname,
+            Option::None => {
+                self.error(tr!(self.locale(), "not-logged-in.delete-channel"));
+                return
+            }
+        };
+        if !self.check_channel_owner(&name, &nickname) {
+            return
+        }
+        // Archive the message history before the channel disappears:
+        match archive::archive_channel(&name) {
+            Result::Ok((path, count)) =>
+                info!("Archived {} messages of channel `{}` into {}", count, name, path.display()),
+            Result::Err(err) => {
+                let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "channel.not-deleted", channel = name)));
+                error!("Could not archive channel `{}`: {}", name, err);
+                return
+            }
+        }
+        let channel = match channel_list::remove_channel(&name) {
+            Result::Ok(Option::Some(channel)) => channel,
+            Result::Ok(Option::None) => return,
+            Result::Err(err) => {
+                error!("Could not save channels: {}", err);
+                return
+            },
+        };
+        info!("Deleted channel `{}`", name);
+        // Inform all clients which are logged in and could read the channel:
+        let command = s2c::Command::ChannelDeleted(name);
+        client_list::for_each(|_, client| match client.nickname() {
+            Option::Some(nickname) if channel.can_read(&nickname) => {
+                if let Result::Err(err) = client.send_command(command.clone()) {
+                    client.error(err.to_string());
+                }
+            },
+            _ => {},
+        });
+    }
+
+    #[doc(hidden)]
+    fn on_set_channel_moderator(&self, channel: String, moderator: String, appoint: bool) {
+        let nickname = match self.nickname() {
+            Option::Some(nickname) => nickname,
+            Option::None => {
+                self.error(tr!(self.locale(), "not-logged-in.channel-moderators"));
+                return
+            }
+        };
+        if !self.check_channel_owner(&channel, &nickname) {
+            return
+        }
+        if !user_list::exists(&moderator) {
+            let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "user.not-found", nickname = moderator)));
+            return
+        }
+        match channel_list::set_moderator(&channel, moderator.clone(), appoint) {
+            Result::Ok(false) => {},
+            Result::Ok(true) if appoint => info!("Appointed `{}` as a moderator of channel `{}`", moderator, channel),
+            Result::Ok(true) => info!("Dismissed `{}` as a moderator of channel `{}`", moderator, channel),
+            Result::Err(err) => error!("Could not save channels: {}", err),
+        }
+    }
+
+    /// Returns whether given user can manage given channel, that is whether they
+    /// are its owner or are allowed to manage channels of other users. Channels
+    /// given by configuration cannot be managed at all. If the user cannot manage
+    /// the channel, client gets a [`s2c::Command::Warning`].
+    #[doc(hidden)]
+    fn check_channel_owner(&self, channel: &str, nickname: &str) -> bool {
+        // Do not reveal whether an invite-only channel exists:
+        if !channel_list::can_read(Option::Some(&channel.to_string()), nickname) {
+            let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "channel.not-found", channel = channel)));
+            return false
+        }
+        match channel_list::get_owner(channel) {
+            Option::Some(owner) if owner == nickname => true,
+            Option::Some(_) => self.require(Capability::ManageChannels),
+            Option::None => {
+                let client_message = tr!(self.locale(), "channel.configured", channel = channel);
+                let _ = self.send_command(s2c::Command::Warning(client_message));
+                false
+            },
+        }
+    }
+
+    #[doc(hidden)]
+    fn send_channel_infos(&self) {
+        let nickname = match self.nickname() {
+            Option::Some(nickname) => nickname,
+            Option::None => return,
+        };
+        for info in channel_list::infos_for(&nickname) {
+            if let Result::Err(err) = self.send_command(s2c::Command::ChannelInfo(info)) {
+                self.error(err.to_string());
+                return
+            }
+        }
+    }
+
+    #[doc(hidden)]
+    fn on_block(&self, blocked: String) {
+        let nickname = match self.nickname() {
+            Option::Some(nickname) => nickname,
+            Option::None => {
+                self.error(tr!(self.locale(), "not-logged-in.block"));
+                return
+            }
+        };
+        if !user_list::exists(&blocked) || blocked == nickname {
+            let _ = self.send_command(s2c::Command::Warning(tr!(self.locale(), "block.not-allowed", nickname = blocked)));
+            return
+        }
+        user_list::block(&nickname, blocked);
+    }
+
+    #[doc(hidden)]
+    fn on_unblock(&self, blocked: String) {
         match self.nickname() {
-            Option::Some(nickname) => message_queue::push(nickname, text),
+            Option::Some(nickname) => user_list::unblock(&nickname, &blocked),
+            Option::None => self.error(tr!(self.locale(), "not-logged-in.unblock")),
+        }
+    }
+
+    /// Returns whether the user the client is logged in as can see given message,
+    /// that is whether they have not blocked its sender and can read its channel.
+    #[doc(hidden)]
+    fn can_see(&self, message: &Message) -> bool {
+        let nickname = match self.nickname() {
+            Option::Some(nickname) => nickname,
+            Option::None => return false,
+        };
+        !self.has_blocked(message.sender()) && channel_list::can_read(message.channel(), &nickname)
+    }
+
+    /// Returns if the user the client is logged in as has blocked given user.
+    #[doc(hidden)]
+    fn has_blocked(&self, other: &str) -> bool {
+        self.nickname()
+            .map(|nickname| user_list::has_blocked(&nickname, other))
+            .unwrap_or(false)
+    }
+
+    #[doc(hidden)]
+    fn on_update_profile(&self, update: Profile) {
+        let nickname = match self.nickname() {
+            Option::Some(nickname) => nickname,
             Option::None => {
-                let message = "Tried to send a message while not logged in";
-                self.error(message.to_string());
-                log(LogLevel::Warning, message);
+                self.error(tr!(self.locale(), "not-logged-in.profile"));
+                return
+            }
+        };
+        // All fields are sanitized like messages. Display name must be an allowed
+        // nickname without line feeds or tabs, which are kept by sanitizing,
+        // other fields must be allowed messages:
+        let global_config = global_config();
+        let current = user_list::get_profile(&nickname).unwrap_or_default();
+        let display_name = merge_profile_field(current.display_name(), update.display_name().map(|name| sanitize::text(name)).as_ref(),
+            |text| sanitize::is_clean_name(text) && global_config.is_allowed_nickname(text));
+        let bio = merge_profile_field(current.bio(), update.bio().map(|bio| sanitize::text(bio)).as_ref(),
+            |text| global_config.is_allowed_message_text(text));
+        let status = merge_profile_field(current.status(), update.status().map(|status| sanitize::text(status)).as_ref(),
+            |text| global_config.is_allowed_message_text(text));
+        let color = match update.color() {
+            Option::Some(color) if color >= COLOR_COUNT => {
+                let client_message = tr!(self.locale(), "profile.invalid-color", max = COLOR_COUNT - 1);
+                let _ = self.send_command(s2c::Command::Warning(client_message));
+                return
             },
+            Option::Some(color) => Option::Some(color),
+            Option::None => current.color(),
+        };
+        match (display_name, bio, status) {
+            (Result::Ok(display_name), Result::Ok(bio), Result::Ok(status)) => {
+                // Tell other users about the new display name:
+                if display_name.as_ref() != current.display_name() {
+                    let notice = match &display_name {
+                        Option::Some(display_name) => tr!(catalog::default_locale(), "notice.display-name-changed", name = display_name),
+                        Option::None => tr!(catalog::default_locale(), "notice.display-name-removed"),
+                    };
+                    message_queue::push_notice(nickname.clone(), Option::None, notice);
+                }
+                user_list::set_profile(&nickname, Profile::new(display_name, bio, status, color));
+            },
+            (Result::Err(text), _, _) | (_, Result::Err(text), _) | (_, _, Result::Err(text)) => {
+                let client_message = tr!(self.locale(), "profile.not-allowed", text = text);
+                let _ = self.send_command(s2c::Command::Warning(client_message));
+            }
+        }
+    }
+
+    #[doc(hidden)]
+    fn on_get_profile(&self, nickname: String) {
+        let command = match user_list::get_profile(&nickname) {
+            Option::Some(profile) => s2c::Command::ProfileInfo { nickname, profile },
+            Option::None => s2c::Command::Warning(tr!(self.locale(), "user.not-found", nickname = nickname)),
+        };
+        if let Result::Err(err) = self.send_command(command) {
+            self.error(err.to_string());
+        }
+    }
+
+    #[doc(hidden)]
+    fn on_list_users(&self) {
+        if self.nickname().is_none() {
+            self.error(tr!(self.locale(), "not-logged-in.list-users"));
+            return
+        }
+        if let Result::Err(err) = self.send_command(s2c::Command::UserList(user_list::user_infos())) {
+            self.error(err.to_string());
+        }
+    }
+
+    #[doc(hidden)]
+    fn on_who_is(&self, nickname: String) {
+        if self.nickname().is_none() {
+            self.error(tr!(self.locale(), "not-logged-in.who-is"));
+            return
+        }
+        let command = match user_list::user_info(&nickname) {
+            Option::Some(info) => s2c::Command::UserInfo(info),
+            Option::None => s2c::Command::Warning(tr!(self.locale(), "user.not-found", nickname = nickname)),
+        };
+        if let Result::Err(err) = self.send_command(command) {
+            self.error(err.to_string());
         }
     }
+
+    #[doc(hidden)]
+    fn on_hide_last_seen(&self, hidden: bool) {
+        match self.nickname() {
+            Option::Some(nickname) => user_list::set_last_seen_hidden(&nickname, hidden),
+            Option::None => self.error(tr!(self.locale(), "not-logged-in.privacy")),
+        }
+    }
+
+    #[doc(hidden)]
+    fn on_time_sync(&self, client_sent_at: DateTime<Utc>, received_at: DateTime<Utc>) {
+        let time_sync = TimeSync::new(client_sent_at, received_at, Utc::now());
+        if let Result::Err(err) = self.send_command(s2c::Command::TimeSync(time_sync)) {
+            self.error(err.to_string());
+        }
+    }
+
+    #[doc(hidden)]
+    fn on_ack_up_to(&self, id: u64) {
+        // Messages which have not been sent to this client cannot be acknowledged:
+        if let Option::Some(nickname) = self.nickname() {
+            user_list::set_last_sent_msg_id(&nickname, id.min(self.last_delivered_msg_id()));
+        }
+    }
+
+    #[doc(hidden)]
+    fn on_mark_read(&self, id: u64) {
+        let nickname = match self.nickname() {
+            Option::Some(nickname) => nickname,
+            Option::None => {
+                self.error(tr!(self.locale(), "not-logged-in.mark-read"));
+                return
+            }
+        };
+        // Messages which have not been sent to this client cannot be read:
+        let id = id.min(self.last_delivered_msg_id());
+        let previous = match user_list::set_last_read_msg_id(&nickname, id) {
+            Option::Some(previous) => previous,
+            Option::None => return,
+        };
+        if user_list::is_last_seen_hidden(&nickname) {
+            return
+        }
+        // Tell each sender about the newest of their messages which has been read:
+        let limit = global_config().history().read().unwrap().get_page_size().get() as usize;
+        let (entries, _) = message_list::entries_in_range(previous, id.saturating_add(1), limit,
+            |_, message| message.sender() != &nickname && !message.is_from_server() && self.can_see(message));
+        let mut newest_read = BTreeMap::new();
+        for entry in entries {
+            newest_read.insert(entry.message().sender().clone(), entry.id());
+        }
+        client_list::for_each(|_, client| if let Option::Some(&up_to) = client.nickname().and_then(|sender| newest_read.get(&sender)) {
+            if let Result::Err(err) = client.send_command(s2c::Command::MessageRead { reader: nickname.clone(), up_to }) {
+                client.error(err.to_string());
+            }
+        });
+    }
+
+    #[doc(hidden)]
+    fn on_fetch_range(&self, after_id: u64, before_id: u64) {
+        if self.nickname().is_none() {
+            self.error(tr!(self.locale(), "not-logged-in.fetch"));
+            return
+        }
+        let limit = global_config().history().read().unwrap().get_search_max_results().get() as usize;
+        let (entries, complete) = message_list::entries_in_range(after_id, before_id, limit,
+            |_, message| self.can_see(message));
+        let response = s2c::Command::HistoryRange { after_id, before_id, entries, complete };
+        if let Result::Err(err) = self.send_command(response) {
+            self.error(err.to_string());
+        }
+    }
+
+    #[doc(hidden)]
+    fn on_fetch_history(&self, before_id: Option<u64>, limit: u32) {
+        if self.nickname().is_none() {
+            self.error(tr!(self.locale(), "not-logged-in.fetch"));
+            return
+        }
+        let page_size = global_config().history().read().unwrap().get_page_size().get();
+        let (entries, more) = message_list::entries_before(before_id, limit.min(page_size) as usize,
+            |_, message| self.can_see(message));
+        let response = s2c::Command::HistoryChunk { before_id, entries, more };
+        if let Result::Err(err) = self.send_command(response) {
+            self.error(err.to_string());
+        }
+    }
+
+    #[doc(hidden)]
+    fn on_set_availability(&self, availability: Availability) {
+        let nickname = match self.nickname() {
+            Option::Some(nickname) => nickname,
+            Option::None => {
+                self.error(tr!(self.locale(), "not-logged-in.availability"));
+                return
+            }
+        };
+        let previous = user_list::set_availability(&nickname, availability);
+        if previous != Availability::DoNotDisturb || is_deferring_direct_messages(&nickname) {
+            return
+        }
+        // Deliver deferred direct messages to all clients of the user:
+        let messages = mailbox::take(&nickname);
+        client_list::for_each(|_, client| if client.nickname().as_ref() == Option::Some(&nickname) {
+            for message in &messages {
+                if let Result::Err(err) = client.send_command(s2c::Command::DirectMessageRecv(message.clone())) {
+                    client.error(err.to_string());
+                    return
+                }
+            }
+        });
+    }
+}
+
+/// Returns whether direct messages addressed to given user are stored instead of
+/// being delivered, because the user does not want to be disturbed and global
+/// configuration says such messages should be deferred.
+#[doc(hidden)]
+fn is_deferring_direct_messages(nickname: &str) -> bool {
+    user_list::get_availability(nickname) == Availability::DoNotDisturb
+        && global_config().direct_messages().read().unwrap().is_defer_when_dnd()
+}
+
+/// Merges an updated field of a [`Profile`] into the current value of the field.
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] with the current value if the field is not updated
+///  -  [`Result::Ok`] with [`Option::None`] if the field is updated to an empty
+///     string
+///  -  [`Result::Ok`] with the updated value if it is allowed by `is_allowed`
+///  -  [`Result::Err`] with the updated value if it is not allowed
+#[doc(hidden)]
+fn merge_profile_field<F>(current: Option<&String>, update: Option<&String>, is_allowed: F) -> Result<Option<String>, String>
+where
+    F: Fn(&str) -> bool
+{
+    match update.map(|update| update.trim()) {
+        Option::None => Result::Ok(current.cloned()),
+        Option::Some("") => Result::Ok(Option::None),
+        Option::Some(update) if is_allowed(update) => Result::Ok(Option::Some(update.to_string())),
+        Option::Some(update) => Result::Err(update.to_string()),
+    }
 }
\ No newline at end of file
@@ -0,0 +1,364 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Registry of handlers of commands sent by clients. Each kind of
+//! [`c2s::Command`] has its own [`Handler`], which also states what is required
+//! from the client before the command is handled, so handlers themselves do not
+//! need to check permissions or rate limits. Commands cannot be used in read-only
+//! sessions unless their handlers are marked as [`Handler::read_only`], so a
+//! newly added command which changes anything is refused by default.
+
+use crate::client::Client;
+
+use chrono::DateTime;
+use chrono::Utc;
+
+use mdchat_common::command::c2s;
+use mdchat_common::markup;
+use mdchat_common::sanitize;
+
+use mdchat_serverconf::Capability;
+use mdchat_serverconf::RateLimitBucket;
+
+use once_cell::sync::Lazy;
+
+use std::collections::HashMap;
+
+/// Function handling a command. It gets the client which has sent the command
+/// and the time the command has been received at.
+type HandleFn = fn(&Client, c2s::Command, DateTime<Utc>);
+
+/// Handlers of commands, keyed by the name of the command they handle, see
+/// [`c2s::Command::name`].
+static HANDLERS: Lazy<HashMap<&'static str, Handler>> = Lazy::new(registry);
+
+/// Creates a [`Handler`] of commands matching given pattern. The handler must be
+/// registered under the name of the commands it handles.
+macro_rules! handler {
+    ($client:ident, $pattern:pat => $handle:expr) => {
+        Handler::new(|$client: &Client, command: c2s::Command, _: DateTime<Utc>| match command {
+            $pattern => $handle,
+            command => unreachable!("Handler of `{}` is registered under a wrong name", command.name()),
+        })
+    }
+}
+
+/// A handler of one kind of [`c2s::Command`]s.
+pub struct Handler {
+    handle: HandleFn,
+    read_only: bool,
+    capability: Option<Capability>,
+    rate_limit: Option<RateLimitBucket>,
+}
+
+impl Handler {
+    /// Creates a new [`Handler`] calling given function, which requires only the
+    /// session of the client not to be read-only.
+    #[doc(hidden)]
+    fn new(handle: HandleFn) -> Self {
+        Self {
+            handle,
+            read_only: false,
+            capability: Option::None,
+            rate_limit: Option::None,
+        }
+    }
+
+    /// Allows the command in read-only sessions, since it does not change
+    /// anything other users can see.
+    #[doc(hidden)]
+    fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    /// Requires the user to be allowed to use given capability.
+    #[doc(hidden)]
+    fn with_capability(mut self, capability: Capability) -> Self {
+        self.capability = Option::Some(capability);
+        self
+    }
+
+    /// Counts each handled command into given rate limit bucket of the user.
+    #[doc(hidden)]
+    fn with_rate_limit(mut self, bucket: RateLimitBucket) -> Self {
+        self.rate_limit = Option::Some(bucket);
+        self
+    }
+
+    /// Handles given command if the client meets the requirements of the
+    /// handler. Otherwise the client is warned and the command is dropped.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `client`: client which has sent the command
+    ///  -  `command`: command to be handled
+    ///  -  `received_at`: time the command has been received at
+    pub fn handle(&self, client: &Client, command: c2s::Command, received_at: DateTime<Utc>) {
+        if !self.read_only && !client.check_writable() {
+            return
+        }
+        if let Option::Some(capability) = self.capability {
+            if !client.require(capability) {
+                return
+            }
+        }
+        if let Option::Some(bucket) = self.rate_limit {
+            if let Option::Some(nickname) = client.nickname() {
+                if !client.check_rate_limit(&nickname, bucket) {
+                    return
+                }
+            }
+        }
+        (self.handle)(client, command, received_at)
+    }
+}
+
+/// Returns the handler of commands with given name, see [`c2s::Command::name`].
+pub fn get(name: &str) -> Option<&'static Handler> {
+    HANDLERS.get(name)
+}
+
+#[doc(hidden)]
+fn registry() -> HashMap<&'static str, Handler> {
+    let handlers = vec![
+        // Connecting:
+        ("Hello", handler!(client, c2s::Command::Hello { client_version, challenge, suppress_echo, locale, encodings, compressions } =>
+            client.on_hello(client_version, challenge, suppress_echo, locale, encodings, compressions))
+            .read_only()),
+        ("Login", handler!(client, c2s::Command::Login(request) => client.on_login(request))
+            .read_only()),
+        ("VerifyCode", handler!(client, c2s::Command::VerifyCode(code) => client.on_verify_code(code))
+            .read_only()),
+        ("TimeSync", Handler::new(|client, command, received_at| match command {
+            c2s::Command::TimeSync(client_sent_at) => client.on_time_sync(client_sent_at, received_at),
+            command => unreachable!("Handler of `{}` is registered under a wrong name", command.name()),
+        }).read_only()),
+        // Receiving the answer is enough to know the client is still connected:
+        ("Pong", handler!(_client, c2s::Command::Pong => {})
+            .read_only()),
+        // Messages. Texts which other users read are sanitized and their markup
+        // is normalized before anything else:
+        ("SendMessage", handler!(client, c2s::Command::SendMessage { channel, kind, text, ttl, client_tag } =>
+            client.on_message(channel, kind, markup::normalize(&sanitize::text(&text)), ttl, client_tag))
+            .with_capability(Capability::Send)
+            .with_rate_limit(RateLimitBucket::Messages)),
+        ("SendDirectMessage", handler!(client, c2s::Command::SendDirectMessage { recipient, text } =>
+            client.on_direct_message(recipient, markup::normalize(&sanitize::text(&text))))
+            .with_capability(Capability::Send)
+            .with_rate_limit(RateLimitBucket::DirectMessages)),
+        ("DeleteMessage", handler!(client, c2s::Command::DeleteMessage(id) => client.on_delete_message(id))),
+        ("React", handler!(client, c2s::Command::React { msg_id, emoji } => client.on_react(msg_id, emoji))),
+        ("AckUpTo", handler!(client, c2s::Command::AckUpTo(id) => client.on_ack_up_to(id))
+            .read_only()),
+        ("MarkRead", handler!(client, c2s::Command::MarkRead(id) => client.on_mark_read(id))
+            .read_only()),
+        ("FetchRange", handler!(client, c2s::Command::FetchRange { after_id, before_id } =>
+            client.on_fetch_range(after_id, before_id))
+            .read_only()),
+        ("FetchHistory", handler!(client, c2s::Command::FetchHistory { before_id, limit } =>
+            client.on_fetch_history(before_id, limit))
+            .read_only()),
+        ("Search", handler!(client, c2s::Command::Search { query, regex, limit } =>
+            client.on_search(query, regex, limit))
+            .read_only()
+            .with_capability(Capability::SearchHistory)
+            .with_rate_limit(RateLimitBucket::Searches)),
+        ("ExportHistory", handler!(client, c2s::Command::ExportHistory { from, to } =>
+            client.on_export_history(from, to))
+            .read_only()
+            .with_capability(Capability::ExportHistory)),
+        // Channels:
+        ("ListChannels", handler!(client, c2s::Command::ListChannels => client.send_channel_infos())
+            .read_only()),
+        ("JoinChannel", handler!(client, c2s::Command::JoinChannel(name) => client.on_join_channel(name))),
+        ("LeaveChannel", handler!(client, c2s::Command::LeaveChannel(name) => client.on_leave_channel(name))),
+        ("Invite", handler!(client, c2s::Command::Invite { channel, nickname } => client.on_invite(channel, nickname))),
+        ("SetTopic", handler!(client, c2s::Command::SetTopic { channel, topic } =>
+            client.on_set_topic(channel, sanitize::text(&topic)))),
+        ("CreateChannel", handler!(client, c2s::Command::CreateChannel { name, mode } =>
+            client.on_create_channel(name, mode))
+            .with_capability(Capability::CreateChannel)),
+        ("DeleteChannel", handler!(client, c2s::Command::DeleteChannel(name) => client.on_delete_channel(name))),
+        ("SetChannelModerator", handler!(client, c2s::Command::SetChannelModerator { channel, nickname, moderator } =>
+            client.on_set_channel_moderator(channel, nickname, moderator))),
+        // Users:
+        ("Block", handler!(client, c2s::Command::Block(nickname) => client.on_block(nickname))),
+        ("Unblock", handler!(client, c2s::Command::Unblock(nickname) => client.on_unblock(nickname))),
+        ("UpdateProfile", handler!(client, c2s::Command::UpdateProfile(profile) => client.on_update_profile(profile))),
+        ("GetProfile", handler!(client, c2s::Command::GetProfile(nickname) => client.on_get_profile(nickname))
+            .read_only()),
+        ("ListUsers", handler!(client, c2s::Command::ListUsers => client.on_list_users())
+            .read_only()),
+        ("WhoIs", handler!(client, c2s::Command::WhoIs(nickname) => client.on_who_is(nickname))
+            .read_only()),
+        ("HideLastSeen", handler!(client, c2s::Command::HideLastSeen(hidden) => client.on_hide_last_seen(hidden))),
+        ("SetAvailability", handler!(client, c2s::Command::SetAvailability(availability) =>
+            client.on_set_availability(availability))),
+        // Administration and moderation:
+        ("Tail", handler!(client, c2s::Command::Tail(level) => client.on_tail(level))
+            .read_only()
+            .with_capability(Capability::ReadLog)),
+        ("SubscribeModEvents", handler!(client, c2s::Command::SubscribeModEvents(subscribe) =>
+            client.on_subscribe_mod_events(subscribe))
+            .read_only()
+            .with_capability(Capability::ModEvents)),
+        ("SetLockdown", handler!(client, c2s::Command::SetLockdown(active) => client.on_set_lockdown(active))
+            .with_capability(Capability::Lockdown)),
+        ("ListConnections", handler!(client, c2s::Command::ListConnections => client.on_list_connections())
+            .read_only()
+            .with_capability(Capability::ListConnections)),
+        ("ApproveUser", handler!(client, c2s::Command::ApproveUser(nickname) => client.on_approve_user(nickname))
+            .with_capability(Capability::ApproveUsers)),
+        ("GetFilterStats", handler!(client, c2s::Command::GetFilterStats => client.on_get_filter_stats())
+            .read_only()
+            .with_capability(Capability::FilterStats)),
+        ("Explain", handler!(client, c2s::Command::Explain { filter, input } => client.on_explain(filter, input))
+            .read_only()
+            .with_capability(Capability::FilterStats)),
+        ("AddFilterRule", handler!(client, c2s::Command::AddFilterRule { filter, rule, persist } =>
+            client.on_add_filter_rule(filter, rule, persist))
+            .with_capability(Capability::Ban)),
+        ("ListReviewQueue", handler!(client, c2s::Command::ListReviewQueue => client.on_list_review_queue())
+            .read_only()
+            .with_capability(Capability::ReviewMessages)),
+        ("ReviewMessage", handler!(client, c2s::Command::ReviewMessage { id, approve } =>
+            client.on_review_message(id, approve))
+            .with_capability(Capability::ReviewMessages)),
+        ("ExportUsers", handler!(client, c2s::Command::ExportUsers => client.on_export_users())
+            .read_only()
+            .with_capability(Capability::ManageUsers)),
+        ("ImportUsers", handler!(client, c2s::Command::ImportUsers { accounts, overwrite } =>
+            client.on_import_users(accounts, overwrite))
+            .with_capability(Capability::ManageUsers)),
+    ];
+    handlers.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::get;
+    use super::Handler;
+
+    use crate::client::Client;
+    use crate::global_config;
+    use crate::GLOBAL_CONFIG;
+
+    use chrono::DateTime;
+    use chrono::Utc;
+
+    use mdchat_common::command::c2s;
+
+    use mdchat_serverconf::Capability;
+    use mdchat_serverconf::Config;
+    use mdchat_serverconf::RateLimitBucket;
+    use mdchat_serverconf::Role;
+
+    use mdswp::MdswpListener;
+    use mdswp::MdswpStream;
+
+    use std::cell::Cell;
+    use std::net::SocketAddr;
+    use std::num::NonZeroU32;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    thread_local! {
+        /// Number of commands passed to [`count`] by the current test.
+        static HANDLED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// Handling function which only counts handled commands.
+    fn count(_: &Client, _: c2s::Command, _: DateTime<Utc>) {
+        HANDLED.with(|handled| handled.set(handled.get() + 1));
+    }
+
+    /// Lets given handler handle a command of given client and returns whether
+    /// the command has been passed to the handling function.
+    fn is_handled(handler: &Handler, client: &Client) -> bool {
+        let before = HANDLED.with(Cell::get);
+        handler.handle(client, c2s::Command::Pong, Utc::now());
+        HANDLED.with(Cell::get) > before
+    }
+
+    /// Returns a client connected over the loopback, logged in as given user if
+    /// a nickname is given, together with the other end of the connection, which
+    /// must be kept open while the client is used.
+    fn connect(nickname: Option<&str>) -> (Arc<Client>, MdswpStream) {
+        GLOBAL_CONFIG.get_or_init(|| Arc::new(Config::new()));
+        let listener = MdswpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+        let local_addr = listener.local_addr().unwrap();
+        let peer = thread::spawn(move || MdswpStream::connect(local_addr).unwrap());
+        let (stream, _) = listener.incoming().next().unwrap().unwrap();
+        let client = Client::new(stream, local_addr, false, false);
+        if let Option::Some(nickname) = nickname {
+            client.set_nickname(nickname.to_string());
+        }
+        (client, peer.join().unwrap())
+    }
+
+    #[test]
+    fn commands_are_refused_in_read_only_sessions_by_default() {
+        let (client, _peer) = connect(Option::Some("handler-reader"));
+        assert!(is_handled(&Handler::new(count), &client));
+        client.read_only.store(true, Ordering::Relaxed);
+        assert!(!is_handled(&Handler::new(count), &client));
+        assert!(is_handled(&Handler::new(count).read_only(), &client));
+    }
+
+    #[test]
+    fn capabilities_are_required_from_users() {
+        let (user, _user_peer) = connect(Option::Some("handler-user"));
+        global_config().roles().write().unwrap().assign("handler-admin".to_string(), Role::Admin);
+        let (admin, _admin_peer) = connect(Option::Some("handler-admin"));
+        let (anonymous, _anonymous_peer) = connect(Option::None);
+        let send = Handler::new(count).with_capability(Capability::Send);
+        let lockdown = Handler::new(count).with_capability(Capability::Lockdown);
+        assert!(is_handled(&send, &user));
+        assert!(!is_handled(&lockdown, &user));
+        assert!(is_handled(&lockdown, &admin));
+        // Users who have not logged in have no capabilities:
+        assert!(!is_handled(&send, &anonymous));
+    }
+
+    #[test]
+    fn rate_limited_commands_are_refused_when_bucket_is_empty() {
+        {
+            let global_config = GLOBAL_CONFIG.get_or_init(|| Arc::new(Config::new()));
+            let mut rate_limits = global_config.rate_limits().write().unwrap();
+            let bucket = rate_limits.get_bucket_mut(RateLimitBucket::Connections);
+            bucket.set_rate(Option::Some((NonZeroU32::MIN, Duration::from_secs(3600)))).unwrap();
+            bucket.set_burst(NonZeroU32::new(2));
+        }
+        let (client, _peer) = connect(Option::Some("handler-limited"));
+        let handler = Handler::new(count).with_rate_limit(RateLimitBucket::Connections);
+        assert!(is_handled(&handler, &client));
+        assert!(is_handled(&handler, &client));
+        assert!(!is_handled(&handler, &client));
+    }
+
+    #[test]
+    fn registered_handlers_handle_commands_of_their_name() {
+        let (client, _peer) = connect(Option::Some("handler-registered"));
+        // Handlers registered under a wrong name would panic:
+        get("Pong").unwrap().handle(&client, c2s::Command::Pong, Utc::now());
+        get("ListChannels").unwrap().handle(&client, c2s::Command::ListChannels, Utc::now());
+        assert!(get("Forwarded").is_none());
+    }
+}
@@ -20,7 +20,9 @@ use crate::client::Client;
 use once_cell::sync::Lazy;
 
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::net::SocketAddr;
+use std::ptr;
 use std::sync::Arc;
 use std::sync::RwLock;
 
@@ -39,13 +41,47 @@ pub fn add_connection(client: Arc<Client>) {
 
 }
 
-/// Removes specified client from the client list and returns a [`ClientInfo`] which
-/// was associated with the client. If the client is not connected, method will
-/// panic.
-pub fn remove_connection(addr: &SocketAddr) -> Arc<Client> {
-    CLIENT_LIST.write().unwrap()
-        .remove(addr)
-        .expect("Specified client not connected")
+/// Removes specified client from the client list. The client may be removed
+/// concurrently by its own thread and by the keepalive reaper, so it is not an
+/// error if the client is not in the list anymore.
+///
+/// # Return value
+///
+///  -  [`Option::Some`] with `true` if the client was the last connection on
+///     which its user was logged in
+///  -  [`Option::Some`] with `false` if the user is logged in on other
+///     connections, or if the client was not logged in
+///  -  [`Option::None`] if the client has been already removed
+pub fn remove_connection(client: &Client) -> Option<bool> {
+    let mut client_list = CLIENT_LIST.write().unwrap();
+    // A new connection may have the same address as a connection which has been
+    // removed already:
+    match client_list.get(client.socket_addr()) {
+        Option::Some(listed) if ptr::eq(Arc::as_ptr(listed), client) => {},
+        _ => return Option::None,
+    }
+    client_list.remove(client.socket_addr());
+    // Connections are counted while the list is locked, so when connections of
+    // the same user are closed concurrently, only one of them is the last one:
+    match client.nickname() {
+        Option::Some(nickname) => Option::Some(count_connections(&client_list, &nickname) == 0),
+        Option::None => Option::Some(false),
+    }
+}
+
+/// Marks given connected client as logged in as given user.
+///
+/// # Return value
+///
+///  -  `true` if the client is the only connection on which the user is logged
+///     in
+///  -  `false` otherwise
+pub fn log_in(client: &Client, nickname: String) -> bool {
+    // Connections are counted while the list is locked, so when the same user
+    // logs in concurrently, only one of the connections is the first one:
+    let client_list = CLIENT_LIST.write().unwrap();
+    client.set_nickname(nickname.clone());
+    count_connections(&client_list, &nickname) == 1
 }
 
 /// Calls the same function for all connected clients.
@@ -55,4 +91,30 @@ pub fn for_each<F>(mut f: F)
     CLIENT_LIST.read().unwrap()
         .iter()
         .for_each(|(sock_addr, client_info)| f(sock_addr, client_info))
-}
\ No newline at end of file
+}
+
+/// Returns all connected clients. Unlike [`for_each`], the client list is not
+/// locked while the clients are used, so they can be removed from it.
+pub fn all() -> Vec<Arc<Client>> {
+    CLIENT_LIST.read().unwrap().values().cloned().collect()
+}
+
+/// Returns the number of connected clients.
+pub fn count() -> usize {
+    CLIENT_LIST.read().unwrap().len()
+}
+
+/// Returns nicknames of all users which are currently logged in.
+pub fn online_nicknames() -> BTreeSet<String> {
+    CLIENT_LIST.read().unwrap()
+        .values()
+        .filter_map(|client| client.nickname())
+        .collect()
+}
+
+#[doc(hidden)]
+fn count_connections(client_list: &BTreeMap<SocketAddr, Arc<Client>>, nickname: &str) -> usize {
+    client_list.values()
+        .filter(|client| client.nickname().as_deref() == Option::Some(nickname))
+        .count()
+}
@@ -0,0 +1,168 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! A module for the state of a connection of a client. Each state allows only
+//! some commands, so a command sent at a wrong moment is always rejected the
+//! same way, instead of each command checking what has happened before.
+
+use mdchat_common::command::c2s;
+
+use std::fmt;
+
+/// State of a connection of a client. States follow each other in this order,
+/// although a client may log in without saying hello first and the connection
+/// can start draining or be closed in any state:
+///
+/// ```text
+/// Connected → HandshakeDone → Authenticated → Draining → Closed
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// The connection has been accepted, but the client has not said hello yet.
+    Connected,
+    /// The server has proven its identity to the client, but no user has logged
+    /// in yet, for example because the user has to verify their e-mail address.
+    HandshakeDone,
+    /// A user has logged in.
+    Authenticated,
+    /// The connection is being closed. Commands queued for the client are still
+    /// written, but commands received from it are ignored.
+    Draining,
+    /// The connection has been closed.
+    Closed,
+}
+
+impl ConnectionState {
+    /// Returns whether given command can be received in this state.
+    ///
+    /// Clients which do not implement the handshake can log in without saying
+    /// hello, unless the server requires clients to report their version, and
+    /// verify their e-mail address afterwards. The handshake cannot be repeated
+    /// and a user cannot log in again over the same connection.
+    pub fn allows(self, command: &c2s::Command) -> bool {
+        match self {
+            Self::Connected => matches!(command,
+                c2s::Command::Hello { .. } | c2s::Command::Login(..) | c2s::Command::VerifyCode(..)
+                | c2s::Command::TimeSync(..)),
+            Self::HandshakeDone => matches!(command,
+                c2s::Command::Login(..) | c2s::Command::VerifyCode(..) | c2s::Command::TimeSync(..)
+                | c2s::Command::Pong),
+            Self::Authenticated => !matches!(command,
+                c2s::Command::Hello { .. } | c2s::Command::Login(..) | c2s::Command::VerifyCode(..)),
+            Self::Draining | Self::Closed => false,
+        }
+    }
+
+    /// Returns whether the connection can change from this state into given one.
+    pub fn can_become(self, next: Self) -> bool {
+        match (self, next) {
+            (Self::Connected, Self::HandshakeDone) => true,
+            (Self::Connected | Self::HandshakeDone, Self::Authenticated) => true,
+            (Self::Connected | Self::HandshakeDone | Self::Authenticated, Self::Draining) => true,
+            // The connection may be closed by the client at any time:
+            (Self::Closed, _) => false,
+            (_, Self::Closed) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns whether commands can be received in this state, that is whether
+    /// the connection is neither draining nor closed.
+    pub fn is_open(self) -> bool {
+        !matches!(self, Self::Draining | Self::Closed)
+    }
+
+    /// Returns whether a user has logged in, see [`ConnectionState::Authenticated`].
+    pub fn is_authenticated(self) -> bool {
+        self == Self::Authenticated
+    }
+}
+
+impl fmt::Display for ConnectionState {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        fmtr.write_str(match self {
+            Self::Connected => "connected",
+            Self::HandshakeDone => "handshake done",
+            Self::Authenticated => "authenticated",
+            Self::Draining => "draining",
+            Self::Closed => "closed",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConnectionState;
+
+    use chrono::Utc;
+
+    use mdchat_common::command::c2s;
+    use mdchat_common::login::LoginRequest;
+
+    /// Returns a command logging in as a user.
+    fn login() -> c2s::Command {
+        c2s::Command::Login(LoginRequest::login("alice".to_string(), "password".to_string()))
+    }
+
+    /// Returns a command verifying an e-mail address.
+    fn verify_code() -> c2s::Command {
+        c2s::Command::VerifyCode("123456".to_string())
+    }
+
+    #[test]
+    fn user_can_verify_email_without_saying_hello() {
+        assert!(ConnectionState::Connected.allows(&login()));
+        assert!(ConnectionState::Connected.allows(&verify_code()));
+        assert!(ConnectionState::HandshakeDone.allows(&verify_code()));
+    }
+
+    #[test]
+    fn commands_of_logged_in_users_wait_for_login() {
+        for state in [ConnectionState::Connected, ConnectionState::HandshakeDone] {
+            assert!(!state.allows(&c2s::Command::ListChannels), "{}", state);
+            assert!(!state.allows(&c2s::Command::MarkRead(1)), "{}", state);
+        }
+        assert!(ConnectionState::Authenticated.allows(&c2s::Command::ListChannels));
+    }
+
+    #[test]
+    fn handshake_and_login_cannot_be_repeated() {
+        assert!(!ConnectionState::Authenticated.allows(&login()));
+        assert!(!ConnectionState::Authenticated.allows(&verify_code()));
+    }
+
+    #[test]
+    fn closing_connections_allow_nothing() {
+        for state in [ConnectionState::Draining, ConnectionState::Closed] {
+            assert!(!state.is_open());
+            assert!(!state.allows(&c2s::Command::TimeSync(Utc::now())), "{}", state);
+            assert!(!state.allows(&c2s::Command::Pong), "{}", state);
+        }
+    }
+
+    #[test]
+    fn states_follow_each_other() {
+        assert!(ConnectionState::Connected.can_become(ConnectionState::HandshakeDone));
+        assert!(ConnectionState::Connected.can_become(ConnectionState::Authenticated));
+        assert!(ConnectionState::HandshakeDone.can_become(ConnectionState::Authenticated));
+        assert!(ConnectionState::Authenticated.can_become(ConnectionState::Draining));
+        assert!(ConnectionState::Draining.can_become(ConnectionState::Closed));
+        assert!(!ConnectionState::Authenticated.can_become(ConnectionState::HandshakeDone));
+        assert!(!ConnectionState::Draining.can_become(ConnectionState::Authenticated));
+        assert!(!ConnectionState::Closed.can_become(ConnectionState::Closed));
+        assert!(!ConnectionState::Closed.can_become(ConnectionState::Connected));
+    }
+}
@@ -0,0 +1,116 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::global_config;
+use crate::process;
+
+use rand::Rng;
+
+use std::io;
+use std::process::Command;
+use std::process::Stdio;
+
+/// Returns whether given string looks like an e-mail address. Only the basic
+/// shape is checked, whether the address exists is checked by sending the
+/// verification code. Addresses starting with `-` are refused, so they cannot be
+/// taken for an option by the command sending the code.
+pub fn is_valid_address(address: &str) -> bool {
+    match address.split_once('@') {
+        Option::Some((local, domain)) => !local.is_empty()
+            && !local.starts_with('-')
+            && domain.contains('.')
+            && !domain.starts_with('.')
+            && !domain.ends_with('.')
+            && !domain.contains('@')
+            && !address.chars().any(char::is_whitespace),
+        Option::None => false,
+    }
+}
+
+/// Generates a random six-digit verification code.
+pub fn generate_code() -> String {
+    format!("{:06}", rand::thread_rng().gen_range(0..1_000_000))
+}
+
+/// Sends given verification code to given e-mail address using the command
+/// given by global configuration. The address and the code are passed to the
+/// command as its last two arguments after `--`, so the address is not taken for
+/// an option. The command is killed if it does not exit within the timeout given
+/// by global configuration.
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] if the command exited successfully
+///  -  [`Result::Err`] if no command is configured, the command could not be
+///     run, it has not exited in time or it exited unsuccessfully
+pub fn send_verification_code(address: &str, code: &str) -> io::Result<()> {
+    let (command, timeout) = {
+        let global_config = global_config();
+        let auth_config = global_config.auth().read().unwrap();
+        (auth_config.get_email().get_command().clone(), auth_config.get_email().get_timeout())
+    };
+    let (program, args) = command.split_first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No command for sending verification codes is configured"))?;
+    let mut child = Command::new(program)
+        .args(args)
+        .arg("--")
+        .arg(address)
+        .arg(code)
+        .stdin(Stdio::null())
+        .spawn()?;
+    let status = process::wait_with_timeout(&mut child, timeout)?;
+    match status.success() {
+        true => Result::Ok(()),
+        false => Result::Err(io::Error::other(format!("`{}` exited with {}", program, status))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::generate_code;
+    use super::is_valid_address;
+
+    #[test]
+    fn plausible_addresses_are_valid() {
+        assert!(is_valid_address("alice@example.com"));
+        assert!(is_valid_address("alice.smith+chat@mail.example.co.uk"));
+        assert!(is_valid_address("a-b@example.com"));
+    }
+
+    #[test]
+    fn malformed_addresses_are_invalid() {
+        for address in ["", "alice", "@example.com", "alice@example", "alice@.example.com", "alice@example.com.",
+                "alice@ex@ample.com", "alice smith@example.com", "alice@example.com\n"] {
+            assert!(!is_valid_address(address), "`{}` is valid", address);
+        }
+    }
+
+    #[test]
+    fn addresses_looking_like_options_are_invalid() {
+        assert!(!is_valid_address("-oFoo=x@a.b"));
+        assert!(!is_valid_address("--help@example.com"));
+    }
+
+    #[test]
+    fn codes_have_six_digits() {
+        for _ in 0..100 {
+            let code = generate_code();
+            assert_eq!(code.len(), 6);
+            assert!(code.chars().all(|c| c.is_ascii_digit()));
+        }
+    }
+}
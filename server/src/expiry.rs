@@ -0,0 +1,42 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use chrono::Utc;
+
+use crate::message_list;
+use crate::message_queue;
+
+use tracing::debug;
+
+use std::thread;
+use std::time::Duration;
+
+/// Function containing a loop for continuous removal of expired messages. Clients
+/// which are logged in are informed about each removed message. This function
+/// should be called in a separate thread.
+pub fn remove_expired_periodically() {
+    loop {
+        let expired = message_list::remove_expired(Utc::now());
+        if !expired.is_empty() {
+            debug!("{} messages have expired", expired.len());
+        }
+        for (id, channel) in expired {
+            message_queue::broadcast_deleted(id, channel.as_ref());
+        }
+        thread::sleep(Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,53 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::global_config;
+
+use mdchat_common::moderation::FilterHits;
+
+use once_cell::sync::Lazy;
+
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+/// Number of matched messages for each pattern and each user.
+static HITS: Lazy<RwLock<BTreeMap<String, BTreeMap<String, u64>>>> = Lazy::new(|| RwLock::new(BTreeMap::new()));
+
+/// Records that a message sent by given user has matched given banned or trial
+/// pattern.
+pub fn record(pattern: &str, nickname: &str) {
+    let mut hits = HITS.write().unwrap();
+    let per_user = hits.entry(pattern.to_string()).or_default();
+    *per_user.entry(nickname.to_string()).or_default() += 1;
+}
+
+/// Returns number of matched messages for all banned and trial patterns of the
+/// message filter, in the order they are given in the configuration.
+pub fn snapshot() -> Vec<FilterHits> {
+    let global_config = global_config();
+    let message_filtering = global_config.message_filtering().read().unwrap();
+    let hits = HITS.read().unwrap();
+    let banned = message_filtering.get_banned_patterns().iter().map(|pattern| (pattern, false));
+    let trial = message_filtering.get_trial_patterns().iter().map(|pattern| (pattern, true));
+    banned.chain(trial)
+        .map(|(pattern, trial)| FilterHits::new(
+            pattern.to_string(),
+            trial,
+            hits.get(pattern.as_str()).cloned().unwrap_or_default(),
+        ))
+        .collect()
+}
@@ -0,0 +1,285 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! A module for the WebSocket gateway. Browser clients cannot open raw
+//! connections, so the gateway accepts WebSocket connections and bridges each of
+//! them to a regular connection to the server. Each binary WebSocket message
+//! carries a single frame, that is a single encoded command.
+//!
+//! The server sees bridged connections as coming from the gateway, so the
+//! gateway sends [`c2s::Command::Forwarded`] with the address of the browser
+//! first. The server trusts it only from the address of the gateway and only
+//! with the secret token of this process, see [`is_upstream_peer`] and
+//! [`is_own_token`], so bans, rate limits and raid detection apply to each
+//! browser separately.
+
+use crate::global_config;
+use crate::http::ConnectionLimit;
+use crate::socket;
+
+use mdchat_common::codec::Encoding;
+use mdchat_common::command::c2s;
+use mdchat_common::Error;
+
+use mdswp::MdswpStream;
+
+use once_cell::sync::Lazy;
+use once_cell::sync::OnceCell;
+
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+
+use subtle::ConstantTimeEq;
+
+use tungstenite::Message;
+use tungstenite::WebSocket;
+
+use tracing::debug;
+use tracing::error;
+use tracing::info;
+use tracing::warn;
+
+use std::convert::TryFrom;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::net::IpAddr;
+use std::net::SocketAddr;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::sync::mpsc;
+use std::sync::mpsc::TryRecvError;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+/// How often frames received from the server are forwarded to the browser.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Maximum time of the WebSocket handshake, and of writing a single frame to the
+/// browser.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Maximum number of browsers connected through the gateway at once. Each of
+/// them takes two threads, one for each direction.
+const MAX_CONNECTIONS: usize = 1024;
+
+/// Secret token the gateway proves it is part of this process with, see
+/// [`c2s::Command::Forwarded`].
+static TOKEN: Lazy<String> = Lazy::new(|| rand::thread_rng()
+    .sample_iter(&Alphanumeric)
+    .take(32)
+    .map(char::from)
+    .collect());
+
+/// Socket address of the server the gateway bridges connections to.
+static UPSTREAM: OnceCell<SocketAddr> = OnceCell::new();
+
+/// Returns whether a connection from given IP address may come from the gateway,
+/// that is whether the gateway runs and bridges connections to given IP address.
+/// The gateway connects from the same address when it bridges connections to the
+/// server on the same host.
+pub fn is_upstream_peer(ip_addr: IpAddr) -> bool {
+    UPSTREAM.get().map(SocketAddr::ip) == Option::Some(ip_addr)
+}
+
+/// Returns whether given token is the secret token of the gateway of this
+/// process, see [`c2s::Command::Forwarded`].
+pub fn is_own_token(token: &str) -> bool {
+    bool::from(TOKEN.as_bytes().ct_eq(token.as_bytes()))
+}
+
+/// Accepts WebSocket connections and bridges them to given address of the server.
+/// This is a blocking function to be run in a separate thread.
+///
+/// # Parameters
+///
+///  -  `listener`: the listener to listen on
+///  -  `upstream`: socket address of the server
+///  -  `keepalive`: time an accepted connection may be idle before TCP
+///     keepalive probes are sent, or [`Option::None`] to disable them
+pub fn listen(listener: &TcpListener, upstream: SocketAddr, keepalive: Option<Duration>) {
+    // The upstream stays the same when the gateway is restarted:
+    let _ = UPSTREAM.set(upstream);
+    let limit = ConnectionLimit::new(MAX_CONNECTIONS);
+    for stream in listener.incoming() {
+        match stream {
+            Result::Ok(stream) => match limit.acquire() {
+                Option::Some(permit) => {
+                    let _ = thread::Builder::new()
+                        .name("gateway connection".to_string())
+                        .spawn(move || {
+                            handle_conn(stream, upstream, keepalive);
+                            drop(permit);
+                        });
+                },
+                Option::None => warn!("Too many connections to the WebSocket gateway, closing a new one"),
+            },
+            Result::Err(err) => warn!("A client could not connect to the WebSocket gateway: {}", err),
+        }
+    }
+}
+
+#[doc(hidden)]
+fn handle_conn(stream: TcpStream, upstream: SocketAddr, keepalive: Option<Duration>) {
+    // Banned browsers are rejected before a connection to the server is opened.
+    // Rate limits are applied by the server, see `listener::admit`:
+    let peer_addr = match stream.peer_addr() {
+        Result::Ok(peer_addr) => peer_addr,
+        Result::Err(_) => return,
+    };
+    if !global_config().is_allowed_ip_addr(&peer_addr.ip()) {
+        return;
+    }
+    if let Result::Err(err) = socket::keep_alive(&stream, keepalive) {
+        debug!("Could not enable TCP keepalive for {}: {}", peer_addr, err);
+    }
+    if let Result::Err(err) = stream.set_write_timeout(Option::Some(HANDSHAKE_TIMEOUT)) {
+        debug!("Could not set write timeout for {}: {}", peer_addr, err);
+        return;
+    }
+    let stream = BrowserStream { stream, deadline: Option::Some(Instant::now() + HANDSHAKE_TIMEOUT) };
+    let mut websocket = match tungstenite::accept(stream) {
+        Result::Ok(websocket) => websocket,
+        Result::Err(err) => {
+            debug!("WebSocket handshake with {} failed: {}", peer_addr, err);
+            return;
+        }
+    };
+    let mut server = match MdswpStream::connect(upstream) {
+        Result::Ok(server) => server,
+        Result::Err(err) => {
+            error!("WebSocket gateway could not connect to {}: {}", upstream, err);
+            let _ = websocket.close(Option::None);
+            return;
+        }
+    };
+    info!("WebSocket client {} connected through the gateway", peer_addr);
+    if let Result::Err(err) = forward(&mut server, peer_addr).and_then(|_| bridge(&mut websocket, &mut server)) {
+        debug!("WebSocket client {} disconnected: {}", peer_addr, err);
+    }
+    let _ = websocket.close(Option::None);
+    let _ = websocket.flush();
+    let _ = server.finish_write();
+    let _ = server.reset();
+}
+
+/// Tells the server the socket address of the browser the connection is bridged
+/// for.
+#[doc(hidden)]
+fn forward(server: &mut MdswpStream, peer_addr: SocketAddr) -> Result<(), Error> {
+    let command = c2s::Command::Forwarded { peer_addr, token: TOKEN.clone() };
+    write_frame(server, &Encoding::Json.encode(&command)?)
+}
+
+/// Forwards frames between the browser and the server until either side closes
+/// the connection.
+#[doc(hidden)]
+fn bridge(websocket: &mut WebSocket<BrowserStream>, server: &mut MdswpStream) -> Result<(), Error> {
+    // Reading from the server blocks, so it is done by a separate thread:
+    let mut server_reader = server.try_clone()?;
+    let (sender, receiver) = mpsc::channel();
+    thread::Builder::new()
+        .name("gateway upstream".to_string())
+        .spawn(move || while let Result::Ok(frame) = read_frame(&mut server_reader) {
+            if sender.send(frame).is_err() {
+                break;
+            }
+        })?;
+    websocket.get_mut().deadline = Option::None;
+    websocket.get_ref().stream.set_read_timeout(Option::Some(POLL_INTERVAL))?;
+    loop {
+        // Forward frames from the server:
+        loop {
+            match receiver.try_recv() {
+                Result::Ok(frame) => websocket.send(Message::Binary(frame)).map_err(ws_error)?,
+                Result::Err(TryRecvError::Empty) => break,
+                Result::Err(TryRecvError::Disconnected) => return Result::Ok(()),
+            }
+        }
+        // Forward frames from the browser:
+        match websocket.read() {
+            Result::Ok(Message::Binary(frame)) => write_frame(server, &frame)?,
+            Result::Ok(Message::Close(_)) => return Result::Ok(()),
+            Result::Ok(_) => {},
+            Result::Err(tungstenite::Error::Io(err))
+                if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {},
+            Result::Err(err) => return Result::Err(ws_error(err)),
+        }
+    }
+}
+
+/// Connection of a browser. Until its deadline is removed, reading fails once the
+/// deadline has passed, so a browser cannot hold the connection open by sending
+/// the WebSocket handshake byte by byte.
+#[doc(hidden)]
+struct BrowserStream {
+    stream: TcpStream,
+    deadline: Option<Instant>,
+}
+
+impl Read for BrowserStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Option::Some(deadline) = self.deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Result::Err(io::Error::new(io::ErrorKind::TimedOut, "WebSocket handshake has not finished in time"))
+            }
+            self.stream.set_read_timeout(Option::Some(remaining))?;
+        }
+        self.stream.read(buf)
+    }
+}
+
+impl Write for BrowserStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+/// Reads a single frame prefixed by its length from the server.
+#[doc(hidden)]
+fn read_frame(server: &mut MdswpStream) -> Result<Vec<u8>, Error> {
+    let mut len = [0; 4];
+    server.read_exact(&mut len)?;
+    let mut frame = vec![0; u32::from_be_bytes(len) as usize];
+    server.read_exact(&mut frame)?;
+    Result::Ok(frame)
+}
+
+/// Writes a single frame prefixed by its length to the server.
+#[doc(hidden)]
+fn write_frame(server: &mut MdswpStream, frame: &[u8]) -> Result<(), Error> {
+    let len = u32::try_from(frame.len())
+        .map_err(|_| Error::Framing("Maximum size of a single command exceeded".to_string()))?;
+    server.write_all(&len.to_be_bytes())?;
+    server.write_all(frame)?;
+    Result::Ok(server.flush()?)
+}
+
+/// Converts an error of the WebSocket connection into an [`Error`]. Errors other
+/// than I/O errors mean that the browser does not follow the WebSocket protocol.
+#[doc(hidden)]
+fn ws_error(err: tungstenite::Error) -> Error {
+    match err {
+        tungstenite::Error::Io(err) => Error::Transport(err),
+        err => Error::Protocol(err.to_string()),
+    }
+}
@@ -0,0 +1,169 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! A module for the HTTP health endpoint used by liveness and readiness probes
+//! and by monitoring.
+
+use crate::client_list;
+use crate::http;
+use crate::http::ConnectionLimit;
+use crate::http::HttpError;
+use crate::lockdown;
+use crate::memory;
+use crate::memory::Store;
+use crate::message_list;
+use crate::socket;
+use crate::user_list;
+
+use once_cell::sync::Lazy;
+
+use serde_json::json;
+
+use tracing::debug;
+
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Time the server has started at.
+static STARTED_AT: Lazy<Instant> = Lazy::new(Instant::now);
+
+/// Number of sockets the server listens on for connections.
+static LISTENERS: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether the server has loaded its state and handles messages.
+static READY: AtomicBool = AtomicBool::new(false);
+
+/// Records the time the server has started at. It should be called as soon as
+/// the server starts, so the uptime is accurate.
+pub fn mark_started() {
+    Lazy::force(&STARTED_AT);
+}
+
+/// Sets the number of sockets the server listens on for connections.
+pub fn set_listeners(listeners: usize) {
+    LISTENERS.store(listeners, Ordering::SeqCst);
+}
+
+/// Sets whether the server has loaded its state and handles messages.
+pub fn set_ready(ready: bool) {
+    READY.store(ready, Ordering::SeqCst);
+}
+
+/// Returns whether the server is ready, that is it has loaded its state, handles
+/// messages and listens for connections.
+pub fn is_ready() -> bool {
+    READY.load(Ordering::SeqCst) && LISTENERS.load(Ordering::SeqCst) > 0
+}
+
+/// Accepts connections to the health endpoint. This is a blocking function to be
+/// run in a separate thread.
+///
+/// # Parameters
+///
+///  -  `listener`: the listener to listen on
+///  -  `keepalive`: time an accepted connection may be idle before TCP
+///     keepalive probes are sent, or [`Option::None`] to disable them
+pub fn listen(listener: &TcpListener, keepalive: Option<Duration>) {
+    let limit = ConnectionLimit::new(http::MAX_CONNECTIONS);
+    for stream in listener.incoming() {
+        match stream {
+            Result::Ok(stream) => match limit.acquire() {
+                Option::Some(permit) => {
+                    let _ = thread::Builder::new()
+                        .name("health request".to_string())
+                        .spawn(move || {
+                            handle_conn(stream, keepalive);
+                            drop(permit);
+                        });
+                },
+                Option::None => debug!("Too many connections to the health endpoint, closing a new one"),
+            },
+            Result::Err(err) => debug!("A client could not connect to the health endpoint: {}", err),
+        }
+    }
+}
+
+#[doc(hidden)]
+fn handle_conn(stream: TcpStream, keepalive: Option<Duration>) {
+    if let Result::Err(err) = socket::keep_alive(&stream, keepalive) {
+        debug!("Could not enable TCP keepalive for a health request: {}", err);
+    }
+    match handle_request(&stream) {
+        Result::Ok((status, content_type, body)) => http::respond(&stream, status, content_type, body.as_bytes()),
+        Result::Err(err) => http::respond(&stream, (err.status, err.reason), "text/plain", err.reason.as_bytes()),
+    }
+}
+
+/// Reads a single request and returns the status, the media type and the body of
+/// the response.
+#[doc(hidden)]
+fn handle_request(stream: &TcpStream) -> Result<((u16, &'static str), &'static str, String), HttpError> {
+    let head = http::read_head(&mut http::reader(stream))?;
+    if head.method != "GET" {
+        return Result::Err(HttpError::new(405, "Method Not Allowed"));
+    }
+    match head.path.as_str() {
+        "/livez" => Result::Ok(((200, "OK"), "text/plain", "ok".to_string())),
+        "/readyz" => match is_ready() {
+            true => Result::Ok(((200, "OK"), "text/plain", "ready".to_string())),
+            false => Result::Ok(((503, "Service Unavailable"), "text/plain", "not ready".to_string())),
+        },
+        "/stats" => Result::Ok(((200, "OK"), "application/json", stats().to_string())),
+        _ => Result::Err(HttpError::new(404, "Not Found")),
+    }
+}
+
+/// Returns basic statistics of the server as numeric gauges, with boolean values
+/// represented by 0 and 1. These are pushed to metrics collectors.
+pub fn gauges() -> Vec<(&'static str, u64)> {
+    vec![
+        ("uptime_secs", STARTED_AT.elapsed().as_secs()),
+        ("ready", is_ready() as u64),
+        ("listeners", LISTENERS.load(Ordering::SeqCst) as u64),
+        ("connections", client_list::count() as u64),
+        ("online_users", client_list::online_nicknames().len() as u64),
+        ("registered_users", user_list::count() as u64),
+        ("messages", message_list::len() as u64),
+        ("lockdown", lockdown::is_active() as u64),
+        ("history_bytes", memory::used(Store::History) as u64),
+        ("pending_bytes", memory::used(Store::Pending) as u64),
+        ("outbound_bytes", memory::used(Store::Outbound) as u64),
+    ]
+}
+
+/// Returns basic statistics of the server.
+#[doc(hidden)]
+fn stats() -> serde_json::Value {
+    json!({
+        "uptime_secs": STARTED_AT.elapsed().as_secs(),
+        "ready": is_ready(),
+        "listeners": LISTENERS.load(Ordering::SeqCst),
+        "connections": client_list::count(),
+        "online_users": client_list::online_nicknames().len(),
+        "registered_users": user_list::count(),
+        "messages": message_list::len(),
+        "lockdown": lockdown::is_active(),
+        "history_bytes": memory::used(Store::History),
+        "pending_bytes": memory::used(Store::Pending),
+        "outbound_bytes": memory::used(Store::Outbound),
+    })
+}
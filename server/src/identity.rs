@@ -0,0 +1,78 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::global_config;
+
+use mdchat_common::hello;
+use mdchat_common::hello::IDENTITY_KEY_LENGTH;
+use mdchat_common::hello::ServerIdentity;
+
+use once_cell::sync::OnceCell;
+
+use std::convert::TryFrom;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::fs::PermissionsExt;
+
+/// The secret identity key of the server.
+static IDENTITY_KEY: OnceCell<[u8; IDENTITY_KEY_LENGTH]> = OnceCell::new();
+
+/// Loads the secret identity key of the server from the key file given by global
+/// configuration. If the file does not exist, a new key is generated and saved
+/// into it, readable only by the owner.
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] with the fingerprint of the identity key
+///  -  [`Result::Err`] if the key file could not be read or created, is readable
+///     by other users or does not contain a valid key
+pub fn load() -> io::Result<String> {
+    let key_file = global_config().identity().read().unwrap().get_key_file().clone();
+    let identity_key = match fs::metadata(&key_file) {
+        Result::Ok(metadata) => {
+            if metadata.permissions().mode() & 0o077 != 0 {
+                return Result::Err(io::Error::new(io::ErrorKind::PermissionDenied,
+                    format!("{} must not be accessible by other users", key_file.display())));
+            }
+            <[u8; IDENTITY_KEY_LENGTH]>::try_from(fs::read(&key_file)?.as_slice())
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData,
+                    format!("{} does not contain a valid identity key", key_file.display())))?
+        },
+        Result::Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            let identity_key = hello::new_identity_key();
+            OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(&key_file)?
+                .write_all(&identity_key)?;
+            identity_key
+        },
+        Result::Err(err) => return Result::Err(err),
+    };
+    IDENTITY_KEY.set(identity_key).map_err(|_| panic!("Identity key already loaded")).unwrap();
+    Result::Ok(hello::identity_fingerprint(&identity_key))
+}
+
+/// Proves the identity of the server by signing given challenge sent by
+/// a client.
+pub fn sign(challenge: &[u8]) -> ServerIdentity {
+    ServerIdentity::sign(IDENTITY_KEY.get().unwrap(), challenge)
+}
@@ -0,0 +1,89 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module for the keepalive reaper, which pings clients which have been silent
+//! for a while and removes connections of clients which have gone away from the
+//! client list, see `flow keepalive` and `flow idle-timeout` options of the
+//! server configuration.
+
+use crate::client_list;
+use crate::global_config;
+
+use chrono::DateTime;
+use chrono::Utc;
+
+use mdchat_common::command::s2c;
+
+use tracing::debug;
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::thread;
+use std::time::Duration;
+
+/// How often connections are checked.
+const REAP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Function containing a loop for periodic pinging of silent clients and
+/// removing of connections which have failed or whose clients have not answered
+/// pings. This function should be called in a separate thread.
+pub fn reap_periodically() {
+    // Time of the last activity of each client when it was pinged, so a silent
+    // client is pinged only once:
+    let mut pinged = HashMap::new();
+    loop {
+        thread::sleep(REAP_INTERVAL);
+        reap(&mut pinged);
+    }
+}
+
+#[doc(hidden)]
+fn reap(pinged: &mut HashMap<SocketAddr, DateTime<Utc>>) {
+    let (keepalive, idle_timeout) = {
+        let global_config = global_config();
+        let flow_config = global_config.flow_control().read().unwrap();
+        (flow_config.get_keepalive(), flow_config.get_idle_timeout())
+    };
+    let now = Utc::now();
+    let clients = client_list::all();
+    pinged.retain(|socket_addr, _| clients.iter().any(|client| client.socket_addr() == socket_addr));
+    for client in clients {
+        // Connections which have failed are removed even if the thread of the
+        // client is still blocked:
+        if client.is_err() {
+            debug!("Removed failed connection of {}", client.socket_addr());
+            client.teardown();
+            continue
+        }
+        // Only clients which understand pings are expected to answer them:
+        let keepalive = match keepalive {
+            Option::Some(keepalive) if client.protocol_version() >= 2 => keepalive,
+            _ => continue,
+        };
+        let last_activity = client.last_activity();
+        let silent_for = (now - last_activity).to_std().unwrap_or_default();
+        if silent_for > idle_timeout {
+            client.disconnect_idle(idle_timeout);
+            client.teardown();
+        } else if silent_for >= keepalive && pinged.get(client.socket_addr()) != Option::Some(&last_activity) {
+            pinged.insert(*client.socket_addr(), last_activity);
+            if let Result::Err(err) = client.send_command(s2c::Command::Ping) {
+                client.error(err.to_string());
+            }
+        }
+    }
+}
@@ -17,45 +17,313 @@
 
 use crate::client::Client;
 use crate::{client_list, global_config};
-use crate::log;
+use crate::gateway;
+use crate::lockdown;
+use crate::raid;
+use crate::ratelimit;
+use crate::supervisor;
 
-use mdlog::LogLevel;
+use mdchat_serverconf::ListenConfig;
+use mdchat_serverconf::RateLimitBucket;
 
 use mdswp::MdswpListener;
 use mdswp::MdswpStream;
 
+use once_cell::sync::Lazy;
+
+use tracing::info;
+use tracing::warn;
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::io;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
 use std::net::SocketAddr;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
 use std::thread;
 
-/// Method for infinite accepting a connection. This is a blocking method to be run
-/// in a separate thread.
+/// Listeners accepting connections by socket addresses they have been bound to.
+static LISTENERS: Lazy<Mutex<HashMap<SocketAddr, Bound>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// A listener accepting connections in its own thread.
+#[doc(hidden)]
+struct Bound {
+    local_addr: SocketAddr,
+    closing: Arc<AtomicBool>,
+}
+
+/// Binds a listener to given socket address and accepts connections in a new
+/// thread.
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] if the listener has been bound
+///  -  [`Result::Err`] if the listener could not be bound
+pub fn bind(sock_addr: SocketAddr) -> io::Result<()> {
+    let listener = MdswpListener::bind(sock_addr)?;
+    let local_addr = listener.local_addr()?;
+    let closing = Arc::new(AtomicBool::new(false));
+    // Listeners wait for connections for arbitrarily long, so only their end is
+    // watched:
+    let listen_listener = cls_clone!(closing -> move || listen(&listener, sock_addr, &closing));
+    supervisor::spawn_critical(thread_name(&sock_addr), listen_listener, Option::None)?;
+    LISTENERS.lock().unwrap().insert(sock_addr, Bound { local_addr, closing });
+    Result::Ok(())
+}
+
+/// Binds a listener to given socket address like [`bind`], but retries with
+/// exponential backoff as given by global configuration if the address is not
+/// available or is in use, since it may become available soon, for example during
+/// boot.
+pub fn bind_retrying(sock_addr: SocketAddr) -> io::Result<()> {
+    let (retries, mut delay) = {
+        let global_config = global_config();
+        let listen_config = global_config.listen().read().unwrap();
+        (listen_config.get_retries(), listen_config.get_retry_delay())
+    };
+    let mut retry = 0;
+    loop {
+        match bind(sock_addr) {
+            Result::Err(err) if is_dual_stack_covered(sock_addr, &err) => {
+                info!("{} is covered by the IPv6 listener on the same port", sock_addr);
+                return Result::Ok(())
+            },
+            Result::Err(err) if retry < retries && is_transient(&err) => {
+                warn!("Could not bind to {}: {}, retrying in {:?}", sock_addr, err, delay);
+                thread::sleep(delay);
+                delay = delay.saturating_mul(2);
+                retry += 1;
+            },
+            result => return result,
+        }
+    }
+}
+
+/// Stops accepting connections at given socket address. Connections accepted
+/// before are not affected by closing the listener itself, see [`apply`].
+/// Returns `false` if there is no listener bound to given address.
+pub fn close(sock_addr: &SocketAddr) -> bool {
+    let bound = match LISTENERS.lock().unwrap().remove(sock_addr) {
+        Option::Some(bound) => bound,
+        Option::None => return false,
+    };
+    supervisor::unwatch(&thread_name(sock_addr));
+    bound.closing.store(true, Ordering::SeqCst);
+    // Wake the listener waiting for a connection up, so it sees it is closing:
+    let mut wake_addr = bound.local_addr;
+    if wake_addr.ip().is_unspecified() {
+        wake_addr.set_ip(match wake_addr {
+            SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        });
+    }
+    let _ = MdswpStream::connect(wake_addr);
+    true
+}
+
+/// Makes the server listen exactly on given socket addresses: binds listeners to
+/// addresses which are not bound yet and closes listeners bound to addresses
+/// which are not given, see [`close`]. Clients accepted on closed listeners or on
+/// listeners whose `admin-only` setting has changed are disconnected, so they
+/// are not served by rules which do not apply anymore, see [`is_stale`].
+///
+/// # Return value
+///
+/// Addresses which could not be bound even after retries (see [`bind_retrying`])
+/// together with the errors. Other addresses are bound even if some of them fail.
+pub fn apply(sock_addrs: &HashSet<SocketAddr>) -> Vec<(SocketAddr, io::Error)> {
+    let bound: HashSet<SocketAddr> = LISTENERS.lock().unwrap().keys().copied().collect();
+    for sock_addr in bound.difference(sock_addrs) {
+        close(sock_addr);
+        info!("Stopped listening at {}", sock_addr);
+    }
+    // IPv6 addresses are bound first, so a dual-stack IPv6 listener covers the
+    // IPv4 wildcard address on the same port:
+    let mut added: Vec<SocketAddr> = sock_addrs.difference(&bound).copied().collect();
+    added.sort_by_key(|sock_addr| sock_addr.is_ipv4());
+    let errors = added.into_iter()
+        .filter_map(|sock_addr| match bind_retrying(sock_addr) {
+            Result::Ok(()) => {
+                info!("Listening at {}", sock_addr);
+                Option::None
+            },
+            Result::Err(err) => Option::Some((sock_addr, err)),
+        })
+        .collect();
+    disconnect_stale();
+    errors
+}
+
+/// Disconnects clients accepted on listeners which are not bound anymore or
+/// whose `admin-only` setting has changed, see [`is_stale`].
+#[doc(hidden)]
+fn disconnect_stale() {
+    let bound: HashSet<SocketAddr> = LISTENERS.lock().unwrap().keys().copied().collect();
+    let stale: Vec<Arc<Client>> = {
+        let global_config = global_config();
+        let listen_config = global_config.listen().read().unwrap();
+        client_list::all().into_iter()
+            .filter(|client| is_stale(client.listener(), client.is_admin_only(), &bound, &listen_config))
+            .collect()
+    };
+    for client in stale {
+        info!("Disconnected {}, since the listener at {} has been closed or changed", client.remote_addr(), client.listener());
+        client.error(tr!(client.locale(), "server.listener-changed"));
+    }
+}
+
+/// Returns whether a client accepted on given listener is served by rules which
+/// do not apply anymore, that is the listener is not bound anymore or its
+/// `admin-only` setting differs from the one the client has been accepted with.
+///
+/// # Parameters
+///
+///  -  `listener`: socket address of the listener the client has been accepted on
+///  -  `admin_only`: whether the listener was admin-only when it accepted the
+///     client
+///  -  `bound`: socket addresses of listeners which are bound
+///  -  `listen_config`: the current configuration of listeners
+#[doc(hidden)]
+fn is_stale(listener: &SocketAddr, admin_only: bool, bound: &HashSet<SocketAddr>, listen_config: &ListenConfig) -> bool {
+    !bound.contains(listener) || listen_config.is_admin_only(listener) != admin_only
+}
+
+/// Returns the number of sockets the server listens on.
+pub fn count() -> usize {
+    LISTENERS.lock().unwrap().len()
+}
+
+/// Returns the name of the thread of the listener bound to given socket address.
+#[doc(hidden)]
+fn thread_name(sock_addr: &SocketAddr) -> String {
+    format!("listener {}", sock_addr)
+}
+
+/// Returns whether given error of binding a socket may pass on its own.
+#[doc(hidden)]
+fn is_transient(err: &io::Error) -> bool {
+    matches!(err.kind(), io::ErrorKind::AddrNotAvailable | io::ErrorKind::AddrInUse)
+}
+
+/// Returns whether binding given IPv4 wildcard address has failed only because
+/// the IPv6 wildcard address on the same port is bound, which accepts IPv4
+/// connections too on systems with dual-stack sockets.
+#[doc(hidden)]
+fn is_dual_stack_covered(sock_addr: SocketAddr, err: &io::Error) -> bool {
+    let ipv6_addr = SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), sock_addr.port());
+    err.kind() == io::ErrorKind::AddrInUse
+        && sock_addr.is_ipv4()
+        && sock_addr.ip().is_unspecified()
+        && sock_addr.port() != 0
+        && LISTENERS.lock().unwrap().contains_key(&ipv6_addr)
+}
+
+/// Method for infinite accepting a connection until the listener is closed. This
+/// is a blocking method to be run in a separate thread.
 ///
 /// # Parameters:
 ///
 ///  -  `listener`: the listener to listen on
-pub fn listen(listener: MdswpListener) {
+///  -  `sock_addr`: the socket address the listener has been bound to
+///  -  `closing`: whether the listener has been closed
+#[doc(hidden)]
+fn listen(listener: &MdswpListener, sock_addr: SocketAddr, closing: &AtomicBool) {
     for client in listener.incoming() {
+        if closing.load(Ordering::SeqCst) {
+            break
+        }
         match client {
-            Result::Ok((stream, peer_addr)) => __handle_conn(stream, peer_addr),
-            Result::Err(err) => log(LogLevel::Warning,
-                &format!("A client could not connect to the server: {}", err))
+            Result::Ok((stream, peer_addr)) => {
+                let admin_only = global_config().listen().read().unwrap().is_admin_only(&sock_addr);
+                __handle_conn(stream, peer_addr, sock_addr, admin_only)
+            },
+            Result::Err(err) => warn!("A client could not connect to the server: {}", err)
         }
     }
 }
 
 #[doc(hidden)]
-fn __handle_conn(mut stream: MdswpStream, peer_addr: SocketAddr) {
-    // Kick all banned IPs
-    if !global_config().is_allowed_ip_addr(&peer_addr.ip()) {
+fn __handle_conn(mut stream: MdswpStream, peer_addr: SocketAddr, listener: SocketAddr, admin_only: bool) {
+    // Connections bridged by the gateway are admitted once the gateway tells the
+    // address of the browser:
+    let from_gateway = gateway::is_upstream_peer(peer_addr.ip());
+    if !from_gateway && !admit(peer_addr) {
         let _ = stream.reset();
         return;
     }
-    let client = Client::new(stream);
-    // Run a thread for the client
-    thread::Builder::new()
-        .name(format!("client {}", peer_addr))
-        .spawn(cls_clone!(client -> move || client.client_thread()))
-        .unwrap();
-    // Add new client stream to the clients:
-    client_list::add_connection(client);
-}
\ No newline at end of file
+    let client = Client::new(stream, listener, admin_only, from_gateway);
+    // Reject new connections during the emergency lockdown, except those on
+    // admin-only listeners, since only administrators and moderators can log in
+    // there:
+    if lockdown::is_active() && !admin_only {
+        client.error(tr!(client.locale(), "server.lockdown"));
+        info!("Rejected connection from {} due to lockdown", peer_addr);
+        return;
+    }
+    // Add new client stream to the clients before its thread runs, so the client
+    // is listed when its connection is torn down, even if it fails at once:
+    client_list::add_connection(Arc::clone(&client));
+    // Run a thread for the client. A connection whose thread has panicked cannot
+    // continue, so it is closed:
+    supervisor::spawn_once(
+        format!("client {}", peer_addr),
+        cls_clone!(client -> move || client.client_thread()),
+        cls_clone!(client -> move || client.teardown()),
+    ).unwrap();
+}
+
+/// Returns whether a connection from given socket address is admitted, that is
+/// the IP address is not banned and it does not connect too often. Admitted
+/// connections are recorded for raid detection.
+pub fn admit(peer_addr: SocketAddr) -> bool {
+    if !global_config().is_allowed_ip_addr(&peer_addr.ip()) {
+        return false;
+    }
+    if ratelimit::take_ip(RateLimitBucket::Connections, peer_addr.ip()).is_err() {
+        info!("Rejected connection from {}, because it connects too often", peer_addr);
+        return false;
+    }
+    raid::record_connection(peer_addr.ip());
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_stale;
+
+    use mdchat_serverconf::ListenConfig;
+
+    use std::collections::HashSet;
+    use std::net::SocketAddr;
+
+    #[test]
+    fn clients_of_closed_listeners_are_stale() {
+        let public: SocketAddr = "127.0.0.1:4000".parse().unwrap();
+        let removed: SocketAddr = "127.0.0.1:4001".parse().unwrap();
+        let bound = HashSet::from([public]);
+        let listen_config = ListenConfig::new();
+        assert!(!is_stale(&public, false, &bound, &listen_config));
+        assert!(is_stale(&removed, false, &bound, &listen_config));
+    }
+
+    #[test]
+    fn clients_of_listeners_with_changed_admin_only_setting_are_stale() {
+        let admin: SocketAddr = "127.0.0.1:4100".parse().unwrap();
+        let public: SocketAddr = "127.0.0.1:4101".parse().unwrap();
+        let bound = HashSet::from([admin, public]);
+        let mut listen_config = ListenConfig::new();
+        listen_config.set_admin_only(HashSet::from([admin]));
+        assert!(!is_stale(&admin, true, &bound, &listen_config));
+        assert!(!is_stale(&public, false, &bound, &listen_config));
+        // The listener has become admin-only, so users who are not
+        // administrators could log in through it otherwise:
+        assert!(is_stale(&admin, false, &bound, &listen_config));
+        // The listener is not admin-only anymore:
+        assert!(is_stale(&public, true, &bound, &listen_config));
+    }
+}
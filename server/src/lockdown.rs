@@ -0,0 +1,49 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::state;
+
+use std::io;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+
+/// Whether the emergency lockdown is active.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Restores the state of the emergency lockdown loaded from the state file, so
+/// restarting the server does not lift the lockdown.
+pub fn restore(active: bool) {
+    ACTIVE.store(active, Ordering::Relaxed);
+}
+
+/// Returns whether the emergency lockdown is active.
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Turns the emergency lockdown on or off and saves its state into the state
+/// file given by global configuration.
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] if the state was changed and saved
+///  -  [`Result::Err`] if the state file could not be written; the state is not
+///     changed in this case
+pub fn set_active(active: bool) -> io::Result<()> {
+    let previous = ACTIVE.swap(active, Ordering::Relaxed);
+    state::save().inspect_err(|_| ACTIVE.store(previous, Ordering::Relaxed))
+}
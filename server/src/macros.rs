@@ -23,4 +23,16 @@ macro_rules! cls_clone {
             $cls
         }
     }
-}
\ No newline at end of file
+}
+/// Returns the text of a message from the message catalog in given locale, see
+/// [`catalog::text`](crate::catalog::text). Placeholders are given as
+/// `name = value` pairs, where values implement [`Display`](std::fmt::Display).
+///
+/// ```
+/// let text = tr!(client.locale(), "channel.not-found", channel = name);
+/// ```
+macro_rules! tr {
+    ($locale:expr, $key:expr $(, $name:ident = $value:expr)* $(,)?) => {
+        crate::catalog::text(&$locale, $key, &[$((stringify!($name), &$value as &dyn std::fmt::Display)),*])
+    }
+}
@@ -0,0 +1,70 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use chrono::Utc;
+
+use crate::global_config;
+
+use mdchat_common::message::DirectMessage;
+
+use once_cell::sync::Lazy;
+
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
+use std::sync::RwLock;
+
+/// Direct messages addressed to users who are offline, by nicknames of the
+/// recipients. Messages are ordered from the oldest to the newest one.
+static MAILBOXES: Lazy<RwLock<BTreeMap<String, VecDeque<DirectMessage>>>> = Lazy::new(|| RwLock::new(BTreeMap::new()));
+
+/// Stores a direct message until its recipient logs in. Messages older than
+/// allowed by global configuration are discarded first.
+///
+/// # Return value
+///
+/// Whether the message has been stored. It is not stored if the recipient has
+/// already as many stored messages as allowed by global configuration.
+pub fn store(message: DirectMessage) -> bool {
+    let max_stored = global_config().direct_messages().read().unwrap().get_max_stored().get() as usize;
+    let mut mailboxes = MAILBOXES.write().unwrap();
+    let mailbox = mailboxes.entry(message.recipient().clone()).or_default();
+    remove_expired(mailbox);
+    if mailbox.len() >= max_stored {
+        return false
+    }
+    mailbox.push_back(message);
+    true
+}
+
+/// Removes all direct messages stored for given user and returns those which
+/// have not expired yet, from the oldest to the newest one.
+pub fn take(nickname: &str) -> Vec<DirectMessage> {
+    let mut mailbox = MAILBOXES.write().unwrap().remove(nickname).unwrap_or_default();
+    remove_expired(&mut mailbox);
+    mailbox.into()
+}
+
+#[doc(hidden)]
+fn remove_expired(mailbox: &mut VecDeque<DirectMessage>) {
+    let offline_ttl = global_config().direct_messages().read().unwrap().get_offline_ttl();
+    let cutoff = offline_ttl
+        .and_then(|ttl| chrono::Duration::from_std(ttl).ok())
+        .and_then(|ttl| Utc::now().checked_sub_signed(ttl));
+    if let Option::Some(cutoff) = cutoff {
+        mailbox.retain(|message| *message.date_time() >= cutoff);
+    }
+}
@@ -18,49 +18,179 @@
 #[macro_use]
 mod macros;
 
+mod archive;
+mod auth;
+mod catalog;
+mod channel;
+mod channel_list;
 mod client;
 mod client_list;
+mod connection_state;
+mod email;
+mod expiry;
+mod filter_stats;
+mod health;
+mod gateway;
+mod http;
+mod identity;
+mod keepalive;
 mod listener;
+mod lockdown;
+mod mailbox;
+mod memory;
 mod message_list;
 mod message_queue;
+mod metrics;
+mod moderation;
+mod outbound;
+mod pepper;
+mod permission;
+mod presence;
+mod preview;
+mod process;
+mod quota;
+mod raid;
+mod ratelimit;
+mod retention;
+mod review_queue;
+mod rules;
+mod socket;
+mod state;
+mod supervisor;
+mod telemetry;
 mod user;
 mod user_list;
+mod webhook;
+mod welcome;
 
 use mdchat_serverconf::Config;
+use mdchat_serverconf::ConfigParseResult;
+use mdchat_serverconf::ENV_CONFIG_FILE;
 
-use mdlog::Logger;
-use mdlog::LogLevel;
+use once_cell::sync::OnceCell;
 
-use mdswp::MdswpListener;
+#[cfg(unix)]
+use signal_hook::consts::SIGHUP;
+#[cfg(unix)]
+use signal_hook::iterator::Signals;
 
-use once_cell::sync::OnceCell;
+use tracing::error;
+use tracing::info;
+use tracing::warn;
 
+use std::env;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+use std::net::SocketAddr;
+use std::path::Path;
 use std::process::exit;
 use std::sync::Arc;
-use std::thread;
+use std::time::Duration;
 
 static GLOBAL_CONFIG: OnceCell<Arc<Config>> = OnceCell::new();
 
+/// Path to the configuration file used if `MDCHAT_CONFIG` environment variable is
+/// not set.
+const DEFAULT_CONFIG_FILE: &str = "/etc/mdchat-server.conf";
+
+/// Socket address the server listens on if it is configured only using
+/// environment variables and no `MDCHAT_LISTEN` variable is set.
+const DEFAULT_ENV_LISTEN: &str = "0.0.0.0:4000";
+
+/// How long the message handler may not report its heartbeat before it is
+/// considered hung and the server quits.
+const MESSAGE_HANDLER_MAX_SILENCE: Duration = Duration::from_secs(60);
+
 /// Returns reference with interior mutability to the global configuration, that is
 /// an [`Arc`] pointing to a server-global [`Config`] instance.
 fn global_config() -> Arc<Config> {
     GLOBAL_CONFIG.get().unwrap().clone()
 }
 
-/// Loads global configuration file. If there is an error, the program ends with
-/// exit code 1.
+/// Loads global configuration file, see [`read_config`]. If there is an error, the
+/// program ends with exit code 1.
 fn load_global_config() {
-    let config = Config::default();
-    match config.process_file("/etc/mdchat-server.conf", false) {
+    match read_config() {
         Result::Err(err) => {
-            eprintln!("Could not load configuration file:\n{:?}", err);
+            eprintln!("Could not load configuration file:\n{}", err);
             exit(1);
         },
-        Result::Ok(()) => GLOBAL_CONFIG.set(Arc::new(config))
+        Result::Ok(config) => GLOBAL_CONFIG.set(Arc::new(config))
             .map_err(|_| panic!("Value already set")).unwrap(),
     }
 }
 
+/// Reads the configuration again and applies changes of `listen` options: binds
+/// listeners to new socket addresses, closes listeners bound to removed ones and
+/// updates which of them are admin-only.
+/// Changes of other options are applied on restart. If there is an error, the
+/// current configuration is kept.
+fn reload_global_config() {
+    let config = match read_config() {
+        Result::Ok(config) => config,
+        Result::Err(err) => {
+            error!("Could not reload configuration file:\n{}", err);
+            return
+        }
+    };
+    for warning in config.lint() {
+        warn!("Configuration: {}", warning);
+    }
+    let listen_sock_addrs = config.listen_sock_addrs().read().unwrap().clone();
+    let admin_only = config.listen().read().unwrap().get_admin_only().clone();
+    *global_config().listen_sock_addrs().write().unwrap() = listen_sock_addrs.clone();
+    global_config().listen().write().unwrap().set_admin_only(admin_only);
+    for (sock_addr, err) in listener::apply(&listen_sock_addrs) {
+        error!("Could not bind to {}: {}", sock_addr, err);
+    }
+    health::set_listeners(listener::count());
+    if listener::count() == 0 {
+        warn!("There is no socket to listen for incoming connections");
+    }
+    info!("Configuration file reloaded");
+}
+
+/// Reloads the configuration whenever the server receives `SIGHUP` signal. This
+/// function should be called in a separate thread.
+#[cfg(unix)]
+fn reload_on_hangup() {
+    let mut signals = Signals::new([SIGHUP]).unwrap();
+    for _ in signals.forever() {
+        reload_global_config();
+    }
+}
+
+/// Reads the configuration file. Its path is given by `MDCHAT_CONFIG` environment
+/// variable, `/etc/mdchat-server.conf` is used by default. If the default file
+/// does not exist, the server is configured using `MDCHAT_*` environment
+/// variables instead, which is useful in containers. Then the rules file is
+/// processed, if it exists.
+fn read_config() -> ConfigParseResult<Config> {
+    let config = Config::default();
+    let (file_path, explicit) = match env::var(ENV_CONFIG_FILE) {
+        Result::Ok(file_path) => (file_path, true),
+        Result::Err(_) => (DEFAULT_CONFIG_FILE.to_string(), false),
+    };
+    let result = match explicit || Path::new(&file_path).exists() {
+        true => config.process_file(file_path, false),
+        false => config.process_env(env::vars()).map(|()| {
+            // Containers are reached from outside, so listen on all interfaces
+            // unless configured otherwise:
+            if config.listen_sock_addrs().read().unwrap().is_empty() {
+                config.listen_sock_addrs().write().unwrap().insert(DEFAULT_ENV_LISTEN.parse().unwrap());
+            }
+        }),
+    };
+    result?;
+    // Filter rules added at runtime are kept in a separate file:
+    let rules_file = config.state().read().unwrap().get_rules_file().clone();
+    if rules_file.exists() {
+        config.process_file(rules_file.display().to_string(), false)?;
+    }
+    Result::Ok(config)
+}
+
 /// Encrypts data as given by global configuration.
 ///
 /// # Parameters
@@ -87,45 +217,192 @@ fn decrypt(data: &[u8]) -> Vec<u8> {
     data.to_vec()
 }
 
-/// Logs a message using logger configured by global configuration.
-fn log(log_level: LogLevel, message: &str) {
-    global_config().logger().write().unwrap().log(log_level, message).unwrap();
-}
-
 fn main() {
+    health::mark_started();
+
     // Load config
     load_global_config();
-    log(LogLevel::Info, "Configuration file loaded successfully");
+    telemetry::init();
+    supervisor::install_panic_hook();
+    info!("Configuration file loaded successfully");
+    for warning in global_config().lint() {
+        warn!("Configuration: {}", warning);
+    }
 
-    // Initialize listeners for incoming connections:
-    let global_config = global_config();
-    let listen_sock_addrs = global_config.listen_sock_addrs().read().unwrap();
-    let mut listener_threads = Vec::with_capacity(listen_sock_addrs.len());
-    for sock_addr in &*listen_sock_addrs {
-        match MdswpListener::bind(sock_addr) {
-            Result::Err(err) => {
-                let message = format!("Could not bind to {}: {}", sock_addr, err);
-                log(LogLevel::Error, &message);
-            },
+    // Endpoint for liveness and readiness probes, started first so the server is
+    // reported as alive while it loads its state:
+    let (health_listen, health_socket) = {
+        let global_config = global_config();
+        let health_config = global_config.health().read().unwrap();
+        (health_config.get_listen(), health_config.get_socket().clone())
+    };
+    if let Option::Some(sock_addr) = health_listen {
+        match socket::bind(sock_addr, &health_socket) {
+            Result::Err(err) => error!("Could not bind health endpoint to {}: {}", sock_addr, err),
             Result::Ok(listener) => {
-                let thread = thread::spawn(|| listener::listen(listener));
-                let message =  format!("Listening at {}", sock_addr);
-                log(LogLevel::Info, &message);
-                listener_threads.push(thread);
+                supervisor::spawn("health".to_string(), move || health::listen(&listener, health_socket.get_keepalive())).unwrap();
+                info!("Health endpoint listening at {}", sock_addr);
             }
         }
     }
 
+    // Load the identity key clients use to recognize the server:
+    match identity::load() {
+        Result::Ok(fingerprint) => info!("Server identity fingerprint is {}", fingerprint),
+        Result::Err(err) => {
+            error!("Could not load the identity key: {}", err);
+            exit(1);
+        }
+    }
+
+    // Select how users are authenticated:
+    match auth::load() {
+        Result::Ok(name) => info!("Users are authenticated by {} provider", name),
+        Result::Err(err) => {
+            error!("Could not set up authentication: {}", err);
+            exit(1);
+        }
+    }
+
+    // Load the pepper for password hashing:
+    match pepper::load() {
+        Result::Ok(true) => info!("Pepper for password hashing loaded"),
+        Result::Ok(false) => warn!("No pepper for password hashing is configured"),
+        Result::Err(err) => {
+            error!("Could not load the pepper: {}", err);
+            exit(1);
+        }
+    }
+
+    // Load catalogs of messages sent to clients in other languages:
+    match catalog::load() {
+        Result::Ok(count) => info!("Loaded {} message catalog(s), default locale is {}", count, catalog::default_locale()),
+        Result::Err(err) => {
+            error!("Could not load message catalogs: {}", err);
+            exit(1);
+        }
+    }
+
+    // Create channels given by configuration:
+    {
+        let global_config = global_config();
+        let channels_config = global_config.channels().read().unwrap();
+        for name in channels_config.get_channels() {
+            channel_list::add_channel(name.clone(), channels_config.get_mode(name));
+        }
+    }
+    if let Result::Err(err) = channel_list::load_channels() {
+        error!("Could not load channels created by users: {}", err);
+        exit(1);
+    }
+    if let Result::Err(err) = channel_list::load_members() {
+        error!("Could not load members of channels: {}", err);
+        exit(1);
+    }
+
+    // Restore state acquired before the restart, such as the emergency lockdown
+    // and topics of channels:
+    if let Result::Err(err) = state::load() {
+        error!("Could not load the state file: {}", err);
+        exit(1);
+    }
+    if lockdown::is_active() {
+        warn!("Emergency lockdown is active");
+    }
+
+    // Initialize listeners for incoming connections:
+    let global_config = global_config();
+    let listen_sock_addrs = global_config.listen_sock_addrs().read().unwrap().clone();
+    let bind_errors = listener::apply(&listen_sock_addrs);
+    for (sock_addr, err) in &bind_errors {
+        error!("Could not bind to {}: {}", sock_addr, err);
+    }
+    if !bind_errors.is_empty() && global_config.listen().read().unwrap().get_require_all() {
+        error!("Some sockets could not be bound and all of them are required. Quitting.");
+        exit(2);
+    }
+
+    health::set_listeners(listener::count());
+
     // No listener means server cannot run.
-    if listener_threads.is_empty() {
-        log(LogLevel::Fatal, "There is no socket to listen for incoming connections. Quitting.");
+    if listener::count() == 0 {
+        error!("There is no socket to listen for incoming connections. Quitting.");
         exit(2);
     }
 
-    // Message handler:
-    let message_handler = thread::Builder::new()
-        .name("message handler".to_string())
-        .spawn(message_queue::handle_incoming)
-        .unwrap();
-    message_handler.join().unwrap().unwrap();
+    // Gateway for browser clients:
+    let (gateway_listen, gateway_upstream, gateway_socket) = {
+        let gateway_config = global_config.gateway().read().unwrap();
+        (gateway_config.get_listen(), gateway_config.get_upstream(), gateway_config.get_socket().clone())
+    };
+    if let Option::Some(sock_addr) = gateway_listen {
+        // Without an explicit upstream, bridge to one of the listening sockets.
+        // Admin-only sockets are skipped, since the gateway serves public
+        // browser clients:
+        let listen_config = global_config.listen().read().unwrap();
+        let upstream = gateway_upstream.or_else(|| listen_sock_addrs.iter()
+            .filter(|listen| !listen_config.is_admin_only(listen))
+            .min()
+            .map(|listen| {
+                let mut upstream = *listen;
+                if upstream.ip().is_unspecified() {
+                    upstream.set_ip(match upstream {
+                        SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+                        SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+                    });
+                }
+                upstream
+            }));
+        drop(listen_config);
+        match upstream.map(|upstream| (upstream, socket::bind(sock_addr, &gateway_socket))) {
+            Option::None => error!("WebSocket gateway has no upstream, since all listening sockets are admin-only"),
+            Option::Some((_, Result::Err(err))) =>
+                error!("Could not bind WebSocket gateway to {}: {}", sock_addr, err),
+            Option::Some((upstream, Result::Ok(listener))) => {
+                supervisor::spawn("gateway".to_string(), move || gateway::listen(&listener, upstream, gateway_socket.get_keepalive())).unwrap();
+                info!("WebSocket gateway listening at {}, bridging to {}", sock_addr, upstream);
+            }
+        }
+    }
+
+    // Endpoint for integrations posting messages:
+    let (webhook_listen, webhook_socket) = {
+        let webhook_config = global_config.webhooks().read().unwrap();
+        (webhook_config.get_listen(), webhook_config.get_socket().clone())
+    };
+    if let Option::Some(sock_addr) = webhook_listen {
+        match socket::bind(sock_addr, &webhook_socket) {
+            Result::Err(err) => error!("Could not bind webhook endpoint to {}: {}", sock_addr, err),
+            Result::Ok(listener) => {
+                supervisor::spawn("webhook".to_string(), move || webhook::listen(&listener, webhook_socket.get_keepalive())).unwrap();
+                info!("Webhook endpoint listening at {}", sock_addr);
+            }
+        }
+    }
+
+    // Reloading of the configuration:
+    #[cfg(unix)]
+    supervisor::spawn("reload".to_string(), reload_on_hangup).unwrap();
+
+    // Removal of expired messages:
+    supervisor::spawn("expiry".to_string(), expiry::remove_expired_periodically).unwrap();
+
+    // Removal of old messages:
+    supervisor::spawn("retention".to_string(), retention::prune_periodically).unwrap();
+
+    // Pushing of metrics to collectors:
+    supervisor::spawn("metrics".to_string(), metrics::push_periodically).unwrap();
+
+    // Pinging of silent clients and removing of dead connections:
+    supervisor::spawn("keepalive".to_string(), keepalive::reap_periodically).unwrap();
+
+    // Broadcasting of join and leave notices:
+    supervisor::spawn("presence".to_string(), presence::flush_periodically).unwrap();
+
+    // Message handler, which is watched like listeners, since no message is
+    // delivered without it:
+    supervisor::spawn_critical("message handler".to_string(), message_queue::handle_incoming,
+        Option::Some(MESSAGE_HANDLER_MAX_SILENCE)).unwrap();
+    health::set_ready(true);
+    supervisor::watch();
 }
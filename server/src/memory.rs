@@ -0,0 +1,104 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! A module for accounting of approximate memory used by in-memory stores of the
+//! server, which are capped as given by global configuration.
+
+use crate::global_config;
+
+use mdchat_common::message::LinkPreview;
+use mdchat_common::message::Message;
+
+use std::mem;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+static HISTORY_BYTES: AtomicUsize = AtomicUsize::new(0);
+static PENDING_BYTES: AtomicUsize = AtomicUsize::new(0);
+static OUTBOUND_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// In-memory stores whose memory is accounted.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Store {
+    /// The message history
+    History,
+    /// Messages waiting to be broadcast
+    Pending,
+    /// Commands waiting to be written to all connections
+    Outbound,
+}
+
+impl Store {
+    #[doc(hidden)]
+    fn counter(self) -> &'static AtomicUsize {
+        match self {
+            Self::History => &HISTORY_BYTES,
+            Self::Pending => &PENDING_BYTES,
+            Self::Outbound => &OUTBOUND_BYTES,
+        }
+    }
+
+    #[doc(hidden)]
+    fn cap(self) -> Option<usize> {
+        let global_config = global_config();
+        let memory_config = global_config.memory().read().unwrap();
+        match self {
+            Self::History => memory_config.get_history_cap(),
+            Self::Pending => memory_config.get_pending_cap(),
+            Self::Outbound => memory_config.get_outbound_cap(),
+        }
+    }
+}
+
+/// Returns approximate number of bytes given message takes in memory.
+pub fn message_size(message: &Message) -> usize {
+    mem::size_of::<Message>()
+        + message.sender().len()
+        + message.channel().map_or(0, String::len)
+        + message.text().len()
+        + message.previews().iter()
+            .map(|preview| mem::size_of::<LinkPreview>()
+                + preview.url().len()
+                + preview.title().map_or(0, String::len)
+                + preview.description().map_or(0, String::len))
+            .sum::<usize>()
+}
+
+/// Accounts given number of bytes as used by given store.
+pub fn add(store: Store, bytes: usize) {
+    store.counter().fetch_add(bytes, Ordering::SeqCst);
+}
+
+/// Accounts given number of bytes as freed by given store.
+pub fn sub(store: Store, bytes: usize) {
+    store.counter().fetch_sub(bytes, Ordering::SeqCst);
+}
+
+/// Returns approximate number of bytes used by given store.
+pub fn used(store: Store) -> usize {
+    store.counter().load(Ordering::SeqCst)
+}
+
+/// Returns whether given store would stay within its cap given by global
+/// configuration, if given number of bytes was added to it.
+pub fn fits(store: Store, bytes: usize) -> bool {
+    store.cap().is_none_or(|cap| used(store) + bytes <= cap)
+}
+
+/// Returns whether given store is over its cap given by global configuration.
+pub fn is_over_cap(store: Store) -> bool {
+    !fits(store, 0)
+}
@@ -16,13 +16,33 @@
  */
 
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::sync::RwLock;
 use once_cell::sync::Lazy;
 
+use crate::memory;
+use crate::memory::Store;
+
+use chrono::DateTime;
+use chrono::Utc;
+
+use mdchat_common::message::HistoryEntry;
 use mdchat_common::message::Message;
+use mdchat_common::message::Reaction;
+
+/// Reactions to a single message. For each emoji, nicknames of the users who have
+/// reacted using it are stored.
+type MessageReactions = BTreeMap<String, BTreeSet<String>>;
 
 static MESSAGE_LIST: Lazy<RwLock<BTreeMap<u64, Message>>> = Lazy::new(|| RwLock::new(BTreeMap::new()));
 static LAST_ID: Lazy<RwLock<u64>> = Lazy::new(|| RwLock::new(0));
+/// Reactions to messages in the message history keyed by message ID. Reactions
+/// are forgotten together with the message they belong to.
+static REACTIONS: Lazy<RwLock<BTreeMap<u64, MessageReactions>>> = Lazy::new(|| RwLock::new(BTreeMap::new()));
+
+/// Maximum number of different emoji which can be used to react to a single
+/// message.
+pub const MAX_REACTION_KINDS: usize = 20;
 
 fn incr_and_get_id() -> u64 {
     let mut last_id = LAST_ID.write().unwrap();
@@ -31,20 +51,380 @@ fn incr_and_get_id() -> u64 {
     return next_id;
 }
 
+/// Writes given message into the message history, assigns it a new ID and
+/// returns the ID. If the message history gets over its memory cap given by
+/// global configuration, the oldest messages are removed, except the new one.
 pub fn push(message: Message) -> u64 {
     let message_id = incr_and_get_id();
     let mut message_list = MESSAGE_LIST.write().unwrap();
-    message_list.insert(message_id, message);
+    memory::add(Store::History, memory::message_size(&message));
+    message_list.insert(message_id, message.with_id(message_id));
+    let mut pruned = false;
+    while memory::is_over_cap(Store::History) && message_list.len() > 1 {
+        let (_, oldest) = message_list.pop_first().unwrap();
+        memory::sub(Store::History, memory::message_size(&oldest));
+        pruned = true;
+    }
+    if pruned {
+        forget_reactions(&message_list);
+    }
     return message_id;
 }
 
+/// Returns the message with given ID, if it is in the message history and has
+/// not been deleted.
+pub fn get(message_id: u64) -> Option<Message> {
+    MESSAGE_LIST.read().unwrap().get(&message_id)
+        .filter(|message| !message.is_deleted())
+        .cloned()
+}
+
+/// Deletes the message with given ID, if `may_delete` returns `true` for it. The
+/// message is replaced with its tombstone, so the message is not delivered
+/// anymore, but its ID is still known to be a part of the message history.
+///
+/// # Parameters
+///
+///  -  `message_id`: ID of the message to delete
+///  -  `may_delete`: decides whether the message may be deleted
+///
+/// # Return value
+///
+/// [`Option::None`] if there is no such message in the message history or it
+/// has already been deleted, otherwise whether the message has been deleted.
+pub fn delete<F>(message_id: u64, may_delete: F) -> Option<bool>
+where
+    F: FnOnce(&Message) -> bool
+{
+    let mut message_list = MESSAGE_LIST.write().unwrap();
+    let message = message_list.get_mut(&message_id)
+        .filter(|message| !message.is_deleted())?;
+    if !may_delete(message) {
+        return Option::Some(false)
+    }
+    memory::sub(Store::History, memory::message_size(message));
+    *message = message.clone().into_tombstone();
+    memory::add(Store::History, memory::message_size(message));
+    REACTIONS.write().unwrap().remove(&message_id);
+    Option::Some(true)
+}
+
+/// Adds a reaction of given user using given emoji to the message with given ID,
+/// or removes the reaction if the user has already reacted this way. A message
+/// can have at most [`MAX_REACTION_KINDS`] different reactions.
+///
+/// # Parameters
+///
+///  -  `message_id`: ID of the message to react to
+///  -  `nickname`: nickname of the user who reacts
+///  -  `emoji`: the emoji used to react
+///
+/// # Return value
+///
+/// [`Option::None`] if there is no such message in the message history or it
+/// has been deleted, otherwise whether the reaction has been added or removed,
+/// which is `false` only if the message already has too many different
+/// reactions.
+pub fn toggle_reaction(message_id: u64, nickname: &str, emoji: &str) -> Option<bool> {
+    // The message list is locked, so the message cannot be removed before its
+    // reactions are stored:
+    let message_list = MESSAGE_LIST.read().unwrap();
+    message_list.get(&message_id).filter(|message| !message.is_deleted())?;
+    let mut reactions = REACTIONS.write().unwrap();
+    let message_reactions = reactions.entry(message_id).or_default();
+    if !message_reactions.contains_key(emoji) && message_reactions.len() >= MAX_REACTION_KINDS {
+        return Option::Some(false)
+    }
+    let users = message_reactions.entry(emoji.to_string()).or_default();
+    if !users.remove(nickname) {
+        users.insert(nickname.to_string());
+    }
+    // Emoji nobody reacts with anymore are not kept:
+    message_reactions.retain(|_, users| !users.is_empty());
+    if message_reactions.is_empty() {
+        reactions.remove(&message_id);
+    }
+    Option::Some(true)
+}
+
+/// Returns all reactions to the message with given ID, ordered by the emoji.
+pub fn reactions(message_id: u64) -> Vec<Reaction> {
+    match REACTIONS.read().unwrap().get(&message_id) {
+        Option::Some(reactions) => reactions.iter()
+            .map(|(emoji, users)| Reaction::new(emoji.clone(), users.iter().cloned().collect()))
+            .collect(),
+        Option::None => Vec::new(),
+    }
+}
+
 pub fn for_messages_newer_than<F>(message_id: u64, mut callback: F)
 where
     F: FnMut(&u64, &Message)
 {
     let message_list = MESSAGE_LIST.read().unwrap();
-    let iter = message_list.iter().filter(|(&id, _)| id > message_id);
+    let now = Utc::now();
+    let iter = message_list.iter()
+        .filter(|(&id, _)| id > message_id)
+        .filter(|(_, message)| !message.is_expired_at(&now) && !message.is_deleted());
     for (msg_id, message) in iter {
         callback(msg_id, message);
     }
-}
\ No newline at end of file
+}
+
+/// Returns at most `limit` newest messages which have neither expired nor been
+/// deleted and for which `predicate` returns `true`. Returned messages are
+/// ordered from the oldest to the newest one.
+pub fn find_newest<F>(limit: usize, mut predicate: F) -> Vec<Message>
+where
+    F: FnMut(&Message) -> bool
+{
+    let message_list = MESSAGE_LIST.read().unwrap();
+    let now = Utc::now();
+    let mut found: Vec<Message> = message_list.values()
+        .rev()
+        .filter(|message| !message.is_expired_at(&now) && !message.is_deleted())
+        .filter(|message| predicate(message))
+        .take(limit)
+        .cloned()
+        .collect();
+    found.reverse();
+    found
+}
+
+/// Returns at most `limit` newest messages which have neither expired nor been
+/// deleted and for which `predicate` returns `true`, together with their IDs.
+/// Returned messages are ordered from the oldest to the newest one.
+pub fn newest_entries<F>(limit: usize, mut predicate: F) -> Vec<HistoryEntry>
+where
+    F: FnMut(&u64, &Message) -> bool
+{
+    let message_list = MESSAGE_LIST.read().unwrap();
+    let now = Utc::now();
+    let mut found: Vec<HistoryEntry> = message_list.iter()
+        .rev()
+        .filter(|(_, message)| !message.is_expired_at(&now) && !message.is_deleted())
+        .filter(|(id, message)| predicate(id, message))
+        .take(limit)
+        .map(|(&id, message)| HistoryEntry::new(id, message.clone()))
+        .collect();
+    found.reverse();
+    found
+}
+
+/// Returns at most `limit` newest messages with ID less than `before_id`, or
+/// newest messages at all if `before_id` is [`Option::None`], which have
+/// neither expired nor been deleted and for which `predicate` returns `true`,
+/// together with their IDs. Returned messages are ordered from the oldest to
+/// the newest one.
+///
+/// # Return value
+///
+/// Tuple of the found messages and a flag, which is `true` if there are older
+/// messages matching `predicate` than the returned ones.
+pub fn entries_before<F>(before_id: Option<u64>, limit: usize, mut predicate: F) -> (Vec<HistoryEntry>, bool)
+where
+    F: FnMut(&u64, &Message) -> bool
+{
+    let message_list = MESSAGE_LIST.read().unwrap();
+    let now = Utc::now();
+    let mut found: Vec<HistoryEntry> = message_list.range(..before_id.unwrap_or(u64::MAX))
+        .rev()
+        .filter(|(_, message)| !message.is_expired_at(&now) && !message.is_deleted())
+        .filter(|(id, message)| predicate(id, message))
+        .take(limit.saturating_add(1))
+        .map(|(&id, message)| HistoryEntry::new(id, message.clone()))
+        .collect();
+    let more = found.len() > limit;
+    found.truncate(limit);
+    found.reverse();
+    (found, more)
+}
+
+/// Returns at most `limit` newest messages with ID greater than `after_id` and
+/// less than `before_id`, which have neither expired nor been deleted and for
+/// which `predicate` returns `true`, together with their IDs. Returned messages
+/// are ordered from the oldest to the newest one.
+///
+/// # Return value
+///
+/// Tuple of the found messages and a flag, which is `true` if no message from
+/// the range is missing from the result, i. e. no message of the range has been
+/// removed from the message history yet and the limit has not been reached.
+pub fn entries_in_range<F>(after_id: u64, before_id: u64, limit: usize, mut predicate: F) -> (Vec<HistoryEntry>, bool)
+where
+    F: FnMut(&u64, &Message) -> bool
+{
+    if after_id.saturating_add(1) >= before_id {
+        return (Vec::new(), true)
+    }
+    let message_list = MESSAGE_LIST.read().unwrap();
+    let now = Utc::now();
+    let mut found: Vec<HistoryEntry> = message_list.range(after_id + 1 .. before_id)
+        .rev()
+        .filter(|(_, message)| !message.is_expired_at(&now) && !message.is_deleted())
+        .filter(|(id, message)| predicate(id, message))
+        .take(limit.saturating_add(1))
+        .map(|(&id, message)| HistoryEntry::new(id, message.clone()))
+        .collect();
+    let truncated = found.len() > limit;
+    found.truncate(limit);
+    found.reverse();
+    let pruned = message_list.keys().next().is_none_or(|&oldest| oldest > after_id + 1)
+        && *LAST_ID.read().unwrap() > after_id;
+    (found, !truncated && !pruned)
+}
+
+/// Returns all messages sent in given time range, which have neither expired nor
+/// been deleted, together with their IDs. If a bound is [`Option::None`], the
+/// range is unbounded from that side. Returned messages are ordered from the
+/// oldest to the newest one.
+pub fn entries_between(from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Vec<HistoryEntry> {
+    let message_list = MESSAGE_LIST.read().unwrap();
+    let now = Utc::now();
+    message_list.iter()
+        .filter(|(_, message)| !message.is_expired_at(&now) && !message.is_deleted())
+        .filter(|(_, message)| from.is_none_or(|from| *message.date_time() >= from))
+        .filter(|(_, message)| to.is_none_or(|to| *message.date_time() <= to))
+        .map(|(&id, message)| HistoryEntry::new(id, message.clone()))
+        .collect()
+}
+
+/// Returns the number of messages in the message history.
+pub fn len() -> usize {
+    MESSAGE_LIST.read().unwrap().len()
+}
+
+/// Returns number of messages sent before given date and time.
+pub fn count_older_than(date_time: DateTime<Utc>) -> usize {
+    let message_list = MESSAGE_LIST.read().unwrap();
+    message_list.values()
+        .filter(|message| *message.date_time() < date_time)
+        .count()
+}
+
+/// Removes all messages sent before given date and time and returns how many
+/// messages have been removed.
+pub fn remove_older_than(date_time: DateTime<Utc>) -> usize {
+    let mut message_list = MESSAGE_LIST.write().unwrap();
+    let len_before = message_list.len();
+    message_list.retain(|_, message| keep_or_free(message, *message.date_time() >= date_time));
+    forget_reactions(&message_list);
+    len_before - message_list.len()
+}
+
+/// Removes all messages which have expired at given date and time and returns
+/// IDs of the removed messages together with channels they were sent to, see
+/// [`Message::channel`].
+pub fn remove_expired(date_time: DateTime<Utc>) -> Vec<(u64, Option<String>)> {
+    let mut message_list = MESSAGE_LIST.write().unwrap();
+    let expired: Vec<(u64, Option<String>)> = message_list.iter()
+        .filter(|(_, message)| message.is_expired_at(&date_time))
+        .map(|(&id, message)| (id, message.channel().cloned()))
+        .collect();
+    expired.iter()
+        .filter_map(|(id, _)| message_list.remove(id))
+        .for_each(|message| memory::sub(Store::History, memory::message_size(&message)));
+    if !expired.is_empty() {
+        forget_reactions(&message_list);
+    }
+    expired
+}
+
+/// Returns all messages sent into given channel, which have not been deleted,
+/// together with their IDs, ordered from the oldest to the newest one.
+pub fn entries_of_channel(channel: &str) -> Vec<HistoryEntry> {
+    let message_list = MESSAGE_LIST.read().unwrap();
+    message_list.iter()
+        .filter(|(_, message)| !message.is_deleted())
+        .filter(|(_, message)| message.channel().is_some_and(|name| name == channel))
+        .map(|(&id, message)| HistoryEntry::new(id, message.clone()))
+        .collect()
+}
+
+/// Removes all messages sent into given channel and returns how many messages
+/// have been removed.
+pub fn remove_channel(channel: &str) -> usize {
+    let mut message_list = MESSAGE_LIST.write().unwrap();
+    let len_before = message_list.len();
+    message_list.retain(|_, message| keep_or_free(message, message.channel().is_none_or(|name| name != channel)));
+    forget_reactions(&message_list);
+    len_before - message_list.len()
+}
+
+/// Returns `keep`. If the message is not kept, its memory is accounted as freed.
+#[doc(hidden)]
+fn keep_or_free(message: &Message, keep: bool) -> bool {
+    if !keep {
+        memory::sub(Store::History, memory::message_size(message));
+    }
+    keep
+}
+
+/// Forgets reactions to messages which are not in given message history anymore.
+#[doc(hidden)]
+fn forget_reactions(message_list: &BTreeMap<u64, Message>) {
+    REACTIONS.write().unwrap().retain(|id, _| message_list.contains_key(id));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::entries_between;
+    use super::find_newest;
+    use super::push;
+    use super::remove_expired;
+
+    use crate::GLOBAL_CONFIG;
+
+    use chrono::Duration;
+    use chrono::Utc;
+
+    use mdchat_common::message::Message;
+    use mdchat_common::message::MessageKind;
+
+    use mdchat_serverconf::Config;
+
+    use std::sync::Arc;
+
+    /// Pushes a message of given sender expiring after given time into the
+    /// message history and returns its ID.
+    fn push_expiring(sender: &str, text: &str, ttl: Duration) -> u64 {
+        GLOBAL_CONFIG.get_or_init(|| Arc::new(Config::new()));
+        let now = Utc::now();
+        push(Message::new(sender.to_string(), Option::None, MessageKind::User, now, text.to_string(), Option::Some(now + ttl), 0))
+    }
+
+    #[test]
+    fn expired_messages_are_not_found() {
+        push_expiring("expiry-find", "expired", Duration::seconds(-1));
+        push_expiring("expiry-find", "alive", Duration::hours(1));
+        let found = find_newest(10, |message| message.sender() == "expiry-find");
+        let texts: Vec<&str> = found.iter().map(|message| message.text().as_str()).collect();
+        assert_eq!(texts, vec!["alive"]);
+    }
+
+    #[test]
+    fn expired_messages_are_not_exported() {
+        push_expiring("expiry-export", "expired", Duration::seconds(-1));
+        push_expiring("expiry-export", "alive", Duration::hours(1));
+        let texts: Vec<String> = entries_between(Option::None, Option::None).iter()
+            .filter(|entry| entry.message().sender() == "expiry-export")
+            .map(|entry| entry.message().text().clone())
+            .collect();
+        assert_eq!(texts, vec!["alive".to_string()]);
+    }
+
+    #[test]
+    fn expired_messages_are_removed_with_their_channels() {
+        GLOBAL_CONFIG.get_or_init(|| Arc::new(Config::new()));
+        let now = Utc::now();
+        let expired = now - Duration::seconds(1);
+        let in_channel = push(Message::new("expiry-remove".to_string(), Option::Some("expiry-team".to_string()),
+            MessageKind::User, now, "secret".to_string(), Option::Some(expired), 0));
+        let in_main_chat = push_expiring("expiry-remove", "public", Duration::seconds(-1));
+        let alive = push_expiring("expiry-remove", "alive", Duration::hours(1));
+        let removed = remove_expired(now);
+        assert!(removed.contains(&(in_channel, Option::Some("expiry-team".to_string()))));
+        assert!(removed.contains(&(in_main_chat, Option::None)));
+        assert!(!removed.iter().any(|(id, _)| *id == alive));
+    }
+}
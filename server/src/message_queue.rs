@@ -17,41 +17,129 @@
 
 use chrono::Utc;
 
+use crate::channel_list;
+use crate::client::Client;
 use crate::client_list;
-use crate::log;
+use crate::global_config;
+use crate::memory;
+use crate::memory::Store;
 use crate::message_list;
+use crate::preview;
+use crate::supervisor;
 use crate::user_list;
 
 use mdchat_common::command::s2c;
 use mdchat_common::message::Message;
-
-use mdlog::LogLevel;
+use mdchat_common::message::MessageKind;
+use mdchat_common::user::Availability;
 
 use once_cell::sync::Lazy;
 
+use tracing::debug;
+use tracing::field;
+use tracing::info_span;
+use tracing::warn;
+
 use std::collections::LinkedList;
-use std::io;
-use std::sync::RwLock;
-use std::thread;
+use std::net::SocketAddr;
+use std::sync::Condvar;
+use std::sync::Mutex;
 use std::time::Duration;
 
-static MESSAGE_QUEUE: Lazy<RwLock<LinkedList<Message>>> = Lazy::new(|| RwLock::new(LinkedList::new()));
+/// ID of a message waiting to be broadcast, its approximate size in bytes, socket
+/// address of the client which has sent it and the tag the client has attached
+/// to it.
+type QueuedMessage = (u64, usize, Option<SocketAddr>, Option<String>);
+
+/// Messages which have been written to the message list, but have not been
+/// broadcast yet.
+static MESSAGE_QUEUE: Lazy<Mutex<LinkedList<QueuedMessage>>> = Lazy::new(|| Mutex::new(LinkedList::new()));
+
+/// Notified when a message is pushed into [`MESSAGE_QUEUE`].
+static QUEUED: Condvar = Condvar::new();
+
+/// Maximum time the message handler waits for a message before it reports to the
+/// supervisor that it is alive.
+const WAIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Lock held while a message is broadcast or missed messages are sent to a user
+/// who has logged in, so no message is delivered to a client twice.
+static DELIVERY: Mutex<()> = Mutex::new(());
 
-/// Pushes a new [`Message`] into message queue.
+/// Writes a new [`Message`] into the message list and pushes it into message
+/// queue to be broadcast. Since the message is stored before it is broadcast,
+/// users who log in meanwhile receive it as a missed message instead.
 ///
 /// # Parameters
 ///
+/// - `origin`: socket address of the client which sent the message, if it was
+///   sent by a client; the client does not receive the message if it has
+///   suppressed the echo of its messages
+/// - `client_tag`: tag the client which sent the message has attached to it; the
+///   client is sent it back in [`s2c::Command::MessageAccepted`] when the
+///   message is broadcast
 /// - `sender`: nickname of the user who sent the message
-/// - `text`: text of the message which client sent
+/// - `channel`: channel the message is sent to, or [`Option::None`] for the
+///   main chat
+/// - `kind`: what kind of a message it is
+/// - `text`: text of the message which client sent; previews of links found in
+///   it are attached as given by global configuration, which may take up to the
+///   configured timeout for each link
+/// - `ttl`: time after which the message expires, if the message should expire
+///   at all; it is adjusted to fit the limits given by global configuration
 ///
-/// # Returns
-pub fn push(sender: String, text: String) {
-    let message = Message::new(sender, Utc::now(), text);
-    MESSAGE_QUEUE.write().unwrap().push_front(message);
+/// # Return value
+///
+/// `true` if the message has been queued, `false` if it has been rejected,
+/// because messages waiting to be broadcast are over their memory cap given by
+/// global configuration.
+pub fn push(origin: Option<SocketAddr>, client_tag: Option<String>, sender: String, channel: Option<String>, kind: MessageKind, text: String, ttl: Option<Duration>) -> bool {
+    let now = Utc::now();
+    let expires_at = ttl
+        .map(|ttl| global_config().message_filtering().read().unwrap().clamp_ttl(ttl))
+        .and_then(|ttl| chrono::Duration::from_std(ttl).ok())
+        .and_then(|ttl| now.checked_add_signed(ttl));
+    let color = user_list::color_of(&sender);
+    // Only the server generates system messages, and they are never previewed:
+    let previews = match kind {
+        MessageKind::System => Vec::new(),
+        _ => preview::previews_of(&text),
+    };
+    let message = Message::new(sender, channel, kind, now, text, expires_at, color)
+        .with_from_server(kind == MessageKind::System)
+        .with_previews(previews);
+    let size = memory::message_size(&message);
+    if !memory::fits(Store::Pending, size) {
+        warn!("Rejected message from `{}`, pending messages are over their memory cap", message.sender());
+        return false;
+    }
+    memory::add(Store::Pending, size);
+    // Messages must be queued in the order of their IDs:
+    let mut message_queue = MESSAGE_QUEUE.lock().unwrap();
+    message_queue.push_front((message_list::push(message), size, origin, client_tag));
+    drop(message_queue);
+    QUEUED.notify_one();
+    true
+}
+
+/// Pushes a new [`Message`] of [`MessageKind::System`] kind into message queue.
+/// Notices never expire. Like other messages, notices are dropped if pending
+/// messages are over their memory cap.
+///
+/// # Parameters
+///
+/// - `nickname`: nickname of the user the notice is about
+/// - `channel`: channel the notice is sent to, or [`Option::None`] for the
+///   main chat
+/// - `text`: text of the notice, which follows the nickname
+pub fn push_notice(nickname: String, channel: Option<String>, text: String) {
+    let _ = push(Option::None, Option::None, nickname, channel, MessageKind::System, text, Option::None);
 }
 
 /// Function contaning a loop for continuous message handling. This function should
-/// be called in a seperate thread.
+/// be called in a seperate thread. It blocks while the queue is empty and reports
+/// to the supervisor that it is alive whenever it wakes up, at least once in
+/// [`WAIT_TIMEOUT`].
 ///
 /// # Usage
 ///
@@ -61,35 +149,185 @@ pub fn push(sender: String, text: String) {
 /// // ...
 /// let msg_queue_handler = thread::spawn(message_queue::handle_incoming);
 /// ```
-pub fn handle_incoming() -> io::Result<()> {
+pub fn handle_incoming() {
     loop {
-        let next = pop();
-        match next {
-            Option::Some(message) => handle_msg(message),
-            Option::None => thread::sleep(Duration::ZERO),
+        supervisor::heartbeat();
+        if let Option::Some((msg_id, origin, client_tag)) = pop(WAIT_TIMEOUT) {
+            handle_msg(msg_id, origin, client_tag);
+        }
+    }
+}
+
+/// Returns the [`s2c::Command`] delivering given message with given ID to given
+/// user. The user is notified about the message if it mentions them, unless they
+/// do not want to be disturbed.
+pub fn recv_command(id: u64, message: &Message, nickname: &str) -> s2c::Command {
+    let mention = message.mentions(nickname)
+        && user_list::get_availability(nickname) != Availability::DoNotDisturb;
+    s2c::Command::MessageRecv { id, message: message.clone(), mention }
+}
+
+/// Informs all clients which are logged in that the message with given ID has
+/// been deleted from the message history, so they can remove it. Only users who
+/// can read the channel of the message are informed.
+///
+/// # Parameters
+///
+///  -  `id`: ID of the deleted message
+///  -  `channel`: the channel the message was sent to, [`Option::None`] for the
+///     main chat
+pub fn broadcast_deleted(id: u64, channel: Option<&String>) {
+    let command = s2c::Command::MessageDeleted(id);
+    client_list::for_each(|_, client| match client.nickname() {
+        Option::Some(nickname) if channel_list::can_read(channel, &nickname) => {
+            if let Result::Err(err) = client.send_command(command.clone()) {
+                client.error(err.to_string());
+            }
+        },
+        _ => {},
+    });
+}
+
+/// Informs all clients which have received the message with given ID about its
+/// current reactions, unless their users cannot read the message anymore.
+///
+/// # Parameters
+///
+///  -  `msg_id`: ID of the message whose reactions have changed
+///  -  `message`: the message whose reactions have changed
+pub fn broadcast_reactions(msg_id: u64, message: &Message) {
+    let _delivery = DELIVERY.lock().unwrap();
+    let command = s2c::Command::ReactionUpdate { msg_id, reactions: message_list::reactions(msg_id) };
+    client_list::for_each(|_, client| match client.nickname() {
+        Option::None => {},
+        Option::Some(nickname) if user_list::has_blocked(&nickname, message.sender()) => {},
+        Option::Some(nickname) if !channel_list::can_read(message.channel(), &nickname) => {},
+        Option::Some(_) if client.last_delivered_msg_id() < msg_id => {},
+        Option::Some(_) => if let Result::Err(err) = client.send_command(command.clone()) {
+            client.error(err.to_string());
+        },
+    });
+}
+
+/// Sends all messages which have been stored since the last message read by
+/// given user, or acknowledged if the user has not marked any message as read,
+/// that is messages sent while the user was not connected and messages which
+/// have been lost on the way to the user or have not been read. If the user
+/// has never acknowledged any message, the newest messages are sent instead, as
+/// many as given by global configuration.
+///
+/// # Parameters
+///
+///  -  `client`: the client the user has logged in from
+///  -  `nickname`: nickname of the user
+pub fn send_missed(client: &Client, nickname: &str) {
+    let _delivery = DELIVERY.lock().unwrap();
+    // Skip messages already delivered to the client, for example when the user
+    // logs in again using the same connection:
+    let can_receive = |&msg_id: &u64, message: &Message| msg_id > client.last_delivered_msg_id()
+        && !user_list::has_blocked(nickname, message.sender())
+        && channel_list::can_read(message.channel(), nickname);
+    let send = |msg_id: u64, message: &Message| {
+        if client.is_err() {
+            return
         }
+        match client.send_command(recv_command(msg_id, message, nickname)) {
+            Result::Ok(()) => client.set_last_delivered_msg_id(msg_id),
+            Result::Err(err) => {
+                client.error(err.to_string());
+                return
+            },
+        }
+        // Reactions added while the user was not connected are sent as well:
+        let reactions = message_list::reactions(msg_id);
+        if !reactions.is_empty() {
+            if let Result::Err(err) = client.send_command(s2c::Command::ReactionUpdate { msg_id, reactions }) {
+                client.error(err.to_string());
+            }
+        }
+    };
+    // Messages which have been received, but not read, are sent again:
+    let last_msg_id = user_list::get_last_read_msg_id(nickname)
+        .or_else(|| user_list::get_last_sent_msg_id(nickname));
+    match last_msg_id {
+        Option::Some(last_msg_id) => message_list::for_messages_newer_than(last_msg_id, |msg_id, message| {
+            if can_receive(msg_id, message) {
+                send(*msg_id, message);
+            }
+        }),
+        Option::None => {
+            let bootstrap = global_config().history().read().unwrap().get_bootstrap();
+            for entry in message_list::newest_entries(bootstrap, can_receive) {
+                send(entry.id(), entry.message());
+            }
+        },
     }
 }
 
+/// Takes the oldest message from the queue, waiting at most given time for one
+/// if the queue is empty.
 #[doc(hidden)]
-fn pop() -> Option<Message> {
-    MESSAGE_QUEUE.write().unwrap().pop_back()
+fn pop(timeout: Duration) -> Option<(u64, Option<SocketAddr>, Option<String>)> {
+    let message_queue = MESSAGE_QUEUE.lock().unwrap();
+    let (mut message_queue, _) = QUEUED
+        .wait_timeout_while(message_queue, timeout, |message_queue| message_queue.is_empty())
+        .unwrap();
+    let (msg_id, size, origin, client_tag) = message_queue.pop_back()?;
+    memory::sub(Store::Pending, size);
+    Option::Some((msg_id, origin, client_tag))
 }
 
 #[doc(hidden)]
-fn handle_msg(message: Message) {
+fn handle_msg(msg_id: u64, origin: Option<SocketAddr>, client_tag: Option<String>) {
+    // The message may have expired or been removed meanwhile:
+    let message = match message_list::get(msg_id) {
+        Option::Some(message) => message,
+        Option::None => return,
+    };
     // Log that message is being processed:
-    let log_message = format!("A new message is being processed: {:?}", message);
-    log(LogLevel::Debug, &log_message);
-    // Add message to message list
-    let msg_id = message_list::push(message.clone());
-    // Send message to all clients that are logged in:
-    let command = s2c::Command::MessageRecv(message);
-    client_list::for_each(|_, client| match client.nickname() {
-        Option::None => {},
-        Option::Some(nickname) => match client.send_command(command.clone()) {
-            Result::Ok(()) => user_list::set_last_sent_msg_id(&nickname, msg_id),
-            Result::Err(err) => client.error(err.to_string()),
+    debug!("A new message is being processed: {:?}", message);
+    let _delivery = DELIVERY.lock().unwrap();
+    let broadcast_span = info_span!("broadcast", message_id = msg_id, recipients = field::Empty);
+    let _broadcast_span = broadcast_span.enter();
+    // Send message to all clients that are logged in, except those whose users
+    // blocked the sender or cannot read the channel, and those which have already
+    // received the message as a missed one. The message is marked as delivered
+    // to the client afterwards, the user acknowledges it later. A client which
+    // has suppressed the echo of its messages does not receive its message, but
+    // it is marked as delivered, so later acknowledgments cover it:
+    let sender = message.sender().clone();
+    let channel = message.channel().cloned();
+    let mut recipients = 0;
+    client_list::for_each(|socket_addr, client| {
+        // The sender learns the ID of its message before receiving the message
+        // itself. Clients using the first version of the protocol do not know
+        // the acknowledgment:
+        if origin == Option::Some(*socket_addr) && client.protocol_version() >= 2 {
+            let accepted = s2c::Command::MessageAccepted {
+                client_tag: client_tag.clone(),
+                id: msg_id,
+                timestamp: *message.date_time(),
+            };
+            if let Result::Err(err) = client.send_command(accepted) {
+                client.error(err.to_string());
+                return
+            }
+        }
+        match client.nickname() {
+            Option::None => {},
+            Option::Some(_) if origin == Option::Some(*socket_addr) && client.is_echo_suppressed() =>
+                client.set_last_delivered_msg_id(msg_id),
+            Option::Some(nickname) if user_list::has_blocked(&nickname, &sender) => {},
+            Option::Some(nickname) if !channel_list::can_read(channel.as_ref(), &nickname) => {},
+            Option::Some(_) if client.last_delivered_msg_id() >= msg_id => {},
+            Option::Some(nickname) => match client.send_command(recv_command(msg_id, &message, &nickname)) {
+                Result::Ok(()) => {
+                    client.set_last_delivered_msg_id(msg_id);
+                    recipients += 1;
+                },
+                Result::Err(err) => client.error(err.to_string()),
+            }
         }
     });
+    broadcast_span.record("recipients", recipients);
 }
\ No newline at end of file
@@ -0,0 +1,113 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! A module pushing metrics of the server to collectors using StatsD protocol or
+//! OpenTelemetry protocol (OTLP) over HTTP. The pushed metrics are the gauges
+//! reported by the health endpoint.
+
+use crate::global_config;
+use crate::health;
+
+use serde_json::json;
+
+use tracing::warn;
+
+use std::io;
+use std::net::SocketAddr;
+use std::net::UdpSocket;
+use std::thread;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+/// Function containing a loop for periodic pushing of metrics to collectors as
+/// given by global configuration. This function should be called in a separate
+/// thread.
+pub fn push_periodically() {
+    loop {
+        let (statsd, otlp, interval, prefix) = {
+            let global_config = global_config();
+            let metrics_config = global_config.metrics().read().unwrap();
+            (
+                metrics_config.get_statsd(),
+                metrics_config.get_otlp().map(str::to_string),
+                metrics_config.get_interval(),
+                metrics_config.get_prefix().to_string(),
+            )
+        };
+        if statsd.is_some() || otlp.is_some() {
+            let gauges = health::gauges();
+            if let Option::Some(addr) = statsd {
+                if let Result::Err(err) = push_statsd(addr, &prefix, &gauges) {
+                    warn!("Could not push metrics to StatsD at {}: {}", addr, err);
+                }
+            }
+            if let Option::Some(url) = otlp {
+                if let Result::Err(err) = push_otlp(&url, &prefix, &gauges) {
+                    warn!("Could not push metrics to OTLP collector at {}: {}", url, err);
+                }
+            }
+        }
+        thread::sleep(interval);
+    }
+}
+
+/// Sends all gauges in a single StatsD datagram.
+#[doc(hidden)]
+fn push_statsd(addr: SocketAddr, prefix: &str, gauges: &[(&str, u64)]) -> io::Result<()> {
+    let local_addr = match addr {
+        SocketAddr::V4(_) => "0.0.0.0:0",
+        SocketAddr::V6(_) => "[::]:0",
+    };
+    let datagram = gauges.iter()
+        .map(|(name, value)| format!("{}.{}:{}|g", prefix, name, value))
+        .collect::<Vec<String>>()
+        .join("\n");
+    UdpSocket::bind(local_addr)?.send_to(datagram.as_bytes(), addr).map(|_| ())
+}
+
+/// Sends all gauges as a JSON-encoded OTLP export request.
+#[doc(hidden)]
+fn push_otlp(url: &str, prefix: &str, gauges: &[(&str, u64)]) -> io::Result<()> {
+    let time_unix_nano = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or(0)
+        .to_string();
+    let metrics: Vec<serde_json::Value> = gauges.iter()
+        .map(|(name, value)| json!({
+            "name": format!("{}.{}", prefix, name),
+            "gauge": {
+                "dataPoints": [{ "timeUnixNano": time_unix_nano, "asInt": value.to_string() }],
+            },
+        }))
+        .collect();
+    let request = json!({
+        "resourceMetrics": [{
+            "resource": {
+                "attributes": [{ "key": "service.name", "value": { "stringValue": env!("CARGO_PKG_NAME") } }],
+            },
+            "scopeMetrics": [{
+                "scope": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") },
+                "metrics": metrics,
+            }],
+        }],
+    });
+    ureq::post(url)
+        .set("Content-Type", "application/json")
+        .send_string(&request.to_string())
+        .map(|_| ())
+        .map_err(io::Error::other)
+}
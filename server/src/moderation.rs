@@ -0,0 +1,31 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use chrono::Utc;
+
+use crate::client_list;
+
+use mdchat_common::moderation::ModEvent;
+use mdchat_common::moderation::ModEventKind;
+
+/// Sends an event relevant for moderation to all clients which are subscribed to
+/// moderation events. This function never logs anything, so it can be called
+/// while logging.
+pub fn publish(kind: ModEventKind) {
+    let event = ModEvent::new(Utc::now(), kind);
+    client_list::for_each(|_, client| client.send_mod_event(&event));
+}
@@ -0,0 +1,277 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! A module for queues of frames waiting to be written to a connection, which
+//! implement flow control of connections as given by global configuration.
+
+use crate::global_config;
+use crate::memory;
+use crate::memory::Store;
+
+use std::collections::VecDeque;
+use std::io;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Maximum number of bytes written to a connection at once.
+const MAX_BATCH_BYTES: usize = 64 * 1024;
+
+/// A queue of frames waiting to be written to a connection by its writer thread.
+/// If the queue grows over the pause limits, the connection is paused until the
+/// queue shrinks to a half of the limits. Frames in all queues are accounted as
+/// [`Store::Outbound`] memory.
+pub struct OutboundQueue {
+    state: Mutex<QueueState>,
+    changed: Condvar,
+    pause_bytes: usize,
+    pause_frames: usize,
+    max_bytes: usize,
+    slow_timeout: Duration,
+    batch_window: Duration,
+}
+
+#[doc(hidden)]
+#[derive(Default)]
+struct QueueState {
+    frames: VecDeque<Vec<u8>>,
+    bytes: usize,
+    closed: bool,
+    reset: bool,
+}
+
+impl OutboundQueue {
+    /// Creates a new empty [`OutboundQueue`] with limits given by global
+    /// configuration.
+    pub fn new() -> Self {
+        let global_config = global_config();
+        let flow_config = global_config.flow_control().read().unwrap();
+        Self {
+            state: Mutex::new(QueueState::default()),
+            changed: Condvar::new(),
+            pause_bytes: flow_config.get_pause_bytes(),
+            pause_frames: flow_config.get_pause_frames(),
+            max_bytes: flow_config.get_max_bytes(),
+            slow_timeout: flow_config.get_slow_timeout(),
+            batch_window: flow_config.get_batch_window(),
+        }
+    }
+
+    /// Pushes a frame at the end of the queue.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if the frame has been pushed
+    ///  -  [`Result::Err`] if the queue is closed, or if the frame would make the
+    ///     queue larger than allowed, or if the queue is not empty and the frame
+    ///     would make all queues larger than their memory cap; in that case the
+    ///     queue is discarded and the connection should be reset
+    pub fn push(&self, frame: Vec<u8>) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return Result::Err(io::Error::new(io::ErrorKind::NotConnected, "Connection is closed"));
+        }
+        // Connections which keep up still receive frames when over the memory
+        // cap, only the slow ones are shed:
+        if state.bytes + frame.len() > self.max_bytes
+            || (!state.frames.is_empty() && !memory::fits(Store::Outbound, frame.len())) {
+            Self::discard(&mut state);
+            self.changed.notify_all();
+            return Result::Err(io::Error::new(io::ErrorKind::TimedOut, "Connection is too slow"));
+        }
+        state.bytes += frame.len();
+        memory::add(Store::Outbound, frame.len());
+        state.frames.push_back(frame);
+        self.changed.notify_all();
+        Result::Ok(())
+    }
+
+    /// Waits for the next frame and removes it from the queue together with the
+    /// frames following it, so they can be written at once. After the first
+    /// frame, frames are collected for the batching window given by global
+    /// configuration, or until the batch is large enough. Returns
+    /// [`Option::None`] if the queue has been closed and all frames have been
+    /// removed.
+    pub fn pop_batch(&self) -> Option<Vec<u8>> {
+        let state = self.state.lock().unwrap();
+        let mut state = self.changed.wait_while(state, |state| state.frames.is_empty() && !state.closed).unwrap();
+        let mut batch = state.frames.pop_front()?;
+        let deadline = Instant::now() + self.batch_window;
+        loop {
+            while let Option::Some(frame) = state.frames.front() {
+                if batch.len() + frame.len() > MAX_BATCH_BYTES {
+                    break
+                }
+                batch.extend_from_slice(frame);
+                state.frames.pop_front();
+            }
+            let now = Instant::now();
+            if state.closed || now >= deadline || !state.frames.is_empty() {
+                break
+            }
+            state = self.changed.wait_timeout(state, deadline - now).unwrap().0;
+        }
+        state.bytes -= batch.len();
+        memory::sub(Store::Outbound, batch.len());
+        self.changed.notify_all();
+        Option::Some(batch)
+    }
+
+    /// Closes the queue. Frames which are already in the queue are still written,
+    /// then the connection is finished. If `reset` is `true`, the connection is
+    /// reset afterwards.
+    pub fn close(&self, reset: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        state.reset |= reset;
+        self.changed.notify_all();
+    }
+
+    /// Closes the queue and discards all frames in it. The connection should be
+    /// reset.
+    pub fn abort(&self) {
+        Self::discard(&mut self.state.lock().unwrap());
+        self.changed.notify_all();
+    }
+
+    /// Returns whether the queue has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().unwrap().closed
+    }
+
+    /// Returns whether the connection should be reset after the queue is empty.
+    pub fn is_reset(&self) -> bool {
+        self.state.lock().unwrap().reset
+    }
+
+    /// If the queue is over the pause limits, waits until it shrinks to a half of
+    /// them. Returns `false` if the queue has not shrunk in time given by global
+    /// configuration, which means the connection is too slow.
+    pub fn wait_until_resumed(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        if state.bytes <= self.pause_bytes && state.frames.len() <= self.pause_frames {
+            return true;
+        }
+        let (_state, timeout) = self.changed
+            .wait_timeout_while(state, self.slow_timeout, |state| !state.closed
+                && (state.bytes > self.pause_bytes / 2 || state.frames.len() > self.pause_frames / 2))
+            .unwrap();
+        !timeout.timed_out()
+    }
+
+    #[doc(hidden)]
+    fn discard(state: &mut QueueState) {
+        state.frames.clear();
+        memory::sub(Store::Outbound, state.bytes);
+        state.bytes = 0;
+        state.closed = true;
+        state.reset = true;
+    }
+}
+
+impl Drop for OutboundQueue {
+    fn drop(&mut self) {
+        // Frames which have not been written are freed:
+        memory::sub(Store::Outbound, self.state.get_mut().unwrap().bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OutboundQueue;
+    use super::MAX_BATCH_BYTES;
+
+    use crate::GLOBAL_CONFIG;
+
+    use mdchat_serverconf::Config;
+
+    use std::io;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    /// Creates a queue with given batching window and otherwise default limits.
+    fn queue(batch_window: Duration) -> OutboundQueue {
+        GLOBAL_CONFIG.get_or_init(|| Arc::new(Config::new()));
+        let mut queue = OutboundQueue::new();
+        queue.batch_window = batch_window;
+        queue
+    }
+
+    #[test]
+    fn queued_frames_are_batched_in_order() {
+        let queue = queue(Duration::ZERO);
+        queue.push(b"first ".to_vec()).unwrap();
+        queue.push(b"second ".to_vec()).unwrap();
+        queue.push(b"third".to_vec()).unwrap();
+        assert_eq!(queue.pop_batch().unwrap(), b"first second third");
+        assert_eq!(queue.state.lock().unwrap().bytes, 0);
+    }
+
+    #[test]
+    fn batches_are_limited_in_size() {
+        let queue = queue(Duration::ZERO);
+        queue.push(vec![1; MAX_BATCH_BYTES / 2]).unwrap();
+        queue.push(vec![2; MAX_BATCH_BYTES / 2]).unwrap();
+        queue.push(vec![3; 1]).unwrap();
+        assert_eq!(queue.pop_batch().unwrap().len(), MAX_BATCH_BYTES);
+        assert_eq!(queue.pop_batch().unwrap(), vec![3]);
+        // A frame larger than a batch is still written at once:
+        queue.push(vec![4; MAX_BATCH_BYTES + 1]).unwrap();
+        queue.push(vec![5; 1]).unwrap();
+        assert_eq!(queue.pop_batch().unwrap().len(), MAX_BATCH_BYTES + 1);
+        assert_eq!(queue.pop_batch().unwrap(), vec![5]);
+    }
+
+    #[test]
+    fn frames_pushed_within_batch_window_are_batched() {
+        let queue = Arc::new(queue(Duration::from_millis(500)));
+        queue.push(b"first ".to_vec()).unwrap();
+        let pusher = {
+            let queue = Arc::clone(&queue);
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                queue.push(b"second".to_vec()).unwrap();
+            })
+        };
+        assert_eq!(queue.pop_batch().unwrap(), b"first second");
+        pusher.join().unwrap();
+    }
+
+    #[test]
+    fn closed_queue_is_drained_before_it_ends() {
+        let queue = queue(Duration::from_secs(5));
+        queue.push(b"last".to_vec()).unwrap();
+        queue.close(false);
+        assert_eq!(queue.push(b"late".to_vec()).unwrap_err().kind(), io::ErrorKind::NotConnected);
+        assert_eq!(queue.pop_batch().unwrap(), b"last");
+        assert_eq!(queue.pop_batch(), Option::None);
+        assert!(queue.is_closed());
+        assert!(!queue.is_reset());
+    }
+
+    #[test]
+    fn oversized_queue_is_discarded() {
+        let mut queue = queue(Duration::ZERO);
+        queue.max_bytes = 8;
+        queue.push(b"12345".to_vec()).unwrap();
+        assert_eq!(queue.push(b"6789".to_vec()).unwrap_err().kind(), io::ErrorKind::TimedOut);
+        assert!(queue.is_closed());
+        assert!(queue.is_reset());
+        assert_eq!(queue.pop_batch(), Option::None);
+    }
+}
@@ -0,0 +1,76 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::global_config;
+
+use once_cell::sync::OnceCell;
+
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+
+/// Minimum length of the pepper in bytes.
+const MIN_PEPPER_LENGTH: usize = 16;
+
+/// The secret pepper mixed into password hashes. Empty if no pepper is used.
+static PEPPER: OnceCell<Vec<u8>> = OnceCell::new();
+
+/// Loads the secret pepper from the pepper file given by global configuration, if
+/// there is any.
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] with whether a pepper is used
+///  -  [`Result::Err`] if the pepper file could not be read, is not a regular
+///     file, is accessible by other users or is too short
+pub fn load() -> io::Result<bool> {
+    let pepper_file = global_config().auth().read().unwrap().get_pepper_file().cloned();
+    let pepper = match pepper_file {
+        Option::Some(pepper_file) => {
+            let metadata = fs::metadata(&pepper_file)?;
+            if !metadata.is_file() {
+                return Result::Err(io::Error::new(io::ErrorKind::InvalidInput,
+                    format!("{} is not a regular file", pepper_file.display())));
+            }
+            if metadata.permissions().mode() & 0o077 != 0 {
+                return Result::Err(io::Error::new(io::ErrorKind::PermissionDenied,
+                    format!("{} must not be accessible by other users", pepper_file.display())));
+            }
+            let pepper = fs::read(&pepper_file)?;
+            if pepper.len() < MIN_PEPPER_LENGTH {
+                return Result::Err(io::Error::new(io::ErrorKind::InvalidData,
+                    format!("{} must contain at least {} bytes", pepper_file.display(), MIN_PEPPER_LENGTH)));
+            }
+            pepper
+        },
+        Option::None => Vec::new(),
+    };
+    let is_used = !pepper.is_empty();
+    PEPPER.set(pepper).map_err(|_| panic!("Pepper already loaded")).unwrap();
+    Result::Ok(is_used)
+}
+
+/// Returns the secret pepper, which is empty if no pepper is used.
+pub fn get() -> &'static [u8] {
+    PEPPER.get().unwrap()
+}
+
+/// Uses no pepper, unless it has been set already, since tests share it.
+#[cfg(test)]
+pub fn init_for_tests() {
+    PEPPER.get_or_init(Vec::new);
+}
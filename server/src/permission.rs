@@ -0,0 +1,76 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::global_config;
+
+use mdchat_serverconf::Capability;
+use mdchat_serverconf::Config;
+
+use std::sync::Arc;
+
+/// Checks whether users are allowed to use capabilities, as given by the `role`
+/// and `permissions` sections of global configuration. Every privileged command
+/// is checked by a [`PermissionChecker`].
+pub struct PermissionChecker {
+    global_config: Arc<Config>,
+}
+
+impl Default for PermissionChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PermissionChecker {
+    /// Creates a new [`PermissionChecker`] using the global configuration.
+    pub fn new() -> Self {
+        Self { global_config: global_config() }
+    }
+
+    /// Returns whether given user is allowed to use given capability.
+    pub fn is_allowed(&self, nickname: &str, capability: Capability) -> bool {
+        let role = self.global_config.role_of(nickname);
+        self.global_config.permissions().read().unwrap().is_allowed(role, capability)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PermissionChecker;
+
+    use crate::GLOBAL_CONFIG;
+
+    use mdchat_serverconf::Capability;
+    use mdchat_serverconf::Config;
+    use mdchat_serverconf::Role;
+
+    use std::sync::Arc;
+
+    #[test]
+    fn roles_of_users_are_checked_against_matrix() {
+        let global_config = GLOBAL_CONFIG.get_or_init(|| Arc::new(Config::new()));
+        global_config.roles().write().unwrap().assign("permission-moderator".to_string(), Role::Moderator);
+        global_config.permissions().write().unwrap().set_role(Capability::Announce, Role::Moderator);
+        let checker = PermissionChecker::new();
+        assert!(checker.is_allowed("permission-moderator", Capability::Announce));
+        assert!(checker.is_allowed("permission-moderator", Capability::Kick));
+        assert!(!checker.is_allowed("permission-moderator", Capability::ManageUsers));
+        // Users without an assigned role are regular users:
+        assert!(!checker.is_allowed("permission-user", Capability::Announce));
+        assert!(checker.is_allowed("permission-user", Capability::Send));
+    }
+}
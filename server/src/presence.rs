@@ -0,0 +1,176 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::catalog;
+use crate::channel_list;
+use crate::client_list;
+use crate::global_config;
+use crate::message_queue;
+use crate::user_list;
+
+use mdchat_common::command::s2c;
+
+use once_cell::sync::Lazy;
+
+use std::net::SocketAddr;
+use std::sync::RwLock;
+use std::thread;
+use std::time::Duration;
+
+/// Represents whether a user has joined or left the chat.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Presence {
+    Joined,
+    Left,
+}
+
+impl Presence {
+    /// Returns the text of the notice about a single user in the default
+    /// locale.
+    #[doc(hidden)]
+    fn verb(&self) -> String {
+        match self {
+            Self::Joined => tr!(catalog::default_locale(), "notice.joined-chat"),
+            Self::Left => tr!(catalog::default_locale(), "notice.left-chat"),
+        }
+    }
+
+    /// Returns the text of the notice following the nickname of the first of
+    /// given number of other users in the default locale.
+    #[doc(hidden)]
+    fn others_verb(&self, others: usize) -> String {
+        match self {
+            Self::Joined => tr!(catalog::default_locale(), "notice.others-joined-chat", count = others),
+            Self::Left => tr!(catalog::default_locale(), "notice.others-left-chat", count = others),
+        }
+    }
+}
+
+/// A notice collected during the collapse window.
+struct PendingNotice {
+    channel: Option<String>,
+    presence: Presence,
+    nickname: String,
+}
+
+/// Notices collected during the collapse window, from the oldest to the newest.
+static PENDING: Lazy<RwLock<Vec<PendingNotice>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+/// Broadcasts a notice that given user has logged in, if enabled by global
+/// configuration. Other logged in clients are also sent
+/// [`s2c::Command::UserJoined`] if the user is not logged in from any other
+/// connection.
+///
+/// # Parameters
+///
+///  -  `nickname`: nickname of the user
+///  -  `socket_addr`: address of the connection the user has logged in on
+///  -  `first_connection`: whether the user is not logged in from any other
+///     connection, see [`client_list::log_in`]
+pub fn joined(nickname: String, socket_addr: &SocketAddr, first_connection: bool) {
+    if first_connection {
+        if let Option::Some(info) = user_list::user_info(&nickname) {
+            push_roster_update(s2c::Command::UserJoined(info), Option::Some(socket_addr));
+        }
+    }
+    if global_config().notices().read().unwrap().is_join_enabled() {
+        record(nickname, Presence::Joined);
+    }
+}
+
+/// Broadcasts a notice that given user has disconnected, if enabled by global
+/// configuration. Logged in clients are also sent [`s2c::Command::UserLeft`] if
+/// the user is not logged in from any other connection.
+///
+/// # Parameters
+///
+///  -  `nickname`: nickname of the user
+///  -  `last_connection`: whether the closed connection was the last one the
+///     user was logged in on, see [`client_list::remove_connection`]
+pub fn left(nickname: String, last_connection: bool) {
+    if last_connection {
+        push_roster_update(s2c::Command::UserLeft(nickname.clone()), Option::None);
+    }
+    if global_config().notices().read().unwrap().is_leave_enabled() {
+        record(nickname, Presence::Left);
+    }
+}
+
+/// Function containing a loop for periodic broadcasting of collected notices,
+/// collapsing them if there are too many of them. This function should be called
+/// in a separate thread.
+pub fn flush_periodically() {
+    loop {
+        let window = global_config().notices().read().unwrap().get_collapse_window();
+        thread::sleep(window.max(Duration::from_secs(1)));
+        flush();
+    }
+}
+
+#[doc(hidden)]
+fn push_roster_update(command: s2c::Command, except: Option<&SocketAddr>) {
+    client_list::for_each(|socket_addr, client| if client.nickname().is_some() && Option::Some(socket_addr) != except {
+        if let Result::Err(err) = client.send_command(command.clone()) {
+            client.error(err.to_string());
+        }
+    });
+}
+
+#[doc(hidden)]
+fn record(nickname: String, presence: Presence) {
+    let (channels, window) = {
+        let global_config = global_config();
+        let notice_config = global_config.notices().read().unwrap();
+        (notice_config.is_channels_enabled(), notice_config.get_collapse_window())
+    };
+    let mut targets = vec![Option::None];
+    if channels {
+        targets.extend(channel_list::channels_of(&nickname).into_iter().map(Option::Some));
+    }
+    for channel in targets {
+        match window.is_zero() {
+            true => message_queue::push_notice(nickname.clone(), channel, presence.verb()),
+            false => PENDING.write().unwrap().push(PendingNotice { channel, presence, nickname: nickname.clone() }),
+        }
+    }
+}
+
+#[doc(hidden)]
+fn flush() {
+    let pending = std::mem::take(&mut *PENDING.write().unwrap());
+    let threshold = global_config().notices().read().unwrap().get_collapse_threshold().get() as usize;
+    // Group the notices by the channel and by whether users joined or left,
+    // keeping the order in which they were collected:
+    let mut groups: Vec<(Option<String>, Presence, Vec<String>)> = Vec::new();
+    for notice in pending {
+        match groups.iter_mut().find(|(channel, presence, _)| *channel == notice.channel && *presence == notice.presence) {
+            Option::Some((_, _, nicknames)) => nicknames.push(notice.nickname),
+            Option::None => groups.push((notice.channel, notice.presence, vec![notice.nickname])),
+        }
+    }
+    for (channel, presence, mut nicknames) in groups {
+        if nicknames.len() <= threshold {
+            for nickname in nicknames {
+                message_queue::push_notice(nickname, channel.clone(), presence.verb());
+            }
+            continue
+        }
+        let others = nicknames.len() - 1;
+        let first = nicknames.swap_remove(0);
+        message_queue::push_notice(first, channel, presence.others_verb(others));
+    }
+}
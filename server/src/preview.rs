@@ -0,0 +1,275 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module detecting links in messages and building their previews, which are
+//! attached to broadcast messages. Titles and descriptions are fetched only from
+//! hosts allowed by global configuration, with strict limits on time and size.
+
+use crate::global_config;
+
+use mdchat_common::markup;
+use mdchat_common::markup::Span;
+use mdchat_common::message::LinkPreview;
+use mdchat_common::sanitize;
+
+use tracing::debug;
+
+use std::io;
+use std::io::Read;
+use std::time::Duration;
+
+/// Maximum number of links for which previews are attached to a message.
+const MAX_PREVIEWS: usize = 3;
+
+/// Maximum number of characters of a fetched title.
+const MAX_TITLE_CHARS: usize = 200;
+
+/// Maximum number of characters of a fetched description.
+const MAX_DESCRIPTION_CHARS: usize = 300;
+
+/// Returns previews of links found in given text of a message, as given by
+/// global configuration. If link detection is off, no previews are returned.
+/// Titles and descriptions of linked pages are fetched if it is allowed,
+/// otherwise the previews contain only the links.
+///
+/// # Parameters
+///
+///  -  `text`: text of the message, which may contain markup
+pub fn previews_of(text: &str) -> Vec<LinkPreview> {
+    let (detect, fetch, timeout, max_size) = {
+        let global_config = global_config();
+        let preview_config = global_config.previews().read().unwrap();
+        (
+            preview_config.is_detect(),
+            preview_config.is_fetch(),
+            preview_config.get_timeout(),
+            preview_config.get_max_size(),
+        )
+    };
+    if !detect {
+        return Vec::new();
+    }
+    find_urls(text).into_iter()
+        .map(|url| {
+            let fetched = host_of(&url)
+                .filter(|host| fetch && global_config().previews().read().unwrap().is_allowed_host(host))
+                .map(|_| fetch_metadata(&url, timeout, max_size));
+            match fetched {
+                Option::Some(Result::Ok((title, description))) => LinkPreview::new(url, title, description),
+                Option::Some(Result::Err(err)) => {
+                    debug!("Could not fetch preview of {}: {}", url, err);
+                    LinkPreview::new(url, Option::None, Option::None)
+                },
+                Option::None => LinkPreview::new(url, Option::None, Option::None),
+            }
+        })
+        .collect()
+}
+
+/// Returns distinct HTTP and HTTPS links found in given text, both targets of
+/// links written in markup and bare URLs, up to [`MAX_PREVIEWS`] links.
+fn find_urls(text: &str) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+    for span in markup::parse(text) {
+        let found: Vec<&str> = match &span {
+            Span::Link { url, .. } => vec![url.as_str()],
+            Span::Text(text) | Span::Bold(text) | Span::Italic(text) => text.split_whitespace()
+                .map(|word| word.trim_start_matches(['(', '<', '"', '\''])
+                    .trim_end_matches(['.', ',', ';', ':', '!', '?', ')', '>', '"', '\'']))
+                .collect(),
+            Span::Code(_) => Vec::new(),
+        };
+        for url in found {
+            if urls.len() == MAX_PREVIEWS {
+                return urls;
+            }
+            if host_of(url).is_some() && markup::is_valid_url(url) && !urls.iter().any(|other| other == url) {
+                urls.push(url.to_string());
+            }
+        }
+    }
+    urls
+}
+
+/// Returns the host of given HTTP or HTTPS URL in lowercase, or
+/// [`Option::None`] if it is not such a URL, or if it contains user
+/// information, which could be used to disguise the host.
+fn host_of(url: &str) -> Option<String> {
+    let lowercase = url.to_ascii_lowercase();
+    let rest = lowercase.strip_prefix("http://").or_else(|| lowercase.strip_prefix("https://"))?;
+    let authority = rest.split(['/', '?', '#']).next().unwrap();
+    if authority.contains('@') || authority.starts_with('[') {
+        return Option::None;
+    }
+    let host = authority.split(':').next().unwrap();
+    match host.is_empty() {
+        true => Option::None,
+        false => Option::Some(host.to_string()),
+    }
+}
+
+/// Fetches the title and the description of the HTML page at given URL.
+/// Redirects are not followed, since they could lead to a host which is not
+/// allowed.
+///
+/// # Parameters
+///
+///  -  `url`: URL of the page
+///  -  `timeout`: maximum time the whole request can take
+///  -  `max_size`: maximum number of bytes read from the page
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] containing the title and the description of the page,
+///     each of them may be missing
+///  -  [`Result::Err`] if the page could not be fetched or it is not an HTML
+///     page
+fn fetch_metadata(url: &str, timeout: Duration, max_size: usize) -> io::Result<(Option<String>, Option<String>)> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout(timeout)
+        .redirects(0)
+        .user_agent(concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")))
+        .build();
+    let response = agent.get(url)
+        .set("Accept", "text/html")
+        .call()
+        .map_err(io::Error::other)?;
+    if response.status() != 200 || response.content_type() != "text/html" {
+        return Result::Err(io::Error::new(io::ErrorKind::InvalidData, "not an HTML page"));
+    }
+    let mut body = Vec::new();
+    response.into_reader().take(max_size as u64).read_to_end(&mut body)?;
+    let html = String::from_utf8_lossy(&body);
+    let title = meta_content(&html, "og:title").or_else(|| title_of(&html));
+    let description = meta_content(&html, "og:description").or_else(|| meta_content(&html, "description"));
+    Result::Ok((
+        title.and_then(|title| clean(&title, MAX_TITLE_CHARS)),
+        description.and_then(|description| clean(&description, MAX_DESCRIPTION_CHARS)),
+    ))
+}
+
+/// Returns the content of the `<title>` element of given HTML page.
+fn title_of(html: &str) -> Option<String> {
+    // ASCII lowercase keeps byte offsets, so they can be used on the original:
+    let lowercase = html.to_ascii_lowercase();
+    let start = lowercase.find("<title")?;
+    let start = start + lowercase[start..].find('>')? + 1;
+    let end = start + lowercase[start..].find("</title")?;
+    Option::Some(html[start..end].to_string())
+}
+
+/// Returns the `content` attribute of the `<meta>` element of given HTML page,
+/// whose `property` or `name` attribute is equal to `name`.
+fn meta_content(html: &str, name: &str) -> Option<String> {
+    let lowercase = html.to_ascii_lowercase();
+    let mut offset = 0;
+    while let Option::Some(start) = lowercase[offset..].find("<meta") {
+        let start = offset + start;
+        let end = start + lowercase[start..].find('>')?;
+        let tag = &html[start..end];
+        let matches = ["property", "name"].iter()
+            .filter_map(|attribute| attribute_of(tag, attribute))
+            .any(|value| value.eq_ignore_ascii_case(name));
+        if matches {
+            return attribute_of(tag, "content");
+        }
+        offset = end;
+    }
+    Option::None
+}
+
+/// Returns the value of given attribute of given HTML tag.
+fn attribute_of(tag: &str, attribute: &str) -> Option<String> {
+    let lowercase = tag.to_ascii_lowercase();
+    let mut offset = 0;
+    while let Option::Some(start) = lowercase[offset..].find(attribute) {
+        let start = offset + start;
+        offset = start + attribute.len();
+        // The attribute name must be a whole word followed by `=`:
+        let preceded_by_space = tag[..start].ends_with(char::is_whitespace);
+        let rest = tag[offset..].trim_start();
+        let value = match rest.strip_prefix('=') {
+            Option::Some(value) if preceded_by_space => value.trim_start(),
+            _ => continue,
+        };
+        let value = match value.chars().next() {
+            Option::Some(quote @ ('"' | '\'')) => value[1..].split(quote).next().unwrap(),
+            _ => value.split(|c: char| c.is_whitespace() || c == '/').next().unwrap(),
+        };
+        return Option::Some(value.to_string());
+    }
+    Option::None
+}
+
+/// Decodes HTML entities in given text, collapses whitespace, removes characters
+/// which are not allowed in messages and truncates the text to given number of
+/// characters.
+///
+/// # Return value
+///
+/// The cleaned text, or [`Option::None`] if nothing is left.
+fn clean(text: &str, max_chars: usize) -> Option<String> {
+    let text = sanitize::text(&decode_entities(text));
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut chars = text.chars();
+    let mut truncated: String = chars.by_ref().take(max_chars).collect();
+    if chars.next().is_some() {
+        truncated.push('…');
+    }
+    match truncated.is_empty() {
+        true => Option::None,
+        false => Option::Some(truncated),
+    }
+}
+
+/// Decodes named HTML entities commonly used in titles and descriptions, and
+/// numeric character references. Unknown entities are kept as they are.
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Option::Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..].find(';')
+            .filter(|end| *end <= 10)
+            .map(|end| &rest[1..end + 1]);
+        let character = entity.and_then(|entity| match entity {
+            "amp" => Option::Some('&'),
+            "lt" => Option::Some('<'),
+            "gt" => Option::Some('>'),
+            "quot" => Option::Some('"'),
+            "apos" => Option::Some('\''),
+            "nbsp" => Option::Some(' '),
+            _ => entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X"))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32),
+        });
+        match (entity, character) {
+            (Option::Some(entity), Option::Some(character)) => {
+                decoded.push(character);
+                rest = &rest[entity.len() + 2..];
+            },
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            },
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
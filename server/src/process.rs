@@ -0,0 +1,51 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! A module for running external commands configured by the administrator, such
+//! as the authentication command or the command sending verification codes.
+
+use std::io;
+use std::process::Child;
+use std::process::ExitStatus;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+/// How often a running command is checked for having exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Waits for given command to exit. If it does not exit within `timeout`, it is
+/// killed.
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] with the exit status of the command
+///  -  [`Result::Err`] with [`io::ErrorKind::TimedOut`] if the command has been
+///     killed, or with another error if its state could not be checked
+pub fn wait_with_timeout(child: &mut Child, timeout: Duration) -> io::Result<ExitStatus> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Option::Some(status) = child.try_wait()? {
+            return Result::Ok(status)
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Result::Err(io::Error::new(io::ErrorKind::TimedOut, "Command has not exited in time"))
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
@@ -0,0 +1,43 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Timelike;
+use chrono::Utc;
+
+use crate::global_config;
+
+use std::num::NonZeroU32;
+
+/// Number of seconds in a day.
+const DAY_SECS: i64 = 24 * 60 * 60;
+
+/// Returns the date and time when the current daily quota period started and
+/// when it ends, as given by global configuration.
+pub fn daily_period(now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+    let daily_reset = global_config().limits().read().unwrap().get_daily_reset().as_secs() as i64;
+    let elapsed = (now.num_seconds_from_midnight() as i64 - daily_reset).rem_euclid(DAY_SECS);
+    let start = (now - Duration::seconds(elapsed)).with_nanosecond(0).unwrap();
+    (start, start + Duration::seconds(DAY_SECS))
+}
+
+/// Returns the number of used messages of a quota at which the user should be
+/// warned that the quota is nearly used up, that is 80 % of the quota.
+pub fn warning_threshold(limit: NonZeroU32) -> u32 {
+    ((limit.get() as u64 * 4).div_ceil(5)) as u32
+}
@@ -0,0 +1,104 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::global_config;
+use crate::moderation;
+
+use mdchat_common::moderation::ModEventKind;
+
+use once_cell::sync::Lazy;
+
+use tracing::warn;
+
+use std::collections::BTreeSet;
+use std::collections::VecDeque;
+use std::net::IpAddr;
+use std::num::NonZeroU32;
+use std::sync::RwLock;
+use std::time::Instant;
+
+/// What a client did, as watched by raid detection.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Activity {
+    Connection,
+    Registration,
+}
+
+/// Recent connections and registrations, from the oldest to the newest.
+static RECENT: Lazy<RwLock<VecDeque<(Instant, IpAddr, Activity)>>> = Lazy::new(|| RwLock::new(VecDeque::new()));
+
+/// When the countermeasures against the last detected raid end.
+static RAID_UNTIL: Lazy<RwLock<Option<Instant>>> = Lazy::new(|| RwLock::new(Option::None));
+
+/// Records a new connection from given IP address and checks whether it is
+/// a part of a raid.
+pub fn record_connection(ip_addr: IpAddr) {
+    record(ip_addr, Activity::Connection);
+}
+
+/// Records a registration from given IP address and checks whether it is a part
+/// of a raid.
+pub fn record_registration(ip_addr: IpAddr) {
+    record(ip_addr, Activity::Registration);
+}
+
+/// Returns whether the countermeasures against a raid are active.
+pub fn is_active() -> bool {
+    RAID_UNTIL.read().unwrap().is_some_and(|until| Instant::now() < until)
+}
+
+#[doc(hidden)]
+fn record(ip_addr: IpAddr, activity: Activity) {
+    let (window, max_connections, max_registrations, min_distinct_ips, duration) = {
+        let global_config = global_config();
+        let raid_config = global_config.raid().read().unwrap();
+        (
+            raid_config.get_window(),
+            raid_config.get_max_connections(),
+            raid_config.get_max_registrations(),
+            raid_config.get_min_distinct_ips(),
+            raid_config.get_duration(),
+        )
+    };
+    let now = Instant::now();
+    // Forget activity older than the window and count the rest:
+    let (connections, registrations, distinct_ips) = {
+        let mut recent = RECENT.write().unwrap();
+        recent.push_back((now, ip_addr, activity));
+        while recent.front().is_some_and(|(at, _, _)| now.duration_since(*at) > window) {
+            recent.pop_front();
+        }
+        let count = |activity| recent.iter().filter(|(_, _, other)| *other == activity).count() as u32;
+        let distinct_ips: BTreeSet<IpAddr> = recent.iter().map(|(_, ip_addr, _)| *ip_addr).collect();
+        (count(Activity::Connection), count(Activity::Registration), distinct_ips.len() as u32)
+    };
+    // Detect the raid:
+    let exceeds = |count: u32, max: Option<NonZeroU32>| max.is_some_and(|max| count > max.get());
+    if distinct_ips < min_distinct_ips.get()
+        || !(exceeds(connections, max_connections) || exceeds(registrations, max_registrations)) {
+        return
+    }
+    let was_active = is_active();
+    *RAID_UNTIL.write().unwrap() = Option::Some(now + duration);
+    if !was_active {
+        warn!(
+            "Raid detected: {} connections and {} registrations from {} IP addresses",
+            connections, registrations, distinct_ips
+        );
+        moderation::publish(ModEventKind::RaidDetected { connections, registrations, distinct_ips });
+    }
+}
@@ -0,0 +1,234 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module for rate limits of actions given by global configuration. Every
+//! bucket has its own tokens for each key, such as an IP address or a nickname.
+//! An action takes a token and the bucket is gradually refilled, so short bursts
+//! are allowed while the average rate is limited.
+
+use crate::global_config;
+
+use mdchat_serverconf::BucketConfig;
+use mdchat_serverconf::RateLimitBucket;
+
+use once_cell::sync::Lazy;
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::num::NonZeroU32;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Number of remembered keys above which keys with full buckets are forgotten.
+const PRUNE_THRESHOLD: usize = 4096;
+
+/// State of a bucket of a single key.
+#[doc(hidden)]
+struct Tokens {
+    available: f64,
+    updated: Instant,
+    blocked_until: Option<Instant>,
+}
+
+impl Tokens {
+    /// Adds tokens for the time elapsed since the last update.
+    fn refill(&mut self, config: &BucketConfig, now: Instant) {
+        if let Option::Some((count, period)) = config.get_rate() {
+            let refilled = now.duration_since(self.updated).as_secs_f64() * count.get() as f64 / period.as_secs_f64();
+            self.available = (self.available + refilled).min(config.get_burst() as f64);
+        }
+        self.updated = now;
+    }
+}
+
+static TOKENS: Lazy<Mutex<HashMap<(RateLimitBucket, String), Tokens>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Takes a token from given bucket of given key.
+///
+/// # Parameters
+///
+///  -  `bucket`: the bucket of the action
+///  -  `key`: who does the action, such as a nickname
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] if the action is allowed
+///  -  [`Result::Err`] containing the time after which the action is allowed
+///     again, if it is not allowed now
+pub fn take(bucket: RateLimitBucket, key: &str) -> Result<(), Duration> {
+    let config = bucket_config(bucket);
+    let (count, period) = match config.get_rate() {
+        Option::Some(rate) => rate,
+        Option::None => return Result::Ok(()),
+    };
+    let now = Instant::now();
+    let mut tokens = TOKENS.lock().unwrap();
+    if tokens.len() > PRUNE_THRESHOLD {
+        prune(&mut tokens, now);
+    }
+    let key_tokens = tokens.entry((bucket, key.to_string())).or_insert_with(|| Tokens {
+        available: config.get_burst() as f64,
+        updated: now,
+        blocked_until: Option::None,
+    });
+    if let Option::Some(blocked_until) = key_tokens.blocked_until.filter(|blocked_until| *blocked_until > now) {
+        return Result::Err(blocked_until - now)
+    }
+    key_tokens.blocked_until = Option::None;
+    key_tokens.refill(&config, now);
+    if key_tokens.available >= 1.0 {
+        key_tokens.available -= 1.0;
+        return Result::Ok(())
+    }
+    // The bucket has run out of tokens:
+    let penalty = config.get_penalty();
+    if !penalty.is_zero() {
+        key_tokens.blocked_until = Option::Some(now + penalty);
+        return Result::Err(penalty)
+    }
+    let missing = 1.0 - key_tokens.available;
+    Result::Err(Duration::from_secs_f64(missing * period.as_secs_f64() / count.get() as f64))
+}
+
+/// Takes a token from given bucket of given IP address, see [`take`]. Loopback
+/// addresses are not limited, since the WebSocket gateway connects from them.
+/// Connections bridged by the gateway are limited by the addresses of browsers
+/// instead, see [`Client::remote_addr`](crate::client::Client::remote_addr).
+pub fn take_ip(bucket: RateLimitBucket, ip_addr: IpAddr) -> Result<(), Duration> {
+    match ip_addr.is_loopback() {
+        true => Result::Ok(()),
+        false => take(bucket, &ip_addr.to_string()),
+    }
+}
+
+/// Takes a token from the bucket of logins of given user from given IP address,
+/// see [`take`]. Logins are limited per IP address and nickname together, so
+/// attempts to guess a password of an account from one address do not lock the
+/// owner of the account out.
+pub fn take_login(ip_addr: IpAddr, nickname: &str) -> Result<(), Duration> {
+    take(RateLimitBucket::Logins, &format!("{} {}", ip_addr, nickname))
+}
+
+/// Forgets keys whose buckets are full and not blocked, since a new key starts
+/// with a full bucket anyway.
+#[doc(hidden)]
+fn prune(tokens: &mut HashMap<(RateLimitBucket, String), Tokens>, now: Instant) {
+    tokens.retain(|(bucket, _), key_tokens| {
+        let config = bucket_config(*bucket);
+        key_tokens.refill(&config, now);
+        key_tokens.blocked_until.is_some_and(|blocked_until| blocked_until > now)
+            || key_tokens.available < config.get_burst() as f64
+    });
+}
+
+/// Returns limits of given bucket given by global configuration. Searches are
+/// limited to one per `history search-interval`, unless their rate is configured.
+#[doc(hidden)]
+fn bucket_config(bucket: RateLimitBucket) -> BucketConfig {
+    let global_config = global_config();
+    let mut config = global_config.rate_limits().read().unwrap().get_bucket(bucket);
+    if bucket == RateLimitBucket::Searches && config.get_rate().is_none() {
+        let search_interval = global_config.history().read().unwrap().get_search_interval();
+        if !search_interval.is_zero() {
+            config.set_rate(Option::Some((NonZeroU32::MIN, search_interval))).unwrap();
+        }
+    }
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::take;
+    use super::take_ip;
+    use super::take_login;
+
+    use crate::GLOBAL_CONFIG;
+
+    use mdchat_serverconf::Config;
+    use mdchat_serverconf::RateLimitBucket;
+
+    use std::net::IpAddr;
+    use std::net::Ipv4Addr;
+    use std::num::NonZeroU32;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// Limits given bucket to one action per hour with given burst and penalty.
+    /// Each test uses its own bucket, since the configuration is global.
+    fn limit(bucket: RateLimitBucket, burst: u32, penalty: Duration) {
+        let global_config = GLOBAL_CONFIG.get_or_init(|| Arc::new(Config::new()));
+        let mut rate_limits = global_config.rate_limits().write().unwrap();
+        let config = rate_limits.get_bucket_mut(bucket);
+        config.set_rate(Option::Some((NonZeroU32::MIN, Duration::from_secs(3600)))).unwrap();
+        config.set_burst(NonZeroU32::new(burst));
+        config.set_penalty(penalty);
+    }
+
+    #[test]
+    fn burst_is_allowed_then_actions_are_rejected() {
+        limit(RateLimitBucket::Messages, 3, Duration::ZERO);
+        for _ in 0..3 {
+            assert!(take(RateLimitBucket::Messages, "burst").is_ok());
+        }
+        let retry_after = take(RateLimitBucket::Messages, "burst").unwrap_err();
+        assert!(retry_after > Duration::from_secs(3500) && retry_after <= Duration::from_secs(3600), "{:?}", retry_after);
+        // Other keys have their own tokens:
+        assert!(take(RateLimitBucket::Messages, "burst-other").is_ok());
+    }
+
+    #[test]
+    fn penalty_is_waited_out() {
+        limit(RateLimitBucket::Searches, 1, Duration::from_secs(900));
+        assert!(take(RateLimitBucket::Searches, "penalty").is_ok());
+        assert_eq!(take(RateLimitBucket::Searches, "penalty"), Result::Err(Duration::from_secs(900)));
+        assert!(take(RateLimitBucket::Searches, "penalty").unwrap_err() <= Duration::from_secs(900));
+    }
+
+    #[test]
+    fn unlimited_bucket_allows_everything() {
+        GLOBAL_CONFIG.get_or_init(|| Arc::new(Config::new()));
+        for _ in 0..1000 {
+            assert!(take(RateLimitBucket::DirectMessages, "unlimited").is_ok());
+        }
+    }
+
+    #[test]
+    fn logins_from_other_addresses_are_not_locked_out() {
+        limit(RateLimitBucket::Logins, 2, Duration::from_secs(900));
+        let attacker = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7));
+        let owner = IpAddr::V4(Ipv4Addr::new(198, 51, 100, 1));
+        assert!(take_login(attacker, "victim").is_ok());
+        assert!(take_login(attacker, "victim").is_ok());
+        assert!(take_login(attacker, "victim").is_err());
+        assert!(take_login(attacker, "victim").is_err());
+        assert!(take_login(owner, "victim").is_ok());
+        // Guessing passwords of other accounts is limited separately too:
+        assert!(take_login(attacker, "someone-else").is_ok());
+    }
+
+    #[test]
+    fn loopback_addresses_are_not_limited() {
+        limit(RateLimitBucket::Registrations, 1, Duration::ZERO);
+        let remote = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        assert!(take_ip(RateLimitBucket::Registrations, remote).is_ok());
+        assert!(take_ip(RateLimitBucket::Registrations, remote).is_err());
+        for _ in 0..10 {
+            assert!(take_ip(RateLimitBucket::Registrations, IpAddr::V4(Ipv4Addr::LOCALHOST)).is_ok());
+        }
+    }
+}
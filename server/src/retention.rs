@@ -0,0 +1,68 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use chrono::Duration;
+use chrono::Utc;
+
+use crate::global_config;
+use crate::message_list;
+
+use tracing::info;
+
+use std::thread;
+
+/// Function containing a loop for periodic removal of old messages from the
+/// message history as given by global configuration. This function should be
+/// called in a separate thread.
+pub fn prune_periodically() {
+    loop {
+        let (retention, check_interval, dry_run) = {
+            let global_config = global_config();
+            let history_config = global_config.history().read().unwrap();
+            (
+                history_config.get_retention(),
+                history_config.get_retention_check_interval(),
+                history_config.is_retention_dry_run(),
+            )
+        };
+        if let Option::Some(retention) = retention {
+            prune(retention, dry_run);
+        }
+        thread::sleep(check_interval);
+    }
+}
+
+#[doc(hidden)]
+fn prune(retention: std::time::Duration, dry_run: bool) {
+    let retention = match Duration::from_std(retention) {
+        Result::Ok(retention) => retention,
+        Result::Err(_) => return,
+    };
+    let cutoff = match Utc::now().checked_sub_signed(retention) {
+        Option::Some(cutoff) => cutoff,
+        Option::None => return,
+    };
+    if dry_run {
+        let count = message_list::count_older_than(cutoff);
+        info!("Retention dry run: {} messages would be removed", count);
+    } else {
+        let count = message_list::remove_older_than(cutoff);
+        if count > 0 {
+            info!("Removed {} messages older than the retention period", count);
+        }
+    }
+}
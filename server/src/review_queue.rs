@@ -0,0 +1,79 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use chrono::Utc;
+
+use mdchat_common::message::MessageKind;
+use mdchat_common::moderation::QuarantinedMessage;
+
+use once_cell::sync::Lazy;
+
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+use std::time::Duration;
+
+/// A message held in the review queue together with the channel it is sent to,
+/// its kind and its requested time to live.
+pub struct Held {
+    pub message: QuarantinedMessage,
+    pub channel: Option<String>,
+    pub kind: MessageKind,
+    pub ttl: Option<Duration>,
+}
+
+/// Messages held in the review queue by their IDs, and the ID of the next held
+/// message.
+static REVIEW_QUEUE: Lazy<RwLock<(BTreeMap<u64, Held>, u64)>> = Lazy::new(|| RwLock::new((BTreeMap::new(), 0)));
+
+/// Holds a message in the review queue until a moderator approves or rejects it.
+///
+/// # Parameters
+///
+///  -  `sender`: nickname of the user who sent the message
+///  -  `channel`: channel the message is sent to, or [`Option::None`] for the
+///     main chat
+///  -  `kind`: what kind of a message it is
+///  -  `text`: text of the message
+///  -  `ttl`: time after which the message should expire once it is delivered
+///  -  `pattern`: the quarantine pattern the message matches
+///
+/// # Return value
+///
+/// The held message including the ID assigned to it.
+pub fn hold(sender: String, channel: Option<String>, kind: MessageKind, text: String, ttl: Option<Duration>, pattern: String) -> QuarantinedMessage {
+    let mut review_queue = REVIEW_QUEUE.write().unwrap();
+    let (messages, next_id) = &mut *review_queue;
+    let message = QuarantinedMessage::new(*next_id, sender, Utc::now(), text, pattern);
+    messages.insert(*next_id, Held { message: message.clone(), channel, kind, ttl });
+    *next_id += 1;
+    message
+}
+
+/// Returns all messages held in the review queue, from the oldest to the newest.
+pub fn list() -> Vec<QuarantinedMessage> {
+    REVIEW_QUEUE.read().unwrap().0.values().map(|held| held.message.clone()).collect()
+}
+
+/// Removes a message from the review queue.
+///
+/// # Return value
+///
+///  -  [`Option::Some`] with the removed message
+///  -  [`Option::None`] if there is no message with given ID in the queue
+pub fn take(id: u64) -> Option<Held> {
+    REVIEW_QUEUE.write().unwrap().0.remove(&id)
+}
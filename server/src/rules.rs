@@ -0,0 +1,112 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module for filter rules added by moderators at runtime. A rule is a line of
+//! the configuration file for the IP address, message or nickname filter. Rules
+//! can be appended to the rules file given by global configuration, which is
+//! processed after the configuration file on startup.
+
+use crate::global_config;
+
+use mdchat_common::moderation::FilterKind;
+
+use mdchat_serverconf::Config;
+
+use std::fs;
+use std::io;
+use std::sync::Mutex;
+
+/// Lock held while the rules file is written, so concurrently added rules do not
+/// overwrite each other.
+static WRITING: Mutex<()> = Mutex::new(());
+
+/// Adds a rule to given filter of global configuration.
+///
+/// # Parameters
+///
+///  -  `filter`: the filter the rule belongs to
+///  -  `rule`: the rule as written in the configuration file without the filter
+///     name, for example `ban (?i)spam`
+///  -  `persist`: whether the rule should be appended to the rules file
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] containing the rule as written in the configuration file
+///  -  [`Result::Err`] if the rule is not valid or could not be saved
+pub fn add(filter: FilterKind, rule: &str, persist: bool) -> Result<String, String> {
+    if rule.contains('\n') {
+        return Result::Err("A rule must not contain a newline".to_string())
+    }
+    let sub_command = rule.split_whitespace().next().unwrap_or_default();
+    if !is_rule(filter, sub_command) {
+        return Result::Err(format!("`{} {}` is not a filter rule", filter, sub_command))
+    }
+    let line = format!("{} {}", filter, rule.trim());
+    // Check the rule on its own before anything is changed:
+    Config::default().process_line(&line)?;
+    if persist {
+        append(&line)?;
+    }
+    global_config().process_line(&line)?;
+    Result::Ok(line)
+}
+
+/// Returns whether given sub-command of a filter adds a rule. Other sub-commands,
+/// such as length limits, cannot be changed at runtime.
+#[doc(hidden)]
+fn is_rule(filter: FilterKind, sub_command: &str) -> bool {
+    matches!((filter, sub_command),
+        (FilterKind::Ip, "allow" | "ban" | "ban-range")
+        | (FilterKind::Message, "ban" | "ban-trial" | "quarantine")
+        | (FilterKind::Nickname, "allow" | "ban"))
+}
+
+/// Appends given line to the rules file. If the rules file cannot be processed
+/// afterwards, for example because it has been broken by hand, its original
+/// content is restored.
+#[doc(hidden)]
+fn append(line: &str) -> Result<(), String> {
+    let _writing = WRITING.lock().unwrap();
+    let file = global_config().state().read().unwrap().get_rules_file().clone();
+    let original = match fs::read_to_string(&file) {
+        Result::Ok(content) => Option::Some(content),
+        Result::Err(err) if err.kind() == io::ErrorKind::NotFound => Option::None,
+        Result::Err(err) => return Result::Err(format!("Could not read the rules file: {}", err)),
+    };
+    let mut content = original.clone().unwrap_or_default();
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(line);
+    content.push('\n');
+    let temp_file = file.with_extension("tmp");
+    fs::write(&temp_file, content)
+        .and_then(|()| fs::rename(&temp_file, &file))
+        .map_err(|err| format!("Could not write the rules file: {}", err))?;
+    // Verify the whole file, since it is processed on startup:
+    if let Result::Err(err) = Config::default().process_file(file.display().to_string(), false) {
+        let restored = match original {
+            Option::Some(original) => fs::write(&file, original),
+            Option::None => fs::remove_file(&file),
+        };
+        return match restored {
+            Result::Ok(()) => Result::Err(format!("The rules file would not be valid, so it has been left unchanged: {}", err)),
+            Result::Err(restore_err) => Result::Err(format!("The rules file is not valid ({}) and could not be restored: {}", err, restore_err)),
+        }
+    }
+    Result::Ok(())
+}
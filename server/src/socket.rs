@@ -0,0 +1,126 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! A module binding the sockets of endpoints served over plain TCP, that is the
+//! WebSocket gateway, the webhook endpoint and the health endpoint. Unlike
+//! sockets given by `listen`, which are bound by the MDSWP transport, these
+//! sockets are configured by [`SocketOptions`] before they are bound.
+
+use mdchat_serverconf::SocketOptions;
+
+use std::io;
+use std::net::SocketAddr;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::time::Duration;
+
+use socket2::Domain;
+use socket2::Protocol;
+use socket2::SockRef;
+use socket2::Socket;
+use socket2::TcpKeepalive;
+use socket2::Type;
+
+/// Maximum number of connections waiting to be accepted.
+const BACKLOG: i32 = 128;
+
+/// Binds a listener to given socket address. Options which are not supported on
+/// the platform the server runs on make the binding fail rather than being
+/// ignored, except `SO_REUSEADDR`, which is never set on Windows.
+///
+/// # Parameters
+///
+///  -  `sock_addr`: the socket address to bind to
+///  -  `options`: options to set before the socket is bound
+///
+/// # Return value
+///
+/// The bound listener or an I/O error if the socket could not be created,
+/// configured or bound.
+pub fn bind(sock_addr: SocketAddr, options: &SocketOptions) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(sock_addr), Type::STREAM, Option::Some(Protocol::TCP))?;
+    // On Windows the option would let other processes bind the same address:
+    #[cfg(not(windows))]
+    socket.set_reuse_address(options.get_reuse_address())?;
+    if options.get_reuse_port() {
+        set_reuse_port(&socket)?;
+    }
+    if let Option::Some(device) = options.get_bind_device() {
+        bind_device(&socket, device)?;
+    }
+    socket.bind(&sock_addr.into())?;
+    socket.listen(BACKLOG)?;
+    Result::Ok(socket.into())
+}
+
+/// Enables TCP keepalive on a long-lived connection, so connections of peers
+/// which disappeared without closing them are detected and closed.
+///
+/// # Parameters
+///
+///  -  `stream`: the accepted connection
+///  -  `time`: time the connection may be idle before keepalive probes are
+///     sent, or [`Option::None`] to leave TCP keepalive disabled
+pub fn keep_alive(stream: &TcpStream, time: Option<Duration>) -> io::Result<()> {
+    match time {
+        Option::None => Result::Ok(()),
+        Option::Some(time) => SockRef::from(stream).set_tcp_keepalive(&TcpKeepalive::new().with_time(time)),
+    }
+}
+
+#[doc(hidden)]
+#[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos", target_os = "cygwin"))))]
+fn set_reuse_port(socket: &Socket) -> io::Result<()> {
+    socket.set_reuse_port(true)
+}
+
+#[doc(hidden)]
+#[cfg(not(all(unix, not(any(target_os = "solaris", target_os = "illumos", target_os = "cygwin")))))]
+fn set_reuse_port(_socket: &Socket) -> io::Result<()> {
+    Result::Err(io::Error::new(io::ErrorKind::Unsupported, "SO_REUSEPORT is not supported on this platform"))
+}
+
+#[doc(hidden)]
+#[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+fn bind_device(socket: &Socket, device: &str) -> io::Result<()> {
+    socket.bind_device(Option::Some(device.as_bytes()))
+}
+
+#[doc(hidden)]
+#[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+fn bind_device(_socket: &Socket, _device: &str) -> io::Result<()> {
+    Result::Err(io::Error::new(io::ErrorKind::Unsupported, "SO_BINDTODEVICE is not supported on this platform"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::bind;
+
+    use mdchat_serverconf::SocketOptions;
+
+    use std::net::SocketAddr;
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn reuse_port_lets_instances_share_address() {
+        let mut options = SocketOptions::new();
+        options.set_reuse_port(true);
+        let first = bind("127.0.0.1:0".parse().unwrap(), &options).unwrap();
+        let sock_addr: SocketAddr = first.local_addr().unwrap();
+        assert!(bind(sock_addr, &options).is_ok());
+        assert!(bind(sock_addr, &SocketOptions::new()).is_err());
+    }
+}
@@ -0,0 +1,90 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! A module for the state file given by global configuration, into which state
+//! acquired at runtime is saved, so moderation actions survive restarts of the
+//! server. The state file is a JSON object in the following form:
+//!
+//! ```json
+//! {
+//!     "lockdown": false,
+//!     "topics": {
+//!         "general": "Anything goes"
+//!     }
+//! }
+//! ```
+
+use crate::channel_list;
+use crate::global_config;
+use crate::lockdown;
+
+use serde_json::json;
+use serde_json::Value;
+
+use std::fs;
+use std::io;
+use std::sync::Mutex;
+
+/// Lock held while the state file is written, so concurrent changes do not
+/// overwrite each other.
+static SAVING: Mutex<()> = Mutex::new(());
+
+/// Loads the state saved by [`save`]. Channels must be created before, since
+/// topics of channels which do not exist are ignored. If the state file does not
+/// exist, nothing happens.
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] if the state was loaded or there is no state file
+///  -  [`Result::Err`] if the state file could not be read or parsed
+pub fn load() -> io::Result<()> {
+    let file = global_config().state().read().unwrap().get_file().clone();
+    let content = match fs::read(&file) {
+        Result::Ok(content) => content,
+        Result::Err(err) if err.kind() == io::ErrorKind::NotFound => return Result::Ok(()),
+        Result::Err(err) => return Result::Err(err),
+    };
+    let state: Value = serde_json::from_slice(&content)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    lockdown::restore(state["lockdown"].as_bool().unwrap_or(false));
+    if let Option::Some(topics) = state["topics"].as_object() {
+        for (channel, topic) in topics {
+            if let Option::Some(topic) = topic.as_str() {
+                channel_list::restore_topic(channel, topic.to_string());
+            }
+        }
+    }
+    Result::Ok(())
+}
+
+/// Saves the current state into the state file given by global configuration.
+/// The file is replaced at once, so it is never left half-written.
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] if the state was saved
+///  -  [`Result::Err`] if the state file could not be written
+pub fn save() -> io::Result<()> {
+    let _saving = SAVING.lock().unwrap();
+    let file = global_config().state().read().unwrap().get_file().clone();
+    let state = json!({
+        "lockdown": lockdown::is_active(),
+        "topics": channel_list::topics(),
+    });
+    let temp_file = file.with_extension("tmp");
+    fs::write(&temp_file, state.to_string())?;
+    fs::rename(temp_file, file)
+}
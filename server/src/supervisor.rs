@@ -0,0 +1,257 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! A module reporting panics of threads of the server through the server log and
+//! recovering from them as given by global configuration, so threads do not die
+//! silently while the rest of the server keeps running.
+//!
+//! Threads the server cannot run without, such as the message handler and the
+//! listeners, are watched by a watchdog, see [`watch`]. When one of them stops or
+//! stops reporting its heartbeat, the watchdog restarts it or quits the server,
+//! so the server never runs in a half-dead state.
+
+use chrono::Utc;
+
+use crate::global_config;
+
+use mdchat_serverconf::PanicStrategy;
+
+use once_cell::sync::Lazy;
+use once_cell::sync::OnceCell;
+
+use tracing::error;
+use tracing::warn;
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::process;
+use std::sync::atomic::AtomicI64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// What the server does when one of its threads panics. It is read once when the
+/// panic hook is installed, since the panicking thread may hold a lock of the
+/// global configuration.
+static STRATEGY: OnceCell<PanicStrategy> = OnceCell::new();
+
+/// How long a subsystem which has panicked waits before it is started again, so a
+/// subsystem panicking right after the start does not occupy the processor.
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+/// How often the watchdog checks critical threads.
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Critical threads watched by the watchdog by their names.
+static CRITICAL: Lazy<Mutex<HashMap<String, Critical>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+thread_local! {
+    /// Time of the last heartbeat of the current thread in milliseconds since
+    /// the Unix epoch, if the thread is critical.
+    static HEARTBEAT: RefCell<Option<Arc<AtomicI64>>> = const { RefCell::new(Option::None) };
+}
+
+/// A thread the server cannot run without.
+#[doc(hidden)]
+struct Critical {
+    subsystem: Arc<dyn Fn() + Send + Sync>,
+    handle: JoinHandle<()>,
+    heartbeat: Arc<AtomicI64>,
+    max_silence: Option<Duration>,
+}
+
+/// Replaces the default panic hook, which only prints the panic to the standard
+/// error output, by one logging the panic together with the name of the thread.
+/// The panic is logged within the spans the thread has entered, so it carries the
+/// context of the connection the thread serves. If the server is configured to
+/// abort on panic, the process is aborted afterwards. This function should be
+/// called right after the server log is set up.
+pub fn install_panic_hook() {
+    let strategy = *STRATEGY.get_or_init(|| global_config().server().read().unwrap().get_on_panic());
+    panic::set_hook(Box::new(move |info| {
+        let thread = thread::current();
+        let thread_name = thread.name().unwrap_or("unnamed");
+        let location = info.location()
+            .map(|location| location.to_string())
+            .unwrap_or_else(|| "unknown location".to_string());
+        error!(thread = thread_name, "Thread panicked at {}: {}", location, payload_text(info.payload()));
+        if strategy == PanicStrategy::Abort {
+            error!("Aborting the server due to a panic");
+            process::abort();
+        }
+    }));
+}
+
+/// Spawns a named thread running given subsystem. If the subsystem panics and the
+/// server is configured to restart subsystems, it is started again in the same
+/// thread after a short delay. Otherwise the thread ends.
+///
+/// # Parameters
+///
+///  -  `name`: name of the thread, which is logged together with its panics
+///  -  `subsystem`: the function running the subsystem
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] with the handle of the thread if it has been spawned
+///  -  [`Result::Err`] if the thread could not be spawned
+pub fn spawn<F>(name: String, subsystem: F) -> io::Result<JoinHandle<()>>
+where
+    F: Fn() + Send + 'static,
+{
+    thread::Builder::new().name(name.clone()).spawn(move || loop {
+        match panic::catch_unwind(AssertUnwindSafe(&subsystem)) {
+            Result::Ok(()) => break,
+            Result::Err(_) if strategy() == PanicStrategy::Restart => {
+                warn!("Restarting {} in {:?}", name, RESTART_DELAY);
+                thread::sleep(RESTART_DELAY);
+            },
+            Result::Err(_) => break,
+        }
+    })
+}
+
+/// Spawns a named thread running given work once, such as serving a connection,
+/// which cannot be started again. If the work panics, `recover` is called, for
+/// example to close the connection, regardless of the panic strategy.
+///
+/// # Parameters
+///
+///  -  `name`: name of the thread, which is logged together with its panics
+///  -  `work`: the function doing the work
+///  -  `recover`: the function called after `work` has panicked
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] with the handle of the thread if it has been spawned
+///  -  [`Result::Err`] if the thread could not be spawned
+pub fn spawn_once<F, R>(name: String, work: F, recover: R) -> io::Result<JoinHandle<()>>
+where
+    F: FnOnce() + Send + 'static,
+    R: FnOnce() + Send + 'static,
+{
+    thread::Builder::new().name(name).spawn(move || {
+        if panic::catch_unwind(AssertUnwindSafe(work)).is_err() {
+            recover();
+        }
+    })
+}
+
+/// Spawns a named thread running given subsystem like [`spawn`] and lets the
+/// watchdog watch it, see [`watch`]. The subsystem should call [`heartbeat`]
+/// regularly if `max_silence` is given.
+///
+/// # Parameters
+///
+///  -  `name`: name of the thread, which is unique among critical threads
+///  -  `subsystem`: the function running the subsystem
+///  -  `max_silence`: how long the subsystem may not report its heartbeat before
+///     it is considered hung, or [`Option::None`] if it may wait for something
+///     for arbitrarily long, such as a listener waiting for a connection
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] if the thread has been spawned
+///  -  [`Result::Err`] if the thread could not be spawned
+pub fn spawn_critical<F>(name: String, subsystem: F, max_silence: Option<Duration>) -> io::Result<()>
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    spawn_critical_arc(name, Arc::new(subsystem), max_silence)
+}
+
+/// Stops watching the critical thread of given name, which is going to end on
+/// purpose, such as a listener which is being closed.
+pub fn unwatch(name: &str) {
+    CRITICAL.lock().unwrap().remove(name);
+}
+
+/// Reports that the current thread is alive. Does nothing if the thread is not
+/// critical.
+pub fn heartbeat() {
+    HEARTBEAT.with(|heartbeat| if let Option::Some(heartbeat) = &*heartbeat.borrow() {
+        heartbeat.store(Utc::now().timestamp_millis(), Ordering::Relaxed);
+    });
+}
+
+/// Watches critical threads forever. A thread which has ended is started again
+/// if the server is configured to restart subsystems, otherwise the server quits.
+/// The server also quits if a thread has not reported its heartbeat for too long,
+/// since a hung thread cannot be replaced safely. This function should be called
+/// by the main thread after all critical threads are spawned.
+pub fn watch() -> ! {
+    loop {
+        thread::sleep(WATCHDOG_INTERVAL);
+        let now = Utc::now().timestamp_millis();
+        let mut stopped = Vec::new();
+        for (name, critical) in CRITICAL.lock().unwrap().iter() {
+            let silence = Duration::from_millis(now.saturating_sub(critical.heartbeat.load(Ordering::Relaxed)).max(0) as u64);
+            if critical.handle.is_finished() {
+                stopped.push((name.clone(), Arc::clone(&critical.subsystem), critical.max_silence));
+            } else if critical.max_silence.is_some_and(|max_silence| silence > max_silence) {
+                error!("Thread `{}` has not reported its heartbeat for {:?}. Quitting.", name, silence);
+                process::exit(1);
+            }
+        }
+        for (name, subsystem, max_silence) in stopped {
+            if strategy() != PanicStrategy::Restart {
+                error!("Thread `{}` has stopped. Quitting.", name);
+                process::exit(1);
+            }
+            warn!("Thread `{}` has stopped, restarting it", name);
+            if let Result::Err(err) = spawn_critical_arc(name.clone(), subsystem, max_silence) {
+                error!("Could not restart thread `{}`: {}. Quitting.", name, err);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+#[doc(hidden)]
+fn spawn_critical_arc(name: String, subsystem: Arc<dyn Fn() + Send + Sync>, max_silence: Option<Duration>) -> io::Result<()> {
+    let heartbeat = Arc::new(AtomicI64::new(Utc::now().timestamp_millis()));
+    let handle = spawn(name.clone(), cls_clone!(subsystem, heartbeat -> move || {
+        HEARTBEAT.with(|current| *current.borrow_mut() = Option::Some(Arc::clone(&heartbeat)));
+        subsystem()
+    }))?;
+    CRITICAL.lock().unwrap().insert(name, Critical { subsystem, handle, heartbeat, max_silence });
+    Result::Ok(())
+}
+
+/// Returns the configured panic strategy, [`PanicStrategy::Log`] if the panic
+/// hook has not been installed yet.
+#[doc(hidden)]
+fn strategy() -> PanicStrategy {
+    STRATEGY.get().copied().unwrap_or(PanicStrategy::Log)
+}
+
+/// Returns the text of given payload of a panic, which is usually a string
+/// given to [`panic!`].
+#[doc(hidden)]
+fn payload_text(payload: &(dyn Any + Send)) -> &str {
+    match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+        (Option::Some(text), _) => text,
+        (_, Option::Some(text)) => text,
+        _ => "unknown cause",
+    }
+}
@@ -0,0 +1,302 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! A module setting up the server log and tracing spans as given by global
+//! configuration. Besides printing them, log records are forwarded to
+//! administrators subscribed to the server log, and spans can be exported using
+//! OpenTelemetry protocol (OTLP) over HTTP.
+
+use chrono::Utc;
+
+use crate::client_list;
+use crate::global_config;
+
+use mdchat_common::log::LogLevel;
+use mdchat_common::log::LogRecord;
+
+use mdchat_serverconf::TracingSubscriberKind;
+
+use serde_json::json;
+
+use tracing::field::Field;
+use tracing::field::Visit;
+use tracing::span;
+use tracing::warn;
+use tracing::Event;
+use tracing::Level;
+use tracing::Subscriber;
+
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+use std::cell::Cell;
+use std::fmt::Debug;
+use std::io;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+/// How often finished spans are exported.
+const OTLP_EXPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Maximum number of finished spans waiting for export. Spans finished while the
+/// buffer is full are dropped, so an unreachable collector cannot exhaust memory.
+const OTLP_MAX_BUFFERED_SPANS: usize = 10_000;
+
+/// Sets up the subscriber of the `tracing` crate as given by global configuration.
+/// Records and spans emitted before calling this function are lost, so it should
+/// be called right after the configuration is loaded.
+pub fn init() {
+    let (subscriber, level) = {
+        let global_config = global_config();
+        let tracing_config = global_config.tracing().read().unwrap();
+        (tracing_config.get_subscriber().clone(), tracing_config.get_level())
+    };
+    let level_filter = match level {
+        LogLevel::Debug => LevelFilter::DEBUG,
+        LogLevel::Info => LevelFilter::INFO,
+        LogLevel::Warning => LevelFilter::WARN,
+        LogLevel::Error | LogLevel::Fatal => LevelFilter::ERROR,
+    };
+    // Durations of spans are printed only when debugging, so the log is not
+    // flooded by them:
+    let span_events = match level {
+        LogLevel::Debug => FmtSpan::CLOSE,
+        _ => FmtSpan::NONE,
+    };
+    let fmt_layer = match subscriber {
+        TracingSubscriberKind::Json => fmt::layer().json().with_span_events(span_events).boxed(),
+        _ => fmt::layer().with_span_events(span_events).boxed(),
+    };
+    let otlp_layer = match subscriber {
+        TracingSubscriberKind::Otlp(url) => Option::Some(OtlpLayer::new(url)),
+        _ => Option::None,
+    };
+    tracing_subscriber::registry()
+        .with(fmt_layer)
+        .with(AdminLogLayer)
+        .with(otlp_layer)
+        .with(level_filter)
+        .init();
+}
+
+thread_local! {
+    /// Whether the current thread is forwarding a log record to administrators.
+    static FORWARDING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// A layer forwarding log records to all administrators who are subscribed to
+/// the server log. Records logged while a record is being forwarded, for example
+/// because a client has been disconnected, are not forwarded, so forwarding
+/// never recurses while the list of clients is locked.
+#[doc(hidden)]
+struct AdminLogLayer;
+
+impl<S: Subscriber> Layer<S> for AdminLogLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if FORWARDING.with(|forwarding| forwarding.replace(true)) {
+            return
+        }
+        let level = match *event.metadata().level() {
+            Level::ERROR => LogLevel::Error,
+            Level::WARN => LogLevel::Warning,
+            Level::INFO => LogLevel::Info,
+            _ => LogLevel::Debug,
+        };
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let record = LogRecord::new(level, Utc::now(), fields.to_text());
+        client_list::for_each(|_, client| client.send_log_record(&record));
+        FORWARDING.with(|forwarding| forwarding.set(false));
+    }
+}
+
+/// Fields of a log record or of a span, with the message stored separately.
+#[doc(hidden)]
+#[derive(Default)]
+struct Fields {
+    message: String,
+    others: Vec<(String, String)>,
+}
+
+impl Fields {
+    /// Returns the message followed by the other fields in `name=value` form.
+    fn to_text(&self) -> String {
+        self.others.iter()
+            .fold(self.message.clone(), |text, (name, value)| format!("{} {}={}", text, name, value))
+            .trim_start()
+            .to_string()
+    }
+}
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.message = value.to_string(),
+            name => self.others.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        match field.name() {
+            "message" => self.message = format!("{:?}", value),
+            name => self.others.push((name.to_string(), format!("{:?}", value))),
+        }
+    }
+}
+
+/// Data of a span which has not finished yet, stored in the extensions of the
+/// span.
+#[doc(hidden)]
+struct OtlpSpan {
+    trace_id: u128,
+    span_id: u64,
+    parent_span_id: Option<u64>,
+    started_at: SystemTime,
+    fields: Fields,
+}
+
+/// A layer exporting finished spans using OTLP over HTTP with JSON encoding.
+/// Spans are buffered and exported periodically by a separate thread.
+#[doc(hidden)]
+struct OtlpLayer {
+    finished: Arc<Mutex<Vec<serde_json::Value>>>,
+}
+
+impl OtlpLayer {
+    /// Creates a new [`OtlpLayer`] and starts a thread exporting spans to given
+    /// URL.
+    fn new(url: String) -> Self {
+        let finished = Arc::new(Mutex::new(Vec::new()));
+        let buffer = Arc::clone(&finished);
+        thread::Builder::new()
+            .name("otlp".to_string())
+            .spawn(move || loop {
+                thread::sleep(OTLP_EXPORT_INTERVAL);
+                let spans: Vec<serde_json::Value> = buffer.lock().unwrap().drain(..).collect();
+                if !spans.is_empty() {
+                    if let Result::Err(err) = export(&url, spans) {
+                        warn!("Could not export spans to OTLP collector at {}: {}", url, err);
+                    }
+                }
+            })
+            .unwrap();
+        Self { finished }
+    }
+}
+
+impl<S> Layer<S> for OtlpLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let span = match ctx.span(id) {
+            Option::Some(span) => span,
+            Option::None => return,
+        };
+        // Spans inherit the trace of their parent:
+        let parent = span.parent()
+            .and_then(|parent| parent.extensions().get::<OtlpSpan>().map(|data| (data.trace_id, data.span_id)));
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        let data = OtlpSpan {
+            trace_id: parent.map(|(trace_id, _)| trace_id).unwrap_or_else(rand::random),
+            span_id: rand::random(),
+            parent_span_id: parent.map(|(_, span_id)| span_id),
+            started_at: SystemTime::now(),
+            fields,
+        };
+        span.extensions_mut().insert(data);
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        if let Option::Some(span) = ctx.span(id) {
+            if let Option::Some(data) = span.extensions_mut().get_mut::<OtlpSpan>() {
+                values.record(&mut data.fields);
+            }
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        let span = match ctx.span(&id) {
+            Option::Some(span) => span,
+            Option::None => return,
+        };
+        let data = match span.extensions_mut().remove::<OtlpSpan>() {
+            Option::Some(data) => data,
+            Option::None => return,
+        };
+        let attributes: Vec<serde_json::Value> = data.fields.others.iter()
+            .map(|(key, value)| json!({ "key": key, "value": { "stringValue": value } }))
+            .collect();
+        let mut otlp_span = json!({
+            "traceId": format!("{:032x}", data.trace_id),
+            "spanId": format!("{:016x}", data.span_id),
+            "name": span.name(),
+            "kind": 1,
+            "startTimeUnixNano": unix_nanos(data.started_at),
+            "endTimeUnixNano": unix_nanos(SystemTime::now()),
+            "attributes": attributes,
+        });
+        if let Option::Some(parent_span_id) = data.parent_span_id {
+            otlp_span["parentSpanId"] = json!(format!("{:016x}", parent_span_id));
+        }
+        let mut finished = self.finished.lock().unwrap();
+        if finished.len() < OTLP_MAX_BUFFERED_SPANS {
+            finished.push(otlp_span);
+        }
+    }
+}
+
+/// Sends given spans as a JSON-encoded OTLP export request.
+#[doc(hidden)]
+fn export(url: &str, spans: Vec<serde_json::Value>) -> io::Result<()> {
+    let request = json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [{ "key": "service.name", "value": { "stringValue": env!("CARGO_PKG_NAME") } }],
+            },
+            "scopeSpans": [{
+                "scope": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") },
+                "spans": spans,
+            }],
+        }],
+    });
+    ureq::post(url)
+        .set("Content-Type", "application/json")
+        .send_string(&request.to_string())
+        .map(|_| ())
+        .map_err(io::Error::other)
+}
+
+/// Returns given time as a string with nanoseconds since the Unix epoch, as
+/// OTLP expects 64-bit integers to be encoded in JSON.
+#[doc(hidden)]
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or(0)
+        .to_string()
+}
@@ -15,9 +15,32 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+use chrono::DateTime;
+use chrono::Utc;
+
+use mdchat_common::profile::Profile;
+use mdchat_common::user::Availability;
+
+use std::collections::BTreeSet;
+
 #[derive(Clone)]
 pub struct User {
     pub nickname: String,
     pub encrypted_password: Vec<u8>,
+    pub password_salt: Vec<u8>,
     pub last_sent_msg_id: Option<u64>,
+    pub last_read_msg_id: Option<u64>,
+    pub blocked: BTreeSet<String>,
+    pub profile: Profile,
+    pub last_seen: Option<DateTime<Utc>>,
+    pub last_seen_hidden: bool,
+    pub availability: Availability,
+    pub messages_today: u32,
+    pub messages_today_since: Option<DateTime<Utc>>,
+    pub approved: bool,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub verification_code: Option<(String, DateTime<Utc>)>,
+    pub verification_failures: u32,
+    pub welcome_pending: bool,
 }
\ No newline at end of file
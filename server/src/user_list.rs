@@ -15,49 +15,570 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+use chrono::DateTime;
+use chrono::Utc;
+
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::sync::RwLock;
 use once_cell::sync::Lazy;
 
+use rand::RngCore;
+
+use subtle::ConstantTimeEq;
+
 use mdcrypt::algorithms::Sha512;
 use mdcrypt::Encrypt;
 
+use mdchat_common::account::AccountFlags;
+use mdchat_common::account::AccountRecord;
+use mdchat_common::account::PasswordHash;
+use mdchat_common::account::PASSWORD_ALGORITHM;
+use mdchat_common::message::COLOR_COUNT;
+use mdchat_common::profile::Profile;
+use mdchat_common::user::Availability;
+use mdchat_common::user::UserInfo;
+
+use crate::client_list;
+use crate::global_config;
+use crate::pepper;
 use crate::user::User;
 
 static USER_LIST: Lazy<RwLock<BTreeMap<String, User>>> = Lazy::new(|| RwLock::new(BTreeMap::new()));
 static PASSWD_CRYPT: Lazy<Sha512> = Lazy::new(|| Sha512::default());
 
-/// Adds a new user into the list of users.
-pub fn add_user(nickname: String, password: String) {
-    // Encrypt password
-    let encrypted_password: Vec<u8> = PASSWD_CRYPT.encrypt(password.into_bytes());
+/// Length of a random salt of each user's password in bytes.
+const SALT_LENGTH: usize = 16;
+
+/// Number of invalid verification codes after which the verification code of a
+/// user is removed, so the code cannot be guessed. The user gets a new code when
+/// logging in again.
+pub const MAX_VERIFICATION_FAILURES: u32 = 5;
+
+/// Salt and hash of a random password which are used when someone tries to log
+/// into a non-existing account, so it takes the same time as logging in with
+/// a wrong password.
+static DUMMY_CREDENTIALS: Lazy<(Vec<u8>, Vec<u8>)> = Lazy::new(|| {
+    let mut salt = vec![0; SALT_LENGTH];
+    let mut password = vec![0; SALT_LENGTH];
+    rand::thread_rng().fill_bytes(&mut salt);
+    rand::thread_rng().fill_bytes(&mut password);
+    let hash = hash_password(&salt, &password);
+    (salt, hash)
+});
+
+/// Adds a new user into the list of users. Users who are not `approved` cannot
+/// send messages until they are approved using [`approve`]. The user has no
+/// password until it is set using [`set_password`].
+pub fn add_user(nickname: String, approved: bool) {
     // Create UserInfo instance
     let user_info = User {
         nickname: nickname.clone(),
-        encrypted_password,
+        encrypted_password: Vec::new(),
+        password_salt: Vec::new(),
         last_sent_msg_id: None,
+        last_read_msg_id: None,
+        blocked: BTreeSet::new(),
+        profile: Profile::default(),
+        last_seen: Option::None,
+        last_seen_hidden: false,
+        availability: Availability::Available,
+        messages_today: 0,
+        messages_today_since: Option::None,
+        approved,
+        email: Option::None,
+        email_verified: true,
+        verification_code: Option::None,
+        verification_failures: 0,
+        welcome_pending: true,
     };
     // Put it into user list:
     let previous_value = USER_LIST.write().unwrap().insert(nickname, user_info);
     assert!(previous_value.is_none(), "Specified user already exists");
 }
 
+/// Removes given user from the list of users.
+pub fn remove_user(nickname: &str) {
+    USER_LIST.write().unwrap().remove(nickname);
+}
+
+/// Sets the password of given user. The password is stored hashed with a new
+/// random salt.
+pub fn set_password(nickname: &str, password: &str) {
+    let mut password_salt = vec![0; SALT_LENGTH];
+    rand::thread_rng().fill_bytes(&mut password_salt);
+    let encrypted_password = hash_password(&password_salt, password.as_bytes());
+    let mut user_list = USER_LIST.write().unwrap();
+    let user = user_list.get_mut(nickname).unwrap();
+    user.password_salt = password_salt;
+    user.encrypted_password = encrypted_password;
+}
+
+/// Returns the number of registered users.
+pub fn count() -> usize {
+    USER_LIST.read().unwrap().len()
+}
+
 /// Returns if given user already exists.
 pub fn exists(nickname: &str) -> bool {
     USER_LIST.read().unwrap().contains_key(nickname)
 }
 
+/// Returns the ID of the newest message the user has acknowledged, or
+/// [`Option::None`] if the user has not acknowledged any message yet.
 pub fn get_last_sent_msg_id(nickname: &str) -> Option<u64> {
     USER_LIST.read().unwrap().get(nickname).unwrap().last_sent_msg_id
 }
 
+/// Records that the user has acknowledged all messages up to the one with given
+/// ID. The ID never moves backwards, so late acknowledgments are ignored.
 pub fn set_last_sent_msg_id(nickname: &str, last_sent_msg_id: u64) {
-    USER_LIST.write().unwrap().get_mut(nickname).unwrap().last_sent_msg_id = Option::Some(last_sent_msg_id);
+    let mut user_list = USER_LIST.write().unwrap();
+    let user = user_list.get_mut(nickname).unwrap();
+    user.last_sent_msg_id = user.last_sent_msg_id.max(Option::Some(last_sent_msg_id));
 }
 
-pub fn verify_password(nickname: &str, candidate_passwd: String) -> bool {
-    let encrypted_candidate: Vec<u8> = PASSWD_CRYPT.encrypt(candidate_passwd.into_bytes());
+/// Returns the ID of the newest message the user has read, or [`Option::None`]
+/// if the user has not marked any message as read yet.
+pub fn get_last_read_msg_id(nickname: &str) -> Option<u64> {
+    USER_LIST.read().unwrap().get(nickname).unwrap().last_read_msg_id
+}
+
+/// Records that the user has read all messages up to the one with given ID. The
+/// ID never moves backwards.
+///
+/// # Return value
+///
+/// [`Option::Some`] with the previous ID, which is 0 if the user has not read any
+/// message before, or [`Option::None`] if the ID has not moved.
+pub fn set_last_read_msg_id(nickname: &str, last_read_msg_id: u64) -> Option<u64> {
+    let mut user_list = USER_LIST.write().unwrap();
+    let user = user_list.get_mut(nickname).unwrap();
+    let previous = user.last_read_msg_id.unwrap_or(0);
+    if previous >= last_read_msg_id {
+        return Option::None
+    }
+    user.last_read_msg_id = Option::Some(last_read_msg_id);
+    Option::Some(previous)
+}
+
+/// Makes user `nickname` block user `blocked`, so messages sent by the blocked
+/// user are not delivered to the blocking one.
+pub fn block(nickname: &str, blocked: String) {
+    USER_LIST.write().unwrap().get_mut(nickname).unwrap().blocked.insert(blocked);
+}
+
+/// Makes user `nickname` unblock previously blocked user `blocked`.
+pub fn unblock(nickname: &str, blocked: &str) {
+    USER_LIST.write().unwrap().get_mut(nickname).unwrap().blocked.remove(blocked);
+}
+
+/// Returns if user `nickname` has blocked user `other`.
+pub fn has_blocked(nickname: &str, other: &str) -> bool {
+    USER_LIST.read().unwrap()
+        .get(nickname)
+        .map(|user| user.blocked.contains(other))
+        .unwrap_or(false)
+}
+
+/// Returns the profile of given user, or [`Option::None`] if the user does not
+/// exist.
+pub fn get_profile(nickname: &str) -> Option<Profile> {
+    USER_LIST.read().unwrap().get(nickname).map(|user| user.profile.clone())
+}
+
+/// Replaces the profile of given user.
+pub fn set_profile(nickname: &str, profile: Profile) {
+    USER_LIST.write().unwrap().get_mut(nickname).unwrap().profile = profile;
+}
+
+/// Records the date and time of the last activity of given user.
+pub fn set_last_seen(nickname: &str, last_seen: DateTime<Utc>) {
+    USER_LIST.write().unwrap().get_mut(nickname).unwrap().last_seen = Option::Some(last_seen);
+}
+
+/// Sets whether the time of the last activity of given user is hidden from other
+/// users.
+pub fn set_last_seen_hidden(nickname: &str, hidden: bool) {
+    USER_LIST.write().unwrap().get_mut(nickname).unwrap().last_seen_hidden = hidden;
+}
+
+/// Returns whether the time of the last activity of given user is hidden from
+/// other users. Such users do not send read receipts either.
+pub fn is_last_seen_hidden(nickname: &str) -> bool {
+    USER_LIST.read().unwrap().get(nickname).unwrap().last_seen_hidden
+}
+
+/// Returns whether given user has not been sent the welcome sequence yet and
+/// marks it as sent. Only users created by registration or by their first login
+/// using an external identity system are sent the welcome sequence.
+pub fn take_welcome_pending(nickname: &str) -> bool {
+    let mut user_list = USER_LIST.write().unwrap();
+    let user = user_list.get_mut(nickname).unwrap();
+    std::mem::replace(&mut user.welcome_pending, false)
+}
+
+/// Returns whether given user wants to be disturbed.
+pub fn get_availability(nickname: &str) -> Availability {
+    USER_LIST.read().unwrap().get(nickname).map(|user| user.availability).unwrap_or_default()
+}
+
+/// Sets whether given user wants to be disturbed and returns the previous
+/// availability.
+pub fn set_availability(nickname: &str, availability: Availability) -> Availability {
+    std::mem::replace(&mut USER_LIST.write().unwrap().get_mut(nickname).unwrap().availability, availability)
+}
+
+/// Returns whether given user has been approved, that is whether the user can
+/// send messages.
+pub fn is_approved(nickname: &str) -> bool {
+    USER_LIST.read().unwrap().get(nickname).is_some_and(|user| user.approved)
+}
+
+/// Approves given user, so the user can send messages.
+///
+/// # Return value
+///
+/// Whether the user exists.
+pub fn approve(nickname: &str) -> bool {
+    match USER_LIST.write().unwrap().get_mut(nickname) {
+        Option::Some(user) => {
+            user.approved = true;
+            true
+        },
+        Option::None => false,
+    }
+}
+
+/// Sets the e-mail address of given user which must be verified before the user
+/// can log in.
+pub fn set_unverified_email(nickname: &str, email: String) {
+    let mut user_list = USER_LIST.write().unwrap();
+    let user = user_list.get_mut(nickname).unwrap();
+    user.email = Option::Some(email);
+    user.email_verified = false;
+    user.verification_code = Option::None;
+    user.verification_failures = 0;
+}
+
+/// Returns the e-mail address of given user if it has not been verified yet.
+pub fn get_unverified_email(nickname: &str) -> Option<String> {
+    USER_LIST.read().unwrap()
+        .get(nickname)
+        .filter(|user| !user.email_verified)
+        .and_then(|user| user.email.clone())
+}
+
+/// Returns whether given user has a verification code which has not expired
+/// yet at `now`.
+pub fn has_valid_verification_code(nickname: &str, now: DateTime<Utc>) -> bool {
+    USER_LIST.read().unwrap()
+        .get(nickname)
+        .and_then(|user| user.verification_code.as_ref())
+        .is_some_and(|(_, expires_at)| *expires_at > now)
+}
+
+/// Sets the verification code of given user, replacing the previous one.
+/// [`Option::None`] removes the code.
+pub fn set_verification_code(nickname: &str, code: Option<(String, DateTime<Utc>)>) {
+    let mut user_list = USER_LIST.write().unwrap();
+    let user = user_list.get_mut(nickname).unwrap();
+    user.verification_code = code;
+    user.verification_failures = 0;
+}
+
+/// Checks given verification code of given user in constant time. If the code
+/// matches and has not expired yet at `now`, the e-mail address of the user is
+/// marked as verified. After [`MAX_VERIFICATION_FAILURES`] invalid codes, the
+/// verification code is removed, see [`has_valid_verification_code`].
+///
+/// # Return value
+///
+/// Whether the e-mail address has been verified.
+pub fn verify_email(nickname: &str, code: &str, now: DateTime<Utc>) -> bool {
+    let mut user_list = USER_LIST.write().unwrap();
+    let user = match user_list.get_mut(nickname) {
+        Option::Some(user) => user,
+        Option::None => return false,
+    };
+    let valid = match &user.verification_code {
+        Option::Some((expected, expires_at)) => bool::from(expected.as_bytes().ct_eq(code.as_bytes())) && *expires_at > now,
+        Option::None => return false,
+    };
+    match valid {
+        true => {
+            user.email_verified = true;
+            user.verification_code = Option::None;
+        },
+        false => {
+            user.verification_failures += 1;
+            if user.verification_failures >= MAX_VERIFICATION_FAILURES {
+                user.verification_code = Option::None;
+            }
+        },
+    }
+    valid
+}
+
+/// Returns how many messages given user has sent in the daily quota period
+/// starting at `period_start`.
+pub fn get_daily_message_count(nickname: &str, period_start: DateTime<Utc>) -> u32 {
     let user_list = USER_LIST.read().unwrap();
     let user = user_list.get(nickname).unwrap();
-    encrypted_candidate == user.encrypted_password
-}
\ No newline at end of file
+    match user.messages_today_since {
+        Option::Some(since) if since == period_start => user.messages_today,
+        _ => 0,
+    }
+}
+
+/// Counts a message sent by given user in the daily quota period starting at
+/// `period_start` and returns how many messages the user has sent in the
+/// period, including this one.
+pub fn count_daily_message(nickname: &str, period_start: DateTime<Utc>) -> u32 {
+    let mut user_list = USER_LIST.write().unwrap();
+    let user = user_list.get_mut(nickname).unwrap();
+    if user.messages_today_since != Option::Some(period_start) {
+        user.messages_today = 0;
+        user.messages_today_since = Option::Some(period_start);
+    }
+    user.messages_today += 1;
+    user.messages_today
+}
+
+/// Returns public information about given user, or [`Option::None`] if the user
+/// does not exist.
+pub fn user_info(nickname: &str) -> Option<UserInfo> {
+    let online = client_list::online_nicknames();
+    USER_LIST.read().unwrap().get(nickname).map(|user| to_user_info(user, &online))
+}
+
+/// Returns public information about all users ordered by their nicknames.
+pub fn user_infos() -> Vec<UserInfo> {
+    let online = client_list::online_nicknames();
+    USER_LIST.read().unwrap().values().map(|user| to_user_info(user, &online)).collect()
+}
+
+#[doc(hidden)]
+fn to_user_info(user: &User, online: &BTreeSet<String>) -> UserInfo {
+    let last_seen = match user.last_seen_hidden {
+        true => Option::None,
+        false => user.last_seen,
+    };
+    UserInfo::new(user.nickname.clone(), online.contains(&user.nickname), last_seen, user.availability)
+}
+
+/// Returns the color assigned to given user. This is the color the user has chosen
+/// in their profile, or a color derived from the nickname otherwise. The derived
+/// color does not change between server restarts.
+pub fn color_of(nickname: &str) -> u8 {
+    get_profile(nickname)
+        .and_then(|profile| profile.color())
+        .unwrap_or_else(|| default_color(nickname))
+}
+
+/// Derives a color from a nickname using FNV-1a hash, which is stable unlike the
+/// hasher from the standard library.
+#[doc(hidden)]
+fn default_color(nickname: &str) -> u8 {
+    let hash = nickname.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    (hash % COLOR_COUNT as u64) as u8
+}
+
+/// Returns all users as account records ordered by their nicknames. Passwords are
+/// exported hashed together with the parameters of the hash.
+pub fn export_accounts() -> Vec<AccountRecord> {
+    let global_config = global_config();
+    USER_LIST.read().unwrap().values().map(|user| {
+        let password = match user.encrypted_password.is_empty() {
+            true => Option::None,
+            false => Option::Some(PasswordHash::new(
+                PASSWORD_ALGORITHM.to_string(), &user.password_salt, &user.encrypted_password)),
+        };
+        let flags = AccountFlags {
+            approved: user.approved,
+            email_verified: user.email_verified,
+            last_seen_hidden: user.last_seen_hidden,
+        };
+        AccountRecord::new(user.nickname.clone(), global_config.role_of(&user.nickname).to_string(), password,
+            user.email.clone(), flags, user.blocked.iter().cloned().collect(), user.profile.clone())
+    }).collect()
+}
+
+/// Imports given account record into the list of users. The role in the record is
+/// ignored, because roles are assigned in the configuration of the server.
+///
+/// # Parameters
+///
+///  -  `record`: the account to import
+///  -  `overwrite`: whether an existing user with the same nickname should be
+///     replaced
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] if the account was imported
+///  -  [`Result::Err`] with the reason if the account was not imported
+pub fn import_account(record: &AccountRecord, overwrite: bool) -> Result<(), String> {
+    let (encrypted_password, password_salt) = match record.password() {
+        Option::None => (Vec::new(), Vec::new()),
+        Option::Some(password) if password.algorithm() != PASSWORD_ALGORITHM =>
+            return Result::Err(format!("unsupported password algorithm `{}`", password.algorithm())),
+        Option::Some(password) => (password.hash()?, password.salt()?),
+    };
+    let flags = record.flags();
+    let user = User {
+        nickname: record.nickname().clone(),
+        encrypted_password,
+        password_salt,
+        last_sent_msg_id: Option::None,
+        last_read_msg_id: Option::None,
+        blocked: record.blocked().iter().cloned().collect(),
+        profile: record.profile().clone(),
+        last_seen: Option::None,
+        last_seen_hidden: flags.last_seen_hidden,
+        availability: Availability::Available,
+        messages_today: 0,
+        messages_today_since: Option::None,
+        approved: flags.approved,
+        email: record.email().cloned(),
+        email_verified: flags.email_verified,
+        verification_code: Option::None,
+        verification_failures: 0,
+        welcome_pending: false,
+    };
+    let mut user_list = USER_LIST.write().unwrap();
+    if !overwrite && user_list.contains_key(record.nickname()) {
+        return Result::Err("user already exists".to_string())
+    }
+    user_list.insert(record.nickname().clone(), user);
+    Result::Ok(())
+}
+
+/// Returns whether given user exists and given password is the password of the
+/// user. Users without a password set never match. The password is checked in
+/// constant time and it takes the same time whether the user exists or not, so
+/// the time of the check reveals neither the password nor which users exist.
+pub fn verify_password(nickname: &str, candidate_passwd: String) -> bool {
+    let user_list = USER_LIST.read().unwrap();
+    let (exists, salt, encrypted_password) = match user_list.get(nickname) {
+        Option::Some(user) => (true, &user.password_salt, &user.encrypted_password),
+        Option::None => (false, &DUMMY_CREDENTIALS.0, &DUMMY_CREDENTIALS.1),
+    };
+    let encrypted_candidate = hash_password(salt, candidate_passwd.as_bytes());
+    let matches: bool = encrypted_candidate.ct_eq(encrypted_password).into();
+    exists & !encrypted_password.is_empty() & matches
+}
+
+/// Hashes given password together with given salt and the secret pepper.
+#[doc(hidden)]
+fn hash_password(salt: &[u8], password: &[u8]) -> Vec<u8> {
+    let data = [pepper::get(), salt, password].concat();
+    PASSWD_CRYPT.encrypt(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::add_user;
+    use super::get_unverified_email;
+    use super::has_valid_verification_code;
+    use super::set_password;
+    use super::set_unverified_email;
+    use super::set_verification_code;
+    use super::verify_email;
+    use super::verify_password;
+    use super::MAX_VERIFICATION_FAILURES;
+
+    use crate::pepper;
+
+    use chrono::Duration;
+    use chrono::Utc;
+
+    /// Adds a user with given nickname whose e-mail address waits for given
+    /// verification code, valid for an hour.
+    fn add_unverified_user(nickname: &str, code: &str) {
+        add_user(nickname.to_string(), true);
+        set_unverified_email(nickname, format!("{}@example.com", nickname));
+        set_verification_code(nickname, Option::Some((code.to_string(), Utc::now() + Duration::hours(1))));
+    }
+
+    #[test]
+    fn valid_code_verifies_email() {
+        add_unverified_user("verify-valid", "123456");
+        assert!(verify_email("verify-valid", "123456", Utc::now()));
+        assert_eq!(get_unverified_email("verify-valid"), Option::None);
+        assert!(!has_valid_verification_code("verify-valid", Utc::now()));
+        // The code cannot be used twice:
+        assert!(!verify_email("verify-valid", "123456", Utc::now()));
+    }
+
+    #[test]
+    fn invalid_or_expired_code_does_not_verify_email() {
+        add_unverified_user("verify-invalid", "123456");
+        assert!(!verify_email("verify-invalid", "654321", Utc::now()));
+        assert!(!verify_email("verify-invalid", "12345", Utc::now()));
+        assert!(!verify_email("verify-invalid", "", Utc::now()));
+        assert!(!verify_email("verify-invalid", "123456", Utc::now() + Duration::hours(2)));
+        assert!(get_unverified_email("verify-invalid").is_some());
+        assert!(!verify_email("verify-nobody", "123456", Utc::now()));
+    }
+
+    #[test]
+    fn code_is_removed_after_too_many_failures() {
+        add_unverified_user("verify-guessed", "123456");
+        for _ in 1..MAX_VERIFICATION_FAILURES {
+            assert!(!verify_email("verify-guessed", "000000", Utc::now()));
+        }
+        assert!(has_valid_verification_code("verify-guessed", Utc::now()));
+        assert!(!verify_email("verify-guessed", "000000", Utc::now()));
+        assert!(!has_valid_verification_code("verify-guessed", Utc::now()));
+        // Even the right code is refused then:
+        assert!(!verify_email("verify-guessed", "123456", Utc::now()));
+        assert!(get_unverified_email("verify-guessed").is_some());
+    }
+
+    #[test]
+    fn new_code_resets_failures() {
+        add_unverified_user("verify-renewed", "123456");
+        for _ in 1..MAX_VERIFICATION_FAILURES {
+            assert!(!verify_email("verify-renewed", "000000", Utc::now()));
+        }
+        set_verification_code("verify-renewed", Option::Some(("111111".to_string(), Utc::now() + Duration::hours(1))));
+        assert!(!verify_email("verify-renewed", "000000", Utc::now()));
+        assert!(verify_email("verify-renewed", "111111", Utc::now()));
+    }
+
+    #[test]
+    fn only_correct_password_is_verified() {
+        pepper::init_for_tests();
+        add_user("auth-alice".to_string(), true);
+        set_password("auth-alice", "correct horse");
+        assert!(verify_password("auth-alice", "correct horse".to_string()));
+        assert!(!verify_password("auth-alice", "correct horse ".to_string()));
+        assert!(!verify_password("auth-alice", String::new()));
+    }
+
+    #[test]
+    fn changed_password_replaces_old_one() {
+        pepper::init_for_tests();
+        add_user("auth-bob".to_string(), true);
+        set_password("auth-bob", "old");
+        set_password("auth-bob", "new");
+        assert!(!verify_password("auth-bob", "old".to_string()));
+        assert!(verify_password("auth-bob", "new".to_string()));
+    }
+
+    #[test]
+    fn nobody_logs_into_non_existing_account() {
+        pepper::init_for_tests();
+        assert!(!verify_password("auth-nobody", String::new()));
+        assert!(!verify_password("auth-nobody", "password".to_string()));
+    }
+
+    #[test]
+    fn nobody_logs_into_account_without_password() {
+        pepper::init_for_tests();
+        add_user("auth-external".to_string(), true);
+        assert!(!verify_password("auth-external", String::new()));
+    }
+}
@@ -0,0 +1,157 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! A module for the HTTP endpoint integrations use to post messages into the
+//! chat.
+
+use crate::channel_list;
+use crate::global_config;
+use crate::http;
+use crate::http::ConnectionLimit;
+use crate::http::HttpError;
+use crate::message_queue;
+use crate::socket;
+
+use mdchat_common::markup;
+use mdchat_common::message::MessageKind;
+use mdchat_common::sanitize;
+
+use serde_json::Value;
+
+use subtle::ConstantTimeEq;
+
+use tracing::debug;
+use tracing::info;
+use tracing::warn;
+
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::thread;
+use std::time::Duration;
+
+/// Path of the endpoint integrations post messages to.
+const PATH: &str = "/webhook";
+
+/// Accepts connections to the webhook endpoint. This is a blocking function to
+/// be run in a separate thread.
+///
+/// # Parameters
+///
+///  -  `listener`: the listener to listen on
+///  -  `keepalive`: time an accepted connection may be idle before TCP
+///     keepalive probes are sent, or [`Option::None`] to disable them
+pub fn listen(listener: &TcpListener, keepalive: Option<Duration>) {
+    let limit = ConnectionLimit::new(http::MAX_CONNECTIONS);
+    for stream in listener.incoming() {
+        match stream {
+            Result::Ok(stream) => match limit.acquire() {
+                Option::Some(permit) => {
+                    let _ = thread::Builder::new()
+                        .name("webhook request".to_string())
+                        .spawn(move || {
+                            handle_conn(stream, keepalive);
+                            drop(permit);
+                        });
+                },
+                Option::None => warn!("Too many connections to the webhook endpoint, closing a new one"),
+            },
+            Result::Err(err) => warn!("An integration could not connect to the webhook endpoint: {}", err),
+        }
+    }
+}
+
+#[doc(hidden)]
+fn handle_conn(stream: TcpStream, keepalive: Option<Duration>) {
+    let peer_addr = match stream.peer_addr() {
+        Result::Ok(peer_addr) => peer_addr,
+        Result::Err(_) => return,
+    };
+    if !global_config().is_allowed_ip_addr(&peer_addr.ip()) {
+        return;
+    }
+    if let Result::Err(err) = socket::keep_alive(&stream, keepalive) {
+        debug!("Could not enable TCP keepalive for {}: {}", peer_addr, err);
+    }
+    let status = match handle_request(&stream) {
+        Result::Ok(integration) => {
+            info!("Integration `{}` posted a message from {}", integration, peer_addr);
+            (204, "No Content")
+        },
+        Result::Err(err) => {
+            warn!("Rejected webhook request from {}: {} {}", peer_addr, err.status, err.reason);
+            (err.status, err.reason)
+        },
+    };
+    http::respond(&stream, status, "text/plain", &[]);
+}
+
+/// Reads a single request, checks the token of the integration and pushes the
+/// posted message into the message queue.
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] with the name of the integration if the message was
+///     posted
+///  -  [`Result::Err`] with the status of the response otherwise
+#[doc(hidden)]
+fn handle_request(stream: &TcpStream) -> Result<String, HttpError> {
+    let mut reader = http::reader(stream);
+    // Check the method and the path:
+    let head = http::read_head(&mut reader)?;
+    match (head.method.as_str(), head.path.as_str()) {
+        ("POST", PATH) => {},
+        (_, PATH) => return Result::Err(HttpError::new(405, "Method Not Allowed")),
+        _ => return Result::Err(HttpError::new(404, "Not Found")),
+    }
+    // Authenticate the integration before reading the body:
+    let token = head.header("authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(HttpError::new(401, "Unauthorized"))?;
+    let integration = find_integration(token.trim()).ok_or(HttpError::new(401, "Unauthorized"))?;
+    // Read the body:
+    let max_body_size = global_config().webhooks().read().unwrap().get_max_body_size();
+    let body = http::read_body(&mut reader, &head, max_body_size)?;
+    // Parse the payload and post the message:
+    let payload: Value = serde_json::from_slice(&body).map_err(|_| HttpError::new(400, "Bad Request"))?;
+    let text = payload.get("text")
+        .and_then(Value::as_str)
+        .map(|text| markup::normalize(&sanitize::text(text.trim())))
+        .ok_or(HttpError::new(422, "Unprocessable Entity"))?;
+    let channel = match payload.get("channel") {
+        Option::None | Option::Some(Value::Null) => Option::None,
+        Option::Some(Value::String(channel)) if channel_list::get_mode(channel).is_some() => Option::Some(channel.clone()),
+        Option::Some(_) => return Result::Err(HttpError::new(422, "Unprocessable Entity")),
+    };
+    if !global_config().is_allowed_message_text(&text) {
+        return Result::Err(HttpError::new(422, "Unprocessable Entity"));
+    }
+    if !message_queue::push(Option::None, Option::None, integration.clone(), channel, MessageKind::Bot, text, Option::None) {
+        return Result::Err(HttpError::new(503, "Service Unavailable"));
+    }
+    Result::Ok(integration)
+}
+
+/// Returns the name of the integration with given token. Tokens are compared in
+/// constant time, so the time of the check does not reveal them.
+#[doc(hidden)]
+fn find_integration(token: &str) -> Option<String> {
+    let global_config = global_config();
+    let webhooks_config = global_config.webhooks().read().unwrap();
+    webhooks_config.get_tokens().iter()
+        .filter(|(_, expected)| bool::from(expected.as_bytes().ct_eq(token.as_bytes())))
+        .map(|(name, _)| name.clone())
+        .next()
+}
@@ -0,0 +1,72 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A module building the welcome sequence, which is sent to users on their first
+//! login after registration, from the template file given by global
+//! configuration.
+
+use crate::global_config;
+
+use std::fs;
+use std::io;
+
+/// Returns texts of messages of the welcome sequence for given user, or an empty
+/// [`Vec`] if there is no welcome sequence. The template file is read each time,
+/// so it can be changed while the server is running. Paragraphs of the file
+/// separated by empty lines are separate messages, lines starting with `#` are
+/// ignored, and `{nickname}` and `{server}` placeholders are replaced.
+///
+/// # Parameters
+///
+///  -  `nickname`: nickname of the new user
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] containing the texts of the messages
+///  -  [`Result::Err`] if the template file could not be read
+pub fn messages(nickname: &str) -> io::Result<Vec<String>> {
+    let (path, server_name) = {
+        let global_config = global_config();
+        let server_config = global_config.server().read().unwrap();
+        (server_config.get_welcome().cloned(), server_config.get_name().cloned().unwrap_or_default())
+    };
+    let path = match path {
+        Option::Some(path) => path,
+        Option::None => return Result::Ok(Vec::new()),
+    };
+    let template = fs::read_to_string(path)?;
+    let mut messages = Vec::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    // An empty line at the end finishes the last paragraph:
+    for line in template.lines().chain([""]) {
+        let line = line.trim_end();
+        if line.starts_with('#') {
+            continue
+        }
+        if !line.is_empty() {
+            paragraph.push(line);
+            continue
+        }
+        if !paragraph.is_empty() {
+            messages.push(paragraph.join("\n")
+                .replace("{nickname}", nickname)
+                .replace("{server}", &server_name));
+            paragraph.clear();
+        }
+    }
+    Result::Ok(messages)
+}
@@ -0,0 +1,65 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Benchmarks of [`MessageFilteringConfig::is_allowed`] with growing sets of
+//! banned patterns. Each message is checked against all patterns unless one of
+//! them matches, so allowed messages are the worst case.
+
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::BenchmarkId;
+use criterion::Criterion;
+
+use mdchat_serverconf::MessageFilteringConfig;
+
+use regex::Regex;
+
+use std::hint::black_box;
+
+/// Numbers of banned patterns the filter is benchmarked with.
+const PATTERN_COUNTS: [usize; 4] = [1, 10, 100, 1000];
+
+/// A message which no pattern matches.
+const ALLOWED_TEXT: &str = "Hello everybody, the build is green again. See you at the meeting tomorrow!";
+
+/// Returns a filter banning given number of words, matched case-insensitively as
+/// whole words, like patterns administrators usually write.
+fn filter(pattern_count: usize) -> MessageFilteringConfig {
+    let mut filter = MessageFilteringConfig::new();
+    for index in 0..pattern_count {
+        filter.ban(Regex::new(&format!(r"(?i)\bbanned{}\b", index)).unwrap());
+    }
+    filter
+}
+
+fn is_allowed(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("is_allowed");
+    for pattern_count in PATTERN_COUNTS {
+        let filter = filter(pattern_count);
+        // Only the last pattern matches the banned message:
+        let banned_text = format!("{} banned{}", ALLOWED_TEXT, pattern_count - 1);
+        group.bench_with_input(BenchmarkId::new("allowed", pattern_count), &filter, |bencher, filter| {
+            bencher.iter(|| filter.is_allowed(black_box(ALLOWED_TEXT)))
+        });
+        group.bench_with_input(BenchmarkId::new("banned by last", pattern_count), &filter, |bencher, filter| {
+            bencher.iter(|| filter.is_allowed(black_box(&banned_text)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, is_allowed);
+criterion_main!(benches);
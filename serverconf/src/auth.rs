@@ -0,0 +1,616 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::REGEX_WHITESPACE;
+use crate::split_command;
+use crate::parse_duration;
+use crate::parse_switch;
+
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Represents a source of identities which users are authenticated against.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuthProviderKind {
+    /// Users register on the server and their passwords are stored by the
+    /// server itself.
+    Builtin,
+    /// Users are authenticated by binding to an LDAP directory.
+    Ldap,
+    /// Users log in using an OAuth access token instead of a password, which is
+    /// checked by the token introspection endpoint of the authorization server.
+    OAuth,
+    /// Users are authenticated by an external command.
+    Command,
+}
+
+impl FromStr for AuthProviderKind {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "builtin" => Result::Ok(Self::Builtin),
+            "ldap" => Result::Ok(Self::Ldap),
+            "oauth" => Result::Ok(Self::OAuth),
+            "command" => Result::Ok(Self::Command),
+            other => Result::Err(format!("`{}` is not a valid authentication provider", other)),
+        }
+    }
+}
+
+/// Represents configurability of authentication of users.
+pub struct AuthConfig {
+    provider: AuthProviderKind,
+    command: Vec<String>,
+    ldap: LdapAuthConfig,
+    oauth: OAuthConfig,
+    email: EmailVerificationConfig,
+    pepper_file: Option<PathBuf>,
+    timeout: Duration,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AuthConfig {
+    /// Creates a new [`AuthConfig`] with default values. Using this constructor
+    /// is same as using [`Default`]'s implementation.
+    pub fn new() -> Self {
+        Self {
+            provider: AuthProviderKind::Builtin,
+            command: Vec::new(),
+            ldap: LdapAuthConfig::new(),
+            oauth: OAuthConfig::new(),
+            email: EmailVerificationConfig::new(),
+            pepper_file: Option::None,
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Merges `self` with `other` instance. All values will be overwritten by
+    /// `other`'s values.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, other: Self) {
+        self.provider = other.provider;
+        self.command = other.command;
+        self.ldap.append(other.ldap);
+        self.oauth.append(other.oauth);
+        self.email.append(other.email);
+        self.pepper_file = other.pepper_file;
+        self.timeout = other.timeout;
+    }
+
+    /// Returns which authentication provider users are authenticated by.
+    pub fn get_provider(&self) -> AuthProviderKind {
+        self.provider
+    }
+
+    /// Sets which authentication provider users are authenticated by.
+    pub fn set_provider(&mut self, provider: AuthProviderKind) {
+        self.provider = provider;
+    }
+
+    /// Returns the command used by [`AuthProviderKind::Command`]: the program
+    /// followed by its arguments. The nickname is appended as the last argument
+    /// after `--`, so it is not taken for an option, and the password is written
+    /// to the standard input of the command.
+    pub fn get_command(&self) -> &Vec<String> {
+        &self.command
+    }
+
+    /// Sets the command used by [`AuthProviderKind::Command`]. See
+    /// [`AuthConfig::get_command`] for more information.
+    pub fn set_command(&mut self, command: Vec<String>) {
+        self.command = command;
+    }
+
+    /// Returns an immutable borrow to the [`LdapAuthConfig`].
+    pub fn get_ldap(&self) -> &LdapAuthConfig {
+        &self.ldap
+    }
+
+    /// Returns a mutable borrow to the [`LdapAuthConfig`].
+    pub fn get_ldap_mut(&mut self) -> &mut LdapAuthConfig {
+        &mut self.ldap
+    }
+
+    /// Returns an immutable borrow to the [`OAuthConfig`].
+    pub fn get_oauth(&self) -> &OAuthConfig {
+        &self.oauth
+    }
+
+    /// Returns a mutable borrow to the [`OAuthConfig`].
+    pub fn get_oauth_mut(&mut self) -> &mut OAuthConfig {
+        &mut self.oauth
+    }
+
+    /// Returns an immutable borrow to the [`EmailVerificationConfig`].
+    pub fn get_email(&self) -> &EmailVerificationConfig {
+        &self.email
+    }
+
+    /// Returns a mutable borrow to the [`EmailVerificationConfig`].
+    pub fn get_email_mut(&mut self) -> &mut EmailVerificationConfig {
+        &mut self.email
+    }
+
+    /// Returns the path to the file containing the secret pepper, which is mixed
+    /// into all password hashes in addition to per-user salts. [`Option::None`]
+    /// means that no pepper is used.
+    pub fn get_pepper_file(&self) -> Option<&PathBuf> {
+        self.pepper_file.as_ref()
+    }
+
+    /// Sets the path to the file containing the secret pepper. [`Option::None`]
+    /// means that no pepper is used.
+    pub fn set_pepper_file(&mut self, pepper_file: Option<PathBuf>) {
+        self.pepper_file = pepper_file;
+    }
+
+    /// Returns how long the server waits for the authentication provider to
+    /// check credentials of a user. If it does not answer in time, the user is
+    /// not logged in.
+    pub fn get_timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Sets how long the server waits for the authentication provider. See
+    /// [`AuthConfig::get_timeout`] for more information.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split command and argument:
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
+        // Parse based on the command
+        match command {
+            "command" => self.__process_command(arg),
+            "email" => arg.ok_or("Sub-command was expected after `auth email`".to_string())
+                .and_then(|arg| self.email.process_line(arg)),
+            "ldap" => arg.ok_or("Sub-command was expected after `auth ldap`".to_string())
+                .and_then(|arg| self.ldap.process_line(arg)),
+            "oauth" => arg.ok_or("Sub-command was expected after `auth oauth`".to_string())
+                .and_then(|arg| self.oauth.process_line(arg)),
+            "pepper-file" => self.__process_pepper_file(arg),
+            "provider" => self.__process_provider(arg),
+            "timeout" => self.__process_timeout(arg),
+            other => Result::Err(format!("`auth {}`: unknown sub-command", other))
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_command(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("A command was expected after `auth command`".to_string())
+            .map(|arg| self.set_command(REGEX_WHITESPACE.split(arg).map(str::to_string).collect()))
+    }
+
+    #[doc(hidden)]
+    fn __process_provider(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("A provider was expected after `auth provider`".to_string())
+            .and_then(|arg| arg.parse())
+            .map(|provider| self.set_provider(provider))
+    }
+
+    #[doc(hidden)]
+    fn __process_timeout(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `auth timeout`".to_string())
+            .and_then(parse_duration)
+            .map(|timeout| self.set_timeout(timeout))
+    }
+
+    #[doc(hidden)]
+    fn __process_pepper_file(&mut self, arg: Option<&str>) -> Result<(), String> {
+        match arg {
+            Option::None => Result::Err("A path was expected after `auth pepper-file`".to_string()),
+            Option::Some("off") => {
+                self.set_pepper_file(Option::None);
+                Result::Ok(())
+            },
+            Option::Some(path) => {
+                self.set_pepper_file(Option::Some(PathBuf::from(path)));
+                Result::Ok(())
+            },
+        }
+    }
+}
+
+/// Represents configurability of authentication against an LDAP directory. A user
+/// is authenticated if binding to the directory as the user succeeds.
+pub struct LdapAuthConfig {
+    url: Option<String>,
+    user_dn: Option<String>,
+}
+
+impl Default for LdapAuthConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LdapAuthConfig {
+    /// Creates a new [`LdapAuthConfig`] with no directory set. Using this
+    /// constructor is same as using [`Default`]'s implementation.
+    pub fn new() -> Self {
+        Self { url: Option::None, user_dn: Option::None }
+    }
+
+    /// Merges `self` with `other` instance. All values will be overwritten by
+    /// `other`'s values.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, other: Self) {
+        self.url = other.url;
+        self.user_dn = other.user_dn;
+    }
+
+    /// Returns the URL of the LDAP server, for example
+    /// `ldaps://ldap.example.com`.
+    pub fn get_url(&self) -> Option<&String> {
+        self.url.as_ref()
+    }
+
+    /// Sets the URL of the LDAP server.
+    pub fn set_url(&mut self, url: Option<String>) {
+        self.url = url;
+    }
+
+    /// Returns the template of the distinguished name users are bound as, in
+    /// which `{nickname}` is replaced by the nickname of the user.
+    pub fn get_user_dn(&self) -> Option<&String> {
+        self.user_dn.as_ref()
+    }
+
+    /// Sets the template of the distinguished name users are bound as. See
+    /// [`LdapAuthConfig::get_user_dn`] for more information.
+    pub fn set_user_dn(&mut self, user_dn: Option<String>) {
+        self.user_dn = user_dn;
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split command and argument:
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
+        // Parse based on the command
+        match command {
+            "url" => self.__process_url(arg),
+            "user-dn" => self.__process_user_dn(arg),
+            other => Result::Err(format!("`auth ldap {}`: unknown sub-command", other))
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_url(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("A URL was expected after `auth ldap url`".to_string())
+            .map(|url| self.set_url(Option::Some(url.to_string())))
+    }
+
+    #[doc(hidden)]
+    fn __process_user_dn(&mut self, arg: Option<&str>) -> Result<(), String> {
+        match arg {
+            Option::None => Result::Err("A distinguished name was expected after `auth ldap user-dn`".to_string()),
+            Option::Some(arg) if !arg.contains("{nickname}") =>
+                Result::Err("`auth ldap user-dn`: `{nickname}` placeholder is missing".to_string()),
+            Option::Some(arg) => {
+                self.set_user_dn(Option::Some(arg.to_string()));
+                Result::Ok(())
+            },
+        }
+    }
+}
+
+/// Represents configurability of authentication using OAuth access tokens. Users
+/// send an access token instead of a password and the server checks it using the
+/// token introspection endpoint (RFC 7662) of the authorization server.
+pub struct OAuthConfig {
+    introspection_url: Option<String>,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    nickname_claim: String,
+}
+
+impl Default for OAuthConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OAuthConfig {
+    /// Creates a new [`OAuthConfig`] with no authorization server set. Using this
+    /// constructor is same as using [`Default`]'s implementation.
+    pub fn new() -> Self {
+        Self {
+            introspection_url: Option::None,
+            client_id: Option::None,
+            client_secret: Option::None,
+            nickname_claim: "username".to_string(),
+        }
+    }
+
+    /// Merges `self` with `other` instance. All values will be overwritten by
+    /// `other`'s values.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, other: Self) {
+        self.introspection_url = other.introspection_url;
+        self.client_id = other.client_id;
+        self.client_secret = other.client_secret;
+        self.nickname_claim = other.nickname_claim;
+    }
+
+    /// Returns the URL of the token introspection endpoint.
+    pub fn get_introspection_url(&self) -> Option<&String> {
+        self.introspection_url.as_ref()
+    }
+
+    /// Sets the URL of the token introspection endpoint.
+    pub fn set_introspection_url(&mut self, introspection_url: Option<String>) {
+        self.introspection_url = introspection_url;
+    }
+
+    /// Returns the client ID the server authenticates with to the introspection
+    /// endpoint.
+    pub fn get_client_id(&self) -> Option<&String> {
+        self.client_id.as_ref()
+    }
+
+    /// Sets the client ID the server authenticates with to the introspection
+    /// endpoint.
+    pub fn set_client_id(&mut self, client_id: Option<String>) {
+        self.client_id = client_id;
+    }
+
+    /// Returns the client secret the server authenticates with to the
+    /// introspection endpoint.
+    pub fn get_client_secret(&self) -> Option<&String> {
+        self.client_secret.as_ref()
+    }
+
+    /// Sets the client secret the server authenticates with to the introspection
+    /// endpoint.
+    pub fn set_client_secret(&mut self, client_secret: Option<String>) {
+        self.client_secret = client_secret;
+    }
+
+    /// Returns the name of the field of the introspection response which must be
+    /// equal to the nickname of the user.
+    pub fn get_nickname_claim(&self) -> &String {
+        &self.nickname_claim
+    }
+
+    /// Sets the name of the field of the introspection response which must be
+    /// equal to the nickname of the user.
+    pub fn set_nickname_claim(&mut self, nickname_claim: String) {
+        self.nickname_claim = nickname_claim;
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split command and argument:
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
+        // Parse based on the command
+        match command {
+            "client-id" => arg.ok_or("An argument was expected after `auth oauth client-id`".to_string())
+                .map(|arg| self.set_client_id(Option::Some(arg.to_string()))),
+            "client-secret" => arg.ok_or("An argument was expected after `auth oauth client-secret`".to_string())
+                .map(|arg| self.set_client_secret(Option::Some(arg.to_string()))),
+            "introspection-url" => arg.ok_or("A URL was expected after `auth oauth introspection-url`".to_string())
+                .map(|arg| self.set_introspection_url(Option::Some(arg.to_string()))),
+            "nickname-claim" => arg.ok_or("An argument was expected after `auth oauth nickname-claim`".to_string())
+                .map(|arg| self.set_nickname_claim(arg.to_string())),
+            other => Result::Err(format!("`auth oauth {}`: unknown sub-command", other))
+        }
+    }
+}
+
+/// Represents configurability of verification of e-mail addresses of new users.
+/// When the verification is turned on, users must give an e-mail address when
+/// registering and the account stays inactive until the user enters the code
+/// sent to the e-mail address.
+pub struct EmailVerificationConfig {
+    verification: bool,
+    command: Vec<String>,
+    code_ttl: Duration,
+    timeout: Duration,
+}
+
+impl Default for EmailVerificationConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EmailVerificationConfig {
+    /// Creates a new [`EmailVerificationConfig`] with the verification turned
+    /// off. Using this constructor is same as using [`Default`]'s
+    /// implementation.
+    pub fn new() -> Self {
+        Self {
+            verification: false,
+            command: Vec::new(),
+            code_ttl: Duration::from_secs(15 * 60),
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Merges `self` with `other` instance. All values will be overwritten by
+    /// `other`'s values.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, other: Self) {
+        self.verification = other.verification;
+        self.command = other.command;
+        self.code_ttl = other.code_ttl;
+        self.timeout = other.timeout;
+    }
+
+    /// Returns whether e-mail addresses of new users are verified.
+    pub fn is_verification(&self) -> bool {
+        self.verification
+    }
+
+    /// Sets whether e-mail addresses of new users are verified.
+    pub fn set_verification(&mut self, verification: bool) {
+        self.verification = verification;
+    }
+
+    /// Returns the command used to send verification codes: the program followed
+    /// by its arguments. The e-mail address and the code are appended as the
+    /// last two arguments after `--` when the command is run.
+    pub fn get_command(&self) -> &Vec<String> {
+        &self.command
+    }
+
+    /// Sets the command used to send verification codes. See
+    /// [`EmailVerificationConfig::get_command`] for more information.
+    pub fn set_command(&mut self, command: Vec<String>) {
+        self.command = command;
+    }
+
+    /// Returns how long a verification code is valid.
+    pub fn get_code_ttl(&self) -> Duration {
+        self.code_ttl
+    }
+
+    /// Sets how long a verification code is valid.
+    pub fn set_code_ttl(&mut self, code_ttl: Duration) {
+        self.code_ttl = code_ttl;
+    }
+
+    /// Returns how long the command sending a verification code may run before
+    /// it is killed and the code is considered not sent.
+    pub fn get_timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Sets how long the command sending a verification code may run. See
+    /// [`EmailVerificationConfig::get_timeout`] for more information.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split command and argument:
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
+        // Parse based on the command
+        match command {
+            "code-ttl" => self.__process_code_ttl(arg),
+            "command" => self.__process_command(arg),
+            "timeout" => self.__process_timeout(arg),
+            "verification" => self.__process_verification(arg),
+            other => Result::Err(format!("`auth email {}`: unknown sub-command", other))
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_code_ttl(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `auth email code-ttl`".to_string())
+            .and_then(parse_duration)
+            .map(|code_ttl| self.set_code_ttl(code_ttl))
+    }
+
+    #[doc(hidden)]
+    fn __process_command(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("A command was expected after `auth email command`".to_string())
+            .map(|arg| self.set_command(REGEX_WHITESPACE.split(arg).map(str::to_string).collect()))
+    }
+
+    #[doc(hidden)]
+    fn __process_timeout(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `auth email timeout`".to_string())
+            .and_then(parse_duration)
+            .map(|timeout| self.set_timeout(timeout))
+    }
+
+    #[doc(hidden)]
+    fn __process_verification(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `auth email verification`".to_string())
+            .and_then(parse_switch)
+            .map(|verification| self.set_verification(verification))
+    }
+}
@@ -0,0 +1,241 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::split_command;
+
+use mdchat_common::channel::ChannelMode;
+
+use regex::Regex;
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+/// Represents configurability of chat channels.
+pub struct ChannelConfig {
+    channels: BTreeSet<String>,
+    modes: BTreeMap<String, ChannelMode>,
+    membership_file: PathBuf,
+    name_pattern: Regex,
+    state_file: PathBuf,
+    archive_dir: PathBuf,
+}
+
+impl Default for ChannelConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChannelConfig {
+    /// Creates a new [`ChannelConfig`] instance with no channels. Using this
+    /// constructor is same as using [`Default`]'s implementation.
+    pub fn new() -> Self {
+        Self {
+            channels: BTreeSet::new(),
+            modes: BTreeMap::new(),
+            membership_file: PathBuf::from("/var/lib/mdchat-server/channel-members"),
+            name_pattern: Regex::new(r"^[a-z0-9][a-z0-9_-]{0,31}$").unwrap(),
+            state_file: PathBuf::from("/var/lib/mdchat-server/channels"),
+            archive_dir: PathBuf::from("/var/lib/mdchat-server/channel-archive"),
+        }
+    }
+
+    /// Merges `self` with `other` instance. Channels of both instances are kept.
+    /// If a channel has a mode set in both instances, `other`'s mode is used.
+    /// Other values will be overwritten by `other`'s values.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, mut other: Self) {
+        self.channels.append(&mut other.channels);
+        self.modes.append(&mut other.modes);
+        self.membership_file = other.membership_file;
+        self.name_pattern = other.name_pattern;
+        self.state_file = other.state_file;
+        self.archive_dir = other.archive_dir;
+    }
+
+    /// Adds a channel which should exist when the server starts.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `name`: name of the channel
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if the channel was added
+    ///  -  [`Result::Err`] if the name of the channel is not valid
+    pub fn add(&mut self, name: String) -> Result<(), String> {
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Result::Err(format!("`{}` is not a valid channel name", name))
+        }
+        self.channels.insert(name);
+        Result::Ok(())
+    }
+
+    /// Returns an immutable borrow to the inner [`BTreeSet`] containing names of
+    /// channels which should exist when the server starts.
+    pub fn get_channels(&self) -> &BTreeSet<String> {
+        &self.channels
+    }
+
+    /// Returns the mode given channel has when the server starts. Channels without
+    /// an explicitly set mode are [public](ChannelMode::Public).
+    pub fn get_mode(&self, name: &str) -> ChannelMode {
+        self.modes.get(name).copied().unwrap_or(ChannelMode::Public)
+    }
+
+    /// Sets the mode given channel has when the server starts.
+    pub fn set_mode(&mut self, name: String, mode: ChannelMode) {
+        self.modes.insert(name, mode);
+    }
+
+    /// Returns the path to the file where members of channels are saved, so
+    /// memberships survive restarts of the server.
+    pub fn get_membership_file(&self) -> &PathBuf {
+        &self.membership_file
+    }
+
+    /// Sets the path to the file where members of channels are saved.
+    pub fn set_membership_file(&mut self, membership_file: PathBuf) {
+        self.membership_file = membership_file;
+    }
+
+    /// Returns the pattern names of channels created by users must match.
+    pub fn get_name_pattern(&self) -> &Regex {
+        &self.name_pattern
+    }
+
+    /// Sets the pattern names of channels created by users must match.
+    pub fn set_name_pattern(&mut self, name_pattern: Regex) {
+        self.name_pattern = name_pattern;
+    }
+
+    /// Returns warnings about rules contradicting each other, see
+    /// [`Config::lint`](crate::Config::lint).
+    pub fn lint(&self) -> Vec<String> {
+        self.modes.keys()
+            .filter(|name| !self.channels.contains(*name))
+            .map(|name| format!("`channel mode {}` has no effect, since the channel is not added by `channel add`", name))
+            .collect()
+    }
+
+    /// Returns whether given name can be used for a channel created by a user.
+    /// Besides matching the name pattern, the name must not contain any
+    /// whitespace.
+    pub fn is_valid_name(&self, name: &str) -> bool {
+        !name.is_empty() && !name.contains(char::is_whitespace) && self.name_pattern.is_match(name)
+    }
+
+    /// Returns the path to the file where channels created by users are saved
+    /// together with their owners and moderators.
+    pub fn get_state_file(&self) -> &PathBuf {
+        &self.state_file
+    }
+
+    /// Sets the path to the file where channels created by users are saved.
+    pub fn set_state_file(&mut self, state_file: PathBuf) {
+        self.state_file = state_file;
+    }
+
+    /// Returns the path to the directory where the message history of deleted
+    /// channels is archived.
+    pub fn get_archive_dir(&self) -> &PathBuf {
+        &self.archive_dir
+    }
+
+    /// Sets the path to the directory where the message history of deleted
+    /// channels is archived.
+    pub fn set_archive_dir(&mut self, archive_dir: PathBuf) {
+        self.archive_dir = archive_dir;
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split command and argument:
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
+        // Parse based on the command
+        match command {
+            "add" => self.__process_add(arg),
+            "archive-dir" => self.__process_archive_dir(arg),
+            "membership-file" => self.__process_membership_file(arg),
+            "mode" => self.__process_mode(arg),
+            "name-pattern" => self.__process_name_pattern(arg),
+            "state-file" => self.__process_state_file(arg),
+            other => Result::Err(format!("`channel {}`: unknown sub-command", other))
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_add(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("A channel name was expected after `channel add`".to_string())
+            .and_then(|name| self.add(name.to_string()))
+    }
+
+    #[doc(hidden)]
+    fn __process_archive_dir(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("A path was expected after `channel archive-dir`".to_string())
+            .map(|path| self.set_archive_dir(PathBuf::from(path)))
+    }
+
+    #[doc(hidden)]
+    fn __process_membership_file(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("A path was expected after `channel membership-file`".to_string())
+            .map(|path| self.set_membership_file(PathBuf::from(path)))
+    }
+
+    #[doc(hidden)]
+    fn __process_mode(&mut self, arg: Option<&str>) -> Result<(), String> {
+        let arg = arg.ok_or("A channel name was expected after `channel mode`".to_string())?;
+        let (name, mode) = split_command(arg)?;
+        let mode = mode
+            .ok_or(format!("A mode was expected after `channel mode {}`", name))?
+            .parse()?;
+        self.set_mode(name.to_string(), mode);
+        Result::Ok(())
+    }
+
+    #[doc(hidden)]
+    fn __process_name_pattern(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("A pattern was expected after `channel name-pattern`".to_string())
+            .and_then(|arg| Regex::new(arg)
+                .map_err(|err| format!("Could not parse given regex after `channel name-pattern`: {}", err)))
+            .map(|regex| self.set_name_pattern(regex))
+    }
+
+    #[doc(hidden)]
+    fn __process_state_file(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("A path was expected after `channel state-file`".to_string())
+            .map(|path| self.set_state_file(PathBuf::from(path)))
+    }
+}
@@ -0,0 +1,164 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::split_command;
+use crate::parse_switch;
+
+/// Represents configurability of which client applications may connect and
+/// which sessions they may open.
+pub struct ClientConfig {
+    min_version: Option<Vec<u64>>,
+    read_only: bool,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClientConfig {
+    /// Creates a new [`ClientConfig`] instance allowing all client versions and
+    /// read-only sessions. Using this constructor is same as using [`Default`]'s
+    /// implementation.
+    pub fn new() -> Self {
+        Self { min_version: Option::None, read_only: true }
+    }
+
+    /// Merges `self` with `other` instance. Minimum version of the `other`
+    /// instance is used, if it is set. Other values will be overwritten by
+    /// `other`'s values.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, other: Self) {
+        if other.min_version.is_some() {
+            self.min_version = other.min_version;
+        }
+        self.read_only = other.read_only;
+    }
+
+    /// Returns whether clients may open read-only sessions, which receive
+    /// messages, but cannot send them.
+    pub fn get_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Sets whether clients may open read-only sessions.
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// Returns the minimum version of client applications allowed to connect,
+    /// or [`Option::None`] if there is no such limit.
+    pub fn get_min_version(&self) -> Option<String> {
+        self.min_version.as_ref().map(|version| version.iter()
+            .map(u64::to_string)
+            .collect::<Vec<_>>()
+            .join("."))
+    }
+
+    /// Sets the minimum version of client applications allowed to connect.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `min_version`: the minimum version, for example `0.2.0`, or
+    ///     [`Option::None`] to allow all versions
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if the version was set
+    ///  -  [`Result::Err`] if given version is not valid
+    pub fn set_min_version(&mut self, min_version: Option<&str>) -> Result<(), String> {
+        self.min_version = match min_version {
+            Option::Some(version) => Option::Some(parse_version(version)
+                .ok_or(format!("`{}` is not a valid version", version))?),
+            Option::None => Option::None,
+        };
+        Result::Ok(())
+    }
+
+    /// Returns if a client application of given version is allowed to connect.
+    /// Versions are compared number by number, anything after a hyphen (`-`) or
+    /// a plus sign (`+`) is ignored. Versions which are not valid are allowed
+    /// only if there is no minimum version.
+    pub fn is_allowed_version(&self, version: &str) -> bool {
+        match &self.min_version {
+            Option::Some(min_version) => parse_version(version)
+                .is_some_and(|version| version >= *min_version),
+            Option::None => true,
+        }
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split command and argument:
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
+        // Parse based on the command
+        match command {
+            "min-version" => self.__process_min_version(arg),
+            "read-only" => self.__process_read_only(arg),
+            other => Result::Err(format!("`client {}`: unknown sub-command", other))
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_min_version(&mut self, arg: Option<&str>) -> Result<(), String> {
+        match arg {
+            Option::Some("off") => self.set_min_version(Option::None),
+            Option::Some(version) => self.set_min_version(Option::Some(version)),
+            Option::None => Result::Err("A version was expected after `client min-version`".to_string()),
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_read_only(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("`on` or `off` was expected after `client read-only`".to_string())
+            .and_then(parse_switch)
+            .map(|read_only| self.set_read_only(read_only))
+    }
+}
+
+/// Parses a version such as `0.2.0` into its numbers. Pre-release and build
+/// metadata (anything after `-` or `+`) are ignored. Trailing zeros are removed,
+/// so `0.2` and `0.2.0` are equal.
+#[doc(hidden)]
+fn parse_version(version: &str) -> Option<Vec<u64>> {
+    let core = version.split(['-', '+']).next().unwrap_or_default();
+    let mut numbers = core.split('.')
+        .map(|number| number.parse().ok())
+        .collect::<Option<Vec<u64>>>()?;
+    while numbers.last() == Option::Some(&0) {
+        numbers.pop();
+    }
+    Option::Some(numbers)
+}
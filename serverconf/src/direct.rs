@@ -0,0 +1,157 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::split_command;
+use crate::parse_duration;
+use crate::parse_switch;
+
+use std::num::NonZeroU32;
+use std::time::Duration;
+
+/// Represents configurability of direct messages. Direct messages addressed to
+/// users who are offline are stored by the server until the recipient logs in.
+/// Optionally, messages addressed to users who do not want to be disturbed are
+/// stored the same way until they change their availability.
+pub struct DirectMessageConfig {
+    max_stored: NonZeroU32,
+    offline_ttl: Option<Duration>,
+    defer_when_dnd: bool,
+}
+
+impl Default for DirectMessageConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DirectMessageConfig {
+    /// Creates a new [`DirectMessageConfig`] with default values, that is at most
+    /// 100 stored messages per user, which are kept for 30 days, and messages
+    /// delivered immediately even if the recipient does not want to be
+    /// disturbed. Using this
+    /// constructor is same as using [`Default`]'s implementation.
+    pub fn new() -> Self {
+        Self {
+            max_stored: NonZeroU32::new(100).unwrap(),
+            offline_ttl: Option::Some(Duration::from_secs(30 * 24 * 60 * 60)),
+            defer_when_dnd: false,
+        }
+    }
+
+    /// Merges `self` with `other` instance. All values will be overwritten by
+    /// `other`'s values.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, other: Self) {
+        self.max_stored = other.max_stored;
+        self.offline_ttl = other.offline_ttl;
+        self.defer_when_dnd = other.defer_when_dnd;
+    }
+
+    /// Returns the maximum number of direct messages stored for a single user
+    /// who is offline.
+    pub fn get_max_stored(&self) -> NonZeroU32 {
+        self.max_stored
+    }
+
+    /// Sets the maximum number of direct messages stored for a single user who
+    /// is offline.
+    pub fn set_max_stored(&mut self, max_stored: NonZeroU32) {
+        self.max_stored = max_stored;
+    }
+
+    /// Returns for how long direct messages addressed to a user who is offline
+    /// are stored, or [`Option::None`] if they are stored until the user logs in.
+    pub fn get_offline_ttl(&self) -> Option<Duration> {
+        self.offline_ttl
+    }
+
+    /// Sets for how long direct messages addressed to a user who is offline are
+    /// stored. [`Option::None`] means until the user logs in.
+    pub fn set_offline_ttl(&mut self, offline_ttl: Option<Duration>) {
+        self.offline_ttl = offline_ttl;
+    }
+
+    /// Returns whether direct messages addressed to users who do not want to be
+    /// disturbed are stored until they change their availability.
+    pub fn is_defer_when_dnd(&self) -> bool {
+        self.defer_when_dnd
+    }
+
+    /// Sets whether direct messages addressed to users who do not want to be
+    /// disturbed are stored until they change their availability.
+    pub fn set_defer_when_dnd(&mut self, defer_when_dnd: bool) {
+        self.defer_when_dnd = defer_when_dnd;
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split command and argument:
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
+        // Parse based on the command
+        match command {
+            "defer-when-dnd" => self.__process_defer_when_dnd(arg),
+            "max-stored" => self.__process_max_stored(arg),
+            "offline-ttl" => self.__process_offline_ttl(arg),
+            other => Result::Err(format!("`direct {}`: unknown sub-command", other))
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_defer_when_dnd(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("`on` or `off` was expected after `direct defer-when-dnd`".to_string())
+            .and_then(parse_switch)
+            .map(|defer_when_dnd| self.set_defer_when_dnd(defer_when_dnd))
+    }
+
+    #[doc(hidden)]
+    fn __process_max_stored(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `direct max-stored`".to_string())
+            .and_then(|arg| arg.parse()
+                .map_err(|err| format!("A positive number was expected after `direct max-stored`: {}", err)))
+            .map(|max_stored| self.set_max_stored(max_stored))
+    }
+
+    #[doc(hidden)]
+    fn __process_offline_ttl(&mut self, arg: Option<&str>) -> Result<(), String> {
+        match arg {
+            Option::None => Result::Err("An argument was expected after `direct offline-ttl`".to_string()),
+            Option::Some("off") => {
+                self.set_offline_ttl(Option::None);
+                Result::Ok(())
+            },
+            Option::Some(arg) => parse_duration(arg)
+                .map(|ttl| self.set_offline_ttl(Option::Some(ttl))),
+        }
+    }
+}
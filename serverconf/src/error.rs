@@ -22,6 +22,9 @@ use std::io;
 
 use thiserror::Error;
 
+/// Name used in place of a file path for configuration given as a string.
+pub const STRING_SOURCE: &str = "<string>";
+
 /// A type alias for result of parsing a configuration file.
 pub type ConfigParseResult<T> = Result<T, ConfigParseError>;
 
@@ -40,13 +43,16 @@ impl ConfigParseError {
     }
 
     /// Creates a new [`ConfigParseError`] instance with [`Syntax`] error kind using
-    /// given given file path, line number and error description.
+    /// given file path, line number, text of the line and error description. If
+    /// the description quotes a token of the line in backticks, the column of
+    /// the token is found, too.
     ///
     /// [`Syntax`]: ConfigParseErrorKind::Syntax
-    pub fn syntax_error(file_path: String, line_num: u32, description: String) -> Self {
+    pub fn syntax_error(file_path: String, line_num: u32, line: &str, description: String) -> Self {
+        let column = find_column(line, &description);
         Self {
             file_path,
-            kind: ConfigParseErrorKind::Syntax { line_num, description }
+            kind: ConfigParseErrorKind::Syntax { line_num, column, line: line.trim().to_string(), description }
         }
     }
 
@@ -90,6 +96,10 @@ pub enum ConfigParseErrorKind {
     Syntax {
         /// Line number, where error was found
         line_num: u32,
+        /// Column of the token which caused the error, if known
+        column: Option<u32>,
+        /// Text of the line, where error was found
+        line: String,
         /// Description of the error
         description: String
     }
@@ -99,7 +109,25 @@ impl Display for ConfigParseErrorKind {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
             Self::Io { io_error } => write!(f, "I/O error: {}", io_error),
-            Self::Syntax { line_num, description } => write!(f, "Line {}: {}", line_num, description)
+            Self::Syntax { line_num, column: Option::Some(column), line, description } =>
+                write!(f, "Line {}, column {}: {}\n    {}", line_num, column, description, line),
+            Self::Syntax { line_num, column: Option::None, line, description } =>
+                write!(f, "Line {}: {}\n    {}", line_num, description, line),
         }
     }
 }
+
+/// Returns the column (counted from 1 in characters) of the last token quoted in
+/// backticks in given description which can be found in given line. Option
+/// names are quoted in descriptions too, so later tokens, which are usually the
+/// offending values, are preferred.
+#[doc(hidden)]
+fn find_column(line: &str, description: &str) -> Option<u32> {
+    description.split('`')
+        .skip(1)
+        .step_by(2)
+        .filter(|token| !token.is_empty())
+        .filter_map(|token| line.find(token))
+        .last()
+        .map(|index| line[..index].chars().count() as u32 + 1)
+}
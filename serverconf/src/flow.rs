@@ -0,0 +1,248 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::parse_duration;
+use crate::split_command;
+
+use std::time::Duration;
+
+/// Represents configurability of flow control of connections. Commands sent to a
+/// client wait in a queue until they are written to the connection. If the
+/// queue of a client on a slow link grows over the pause limits, the server
+/// stops reading commands of the client until the queue shrinks to a half. The
+/// client is disconnected if it stays paused for too long or if its queue grows
+/// over the maximum size.
+///
+/// Clients which have not sent anything for a while are sent keepalive pings and
+/// are disconnected if they do not respond, so connections of peers which have
+/// gone away silently do not stay open.
+pub struct FlowControlConfig {
+    pause_bytes: usize,
+    pause_frames: usize,
+    max_bytes: usize,
+    slow_timeout: Duration,
+    batch_window: Duration,
+    keepalive: Option<Duration>,
+    idle_timeout: Duration,
+}
+
+impl Default for FlowControlConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FlowControlConfig {
+    /// Creates a new [`FlowControlConfig`] with default values, that is pausing
+    /// at 256 KiB or 1024 frames, disconnecting at 4 MiB or after being paused
+    /// for 30 seconds, writing queued frames without waiting for more and pinging
+    /// clients silent for 30 seconds, which are disconnected after 90 seconds of
+    /// silence. Using this constructor is same as using [`Default`]'s
+    /// implementation.
+    pub fn new() -> Self {
+        Self {
+            pause_bytes: 256 * 1024,
+            pause_frames: 1024,
+            max_bytes: 4 * 1024 * 1024,
+            slow_timeout: Duration::from_secs(30),
+            batch_window: Duration::ZERO,
+            keepalive: Option::Some(Duration::from_secs(30)),
+            idle_timeout: Duration::from_secs(90),
+        }
+    }
+
+    /// Merges `self` with `other` instance. All values will be overwritten by
+    /// `other`'s values.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, other: Self) {
+        self.pause_bytes = other.pause_bytes;
+        self.pause_frames = other.pause_frames;
+        self.max_bytes = other.max_bytes;
+        self.slow_timeout = other.slow_timeout;
+        self.batch_window = other.batch_window;
+        self.keepalive = other.keepalive;
+        self.idle_timeout = other.idle_timeout;
+    }
+
+    /// Returns how many bytes may wait in the queue of a client before the
+    /// client is paused.
+    pub fn get_pause_bytes(&self) -> usize {
+        self.pause_bytes
+    }
+
+    /// Sets how many bytes may wait in the queue of a client before the client
+    /// is paused.
+    pub fn set_pause_bytes(&mut self, pause_bytes: usize) {
+        self.pause_bytes = pause_bytes;
+    }
+
+    /// Returns how many frames may wait in the queue of a client before the
+    /// client is paused.
+    pub fn get_pause_frames(&self) -> usize {
+        self.pause_frames
+    }
+
+    /// Sets how many frames may wait in the queue of a client before the client
+    /// is paused.
+    pub fn set_pause_frames(&mut self, pause_frames: usize) {
+        self.pause_frames = pause_frames;
+    }
+
+    /// Returns how many bytes may wait in the queue of a client before the
+    /// client is disconnected.
+    pub fn get_max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+
+    /// Sets how many bytes may wait in the queue of a client before the client
+    /// is disconnected.
+    pub fn set_max_bytes(&mut self, max_bytes: usize) {
+        self.max_bytes = max_bytes;
+    }
+
+    /// Returns how long a client may stay paused before it is disconnected.
+    pub fn get_slow_timeout(&self) -> Duration {
+        self.slow_timeout
+    }
+
+    /// Sets how long a client may stay paused before it is disconnected.
+    pub fn set_slow_timeout(&mut self, slow_timeout: Duration) {
+        self.slow_timeout = slow_timeout;
+    }
+
+    /// Returns how long the writer of a connection waits for more frames after
+    /// the first one, so they are written together.
+    pub fn get_batch_window(&self) -> Duration {
+        self.batch_window
+    }
+
+    /// Sets how long the writer of a connection waits for more frames after the
+    /// first one, so they are written together.
+    pub fn set_batch_window(&mut self, batch_window: Duration) {
+        self.batch_window = batch_window;
+    }
+
+    /// Returns how long a client may be silent before it is sent a keepalive ping,
+    /// or [`Option::None`] if clients are not pinged.
+    pub fn get_keepalive(&self) -> Option<Duration> {
+        self.keepalive
+    }
+
+    /// Sets how long a client may be silent before it is sent a keepalive ping.
+    /// If [`Option::None`] is given, clients are not pinged.
+    pub fn set_keepalive(&mut self, keepalive: Option<Duration>) {
+        self.keepalive = keepalive;
+    }
+
+    /// Returns how long a pinged client may be silent before it is disconnected.
+    pub fn get_idle_timeout(&self) -> Duration {
+        self.idle_timeout
+    }
+
+    /// Sets how long a pinged client may be silent before it is disconnected.
+    pub fn set_idle_timeout(&mut self, idle_timeout: Duration) {
+        self.idle_timeout = idle_timeout;
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split command and argument:
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
+        // Parse based on the command
+        match command {
+            "pause-bytes" => Self::__parse_positive("pause-bytes", arg).map(|pause_bytes| self.set_pause_bytes(pause_bytes)),
+            "pause-frames" => Self::__parse_positive("pause-frames", arg).map(|pause_frames| self.set_pause_frames(pause_frames)),
+            "max-bytes" => Self::__parse_positive("max-bytes", arg).map(|max_bytes| self.set_max_bytes(max_bytes)),
+            "slow-timeout" => self.__process_slow_timeout(arg),
+            "batch-window" => self.__process_batch_window(arg),
+            "keepalive" => self.__process_keepalive(arg),
+            "idle-timeout" => self.__process_idle_timeout(arg),
+            other => Result::Err(format!("`flow {}`: unknown sub-command", other))
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_slow_timeout(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `flow slow-timeout`".to_string())
+            .and_then(parse_duration)
+            .map(|slow_timeout| self.set_slow_timeout(slow_timeout))
+    }
+
+    #[doc(hidden)]
+    fn __process_batch_window(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `flow batch-window`".to_string())
+            .and_then(parse_duration)
+            .map(|batch_window| self.set_batch_window(batch_window))
+    }
+
+    #[doc(hidden)]
+    fn __process_keepalive(&mut self, arg: Option<&str>) -> Result<(), String> {
+        match arg {
+            Option::None => Result::Err("An argument was expected after `flow keepalive`".to_string()),
+            Option::Some("off") => {
+                self.set_keepalive(Option::None);
+                Result::Ok(())
+            },
+            Option::Some(arg) => match parse_duration(arg)? {
+                Duration::ZERO => Result::Err("`flow keepalive` must be greater than 0".to_string()),
+                keepalive => {
+                    self.set_keepalive(Option::Some(keepalive));
+                    Result::Ok(())
+                },
+            },
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_idle_timeout(&mut self, arg: Option<&str>) -> Result<(), String> {
+        match arg.map(parse_duration) {
+            Option::None => Result::Err("An argument was expected after `flow idle-timeout`".to_string()),
+            Option::Some(Result::Ok(Duration::ZERO)) => Result::Err("`flow idle-timeout` must be greater than 0".to_string()),
+            Option::Some(Result::Ok(idle_timeout)) => {
+                self.set_idle_timeout(idle_timeout);
+                Result::Ok(())
+            },
+            Option::Some(Result::Err(err)) => Result::Err(err),
+        }
+    }
+
+    #[doc(hidden)]
+    fn __parse_positive(command: &str, arg: Option<&str>) -> Result<usize, String> {
+        match arg.map(str::parse::<usize>) {
+            Option::None => Result::Err(format!("An argument was expected after `flow {}`", command)),
+            Option::Some(Result::Ok(0)) => Result::Err(format!("`flow {}` must be greater than 0", command)),
+            Option::Some(Result::Ok(value)) => Result::Ok(value),
+            Option::Some(Result::Err(err)) => Result::Err(format!("A positive number was expected after `flow {}`: {}", command, err)),
+        }
+    }
+}
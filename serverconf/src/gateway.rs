@@ -0,0 +1,152 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::split_command;
+use crate::SocketOptions;
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// Represents configurability of the WebSocket gateway. The gateway lets browser
+/// clients connect to the server over WebSocket. Every WebSocket connection is
+/// bridged to a regular connection to the server, so the gateway works with any
+/// version of the protocol.
+pub struct GatewayConfig {
+    listen: Option<SocketAddr>,
+    upstream: Option<SocketAddr>,
+    socket: SocketOptions,
+}
+
+impl Default for GatewayConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GatewayConfig {
+    /// Creates a new [`GatewayConfig`] with default values, that is with the
+    /// gateway disabled and TCP keepalive probes sent after a minute of
+    /// inactivity. Using this constructor is same as using [`Default`]'s
+    /// implementation.
+    pub fn new() -> Self {
+        let mut socket = SocketOptions::new();
+        socket.set_keepalive(Option::Some(Duration::from_secs(60)));
+        Self {
+            listen: Option::None,
+            upstream: Option::None,
+            socket,
+        }
+    }
+
+    /// Merges `self` with `other` instance. All values will be overwritten by
+    /// `other`'s values.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, other: Self) {
+        self.listen = other.listen;
+        self.upstream = other.upstream;
+        self.socket = other.socket;
+    }
+
+    /// Returns the socket address the gateway listens on for WebSocket
+    /// connections, or [`Option::None`] if the gateway is disabled.
+    pub fn get_listen(&self) -> Option<SocketAddr> {
+        self.listen
+    }
+
+    /// Sets the socket address the gateway listens on for WebSocket connections.
+    /// [`Option::None`] disables the gateway.
+    pub fn set_listen(&mut self, listen: Option<SocketAddr>) {
+        self.listen = listen;
+    }
+
+    /// Returns the socket address of the server the gateway bridges connections
+    /// to, or [`Option::None`] if one of the addresses the server listens on
+    /// should be used.
+    pub fn get_upstream(&self) -> Option<SocketAddr> {
+        self.upstream
+    }
+
+    /// Sets the socket address of the server the gateway bridges connections to.
+    /// [`Option::None`] means one of the addresses the server listens on.
+    pub fn set_upstream(&mut self, upstream: Option<SocketAddr>) {
+        self.upstream = upstream;
+    }
+
+    /// Returns options of the socket the gateway listens on.
+    pub fn get_socket(&self) -> &SocketOptions {
+        &self.socket
+    }
+
+    /// Sets options of the socket the gateway listens on.
+    pub fn set_socket(&mut self, socket: SocketOptions) {
+        self.socket = socket;
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split command and argument:
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
+        // Parse based on the command
+        match command {
+            "listen" => self.__process_listen(arg),
+            "upstream" => self.__process_upstream(arg),
+            command if SocketOptions::is_sub_command(command) => self.socket.process_line(line, "gateway"),
+            other => Result::Err(format!("`gateway {}`: unknown sub-command", other))
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_listen(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `gateway listen`".to_string())
+            .and_then(|arg| parse_socket_addr(arg, "gateway listen"))
+            .map(|listen| self.set_listen(listen))
+    }
+
+    #[doc(hidden)]
+    fn __process_upstream(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `gateway upstream`".to_string())
+            .and_then(|arg| parse_socket_addr(arg, "gateway upstream"))
+            .map(|upstream| self.set_upstream(upstream))
+    }
+}
+
+/// Parses a socket address, or `off` meaning [`Option::None`].
+#[doc(hidden)]
+fn parse_socket_addr(arg: &str, option: &str) -> Result<Option<SocketAddr>, String> {
+    match arg {
+        "off" => Result::Ok(Option::None),
+        arg => arg.parse()
+            .map(Option::Some)
+            .map_err(|err| format!("A socket address was expected after `{}`: {}", option, err)),
+    }
+}
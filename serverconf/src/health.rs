@@ -0,0 +1,119 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::split_command;
+use crate::SocketOptions;
+
+use std::net::SocketAddr;
+
+/// Represents configurability of the health endpoint. The endpoint reports
+/// whether the server is alive and ready to accept connections, together with
+/// basic statistics, for monitoring and orchestration systems like Kubernetes.
+pub struct HealthConfig {
+    listen: Option<SocketAddr>,
+    socket: SocketOptions,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HealthConfig {
+    /// Creates a new [`HealthConfig`] with default values, that is with the
+    /// endpoint disabled. Using this constructor is same as using [`Default`]'s
+    /// implementation.
+    pub fn new() -> Self {
+        Self {
+            listen: Option::None,
+            socket: SocketOptions::new(),
+        }
+    }
+
+    /// Merges `self` with `other` instance. All values will be overwritten by
+    /// `other`'s values.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, other: Self) {
+        self.listen = other.listen;
+        self.socket = other.socket;
+    }
+
+    /// Returns the socket address the health endpoint listens on, or
+    /// [`Option::None`] if the endpoint is disabled.
+    pub fn get_listen(&self) -> Option<SocketAddr> {
+        self.listen
+    }
+
+    /// Sets the socket address the health endpoint listens on. [`Option::None`]
+    /// disables the endpoint.
+    pub fn set_listen(&mut self, listen: Option<SocketAddr>) {
+        self.listen = listen;
+    }
+
+    /// Returns options of the socket the health endpoint listens on.
+    pub fn get_socket(&self) -> &SocketOptions {
+        &self.socket
+    }
+
+    /// Sets options of the socket the health endpoint listens on.
+    pub fn set_socket(&mut self, socket: SocketOptions) {
+        self.socket = socket;
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split command and argument:
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
+        // Parse based on the command
+        match command {
+            "listen" => self.__process_listen(arg),
+            command if SocketOptions::is_sub_command(command) => self.socket.process_line(line, "health"),
+            other => Result::Err(format!("`health {}`: unknown sub-command", other))
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_listen(&mut self, arg: Option<&str>) -> Result<(), String> {
+        match arg {
+            Option::None => Result::Err("An argument was expected after `health listen`".to_string()),
+            Option::Some("off") => {
+                self.set_listen(Option::None);
+                Result::Ok(())
+            },
+            Option::Some(arg) => arg.parse()
+                .map_err(|err| format!("A socket address was expected after `health listen`: {}", err))
+                .map(|listen| self.set_listen(Option::Some(listen))),
+        }
+    }
+}
@@ -0,0 +1,240 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::split_command;
+use crate::parse_duration;
+use crate::parse_switch;
+
+use std::num::NonZeroU32;
+use std::time::Duration;
+
+/// Represents configurability of access to the message history.
+pub struct HistoryConfig {
+    bootstrap: usize,
+    page_size: NonZeroU32,
+    retention: Option<Duration>,
+    retention_check_interval: Duration,
+    retention_dry_run: bool,
+    search_interval: Duration,
+    search_max_results: NonZeroU32,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HistoryConfig {
+    /// Creates a new [`HistoryConfig`] with default values. Using this
+    /// constructor is same as using [`Default`]'s implementation.
+    pub fn new() -> Self {
+        Self {
+            bootstrap: 0,
+            page_size: NonZeroU32::new(50).unwrap(),
+            retention: Option::None,
+            retention_check_interval: Duration::from_secs(60 * 60),
+            retention_dry_run: false,
+            search_interval: Duration::from_secs(5),
+            search_max_results: NonZeroU32::new(100).unwrap(),
+        }
+    }
+
+    /// Merges `self` with `other` instance. All values will be overwritten by
+    /// `other`'s values.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, other: Self) {
+        self.bootstrap = other.bootstrap;
+        self.page_size = other.page_size;
+        self.retention = other.retention;
+        self.retention_check_interval = other.retention_check_interval;
+        self.retention_dry_run = other.retention_dry_run;
+        self.search_interval = other.search_interval;
+        self.search_max_results = other.search_max_results;
+    }
+
+    /// Returns how many newest messages are sent to users who log in for the
+    /// first time, so they do not see an empty chat.
+    pub fn get_bootstrap(&self) -> usize {
+        self.bootstrap
+    }
+
+    /// Sets how many newest messages are sent to users who log in for the first
+    /// time.
+    pub fn set_bootstrap(&mut self, bootstrap: usize) {
+        self.bootstrap = bootstrap;
+    }
+
+    /// Returns the maximum number of messages sent as a single page of the
+    /// message history to clients fetching older messages.
+    pub fn get_page_size(&self) -> NonZeroU32 {
+        self.page_size
+    }
+
+    /// Sets the maximum number of messages sent as a single page of the message
+    /// history.
+    pub fn set_page_size(&mut self, page_size: NonZeroU32) {
+        self.page_size = page_size;
+    }
+
+    /// Returns how old messages must be to be removed from the message history.
+    /// [`Option::None`] means that messages are kept forever.
+    pub fn get_retention(&self) -> Option<Duration> {
+        self.retention
+    }
+
+    /// Sets how old messages must be to be removed from the message history.
+    /// [`Option::None`] means that messages are kept forever.
+    pub fn set_retention(&mut self, retention: Option<Duration>) {
+        self.retention = retention;
+    }
+
+    /// Returns how often the message history is checked for messages to be
+    /// removed.
+    pub fn get_retention_check_interval(&self) -> Duration {
+        self.retention_check_interval
+    }
+
+    /// Sets how often the message history is checked for messages to be removed.
+    pub fn set_retention_check_interval(&mut self, retention_check_interval: Duration) {
+        self.retention_check_interval = retention_check_interval;
+    }
+
+    /// Returns whether old messages should be only counted instead of being
+    /// removed.
+    pub fn is_retention_dry_run(&self) -> bool {
+        self.retention_dry_run
+    }
+
+    /// Sets whether old messages should be only counted instead of being
+    /// removed.
+    pub fn set_retention_dry_run(&mut self, retention_dry_run: bool) {
+        self.retention_dry_run = retention_dry_run;
+    }
+
+    /// Returns the minimum time between two searches of the same client.
+    pub fn get_search_interval(&self) -> Duration {
+        self.search_interval
+    }
+
+    /// Sets the minimum time between two searches of the same client.
+    pub fn set_search_interval(&mut self, search_interval: Duration) {
+        self.search_interval = search_interval;
+    }
+
+    /// Returns the maximum number of messages returned by a single search.
+    pub fn get_search_max_results(&self) -> NonZeroU32 {
+        self.search_max_results
+    }
+
+    /// Sets the maximum number of messages returned by a single search.
+    pub fn set_search_max_results(&mut self, search_max_results: NonZeroU32) {
+        self.search_max_results = search_max_results;
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split command and argument:
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
+        // Parse based on the command
+        match command {
+            "bootstrap" => self.__process_bootstrap(arg),
+            "page-size" => self.__process_page_size(arg),
+            "retention" => self.__process_retention(arg),
+            "retention-check-interval" => self.__process_retention_check_interval(arg),
+            "retention-dry-run" => self.__process_retention_dry_run(arg),
+            "search-interval" => self.__process_search_interval(arg),
+            "search-max-results" => self.__process_search_max_results(arg),
+            other => Result::Err(format!("`history {}`: unknown sub-command", other))
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_bootstrap(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `history bootstrap`".to_string())
+            .and_then(|arg| arg.parse()
+                .map_err(|err| format!("A number was expected after `history bootstrap`: {}", err)))
+            .map(|bootstrap| self.set_bootstrap(bootstrap))
+    }
+
+    #[doc(hidden)]
+    fn __process_page_size(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `history page-size`".to_string())
+            .and_then(|arg| arg.parse()
+                .map_err(|err| format!("A positive number was expected after `history page-size`: {}", err)))
+            .map(|page_size| self.set_page_size(page_size))
+    }
+
+    #[doc(hidden)]
+    fn __process_retention(&mut self, arg: Option<&str>) -> Result<(), String> {
+        match arg {
+            Option::None => Result::Err("An argument was expected after `history retention`".to_string()),
+            Option::Some("off") => {
+                self.set_retention(Option::None);
+                Result::Ok(())
+            },
+            Option::Some(arg) => parse_duration(arg)
+                .map(|retention| self.set_retention(Option::Some(retention))),
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_retention_check_interval(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `history retention-check-interval`".to_string())
+            .and_then(parse_duration)
+            .map(|interval| self.set_retention_check_interval(interval))
+    }
+
+    #[doc(hidden)]
+    fn __process_retention_dry_run(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `history retention-dry-run`".to_string())
+            .and_then(parse_switch)
+            .map(|dry_run| self.set_retention_dry_run(dry_run))
+    }
+
+    #[doc(hidden)]
+    fn __process_search_interval(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `history search-interval`".to_string())
+            .and_then(parse_duration)
+            .map(|interval| self.set_search_interval(interval))
+    }
+
+    #[doc(hidden)]
+    fn __process_search_max_results(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `history search-max-results`".to_string())
+            .and_then(|arg| arg.parse()
+                .map_err(|err| format!("A positive number was expected after `history search-max-results`: {}", err)))
+            .map(|max_results| self.set_search_max_results(max_results))
+    }
+}
@@ -0,0 +1,98 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::split_command;
+
+use std::path::PathBuf;
+
+/// Represents configurability of the identity of the server. Server proves its
+/// identity to clients using a long-term identity key, so clients can detect
+/// that another server is impersonating the server. The key does not protect
+/// connections against a man in the middle relaying them to the server.
+pub struct IdentityConfig {
+    key_file: PathBuf,
+}
+
+impl Default for IdentityConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IdentityConfig {
+    /// Creates a new [`IdentityConfig`] with default values. Using this
+    /// constructor is same as using [`Default`]'s implementation.
+    pub fn new() -> Self {
+        Self {
+            key_file: PathBuf::from("/var/lib/mdchat-server/identity.key"),
+        }
+    }
+
+    /// Merges `self` with `other` instance. All values will be overwritten by
+    /// `other`'s values.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, other: Self) {
+        self.key_file = other.key_file;
+    }
+
+    /// Returns the path to the file containing the secret identity key of the
+    /// server. If the file does not exist, a new key is generated and saved into
+    /// it.
+    pub fn get_key_file(&self) -> &PathBuf {
+        &self.key_file
+    }
+
+    /// Sets the path to the file containing the secret identity key of the
+    /// server.
+    pub fn set_key_file(&mut self, key_file: PathBuf) {
+        self.key_file = key_file;
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split command and argument:
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
+        // Parse based on the command
+        match command {
+            "key-file" => self.__process_key_file(arg),
+            other => Result::Err(format!("`identity {}`: unknown sub-command", other))
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_key_file(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("A path was expected after `identity key-file`".to_string())
+            .map(|path| self.set_key_file(PathBuf::from(path)))
+    }
+}
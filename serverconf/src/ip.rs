@@ -17,11 +17,17 @@
 
 use crate::ConfigParseError;
 use crate::ConfigParseResult;
+use crate::STRING_SOURCE;
 use crate::REGEX_WHITESPACE;
+use crate::split_command;
+
+use mdchat_common::moderation::FilterDecision;
 
 use std::cmp::max;
 use std::cmp::min;
 use std::collections::HashSet;
+use std::fmt::Display;
+use std::hash::Hash;
 use std::net::AddrParseError;
 use std::net::IpAddr;
 use std::net::Ipv4Addr;
@@ -153,6 +159,14 @@ impl IpFilteringConfig {
         self.v6_banned_ranges.insert(range)
     }
 
+    /// Returns warnings about rules contradicting each other, see
+    /// [`Config::lint`](crate::Config::lint).
+    pub fn lint(&self) -> Vec<String> {
+        let mut warnings = lint_family(&self.v4_allowed, &self.v4_banned, &self.v4_banned_ranges);
+        warnings.append(&mut lint_family(&self.v6_allowed, &self.v6_banned, &self.v6_banned_ranges));
+        warnings
+    }
+
     /// Returns a [`bool`], if specified IP address is banned (`false`) or not
     /// (`true`).
     ///
@@ -160,25 +174,16 @@ impl IpFilteringConfig {
     ///
     ///  -  `true`, if IP address *is not* banned
     ///  -  `false`, if IP address *is* banned
-    //noinspection RsLift
     pub fn is_allowed(&self, addr: &IpAddr) -> bool {
+        self.explain(addr).is_allowed()
+    }
+
+    /// Returns whether specified IP address is allowed and which rule decided
+    /// it.
+    pub fn explain(&self, addr: &IpAddr) -> FilterDecision {
         match addr {
-            IpAddr::V4(addr) => {
-                if self.v4_allowed.contains(addr) { return true }
-                if self.v4_banned.contains(addr) { return false }
-                for range in &self.v4_banned_ranges {
-                    if range.contains(addr) { return false }
-                }
-                return true
-            },
-            IpAddr::V6(addr) => {
-                if self.v6_allowed.contains(addr) { return true }
-                if self.v6_banned.contains(addr) { return false }
-                for range in &self.v6_banned_ranges {
-                    if range.contains(addr) { return false }
-                }
-                return true
-            }
+            IpAddr::V4(addr) => explain_family(addr, &self.v4_allowed, &self.v4_banned, &self.v4_banned_ranges),
+            IpAddr::V6(addr) => explain_family(addr, &self.v6_allowed, &self.v6_banned, &self.v6_banned_ranges),
         }
     }
 
@@ -190,10 +195,9 @@ impl IpFilteringConfig {
             return Result::Ok(())
         }
         // Process each line:
-        let line_num = 1u32;
-        for line in string.lines() {
+        for (line_num, line) in (1u32..).zip(string.lines()) {
             self.process_line(line)
-                .map_err(|err| ConfigParseError::syntax_error(String::new(), line_num, err))?;
+                .map_err(|err| ConfigParseError::syntax_error(STRING_SOURCE.to_string(), line_num, line, err))?;
         }
         Result::Ok(())
     }
@@ -202,9 +206,8 @@ impl IpFilteringConfig {
         let line = line.trim();
         assert!(!line.is_empty(), "Passed empty string to process_line");
         assert!(!line.contains("\n"), "Passed multi-line input to process_line");
-        let split: Vec<&str> = REGEX_WHITESPACE.splitn(line, 2).collect();
-        let subcommand = split[0];
-        let arg = split.get(1).map(|s| s.trim());
+        let (subcommand, arg) = split_command(line)?;
+        let arg = arg.as_deref();
         // Do something based on the subcommand:
         match subcommand {
             "allow" => self.__process_allow(arg),
@@ -253,4 +256,54 @@ impl IpFilteringConfig {
         // Ban IP range:
         self.ban_range(from, to).map(|_| ())
     }
-}
\ No newline at end of file
+}
+
+/// Returns whether an IP address of one family is allowed and which rule decided
+/// it, see [`IpFilteringConfig::explain`].
+#[doc(hidden)]
+fn explain_family<A>(addr: &A, allowed: &HashSet<A>, banned: &HashSet<A>, banned_ranges: &HashSet<RangeInclusive<A>>) -> FilterDecision
+    where A: Display + Eq + Hash + Ord,
+{
+    if allowed.contains(addr) {
+        return FilterDecision::allowed(format!("`ip allow {}`", addr))
+    }
+    if banned.contains(addr) {
+        return FilterDecision::rejected(format!("`ip ban {}`", addr))
+    }
+    match banned_ranges.iter().find(|range| range.contains(addr)) {
+        Option::Some(range) => FilterDecision::rejected(format!("`ip ban-range {} {}`", range.start(), range.end())),
+        Option::None => FilterDecision::allowed("no rule matches".to_string()),
+    }
+}
+
+/// Returns warnings about IP addresses of one family, see
+/// [`IpFilteringConfig::lint`]: overlapping banned ranges, banned addresses which
+/// are in a banned range and allowed addresses which are not banned at all.
+#[doc(hidden)]
+fn lint_family<A>(allowed: &HashSet<A>, banned: &HashSet<A>, banned_ranges: &HashSet<RangeInclusive<A>>) -> Vec<String>
+    where A: Copy + Display + Ord,
+{
+    let mut warnings = Vec::new();
+    // Sort everything, so warnings are always in the same order:
+    let mut ranges: Vec<&RangeInclusive<A>> = banned_ranges.iter().collect();
+    ranges.sort_by_key(|range| (*range.start(), *range.end()));
+    let mut banned: Vec<&A> = banned.iter().collect();
+    banned.sort();
+    let mut allowed: Vec<&A> = allowed.iter().collect();
+    allowed.sort();
+    for (i, range) in ranges.iter().enumerate() {
+        for other in ranges[i + 1..].iter().filter(|other| other.start() <= range.end()) {
+            warnings.push(format!("Banned ranges `{}` - `{}` and `{}` - `{}` overlap",
+                range.start(), range.end(), other.start(), other.end()));
+        }
+    }
+    for addr in &banned {
+        if let Option::Some(range) = ranges.iter().find(|range| range.contains(addr)) {
+            warnings.push(format!("Banned address `{}` is already in banned range `{}` - `{}`", addr, range.start(), range.end()));
+        }
+    }
+    for addr in allowed.into_iter().filter(|addr| !banned.contains(addr) && !ranges.iter().any(|range| range.contains(*addr))) {
+        warnings.push(format!("`ip allow {}` has no effect, since the address is not banned", addr));
+    }
+    warnings
+}
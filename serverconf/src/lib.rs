@@ -22,48 +22,229 @@
 
 #[doc(hidden)]
 mod error;
+#[doc(hidden)]
+mod syntax;
 
+pub mod auth;
+pub mod channel;
+pub mod client;
+pub mod direct;
+pub mod flow;
+pub mod gateway;
+pub mod health;
+pub mod history;
+pub mod identity;
 pub mod ip;
+pub mod limits;
+pub mod listen;
+pub mod locale;
+pub mod lockdown;
+pub mod memory;
 pub mod message;
+pub mod metrics;
 pub mod nickname;
+pub mod notice;
+pub mod permission;
+pub mod preview;
+pub mod raid;
+pub mod ratelimit;
+pub mod role;
+pub mod server;
+pub mod socket;
+pub mod state;
+pub mod tracing;
+pub mod webhook;
 
+pub use crate::auth::AuthConfig;
+pub use crate::auth::AuthProviderKind;
+pub use crate::auth::EmailVerificationConfig;
+pub use crate::auth::LdapAuthConfig;
+pub use crate::auth::OAuthConfig;
+pub use crate::channel::ChannelConfig;
+pub use crate::client::ClientConfig;
+pub use crate::direct::DirectMessageConfig;
 pub use crate::error::ConfigParseError;
 pub use crate::error::ConfigParseResult;
 pub use crate::error::ConfigParseErrorKind;
+pub use crate::error::STRING_SOURCE;
+pub use crate::flow::FlowControlConfig;
+pub use crate::gateway::GatewayConfig;
+pub use crate::health::HealthConfig;
+pub use crate::history::HistoryConfig;
+pub use crate::identity::IdentityConfig;
 pub use crate::ip::IpFilteringConfig;
+pub use crate::limits::LimitsConfig;
+pub use crate::listen::ListenConfig;
+pub use crate::locale::LocaleConfig;
+pub use crate::lockdown::LockdownConfig;
+pub use crate::memory::MemoryConfig;
 pub use crate::message::MessageFilteringConfig;
+pub use crate::metrics::MetricsConfig;
 pub use crate::nickname::NicknameFilteringConfig;
-
-use mdlog::loggers::TextLogger;
+pub use crate::notice::NoticeConfig;
+pub use crate::permission::Capability;
+pub use crate::permission::PermissionConfig;
+pub use crate::preview::PreviewConfig;
+pub use crate::raid::RaidConfig;
+pub use crate::ratelimit::BucketConfig;
+pub use crate::ratelimit::RateLimitBucket;
+pub use crate::ratelimit::RateLimitConfig;
+pub use crate::role::Role;
+pub use crate::role::RoleConfig;
+pub use crate::server::PanicStrategy;
+pub use crate::server::ServerConfig;
+pub use crate::socket::SocketOptions;
+pub use crate::state::StateConfig;
+pub use crate::tracing::TracingConfig;
+pub use crate::tracing::TracingSubscriberKind;
+pub use crate::webhook::WebhookConfig;
 
 use once_cell::sync::Lazy;
 
 use regex::Regex;
 
+use std::borrow::Cow;
 use std::collections::HashSet;
 use std::fmt::Display;
 use std::fs::File;
-use std::io::BufRead;
 use std::io::BufReader;
-use std::io::Stdout;
-use std::io::stdout;
+use std::io::Read;
 use std::net::AddrParseError;
 use std::net::IpAddr;
 use std::net::SocketAddr;
 use std::num::NonZeroU16;
 use std::path::Path;
 use std::sync::RwLock;
-use mdlog::LogLevel;
+use std::time::Duration;
 
 static REGEX_WHITESPACE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\s+").unwrap());
 
+/// Prefix of environment variables which are mapped to configuration options, see
+/// [`Config::process_env`].
+pub const ENV_PREFIX: &str = "MDCHAT_";
+
+/// Name of the environment variable containing the path to the configuration
+/// file. It is not mapped to a configuration option.
+pub const ENV_CONFIG_FILE: &str = "MDCHAT_CONFIG";
+
+/// Converts the name of an environment variable without [`ENV_PREFIX`] to the
+/// option it stands for, see [`Config::process_env`].
+#[doc(hidden)]
+fn env_var_to_option(name: &str) -> String {
+    let name = name.to_lowercase();
+    let (option, sub_commands) = name.split_once('_').unwrap_or((&name, ""));
+    sub_commands.split("__")
+        .filter(|sub_command| !sub_command.is_empty())
+        .fold(option.to_string(), |line, sub_command| format!("{} {}", line, sub_command.replace('_', "-")))
+}
+
+/// Parses a duration used in configuration file. Duration is a non-negative
+/// integer optionally followed by a unit: `ms` for milliseconds, `s` for seconds
+/// (default), `m` for minutes, `h` for hours or `d` for days.
+#[doc(hidden)]
+fn parse_duration(string: &str) -> Result<Duration, String> {
+    if let Option::Some(number) = string.strip_suffix("ms") {
+        return number.parse::<u64>()
+            .map(Duration::from_millis)
+            .map_err(|_| format!("`{}` is not a valid duration", string));
+    }
+    let (number, multiplier) = match string.char_indices().last() {
+        Option::Some((i, 's')) => (&string[..i], 1),
+        Option::Some((i, 'm')) => (&string[..i], 60),
+        Option::Some((i, 'h')) => (&string[..i], 60 * 60),
+        Option::Some((i, 'd')) => (&string[..i], 24 * 60 * 60),
+        _ => (string, 1),
+    };
+    number.parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .map(Duration::from_secs)
+        .ok_or(format!("`{}` is not a valid duration", string))
+}
+
+/// Parses a boolean value used in configuration file, that is `on` or `off`.
+#[doc(hidden)]
+fn parse_switch(string: &str) -> Result<bool, String> {
+    match string {
+        "on" => Result::Ok(true),
+        "off" => Result::Ok(false),
+        other => Result::Err(format!("`on` or `off` was expected, but `{}` was found", other)),
+    }
+}
+
+/// Splits a line into a command and its argument, which is the rest of the line.
+/// The argument is unquoted, see [`unquote`].
+#[doc(hidden)]
+fn split_command(line: &str) -> Result<(&str, Option<Cow<'_, str>>), String> {
+    let mut split = REGEX_WHITESPACE.splitn(line.trim(), 2);
+    let command = split.next().unwrap();
+    let arg = split.next().map(unquote).transpose()?;
+    Result::Ok((command, arg))
+}
+
+/// Removes double quotes enclosing an argument, so it can contain leading or
+/// trailing whitespace. Inside the quotes, `\"` stands for a double quote, `\\`
+/// for a backslash, `\n` for a newline and `\t` for a tab. Other backslashes are
+/// kept as they are, so regular expressions do not need to be escaped twice. An
+/// argument which does not start with a double quote is returned trimmed.
+#[doc(hidden)]
+fn unquote(arg: &str) -> Result<Cow<'_, str>, String> {
+    let arg = arg.trim();
+    let quoted = match arg.strip_prefix('"') {
+        Option::Some(quoted) => quoted,
+        Option::None => return Result::Ok(Cow::Borrowed(arg)),
+    };
+    let mut unquoted = String::with_capacity(quoted.len());
+    let mut chars = quoted.chars();
+    while let Option::Some(c) = chars.next() {
+        match c {
+            '"' if chars.as_str().is_empty() => return Result::Ok(Cow::Owned(unquoted)),
+            '"' => return Result::Err(format!("Unexpected `{}` after the closing quote", chars.as_str())),
+            '\\' => match chars.next() {
+                Option::Some('"') => unquoted.push('"'),
+                Option::Some('\\') => unquoted.push('\\'),
+                Option::Some('n') => unquoted.push('\n'),
+                Option::Some('t') => unquoted.push('\t'),
+                Option::Some(other) => { unquoted.push('\\'); unquoted.push(other) },
+                Option::None => break,
+            },
+            other => unquoted.push(other),
+        }
+    }
+    Result::Err(format!("Closing quote is missing in `{}`", arg))
+}
+
 /// Represents a complete configuration of the server.
 pub struct Config {
+    auth: RwLock<AuthConfig>,
+    channels: RwLock<ChannelConfig>,
+    client: RwLock<ClientConfig>,
+    direct_messages: RwLock<DirectMessageConfig>,
+    flow_control: RwLock<FlowControlConfig>,
+    gateway: RwLock<GatewayConfig>,
+    health: RwLock<HealthConfig>,
+    history: RwLock<HistoryConfig>,
+    identity: RwLock<IdentityConfig>,
     ip_filtering: RwLock<IpFilteringConfig>,
+    limits: RwLock<LimitsConfig>,
+    listen: RwLock<ListenConfig>,
+    locale: RwLock<LocaleConfig>,
+    lockdown: RwLock<LockdownConfig>,
+    memory: RwLock<MemoryConfig>,
     message_filtering: RwLock<MessageFilteringConfig>,
+    metrics: RwLock<MetricsConfig>,
     nickname_filtering: RwLock<NicknameFilteringConfig>,
+    notices: RwLock<NoticeConfig>,
     listen_sock_addrs: RwLock<HashSet<SocketAddr>>,
-    logger: RwLock<TextLogger<Stdout>>,
+    permissions: RwLock<PermissionConfig>,
+    previews: RwLock<PreviewConfig>,
+    raid: RwLock<RaidConfig>,
+    rate_limits: RwLock<RateLimitConfig>,
+    roles: RwLock<RoleConfig>,
+    server: RwLock<ServerConfig>,
+    state: RwLock<StateConfig>,
+    tracing: RwLock<TracingConfig>,
+    webhooks: RwLock<WebhookConfig>,
 }
 
 impl Default for Config {
@@ -78,11 +259,35 @@ impl Config {
     /// Calling this constructor is same as using [`Default`] trait implementation.
     pub fn new() -> Self {
         Self {
+            auth: RwLock::new(AuthConfig::new()),
+            channels: RwLock::new(ChannelConfig::new()),
+            client: RwLock::new(ClientConfig::new()),
+            direct_messages: RwLock::new(DirectMessageConfig::new()),
+            flow_control: RwLock::new(FlowControlConfig::new()),
+            gateway: RwLock::new(GatewayConfig::new()),
+            health: RwLock::new(HealthConfig::new()),
+            history: RwLock::new(HistoryConfig::new()),
+            identity: RwLock::new(IdentityConfig::new()),
             ip_filtering: RwLock::new(IpFilteringConfig::new()),
+            limits: RwLock::new(LimitsConfig::new()),
+            listen: RwLock::new(ListenConfig::new()),
+            locale: RwLock::new(LocaleConfig::new()),
+            lockdown: RwLock::new(LockdownConfig::new()),
+            memory: RwLock::new(MemoryConfig::new()),
             listen_sock_addrs: RwLock::new(HashSet::new()),
-            logger: RwLock::new(TextLogger::new(LogLevel::Debug, stdout())),
             message_filtering: RwLock::new(MessageFilteringConfig::new()),
-            nickname_filtering: RwLock::new(NicknameFilteringConfig::new())
+            metrics: RwLock::new(MetricsConfig::new()),
+            nickname_filtering: RwLock::new(NicknameFilteringConfig::new()),
+            notices: RwLock::new(NoticeConfig::new()),
+            permissions: RwLock::new(PermissionConfig::new()),
+            previews: RwLock::new(PreviewConfig::new()),
+            raid: RwLock::new(RaidConfig::new()),
+            rate_limits: RwLock::new(RateLimitConfig::new()),
+            roles: RwLock::new(RoleConfig::new()),
+            server: RwLock::new(ServerConfig::new()),
+            state: RwLock::new(StateConfig::new()),
+            tracing: RwLock::new(TracingConfig::new()),
+            webhooks: RwLock::new(WebhookConfig::new()),
         }
     }
 
@@ -91,15 +296,117 @@ impl Config {
     /// Fields, which are not collections, will get overwritten by the `other`
     /// instance. Fields which are collections, will be merged with `self`'s fields.
     pub fn append(&self, other: Self) {
+        // Authentication
+        self.auth.write().unwrap().append(other.auth.into_inner().unwrap());
+        // Channels
+        self.channels.write().unwrap().append(other.channels.into_inner().unwrap());
+        // Client applications
+        self.client.write().unwrap().append(other.client.into_inner().unwrap());
+        // Direct messages
+        self.direct_messages.write().unwrap().append(other.direct_messages.into_inner().unwrap());
+        // Flow control of connections
+        self.flow_control.write().unwrap().append(other.flow_control.into_inner().unwrap());
+        // WebSocket gateway
+        self.gateway.write().unwrap().append(other.gateway.into_inner().unwrap());
+        // Health endpoint
+        self.health.write().unwrap().append(other.health.into_inner().unwrap());
+        // Message history
+        self.history.write().unwrap().append(other.history.into_inner().unwrap());
+        // Server identity
+        self.identity.write().unwrap().append(other.identity.into_inner().unwrap());
         // IP address, nickname and message filtering
         self.ip_filtering.write().unwrap().append(&*other.ip_filtering.read().unwrap());
         self.message_filtering.write().unwrap().append(other.message_filtering.into_inner().unwrap());
         self.nickname_filtering.write().unwrap().append(other.nickname_filtering.into_inner().unwrap());
+        // Message quotas
+        self.limits.write().unwrap().append(other.limits.into_inner().unwrap());
+        // Languages of messages sent to clients
+        self.locale.write().unwrap().append(other.locale.into_inner().unwrap());
+        // Emergency lockdown
+        self.lockdown.write().unwrap().append(other.lockdown.into_inner().unwrap());
+        // Memory caps
+        self.memory.write().unwrap().append(other.memory.into_inner().unwrap());
+        // Metrics export
+        self.metrics.write().unwrap().append(other.metrics.into_inner().unwrap());
+        // Handling of failures of binding listeners
+        self.listen.write().unwrap().append(other.listen.into_inner().unwrap());
         // Listener socket addresses
         let mut self_listen = self.listen_sock_addrs.write().unwrap();
         let other_listen = other.listen_sock_addrs.read().unwrap();
         *self_listen = &*self_listen | &*other_listen;
-        // Nickname filtering
+        // Join and leave notices
+        self.notices.write().unwrap().append(other.notices.into_inner().unwrap());
+        // Permissions
+        self.permissions.write().unwrap().append(other.permissions.into_inner().unwrap());
+        // Link previews
+        self.previews.write().unwrap().append(other.previews.into_inner().unwrap());
+        // Raid detection
+        self.raid.write().unwrap().append(other.raid.into_inner().unwrap());
+        // Rate limits
+        self.rate_limits.write().unwrap().append(other.rate_limits.into_inner().unwrap());
+        // Roles
+        self.roles.write().unwrap().append(other.roles.into_inner().unwrap());
+        // Name and message of the day
+        self.server.write().unwrap().append(other.server.into_inner().unwrap());
+        // State acquired at runtime
+        self.state.write().unwrap().append(other.state.into_inner().unwrap());
+        // Server log and tracing spans
+        self.tracing.write().unwrap().append(other.tracing.into_inner().unwrap());
+        // Inbound webhooks
+        self.webhooks.write().unwrap().append(other.webhooks.into_inner().unwrap());
+    }
+
+    /// Returns a read-write lock to the [`AuthConfig`] instance of the [`Config`].
+    pub fn auth(&self) -> &RwLock<AuthConfig> {
+        &self.auth
+    }
+
+    /// Returns a read-write lock to the [`ChannelConfig`] instance of the
+    /// [`Config`].
+    pub fn channels(&self) -> &RwLock<ChannelConfig> {
+        &self.channels
+    }
+
+    /// Returns a read-write lock to the [`ClientConfig`] instance of the
+    /// [`Config`].
+    pub fn client(&self) -> &RwLock<ClientConfig> {
+        &self.client
+    }
+
+    /// Returns a read-write lock to the [`DirectMessageConfig`] instance of the
+    /// [`Config`].
+    pub fn direct_messages(&self) -> &RwLock<DirectMessageConfig> {
+        &self.direct_messages
+    }
+
+    /// Returns a read-write lock to the [`FlowControlConfig`] instance of the
+    /// [`Config`].
+    pub fn flow_control(&self) -> &RwLock<FlowControlConfig> {
+        &self.flow_control
+    }
+
+    /// Returns a read-write lock to the [`GatewayConfig`] instance of the
+    /// [`Config`].
+    pub fn gateway(&self) -> &RwLock<GatewayConfig> {
+        &self.gateway
+    }
+
+    /// Returns a read-write lock to the [`HealthConfig`] instance of the
+    /// [`Config`].
+    pub fn health(&self) -> &RwLock<HealthConfig> {
+        &self.health
+    }
+
+    /// Returns a read-write lock to the [`HistoryConfig`] instance of the
+    /// [`Config`].
+    pub fn history(&self) -> &RwLock<HistoryConfig> {
+        &self.history
+    }
+
+    /// Returns a read-write lock to the [`IdentityConfig`] instance of the
+    /// [`Config`].
+    pub fn identity(&self) -> &RwLock<IdentityConfig> {
+        &self.identity
     }
 
     /// Returns a read-write lock to the [`IpFilteringConfig`] instance of the
@@ -108,20 +415,109 @@ impl Config {
         &self.ip_filtering
     }
 
+    /// Returns a read-write lock to the [`LimitsConfig`] instance of the
+    /// [`Config`].
+    pub fn limits(&self) -> &RwLock<LimitsConfig> {
+        &self.limits
+    }
+
+    /// Returns a read-write lock to the [`LocaleConfig`] instance of the
+    /// [`Config`].
+    pub fn locale(&self) -> &RwLock<LocaleConfig> {
+        &self.locale
+    }
+
+    /// Returns a read-write lock to the [`ListenConfig`] instance of the
+    /// [`Config`].
+    pub fn listen(&self) -> &RwLock<ListenConfig> {
+        &self.listen
+    }
+
+    /// Returns a read-write lock to the [`LockdownConfig`] instance of the
+    /// [`Config`].
+    pub fn lockdown(&self) -> &RwLock<LockdownConfig> {
+        &self.lockdown
+    }
+
     /// Returns a read-write lock to the [`MessageFilteringConfig`] instance of the
     /// [`Config`].
     pub fn message_filtering(&self) -> &RwLock<MessageFilteringConfig> {
         &self.message_filtering
     }
+
+    /// Returns a read-write lock to the [`MemoryConfig`] instance of the
+    /// [`Config`].
+    pub fn memory(&self) -> &RwLock<MemoryConfig> {
+        &self.memory
+    }
+
+    /// Returns a read-write lock to the [`MetricsConfig`] instance of the
+    /// [`Config`].
+    pub fn metrics(&self) -> &RwLock<MetricsConfig> {
+        &self.metrics
+    }
     /// Returns a read-write lock to the [`NicknameFilteringConfig`] instance of the
     /// [`Config`].
     pub fn nickname_filtering(&self) -> &RwLock<NicknameFilteringConfig> {
         &self.nickname_filtering
     }
 
-    /// Returns a read-write lock to the [`TextLogger`] printing to [`stdout`]
-    pub fn logger(&self) -> &RwLock<TextLogger<Stdout>> {
-        &self.logger
+    /// Returns a read-write lock to the [`NoticeConfig`] instance of the
+    /// [`Config`].
+    pub fn notices(&self) -> &RwLock<NoticeConfig> {
+        &self.notices
+    }
+
+    /// Returns a read-write lock to the [`PermissionConfig`] instance of the
+    /// [`Config`].
+    pub fn permissions(&self) -> &RwLock<PermissionConfig> {
+        &self.permissions
+    }
+
+    /// Returns a read-write lock to the [`PreviewConfig`] instance of the
+    /// [`Config`].
+    pub fn previews(&self) -> &RwLock<PreviewConfig> {
+        &self.previews
+    }
+
+    /// Returns a read-write lock to the [`RaidConfig`] instance of the [`Config`].
+    pub fn raid(&self) -> &RwLock<RaidConfig> {
+        &self.raid
+    }
+
+    /// Returns a read-write lock to the [`RateLimitConfig`] instance of the
+    /// [`Config`].
+    pub fn rate_limits(&self) -> &RwLock<RateLimitConfig> {
+        &self.rate_limits
+    }
+
+    /// Returns a read-write lock to the [`RoleConfig`] instance of the [`Config`].
+    pub fn roles(&self) -> &RwLock<RoleConfig> {
+        &self.roles
+    }
+
+    /// Returns a read-write lock to the [`ServerConfig`] instance of the
+    /// [`Config`].
+    pub fn server(&self) -> &RwLock<ServerConfig> {
+        &self.server
+    }
+
+    /// Returns a read-write lock to the [`WebhookConfig`] instance of the
+    /// [`Config`].
+    pub fn webhooks(&self) -> &RwLock<WebhookConfig> {
+        &self.webhooks
+    }
+
+    /// Returns a read-write lock to the [`StateConfig`] instance of the
+    /// [`Config`].
+    pub fn state(&self) -> &RwLock<StateConfig> {
+        &self.state
+    }
+
+    /// Returns a read-write lock to the [`TracingConfig`] instance of the
+    /// [`Config`].
+    pub fn tracing(&self) -> &RwLock<TracingConfig> {
+        &self.tracing
     }
 
     pub fn process_file<P>(&self, file_path: P, rollback_on_error: bool) -> ConfigParseResult<()>
@@ -142,19 +538,10 @@ impl Config {
         let convert_io_err = |io_error| ConfigParseError::io_error(file_name.clone(), io_error);
         // Open the file for reading
         let file = File::open(&file_path).map_err(convert_io_err)?;
-        // Create buffered reader
-        let reader = BufReader::new(file);
-        // Store line number:
-        let mut line_num = 1u32;
-        // Read file line by line:
-        for line in reader.lines() {
-            line.map_err(convert_io_err)
-                .and_then(|line| self.process_line(&line)
-                    .map_err(|desc| ConfigParseError::syntax_error(file_name.clone(), line_num, desc))
-                )?;
-            line_num += 1;
-        }
-        Result::Ok(())
+        // Read the whole file, since blocks can span multiple lines:
+        let mut source = String::new();
+        BufReader::new(file).read_to_string(&mut source).map_err(convert_io_err)?;
+        self.__process_source(&file_name, &source)
     }
 
     /// Processes given string as a part of a configuration file.
@@ -164,18 +551,62 @@ impl Config {
     ///  -  [`Result::Ok`] if parsing succeeded
     ///  -  [`Result::Err`] if parsing failed
     pub fn process_string(&self, string: &str) -> ConfigParseResult<()> {
-        // Process line by line:
-        let mut line_num = 1u32;
-        for line in string.lines() {
-            // If there is an config return immediately (`?` operator):
-            self.process_line(line)
-                .map_err(|desc| ConfigParseError::syntax_error(String::new(), line_num, desc))?;
-            line_num += 1;
+        self.__process_source(STRING_SOURCE, string)
+    }
+
+    /// Processes given text of a configuration file statement by statement, see
+    /// [`syntax::statements`].
+    #[doc(hidden)]
+    fn __process_source(&self, source_name: &str, source: &str) -> ConfigParseResult<()> {
+        for statement in syntax::statements(source_name, source)? {
+            // If there is an error, return immediately (`?` operator):
+            self.process_line(&statement.text)
+                .map_err(|desc| ConfigParseError::syntax_error(source_name.to_string(), statement.line_num, statement.line, desc))?;
         }
         // If processing was successful, return Ok:
         Result::Ok(())
     }
 
+    /// Processes environment variables starting with [`ENV_PREFIX`] as
+    /// configuration options, so the server can be configured without
+    /// a configuration file. Other variables and [`ENV_CONFIG_FILE`] are ignored.
+    ///
+    /// The name of a variable without the prefix is converted to lowercase. Its
+    /// first word is the option, the rest is a sub-command where `_` stands for
+    /// `-` and `__` separates sub-commands of nested options. The value of the
+    /// variable is the argument; each line of a multi-line value is processed
+    /// separately. For example:
+    ///
+    ///  -  `MDCHAT_LISTEN=0.0.0.0:4000` is `listen 0.0.0.0:4000`
+    ///  -  `MDCHAT_HISTORY_SEARCH_MAX_RESULTS=50` is
+    ///     `history search-max-results 50`
+    ///  -  `MDCHAT_AUTH_EMAIL__CODE_TTL=1h` is `auth email code-ttl 1h`
+    ///
+    /// Variables are processed in the order of their names.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed, the error contains the name of the
+    ///     variable instead of a file path
+    pub fn process_env<I>(&self, vars: I) -> ConfigParseResult<()>
+        where I: IntoIterator<Item = (String, String)>,
+    {
+        let mut vars: Vec<(String, String)> = vars.into_iter()
+            .filter(|(name, _)| name.starts_with(ENV_PREFIX) && name != ENV_CONFIG_FILE)
+            .collect();
+        vars.sort();
+        for (name, value) in vars {
+            let option = env_var_to_option(&name[ENV_PREFIX.len()..]);
+            for (line_num, value) in (1u32..).zip(value.lines()) {
+                let line = format!("{} {}", option, value);
+                self.process_line(&line)
+                    .map_err(|desc| ConfigParseError::syntax_error(name.clone(), line_num, &line, desc))?;
+            }
+        }
+        Result::Ok(())
+    }
+
     /// Processes a single line of configuration file. If a newline character is
     /// found after the trim of the line, method will panic.
     ///
@@ -198,46 +629,227 @@ impl Config {
             return Result::Ok(())
         }
         // If it is not an empty line or a comment, parse it:
-        // Split once by space:
-        let split: Vec<&str> = REGEX_WHITESPACE.splitn(line.trim(), 2).collect();
-        // Separate and trim option and argument. Trim argument and if the argument
-        // is empty, discard it.
-        let option = split[0].trim();
-        let arg = split.get(1).map(|s| s.trim());
+        // Separate option and its unquoted argument:
+        let (option, arg) = split_command(line)?;
+        let arg = arg.as_deref();
         // Based on the option parse it differently:
         match option {
+            "auth" => self.__process_auth_command(arg),
+            "channel" => self.__process_channel_command(arg),
+            "client" => self.__process_client_command(arg),
+            "direct" => self.__process_direct_command(arg),
+            "flow" => self.__process_flow_command(arg),
+            "gateway" => self.__process_gateway_command(arg),
+            "health" => self.__process_health_command(arg),
+            "history" => self.__process_history_command(arg),
+            "identity" => self.__process_identity_command(arg),
             "ip" => self.__process_ip_command(arg),
+            "limits" => self.__process_limits_command(arg),
+            "lockdown" => self.__process_lockdown_command(arg),
+            "memory" => self.__process_memory_command(arg),
             "message" => self.__process_message_command(arg),
+            "metrics" => self.__process_metrics_command(arg),
             "listen" => self.__process_listen_command(arg),
+            "locale" => self.__process_locale_command(arg),
             "nickname" => self.__process_nickname_command(arg),
+            "notice" => self.__process_notice_command(arg),
+            "permissions" => self.__process_permissions_command(arg),
+            "preview" => self.__process_preview_command(arg),
+            "raid" => self.__process_raid_command(arg),
+            "ratelimit" => self.__process_ratelimit_command(arg),
+            "role" => self.__process_role_command(arg),
+            "server" => self.__process_server_command(arg),
+            "state" => self.__process_state_command(arg),
+            "tracing" => self.__process_tracing_command(arg),
+            "webhook" => self.__process_webhook_command(arg),
             other => Result::Err(format!("`{}` is an invalid option", other))
         }
     }
 
+    #[doc(hidden)]
+    fn __process_auth_command(&self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("Sub-command was expected after `auth`".to_string())
+            .and_then(|arg| self.auth.write().unwrap().process_line(arg))
+    }
+
+    #[doc(hidden)]
+    fn __process_channel_command(&self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("Sub-command was expected after `channel`".to_string())
+            .and_then(|arg| self.channels.write().unwrap().process_line(arg))
+    }
+
+    #[doc(hidden)]
+    fn __process_client_command(&self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("Sub-command was expected after `client`".to_string())
+            .and_then(|arg| self.client.write().unwrap().process_line(arg))
+    }
+
+    #[doc(hidden)]
+    fn __process_direct_command(&self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("Sub-command was expected after `direct`".to_string())
+            .and_then(|arg| self.direct_messages.write().unwrap().process_line(arg))
+    }
+
+    #[doc(hidden)]
+    fn __process_flow_command(&self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("Sub-command was expected after `flow`".to_string())
+            .and_then(|arg| self.flow_control.write().unwrap().process_line(arg))
+    }
+
+    #[doc(hidden)]
+    fn __process_gateway_command(&self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("Sub-command was expected after `gateway`".to_string())
+            .and_then(|arg| self.gateway.write().unwrap().process_line(arg))
+    }
+
+    #[doc(hidden)]
+    fn __process_health_command(&self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("Sub-command was expected after `health`".to_string())
+            .and_then(|arg| self.health.write().unwrap().process_line(arg))
+    }
+
+    #[doc(hidden)]
+    fn __process_history_command(&self, arg: Option<&str>) -> Result<(), String> {
+        let arg = arg.ok_or("Sub-command was expected after `history`".to_string())?;
+        // `history search-role` is kept for compatibility with older configuration
+        // files, it is the `search-history` permission now:
+        let (sub_command, role) = split_command(arg)?;
+        match sub_command {
+            "search-role" => role
+                .ok_or("A role was expected after `history search-role`".to_string())
+                .and_then(|role| role.parse())
+                .map(|role| self.permissions.write().unwrap().set_role(Capability::SearchHistory, role)),
+            _ => self.history.write().unwrap().process_line(arg),
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_identity_command(&self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("Sub-command was expected after `identity`".to_string())
+            .and_then(|arg| self.identity.write().unwrap().process_line(arg))
+    }
+
     #[doc(hidden)]
     fn __process_ip_command(&self, arg: Option<&str>) -> Result<(), String> {
         arg.ok_or("Sub-command was expected after `ip`".to_string())
             .and_then(|arg| self.ip_filtering.write().unwrap().process_line(arg))
     }
 
+    #[doc(hidden)]
+    fn __process_limits_command(&self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("Sub-command was expected after `limits`".to_string())
+            .and_then(|arg| self.limits.write().unwrap().process_line(arg))
+    }
+
+    #[doc(hidden)]
+    fn __process_locale_command(&self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("Sub-command was expected after `locale`".to_string())
+            .and_then(|arg| self.locale.write().unwrap().process_line(arg))
+    }
+
+    #[doc(hidden)]
+    fn __process_lockdown_command(&self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("Sub-command was expected after `lockdown`".to_string())
+            .and_then(|arg| self.lockdown.write().unwrap().process_line(arg))
+    }
+
     #[doc(hidden)]
     fn __process_message_command(&self, arg: Option<&str>) -> Result<(), String> {
         arg.ok_or("Sub-command was expected after `message`".to_string())
             .and_then(|arg| self.message_filtering.write().unwrap().process_line(arg))
     }
 
+    #[doc(hidden)]
+    fn __process_memory_command(&self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("Sub-command was expected after `memory`".to_string())
+            .and_then(|arg| self.memory.write().unwrap().process_line(arg))
+    }
+
+    #[doc(hidden)]
+    fn __process_metrics_command(&self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("Sub-command was expected after `metrics`".to_string())
+            .and_then(|arg| self.metrics.write().unwrap().process_line(arg))
+    }
+
     #[doc(hidden)]
     fn __process_nickname_command(&self, arg: Option<&str>) -> Result<(), String> {
         arg.ok_or("Sub-command was expected after `nickname`".to_string())
             .and_then(|arg| self.nickname_filtering.write().unwrap().process_line(arg))
     }
 
+    #[doc(hidden)]
+    fn __process_notice_command(&self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("Sub-command was expected after `notice`".to_string())
+            .and_then(|arg| self.notices.write().unwrap().process_line(arg))
+    }
+
+    #[doc(hidden)]
+    fn __process_permissions_command(&self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("A capability was expected after `permissions`".to_string())
+            .and_then(|arg| self.permissions.write().unwrap().process_line(arg))
+    }
+
+    #[doc(hidden)]
+    fn __process_preview_command(&self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("Sub-command was expected after `preview`".to_string())
+            .and_then(|arg| self.previews.write().unwrap().process_line(arg))
+    }
+
+    #[doc(hidden)]
+    fn __process_raid_command(&self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("Sub-command was expected after `raid`".to_string())
+            .and_then(|arg| self.raid.write().unwrap().process_line(arg))
+    }
+
+    #[doc(hidden)]
+    fn __process_ratelimit_command(&self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("Bucket was expected after `ratelimit`".to_string())
+            .and_then(|arg| self.rate_limits.write().unwrap().process_line(arg))
+    }
+
+    #[doc(hidden)]
+    fn __process_role_command(&self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("Sub-command was expected after `role`".to_string())
+            .and_then(|arg| self.roles.write().unwrap().process_line(arg))
+    }
+
+    #[doc(hidden)]
+    fn __process_server_command(&self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("Sub-command was expected after `server`".to_string())
+            .and_then(|arg| self.server.write().unwrap().process_line(arg))
+    }
+
+    #[doc(hidden)]
+    fn __process_state_command(&self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("Sub-command was expected after `state`".to_string())
+            .and_then(|arg| self.state.write().unwrap().process_line(arg))
+    }
+
+    #[doc(hidden)]
+    fn __process_tracing_command(&self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("Sub-command was expected after `tracing`".to_string())
+            .and_then(|arg| self.tracing.write().unwrap().process_line(arg))
+    }
+
+    #[doc(hidden)]
+    fn __process_webhook_command(&self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("Sub-command was expected after `webhook`".to_string())
+            .and_then(|arg| self.webhooks.write().unwrap().process_line(arg))
+    }
+
     #[doc(hidden)]
     fn __process_listen_command(&self, arg: Option<&str>) -> Result<(), String> {
+        // Sub-commands configure binding, anything else is a socket address:
+        if let Option::Some(arg) = arg.filter(|arg| ListenConfig::is_sub_command(REGEX_WHITESPACE.splitn(arg, 2).next().unwrap())) {
+            let mut listen_config = self.listen.write().unwrap();
+            listen_config.process_line(arg)?;
+            // Admin-only sockets are listened on like any other:
+            self.listen_sock_addrs.write().unwrap().extend(listen_config.get_admin_only());
+            return Result::Ok(());
+        }
         arg.ok_or("Socket address was expected after `listen`".to_string())
-            .and_then(|arg| arg.parse().map_err(|err|
-                format!("Invalid socket address after `listen`: {}", err)))
-            .map(|sockaddr| { self.listen_sock_addrs.write().unwrap().insert(sockaddr); })
+            .and_then(ListenConfig::parse_sock_addrs)
+            .map(|sock_addrs| self.listen_sock_addrs.write().unwrap().extend(sock_addrs))
 
     }
 
@@ -253,7 +865,61 @@ impl Config {
         self.nickname_filtering.read().unwrap().is_allowed(nickname)
     }
 
+    pub fn role_of(&self, nickname: &str) -> Role {
+        self.roles.read().unwrap().role_of(nickname)
+    }
+
     pub fn listen_sock_addrs(&self) -> &RwLock<HashSet<SocketAddr>> {
         &self.listen_sock_addrs
     }
-}
\ No newline at end of file
+
+    /// Analyzes the configuration for rules which are valid on their own, but
+    /// contradict or shadow each other, such as an allowed nickname matching
+    /// a banned pattern, overlapping banned IP address ranges or sockets which
+    /// cannot be bound together. The configuration is used as it is regardless of
+    /// the result.
+    ///
+    /// # Return value
+    ///
+    /// A [`Vec`] of human-readable warnings, which is empty if nothing was found.
+    pub fn lint(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        warnings.append(&mut self.channels.read().unwrap().lint());
+        warnings.append(&mut self.ip_filtering.read().unwrap().lint());
+        warnings.append(&mut self.locale.read().unwrap().lint());
+        warnings.append(&mut self.message_filtering.read().unwrap().lint());
+        warnings.append(&mut self.nickname_filtering.read().unwrap().lint());
+        warnings.append(&mut self.previews.read().unwrap().lint());
+        // A socket bound to a specific address cannot be bound together with the
+        // wildcard address of the same family and port:
+        let mut listen_sock_addrs: Vec<SocketAddr> = self.listen_sock_addrs.read().unwrap().iter().copied().collect();
+        listen_sock_addrs.sort();
+        for sock_addr in &listen_sock_addrs {
+            let wildcard = listen_sock_addrs.iter()
+                .find(|other| other.ip().is_unspecified() && other.port() == sock_addr.port()
+                    && other.is_ipv4() == sock_addr.is_ipv4() && *other != sock_addr);
+            if let Option::Some(wildcard) = wildcard {
+                warnings.push(format!("`listen {}` overlaps with `listen {}`, so one of them cannot be bound", sock_addr, wildcard));
+            }
+        }
+        // Endpoints using TCP must not share a socket address:
+        let endpoints = [
+            ("gateway listen", self.gateway.read().unwrap().get_listen()),
+            ("health listen", self.health.read().unwrap().get_listen()),
+            ("webhook listen", self.webhooks.read().unwrap().get_listen()),
+        ];
+        let endpoints: Vec<(&str, SocketAddr)> = endpoints.into_iter()
+            .filter_map(|(option, sock_addr)| sock_addr.map(|sock_addr| (option, sock_addr)))
+            .collect();
+        for (i, (option, sock_addr)) in endpoints.iter().enumerate() {
+            let conflicting = endpoints[i + 1..].iter()
+                .filter(|(_, other)| other.port() == sock_addr.port() && other.is_ipv4() == sock_addr.is_ipv4())
+                .filter(|(_, other)| other.ip() == sock_addr.ip() || other.ip().is_unspecified() || sock_addr.ip().is_unspecified());
+            for (other_option, other) in conflicting {
+                warnings.push(format!("`{} {}` conflicts with `{} {}`, so one of them cannot be bound",
+                    option, sock_addr, other_option, other));
+            }
+        }
+        warnings
+    }
+}
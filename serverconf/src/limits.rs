@@ -0,0 +1,157 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::split_command;
+
+use std::num::NonZeroU32;
+use std::time::Duration;
+
+/// Represents configurability of how many messages users can send.
+pub struct LimitsConfig {
+    messages_per_day: Option<NonZeroU32>,
+    messages_per_session: Option<NonZeroU32>,
+    daily_reset: Duration,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LimitsConfig {
+    /// Creates a new [`LimitsConfig`] with no limits and daily quota reset at
+    /// midnight (UTC). Using this constructor is same as using [`Default`]'s
+    /// implementation.
+    pub fn new() -> Self {
+        Self {
+            messages_per_day: Option::None,
+            messages_per_session: Option::None,
+            daily_reset: Duration::ZERO,
+        }
+    }
+
+    /// Merges `self` with `other` instance. All values will be overwritten by
+    /// `other`'s values.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, other: Self) {
+        self.messages_per_day = other.messages_per_day;
+        self.messages_per_session = other.messages_per_session;
+        self.daily_reset = other.daily_reset;
+    }
+
+    /// Returns how many messages a single user can send per day. [`Option::None`]
+    /// means that there is no limit.
+    pub fn get_messages_per_day(&self) -> Option<NonZeroU32> {
+        self.messages_per_day
+    }
+
+    /// Sets how many messages a single user can send per day. [`Option::None`]
+    /// means that there is no limit.
+    pub fn set_messages_per_day(&mut self, messages_per_day: Option<NonZeroU32>) {
+        self.messages_per_day = messages_per_day;
+    }
+
+    /// Returns how many messages can be sent using a single connection.
+    /// [`Option::None`] means that there is no limit.
+    pub fn get_messages_per_session(&self) -> Option<NonZeroU32> {
+        self.messages_per_session
+    }
+
+    /// Sets how many messages can be sent using a single connection.
+    /// [`Option::None`] means that there is no limit.
+    pub fn set_messages_per_session(&mut self, messages_per_session: Option<NonZeroU32>) {
+        self.messages_per_session = messages_per_session;
+    }
+
+    /// Returns the time of day (UTC) when daily quotas are reset, as a duration
+    /// since midnight.
+    pub fn get_daily_reset(&self) -> Duration {
+        self.daily_reset
+    }
+
+    /// Sets the time of day (UTC) when daily quotas are reset, as a duration
+    /// since midnight.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if the time was set
+    ///  -  [`Result::Err`] if given duration is not shorter than a day
+    pub fn set_daily_reset(&mut self, daily_reset: Duration) -> Result<(), String> {
+        if daily_reset >= Duration::from_secs(24 * 60 * 60) {
+            return Result::Err("Daily quota reset must be a time of day".to_string())
+        }
+        self.daily_reset = daily_reset;
+        Result::Ok(())
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split command and argument:
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
+        // Parse based on the command
+        match command {
+            "daily-reset" => self.__process_daily_reset(arg),
+            "messages-per-day" => self.__process_limit("messages-per-day", arg)
+                .map(|limit| self.set_messages_per_day(limit)),
+            "messages-per-session" => self.__process_limit("messages-per-session", arg)
+                .map(|limit| self.set_messages_per_session(limit)),
+            other => Result::Err(format!("`limits {}`: unknown sub-command", other))
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_limit(&self, command: &str, arg: Option<&str>) -> Result<Option<NonZeroU32>, String> {
+        match arg {
+            Option::None => Result::Err(format!("An argument was expected after `limits {}`", command)),
+            Option::Some("off") => Result::Ok(Option::None),
+            Option::Some(arg) => arg.parse()
+                .map(Option::Some)
+                .map_err(|err| format!("A positive number or `off` was expected after `limits {}`: {}", command, err)),
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_daily_reset(&mut self, arg: Option<&str>) -> Result<(), String> {
+        let arg = arg.ok_or("A time was expected after `limits daily-reset`".to_string())?;
+        let invalid = || format!("`{}` is not a valid time, `HH:MM` was expected", arg);
+        let (hours, minutes) = arg.split_once(':').ok_or_else(invalid)?;
+        let hours: u64 = hours.parse().map_err(|_| invalid())?;
+        let minutes: u64 = minutes.parse().map_err(|_| invalid())?;
+        if hours >= 24 || minutes >= 60 {
+            return Result::Err(invalid())
+        }
+        self.set_daily_reset(Duration::from_secs(hours * 60 * 60 + minutes * 60))
+    }
+}
@@ -0,0 +1,217 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::split_command;
+use crate::parse_duration;
+use crate::parse_switch;
+
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+use std::net::SocketAddr;
+use std::net::ToSocketAddrs;
+use std::time::Duration;
+
+/// Represents configurability of how failures of binding sockets given by
+/// `listen` options are handled and which sockets are admin-only. Binding a
+/// socket is retried with exponential backoff if it fails for a reason which may
+/// pass, such as when the address is not available yet during boot.
+///
+/// Socket options, such as `SO_REUSEADDR` or TCP keepalive, are not configurable,
+/// since the MDSWP transport binds the sockets itself and does not expose their
+/// options. Sockets served over plain TCP are configured by
+/// [`SocketOptions`](crate::SocketOptions) instead.
+pub struct ListenConfig {
+    admin_only: HashSet<SocketAddr>,
+    require_all: bool,
+    retries: u32,
+    retry_delay: Duration,
+}
+
+impl Default for ListenConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ListenConfig {
+    /// Creates a new [`ListenConfig`] with default values, that is running the
+    /// server if at least one socket is bound and retrying 5 times, first after
+    /// 500 milliseconds. Using this constructor is same as using [`Default`]'s
+    /// implementation.
+    pub fn new() -> Self {
+        Self {
+            admin_only: HashSet::new(),
+            require_all: false,
+            retries: 5,
+            retry_delay: Duration::from_millis(500),
+        }
+    }
+
+    /// Merges `self` with `other` instance. Admin-only sockets are added to the
+    /// current ones, other values will be overwritten by `other`'s values.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, other: Self) {
+        self.admin_only.extend(other.admin_only);
+        self.require_all = other.require_all;
+        self.retries = other.retries;
+        self.retry_delay = other.retry_delay;
+    }
+
+    /// Returns socket addresses on which only administrators and moderators can
+    /// log in.
+    pub fn get_admin_only(&self) -> &HashSet<SocketAddr> {
+        &self.admin_only
+    }
+
+    /// Sets socket addresses on which only administrators and moderators can log
+    /// in.
+    pub fn set_admin_only(&mut self, admin_only: HashSet<SocketAddr>) {
+        self.admin_only = admin_only;
+    }
+
+    /// Returns whether only administrators and moderators can log in on given
+    /// socket address.
+    pub fn is_admin_only(&self, sock_addr: &SocketAddr) -> bool {
+        self.admin_only.contains(sock_addr)
+    }
+
+    /// Returns whether the server should quit on startup if any socket cannot be
+    /// bound.
+    pub fn get_require_all(&self) -> bool {
+        self.require_all
+    }
+
+    /// Sets whether the server should quit on startup if any socket cannot be
+    /// bound.
+    pub fn set_require_all(&mut self, require_all: bool) {
+        self.require_all = require_all;
+    }
+
+    /// Returns how many times binding of a socket is retried.
+    pub fn get_retries(&self) -> u32 {
+        self.retries
+    }
+
+    /// Sets how many times binding of a socket is retried.
+    pub fn set_retries(&mut self, retries: u32) {
+        self.retries = retries;
+    }
+
+    /// Returns the delay before the first retry of binding a socket. The delay
+    /// doubles with each retry.
+    pub fn get_retry_delay(&self) -> Duration {
+        self.retry_delay
+    }
+
+    /// Sets the delay before the first retry of binding a socket.
+    pub fn set_retry_delay(&mut self, retry_delay: Duration) {
+        self.retry_delay = retry_delay;
+    }
+
+    /// Returns whether given sub-command of `listen` option is handled by
+    /// [`ListenConfig`], rather than being a socket address.
+    pub fn is_sub_command(command: &str) -> bool {
+        matches!(command, "admin-only" | "require-all" | "retries" | "retry-delay")
+    }
+
+    /// Parses socket addresses given by `listen` option. Besides a socket
+    /// address, the argument may be `*:<port>`, which means both `0.0.0.0:<port>`
+    /// and `[::]:<port>`, or `<hostname>:<port>`, which means all addresses the
+    /// host name resolves to at the moment.
+    pub fn parse_sock_addrs(arg: &str) -> Result<Vec<SocketAddr>, String> {
+        if let Result::Ok(sock_addr) = arg.parse() {
+            return Result::Ok(vec![sock_addr]);
+        }
+        if let Option::Some(port) = arg.strip_prefix("*:") {
+            let port: u16 = port.parse()
+                .map_err(|err| format!("Invalid port after `listen *:`: {}", err))?;
+            return Result::Ok(vec![
+                SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port),
+                SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), port),
+            ]);
+        }
+        let sock_addrs: Vec<SocketAddr> = arg.to_socket_addrs()
+            .map_err(|err| format!("Invalid socket address or unresolvable host name `{}` after `listen`: {}", arg, err))?
+            .collect();
+        match sock_addrs.is_empty() {
+            true => Result::Err(format!("Host name `{}` after `listen` does not resolve to any address", arg)),
+            false => Result::Ok(sock_addrs),
+        }
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic. Sockets given by
+    /// `admin-only` sub-command are only marked as admin-only, the caller must
+    /// listen on them too.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split command and argument:
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
+        // Parse based on the command
+        match command {
+            "admin-only" => self.__process_admin_only(arg),
+            "require-all" => self.__process_require_all(arg),
+            "retries" => self.__process_retries(arg),
+            "retry-delay" => self.__process_retry_delay(arg),
+            other => Result::Err(format!("`listen {}`: unknown sub-command", other))
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_admin_only(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("Socket address was expected after `listen admin-only`".to_string())
+            .and_then(Self::parse_sock_addrs)
+            .map(|sock_addrs| self.admin_only.extend(sock_addrs))
+    }
+
+    #[doc(hidden)]
+    fn __process_require_all(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("`on` or `off` was expected after `listen require-all`".to_string())
+            .and_then(parse_switch)
+            .map(|require_all| self.set_require_all(require_all))
+    }
+
+    #[doc(hidden)]
+    fn __process_retries(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("A number was expected after `listen retries`".to_string())
+            .and_then(|arg| arg.parse().map_err(|err| format!("Invalid number after `listen retries`: {}", err)))
+            .map(|retries| self.set_retries(retries))
+    }
+
+    #[doc(hidden)]
+    fn __process_retry_delay(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("A duration was expected after `listen retry-delay`".to_string())
+            .and_then(parse_duration)
+            .map(|retry_delay| self.set_retry_delay(retry_delay))
+    }
+}
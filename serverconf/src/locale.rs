@@ -0,0 +1,165 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::split_command;
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Locale of the messages built into the server.
+pub const BUILTIN_LOCALE: &str = "en";
+
+/// Represents configurability of languages of messages the server sends to
+/// clients, such as errors, warnings and notices. Each client asks for a locale
+/// when it connects, and it is sent messages from the catalog of that locale,
+/// if there is any. Notices are a part of the message history shared by all
+/// users, so they always use the default locale.
+pub struct LocaleConfig {
+    default: String,
+    catalogs: BTreeMap<String, PathBuf>,
+}
+
+impl Default for LocaleConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LocaleConfig {
+    /// Creates a new [`LocaleConfig`] with default values, that is with the
+    /// [`BUILTIN_LOCALE`] as the default locale and without any catalogs. Using
+    /// this constructor is same as using [`Default`]'s implementation.
+    pub fn new() -> Self {
+        Self {
+            default: BUILTIN_LOCALE.to_string(),
+            catalogs: BTreeMap::new(),
+        }
+    }
+
+    /// Merges `self` with `other` instance. Catalogs of both instances are kept,
+    /// the default locale is overwritten by `other`'s one.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, mut other: Self) {
+        self.default = other.default;
+        self.catalogs.append(&mut other.catalogs);
+    }
+
+    /// Normalizes given locale tag, so tags can be compared. Underscores used by
+    /// POSIX locales are replaced by hyphens and letters are lowercased, for
+    /// example `pt_BR` becomes `pt-br`.
+    pub fn normalize_tag(tag: &str) -> String {
+        tag.trim().replace('_', "-").to_ascii_lowercase()
+    }
+
+    /// Returns whether given string is a valid locale tag, that is whether it
+    /// consists of ASCII letters and digits separated by hyphens or underscores.
+    pub fn is_valid_tag(tag: &str) -> bool {
+        !tag.is_empty() && tag.split(['-', '_'])
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric()))
+    }
+
+    /// Returns the locale used for clients which ask for a locale without
+    /// a catalog, and for notices.
+    pub fn get_default(&self) -> &String {
+        &self.default
+    }
+
+    /// Sets the locale used for clients which ask for a locale without a catalog,
+    /// and for notices. The tag is normalized, see [`LocaleConfig::normalize_tag`].
+    pub fn set_default(&mut self, default: &str) {
+        self.default = Self::normalize_tag(default);
+    }
+
+    /// Returns an immutable borrow to the inner [`BTreeMap`] mapping normalized
+    /// locale tags to paths to their catalog files.
+    pub fn get_catalogs(&self) -> &BTreeMap<String, PathBuf> {
+        &self.catalogs
+    }
+
+    /// Adds a catalog file of given locale. The tag is normalized, see
+    /// [`LocaleConfig::normalize_tag`].
+    pub fn add_catalog(&mut self, tag: &str, path: PathBuf) {
+        self.catalogs.insert(Self::normalize_tag(tag), path);
+    }
+
+    /// Returns warnings about options which have no effect, see
+    /// [`Config::lint`](crate::Config::lint).
+    pub fn lint(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.default != BUILTIN_LOCALE && !self.catalogs.contains_key(&self.default) {
+            warnings.push(format!("`locale default {}` has no effect, since there is no `locale catalog {}`",
+                self.default, self.default));
+        }
+        if self.catalogs.contains_key(BUILTIN_LOCALE) {
+            warnings.push(format!("`locale catalog {}` overrides messages built into the server", BUILTIN_LOCALE));
+        }
+        warnings
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split command and argument:
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
+        // Parse based on the command
+        match command {
+            "catalog" => self.__process_catalog(arg),
+            "default" => self.__process_default(arg),
+            other => Result::Err(format!("`locale {}`: unknown sub-command", other))
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_catalog(&mut self, arg: Option<&str>) -> Result<(), String> {
+        let arg = arg.ok_or("A locale and a path were expected after `locale catalog`".to_string())?;
+        let (tag, path) = split_command(arg)?;
+        let path = path.ok_or(format!("A path was expected after `locale catalog {}`", tag))?;
+        if !Self::is_valid_tag(tag) {
+            return Result::Err(format!("`{}` is not a valid locale", tag));
+        }
+        self.add_catalog(tag, PathBuf::from(path.as_ref()));
+        Result::Ok(())
+    }
+
+    #[doc(hidden)]
+    fn __process_default(&mut self, arg: Option<&str>) -> Result<(), String> {
+        match arg {
+            Option::None => Result::Err("A locale was expected after `locale default`".to_string()),
+            Option::Some(tag) if !Self::is_valid_tag(tag) => Result::Err(format!("`{}` is not a valid locale", tag)),
+            Option::Some(tag) => {
+                self.set_default(tag);
+                Result::Ok(())
+            },
+        }
+    }
+}
@@ -0,0 +1,98 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::split_command;
+use crate::parse_duration;
+
+use std::time::Duration;
+
+/// Represents configurability of the emergency lockdown. During the lockdown,
+/// nobody can register, new connections are rejected and users can send messages
+/// only rarely.
+pub struct LockdownConfig {
+    message_interval: Duration,
+}
+
+impl Default for LockdownConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LockdownConfig {
+    /// Creates a new [`LockdownConfig`] with default values. Using this
+    /// constructor is same as using [`Default`]'s implementation.
+    pub fn new() -> Self {
+        Self {
+            message_interval: Duration::from_secs(10),
+        }
+    }
+
+    /// Merges `self` with `other` instance. All values will be overwritten by
+    /// `other`'s values.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, other: Self) {
+        self.message_interval = other.message_interval;
+    }
+
+    /// Returns the minimum time between two messages sent by the same client
+    /// during the lockdown.
+    pub fn get_message_interval(&self) -> Duration {
+        self.message_interval
+    }
+
+    /// Sets the minimum time between two messages sent by the same client during
+    /// the lockdown.
+    pub fn set_message_interval(&mut self, message_interval: Duration) {
+        self.message_interval = message_interval;
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split command and argument:
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
+        // Parse based on the command
+        match command {
+            "message-interval" => self.__process_message_interval(arg),
+            other => Result::Err(format!("`lockdown {}`: unknown sub-command", other))
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_message_interval(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `lockdown message-interval`".to_string())
+            .and_then(parse_duration)
+            .map(|interval| self.set_message_interval(interval))
+    }
+}
@@ -0,0 +1,135 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::split_command;
+
+/// Represents configurability of memory caps of in-memory stores of the server.
+/// Sizes are approximate and given in bytes. Each store sheds data differently
+/// when its cap is reached:
+///
+///  -  history: the oldest messages are removed from the message history
+///  -  pending: new messages waiting to be broadcast are rejected
+///  -  outbound: clients which have not received previous commands yet are
+///     disconnected as too slow
+pub struct MemoryConfig {
+    history_cap: Option<usize>,
+    pending_cap: Option<usize>,
+    outbound_cap: Option<usize>,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryConfig {
+    /// Creates a new [`MemoryConfig`] with default values, that is without any
+    /// caps. Using this constructor is same as using [`Default`]'s
+    /// implementation.
+    pub fn new() -> Self {
+        Self {
+            history_cap: Option::None,
+            pending_cap: Option::None,
+            outbound_cap: Option::None,
+        }
+    }
+
+    /// Merges `self` with `other` instance. All values will be overwritten by
+    /// `other`'s values.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, other: Self) {
+        self.history_cap = other.history_cap;
+        self.pending_cap = other.pending_cap;
+        self.outbound_cap = other.outbound_cap;
+    }
+
+    /// Returns the cap of the message history, or [`Option::None`] if the
+    /// message history is not capped.
+    pub fn get_history_cap(&self) -> Option<usize> {
+        self.history_cap
+    }
+
+    /// Sets the cap of the message history. [`Option::None`] removes the cap.
+    pub fn set_history_cap(&mut self, history_cap: Option<usize>) {
+        self.history_cap = history_cap;
+    }
+
+    /// Returns the cap of messages waiting to be broadcast, or [`Option::None`]
+    /// if they are not capped.
+    pub fn get_pending_cap(&self) -> Option<usize> {
+        self.pending_cap
+    }
+
+    /// Sets the cap of messages waiting to be broadcast. [`Option::None`] removes
+    /// the cap.
+    pub fn set_pending_cap(&mut self, pending_cap: Option<usize>) {
+        self.pending_cap = pending_cap;
+    }
+
+    /// Returns the cap of commands waiting to be written to all connections, or
+    /// [`Option::None`] if they are not capped.
+    pub fn get_outbound_cap(&self) -> Option<usize> {
+        self.outbound_cap
+    }
+
+    /// Sets the cap of commands waiting to be written to all connections.
+    /// [`Option::None`] removes the cap.
+    pub fn set_outbound_cap(&mut self, outbound_cap: Option<usize>) {
+        self.outbound_cap = outbound_cap;
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split command and argument:
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
+        // Parse based on the command
+        match command {
+            "history-cap" => Self::__parse_cap("history-cap", arg).map(|cap| self.set_history_cap(cap)),
+            "pending-cap" => Self::__parse_cap("pending-cap", arg).map(|cap| self.set_pending_cap(cap)),
+            "outbound-cap" => Self::__parse_cap("outbound-cap", arg).map(|cap| self.set_outbound_cap(cap)),
+            other => Result::Err(format!("`memory {}`: unknown sub-command", other))
+        }
+    }
+
+    #[doc(hidden)]
+    fn __parse_cap(command: &str, arg: Option<&str>) -> Result<Option<usize>, String> {
+        match arg {
+            Option::None => Result::Err(format!("An argument was expected after `memory {}`", command)),
+            Option::Some("off") => Result::Ok(Option::None),
+            Option::Some(arg) => arg.parse()
+                .map(Option::Some)
+                .map_err(|err| format!("A number of bytes was expected after `memory {}`: {}", command, err)),
+        }
+    }
+}
@@ -17,18 +17,27 @@
 
 use crate::ConfigParseError;
 use crate::ConfigParseResult;
-use crate::REGEX_WHITESPACE;
+use crate::STRING_SOURCE;
+use crate::split_command;
+use crate::parse_duration;
+
+use mdchat_common::moderation::FilterDecision;
 
 use regex::Regex;
 
 use std::collections::HashSet;
 use std::num::NonZeroU16;
+use std::time::Duration;
 
 /// Represents configurability of banning and allowing nicknames of users.
 pub struct MessageFilteringConfig {
     min_len: NonZeroU16,
     max_len: NonZeroU16,
-    banned: Vec<Regex>
+    min_ttl: Duration,
+    max_ttl: Duration,
+    banned: Vec<Regex>,
+    trial: Vec<Regex>,
+    quarantine: Vec<Regex>,
 }
 
 impl MessageFilteringConfig {
@@ -37,15 +46,20 @@ impl MessageFilteringConfig {
         Self {
             min_len: unsafe { NonZeroU16::new_unchecked(1) },
             max_len: unsafe { NonZeroU16::new_unchecked(u16::MAX) },
+            min_ttl: Duration::from_secs(5),
+            max_ttl: Duration::from_secs(7 * 24 * 60 * 60),
             banned: Vec::new(),
+            trial: Vec::new(),
+            quarantine: Vec::new(),
         }
     }
 
     /// Merges `self` with `other` instance in this way:
     ///
-    ///  -  minimum and maximum length will be overwritten by `other`'s values
-    ///  -  registry of banned regex patterns will be merged with `other`'s
-    ///     values
+    ///  -  minimum and maximum length and time to live will be overwritten by
+    ///     `other`'s values
+    ///  -  registries of banned, trial and quarantine regex patterns will be
+    ///     merged with `other`'s values
     ///
     /// # Parameters
     ///
@@ -53,7 +67,11 @@ impl MessageFilteringConfig {
     pub fn append(&mut self, mut other: Self) {
         self.min_len = other.min_len;
         self.max_len = other.max_len;
-        self.banned.append(&mut other.banned)
+        self.min_ttl = other.min_ttl;
+        self.max_ttl = other.max_ttl;
+        self.banned.append(&mut other.banned);
+        self.trial.append(&mut other.trial);
+        self.quarantine.append(&mut other.quarantine);
     }
 
     /// Returns minimum message length required by the [`MessageFilteringConfig`]
@@ -108,6 +126,62 @@ impl MessageFilteringConfig {
         }
     }
 
+    /// Returns minimum time to live of an expiring message.
+    pub fn get_min_ttl(&self) -> Duration {
+        self.min_ttl
+    }
+
+    /// Sets minimum time to live of an expiring message.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `min_ttl`: minimum time to live to be set
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if setting the minimum time to live was successful
+    ///  -  [`Result::Err`] if the new minimum time to live was greater than
+    ///     maximum time to live
+    pub fn set_min_ttl(&mut self, min_ttl: Duration) -> Result<(), String> {
+        if min_ttl > self.max_ttl {
+            Result::Err("Tried to set minimum time to live greater than maximum time to live".to_string())
+        } else {
+            self.min_ttl = min_ttl;
+            Result::Ok(())
+        }
+    }
+
+    /// Returns maximum time to live of an expiring message.
+    pub fn get_max_ttl(&self) -> Duration {
+        self.max_ttl
+    }
+
+    /// Sets maximum time to live of an expiring message.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `max_ttl`: maximum time to live to be set
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if setting the maximum time to live was successful
+    ///  -  [`Result::Err`] if the new maximum time to live was lower than
+    ///     minimum time to live
+    pub fn set_max_ttl(&mut self, max_ttl: Duration) -> Result<(), String> {
+        if max_ttl < self.min_ttl {
+            Result::Err("Tried to set maximum time to live lower than minimum time to live".to_string())
+        } else {
+            self.max_ttl = max_ttl;
+            Result::Ok(())
+        }
+    }
+
+    /// Returns given time to live adjusted to fit between minimum and maximum
+    /// time to live.
+    pub fn clamp_ttl(&self, ttl: Duration) -> Duration {
+        ttl.clamp(self.min_ttl, self.max_ttl)
+    }
+
     /// Method for banning messages by a specific [`Regex`] pattern.
     ///
     /// # Parameters
@@ -129,19 +203,126 @@ impl MessageFilteringConfig {
         &mut self.banned
     }
 
+    /// Method for adding a trial pattern. Messages matching a trial pattern are
+    /// not banned, but the server should record that the pattern would have
+    /// banned them. This allows checking a new pattern before it is enforced.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `regex`: pattern of messages which would be banned
+    pub fn ban_trial(&mut self, regex: Regex) {
+        self.trial.push(regex);
+    }
+
+    /// Returns an immutable borrow to the inner [`Vec`] of trial [`Regex`]
+    /// patterns. See [`MessageFilteringConfig::ban_trial`] for more information.
+    pub fn get_trial_patterns(&self) -> &Vec<Regex> {
+        &self.trial
+    }
+
+    /// Method for adding a quarantine pattern. Messages matching a quarantine
+    /// pattern are not delivered until a moderator approves them.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `regex`: pattern of messages which should be reviewed
+    pub fn quarantine(&mut self, regex: Regex) {
+        self.quarantine.push(regex);
+    }
+
+    /// Returns an immutable borrow to the inner [`Vec`] of quarantine [`Regex`]
+    /// patterns. See [`MessageFilteringConfig::quarantine`] for more
+    /// information.
+    pub fn get_quarantine_patterns(&self) -> &Vec<Regex> {
+        &self.quarantine
+    }
+
+    /// Returns the first quarantine pattern matching given message text, if any.
+    pub fn find_quarantine_pattern(&self, text: &str) -> Option<&Regex> {
+        self.quarantine.iter().find(|pattern| pattern.is_match(text))
+    }
+
+    /// Returns the first banned pattern matching given message text, if any.
+    pub fn find_banned_pattern(&self, text: &str) -> Option<&Regex> {
+        self.banned.iter().find(|pattern| pattern.is_match(text))
+    }
+
+    /// Returns all trial patterns matching given message text.
+    pub fn find_trial_patterns<'a>(&'a self, text: &'a str) -> impl Iterator<Item = &'a Regex> {
+        self.trial.iter().filter(move |pattern| pattern.is_match(text))
+    }
+
+    /// Returns warnings about rules contradicting each other, see
+    /// [`Config::lint`](crate::Config::lint).
+    pub fn lint(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.min_len > self.max_len {
+            warnings.push(format!("`message min-length {}` is greater than `message max-length {}`, so no message can be sent",
+                self.min_len, self.max_len));
+        }
+        if self.min_ttl > self.max_ttl {
+            warnings.push(format!("`message min-ttl` is greater than `message max-ttl`, so expiring messages always live for {} seconds",
+                self.max_ttl.as_secs()));
+        }
+        let is_banned = |pattern: &Regex| self.banned.iter().any(|banned| banned.as_str() == pattern.as_str());
+        for pattern in self.trial.iter().filter(|pattern| is_banned(pattern)) {
+            warnings.push(format!("Trial pattern `{}` is already banned by `message ban`", pattern));
+        }
+        for pattern in self.quarantine.iter().filter(|pattern| is_banned(pattern)) {
+            warnings.push(format!("Quarantine pattern `{}` is shadowed by the same pattern of `message ban`", pattern));
+        }
+        warnings
+    }
+
+    /// Returns whether given message text is allowed and which rule decided it.
+    /// For an allowed text, the decision also mentions a quarantine pattern
+    /// holding the message for review and trial patterns which would reject it.
+    pub fn explain(&self, text: &str) -> FilterDecision {
+        // Check for length:
+        if text.len() < self.min_len.get() as usize {
+            return FilterDecision::rejected(format!("`message min-length {}`", self.min_len))
+        }
+        if text.len() > self.max_len.get() as usize {
+            return FilterDecision::rejected(format!("`message max-length {}`", self.max_len))
+        }
+        // Check for banned patterns:
+        if let Option::Some(pattern) = self.find_banned_pattern(text) {
+            return FilterDecision::rejected(format!("`message ban {}`", pattern))
+        }
+        let mut decision = match self.find_quarantine_pattern(text) {
+            Option::Some(pattern) => format!("held for review by `message quarantine {}`", pattern),
+            Option::None => "no rule matches".to_string(),
+        };
+        for pattern in self.find_trial_patterns(text) {
+            decision.push_str(&format!(", but `message ban-trial {}` would reject it", pattern));
+        }
+        FilterDecision::allowed(decision)
+    }
+
     /// Returns whether given message is allowed to be used.
     pub fn is_allowed(&self, text: &str) -> bool {
+        self.check(text).is_ok()
+    }
+
+    /// Checks whether given message text is allowed.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if the text is allowed
+    ///  -  [`Result::Err`] with the reason why the text is not allowed
+    pub fn check(&self, text: &str) -> Result<(), String> {
         // Check for length:
-        let min_len = self.min_len.get() as usize;
-        let max_len = self.max_len.get() as usize;
-        if !(min_len..=max_len).contains(&text.len()) {
-            return false
+        if text.len() < self.min_len.get() as usize {
+            return Result::Err(format!("shorter than {} bytes", self.min_len))
+        }
+        if text.len() > self.max_len.get() as usize {
+            return Result::Err(format!("longer than {} bytes", self.max_len))
         }
         // Check for banned patterns:
-        for pattern in &self.banned {
-            if pattern.is_match(text) { return false }
+        match self.find_banned_pattern(text) {
+            Option::Some(pattern) => Result::Err(format!("matches banned pattern `{}`", pattern)),
+            Option::None => Result::Ok(()),
         }
-        return true
     }
 
     /// Processes given string as a part of a configuration file.
@@ -158,10 +339,9 @@ impl MessageFilteringConfig {
             return Result::Ok(())
         }
         // Process each line:
-        let line_num = 1u32;
-        for line in string.lines() {
+        for (line_num, line) in (1u32..).zip(string.lines()) {
             self.process_line(line)
-                .map_err(|err| ConfigParseError::syntax_error(String::new(), line_num, err))?;
+                .map_err(|err| ConfigParseError::syntax_error(STRING_SOURCE.to_string(), line_num, line, err))?;
         }
         Result::Ok(())
     }
@@ -184,14 +364,17 @@ impl MessageFilteringConfig {
         // Do not process empty lines any further:
         if line.is_empty() { return Result::Ok(()) }
         // Split command and argument:
-        let split: Vec<&str> = REGEX_WHITESPACE.splitn(line, 2).collect();
-        let command = split[0];
-        let arg = split.get(1).map(|x| x.to_owned());
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
         // Parse based on the command
         match command {
             "ban" => self.__process_ban(arg),
+            "ban-trial" => self.__process_ban_trial(arg),
             "max-length" => self.__process_max_length(arg),
+            "max-ttl" => self.__process_max_ttl(arg),
             "min-length" => self.__process_min_length(arg),
+            "min-ttl" => self.__process_min_ttl(arg),
+            "quarantine" => self.__process_quarantine(arg),
             other => Result::Err(format!("`nickname {}`: unknown sub-command", other))
         }
     }
@@ -204,6 +387,22 @@ impl MessageFilteringConfig {
             .map(|regex| self.ban(regex))
     }
 
+    #[doc(hidden)]
+    fn __process_ban_trial(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `message ban-trial`".to_string())
+            .and_then(|arg| Regex::new(arg)
+                .map_err(|err| format!("Could not parse given regex after `message ban-trial`: {}", err)))
+            .map(|regex| self.ban_trial(regex))
+    }
+
+    #[doc(hidden)]
+    fn __process_quarantine(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `message quarantine`".to_string())
+            .and_then(|arg| Regex::new(arg)
+                .map_err(|err| format!("Could not parse given regex after `message quarantine`: {}", err)))
+            .map(|regex| self.quarantine(regex))
+    }
+
     #[doc(hidden)]
     fn __process_min_length(&mut self, arg: Option<&str>) -> Result<(), String> {
         arg.ok_or("An argument was expected after `message min-length`".to_string())
@@ -219,4 +418,18 @@ impl MessageFilteringConfig {
                 .map_err(|err| format!("A number between 1 and 65535 was expected after `nickname max-length`: {}", err)))
             .and_then(|arg| self.set_max_len(arg))
     }
+
+    #[doc(hidden)]
+    fn __process_min_ttl(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `message min-ttl`".to_string())
+            .and_then(parse_duration)
+            .and_then(|min_ttl| self.set_min_ttl(min_ttl))
+    }
+
+    #[doc(hidden)]
+    fn __process_max_ttl(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `message max-ttl`".to_string())
+            .and_then(parse_duration)
+            .and_then(|max_ttl| self.set_max_ttl(max_ttl))
+    }
 }
\ No newline at end of file
@@ -0,0 +1,193 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::parse_duration;
+use crate::split_command;
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// Represents configurability of pushing metrics to a collector. Metrics can be
+/// pushed using StatsD protocol, OpenTelemetry protocol (OTLP) over HTTP, or both.
+pub struct MetricsConfig {
+    statsd: Option<SocketAddr>,
+    otlp: Option<String>,
+    interval: Duration,
+    prefix: String,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MetricsConfig {
+    /// Creates a new [`MetricsConfig`] with default values, that is with pushing
+    /// disabled, an interval of 10 seconds and `mdchat` prefix. Using this
+    /// constructor is same as using [`Default`]'s implementation.
+    pub fn new() -> Self {
+        Self {
+            statsd: Option::None,
+            otlp: Option::None,
+            interval: Duration::from_secs(10),
+            prefix: "mdchat".to_string(),
+        }
+    }
+
+    /// Merges `self` with `other` instance. All values will be overwritten by
+    /// `other`'s values.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, other: Self) {
+        self.statsd = other.statsd;
+        self.otlp = other.otlp;
+        self.interval = other.interval;
+        self.prefix = other.prefix;
+    }
+
+    /// Returns the socket address of the StatsD collector, or [`Option::None`] if
+    /// pushing using StatsD is disabled.
+    pub fn get_statsd(&self) -> Option<SocketAddr> {
+        self.statsd
+    }
+
+    /// Sets the socket address of the StatsD collector. [`Option::None`] disables
+    /// pushing using StatsD.
+    pub fn set_statsd(&mut self, statsd: Option<SocketAddr>) {
+        self.statsd = statsd;
+    }
+
+    /// Returns the URL metrics are pushed to using OTLP over HTTP, or
+    /// [`Option::None`] if pushing using OTLP is disabled.
+    pub fn get_otlp(&self) -> Option<&str> {
+        self.otlp.as_deref()
+    }
+
+    /// Sets the URL metrics are pushed to using OTLP over HTTP. [`Option::None`]
+    /// disables pushing using OTLP.
+    pub fn set_otlp(&mut self, otlp: Option<String>) {
+        self.otlp = otlp;
+    }
+
+    /// Returns how often metrics are pushed.
+    pub fn get_interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Sets how often metrics are pushed.
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Returns the prefix of names of the metrics.
+    pub fn get_prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Sets the prefix of names of the metrics.
+    pub fn set_prefix(&mut self, prefix: String) {
+        self.prefix = prefix;
+    }
+
+    /// Returns whether pushing metrics is enabled at all.
+    pub fn is_enabled(&self) -> bool {
+        self.statsd.is_some() || self.otlp.is_some()
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split command and argument:
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
+        // Parse based on the command
+        match command {
+            "statsd" => self.__process_statsd(arg),
+            "otlp" => self.__process_otlp(arg),
+            "interval" => self.__process_interval(arg),
+            "prefix" => self.__process_prefix(arg),
+            other => Result::Err(format!("`metrics {}`: unknown sub-command", other))
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_statsd(&mut self, arg: Option<&str>) -> Result<(), String> {
+        match arg {
+            Option::None => Result::Err("An argument was expected after `metrics statsd`".to_string()),
+            Option::Some("off") => {
+                self.set_statsd(Option::None);
+                Result::Ok(())
+            },
+            Option::Some(arg) => arg.parse()
+                .map_err(|err| format!("A socket address was expected after `metrics statsd`: {}", err))
+                .map(|statsd| self.set_statsd(Option::Some(statsd))),
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_otlp(&mut self, arg: Option<&str>) -> Result<(), String> {
+        match arg {
+            Option::None => Result::Err("An argument was expected after `metrics otlp`".to_string()),
+            Option::Some("off") => {
+                self.set_otlp(Option::None);
+                Result::Ok(())
+            },
+            Option::Some(arg) if arg.starts_with("http://") || arg.starts_with("https://") => {
+                self.set_otlp(Option::Some(arg.to_string()));
+                Result::Ok(())
+            },
+            Option::Some(_) => Result::Err("An HTTP or HTTPS URL was expected after `metrics otlp`".to_string()),
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_interval(&mut self, arg: Option<&str>) -> Result<(), String> {
+        match arg.ok_or("An argument was expected after `metrics interval`".to_string()).and_then(parse_duration)? {
+            interval if interval.is_zero() => Result::Err("`metrics interval` must be at least 1 second".to_string()),
+            interval => {
+                self.set_interval(interval);
+                Result::Ok(())
+            },
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_prefix(&mut self, arg: Option<&str>) -> Result<(), String> {
+        match arg {
+            Option::None => Result::Err("An argument was expected after `metrics prefix`".to_string()),
+            Option::Some(arg) if arg.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.') => {
+                self.set_prefix(arg.to_string());
+                Result::Ok(())
+            },
+            Option::Some(_) => Result::Err("`metrics prefix` may contain only letters, digits, `_` and `.`".to_string()),
+        }
+    }
+}
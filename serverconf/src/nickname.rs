@@ -17,7 +17,13 @@
 
 use crate::ConfigParseError;
 use crate::ConfigParseResult;
-use crate::REGEX_WHITESPACE;
+use crate::STRING_SOURCE;
+use crate::split_command;
+
+use mdchat_common::message::SERVER_PREFIX;
+use mdchat_common::message::is_reserved_name;
+use mdchat_common::moderation::FilterDecision;
+use mdchat_common::sanitize::is_clean_name;
 
 use regex::Regex;
 
@@ -153,21 +159,61 @@ impl NicknameFilteringConfig {
         &mut self.banned
     }
 
+    /// Returns warnings about rules contradicting each other, see
+    /// [`Config::lint`](crate::Config::lint).
+    pub fn lint(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.min_len > self.max_len {
+            warnings.push(format!("`nickname min-length {}` is greater than `nickname max-length {}`, so no nickname can be used",
+                self.min_len, self.max_len));
+        }
+        let mut allowed: Vec<&String> = self.allowed.iter().collect();
+        allowed.sort();
+        for nickname in allowed.iter().filter(|nickname| is_reserved_name(nickname)) {
+            warnings.push(format!("Nickname `{}` is allowed by `nickname allow`, but nicknames starting with `{}` are reserved",
+                nickname, SERVER_PREFIX));
+        }
+        for nickname in allowed {
+            for pattern in self.banned.iter().filter(|pattern| pattern.is_match(nickname)) {
+                warnings.push(format!("Nickname `{}` is allowed by `nickname allow`, but it also matches banned pattern `{}`",
+                    nickname, pattern));
+            }
+        }
+        warnings
+    }
+
     /// Returns whether given nickname is allowed to be used.
     pub fn is_allowed(&self, nick: &str) -> bool {
+        self.explain(nick).is_allowed()
+    }
+
+    /// Returns whether given nickname is allowed to be used and which rule
+    /// decided it. Nicknames reserved for the server are never allowed, see
+    /// [`is_reserved_name`].
+    pub fn explain(&self, nick: &str) -> FilterDecision {
+        // Nobody can impersonate the server:
+        if is_reserved_name(nick) {
+            return FilterDecision::rejected(format!("nicknames starting with `{}` are reserved", SERVER_PREFIX))
+        }
+        if !is_clean_name(nick) {
+            return FilterDecision::rejected("nicknames cannot contain control, zero-width or bidirectional characters".to_string())
+        }
         // Check for exceptions:
-        if self.allowed.contains(nick) { return true }
-        let min_len = self.min_len.get() as usize;
-        let max_len = self.max_len.get() as usize;
+        if self.allowed.contains(nick) {
+            return FilterDecision::allowed(format!("`nickname allow {}`", nick))
+        }
         // Check for length:
-        if !(min_len..=max_len).contains(&nick.len()) {
-            return false
+        if nick.len() < self.min_len.get() as usize {
+            return FilterDecision::rejected(format!("`nickname min-length {}`", self.min_len))
+        }
+        if nick.len() > self.max_len.get() as usize {
+            return FilterDecision::rejected(format!("`nickname max-length {}`", self.max_len))
         }
         // Check for banned patterns:
-        for pattern in &self.banned {
-            if pattern.is_match(nick) { return false }
+        match self.banned.iter().find(|pattern| pattern.is_match(nick)) {
+            Option::Some(pattern) => FilterDecision::rejected(format!("`nickname ban {}`", pattern)),
+            Option::None => FilterDecision::allowed("no rule matches".to_string()),
         }
-        return true
     }
 
     /// Processes given string as a part of a configuration file.
@@ -184,10 +230,9 @@ impl NicknameFilteringConfig {
             return Result::Ok(())
         }
         // Process each line:
-        let line_num = 1u32;
-        for line in string.lines() {
+        for (line_num, line) in (1u32..).zip(string.lines()) {
             self.process_line(line)
-                .map_err(|err| ConfigParseError::syntax_error(String::new(), line_num, err))?;
+                .map_err(|err| ConfigParseError::syntax_error(STRING_SOURCE.to_string(), line_num, line, err))?;
         }
         Result::Ok(())
     }
@@ -210,9 +255,8 @@ impl NicknameFilteringConfig {
         // Do not process empty lines any further:
         if line.is_empty() { return Result::Ok(()) }
         // Split command and argument:
-        let split: Vec<&str> = REGEX_WHITESPACE.splitn(line, 2).collect();
-        let command = split[0];
-        let arg = split.get(1).map(|x| x.to_owned());
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
         // Parse based on the command
         match command {
             "allow" => self.__process_allow(arg),
@@ -0,0 +1,178 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::split_command;
+use crate::parse_duration;
+use crate::parse_switch;
+
+use std::num::NonZeroU32;
+use std::time::Duration;
+
+/// Represents configurability of system notices broadcast when users log in or
+/// disconnect. Notices are sent into the main chat and, optionally, into channels
+/// the user is a member of. When many users join or leave at once, their notices
+/// are collapsed into a single one.
+pub struct NoticeConfig {
+    join: bool,
+    leave: bool,
+    channels: bool,
+    collapse_window: Duration,
+    collapse_threshold: NonZeroU32,
+}
+
+impl Default for NoticeConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NoticeConfig {
+    /// Creates a new [`NoticeConfig`] with default values, that is with both join
+    /// and leave notices sent also into channels and collapsed when more than
+    /// 3 users join or leave within 10 seconds. Using this constructor is same as
+    /// using [`Default`]'s implementation.
+    pub fn new() -> Self {
+        Self {
+            join: true,
+            leave: true,
+            channels: true,
+            collapse_window: Duration::from_secs(10),
+            collapse_threshold: NonZeroU32::new(3).unwrap(),
+        }
+    }
+
+    /// Merges `self` with `other` instance. All values will be overwritten by
+    /// `other`'s values.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, other: Self) {
+        self.join = other.join;
+        self.leave = other.leave;
+        self.channels = other.channels;
+        self.collapse_window = other.collapse_window;
+        self.collapse_threshold = other.collapse_threshold;
+    }
+
+    /// Returns whether a notice is broadcast when a user logs in.
+    pub fn is_join_enabled(&self) -> bool {
+        self.join
+    }
+
+    /// Sets whether a notice is broadcast when a user logs in.
+    pub fn set_join_enabled(&mut self, join: bool) {
+        self.join = join;
+    }
+
+    /// Returns whether a notice is broadcast when a user disconnects.
+    pub fn is_leave_enabled(&self) -> bool {
+        self.leave
+    }
+
+    /// Sets whether a notice is broadcast when a user disconnects.
+    pub fn set_leave_enabled(&mut self, leave: bool) {
+        self.leave = leave;
+    }
+
+    /// Returns whether notices are also sent into channels the user is a member
+    /// of, not only into the main chat.
+    pub fn is_channels_enabled(&self) -> bool {
+        self.channels
+    }
+
+    /// Sets whether notices are also sent into channels the user is a member of.
+    pub fn set_channels_enabled(&mut self, channels: bool) {
+        self.channels = channels;
+    }
+
+    /// Returns the time during which notices are collected before they are
+    /// broadcast, so they can be collapsed. Zero means notices are broadcast
+    /// immediately and never collapsed.
+    pub fn get_collapse_window(&self) -> Duration {
+        self.collapse_window
+    }
+
+    /// Sets the time during which notices are collected before they are
+    /// broadcast.
+    pub fn set_collapse_window(&mut self, collapse_window: Duration) {
+        self.collapse_window = collapse_window;
+    }
+
+    /// Returns the highest number of users joining or leaving within the
+    /// collapse window whose notices are broadcast separately. Notices of more
+    /// users are collapsed into a single one.
+    pub fn get_collapse_threshold(&self) -> NonZeroU32 {
+        self.collapse_threshold
+    }
+
+    /// Sets the highest number of users joining or leaving within the collapse
+    /// window whose notices are broadcast separately.
+    pub fn set_collapse_threshold(&mut self, collapse_threshold: NonZeroU32) {
+        self.collapse_threshold = collapse_threshold;
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split command and argument:
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
+        // Parse based on the command
+        match command {
+            "channels" => self.__process_switch(command, arg).map(|channels| self.set_channels_enabled(channels)),
+            "collapse-threshold" => self.__process_collapse_threshold(arg),
+            "collapse-window" => self.__process_collapse_window(arg),
+            "join" => self.__process_switch(command, arg).map(|join| self.set_join_enabled(join)),
+            "leave" => self.__process_switch(command, arg).map(|leave| self.set_leave_enabled(leave)),
+            other => Result::Err(format!("`notice {}`: unknown sub-command", other))
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_switch(&self, command: &str, arg: Option<&str>) -> Result<bool, String> {
+        arg.ok_or(format!("`on` or `off` was expected after `notice {}`", command))
+            .and_then(parse_switch)
+    }
+
+    #[doc(hidden)]
+    fn __process_collapse_threshold(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `notice collapse-threshold`".to_string())
+            .and_then(|arg| arg.parse()
+                .map_err(|err| format!("A positive number was expected after `notice collapse-threshold`: {}", err)))
+            .map(|threshold| self.set_collapse_threshold(threshold))
+    }
+
+    #[doc(hidden)]
+    fn __process_collapse_window(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `notice collapse-window`".to_string())
+            .and_then(parse_duration)
+            .map(|window| self.set_collapse_window(window))
+    }
+}
@@ -0,0 +1,305 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::split_command;
+use crate::Role;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Represents an action which only users with a sufficient role are allowed to
+/// do. See [`PermissionConfig`] for assigning the lowest role required to each
+/// capability.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// Sending messages.
+    Send,
+    /// Sending messages more often than allowed during the emergency lockdown or
+    /// a raid.
+    BypassMessageInterval,
+    /// Sending messages into moderated channels without being their member.
+    SendModerated,
+    /// Creating new channels.
+    CreateChannel,
+    /// Deleting channels and appointing their moderators without being their
+    /// owner.
+    ManageChannels,
+    /// Inviting users into any channel, even without being its member.
+    Invite,
+    /// Disconnecting other users.
+    Kick,
+    /// Banning other users.
+    Ban,
+    /// Sending announcements to all users.
+    Announce,
+    /// Searching the message history.
+    SearchHistory,
+    /// Exporting the message history.
+    ExportHistory,
+    /// Changing topics of channels.
+    SetTopic,
+    /// Approving and rejecting messages held for review.
+    ReviewMessages,
+    /// Approving users registered during a raid.
+    ApproveUsers,
+    /// Receiving events relevant for moderation.
+    ModEvents,
+    /// Reading the server log.
+    ReadLog,
+    /// Reading statistics of the message filter.
+    FilterStats,
+    /// Turning the emergency lockdown on and off.
+    Lockdown,
+    /// Listing connections to the server.
+    ListConnections,
+    /// Exporting and importing user accounts.
+    ManageUsers,
+}
+
+impl Capability {
+    /// All capabilities, in the order they are documented.
+    pub const ALL: [Capability; 20] = [
+        Self::Send, Self::BypassMessageInterval, Self::SendModerated, Self::CreateChannel,
+        Self::ManageChannels, Self::Invite, Self::Kick, Self::Ban,
+        Self::Announce, Self::SearchHistory, Self::ExportHistory, Self::SetTopic,
+        Self::ReviewMessages, Self::ApproveUsers, Self::ModEvents, Self::ReadLog,
+        Self::FilterStats, Self::Lockdown, Self::ListConnections, Self::ManageUsers,
+    ];
+
+    /// Returns the name of the capability used in configuration files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Send => "send",
+            Self::BypassMessageInterval => "bypass-message-interval",
+            Self::SendModerated => "send-moderated",
+            Self::CreateChannel => "create-channel",
+            Self::ManageChannels => "manage-channels",
+            Self::Invite => "invite",
+            Self::Kick => "kick",
+            Self::Ban => "ban",
+            Self::Announce => "announce",
+            Self::SearchHistory => "search-history",
+            Self::ExportHistory => "export-history",
+            Self::SetTopic => "set-topic",
+            Self::ReviewMessages => "review-messages",
+            Self::ApproveUsers => "approve-users",
+            Self::ModEvents => "mod-events",
+            Self::ReadLog => "read-log",
+            Self::FilterStats => "filter-stats",
+            Self::Lockdown => "lockdown",
+            Self::ListConnections => "list-connections",
+            Self::ManageUsers => "manage-users",
+        }
+    }
+
+    /// Returns the lowest role required for the capability if it is not
+    /// configured.
+    pub fn default_role(&self) -> Role {
+        match self {
+            Self::Send | Self::CreateChannel | Self::SearchHistory => Role::User,
+            Self::BypassMessageInterval | Self::SendModerated | Self::Invite | Self::Kick | Self::Ban
+                | Self::SetTopic | Self::ReviewMessages | Self::ApproveUsers | Self::ModEvents => Role::Moderator,
+            Self::ManageChannels | Self::Announce | Self::ExportHistory | Self::ReadLog | Self::FilterStats
+                | Self::Lockdown | Self::ListConnections | Self::ManageUsers => Role::Admin,
+        }
+    }
+}
+
+impl FromStr for Capability {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        Self::ALL.iter()
+            .find(|capability| capability.name() == string)
+            .copied()
+            .ok_or(format!("`{}` is not a valid capability", string))
+    }
+}
+
+/// Describes the action the capability allows, so it can be used in sentences
+/// like "You are not allowed to ...".
+impl fmt::Display for Capability {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        fmtr.write_str(match self {
+            Self::Send => "send messages",
+            Self::BypassMessageInterval => "send messages without limits",
+            Self::SendModerated => "send messages into moderated channels",
+            Self::CreateChannel => "create channels",
+            Self::ManageChannels => "manage channels of other users",
+            Self::Invite => "invite users into channels",
+            Self::Kick => "kick users",
+            Self::Ban => "ban users",
+            Self::Announce => "send announcements",
+            Self::SearchHistory => "search the message history",
+            Self::ExportHistory => "export the message history",
+            Self::SetTopic => "change channel topics",
+            Self::ReviewMessages => "review messages",
+            Self::ApproveUsers => "approve users",
+            Self::ModEvents => "receive moderation events",
+            Self::ReadLog => "read the server log",
+            Self::FilterStats => "read message filter statistics",
+            Self::Lockdown => "change the lockdown",
+            Self::ListConnections => "list connections",
+            Self::ManageUsers => "export and import user accounts",
+        })
+    }
+}
+
+/// Represents configurability of permissions, that is the lowest role a user must
+/// have to be allowed to use each [`Capability`].
+pub struct PermissionConfig {
+    roles: HashMap<Capability, Role>,
+}
+
+impl Default for PermissionConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PermissionConfig {
+    /// Creates a new [`PermissionConfig`] instance where each capability requires
+    /// its [default role](Capability::default_role). Using this constructor is
+    /// same as using [`Default`]'s implementation.
+    pub fn new() -> Self {
+        Self { roles: HashMap::new() }
+    }
+
+    /// Merges `self` with `other` instance. If a capability is configured in both
+    /// instances, `other`'s role is used.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, other: Self) {
+        self.roles.extend(other.roles);
+    }
+
+    /// Returns the lowest role a user must have to be allowed to use given
+    /// capability.
+    pub fn get_role(&self, capability: Capability) -> Role {
+        self.roles.get(&capability).copied().unwrap_or_else(|| capability.default_role())
+    }
+
+    /// Sets the lowest role a user must have to be allowed to use given
+    /// capability.
+    pub fn set_role(&mut self, capability: Capability, role: Role) {
+        self.roles.insert(capability, role);
+    }
+
+    /// Returns whether a user with given role is allowed to use given capability.
+    pub fn is_allowed(&self, role: Role, capability: Capability) -> bool {
+        role >= self.get_role(capability)
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split capability and role:
+        let (capability, role) = split_command(line)?;
+        let capability: Capability = capability.parse()
+            .map_err(|err| format!("`permissions`: {}", err))?;
+        role
+            .ok_or(format!("A role was expected after `permissions {}`", capability.name()))
+            .and_then(|arg| arg.parse())
+            .map(|role| self.set_role(capability, role))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Capability;
+    use super::PermissionConfig;
+
+    use crate::Role;
+
+    #[test]
+    fn default_roles_are_required() {
+        let config = PermissionConfig::new();
+        assert!(config.is_allowed(Role::User, Capability::Send));
+        assert!(!config.is_allowed(Role::User, Capability::Kick));
+        assert!(config.is_allowed(Role::Moderator, Capability::Kick));
+        assert!(!config.is_allowed(Role::Moderator, Capability::Lockdown));
+        assert!(config.is_allowed(Role::Admin, Capability::Lockdown));
+    }
+
+    #[test]
+    fn higher_roles_have_all_capabilities_of_lower_roles() {
+        let mut config = PermissionConfig::new();
+        config.process_line("announce moderator").unwrap();
+        config.process_line("search-history admin").unwrap();
+        for capability in Capability::ALL {
+            if config.is_allowed(Role::User, capability) {
+                assert!(config.is_allowed(Role::Moderator, capability), "{}", capability.name());
+            }
+            if config.is_allowed(Role::Moderator, capability) {
+                assert!(config.is_allowed(Role::Admin, capability), "{}", capability.name());
+            }
+        }
+    }
+
+    #[test]
+    fn configured_roles_override_defaults() {
+        let mut config = PermissionConfig::new();
+        config.process_line("announce moderator").unwrap();
+        config.process_line("send moderator").unwrap();
+        assert!(config.is_allowed(Role::Moderator, Capability::Announce));
+        assert!(!config.is_allowed(Role::User, Capability::Announce));
+        assert!(!config.is_allowed(Role::User, Capability::Send));
+        // Other capabilities keep their defaults:
+        assert_eq!(config.get_role(Capability::Kick), Role::Moderator);
+    }
+
+    #[test]
+    fn appended_roles_win() {
+        let mut config = PermissionConfig::new();
+        config.set_role(Capability::Kick, Role::Admin);
+        let mut other = PermissionConfig::new();
+        other.set_role(Capability::Kick, Role::User);
+        config.append(other);
+        assert_eq!(config.get_role(Capability::Kick), Role::User);
+    }
+
+    #[test]
+    fn invalid_lines_are_rejected() {
+        let mut config = PermissionConfig::new();
+        assert!(config.process_line("fly admin").is_err());
+        assert!(config.process_line("kick").is_err());
+        assert!(config.process_line("kick owner").is_err());
+        assert_eq!(config.get_role(Capability::Kick), Role::Moderator);
+    }
+
+    #[test]
+    fn capabilities_are_parsed_from_their_names() {
+        for capability in Capability::ALL {
+            assert_eq!(capability.name().parse::<Capability>(), Result::Ok(capability));
+        }
+    }
+}
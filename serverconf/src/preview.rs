@@ -0,0 +1,217 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::parse_duration;
+use crate::parse_switch;
+use crate::split_command;
+
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+/// Represents configurability of link previews. URLs found in messages can be
+/// attached to broadcast messages, so clients can render them as link cards,
+/// optionally together with the title and the description of the linked page.
+/// Pages are fetched only from allowed hosts, so users cannot make the server
+/// send requests to arbitrary addresses.
+pub struct PreviewConfig {
+    detect: bool,
+    fetch: bool,
+    allowed_hosts: BTreeSet<String>,
+    timeout: Duration,
+    max_size: usize,
+}
+
+impl Default for PreviewConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PreviewConfig {
+    /// Creates a new [`PreviewConfig`] with default values, that is with link
+    /// previews disabled, no allowed hosts, a timeout of 2 seconds and pages
+    /// limited to 256 KiB. Using this constructor is same as using [`Default`]'s
+    /// implementation.
+    pub fn new() -> Self {
+        Self {
+            detect: false,
+            fetch: false,
+            allowed_hosts: BTreeSet::new(),
+            timeout: Duration::from_secs(2),
+            max_size: 256 * 1024,
+        }
+    }
+
+    /// Merges `self` with `other` instance. Allowed hosts of both instances are
+    /// kept, other values will be overwritten by `other`'s values.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, mut other: Self) {
+        self.detect = other.detect;
+        self.fetch = other.fetch;
+        self.allowed_hosts.append(&mut other.allowed_hosts);
+        self.timeout = other.timeout;
+        self.max_size = other.max_size;
+    }
+
+    /// Returns whether URLs found in messages are attached to them.
+    pub fn is_detect(&self) -> bool {
+        self.detect
+    }
+
+    /// Sets whether URLs found in messages are attached to them.
+    pub fn set_detect(&mut self, detect: bool) {
+        self.detect = detect;
+    }
+
+    /// Returns whether titles and descriptions of linked pages on allowed hosts
+    /// are fetched.
+    pub fn is_fetch(&self) -> bool {
+        self.fetch
+    }
+
+    /// Sets whether titles and descriptions of linked pages on allowed hosts are
+    /// fetched.
+    pub fn set_fetch(&mut self, fetch: bool) {
+        self.fetch = fetch;
+    }
+
+    /// Returns whether pages on given host can be fetched. Subdomains of allowed
+    /// hosts are allowed too.
+    pub fn is_allowed_host(&self, host: &str) -> bool {
+        let host = host.to_lowercase();
+        self.allowed_hosts.iter().any(|allowed| host == *allowed
+            || host.strip_suffix(allowed.as_str()).is_some_and(|subdomain| subdomain.ends_with('.')))
+    }
+
+    /// Allows fetching pages on given host and its subdomains.
+    pub fn allow_host(&mut self, host: String) {
+        self.allowed_hosts.insert(host.to_lowercase());
+    }
+
+    /// Returns an immutable borrow to the inner [`BTreeSet`] containing allowed
+    /// hosts.
+    pub fn get_allowed_hosts(&self) -> &BTreeSet<String> {
+        &self.allowed_hosts
+    }
+
+    /// Returns the maximum time a page can be fetched for.
+    pub fn get_timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Sets the maximum time a page can be fetched for.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Returns the maximum number of bytes read from a page.
+    pub fn get_max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Sets the maximum number of bytes read from a page.
+    pub fn set_max_size(&mut self, max_size: usize) {
+        self.max_size = max_size;
+    }
+
+    /// Returns warnings about options which have no effect, see
+    /// [`Config::lint`](crate::Config::lint).
+    pub fn lint(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.fetch && !self.detect {
+            warnings.push("`preview fetch on` has no effect, because `preview detect` is off".to_string());
+        }
+        if self.fetch && self.allowed_hosts.is_empty() {
+            warnings.push("`preview fetch on` has no effect, because no host is allowed by `preview allow`".to_string());
+        }
+        warnings
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split command and argument:
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
+        // Parse based on the command
+        match command {
+            "allow" => self.__process_allow(arg),
+            "detect" => self.__process_detect(arg),
+            "fetch" => self.__process_fetch(arg),
+            "max-size" => self.__process_max_size(arg),
+            "timeout" => self.__process_timeout(arg),
+            other => Result::Err(format!("`preview {}`: unknown sub-command", other))
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_allow(&mut self, arg: Option<&str>) -> Result<(), String> {
+        match arg {
+            Option::None => Result::Err("A host was expected after `preview allow`".to_string()),
+            Option::Some(host) if host.contains(|c: char| c.is_whitespace() || c == '/' || c == ':') =>
+                Result::Err(format!("`{}` is not a valid host for `preview allow`", host)),
+            Option::Some(host) => {
+                self.allow_host(host.to_string());
+                Result::Ok(())
+            },
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_detect(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `preview detect`".to_string())
+            .and_then(parse_switch)
+            .map(|detect| self.set_detect(detect))
+    }
+
+    #[doc(hidden)]
+    fn __process_fetch(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `preview fetch`".to_string())
+            .and_then(parse_switch)
+            .map(|fetch| self.set_fetch(fetch))
+    }
+
+    #[doc(hidden)]
+    fn __process_max_size(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `preview max-size`".to_string())
+            .and_then(|arg| arg.parse()
+                .map_err(|err| format!("A number was expected after `preview max-size`: {}", err)))
+            .map(|max_size| self.set_max_size(max_size))
+    }
+
+    #[doc(hidden)]
+    fn __process_timeout(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `preview timeout`".to_string())
+            .and_then(parse_duration)
+            .map(|timeout| self.set_timeout(timeout))
+    }
+}
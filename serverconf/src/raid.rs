@@ -0,0 +1,195 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::split_command;
+use crate::parse_duration;
+
+use std::num::NonZeroU32;
+use std::time::Duration;
+
+/// Represents configurability of raid detection. A raid is detected when too
+/// many clients connect or register from enough different IP addresses within
+/// a short time window. During a raid, new accounts must be approved by
+/// a moderator and messages are limited the same way as during the emergency
+/// lockdown.
+pub struct RaidConfig {
+    window: Duration,
+    max_connections: Option<NonZeroU32>,
+    max_registrations: Option<NonZeroU32>,
+    min_distinct_ips: NonZeroU32,
+    duration: Duration,
+}
+
+impl Default for RaidConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RaidConfig {
+    /// Creates a new [`RaidConfig`] with raid detection turned off. Using this
+    /// constructor is same as using [`Default`]'s implementation.
+    pub fn new() -> Self {
+        Self {
+            window: Duration::from_secs(60),
+            max_connections: Option::None,
+            max_registrations: Option::None,
+            min_distinct_ips: NonZeroU32::new(5).unwrap(),
+            duration: Duration::from_secs(15 * 60),
+        }
+    }
+
+    /// Merges `self` with `other` instance. All values will be overwritten by
+    /// `other`'s values.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, other: Self) {
+        self.window = other.window;
+        self.max_connections = other.max_connections;
+        self.max_registrations = other.max_registrations;
+        self.min_distinct_ips = other.min_distinct_ips;
+        self.duration = other.duration;
+    }
+
+    /// Returns the time window in which connections and registrations are
+    /// counted.
+    pub fn get_window(&self) -> Duration {
+        self.window
+    }
+
+    /// Sets the time window in which connections and registrations are counted.
+    pub fn set_window(&mut self, window: Duration) {
+        self.window = window;
+    }
+
+    /// Returns how many new connections within the time window are allowed
+    /// before a raid is detected. [`Option::None`] means that connections are
+    /// not watched.
+    pub fn get_max_connections(&self) -> Option<NonZeroU32> {
+        self.max_connections
+    }
+
+    /// Sets how many new connections within the time window are allowed before
+    /// a raid is detected. [`Option::None`] means that connections are not
+    /// watched.
+    pub fn set_max_connections(&mut self, max_connections: Option<NonZeroU32>) {
+        self.max_connections = max_connections;
+    }
+
+    /// Returns how many registrations within the time window are allowed before
+    /// a raid is detected. [`Option::None`] means that registrations are not
+    /// watched.
+    pub fn get_max_registrations(&self) -> Option<NonZeroU32> {
+        self.max_registrations
+    }
+
+    /// Sets how many registrations within the time window are allowed before
+    /// a raid is detected. [`Option::None`] means that registrations are not
+    /// watched.
+    pub fn set_max_registrations(&mut self, max_registrations: Option<NonZeroU32>) {
+        self.max_registrations = max_registrations;
+    }
+
+    /// Returns from how many different IP addresses the connections or
+    /// registrations within the time window must come to be considered a raid.
+    pub fn get_min_distinct_ips(&self) -> NonZeroU32 {
+        self.min_distinct_ips
+    }
+
+    /// Sets from how many different IP addresses the connections or
+    /// registrations within the time window must come to be considered a raid.
+    pub fn set_min_distinct_ips(&mut self, min_distinct_ips: NonZeroU32) {
+        self.min_distinct_ips = min_distinct_ips;
+    }
+
+    /// Returns how long the countermeasures last after the raid was last
+    /// detected.
+    pub fn get_duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Sets how long the countermeasures last after the raid was last detected.
+    pub fn set_duration(&mut self, duration: Duration) {
+        self.duration = duration;
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split command and argument:
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
+        // Parse based on the command
+        match command {
+            "duration" => self.__process_duration(arg),
+            "max-connections" => self.__process_threshold("max-connections", arg)
+                .map(|max_connections| self.set_max_connections(max_connections)),
+            "max-registrations" => self.__process_threshold("max-registrations", arg)
+                .map(|max_registrations| self.set_max_registrations(max_registrations)),
+            "min-distinct-ips" => self.__process_min_distinct_ips(arg),
+            "window" => self.__process_window(arg),
+            other => Result::Err(format!("`raid {}`: unknown sub-command", other))
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_duration(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `raid duration`".to_string())
+            .and_then(parse_duration)
+            .map(|duration| self.set_duration(duration))
+    }
+
+    #[doc(hidden)]
+    fn __process_threshold(&self, command: &str, arg: Option<&str>) -> Result<Option<NonZeroU32>, String> {
+        match arg {
+            Option::None => Result::Err(format!("An argument was expected after `raid {}`", command)),
+            Option::Some("off") => Result::Ok(Option::None),
+            Option::Some(arg) => arg.parse()
+                .map(Option::Some)
+                .map_err(|err| format!("A positive number or `off` was expected after `raid {}`: {}", command, err)),
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_min_distinct_ips(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `raid min-distinct-ips`".to_string())
+            .and_then(|arg| arg.parse()
+                .map_err(|err| format!("A positive number was expected after `raid min-distinct-ips`: {}", err)))
+            .map(|min_distinct_ips| self.set_min_distinct_ips(min_distinct_ips))
+    }
+
+    #[doc(hidden)]
+    fn __process_window(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `raid window`".to_string())
+            .and_then(parse_duration)
+            .map(|window| self.set_window(window))
+    }
+}
@@ -0,0 +1,245 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::parse_duration;
+use crate::split_command;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::num::NonZeroU32;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// An enumeration of actions whose rate can be limited. Each action has its own
+/// bucket of tokens for every client or user, see [`BucketConfig`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RateLimitBucket {
+    /// New connections from a single IP address
+    Connections,
+    /// Login attempts to a single account from a single IP address
+    Logins,
+    /// Registrations from a single IP address
+    Registrations,
+    /// Messages sent by a single user
+    Messages,
+    /// Direct messages sent by a single user
+    DirectMessages,
+    /// Searches of the message history by a single user
+    Searches,
+}
+
+impl FromStr for RateLimitBucket {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "connections" => Result::Ok(Self::Connections),
+            "logins" => Result::Ok(Self::Logins),
+            "registrations" => Result::Ok(Self::Registrations),
+            "messages" => Result::Ok(Self::Messages),
+            "dms" => Result::Ok(Self::DirectMessages),
+            "searches" => Result::Ok(Self::Searches),
+            other => Result::Err(format!("`{}` is not a valid rate limit bucket", other)),
+        }
+    }
+}
+
+impl fmt::Display for RateLimitBucket {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        fmtr.write_str(match self {
+            Self::Connections => "connections",
+            Self::Logins => "logins",
+            Self::Registrations => "registrations",
+            Self::Messages => "messages",
+            Self::DirectMessages => "dms",
+            Self::Searches => "searches",
+        })
+    }
+}
+
+/// Represents limits of a single bucket. The bucket holds at most `burst` tokens
+/// and is refilled by `count` tokens per `period` given by the rate. Each action
+/// takes a token; when there is none, the action is rejected and, if there is
+/// a penalty, all actions are rejected until the penalty passes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BucketConfig {
+    rate: Option<(NonZeroU32, Duration)>,
+    burst: Option<NonZeroU32>,
+    penalty: Duration,
+}
+
+impl BucketConfig {
+    /// Creates a new [`BucketConfig`] without any limit. Using this constructor is
+    /// same as using [`Default`]'s implementation.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns how many tokens are added to the bucket per period.
+    /// [`Option::None`] means that the action is not limited.
+    pub fn get_rate(&self) -> Option<(NonZeroU32, Duration)> {
+        self.rate
+    }
+
+    /// Sets how many tokens are added to the bucket per period. [`Option::None`]
+    /// means that the action is not limited.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if the rate was set
+    ///  -  [`Result::Err`] if the period is zero
+    pub fn set_rate(&mut self, rate: Option<(NonZeroU32, Duration)>) -> Result<(), String> {
+        if rate.is_some_and(|(_, period)| period.is_zero()) {
+            return Result::Err("Period of a rate limit must not be zero".to_string())
+        }
+        self.rate = rate;
+        Result::Ok(())
+    }
+
+    /// Returns how many tokens the bucket can hold, that is how many actions can
+    /// be done at once. Defaults to the count of the rate.
+    pub fn get_burst(&self) -> u32 {
+        self.burst
+            .or(self.rate.map(|(count, _)| count))
+            .map(NonZeroU32::get)
+            .unwrap_or(u32::MAX)
+    }
+
+    /// Sets how many tokens the bucket can hold. [`Option::None`] means that it
+    /// is the count of the rate.
+    pub fn set_burst(&mut self, burst: Option<NonZeroU32>) {
+        self.burst = burst;
+    }
+
+    /// Returns for how long all actions are rejected after the bucket runs out of
+    /// tokens.
+    pub fn get_penalty(&self) -> Duration {
+        self.penalty
+    }
+
+    /// Sets for how long all actions are rejected after the bucket runs out of
+    /// tokens.
+    pub fn set_penalty(&mut self, penalty: Duration) {
+        self.penalty = penalty;
+    }
+}
+
+/// Represents configurability of rate limits of connections, logins,
+/// registrations, messages, direct messages and searches. By default, nothing is
+/// limited.
+pub struct RateLimitConfig {
+    buckets: BTreeMap<RateLimitBucket, BucketConfig>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RateLimitConfig {
+    /// Creates a new [`RateLimitConfig`] without any limits. Using this
+    /// constructor is same as using [`Default`]'s implementation.
+    pub fn new() -> Self {
+        Self {
+            buckets: BTreeMap::new(),
+        }
+    }
+
+    /// Merges `self` with `other` instance. Buckets configured in `other` will be
+    /// overwritten by `other`'s values.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, mut other: Self) {
+        self.buckets.append(&mut other.buckets);
+    }
+
+    /// Returns limits of given bucket.
+    pub fn get_bucket(&self, bucket: RateLimitBucket) -> BucketConfig {
+        self.buckets.get(&bucket).copied().unwrap_or_default()
+    }
+
+    /// Returns a mutable borrow to limits of given bucket.
+    pub fn get_bucket_mut(&mut self, bucket: RateLimitBucket) -> &mut BucketConfig {
+        self.buckets.entry(bucket).or_default()
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split bucket and the rest of the line:
+        let (bucket, rest) = split_command(line)?;
+        let bucket: RateLimitBucket = bucket.parse()
+            .map_err(|err| format!("`ratelimit`: {}", err))?;
+        let rest = rest.ok_or(format!("Sub-command was expected after `ratelimit {}`", bucket))?;
+        let (command, arg) = split_command(&rest)?;
+        let arg = arg.as_deref();
+        // Parse based on the command
+        match command {
+            "burst" => self.__process_burst(bucket, arg),
+            "penalty" => self.__process_penalty(bucket, arg),
+            "rate" => self.__process_rate(bucket, arg),
+            other => Result::Err(format!("`ratelimit {} {}`: unknown sub-command", bucket, other))
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_burst(&mut self, bucket: RateLimitBucket, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or(format!("A positive number was expected after `ratelimit {} burst`", bucket))
+            .and_then(|arg| arg.parse()
+                .map_err(|err| format!("A positive number was expected after `ratelimit {} burst`: {}", bucket, err)))
+            .map(|burst| self.get_bucket_mut(bucket).set_burst(Option::Some(burst)))
+    }
+
+    #[doc(hidden)]
+    fn __process_penalty(&mut self, bucket: RateLimitBucket, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or(format!("A duration was expected after `ratelimit {} penalty`", bucket))
+            .and_then(parse_duration)
+            .map(|penalty| self.get_bucket_mut(bucket).set_penalty(penalty))
+    }
+
+    #[doc(hidden)]
+    fn __process_rate(&mut self, bucket: RateLimitBucket, arg: Option<&str>) -> Result<(), String> {
+        let arg = arg.ok_or(format!("A rate or `off` was expected after `ratelimit {} rate`", bucket))?;
+        if arg == "off" {
+            return self.get_bucket_mut(bucket).set_rate(Option::None)
+        }
+        let invalid = || format!("`{}` is not a valid rate, `<count>/<duration>` was expected", arg);
+        let (count, period) = arg.split_once('/').ok_or_else(invalid)?;
+        let count: NonZeroU32 = count.parse().map_err(|_| invalid())?;
+        // A unit without a number, such as `m`, stands for one unit:
+        let period = match period.starts_with(|c: char| c.is_ascii_digit()) {
+            _ if period.is_empty() => return Result::Err(invalid()),
+            true => parse_duration(period)?,
+            false => parse_duration(&format!("1{}", period))?,
+        };
+        self.get_bucket_mut(bucket).set_rate(Option::Some((count, period)))
+    }
+}
@@ -0,0 +1,134 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::split_command;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Represents a role of a user. Roles are ordered by their privileges, so a
+/// [`Role::Admin`] has all privileges a [`Role::Moderator`] has.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Role {
+    /// A regular user without any special privileges.
+    User,
+    /// A user who is allowed to moderate the chat.
+    Moderator,
+    /// A user who is allowed to administer the server.
+    Admin,
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "user" => Result::Ok(Self::User),
+            "moderator" => Result::Ok(Self::Moderator),
+            "admin" => Result::Ok(Self::Admin),
+            other => Result::Err(format!("`{}` is not a valid role", other)),
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        fmtr.write_str(match self {
+            Self::User => "user",
+            Self::Moderator => "moderator",
+            Self::Admin => "admin",
+        })
+    }
+}
+
+/// Represents configurability of roles assigned to users.
+pub struct RoleConfig {
+    roles: HashMap<String, Role>,
+}
+
+impl Default for RoleConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RoleConfig {
+    /// Creates a new [`RoleConfig`] instance with no roles assigned. Using this
+    /// constructor is same as using [`Default`]'s implementation.
+    pub fn new() -> Self {
+        Self { roles: HashMap::new() }
+    }
+
+    /// Merges `self` with `other` instance. If a user has a role assigned in both
+    /// instances, `other`'s role is used.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, other: Self) {
+        self.roles.extend(other.roles);
+    }
+
+    /// Assigns given role to a user.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `nickname`: nickname of the user
+    ///  -  `role`: role to be assigned
+    pub fn assign(&mut self, nickname: String, role: Role) {
+        self.roles.insert(nickname, role);
+    }
+
+    /// Returns the role of the user with given nickname. Users without an
+    /// explicitly assigned role have [`Role::User`] role.
+    pub fn role_of(&self, nickname: &str) -> Role {
+        self.roles.get(nickname).copied().unwrap_or(Role::User)
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split command and argument:
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
+        // Parse based on the command
+        match command {
+            "admin" => self.__process_assign(Role::Admin, "admin", arg),
+            "moderator" => self.__process_assign(Role::Moderator, "moderator", arg),
+            other => Result::Err(format!("`role {}`: unknown sub-command", other))
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_assign(&mut self, role: Role, command: &str, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or(format!("A nickname was expected after `role {}`", command))
+            .map(|nickname| self.assign(nickname.to_string(), role))
+    }
+}
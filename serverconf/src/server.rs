@@ -0,0 +1,209 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::split_command;
+
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Represents what the server does when one of its threads panics. The panic is
+/// logged in every case.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PanicStrategy {
+    /// Only the thread which has panicked ends, the rest of the server keeps
+    /// running.
+    Log,
+    /// The subsystem the thread belongs to, such as the message handler or a
+    /// listener, is started again.
+    Restart,
+    /// The whole server is aborted, so it can be restarted by a supervisor
+    /// such as systemd.
+    Abort,
+}
+
+impl FromStr for PanicStrategy {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "log" => Result::Ok(Self::Log),
+            "restart" => Result::Ok(Self::Restart),
+            "abort" => Result::Ok(Self::Abort),
+            other => Result::Err(format!("`{}` is not a valid panic strategy", other)),
+        }
+    }
+}
+
+/// Represents configurability of how the server introduces itself to clients
+/// when they connect and of how it copes with its own failures.
+pub struct ServerConfig {
+    name: Option<String>,
+    motd: Vec<String>,
+    welcome: Option<PathBuf>,
+    on_panic: Option<PanicStrategy>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ServerConfig {
+    /// Creates a new [`ServerConfig`] instance without a name, a message of
+    /// the day and a welcome sequence, which only logs panics of its threads.
+    /// Using this constructor is same as using [`Default`]'s implementation.
+    pub fn new() -> Self {
+        Self { name: Option::None, motd: Vec::new(), welcome: Option::None, on_panic: Option::None }
+    }
+
+    /// Merges `self` with `other` instance. The name, the message of the day, the
+    /// welcome sequence and the panic strategy of the `other` instance are used,
+    /// if they are set.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, other: Self) {
+        if other.name.is_some() {
+            self.name = other.name;
+        }
+        if !other.motd.is_empty() {
+            self.motd = other.motd;
+        }
+        if other.welcome.is_some() {
+            self.welcome = other.welcome;
+        }
+        if other.on_panic.is_some() {
+            self.on_panic = other.on_panic;
+        }
+    }
+
+    /// Returns the name of the server shown to clients, if any.
+    pub fn get_name(&self) -> Option<&String> {
+        self.name.as_ref()
+    }
+
+    /// Sets the name of the server shown to clients.
+    pub fn set_name(&mut self, name: Option<String>) {
+        self.name = name;
+    }
+
+    /// Returns the message of the day shown to clients when they connect, or
+    /// [`Option::None`] if there is no message. Lines are separated by newline
+    /// characters.
+    pub fn get_motd(&self) -> Option<String> {
+        match self.motd.is_empty() {
+            true => Option::None,
+            false => Option::Some(self.motd.join("\n")),
+        }
+    }
+
+    /// Returns lines of the message of the day.
+    pub fn get_motd_lines(&self) -> &Vec<String> {
+        &self.motd
+    }
+
+    /// Returns a mutable reference to lines of the message of the day.
+    pub fn get_motd_lines_mut(&mut self) -> &mut Vec<String> {
+        &mut self.motd
+    }
+
+    /// Returns the path to the template file of the welcome sequence, which is
+    /// sent to users on their first login after registration, or
+    /// [`Option::None`] if there is no welcome sequence.
+    pub fn get_welcome(&self) -> Option<&PathBuf> {
+        self.welcome.as_ref()
+    }
+
+    /// Sets the path to the template file of the welcome sequence, see
+    /// [`ServerConfig::get_welcome`].
+    pub fn set_welcome(&mut self, welcome: Option<PathBuf>) {
+        self.welcome = welcome;
+    }
+
+    /// Returns what the server does when one of its threads panics,
+    /// [`PanicStrategy::Log`] by default.
+    pub fn get_on_panic(&self) -> PanicStrategy {
+        self.on_panic.unwrap_or(PanicStrategy::Log)
+    }
+
+    /// Sets what the server does when one of its threads panics.
+    pub fn set_on_panic(&mut self, on_panic: PanicStrategy) {
+        self.on_panic = Option::Some(on_panic);
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split command and argument:
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
+        // Parse based on the command
+        match command {
+            "motd" => self.__process_motd(arg),
+            "name" => self.__process_name(arg),
+            "on-panic" => self.__process_on_panic(arg),
+            "welcome" => self.__process_welcome(arg),
+            other => Result::Err(format!("`server {}`: unknown sub-command", other))
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_motd(&mut self, arg: Option<&str>) -> Result<(), String> {
+        match arg {
+            Option::Some("off") => self.motd.clear(),
+            Option::Some(line) => self.motd.push(line.to_string()),
+            Option::None => return Result::Err("A line of the message or `off` was expected after `server motd`".to_string()),
+        }
+        Result::Ok(())
+    }
+
+    #[doc(hidden)]
+    fn __process_name(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("A name was expected after `server name`".to_string())
+            .map(|name| self.set_name(Option::Some(name.to_string())))
+    }
+
+    #[doc(hidden)]
+    fn __process_on_panic(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("`log`, `restart` or `abort` was expected after `server on-panic`".to_string())
+            .and_then(str::parse)
+            .map(|on_panic| self.set_on_panic(on_panic))
+    }
+
+    #[doc(hidden)]
+    fn __process_welcome(&mut self, arg: Option<&str>) -> Result<(), String> {
+        match arg {
+            Option::Some("off") => self.set_welcome(Option::None),
+            Option::Some(path) => self.set_welcome(Option::Some(PathBuf::from(path))),
+            Option::None => return Result::Err("A path or `off` was expected after `server welcome`".to_string()),
+        }
+        Result::Ok(())
+    }
+}
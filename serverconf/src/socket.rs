@@ -0,0 +1,224 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::parse_duration;
+use crate::parse_switch;
+use crate::split_command;
+
+use std::time::Duration;
+
+/// Represents configurability of a socket served over plain TCP, that is the
+/// socket of the WebSocket gateway, the health endpoint or the webhook endpoint.
+/// The options are set before the socket is bound, except TCP keepalive, which
+/// is enabled on each accepted connection. Sockets given by `listen` are bound by
+/// the MDSWP transport, which does not expose their options.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SocketOptions {
+    reuse_address: bool,
+    reuse_port: bool,
+    keepalive: Option<Duration>,
+    bind_device: Option<String>,
+}
+
+impl Default for SocketOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SocketOptions {
+    /// Creates a new [`SocketOptions`] instance with default values, that is
+    /// with `SO_REUSEADDR` set, but without `SO_REUSEPORT`, TCP keepalive and
+    /// binding to a device. Using this constructor is same as using
+    /// [`Default`]'s implementation.
+    pub fn new() -> Self {
+        Self {
+            reuse_address: true,
+            reuse_port: false,
+            keepalive: Option::None,
+            bind_device: Option::None,
+        }
+    }
+
+    /// Returns whether `SO_REUSEADDR` is set, so the socket can be bound while
+    /// connections of the previous run of the server are still closing. The
+    /// option is never set on Windows, where it would let other processes bind
+    /// the same address.
+    pub fn get_reuse_address(&self) -> bool {
+        self.reuse_address
+    }
+
+    /// Sets whether `SO_REUSEADDR` is set, see
+    /// [`SocketOptions::get_reuse_address`].
+    pub fn set_reuse_address(&mut self, reuse_address: bool) {
+        self.reuse_address = reuse_address;
+    }
+
+    /// Returns whether `SO_REUSEPORT` is set, so more instances of the server can
+    /// bind the same address and the system balances connections among them.
+    pub fn get_reuse_port(&self) -> bool {
+        self.reuse_port
+    }
+
+    /// Sets whether `SO_REUSEPORT` is set, see [`SocketOptions::get_reuse_port`].
+    pub fn set_reuse_port(&mut self, reuse_port: bool) {
+        self.reuse_port = reuse_port;
+    }
+
+    /// Returns the time an accepted connection may be idle before TCP keepalive
+    /// probes are sent, or [`Option::None`] if TCP keepalive is not enabled.
+    pub fn get_keepalive(&self) -> Option<Duration> {
+        self.keepalive
+    }
+
+    /// Sets the time an accepted connection may be idle before TCP keepalive
+    /// probes are sent. [`Option::None`] disables TCP keepalive.
+    pub fn set_keepalive(&mut self, keepalive: Option<Duration>) {
+        self.keepalive = keepalive;
+    }
+
+    /// Returns the name of the network interface the socket is bound to using
+    /// `SO_BINDTODEVICE`, or [`Option::None`] if it is not bound to a device.
+    pub fn get_bind_device(&self) -> Option<&String> {
+        self.bind_device.as_ref()
+    }
+
+    /// Sets the name of the network interface the socket is bound to.
+    /// [`Option::None`] means the socket is not bound to a device.
+    pub fn set_bind_device(&mut self, bind_device: Option<String>) {
+        self.bind_device = bind_device;
+    }
+
+    /// Returns whether given sub-command of a section sets a socket option, so
+    /// it should be processed by [`SocketOptions::process_line`].
+    pub fn is_sub_command(command: &str) -> bool {
+        matches!(command, "reuse-address" | "reuse-port" | "keepalive" | "bind-device")
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `line`: the line without the name of the section
+    ///  -  `section`: name of the section the socket belongs to, such as
+    ///     `gateway`, used in error messages
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str, section: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split command and argument:
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref()
+            .ok_or(format!("An argument was expected after `{} {}`", section, command))?;
+        // Parse based on the command
+        match command {
+            "reuse-address" => parse_switch(arg).map(|reuse_address| self.set_reuse_address(reuse_address)),
+            "reuse-port" => parse_switch(arg).map(|reuse_port| self.set_reuse_port(reuse_port)),
+            "keepalive" => self.__process_keepalive(arg, section),
+            "bind-device" => self.__process_bind_device(arg, section),
+            other => Result::Err(format!("`{} {}`: unknown sub-command", section, other))
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_keepalive(&mut self, arg: &str, section: &str) -> Result<(), String> {
+        match arg {
+            "off" => {
+                self.set_keepalive(Option::None);
+                Result::Ok(())
+            },
+            arg => match parse_duration(arg)? {
+                keepalive if keepalive < Duration::from_secs(1) =>
+                    Result::Err(format!("`{} keepalive` must be at least one second", section)),
+                keepalive => {
+                    self.set_keepalive(Option::Some(keepalive));
+                    Result::Ok(())
+                },
+            },
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_bind_device(&mut self, arg: &str, section: &str) -> Result<(), String> {
+        match arg {
+            "off" => self.set_bind_device(Option::None),
+            arg if arg.is_empty() || arg.contains(['/', '\0']) || arg.chars().any(char::is_whitespace) =>
+                return Result::Err(format!("`{}` is not a valid interface name for `{} bind-device`", arg, section)),
+            arg => self.set_bind_device(Option::Some(arg.to_string())),
+        }
+        Result::Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SocketOptions;
+
+    use crate::Config;
+
+    use std::time::Duration;
+
+    #[test]
+    fn options_are_parsed() {
+        let mut options = SocketOptions::new();
+        options.process_line("reuse-address off", "gateway").unwrap();
+        options.process_line("reuse-port on", "gateway").unwrap();
+        options.process_line("keepalive 30s", "gateway").unwrap();
+        options.process_line("bind-device eth1", "gateway").unwrap();
+        assert!(!options.get_reuse_address());
+        assert!(options.get_reuse_port());
+        assert_eq!(options.get_keepalive(), Option::Some(Duration::from_secs(30)));
+        assert_eq!(options.get_bind_device().map(String::as_str), Option::Some("eth1"));
+        options.process_line("keepalive off", "gateway").unwrap();
+        options.process_line("bind-device off", "gateway").unwrap();
+        assert_eq!(options.get_keepalive(), Option::None);
+        assert_eq!(options.get_bind_device(), Option::None);
+    }
+
+    #[test]
+    fn invalid_options_are_rejected() {
+        let mut options = SocketOptions::new();
+        assert!(options.process_line("reuse-port yes", "health").is_err());
+        assert!(options.process_line("keepalive 500ms", "health").is_err());
+        assert!(options.process_line("keepalive", "health").is_err());
+        assert!(options.process_line("bind-device a/b", "health").is_err());
+        assert_eq!(options, SocketOptions::new());
+    }
+
+    #[test]
+    fn options_are_set_per_section() {
+        let config = Config::new();
+        config.process_line("gateway reuse-port on").unwrap();
+        config.process_line("webhook keepalive 2m").unwrap();
+        config.process_line("health bind-device lo").unwrap();
+        assert!(config.gateway().read().unwrap().get_socket().get_reuse_port());
+        assert!(!config.webhooks().read().unwrap().get_socket().get_reuse_port());
+        assert_eq!(config.gateway().read().unwrap().get_socket().get_keepalive(), Option::Some(Duration::from_secs(60)));
+        assert_eq!(config.webhooks().read().unwrap().get_socket().get_keepalive(), Option::Some(Duration::from_secs(120)));
+        assert_eq!(config.health().read().unwrap().get_socket().get_bind_device().map(String::as_str), Option::Some("lo"));
+        assert!(config.process_line("gateway reuse-portal on").is_err());
+    }
+}
@@ -0,0 +1,116 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::split_command;
+
+use std::path::PathBuf;
+
+/// Represents configurability of the state file, into which the server saves
+/// state acquired at runtime, such as whether the emergency lockdown is active or
+/// topics of channels. The state is saved whenever it changes and loaded on
+/// startup, so it survives restarts of the server. Filter rules added at runtime
+/// are saved into a separate rules file, which is a configuration file.
+pub struct StateConfig {
+    file: PathBuf,
+    rules_file: PathBuf,
+}
+
+impl Default for StateConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StateConfig {
+    /// Creates a new [`StateConfig`] with default values. Using this constructor
+    /// is same as using [`Default`]'s implementation.
+    pub fn new() -> Self {
+        Self {
+            file: PathBuf::from("/var/lib/mdchat-server/state.json"),
+            rules_file: PathBuf::from("/var/lib/mdchat-server/rules.conf"),
+        }
+    }
+
+    /// Merges `self` with `other` instance. All values will be overwritten by
+    /// `other`'s values.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, other: Self) {
+        self.file = other.file;
+        self.rules_file = other.rules_file;
+    }
+
+    /// Returns the path to the state file.
+    pub fn get_file(&self) -> &PathBuf {
+        &self.file
+    }
+
+    /// Sets the path to the state file.
+    pub fn set_file(&mut self, file: PathBuf) {
+        self.file = file;
+    }
+
+    /// Returns the path to the rules file, into which filter rules added at
+    /// runtime are appended. It is processed after the configuration file.
+    pub fn get_rules_file(&self) -> &PathBuf {
+        &self.rules_file
+    }
+
+    /// Sets the path to the rules file.
+    pub fn set_rules_file(&mut self, rules_file: PathBuf) {
+        self.rules_file = rules_file;
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split command and argument:
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
+        // Parse based on the command
+        match command {
+            "file" => self.__process_file(arg),
+            "rules-file" => self.__process_rules_file(arg),
+            other => Result::Err(format!("`state {}`: unknown sub-command", other))
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_file(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("A path was expected after `state file`".to_string())
+            .map(|path| self.set_file(PathBuf::from(path)))
+    }
+
+    #[doc(hidden)]
+    fn __process_rules_file(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("A path was expected after `state rules-file`".to_string())
+            .map(|path| self.set_rules_file(PathBuf::from(path)))
+    }
+}
@@ -0,0 +1,311 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Splitting of configuration files into statements. Besides the flat form, where
+//! each line is an option, options can be grouped into blocks:
+//!
+//! ```text
+//! message {
+//!     max-length 500; ban "(?i)spam"
+//! }
+//! ```
+//!
+//! Each statement inside a block is prefixed by the words preceding the block, so
+//! the example above is the same as `message max-length 500` followed by
+//! `message ban "(?i)spam"`. Blocks can be nested.
+
+use crate::error::ConfigParseError;
+use crate::error::ConfigParseResult;
+
+use std::ops::Range;
+
+/// Represents a token of a line of a configuration file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[doc(hidden)]
+enum Token {
+    /// A word or a quoted argument
+    Word,
+    /// `{` opening a block
+    Open,
+    /// `}` closing a block
+    Close,
+    /// `;` ending a statement
+    End,
+}
+
+/// Represents a single option of a configuration file in the flat form.
+pub(crate) struct Statement<'a> {
+    /// Number of the line where the statement is written
+    pub line_num: u32,
+    /// Text of the line where the statement is written
+    pub line: &'a str,
+    /// The option in the flat form, that is prefixed by enclosing blocks
+    pub text: String,
+}
+
+/// Splits given line into tokens. Words are separated by whitespace; a word
+/// starting with `"` ends with the closing quote, so it can contain whitespace.
+/// `{` and `}` are tokens only if they are separate words, `;` also ends a word.
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] containing tokens and their ranges in the line
+///  -  [`Result::Err`] if a closing quote is missing
+#[doc(hidden)]
+fn tokenize(line: &str) -> Result<Vec<(Token, Range<usize>)>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.char_indices().peekable();
+    while let Option::Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue
+        }
+        let mut end = start;
+        if c == '"' {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Option::Some((_, '\\')) => { chars.next(); },
+                    Option::Some((i, '"')) => { end = i + 1; break },
+                    Option::Some(_) => {},
+                    Option::None => return Result::Err(format!("Closing quote is missing in `{}`", &line[start..])),
+                }
+            }
+            tokens.push((Token::Word, start..end));
+            continue
+        }
+        while let Option::Some(&(i, c)) = chars.peek().filter(|(_, c)| !c.is_whitespace() && *c != '"') {
+            end = i + c.len_utf8();
+            chars.next();
+            if c == ';' { break }
+        }
+        match &line[start..end] {
+            "{" => tokens.push((Token::Open, start..end)),
+            "}" => tokens.push((Token::Close, start..end)),
+            ";" => tokens.push((Token::End, start..end)),
+            word if word.ends_with(';') => {
+                tokens.push((Token::Word, start..end - 1));
+                tokens.push((Token::End, end - 1..end));
+            },
+            _ => tokens.push((Token::Word, start..end)),
+        }
+    }
+    Result::Ok(tokens)
+}
+
+/// Splits given configuration into statements in the flat form. Lines outside of
+/// blocks which do not open a block are kept as they are, so they may contain
+/// `;`, `{` and `}` without quoting. Empty lines and comments are skipped.
+///
+/// # Parameters
+///
+///  -  `source_name`: path of the file the configuration is read from, used in
+///     errors
+///  -  `source`: text of the configuration
+///
+/// # Return value
+///
+///  -  [`Result::Ok`] containing the statements in the order they are written in
+///  -  [`Result::Err`] if blocks are not properly opened or closed
+pub(crate) fn statements<'a>(source_name: &str, source: &'a str) -> ConfigParseResult<Vec<Statement<'a>>> {
+    let error = |line_num, line, description: String|
+        ConfigParseError::syntax_error(source_name.to_string(), line_num, line, description);
+    let mut statements = Vec::new();
+    // Prefixes of open blocks and lines where they were opened:
+    let mut blocks: Vec<(String, u32, &str)> = Vec::new();
+    for (line_num, line) in (1u32..).zip(source.lines()) {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue
+        }
+        let tokens = match tokenize(line) {
+            Result::Ok(tokens) => tokens,
+            Result::Err(description) if !blocks.is_empty() => return Result::Err(error(line_num, line, description)),
+            Result::Err(_) => Vec::new(),
+        };
+        // The flat form outside of blocks, a stray `}` is reported below:
+        let is_block = tokens.iter().any(|(token, _)| *token == Token::Open)
+            || tokens.first().is_some_and(|(token, _)| *token == Token::Close);
+        if blocks.is_empty() && !is_block {
+            statements.push(Statement { line_num, line, text: trimmed.to_string() });
+            continue
+        }
+        // Range of the statement being read:
+        let mut range: Option<Range<usize>> = Option::None;
+        for (token, token_range) in tokens {
+            match token {
+                Token::Word => match range.as_mut() {
+                    Option::Some(range) => range.end = token_range.end,
+                    Option::None => range = Option::Some(token_range),
+                },
+                Token::Open => {
+                    let header = range.take()
+                        .map(|range| &line[range])
+                        .ok_or_else(|| error(line_num, line, "An option was expected before `{`".to_string()))?;
+                    let prefix = match blocks.last() {
+                        Option::Some((prefix, _, _)) => format!("{} {}", prefix, header),
+                        Option::None => header.to_string(),
+                    };
+                    blocks.push((prefix, line_num, line));
+                },
+                Token::End | Token::Close => {
+                    if let Option::Some(range) = range.take() {
+                        statements.push(statement(&blocks, line_num, line, range));
+                    }
+                    if token == Token::Close && blocks.pop().is_none() {
+                        return Result::Err(error(line_num, line, "Unexpected `}` outside of a block".to_string()))
+                    }
+                },
+            }
+        }
+        if let Option::Some(range) = range {
+            statements.push(statement(&blocks, line_num, line, range));
+        }
+    }
+    match blocks.last() {
+        Option::Some(&(ref prefix, line_num, line)) =>
+            Result::Err(error(line_num, line, format!("Block `{}` is not closed", prefix))),
+        Option::None => Result::Ok(statements),
+    }
+}
+
+/// Creates a statement from given range of a line, prefixed by the innermost open
+/// block.
+#[doc(hidden)]
+fn statement<'a>(blocks: &[(String, u32, &str)], line_num: u32, line: &'a str, range: Range<usize>) -> Statement<'a> {
+    let text = match blocks.last() {
+        Option::Some((prefix, _, _)) => format!("{} {}", prefix, &line[range]),
+        Option::None => line[range].to_string(),
+    };
+    Statement { line_num, line, text }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::statements;
+    use super::tokenize;
+    use super::Token;
+
+    /// Returns tokens of given line together with their text.
+    fn tokens(line: &str) -> Vec<(Token, &str)> {
+        tokenize(line).unwrap().into_iter()
+            .map(|(token, range)| (token, &line[range]))
+            .collect()
+    }
+
+    /// Returns texts of statements of given configuration.
+    fn texts(source: &str) -> Vec<String> {
+        statements("test.conf", source).unwrap().into_iter()
+            .map(|statement| statement.text)
+            .collect()
+    }
+
+    #[test]
+    fn tokenize_splits_words_by_whitespace() {
+        assert_eq!(tokens("  message\tmax-length  500 "), vec![
+            (Token::Word, "message"),
+            (Token::Word, "max-length"),
+            (Token::Word, "500"),
+        ]);
+        assert!(tokens("").is_empty());
+    }
+
+    #[test]
+    fn tokenize_keeps_quoted_words_together() {
+        assert_eq!(tokens(r#"ban "a b" c"#), vec![
+            (Token::Word, "ban"),
+            (Token::Word, r#""a b""#),
+            (Token::Word, "c"),
+        ]);
+        assert_eq!(tokens(r#""a \" b;" {"#), vec![
+            (Token::Word, r#""a \" b;""#),
+            (Token::Open, "{"),
+        ]);
+    }
+
+    #[test]
+    fn tokenize_ends_words_by_semicolon() {
+        assert_eq!(tokens("a;b ; c;"), vec![
+            (Token::Word, "a"),
+            (Token::End, ";"),
+            (Token::Word, "b"),
+            (Token::End, ";"),
+            (Token::Word, "c"),
+            (Token::End, ";"),
+        ]);
+    }
+
+    #[test]
+    fn tokenize_recognizes_braces_only_as_separate_words() {
+        assert_eq!(tokens("a{ } {b"), vec![
+            (Token::Word, "a{"),
+            (Token::Close, "}"),
+            (Token::Word, "{b"),
+        ]);
+    }
+
+    #[test]
+    fn tokenize_handles_multibyte_characters() {
+        assert_eq!(tokens("žluť; \"kůň\""), vec![
+            (Token::Word, "žluť"),
+            (Token::End, ";"),
+            (Token::Word, "\"kůň\""),
+        ]);
+    }
+
+    #[test]
+    fn tokenize_fails_without_closing_quote() {
+        assert!(tokenize(r#"ban "abc"#).is_err());
+        assert!(tokenize(r#"ban "abc\""#).is_err());
+    }
+
+    #[test]
+    fn statements_keep_flat_lines_as_they_are() {
+        let source = "# comment\n\nmessage ban a;b}\n  listen 127.0.0.1:4000  \n";
+        assert_eq!(texts(source), vec!["message ban a;b}", "listen 127.0.0.1:4000"]);
+        let line_nums: Vec<u32> = statements("test.conf", source).unwrap().iter()
+            .map(|statement| statement.line_num)
+            .collect();
+        assert_eq!(line_nums, vec![3, 4]);
+    }
+
+    #[test]
+    fn statements_prefix_options_in_blocks() {
+        let source = "message {\n    max-length 500; ban \"(?i)spam eggs\"\n}\n";
+        assert_eq!(texts(source), vec!["message max-length 500", "message ban \"(?i)spam eggs\""]);
+        assert_eq!(texts("message { max-length 500 }"), vec!["message max-length 500"]);
+    }
+
+    #[test]
+    fn statements_prefix_options_in_nested_blocks() {
+        let source = "auth {\n    ldap {\n        url ldap://localhost\n    }\n    timeout 5s\n}\n";
+        assert_eq!(texts(source), vec!["auth ldap url ldap://localhost", "auth timeout 5s"]);
+    }
+
+    #[test]
+    fn statements_fail_on_unbalanced_blocks() {
+        assert!(statements("test.conf", "message {\nmax-length 500\n").is_err());
+        assert!(statements("test.conf", "}\n").is_err());
+        assert!(statements("test.conf", "message { max-length 500 } }\n").is_err());
+        assert!(statements("test.conf", "{\n}\n").is_err());
+    }
+
+    #[test]
+    fn statements_fail_on_missing_quote_in_block() {
+        assert!(statements("test.conf", "message {\nban \"abc\n}\n").is_err());
+    }
+}
@@ -0,0 +1,152 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::REGEX_WHITESPACE;
+use crate::split_command;
+
+use mdchat_common::log::LogLevel;
+
+use std::str::FromStr;
+
+/// Represents how the server log and spans are reported.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TracingSubscriberKind {
+    /// Human-readable lines printed to the standard output.
+    Fmt,
+    /// JSON objects printed to the standard output, one per line.
+    Json,
+    /// Human-readable lines printed to the standard output, while spans are also
+    /// exported to given URL using OpenTelemetry protocol (OTLP) over HTTP.
+    Otlp(String),
+}
+
+impl FromStr for TracingSubscriberKind {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let split: Vec<&str> = REGEX_WHITESPACE.splitn(string, 2).collect();
+        match (split[0], split.get(1)) {
+            ("fmt", Option::None) => Result::Ok(Self::Fmt),
+            ("json", Option::None) => Result::Ok(Self::Json),
+            ("otlp", Option::Some(url)) if url.starts_with("http://") || url.starts_with("https://") =>
+                Result::Ok(Self::Otlp(url.to_string())),
+            ("otlp", _) => Result::Err("An HTTP or HTTPS URL was expected after `otlp`".to_string()),
+            _ => Result::Err(format!("`{}` is not a valid tracing subscriber", string)),
+        }
+    }
+}
+
+/// Represents configurability of the server log and of tracing spans.
+pub struct TracingConfig {
+    subscriber: TracingSubscriberKind,
+    level: LogLevel,
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TracingConfig {
+    /// Creates a new [`TracingConfig`] with default values, that is printing
+    /// human-readable lines of all levels. Using this constructor is same as
+    /// using [`Default`]'s implementation.
+    pub fn new() -> Self {
+        Self {
+            subscriber: TracingSubscriberKind::Fmt,
+            level: LogLevel::Debug,
+        }
+    }
+
+    /// Merges `self` with `other` instance. All values will be overwritten by
+    /// `other`'s values.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, other: Self) {
+        self.subscriber = other.subscriber;
+        self.level = other.level;
+    }
+
+    /// Returns how the server log and spans are reported.
+    pub fn get_subscriber(&self) -> &TracingSubscriberKind {
+        &self.subscriber
+    }
+
+    /// Sets how the server log and spans are reported.
+    pub fn set_subscriber(&mut self, subscriber: TracingSubscriberKind) {
+        self.subscriber = subscriber;
+    }
+
+    /// Returns the lowest level of reported records and spans.
+    pub fn get_level(&self) -> LogLevel {
+        self.level
+    }
+
+    /// Sets the lowest level of reported records and spans.
+    pub fn set_level(&mut self, level: LogLevel) {
+        self.level = level;
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split command and argument:
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
+        // Parse based on the command
+        match command {
+            "subscriber" => self.__process_subscriber(arg),
+            "level" => self.__process_level(arg),
+            other => Result::Err(format!("`tracing {}`: unknown sub-command", other))
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_subscriber(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `tracing subscriber`".to_string())
+            .and_then(str::parse)
+            .map(|subscriber| self.set_subscriber(subscriber))
+    }
+
+    #[doc(hidden)]
+    fn __process_level(&mut self, arg: Option<&str>) -> Result<(), String> {
+        let level = match arg {
+            Option::None => return Result::Err("An argument was expected after `tracing level`".to_string()),
+            Option::Some("debug") => LogLevel::Debug,
+            Option::Some("info") => LogLevel::Info,
+            Option::Some("warning") => LogLevel::Warning,
+            Option::Some("error") => LogLevel::Error,
+            Option::Some(other) => return Result::Err(format!("`{}` is not a valid level", other)),
+        };
+        self.set_level(level);
+        Result::Ok(())
+    }
+}
@@ -0,0 +1,177 @@
+/*
+ * Copyright (c) 2022  Michal Douša.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::split_command;
+use crate::SocketOptions;
+
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+
+/// Represents configurability of inbound webhooks. Integrations, such as CI
+/// systems or monitoring, can post messages into the chat by sending an HTTP
+/// request authenticated by a token of the integration.
+pub struct WebhookConfig {
+    listen: Option<SocketAddr>,
+    tokens: BTreeMap<String, String>,
+    max_body_size: usize,
+    socket: SocketOptions,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WebhookConfig {
+    /// Creates a new [`WebhookConfig`] with default values, that is with webhooks
+    /// disabled, no integrations and requests limited to 64 KiB. Using this
+    /// constructor is same as using [`Default`]'s implementation.
+    pub fn new() -> Self {
+        Self {
+            listen: Option::None,
+            tokens: BTreeMap::new(),
+            max_body_size: 64 * 1024,
+            socket: SocketOptions::new(),
+        }
+    }
+
+    /// Merges `self` with `other` instance. Integrations of both instances are
+    /// kept, if an integration is in both instances, `other`'s token is used.
+    /// Other values will be overwritten by `other`'s values.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `other`: the instance to merge
+    pub fn append(&mut self, mut other: Self) {
+        self.listen = other.listen;
+        self.tokens.append(&mut other.tokens);
+        self.max_body_size = other.max_body_size;
+        self.socket = other.socket;
+    }
+
+    /// Returns the socket address the HTTP endpoint for webhooks listens on, or
+    /// [`Option::None`] if webhooks are disabled.
+    pub fn get_listen(&self) -> Option<SocketAddr> {
+        self.listen
+    }
+
+    /// Sets the socket address the HTTP endpoint for webhooks listens on.
+    /// [`Option::None`] disables webhooks.
+    pub fn set_listen(&mut self, listen: Option<SocketAddr>) {
+        self.listen = listen;
+    }
+
+    /// Returns options of the socket the HTTP endpoint for webhooks listens on.
+    pub fn get_socket(&self) -> &SocketOptions {
+        &self.socket
+    }
+
+    /// Sets options of the socket the HTTP endpoint for webhooks listens on.
+    pub fn set_socket(&mut self, socket: SocketOptions) {
+        self.socket = socket;
+    }
+
+    /// Adds an integration allowed to post messages, or changes its token.
+    ///
+    /// # Parameters
+    ///
+    ///  -  `name`: name of the integration, which is used as the sender of its
+    ///     messages
+    ///  -  `token`: secret token the integration authenticates with
+    pub fn set_token(&mut self, name: String, token: String) {
+        self.tokens.insert(name, token);
+    }
+
+    /// Returns an immutable borrow to the inner [`BTreeMap`] containing tokens of
+    /// integrations by their names.
+    pub fn get_tokens(&self) -> &BTreeMap<String, String> {
+        &self.tokens
+    }
+
+    /// Returns the maximum size of the body of a request in bytes.
+    pub fn get_max_body_size(&self) -> usize {
+        self.max_body_size
+    }
+
+    /// Sets the maximum size of the body of a request in bytes.
+    pub fn set_max_body_size(&mut self, max_body_size: usize) {
+        self.max_body_size = max_body_size;
+    }
+
+    /// Processes a single line of configuration file. If a newline character is
+    /// found after the trim of the line, method will panic.
+    ///
+    /// # Panicking
+    ///
+    /// Panics if a newline character is in the middle of given string.
+    ///
+    /// # Return value
+    ///
+    ///  -  [`Result::Ok`] if parsing succeeded
+    ///  -  [`Result::Err`] if parsing failed
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        // Trim line and ensure that there is no newline character:
+        let line = line.trim();
+        assert!(!line.contains('\n'), "Passed multi-line string to process_line");
+        // Split command and argument:
+        let (command, arg) = split_command(line)?;
+        let arg = arg.as_deref();
+        // Parse based on the command
+        match command {
+            "listen" => self.__process_listen(arg),
+            "max-body-size" => self.__process_max_body_size(arg),
+            "token" => self.__process_token(arg),
+            command if SocketOptions::is_sub_command(command) => self.socket.process_line(line, "webhook"),
+            other => Result::Err(format!("`webhook {}`: unknown sub-command", other))
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_listen(&mut self, arg: Option<&str>) -> Result<(), String> {
+        match arg {
+            Option::None => Result::Err("An argument was expected after `webhook listen`".to_string()),
+            Option::Some("off") => {
+                self.set_listen(Option::None);
+                Result::Ok(())
+            },
+            Option::Some(arg) => arg.parse()
+                .map_err(|err| format!("A socket address was expected after `webhook listen`: {}", err))
+                .map(|listen| self.set_listen(Option::Some(listen))),
+        }
+    }
+
+    #[doc(hidden)]
+    fn __process_max_body_size(&mut self, arg: Option<&str>) -> Result<(), String> {
+        arg.ok_or("An argument was expected after `webhook max-body-size`".to_string())
+            .and_then(|arg| arg.parse()
+                .map_err(|err| format!("A number was expected after `webhook max-body-size`: {}", err)))
+            .map(|max_body_size| self.set_max_body_size(max_body_size))
+    }
+
+    #[doc(hidden)]
+    fn __process_token(&mut self, arg: Option<&str>) -> Result<(), String> {
+        let arg = arg.ok_or("An integration name was expected after `webhook token`".to_string())?;
+        let (name, token) = split_command(arg)?;
+        let token = token
+            .ok_or(format!("A token was expected after `webhook token {}`", name))?;
+        if token.contains(char::is_whitespace) {
+            return Result::Err(format!("Token of `{}` integration must not contain whitespace", name))
+        }
+        self.set_token(name.to_string(), token.to_string());
+        Result::Ok(())
+    }
+}